@@ -0,0 +1,13 @@
+//! Feed arbitrary input to the board toml parser. Run with `cargo fuzz run board_toml`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use iron_coder::board::Board;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        // either outcome is fine, as long as it doesn't panic
+        let _ = Board::from_toml_str(s);
+    }
+});
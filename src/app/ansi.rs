@@ -0,0 +1,272 @@
+//! Title: Iron Coder App Module - ANSI Escapes
+//! Description: Reading the ANSI escape sequences in the output of tools and firmware, so
+//!   colored logs are shown in their colors rather than as escape bytes. Each line is
+//!   played onto a line of cells like a terminal would, so carriage returns and erasing
+//!   (i.e. progress bars) leave just what would be on the screen. Colors and text styles
+//!   carry on from line to line.
+
+use std::ops::Range;
+
+use egui::{Color32, FontId, Stroke, Visuals};
+use egui::text::{LayoutJob, TextFormat};
+
+/// A color set by an escape sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnsiColor {
+    /// One of the 256 terminal colors, the first 16 being the standard and bright colors
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// How some text is styled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<AnsiColor>,
+    pub bg: Option<AnsiColor>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+}
+
+impl Style {
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+}
+
+/// Some styled text in a line, by its byte range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub style: Style,
+}
+
+/// A line without its escape sequences, and how it's styled. Text that isn't in a span
+/// is plain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StyledLine {
+    pub text: String,
+    pub spans: Vec<Span>,
+}
+
+/// Reads the escape sequences in lines of output. The style is kept from one line to
+/// the next, since programs often color several lines at once.
+#[derive(Clone, Debug, Default)]
+pub struct AnsiParser {
+    style: Style,
+}
+
+/// The numbers of a CSI sequence, with missing ones as 0.
+fn parameters(params: &str) -> Vec<u32> {
+    params.split(';').map(|p| p.parse().unwrap_or(0)).collect()
+}
+
+/// An extended color, i.e. `5;208` or `2;255;128;0` after a 38 or 48.
+fn extended_color(params: &mut impl Iterator<Item = u32>) -> Option<AnsiColor> {
+    match params.next()? {
+        5 => Some(AnsiColor::Indexed(params.next()?.min(255) as u8)),
+        2 => {
+            let mut channel = || params.next().map(|c| c.min(255) as u8);
+            Some(AnsiColor::Rgb(channel()?, channel()?, channel()?))
+        },
+        _ => None,
+    }
+}
+
+impl AnsiParser {
+
+    /// The style the next line starts with.
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    fn select_graphic_rendition(&mut self, params: &str) {
+        let mut params = parameters(params).into_iter();
+        while let Some(param) = params.next() {
+            let style = &mut self.style;
+            match param {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.inverse = true,
+                22 => (style.bold, style.dim) = (false, false),
+                23 => style.italic = false,
+                24 => style.underline = false,
+                27 => style.inverse = false,
+                30..=37 => style.fg = Some(AnsiColor::Indexed((param - 30) as u8)),
+                38 => style.fg = extended_color(&mut params),
+                39 => style.fg = None,
+                40..=47 => style.bg = Some(AnsiColor::Indexed((param - 40) as u8)),
+                48 => style.bg = extended_color(&mut params),
+                49 => style.bg = None,
+                90..=97 => style.fg = Some(AnsiColor::Indexed((param - 90 + 8) as u8)),
+                100..=107 => style.bg = Some(AnsiColor::Indexed((param - 100 + 8) as u8)),
+                _ => (),
+            }
+        }
+    }
+
+    /// Read a line of output, without its line ending.
+    pub fn line(&mut self, raw: &str) -> StyledLine {
+        let mut cells: Vec<(char, Style)> = vec![];
+        let mut cursor = 0;
+        let mut chars = raw.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => match chars.next() {
+                    // control sequences end with a byte from @ to ~
+                    Some('[') => {
+                        let mut params = String::new();
+                        let mut command = None;
+                        for c in chars.by_ref() {
+                            if ('@'..='~').contains(&c) {
+                                command = Some(c);
+                                break;
+                            }
+                            params.push(c);
+                        }
+                        let n = || parameters(&params)[0].max(1) as usize;
+                        match command {
+                            Some('m') => self.select_graphic_rendition(&params),
+                            Some('K') => match parameters(&params)[0] {
+                                0 => cells.truncate(cursor),
+                                1 => cells.iter_mut().take(cursor + 1).for_each(|cell| *cell = (' ', Style::default())),
+                                _ => cells.clear(),
+                            },
+                            Some('C') => cursor += n(),
+                            Some('D') => cursor = cursor.saturating_sub(n()),
+                            Some('G') => cursor = n() - 1,
+                            // moving between lines and everything else can't be shown in a line
+                            _ => (),
+                        }
+                    },
+                    // operating system commands, i.e. window titles, end with BEL or ESC \
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    },
+                    // character set selection takes one more character
+                    Some('(' | ')' | '*' | '+') => {
+                        chars.next();
+                    },
+                    _ => (),
+                },
+                '\r' => cursor = 0,
+                '\x08' => cursor = cursor.saturating_sub(1),
+                c if c.is_control() && c != '\t' => (),
+                c => {
+                    if cells.len() < cursor {
+                        cells.resize(cursor, (' ', Style::default()));
+                    }
+                    match cells.get_mut(cursor) {
+                        Some(cell) => *cell = (c, self.style),
+                        None => cells.push((c, self.style)),
+                    }
+                    cursor += 1;
+                },
+            }
+        }
+        let mut line = StyledLine::default();
+        for (c, style) in cells {
+            let start = line.text.len();
+            line.text.push(c);
+            if style.is_plain() {
+                continue;
+            }
+            match line.spans.last_mut() {
+                Some(span) if span.style == style && span.range.end == start => span.range.end = line.text.len(),
+                _ => line.spans.push(Span { range: start..line.text.len(), style }),
+            }
+        }
+        line
+    }
+}
+
+/// Some output without its escape sequences, with each line as it would be shown.
+pub fn strip(text: &str) -> String {
+    let mut parser = AnsiParser::default();
+    text.split_inclusive('\n').map(|line| {
+        let ending = &line[line.trim_end_matches(['\n', '\r']).len()..];
+        parser.line(line.trim_end_matches(['\n', '\r'])).text + ending
+    }).collect()
+}
+
+/// The standard and bright colors, for dark and light backgrounds.
+const DARK_PALETTE: [(u8, u8, u8); 16] = [
+    (40, 40, 40), (225, 80, 80), (110, 200, 90), (220, 190, 80), (90, 150, 240), (200, 110, 220), (80, 200, 210), (210, 210, 210),
+    (120, 120, 120), (255, 120, 120), (150, 235, 120), (250, 225, 120), (140, 185, 255), (235, 150, 250), (130, 235, 240), (255, 255, 255),
+];
+const LIGHT_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (180, 30, 30), (30, 130, 30), (150, 110, 0), (20, 80, 190), (140, 40, 160), (0, 130, 140), (120, 120, 120),
+    (90, 90, 90), (220, 50, 50), (40, 160, 40), (180, 140, 0), (50, 110, 230), (170, 60, 190), (0, 160, 170), (60, 60, 60),
+];
+
+/// The color to show a terminal color in, readable with the visuals.
+pub fn color32(color: AnsiColor, visuals: &Visuals) -> Color32 {
+    let (r, g, b) = match color {
+        AnsiColor::Rgb(r, g, b) => (r, g, b),
+        AnsiColor::Indexed(i @ 0..=15) if visuals.dark_mode => DARK_PALETTE[i as usize],
+        AnsiColor::Indexed(i @ 0..=15) => LIGHT_PALETTE[i as usize],
+        // a 6x6x6 cube, then 24 grays
+        AnsiColor::Indexed(i @ 16..=231) => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        },
+        AnsiColor::Indexed(i) => {
+            let gray = 8 + (i - 232) * 10;
+            (gray, gray, gray)
+        },
+    };
+    Color32::from_rgb(r, g, b)
+}
+
+fn text_format(style: Style, font: &FontId, color: Color32, visuals: &Visuals) -> TextFormat {
+    // bold standard colors are shown bright, like terminals do
+    let fg = match style.fg {
+        Some(AnsiColor::Indexed(i @ 0..=7)) if style.bold => Some(AnsiColor::Indexed(i + 8)),
+        fg => fg,
+    };
+    let mut color = fg.map(|c| color32(c, visuals)).unwrap_or(color);
+    let mut background = style.bg.map(|c| color32(c, visuals)).unwrap_or(Color32::TRANSPARENT);
+    if style.inverse {
+        let under = if background == Color32::TRANSPARENT { visuals.extreme_bg_color } else { background };
+        (color, background) = (under, color);
+    }
+    if style.dim {
+        color = color.gamma_multiply(0.6);
+    }
+    TextFormat {
+        font_id: font.clone(),
+        color,
+        background,
+        italics: style.italic,
+        underline: if style.underline { Stroke::new(1.0, color) } else { Stroke::NONE },
+        ..Default::default()
+    }
+}
+
+/// Lay out part of a styled line, with `color` for text that isn't colored.
+pub fn layout_job(line: &StyledLine, range: Range<usize>, font: FontId, color: Color32, visuals: &Visuals) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let mut at = range.start;
+    let mut append = |job: &mut LayoutJob, end: usize, style: Style| {
+        if end > at {
+            job.append(&line.text[at..end], 0.0, text_format(style, &font, color, visuals));
+            at = end;
+        }
+    };
+    for span in line.spans.iter().filter(|s| s.range.end > range.start && s.range.start < range.end) {
+        append(&mut job, span.range.start.max(range.start), Style::default());
+        append(&mut job, span.range.end.min(range.end), span.style);
+    }
+    append(&mut job, range.end, Style::default());
+    job
+}
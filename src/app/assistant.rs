@@ -0,0 +1,546 @@
+//! Title: Iron Coder App Module - Assistant
+//! Description: Optional code actions answered by a language model the user sets up:
+//!   generating the driver setup for a peripheral board, explaining a build error, and
+//!   porting a function to another board. Nothing is sent until the user asks, the
+//!   prompt is built only from the project (its boards, connections, Cargo.toml and
+//!   sources), and it's shown in full before it's sent. The provider is behind a trait;
+//!   OpenAI-compatible servers, the Anthropic Messages API and Ollama are built in.
+
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+
+use crate::app::jobs::{self, JobId, JobState};
+use crate::board::Board;
+use crate::project::Project;
+use crate::project::backend::{Diagnostic, Severity};
+use crate::project::explain::KnowledgeBase;
+
+/// The most of any one file put in a prompt.
+const MAX_SECTION_BYTES: usize = 16 * 1024;
+/// The lines of source shown on each side of an error.
+const ERROR_CONTEXT_LINES: usize = 12;
+/// How long to wait for an answer. Local models can be slow.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// The wire format of a provider.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum ProviderKind {
+    /// `/chat/completions`, which OpenAI and most local servers (llama.cpp, vLLM, LM Studio) speak
+    #[default]
+    OpenAiCompatible,
+    /// `/v1/messages`
+    Anthropic,
+    /// `/api/chat`
+    Ollama,
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProviderKind::OpenAiCompatible => write!(f, "OpenAI-compatible"),
+            ProviderKind::Anthropic => write!(f, "Anthropic"),
+            ProviderKind::Ollama => write!(f, "Ollama"),
+        }
+    }
+}
+
+impl ProviderKind {
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAiCompatible => "https://api.openai.com/v1",
+            ProviderKind::Anthropic => "https://api.anthropic.com",
+            ProviderKind::Ollama => "http://localhost:11434",
+        }
+    }
+}
+
+/// Where the assistant's questions go. The key isn't saved; it's typed in each session
+/// or read from an environment variable.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct AssistantConfig {
+    pub provider: ProviderKind,
+    pub url: String,
+    pub model: String,
+    /// The environment variable to read the key from
+    pub key_variable: String,
+    pub max_tokens: u32,
+}
+
+impl Default for AssistantConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            url: ProviderKind::default().default_url().to_string(),
+            model: String::new(),
+            key_variable: "IRON_CODER_ASSISTANT_KEY".to_string(),
+            max_tokens: 2048,
+        }
+    }
+}
+
+/// A question for the model.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Prompt {
+    pub system: String,
+    /// Titled pieces of the project the question is about
+    pub context: Vec<(String, String)>,
+    pub request: String,
+}
+
+impl Prompt {
+    /// The user message: the context, then the request.
+    pub fn message(&self) -> String {
+        let mut text = String::new();
+        for (title, body) in self.context.iter() {
+            text += &format!("## {}\n```\n{}\n```\n\n", title, body.trim_end());
+        }
+        text + &self.request
+    }
+}
+
+/// Something that answers prompts.
+pub trait AssistantProvider {
+    fn complete(&self, prompt: &Prompt) -> Result<String, String>;
+}
+
+/// A provider reached over HTTP.
+pub struct HttpProvider {
+    pub config: AssistantConfig,
+    pub key: String,
+}
+
+impl HttpProvider {
+
+    /// The url, headers and body of a request.
+    pub fn request(&self, prompt: &Prompt) -> (String, Vec<(&'static str, String)>, Value) {
+        let base = self.config.url.trim().trim_end_matches('/');
+        let (model, max_tokens) = (self.config.model.trim(), self.config.max_tokens);
+        let mut headers = vec![];
+        let (url, body) = match self.config.provider {
+            ProviderKind::OpenAiCompatible => {
+                if !self.key.is_empty() {
+                    headers.push(("Authorization", format!("Bearer {}", self.key)));
+                }
+                let messages = json!([{ "role": "system", "content": prompt.system }, { "role": "user", "content": prompt.message() }]);
+                (format!("{}/chat/completions", base), json!({ "model": model, "messages": messages, "max_tokens": max_tokens }))
+            },
+            ProviderKind::Anthropic => {
+                headers.push(("x-api-key", self.key.clone()));
+                headers.push(("anthropic-version", "2023-06-01".to_string()));
+                let messages = json!([{ "role": "user", "content": prompt.message() }]);
+                (format!("{}/v1/messages", base), json!({ "model": model, "system": prompt.system, "messages": messages, "max_tokens": max_tokens }))
+            },
+            ProviderKind::Ollama => {
+                let messages = json!([{ "role": "system", "content": prompt.system }, { "role": "user", "content": prompt.message() }]);
+                (format!("{}/api/chat", base), json!({ "model": model, "messages": messages, "stream": false }))
+            },
+        };
+        (url, headers, body)
+    }
+}
+
+/// Pull the answer out of a provider's response.
+pub fn parse_response(provider: ProviderKind, response: &Value) -> Result<String, String> {
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(Value::as_str).map(str::to_string).unwrap_or_else(|| error.to_string());
+        return Err(message);
+    }
+    let answer = match provider {
+        ProviderKind::OpenAiCompatible => response["choices"][0]["message"]["content"].as_str().map(str::to_string),
+        ProviderKind::Anthropic => {
+            let text: Vec<&str> = response["content"].as_array().into_iter().flatten()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect();
+            (!text.is_empty()).then(|| text.join(""))
+        },
+        ProviderKind::Ollama => response["message"]["content"].as_str().map(str::to_string),
+    };
+    answer.ok_or_else(|| "the response had no answer in it".to_string())
+}
+
+impl AssistantProvider for HttpProvider {
+    fn complete(&self, prompt: &Prompt) -> Result<String, String> {
+        if self.config.model.trim().is_empty() {
+            return Err("pick a model".to_string());
+        }
+        let (url, headers, body) = self.request(prompt);
+        let mut request = ureq::post(&url).timeout(RESPONSE_TIMEOUT);
+        for (name, value) in headers.iter() {
+            request = request.set(name, value);
+        }
+        request = request.set("Content-Type", "application/json");
+        let text = match request.send_string(&body.to_string()) {
+            Ok(response) => response.into_string().map_err(|e| e.to_string())?,
+            // the body of an error usually says what was wrong
+            Err(ureq::Error::Status(code, response)) => {
+                let body: Value = response.into_string().ok().and_then(|t| serde_json::from_str(&t).ok()).unwrap_or(Value::Null);
+                return Err(parse_response(self.config.provider, &body).err().unwrap_or_else(|| format!("the server answered {}", code)));
+            },
+            Err(e) => return Err(e.to_string()),
+        };
+        let response: Value = serde_json::from_str(&text).map_err(|e| format!("the response isn't JSON: {}", e))?;
+        parse_response(self.config.provider, &response)
+    }
+}
+
+/// The code blocks in an answer.
+pub fn code_blocks(answer: &str) -> Vec<String> {
+    let mut blocks = vec![];
+    let mut current: Option<String> = None;
+    for line in answer.lines() {
+        if line.trim_start().starts_with("```") {
+            match current.take() {
+                Some(block) => blocks.push(block),
+                None => current = Some(String::new()),
+            }
+        } else if let Some(block) = &mut current {
+            *block += line;
+            block.push('\n');
+        }
+    }
+    blocks
+}
+
+const SYSTEM: &str = "You are helping write embedded Rust firmware in Iron Coder. Answer with \
+working code for the boards and crates described, and say when something in the context \
+isn't enough to be sure.";
+
+/// Cut a piece of context down to size, on a character boundary.
+fn limit(text: &str) -> String {
+    if text.len() <= MAX_SECTION_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_SECTION_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n... (cut to {} bytes)", &text[..end], MAX_SECTION_BYTES)
+}
+
+/// A board as it's described to Iron Coder.
+fn board_section(role: &str, board: &Board) -> (String, String) {
+    let description = toml::to_string(board).unwrap_or_else(|_| board.get_name().to_string());
+    (format!("{}: {}", role, board.get_name()), limit(&description))
+}
+
+/// The project's Cargo.toml, if it has one.
+fn manifest_section(project: &Project) -> Option<(String, String)> {
+    let manifest = fs::read_to_string(project.location()?.join("Cargo.toml")).ok()?;
+    Some(("Cargo.toml".to_string(), limit(&manifest)))
+}
+
+/// Ask for the code that sets up a peripheral board's driver.
+pub fn driver_init(project: &Project, peripheral: &Board) -> Result<Prompt, String> {
+    let main_board = project.system.main_board.as_ref().ok_or("pick the project's main board first")?;
+    let mut context = vec![board_section("main board", main_board), board_section("peripheral board", peripheral)];
+    let connections: Vec<String> = project.system.connections.iter()
+        .filter(|c| c.start_board == *peripheral || c.end_board == *peripheral)
+        .map(|c| format!("{}: {} {} <-> {} {} ({})", c.name, c.start_board.get_name(), c.start_pin, c.end_board.get_name(), c.end_pin, c.interface_mapping.interface.iface_type))
+        .collect();
+    if !connections.is_empty() {
+        context.push(("connections".to_string(), connections.join("\n")));
+    }
+    context.extend(manifest_section(project));
+    let request = format!(
+        "Write the Rust code that sets up the driver for the {} on the {}, using the connections \
+        above and the crates already in Cargo.toml where possible. Give one code block, then list \
+        any crate that needs adding.",
+        peripheral.get_name(), main_board.get_name(),
+    );
+    Ok(Prompt { system: SYSTEM.to_string(), context, request })
+}
+
+/// Ask what a build error means and how to fix it. Only source inside the project is
+/// included.
+pub fn explain_error(project: &Project, diagnostic: &Diagnostic) -> Result<Prompt, String> {
+    let mut context = vec![("error".to_string(), diagnostic.to_string())];
+    let project_dir = project.location().ok_or("save the project first")?;
+    if let (Some(file), Some(line)) = (&diagnostic.file, diagnostic.line) {
+        let path = if file.is_absolute() { file.clone() } else { project_dir.join(file) };
+        let inside = path.canonicalize().ok().zip(project_dir.canonicalize().ok()).is_some_and(|(p, d)| p.starts_with(d));
+        if let Some(source) = inside.then(|| fs::read_to_string(&path).ok()).flatten() {
+            let line = line as usize;
+            let first = line.saturating_sub(ERROR_CONTEXT_LINES).max(1);
+            let excerpt: Vec<String> = source.lines().enumerate().skip(first - 1).take(line + ERROR_CONTEXT_LINES + 1 - first)
+                .map(|(i, text)| format!("{:>5}{} {}", i + 1, if i + 1 == line { ">" } else { " " }, text))
+                .collect();
+            context.push((file.display().to_string(), excerpt.join("\n")));
+        }
+    }
+    if let Some(entry) = KnowledgeBase::builtin().explain(&diagnostic.message) {
+        context.push(("known cause".to_string(), format!("{}\n{}", entry.title, entry.explanation)));
+    }
+    if let Some(main_board) = &project.system.main_board {
+        context.push(board_section("main board", main_board));
+    }
+    context.extend(manifest_section(project));
+    let request = "Explain what causes this error in this project and how to fix it. Show the \
+        changed code, if any.".to_string();
+    Ok(Prompt { system: SYSTEM.to_string(), context, request })
+}
+
+/// The functions in some source, with their code, i.e. `main` or `Display::new`.
+pub fn functions(code: &str) -> Vec<(String, String)> {
+    let Ok(file) = syn::parse_file(code) else {
+        return vec![];
+    };
+    let unparse = |f: syn::ItemFn| prettyplease::unparse(&syn::File { shebang: None, attrs: vec![], items: vec![syn::Item::Fn(f)] });
+    let mut found = vec![];
+    for item in file.items {
+        match item {
+            syn::Item::Fn(f) => found.push((f.sig.ident.to_string(), unparse(f))),
+            syn::Item::Impl(block) => {
+                let owner = match &*block.self_ty {
+                    syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default(),
+                    _ => continue,
+                };
+                for item in block.items {
+                    if let syn::ImplItem::Fn(m) = item {
+                        let name = format!("{}::{}", owner, m.sig.ident);
+                        found.push((name, unparse(syn::ItemFn { attrs: m.attrs, vis: m.vis, sig: m.sig, block: Box::new(m.block) })));
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+    found
+}
+
+/// Ask for a function to be rewritten for another board.
+pub fn port_function(project: &Project, name: &str, code: &str, target: &Board) -> Result<Prompt, String> {
+    let main_board = project.system.main_board.as_ref().ok_or("pick the project's main board first")?;
+    let mut context = vec![(format!("fn {}", name), limit(code)), board_section("current board", main_board), board_section("new board", target)];
+    context.extend(manifest_section(project));
+    let request = format!(
+        "Port `{}` from the {} to the {}: use the new board's HAL or BSP, pins and peripherals, \
+        and keep its behavior. Give the new function in one code block, then the Cargo.toml changes.",
+        name, main_board.get_name(), target.get_name(),
+    );
+    Ok(Prompt { system: SYSTEM.to_string(), context, request })
+}
+
+/// The code actions on offer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Action {
+    #[default]
+    DriverInit,
+    ExplainError,
+    PortFunction,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::DriverInit => write!(f, "generate driver init for a peripheral"),
+            Action::ExplainError => write!(f, "explain a build error"),
+            Action::PortFunction => write!(f, "port a function to another board"),
+        }
+    }
+}
+
+/// The state of the assistant window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct AssistantPanel {
+    pub config: AssistantConfig,
+    action: Action,
+    #[serde(skip)]
+    key: String,
+    #[serde(skip)]
+    peripheral: usize,
+    #[serde(skip)]
+    diagnostic: usize,
+    #[serde(skip)]
+    function: usize,
+    #[serde(skip)]
+    target: String,
+    #[serde(skip)]
+    prompt: Option<Prompt>,
+    #[serde(skip)]
+    answer: Arc<Mutex<Option<String>>>,
+    #[serde(skip)]
+    job: Option<JobId>,
+}
+
+impl AssistantPanel {
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        let config = &mut self.config;
+        egui::Grid::new("assistant_settings").num_columns(2).show(ui, |ui| {
+            ui.label("provider:");
+            egui::ComboBox::from_id_source("assistant_provider").selected_text(config.provider.to_string()).show_ui(ui, |ui| {
+                for provider in enum_iterator::all::<ProviderKind>() {
+                    if ui.selectable_value(&mut config.provider, provider, provider.to_string()).changed() {
+                        config.url = provider.default_url().to_string();
+                    }
+                }
+            });
+            ui.end_row();
+            ui.label("url:");
+            ui.text_edit_singleline(&mut config.url);
+            ui.end_row();
+            ui.label("model:");
+            ui.text_edit_singleline(&mut config.model);
+            ui.end_row();
+            ui.label("key:");
+            ui.add(egui::TextEdit::singleline(&mut self.key).password(true).hint_text(format!("not saved; or set ${}", config.key_variable)));
+            ui.end_row();
+            ui.label("key variable:");
+            ui.text_edit_singleline(&mut config.key_variable);
+            ui.end_row();
+            ui.label("answer length:");
+            ui.add(egui::DragValue::new(&mut config.max_tokens).clamp_range(64..=32768).suffix(" tokens"));
+            ui.end_row();
+        });
+    }
+
+    /// Pick what the action is about, and build its prompt.
+    fn prepare(&mut self, ui: &mut egui::Ui, project: &Project) -> Option<Result<Prompt, String>> {
+        let mut prepare = None;
+        match self.action {
+            Action::DriverInit => {
+                let peripherals = &project.system.peripheral_boards;
+                if peripherals.is_empty() {
+                    ui.label("add a peripheral board to the project first");
+                    return None;
+                }
+                self.peripheral = self.peripheral.min(peripherals.len() - 1);
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("assistant_peripheral").selected_text(peripherals[self.peripheral].get_name()).show_ui(ui, |ui| {
+                        for (i, board) in peripherals.iter().enumerate() {
+                            ui.selectable_value(&mut self.peripheral, i, board.get_name());
+                        }
+                    });
+                    if ui.button("prepare").clicked() {
+                        prepare = Some(driver_init(project, &peripherals[self.peripheral]));
+                    }
+                });
+            },
+            Action::ExplainError => {
+                let diagnostics: Vec<Diagnostic> = project.terminal_diagnostics().into_iter().filter(|d| d.severity == Severity::Error).collect();
+                if diagnostics.is_empty() {
+                    ui.label("there are no errors in the terminal; build the project first");
+                    return None;
+                }
+                self.diagnostic = self.diagnostic.min(diagnostics.len() - 1);
+                egui::ComboBox::from_id_source("assistant_diagnostic").width(ui.available_width()).selected_text(diagnostics[self.diagnostic].to_string()).show_ui(ui, |ui| {
+                    for (i, diagnostic) in diagnostics.iter().enumerate() {
+                        ui.selectable_value(&mut self.diagnostic, i, diagnostic.to_string());
+                    }
+                });
+                if ui.button("prepare").clicked() {
+                    prepare = Some(explain_error(project, &diagnostics[self.diagnostic]));
+                }
+            },
+            Action::PortFunction => {
+                let Some((_, code)) = project.code_editor.active_code() else {
+                    ui.label("open the file with the function in the editor");
+                    return None;
+                };
+                let functions = functions(code);
+                if functions.is_empty() {
+                    ui.label("there are no functions in the open file (or it doesn't parse)");
+                    return None;
+                }
+                self.function = self.function.min(functions.len() - 1);
+                let targets: Vec<&Board> = project.known_boards.iter()
+                    .filter(|b| b.is_main_board() && project.system.main_board.as_ref() != Some(*b))
+                    .collect();
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("assistant_function").selected_text(functions[self.function].0.as_str()).show_ui(ui, |ui| {
+                        for (i, (name, _)) in functions.iter().enumerate() {
+                            ui.selectable_value(&mut self.function, i, name);
+                        }
+                    });
+                    ui.label("to");
+                    egui::ComboBox::from_id_source("assistant_target").selected_text(self.target.as_str()).show_ui(ui, |ui| {
+                        for board in targets.iter() {
+                            ui.selectable_value(&mut self.target, board.get_name().to_string(), board.get_name());
+                        }
+                    });
+                    let target = targets.iter().find(|b| b.get_name() == self.target);
+                    if ui.add_enabled(target.is_some(), egui::Button::new("prepare")).clicked() {
+                        let (name, code) = &functions[self.function];
+                        prepare = target.map(|target| port_function(project, name, code, target));
+                    }
+                });
+            },
+        }
+        prepare
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, project: &Project) {
+        let scheduler = jobs::shared(ctx);
+        let running = self.job.is_some_and(|id| scheduler.state(id) == Some(JobState::Running));
+        egui::CollapsingHeader::new("provider").default_open(self.config.model.is_empty()).show(ui, |ui| {
+            self.settings(ui);
+        });
+        ui.separator();
+        egui::ComboBox::from_id_source("assistant_action").selected_text(self.action.to_string()).show_ui(ui, |ui| {
+            for action in enum_iterator::all::<Action>() {
+                if ui.selectable_value(&mut self.action, action, action.to_string()).changed() {
+                    self.prompt = None;
+                }
+            }
+        });
+        match self.prepare(ui, project) {
+            Some(Ok(prompt)) => self.prompt = Some(prompt),
+            Some(Err(e)) => *self.answer.lock().unwrap() = Some(e),
+            None => (),
+        }
+        if let Some(prompt) = &self.prompt {
+            ui.separator();
+            ui.label("This is everything that will be sent:");
+            let mut message = prompt.message();
+            egui::ScrollArea::vertical().id_source("assistant_prompt").max_height(200.0).show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut message).code_editor().interactive(false).desired_width(f32::INFINITY));
+            });
+            ui.horizontal(|ui| {
+                if running {
+                    if ui.button("stop waiting").clicked() {
+                        if let Some(id) = self.job.take() {
+                            scheduler.cancel(id);
+                        }
+                    }
+                    ui.spinner();
+                } else if ui.button("send").clicked() {
+                    let key = match self.key.is_empty() {
+                        true => std::env::var(self.config.key_variable.trim()).unwrap_or_default(),
+                        false => self.key.clone(),
+                    };
+                    let provider = HttpProvider { config: self.config.clone(), key };
+                    let (prompt, answer) = (prompt.clone(), self.answer.clone());
+                    *answer.lock().unwrap() = None;
+                    self.job = Some(scheduler.spawn("assistant", Some(ctx), move |_job| {
+                        let result = provider.complete(&prompt);
+                        *answer.lock().unwrap() = Some(match &result {
+                            Ok(text) => text.clone(),
+                            Err(e) => format!("The assistant couldn't answer: {}", e),
+                        });
+                        result.map(|_| ())
+                    }));
+                }
+            });
+        }
+        let answer = self.answer.lock().unwrap().clone();
+        if let Some(answer) = answer {
+            ui.separator();
+            egui::ScrollArea::vertical().id_source("assistant_answer").max_height(300.0).show(ui, |ui| {
+                ui.label(&answer);
+            });
+            let blocks = code_blocks(&answer);
+            if !blocks.is_empty() && ui.button("copy code").clicked() {
+                ui.output_mut(|o| o.copied_text = blocks.join("\n"));
+            }
+        }
+    }
+}
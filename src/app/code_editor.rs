@@ -26,6 +26,8 @@ use std::io::{Read, Write, Seek};
 use std::sync::Arc;
 use crate::app::icons::IconSet;
 use crate::app::Warnings;
+use crate::app::preview::Preview;
+use crate::paths;
 // use crate::app::colorscheme::ColorScheme;
 
 /// This module contains functionality for the code editor.
@@ -45,6 +47,9 @@ struct CodeFile {
     path: Option<PathBuf>,
     file: Option<fs::File>,
     synced: bool,   // represents whether the code buffer is synced to the filesystem
+    large: Option<LargeFile>,   // set if the file is shown in large-file mode
+    preview: Option<Preview>,   // set if the file isn't text, so it's previewed instead
+    goto: Option<usize>,    // the line to put the cursor on, the next time it's shown
 }
 
 impl Default for CodeFile {
@@ -54,6 +59,9 @@ impl Default for CodeFile {
             path: None,
             file: None,
             synced: false,
+            large: None,
+            preview: None,
+            goto: None,
         }
     }
 }
@@ -64,6 +72,14 @@ impl CodeFile {
         let CodeFile { code, .. } = self;
         code.clear();
         self.path = Some(file_path.canonicalize()?);
+        // files that aren't text are previewed, and never written back
+        let mut bytes = vec![];
+        fs::File::open(file_path)?.read_to_end(&mut bytes)?;
+        if let Some(preview) = Preview::of(file_path, bytes) {
+            self.preview = Some(preview);
+            self.synced = true;
+            return Ok(());
+        }
         self.file = Some(fs::OpenOptions::new()
             .read(true)
             .write(true)
@@ -73,6 +89,7 @@ impl CodeFile {
             file.read_to_string(code)?;
             self.synced = true;
         }
+        self.large = LargeFile::detect(&self.code);
         Ok(())
     }
 
@@ -88,6 +105,46 @@ impl CodeFile {
     }
 }
 
+/// Files bigger than this, i.e. generated PAC code or logs, are opened in large-file mode.
+pub const LARGE_FILE_BYTES: usize = 1024 * 1024;
+
+/// Where each line of some code starts.
+pub fn line_starts(code: &str) -> Vec<usize> {
+    std::iter::once(0).chain(code.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// How a large file is shown. It isn't highlighted, and while it's read only, just the
+/// lines in view are laid out, so opening one doesn't hold up the UI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LargeFile {
+    line_starts: Vec<usize>,
+    pub read_only: bool,
+}
+
+impl LargeFile {
+
+    /// Large-file mode for the code, if it's large enough to need it.
+    pub fn detect(code: &str) -> Option<Self> {
+        (code.len() > LARGE_FILE_BYTES).then(|| Self { line_starts: line_starts(code), read_only: true })
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// A line of the code, without its line ending.
+    pub fn line<'a>(&self, code: &'a str, i: usize) -> &'a str {
+        let start = self.line_starts.get(i).copied().unwrap_or(code.len()).min(code.len());
+        let end = self.line_starts.get(i + 1).copied().unwrap_or(code.len()).min(code.len());
+        code.get(start..end).unwrap_or_default().trim_end_matches(['\n', '\r'])
+    }
+
+    /// Find the lines again, after the code was edited.
+    pub fn reindex(&mut self, code: &str) {
+        self.line_starts = line_starts(code);
+    }
+}
+
 pub struct CodeEditor {
     tabs: Vec<CodeFile>,
     active_tab: Option<usize>,
@@ -122,15 +179,10 @@ impl CodeEditor {
     // unless file_path already exists in one of the tabs, in which case
     // the active tab is switched to that tab
     pub fn load_from_file(&mut self, file_path: &Path) -> std::io::Result<()> {
-        let predicate = |elem: &CodeFile| {
-            // elem is a CodeFile, see it the path matches the arg to this method
-            if let Some(path) = &elem.path {
-                // use canonicalize() here to keep things consistent
-                return path.as_path() == file_path.canonicalize().unwrap();
-            } else {
-                return false;
-            }
-        };
+        // use canonicalize() here to keep things consistent
+        let canonical = file_path.canonicalize()?;
+        // elem is a CodeFile, see it the path matches the arg to this method
+        let predicate = |elem: &CodeFile| elem.path.as_deref() == Some(canonical.as_path());
         if let Some(i) = self.tabs.iter().position(predicate) {
             self.active_tab = Some(i);
         } else {
@@ -142,6 +194,44 @@ impl CodeEditor {
         Ok(())
     }
 
+    /// Open a file, or switch to its tab, with the cursor at the start of a line. Lines
+    /// are counted from 1, like in compiler messages.
+    pub fn goto_line(&mut self, file_path: &Path, line: usize) -> std::io::Result<()> {
+        self.load_from_file(file_path)?;
+        if let Some(tab) = self.active_tab.and_then(|i| self.tabs.get_mut(i)) {
+            tab.goto = Some(line);
+        }
+        Ok(())
+    }
+
+    /// The line the cursor is going to be put on in the active tab, if there is one.
+    pub fn pending_goto(&self) -> Option<usize> {
+        self.tabs.get(self.active_tab?)?.goto
+    }
+
+    // points the tabs of files that were moved or renamed (or are in a folder that was)
+    // at where they are now
+    pub fn file_moved(&mut self, from: &Path, to: &Path) {
+        for tab in self.tabs.iter_mut() {
+            if let Some(rest) = tab.path.as_ref().and_then(|p| p.strip_prefix(from).ok()) {
+                tab.path = Some(match rest.as_os_str().is_empty() {
+                    true => to.to_path_buf(),
+                    false => to.join(rest),
+                });
+            }
+        }
+    }
+
+    // closes the tabs of files that were deleted, unless they have unsaved changes
+    pub fn file_deleted(&mut self, deleted: &Path) {
+        let active = self.active_tab.and_then(|i| self.tabs.get(i)).and_then(|t| t.path.clone());
+        self.tabs.retain(|tab| !(tab.synced && tab.path.as_ref().is_some_and(|p| p.starts_with(deleted))));
+        self.active_tab = match active {
+            Some(active) => self.tabs.iter().position(|t| t.path.as_ref() == Some(&active)),
+            None => None,
+        }.or((!self.tabs.is_empty()).then(|| self.tabs.len() - 1));
+    }
+
     // iterates through and saves all open tabs
     pub fn save_all(&mut self) -> std::io::Result<()> {
         for tab in self.tabs.iter_mut() {
@@ -222,6 +312,16 @@ impl CodeEditor {
             i = active_tab.unwrap();
         }
 
+        if let CodeFile { preview: Some(preview), path, .. } = &mut tabs[i] {
+            let key = path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+            preview.display(ui, &key);
+            return;
+        }
+        if tabs[i].large.is_some() {
+            display_large_file(ui, &mut tabs[i]);
+            return;
+        }
+
         let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
             // Call the highlight function (below), which is a memoized version
             // of this struct's highlight method
@@ -231,15 +331,26 @@ impl CodeEditor {
 
         ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
             let former_contents = tabs[i].code.clone();
-            let resp = ui.add(
-                egui::TextEdit::multiline(&mut tabs[i].code)
-                    .font(egui::TextStyle::Name("EditorFont".into()))
-                    .code_editor()
-                    .lock_focus(true)
-                    .desired_width(f32::INFINITY)
-                    .frame(false)
-                    .layouter(&mut layouter),
-            );
+            let output = egui::TextEdit::multiline(&mut tabs[i].code)
+                .font(egui::TextStyle::Name("EditorFont".into()))
+                .code_editor()
+                .lock_focus(true)
+                .desired_width(f32::INFINITY)
+                .frame(false)
+                .layouter(&mut layouter)
+                .show(ui);
+            // put the cursor on the line that was asked for, and scroll to it
+            if let Some(line) = tabs[i].goto.take() {
+                let start = line_starts(&tabs[i].code).get(line.saturating_sub(1)).copied().unwrap_or(tabs[i].code.len());
+                let ccursor = egui::text::CCursor::new(tabs[i].code[..start].chars().count());
+                let mut state = output.state.clone();
+                state.cursor.set_char_range(Some(egui::text::CCursorRange::one(ccursor)));
+                state.store(ctx, output.response.id);
+                output.response.request_focus();
+                let rect = output.galley.pos_from_ccursor(ccursor).translate(output.galley_pos.to_vec2());
+                ui.scroll_to_rect(rect, Some(egui::Align::Center));
+            }
+            let resp = output.response;
             // check if the code has changed, so we can set the synced flag
             if tabs[i].synced && tabs[i].code != former_contents {
                 tabs[i].synced = false;
@@ -260,6 +371,16 @@ impl CodeEditor {
         });
     }
 
+    /// The preview of the active tab, if it's a file that isn't text.
+    pub fn active_preview(&self) -> Option<&Preview> {
+        self.tabs.get(self.active_tab?)?.preview.as_ref()
+    }
+
+    /// The large-file mode of the active tab, if it's in it.
+    pub fn active_large_file(&self) -> Option<&LargeFile> {
+        self.tabs.get(self.active_tab?)?.large.as_ref()
+    }
+
     pub fn close_all_tabs(&mut self) {
         self.active_tab = None;
         self.tabs.clear();
@@ -268,6 +389,15 @@ impl CodeEditor {
     pub fn get_active_tab(&self) -> Option<usize> {
         return self.active_tab;
     }
+
+    /// The code in the active tab, and the file it's from. Previewed files have no code.
+    pub fn active_code(&self) -> Option<(Option<&Path>, &str)> {
+        let tab = self.tabs.get(self.active_tab?)?;
+        if tab.preview.is_some() {
+            return None;
+        }
+        Some((tab.path.as_deref(), tab.code.as_str()))
+    }
     pub fn close_tab(&mut self, i: usize, warnings: &mut Warnings) {
         if self.tabs.len() == 0 {
             return;
@@ -317,9 +447,7 @@ impl CodeEditor {
                 // extract the file name for the tab and display it
                 // as a clickable label
                 let p = code_file.path.clone().unwrap();
-                let fname = p.as_path().file_name().unwrap();
-                let fname = fname.to_str().unwrap();
-                let mut text = RichText::new(fname);
+                let mut text = RichText::new(paths::display_name(&p));
                 // active tab should be hightlighted
                 if let Some(at) = self.active_tab {
                     if at == i {
@@ -344,6 +472,54 @@ impl CodeEditor {
 
 }
 
+// Shows a file in large-file mode: without highlighting, and while it's read only, laying
+// out just the lines in view
+fn display_large_file(ui: &mut Ui, tab: &mut CodeFile) {
+    let CodeFile { code, large, synced, .. } = tab;
+    let Some(mode) = large else {
+        return;
+    };
+    let mut open_normally = false;
+    ui.horizontal(|ui| {
+        let megabytes = code.len() as f64 / (1024.0 * 1024.0);
+        ui.colored_label(ui.visuals().warn_fg_color, format!("large file ({:.1} MB, {} lines), shown without highlighting", megabytes, mode.line_count()));
+        if ui.checkbox(&mut mode.read_only, "read only").changed() && mode.read_only {
+            mode.reindex(code);
+        }
+        if ui.button("open normally").on_hover_text("highlight and lay out the whole file, which can be slow").clicked() {
+            open_normally = true;
+        }
+    });
+    let font = egui::TextStyle::Name("EditorFont".into());
+    if mode.read_only {
+        let row_height = ui.text_style_height(&font);
+        let number_width = mode.line_count().to_string().len();
+        ScrollArea::both().auto_shrink([false; 2]).show_rows(ui, row_height, mode.line_count(), |ui, rows| {
+            for row in rows {
+                let text = format!("{:>width$}  {}", row + 1, mode.line(code, row), width = number_width);
+                ui.add(Label::new(RichText::new(text).text_style(font.clone())).wrap(false));
+            }
+        });
+    } else {
+        ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
+            let response = ui.add(
+                egui::TextEdit::multiline(code)
+                    .font(font)
+                    .code_editor()
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY)
+                    .frame(false),
+            );
+            if response.changed() {
+                *synced = false;
+            }
+        });
+    }
+    if open_normally {
+        *large = None;
+    }
+}
+
 fn as_byte_range(whole: &str, range: &str) -> std::ops::Range<usize> {
     let whole_start = whole.as_ptr() as usize;
     let range_start = range.as_ptr() as usize;
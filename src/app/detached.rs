@@ -0,0 +1,141 @@
+//! Title: Iron Coder App Module - Detached Windows
+//! Description: Tool windows that can be torn out of the main window into OS windows of
+//!   their own, so the serial monitor or signal viewer can live on a second monitor while
+//!   the main window keeps the editor. Where each detached window was is saved with the
+//!   rest of the app state, so the layout comes back on restart.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+/// The size of a window when it's first detached.
+const DEFAULT_SIZE: [f32; 2] = [520.0, 400.0];
+
+/// Where a detached window is, in monitor space.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowPlacement {
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+}
+
+impl Default for WindowPlacement {
+    fn default() -> Self {
+        Self { position: None, size: DEFAULT_SIZE }
+    }
+}
+
+/// The tool windows that have been detached, and where they are.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct DetachedWindows {
+    /// The detached windows by title, where they were last seen
+    placements: BTreeMap<String, WindowPlacement>,
+    /// Where the open windows were created. egui moves a window whenever its builder
+    /// changes, so this stays put while the window is dragged around.
+    #[serde(skip)]
+    created: HashMap<String, WindowPlacement>,
+}
+
+impl DetachedWindows {
+
+    pub fn is_detached(&self, title: &str) -> bool {
+        self.placements.contains_key(title)
+    }
+
+    pub fn placement(&self, title: &str) -> Option<WindowPlacement> {
+        self.placements.get(title).copied()
+    }
+
+    /// Move a tool window into a window of its own, where it was last if it's been detached
+    /// before.
+    pub fn detach(&mut self, title: &str) {
+        self.placements.entry(title.to_string()).or_default();
+    }
+
+    /// Put a tool window back in the main window.
+    pub fn attach(&mut self, title: &str) {
+        self.placements.remove(title);
+        self.created.remove(title);
+    }
+
+    /// Keep track of where a detached window has been moved to.
+    pub fn moved(&mut self, title: &str, placement: WindowPlacement) {
+        if let Some(saved) = self.placements.get_mut(title) {
+            *saved = placement;
+        }
+    }
+
+    /// Show a tool window, in the main window or in one of its own if it's been detached.
+    /// `open` is cleared when the window is closed; a detached window stays detached, so
+    /// it opens where it was next time.
+    pub fn show(&mut self, ctx: &egui::Context, title: &str, open: &mut bool, add_contents: impl FnOnce(&mut egui::Ui)) {
+        if !*open {
+            self.created.remove(title);
+            return;
+        }
+        let mut add_contents = Some(add_contents);
+        let mut detach = false;
+        let mut attach = false;
+        if let Some(placement) = self.placement(title) {
+            let created = *self.created.entry(title.to_string()).or_insert(placement);
+            let mut builder = egui::ViewportBuilder::default().with_title(title).with_inner_size(created.size);
+            if let Some(position) = created.position {
+                builder = builder.with_position(position);
+            }
+            let mut now = placement;
+            let id = egui::ViewportId::from_hash_of(("detached window", title));
+            let shown = ctx.show_viewport_immediate(id, builder, |ctx, class| {
+                if class == egui::ViewportClass::Embedded {
+                    // the backend can't open another window
+                    return false;
+                }
+                egui::TopBottomPanel::top("detached window bar").show(ctx, |ui| {
+                    attach = ui.small_button("dock").on_hover_text("move back into the main window").clicked();
+                });
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if let Some(add_contents) = add_contents.take() {
+                        add_contents(ui);
+                    }
+                });
+                ctx.input(|input| {
+                    let viewport = input.viewport();
+                    if viewport.close_requested() {
+                        *open = false;
+                    }
+                    if let Some(outer) = viewport.outer_rect {
+                        now.position = Some([outer.min.x, outer.min.y]);
+                    }
+                    if let Some(inner) = viewport.inner_rect {
+                        now.size = [inner.width(), inner.height()];
+                    }
+                });
+                true
+            });
+            self.moved(title, now);
+            if shown {
+                if attach {
+                    self.attach(title);
+                }
+                return;
+            }
+        }
+        egui::Window::new(title)
+        .open(open)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            if ctx.embed_viewports() {
+                // there's nowhere to detach to
+            } else if ui.small_button("pop out").on_hover_text("move to a window of its own").clicked() {
+                detach = true;
+            }
+            if let Some(add_contents) = add_contents.take() {
+                add_contents(ui);
+            }
+        });
+        if detach {
+            self.detach(title);
+        }
+    }
+}
@@ -0,0 +1,612 @@
+//! Title: Iron Coder App Module - Devices
+//! Description: Finding IP-connected boards on the local network, with mDNS (DNS-SD)
+//!   and SSDP, and a panel listing them with a few quick actions. Project firmware can
+//!   advertise an `_iron-coder._tcp` service whose TXT record describes what it offers:
+//!   `telemetry=<port>` for a TCP stream of text lines, and `ota=<path>` for an HTTP
+//!   endpoint that takes a firmware image in a POST request.
+
+use log::info;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use rfd::FileDialog;
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event, EventBus};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+
+const MDNS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+const SSDP_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+/// How long a scan listens for answers.
+const SCAN_TIME: Duration = Duration::from_secs(3);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// The most telemetry we keep around for display.
+const MAX_TELEMETRY_LEN: usize = 64 * 1024;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+
+/// How a device was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiscoverySource {
+    Mdns,
+    Ssdp,
+}
+
+impl fmt::Display for DiscoverySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiscoverySource::Mdns => write!(f, "mDNS"),
+            DiscoverySource::Ssdp => write!(f, "SSDP"),
+        }
+    }
+}
+
+/// A device found on the network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkDevice {
+    /// The service instance name, or for SSDP the server description
+    pub name: String,
+    /// The service type, i.e. _iron-coder._tcp, or for SSDP the search target
+    pub service: String,
+    pub hostname: String,
+    pub addresses: Vec<IpAddr>,
+    pub port: u16,
+    /// The TXT record of an mDNS service
+    pub txt: BTreeMap<String, String>,
+    pub source: DiscoverySource,
+}
+
+impl NetworkDevice {
+
+    /// What identifies the device across scans.
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.service, self.name)
+    }
+
+    /// The address to connect to, preferring IPv4.
+    pub fn address(&self) -> Option<IpAddr> {
+        self.addresses.iter().find(|a| a.is_ipv4()).or(self.addresses.first()).copied()
+    }
+
+    /// The port of the telemetry stream, if the device has one.
+    pub fn telemetry_port(&self) -> Option<u16> {
+        self.txt.get("telemetry").and_then(|p| p.parse().ok())
+    }
+
+    /// The URL to post firmware images to, if the device takes updates.
+    pub fn ota_url(&self) -> Option<String> {
+        let path = self.txt.get("ota")?;
+        let host = match self.address()? {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{}]", ip),
+        };
+        Some(format!("http://{}:{}/{}", host, self.port, path.trim_start_matches('/')))
+    }
+}
+
+/// A DNS resource record, with the parts we care about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    Ptr { name: String, target: String },
+    Srv { name: String, port: u16, target: String },
+    Txt { name: String, entries: BTreeMap<String, String> },
+    Address { name: String, address: IpAddr },
+}
+
+/// Append a domain name, i.e. _http._tcp.local, as DNS labels.
+fn push_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend(label);
+    }
+    out.push(0);
+}
+
+/// Build an mDNS query asking for the instances of each service type (i.e. _http._tcp).
+pub fn mdns_query(services: &[String]) -> Vec<u8> {
+    let mut query = vec![0, 0, 0, 0];
+    query.extend((services.len() as u16).to_be_bytes());
+    query.extend([0; 6]);
+    for service in services {
+        push_name(&mut query, &format!("{}.local", service.trim_end_matches(".local")));
+        query.extend(TYPE_PTR.to_be_bytes());
+        // class IN, asking for a unicast response
+        query.extend(0x8001u16.to_be_bytes());
+    }
+    query
+}
+
+/// Read a (possibly compressed) domain name at `at`, returning it and the offset after it.
+fn read_name(msg: &[u8], at: usize) -> Result<(String, usize), String> {
+    let mut labels: Vec<String> = vec![];
+    let mut pos = at;
+    let mut end = None;
+    // a limit on the number of labels also stops compression loops
+    for _ in 0..128 {
+        let len = *msg.get(pos).ok_or("name runs past the end of the message")? as usize;
+        match len {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+            },
+            0xc0.. => {
+                let low = *msg.get(pos + 1).ok_or("truncated name pointer")? as usize;
+                end.get_or_insert(pos + 2);
+                pos = ((len & 0x3f) << 8) | low;
+            },
+            1..=63 => {
+                let label = msg.get(pos + 1..pos + 1 + len).ok_or("label runs past the end of the message")?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+                pos += 1 + len;
+            },
+            _ => return Err(format!("bad label length 0x{:02x}", len)),
+        }
+    }
+    Err("name is too long".to_string())
+}
+
+/// Parse the records of an mDNS response.
+pub fn parse_dns_response(msg: &[u8]) -> Result<Vec<Record>, String> {
+    let u16_at = |i: usize| msg.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or("truncated message");
+    let flags = u16_at(2)?;
+    if flags & 0x8000 == 0 {
+        return Err("not a response".to_string());
+    }
+    let questions = u16_at(4)?;
+    let records = u16_at(6)? as usize + u16_at(8)? as usize + u16_at(10)? as usize;
+    let mut at = 12;
+    for _ in 0..questions {
+        at = read_name(msg, at)?.1 + 4;
+    }
+    let mut parsed = vec![];
+    for _ in 0..records {
+        let (name, after) = read_name(msg, at)?;
+        let kind = u16_at(after)?;
+        let len = u16_at(after + 8)? as usize;
+        let data_at = after + 10;
+        let data = msg.get(data_at..data_at + len).ok_or("record runs past the end of the message")?;
+        at = data_at + len;
+        let record = match kind {
+            TYPE_PTR => Record::Ptr { name, target: read_name(msg, data_at)?.0 },
+            TYPE_SRV if len >= 6 => Record::Srv {
+                name,
+                port: u16::from_be_bytes([data[4], data[5]]),
+                target: read_name(msg, data_at + 6)?.0,
+            },
+            TYPE_TXT => {
+                let mut entries = BTreeMap::new();
+                let mut i = 0;
+                while let Some(&entry_len) = data.get(i) {
+                    let entry = data.get(i + 1..i + 1 + entry_len as usize).ok_or("truncated TXT record")?;
+                    let entry = String::from_utf8_lossy(entry);
+                    match entry.split_once('=') {
+                        Some((k, v)) => entries.insert(k.to_lowercase(), v.to_string()),
+                        None if !entry.is_empty() => entries.insert(entry.to_lowercase(), String::new()),
+                        None => None,
+                    };
+                    i += 1 + entry_len as usize;
+                }
+                Record::Txt { name, entries }
+            },
+            TYPE_A if len == 4 => Record::Address { name, address: IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])) },
+            TYPE_AAAA if len == 16 => {
+                let octets: [u8; 16] = data.try_into().map_err(|_| "bad AAAA record")?;
+                Record::Address { name, address: IpAddr::V6(Ipv6Addr::from(octets)) }
+            },
+            _ => continue,
+        };
+        parsed.push(record);
+    }
+    Ok(parsed)
+}
+
+/// Put together the devices described by a set of mDNS records, for the given service types.
+pub fn devices_from_records(records: &[Record], services: &[String]) -> Vec<NetworkDevice> {
+    let mut devices = vec![];
+    for record in records {
+        let Record::Ptr { name: service, target: instance } = record else {
+            continue;
+        };
+        let service = service.trim_end_matches(".local");
+        if !services.iter().any(|s| s.trim_end_matches(".local").eq_ignore_ascii_case(service)) {
+            continue;
+        }
+        let mut device = NetworkDevice {
+            name: instance.strip_suffix(&format!(".{}.local", service)).unwrap_or(instance).to_string(),
+            service: service.to_string(),
+            hostname: String::new(),
+            addresses: vec![],
+            port: 0,
+            txt: BTreeMap::new(),
+            source: DiscoverySource::Mdns,
+        };
+        for record in records {
+            match record {
+                Record::Srv { name, port, target } if name == instance => {
+                    device.port = *port;
+                    device.hostname = target.clone();
+                },
+                Record::Txt { name, entries } if name == instance => device.txt = entries.clone(),
+                _ => (),
+            }
+        }
+        device.addresses = records.iter().filter_map(|r| match r {
+            Record::Address { name, address } if *name == device.hostname => Some(*address),
+            _ => None,
+        }).collect();
+        devices.push(device);
+    }
+    devices
+}
+
+/// Build an SSDP search request.
+pub fn ssdp_search(target: &str) -> Vec<u8> {
+    format!("M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n", SSDP_ADDR, target).into_bytes()
+}
+
+/// Parse an SSDP search response from `from`.
+pub fn parse_ssdp_response(msg: &[u8], from: IpAddr) -> Option<NetworkDevice> {
+    let text = std::str::from_utf8(msg).ok()?;
+    let mut lines = text.lines();
+    if !lines.next()?.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+    let headers: BTreeMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_uppercase(), v.trim().to_string()))
+        .collect();
+    // the host and port of the description URL, i.e. http://192.168.1.20:80/desc.xml
+    let location = headers.get("LOCATION")?;
+    let authority = location.split("://").nth(1)?.split('/').next()?;
+    let (host, port) = match authority.strip_prefix('[').and_then(|a| a.split_once(']')) {
+        // an IPv6 address
+        Some((host, port)) => (host, port.strip_prefix(':')),
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => 80,
+    };
+    let address = host.parse().unwrap_or(from);
+    Some(NetworkDevice {
+        name: headers.get("SERVER").or(headers.get("USN")).cloned().unwrap_or_else(|| location.clone()),
+        service: headers.get("ST").cloned().unwrap_or_default(),
+        hostname: host.to_string(),
+        addresses: vec![address],
+        port,
+        txt: BTreeMap::new(),
+        source: DiscoverySource::Ssdp,
+    })
+}
+
+/// Send the mDNS and SSDP queries, and publish the devices that answer.
+fn scan(services: &[String], ssdp: bool, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| e.to_string())?;
+    socket.set_read_timeout(Some(Duration::from_millis(250))).map_err(|e| e.to_string())?;
+    socket.set_multicast_ttl_v4(2).map_err(|e| e.to_string())?;
+    if !services.is_empty() {
+        socket.send_to(&mdns_query(services), MDNS_ADDR).map_err(|e| format!("couldn't send mDNS query: {}", e))?;
+    }
+    if ssdp {
+        socket.send_to(&ssdp_search("ssdp:all"), SSDP_ADDR).map_err(|e| format!("couldn't send SSDP search: {}", e))?;
+    }
+    job.set_status("listening for devices");
+    let start = Instant::now();
+    let mut buf = [0u8; 9000];
+    while start.elapsed() < SCAN_TIME && !job.is_cancelled() {
+        job.set_progress(start.elapsed().as_secs_f32() / SCAN_TIME.as_secs_f32());
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        let devices = if buf.starts_with(b"HTTP/") {
+            parse_ssdp_response(&buf[..len], from.ip()).into_iter().collect()
+        } else {
+            match parse_dns_response(&buf[..len]) {
+                Ok(records) => devices_from_records(&records, services),
+                Err(e) => {
+                    info!("ignoring mDNS packet from {}: {}", from, e);
+                    continue;
+                },
+            }
+        };
+        for mut device in devices {
+            // the responder didn't include its address record
+            if device.addresses.is_empty() {
+                device.addresses.push(from.ip());
+            }
+            bus.publish(Event::DeviceFound(device));
+        }
+        job.request_repaint();
+    }
+    Ok(())
+}
+
+/// The system ping command for an address.
+pub fn ping_command(address: IpAddr) -> duct::Expression {
+    let count = if cfg!(windows) { "-n" } else { "-c" };
+    duct::cmd!("ping", count, "3", address.to_string())
+}
+
+/// The round trip times, in milliseconds, in the output of ping (i.e. "time=12.3 ms",
+/// or "time<1ms" on Windows).
+pub fn parse_ping_times(output: &str) -> Vec<f32> {
+    output.lines().filter_map(|line| {
+        let (_, rest) = line.split_once("time=").or_else(|| line.split_once("time<"))?;
+        let number: String = rest.chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+        number.parse().ok()
+    }).collect()
+}
+
+fn ping(address: IpAddr, job: &JobContext) -> Result<(), String> {
+    job.set_status(&format!("pinging {}", address));
+    let output = ping_command(address).stderr_to_stdout().stdout_capture().unchecked().run()
+        .map_err(|e| format!("couldn't run ping: {}", e))?;
+    let times = parse_ping_times(&String::from_utf8_lossy(&output.stdout));
+    if times.is_empty() {
+        return Err(format!("no reply from {}", address));
+    }
+    let average = times.iter().sum::<f32>() / times.len() as f32;
+    job.set_status(&format!("{} replies, {:.1} ms average", times.len(), average));
+    Ok(())
+}
+
+/// Read text lines from a device's telemetry port until cancelled or disconnected.
+fn stream_telemetry(address: SocketAddr, key: String, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| format!("couldn't connect to {}: {}", address, e))?;
+    stream.set_read_timeout(Some(Duration::from_millis(250))).map_err(|e| e.to_string())?;
+    job.set_status(&format!("streaming from {}", address));
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    while !job.is_cancelled() {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.ends_with(b"\n") => {
+                bus.publish(Event::DeviceTelemetry { device: key.clone(), line: String::from_utf8_lossy(&line).to_string() });
+                line.clear();
+                job.request_repaint();
+            },
+            Ok(_) => (),
+            // a partial line stays in the buffer until the rest arrives
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Post a firmware image to a device's update endpoint.
+fn send_update(url: &str, image: &Path, job: &JobContext) -> Result<(), String> {
+    let data = fs::read(image).map_err(|e| format!("couldn't read {}: {}", image.display(), e))?;
+    job.set_status(&format!("sending {} bytes to {}", data.len(), url));
+    let response = ureq::post(url)
+        .set("Content-Type", "application/octet-stream")
+        .timeout(Duration::from_secs(120))
+        .send_bytes(&data)
+        .map_err(|e| e.to_string())?;
+    job.set_status(&format!("device answered {} {}", response.status(), response.status_text()));
+    Ok(())
+}
+
+/// The state of the devices window.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevicesPanel {
+    /// The mDNS service types to look for
+    pub services: Vec<String>,
+    pub ssdp: bool,
+    #[serde(skip)]
+    devices: Vec<NetworkDevice>,
+    #[serde(skip)]
+    scan_job: Option<JobId>,
+    /// The last quick action job run on each device, by key
+    #[serde(skip)]
+    actions: BTreeMap<String, JobId>,
+    #[serde(skip)]
+    telemetry: Option<(String, JobId)>,
+    #[serde(skip)]
+    telemetry_buffer: String,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+}
+
+impl Default for DevicesPanel {
+    fn default() -> Self {
+        Self {
+            services: vec!["_iron-coder._tcp".to_string(), "_http._tcp".to_string(), "_arduino._tcp".to_string()],
+            ssdp: false,
+            devices: vec![],
+            scan_job: None,
+            actions: BTreeMap::new(),
+            telemetry: None,
+            telemetry_buffer: String::new(),
+            events: None,
+        }
+    }
+}
+
+impl DevicesPanel {
+
+    fn is_running(ctx: &egui::Context, job: Option<JobId>) -> bool {
+        job.and_then(|id| jobs::shared(ctx).state(id)) == Some(JobState::Running)
+    }
+
+    /// Start looking for devices. Devices found before stay listed.
+    pub fn scan(&mut self, ctx: &egui::Context) {
+        let services = self.services.clone();
+        let ssdp = self.ssdp;
+        let bus = events::shared(ctx);
+        self.scan_job = Some(jobs::shared(ctx).spawn("device discovery", Some(ctx), move |job| {
+            scan(&services, ssdp, &bus, job)
+        }));
+    }
+
+    fn start_telemetry(&mut self, ctx: &egui::Context, device: &NetworkDevice, address: SocketAddr) {
+        self.stop_telemetry(ctx);
+        self.telemetry_buffer.clear();
+        let key = device.key();
+        let bus = events::shared(ctx);
+        let job_key = key.clone();
+        let id = jobs::shared(ctx).spawn(&format!("telemetry ({})", device.name), Some(ctx), move |job| {
+            stream_telemetry(address, job_key, &bus, job)
+        });
+        self.telemetry = Some((key, id));
+    }
+
+    fn stop_telemetry(&mut self, ctx: &egui::Context) {
+        if let Some((_, id)) = self.telemetry.take() {
+            jobs::shared(ctx).cancel(id);
+        }
+    }
+
+    /// Show one device's row in the device table.
+    fn display_device(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, device: &NetworkDevice, project_dir: Option<&Path>) {
+        let key = device.key();
+        let name = ui.label(&device.name);
+        if !device.txt.is_empty() {
+            let txt: Vec<String> = device.txt.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+            name.on_hover_text(txt.join("\n"));
+        }
+        let address = device.address();
+        ui.label(match address {
+            Some(ip) if !device.hostname.is_empty() => format!("{} ({})", device.hostname.trim_end_matches('.'), ip),
+            Some(ip) => ip.to_string(),
+            None => device.hostname.clone(),
+        });
+        ui.label(format!("{} ({})", device.service, device.source));
+        ui.horizontal(|ui| {
+            let busy = Self::is_running(ctx, self.actions.get(&key).copied());
+            ui.add_enabled_ui(!busy, |ui| {
+                if let Some(ip) = address {
+                    if ui.small_button("ping").clicked() {
+                        let id = jobs::shared(ctx).spawn(&format!("ping {}", ip), Some(ctx), move |job| ping(ip, job));
+                        self.actions.insert(key.clone(), id);
+                    }
+                }
+                if let (Some(ip), Some(port)) = (address, device.telemetry_port()) {
+                    let streaming = self.telemetry.as_ref().is_some_and(|(k, id)| *k == key && Self::is_running(ctx, Some(*id)));
+                    if streaming {
+                        if ui.small_button("stop telemetry").clicked() {
+                            self.stop_telemetry(ctx);
+                        }
+                    } else if ui.small_button("telemetry").clicked() {
+                        self.start_telemetry(ctx, device, SocketAddr::new(ip, port));
+                    }
+                }
+                if let Some(url) = device.ota_url() {
+                    if ui.small_button("update").on_hover_text(format!("send a firmware image to {}", url)).clicked() {
+                        // start where packaged slot images go
+                        let mut dialog = FileDialog::new().add_filter("firmware image", &["bin"]);
+                        if let Some(dir) = project_dir {
+                            let images = dir.join("target").join("iron-coder");
+                            dialog = dialog.set_directory(if images.is_dir() { images } else { dir.to_path_buf() });
+                        }
+                        let picked = dialog.pick_file();
+                        if let Some(image) = picked {
+                            let id = jobs::shared(ctx).spawn(&format!("update {}", device.name), Some(ctx), move |job| send_update(&url, &image, job));
+                            self.actions.insert(key.clone(), id);
+                        }
+                    }
+                }
+            });
+            // the outcome of the last action
+            if let Some(info) = self.actions.get(&key).and_then(|id| jobs::shared(ctx).jobs().into_iter().find(|j| j.id == *id)) {
+                match info.state {
+                    JobState::Running => { ui.spinner(); },
+                    JobState::Failed(e) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+                    _ => { ui.label(info.status); },
+                }
+            }
+        });
+        ui.end_row();
+    }
+
+    /// Stop listening on the event bus, for when the window is closed. What's found or
+    /// streamed while it's closed isn't kept for it.
+    pub fn close(&mut self) {
+        self.events = None;
+    }
+
+    /// Show the discovery settings, the devices found, and any telemetry stream.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, project_dir: Option<&Path>) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                Event::DeviceFound(device) => {
+                    match self.devices.iter_mut().find(|d| d.key() == device.key()) {
+                        Some(existing) => *existing = device,
+                        None => self.devices.push(device),
+                    }
+                },
+                Event::DeviceTelemetry { device, line } if self.telemetry.as_ref().is_some_and(|(key, _)| *key == device) => {
+                    self.telemetry_buffer += &line;
+                },
+                _ => (),
+            }
+        }
+        if self.telemetry_buffer.len() > MAX_TELEMETRY_LEN {
+            let mut cut = self.telemetry_buffer.len() - MAX_TELEMETRY_LEN;
+            while !self.telemetry_buffer.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.telemetry_buffer.drain(..cut);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("services:");
+            let mut services = self.services.join(", ");
+            if ui.add(egui::TextEdit::singleline(&mut services).desired_width(260.0)).changed() {
+                self.services = services.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+            }
+            ui.checkbox(&mut self.ssdp, "SSDP");
+            if Self::is_running(ctx, self.scan_job) {
+                ui.spinner();
+            } else if ui.button("scan").clicked() {
+                self.scan(ctx);
+            }
+            if ui.button("clear").clicked() {
+                self.devices.clear();
+            }
+        });
+        ui.separator();
+        if self.devices.is_empty() {
+            ui.label("no devices found yet");
+        }
+        let devices = self.devices.clone();
+        egui::Grid::new("network_devices").num_columns(4).striped(true).show(ui, |ui| {
+            for device in devices.iter() {
+                self.display_device(ctx, ui, device, project_dir);
+            }
+        });
+        if let Some((key, _)) = &self.telemetry {
+            ui.separator();
+            ui.label(format!("telemetry from {}", key));
+            egui::ScrollArea::both()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.telemetry_buffer)
+                    .code_editor()
+                    .interactive(false)
+                    .desired_width(f32::INFINITY)
+                );
+            });
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Title: Iron Coder App Module - Events
+//! Description: This module defines the typed event bus that subsystems use to talk to
+//!   each other. Producers publish an Event, and every panel (or test) that subscribed
+//!   gets its own copy, instead of reaching into shared state.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::app::devices::NetworkDevice;
+use crate::project::backend::Diagnostic;
+use crate::project::health::Finding;
+use crate::project::lorawan::Uplink;
+use crate::project::mcumgr::ImageSlot;
+
+/// The key used to store the shared bus in the egui Context temp data.
+pub const EVENT_BUS_ID: &str = "event_bus";
+
+/// The events that can be published on the bus.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A board was picked in the board selector. Contains the board's key.
+    BoardSelected(String),
+    /// A board's quick start was asked for. Contains the board's key.
+    ShowQuickStart(String),
+    /// A build job ended. `success` is false if it failed or was cancelled.
+    BuildFinished { success: bool },
+    /// A debug probe was detected. Contains the probe description.
+    ProbeAttached(String),
+    /// A line of data arrived from the serial monitor.
+    SerialData(String),
+    /// A COBS frame arrived from the serial monitor, without its zero delimiter.
+    SerialFrame(Vec<u8>),
+    /// A line of RTT output arrived from a board attached with probe-rs.
+    RttData(String),
+    /// A LoRaWAN uplink was received from a gateway or network server.
+    LoraUplink(Uplink),
+    /// The image slots of a device running MCUboot were read.
+    ImageState(Vec<ImageSlot>),
+    /// A device answered a network discovery query.
+    DeviceFound(NetworkDevice),
+    /// A line arrived on a network device's telemetry stream. `device` is the device key.
+    DeviceTelemetry { device: String, line: String },
+    /// The project health check finished, with what it found, worst first.
+    HealthChecked(Vec<Finding>),
+    /// Clippy finished checking the project's lints, with what it found.
+    LintsChecked(Vec<Diagnostic>),
+    /// An example was clicked in a board widget, to be opened in the code editor.
+    /// Contains the example's path.
+    ExamplePicked(PathBuf),
+    /// A new project was asked for from one of a board's examples.
+    /// Contains the board's key and the example's path.
+    ProjectFromExample { board: String, example: PathBuf },
+}
+
+/// The EventBus is a cheaply cloneable handle; all clones share the same subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+}
+
+impl EventBus {
+
+    /// Get a receiver that will see every event published from now on.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send an event to all subscribers, forgetting the ones that have hung up.
+    pub fn publish(&self, event: Event) {
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+/// Fetch the app-wide event bus from the egui Context, creating it if needed.
+pub fn shared(ctx: &egui::Context) -> EventBus {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_insert_with(EVENT_BUS_ID.into(), EventBus::default).clone()
+    })
+}
@@ -0,0 +1,541 @@
+//! Title: Iron Coder App Module - Export
+//! Description: Records the telemetry published on the event bus (numbers in serial
+//!   monitor lines, postcard messages from the serial monitor, and network device
+//!   telemetry), and exports it as CSV, Parquet or InfluxDB line protocol for analysis.
+//!   Exports can be limited to some of the fields, and resampled onto a regular grid.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use enum_iterator::Sequence;
+use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::column::writer::ColumnWriter;
+use parquet::data_type::ByteArray;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use rfd::FileDialog;
+use serde::{Serialize, Deserialize};
+
+use crate::app::ansi;
+use crate::app::events::{self, Event};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::app::signals::parse_sample_line;
+use crate::app::toolbox::parse_si;
+use crate::project::messages::{self, Direction, MessageSchema, Value};
+
+/// The most records kept; the oldest are dropped after this.
+const MAX_RECORDS: usize = 500_000;
+
+/// The samples that arrived together, i.e. from one line or message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub time: f64,
+    /// Where the record came from, i.e. "serial" or a network device's key
+    pub source: String,
+    pub values: Vec<(String, f64)>,
+}
+
+/// Add the numbers in a decoded message to `out`, named by their path in the message,
+/// i.e. "Telemetry.samples[1]". Strings are left out, and bools count as 0 or 1.
+pub fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, f64)>) {
+    match value {
+        Value::Bool(b) => out.push((prefix.to_string(), *b as u8 as f64)),
+        Value::Unsigned(n) => out.push((prefix.to_string(), *n as f64)),
+        Value::Signed(n) => out.push((prefix.to_string(), *n as f64)),
+        Value::Float(x) => out.push((prefix.to_string(), *x)),
+        Value::String(_) | Value::Option(None) => (),
+        Value::Option(Some(v)) => flatten(prefix, v, out),
+        Value::List(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, i), item, out);
+            }
+        },
+        Value::Message(name, fields) => {
+            let prefix = if prefix.is_empty() { name.clone() } else { prefix.to_string() };
+            for (field, value) in fields.iter() {
+                flatten(&format!("{}.{}", prefix, field), value, out);
+            }
+        },
+    }
+}
+
+/// Which streams are recorded.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Sources {
+    pub serial_lines: bool,
+    pub serial_messages: bool,
+    pub network_telemetry: bool,
+}
+
+impl Default for Sources {
+    fn default() -> Self {
+        Self { serial_lines: true, serial_messages: true, network_telemetry: true }
+    }
+}
+
+impl Sources {
+
+    /// The source and samples of an event, if it's one being recorded and holds any.
+    pub fn samples(&self, event: Event, schema: &MessageSchema) -> Option<(String, Vec<(String, f64)>)> {
+        let (source, values) = match event {
+            Event::SerialData(line) if self.serial_lines => ("serial".to_string(), parse_sample_line(&ansi::strip(&line))),
+            Event::SerialFrame(frame) if self.serial_messages => {
+                let message = messages::cobs_decode(&frame).and_then(|bytes| schema.decode(Direction::ToHost, &bytes)).ok()?;
+                let mut values = vec![];
+                flatten("", &message, &mut values);
+                ("serial".to_string(), values)
+            },
+            Event::DeviceTelemetry { device, line } if self.network_telemetry => (device, parse_sample_line(&line)),
+            _ => return None,
+        };
+        (!values.is_empty()).then_some((source, values))
+    }
+}
+
+/// How the samples that fall in one period are combined when resampling.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Aggregation {
+    #[default]
+    Mean,
+    Min,
+    Max,
+    Last,
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Aggregation::Mean => write!(f, "mean"),
+            Aggregation::Min => write!(f, "min"),
+            Aggregation::Max => write!(f, "max"),
+            Aggregation::Last => write!(f, "last"),
+        }
+    }
+}
+
+impl Aggregation {
+    fn apply(&self, samples: &[f64]) -> Option<f64> {
+        let last = *samples.last()?;
+        Some(match self {
+            Aggregation::Mean => samples.iter().sum::<f64>() / samples.len() as f64,
+            Aggregation::Min => samples.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregation::Max => samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Last => last,
+        })
+    }
+}
+
+/// Resampling onto a grid of `period` seconds, aligned to the Unix epoch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resampling {
+    pub period: f64,
+    pub aggregation: Aggregation,
+}
+
+/// A row of an export. Values are None where a field had no samples. Resampled rows
+/// combine sources, so they don't have one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub time: f64,
+    pub source: Option<String>,
+    pub values: Vec<Option<f64>>,
+}
+
+/// The records to export, laid out as a table with a column per field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub fields: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl Table {
+
+    /// Lay out the selected fields of the records. Without resampling, each record that
+    /// has any of the fields makes a row; with it, each period that has any does.
+    pub fn new(records: &[Record], fields: &[String], resampling: Option<Resampling>) -> Self {
+        let column = |name: &str| fields.iter().position(|f| f == name);
+        let mut rows = vec![];
+        match resampling {
+            None => for record in records.iter() {
+                let mut values = vec![None; fields.len()];
+                for (name, value) in record.values.iter() {
+                    if let Some(i) = column(name) {
+                        values[i] = Some(*value);
+                    }
+                }
+                if values.iter().any(Option::is_some) {
+                    rows.push(Row { time: record.time, source: Some(record.source.clone()), values });
+                }
+            },
+            Some(Resampling { period, aggregation }) => {
+                let mut periods: BTreeMap<i64, Vec<Vec<f64>>> = BTreeMap::new();
+                for record in records.iter() {
+                    for (name, value) in record.values.iter() {
+                        if let Some(i) = column(name) {
+                            let samples = periods.entry((record.time / period).floor() as i64).or_insert_with(|| vec![vec![]; fields.len()]);
+                            samples[i].push(*value);
+                        }
+                    }
+                }
+                rows = periods.into_iter().map(|(n, samples)| Row {
+                    time: n as f64 * period,
+                    source: None,
+                    values: samples.iter().map(|s| aggregation.apply(s)).collect(),
+                }).collect();
+            },
+        }
+        Self { fields: fields.to_vec(), rows }
+    }
+
+    pub fn has_sources(&self) -> bool {
+        self.rows.iter().any(|r| r.source.is_some())
+    }
+}
+
+/// The file formats data can be exported in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+    InfluxLine,
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "CSV"),
+            ExportFormat::Parquet => write!(f, "Parquet"),
+            ExportFormat::InfluxLine => write!(f, "InfluxDB line protocol"),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::InfluxLine => "lp",
+        }
+    }
+}
+
+/// Write a table as CSV, with the time in seconds since the Unix epoch.
+pub fn to_csv(table: &Table) -> String {
+    let quote = |s: &str| if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() };
+    let sources = table.has_sources();
+    let mut csv = String::from(if sources { "time,source" } else { "time" });
+    for field in table.fields.iter() {
+        csv += &format!(",{}", quote(field));
+    }
+    csv.push('\n');
+    for row in table.rows.iter() {
+        csv += &format!("{:.6}", row.time);
+        if sources {
+            csv += &format!(",{}", quote(row.source.as_deref().unwrap_or_default()));
+        }
+        for value in row.values.iter() {
+            csv.push(',');
+            if let Some(value) = value {
+                csv += &value.to_string();
+            }
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Write a table as InfluxDB line protocol, one point per row with nanosecond times and
+/// the row's source as a tag. Values that aren't finite numbers are left out, since
+/// Influx can't store them.
+pub fn to_influx(table: &Table, measurement: &str) -> String {
+    let escape = |s: &str, special: &[char]| {
+        s.chars().fold(String::new(), |mut out, c| {
+            if special.contains(&c) { out.push('\\'); }
+            out.push(c);
+            out
+        })
+    };
+    let measurement = escape(measurement, &[',', ' ']);
+    let fields: Vec<String> = table.fields.iter().map(|f| escape(f, &[',', '=', ' '])).collect();
+    let mut lines = String::new();
+    for row in table.rows.iter() {
+        let values: Vec<String> = row.values.iter().zip(fields.iter())
+            .filter_map(|(v, f)| v.filter(|v| v.is_finite()).map(|v| format!("{}={}", f, v)))
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        let tag = row.source.as_deref().map(|s| format!(",source={}", escape(s, &[',', '=', ' ']))).unwrap_or_default();
+        lines += &format!("{}{} {} {}\n", measurement, tag, values.join(","), (row.time * 1e9).round() as i64);
+    }
+    lines
+}
+
+/// Write a table as Parquet, with the time as a UTC timestamp in microseconds, the
+/// source as a string if the rows have one, and an optional double column per field.
+pub fn to_parquet(table: &Table) -> Result<Vec<u8>, String> {
+    let error = |e: parquet::errors::ParquetError| e.to_string();
+    let time = Type::primitive_type_builder("time", PhysicalType::INT64)
+        .with_repetition(Repetition::REQUIRED)
+        .with_logical_type(Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MICROS(Default::default()) }))
+        .build().map_err(error)?;
+    let mut columns = vec![Arc::new(time)];
+    let sources = table.has_sources();
+    if sources {
+        let source = Type::primitive_type_builder("source", PhysicalType::BYTE_ARRAY)
+            .with_repetition(Repetition::REQUIRED)
+            .with_logical_type(Some(LogicalType::String))
+            .build().map_err(error)?;
+        columns.push(Arc::new(source));
+    }
+    for field in table.fields.iter() {
+        let column = Type::primitive_type_builder(field, PhysicalType::DOUBLE).with_repetition(Repetition::OPTIONAL).build().map_err(error)?;
+        columns.push(Arc::new(column));
+    }
+    let schema = Type::group_type_builder("telemetry").with_fields(columns).build().map_err(error)?;
+    let mut bytes = vec![];
+    let mut writer = SerializedFileWriter::new(&mut bytes, Arc::new(schema), Arc::new(WriterProperties::builder().build())).map_err(error)?;
+    let mut row_group = writer.next_row_group().map_err(error)?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column().map_err(error)? {
+        match column.untyped() {
+            ColumnWriter::Int64ColumnWriter(w) => {
+                let times: Vec<i64> = table.rows.iter().map(|r| (r.time * 1e6).round() as i64).collect();
+                w.write_batch(&times, None, None).map_err(error)?;
+            },
+            ColumnWriter::ByteArrayColumnWriter(w) => {
+                let sources: Vec<ByteArray> = table.rows.iter().map(|r| r.source.as_deref().unwrap_or_default().into()).collect();
+                w.write_batch(&sources, None, None).map_err(error)?;
+            },
+            ColumnWriter::DoubleColumnWriter(w) => {
+                // the field columns come after the time and source
+                let i = index - 1 - sources as usize;
+                let values: Vec<f64> = table.rows.iter().filter_map(|r| r.values[i]).collect();
+                let present: Vec<i16> = table.rows.iter().map(|r| r.values[i].is_some() as i16).collect();
+                w.write_batch(&values, Some(&present), None).map_err(error)?;
+            },
+            _ => return Err("unexpected parquet column".to_string()),
+        }
+        column.close().map_err(error)?;
+        index += 1;
+    }
+    row_group.close().map_err(error)?;
+    writer.close().map_err(error)?;
+    Ok(bytes)
+}
+
+/// The records captured so far, shared with the recording job.
+#[derive(Default)]
+pub struct Capture {
+    records: Vec<Record>,
+    /// Every field seen, including ones whose records were dropped
+    fields: BTreeSet<String>,
+}
+
+impl Capture {
+
+    pub fn push(&mut self, record: Record) {
+        for (name, _) in record.values.iter() {
+            if !self.fields.contains(name) {
+                self.fields.insert(name.clone());
+            }
+        }
+        self.records.push(record);
+        if self.records.len() > MAX_RECORDS {
+            self.records.drain(..self.records.len() - MAX_RECORDS);
+        }
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn fields(&self) -> &BTreeSet<String> {
+        &self.fields
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.fields.clear();
+    }
+}
+
+/// Stamp and store the events until the job is cancelled.
+fn record(rx: std::sync::mpsc::Receiver<Event>, sources: Sources, schema: MessageSchema, capture: &Mutex<Capture>, job: &JobContext) -> Result<(), String> {
+    while !job.is_cancelled() {
+        let event = match rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs_f64();
+        if let Some((source, values)) = sources.samples(event, &schema) {
+            let mut capture = capture.lock().unwrap();
+            capture.push(Record { time, source, values });
+            job.set_status(&format!("{} records", capture.records().len()));
+        }
+    }
+    Ok(())
+}
+
+/// The state of the data export window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExportPanel {
+    pub sources: Sources,
+    pub format: ExportFormat,
+    /// Fields left out of exports, so fields seen for the first time are included
+    excluded: BTreeSet<String>,
+    resample: bool,
+    /// The resampling period in seconds, as typed, i.e. "100m"
+    period: String,
+    aggregation: Aggregation,
+    /// The InfluxDB measurement to write to
+    measurement: String,
+    #[serde(skip)]
+    capture: Arc<Mutex<Capture>>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl ExportPanel {
+
+    fn is_recording(&self, ctx: &egui::Context) -> bool {
+        self.job.is_some_and(|id| jobs::shared(ctx).state(id) == Some(JobState::Running))
+    }
+
+    /// Start recording the selected sources on a background job, so records are stamped
+    /// when they arrive rather than when the window is drawn.
+    pub fn start(&mut self, ctx: &egui::Context, schema: &MessageSchema) {
+        let rx = events::shared(ctx).subscribe();
+        let (sources, schema, capture) = (self.sources, schema.clone(), self.capture.clone());
+        self.job = Some(jobs::shared(ctx).spawn("telemetry recorder", Some(ctx), move |job| {
+            record(rx, sources, schema, &capture, job)
+        }));
+    }
+
+    pub fn stop(&mut self, ctx: &egui::Context) {
+        if let Some(id) = self.job.take() {
+            jobs::shared(ctx).cancel(id);
+        }
+    }
+
+    fn resampling(&self) -> Result<Option<Resampling>, String> {
+        if !self.resample {
+            return Ok(None);
+        }
+        match parse_si(&self.period)? {
+            period if period > 0.0 => Ok(Some(Resampling { period, aggregation: self.aggregation })),
+            _ => Err("the resampling period needs to be more than zero".to_string()),
+        }
+    }
+
+    fn export(&self, fields: &[String]) -> Result<Option<String>, String> {
+        let table = Table::new(self.capture.lock().unwrap().records(), fields, self.resampling()?);
+        let bytes = match self.format {
+            ExportFormat::Csv => to_csv(&table).into_bytes(),
+            ExportFormat::Parquet => to_parquet(&table)?,
+            ExportFormat::InfluxLine => {
+                let measurement = if self.measurement.trim().is_empty() { "telemetry" } else { self.measurement.trim() };
+                to_influx(&table, measurement).into_bytes()
+            },
+        };
+        let extension = self.format.extension();
+        let Some(path) = FileDialog::new().add_filter(self.format.to_string(), &[extension]).set_file_name(format!("telemetry.{}", extension)).save_file() else {
+            return Ok(None);
+        };
+        fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        Ok(Some(format!("wrote {} rows to {}", table.rows.len(), path.display())))
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, schema: &MessageSchema) {
+        let recording = self.is_recording(ctx);
+        ui.add_enabled_ui(!recording, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("record:");
+                ui.checkbox(&mut self.sources.serial_lines, "serial lines");
+                ui.checkbox(&mut self.sources.serial_messages, "serial messages");
+                ui.checkbox(&mut self.sources.network_telemetry, "network telemetry");
+            });
+        });
+        let (count, fields, span) = {
+            let capture = self.capture.lock().unwrap();
+            let records = capture.records();
+            let span = records.first().zip(records.last()).map_or(0.0, |(a, b)| b.time - a.time);
+            (records.len(), capture.fields().iter().cloned().collect::<Vec<_>>(), span)
+        };
+        ui.horizontal(|ui| {
+            if recording {
+                if ui.button("stop").clicked() {
+                    self.stop(ctx);
+                }
+            } else if ui.button("record").clicked() {
+                self.start(ctx, schema);
+            }
+            if ui.button("clear").clicked() {
+                self.capture.lock().unwrap().clear();
+            }
+            ui.label(format!("{} records over {:.1} s", count, span));
+        });
+        ui.separator();
+        ui.label("fields:");
+        egui::ScrollArea::vertical().max_height(140.0).show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for field in fields.iter() {
+                    let mut included = !self.excluded.contains(field);
+                    if ui.checkbox(&mut included, field).changed() {
+                        if included { self.excluded.remove(field); } else { self.excluded.insert(field.clone()); }
+                    }
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.resample, "resample every");
+            ui.add_enabled(self.resample, egui::TextEdit::singleline(&mut self.period).hint_text("100m").desired_width(60.0));
+            ui.label("s, taking the");
+            ui.add_enabled_ui(self.resample, |ui| {
+                egui::ComboBox::from_id_source("export_aggregation").selected_text(self.aggregation.to_string()).show_ui(ui, |ui| {
+                    for aggregation in enum_iterator::all::<Aggregation>() {
+                        ui.selectable_value(&mut self.aggregation, aggregation, aggregation.to_string());
+                    }
+                });
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.label("format:");
+            egui::ComboBox::from_id_source("export_format").selected_text(self.format.to_string()).show_ui(ui, |ui| {
+                for format in enum_iterator::all::<ExportFormat>() {
+                    ui.selectable_value(&mut self.format, format, format.to_string());
+                }
+            });
+            if self.format == ExportFormat::InfluxLine {
+                ui.label("measurement:");
+                ui.add(egui::TextEdit::singleline(&mut self.measurement).hint_text("telemetry").desired_width(100.0));
+            }
+        });
+        let selected: Vec<String> = fields.into_iter().filter(|f| !self.excluded.contains(f)).collect();
+        ui.horizontal(|ui| {
+            if ui.add_enabled(count > 0 && !selected.is_empty(), egui::Button::new("export...")).clicked() {
+                match self.export(&selected) {
+                    Ok(Some(message)) => self.message = Some(message),
+                    Ok(None) => (),
+                    Err(e) => self.message = Some(e),
+                }
+            }
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+        });
+    }
+}
@@ -0,0 +1,87 @@
+//! Title: Iron Coder App Module - Focus Mode
+//! Description: A distraction-free way of editing, where everything but the code editor
+//!   is put away and the code sits in a centered column with its surroundings dimmed.
+//!   The tool windows that were open are put back when focus mode is left.
+
+use egui::{Color32, Key, KeyboardShortcut, Modifiers, Rect};
+use serde::{Serialize, Deserialize};
+
+use crate::app::ToolWindows;
+use crate::app::code_editor::CodeEditor;
+
+/// Toggles focus mode.
+pub const SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::F);
+/// How wide the code column is, in characters of the editor font.
+pub const COLUMN_CHARS: f32 = 100.0;
+/// How much the surroundings of the column are darkened.
+const DIM_ALPHA: u8 = 96;
+
+/// Whether focus mode is on, and the layout to go back to when it's left. It's saved with
+/// the app, so quitting in focus mode doesn't lose the layout.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FocusMode {
+    /// The tool windows that were open before focus mode was turned on
+    saved: Option<ToolWindows>,
+}
+
+impl FocusMode {
+
+    pub fn is_on(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Put the tool windows away and remember which were open.
+    pub fn enter(&mut self, tool_windows: &mut ToolWindows) {
+        if self.saved.is_none() {
+            self.saved = Some(std::mem::take(tool_windows));
+        }
+    }
+
+    /// Open the tool windows that were open before.
+    pub fn exit(&mut self, tool_windows: &mut ToolWindows) {
+        if let Some(saved) = self.saved.take() {
+            *tool_windows = saved;
+        }
+    }
+
+    pub fn toggle(&mut self, tool_windows: &mut ToolWindows) {
+        if self.is_on() {
+            self.exit(tool_windows);
+        } else {
+            self.enter(tool_windows);
+        }
+    }
+}
+
+/// The centered column the code goes in, `char_width` being the width of a character in
+/// the editor font. It takes the whole width when there isn't room for it.
+pub fn column_rect(available: Rect, char_width: f32) -> Rect {
+    let width = (COLUMN_CHARS * char_width).min(available.width());
+    Rect::from_center_size(available.center(), egui::vec2(width, available.height()))
+}
+
+/// Show the code editor on its own, in a column in the middle of the window.
+pub fn display_editor(ctx: &egui::Context, code_editor: &mut CodeEditor) {
+    let frame = egui::Frame::canvas(&ctx.style());
+    egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+        let font = egui::TextStyle::Name("EditorFont".into()).resolve(ui.style());
+        let char_width = ui.fonts(|f| f.glyph_width(&font, '0'));
+        let available = ui.max_rect();
+        let column = column_rect(available, char_width);
+        let dim = Color32::from_black_alpha(DIM_ALPHA);
+        ui.painter().rect_filled(Rect::from_min_max(available.min, egui::pos2(column.min.x, available.max.y)), 0.0, dim);
+        ui.painter().rect_filled(Rect::from_min_max(egui::pos2(column.max.x, available.min.y), available.max), 0.0, dim);
+        ui.allocate_ui_at_rect(column, |ui| {
+            code_editor.display_code(ctx, ui);
+        });
+        let hint = format!("focus mode, {} to leave", ctx.format_shortcut(&SHORTCUT));
+        ui.painter().text(
+            available.right_bottom() - egui::vec2(8.0, 8.0),
+            egui::Align2::RIGHT_BOTTOM,
+            hint,
+            egui::TextStyle::Small.resolve(ui.style()),
+            ui.visuals().weak_text_color(),
+        );
+    });
+}
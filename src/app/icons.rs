@@ -39,7 +39,7 @@ pub fn load_icons(icon_path: &Path) -> HashMap<&'static str, Image> {
         ("right_arrow_icon", "005b_53.gif"),
     ];
     for (icon_name, icon_file) in icon_names_and_files.into_iter() {
-        let p = icon_path.join(icon_file).as_path().to_str().unwrap().to_string();
+        let p = icon_path.join(icon_file).to_string_lossy().to_string();
         // attempt to open the icon image file
         let image = Image::new(p).fit_to_exact_size(DEFAULT_ICON_SIZE);
         icon_map.insert(icon_name, image);
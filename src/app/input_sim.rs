@@ -0,0 +1,345 @@
+//! Title: Iron Coder App Module - Input Simulator
+//! Description: Stand-ins for the touch screens and rotary encoders of the system's
+//!   boards, so UI firmware can be worked on without the hardware. Touches and turns
+//!   are sent to the board as text lines over the serial monitor's port, and a module
+//!   written to the project's `src` directory turns those lines back into input events
+//!   for the firmware's input layer.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::input::InputDevice;
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "input_sim.rs";
+/// The word every simulator line starts with, so the firmware can tell them apart from
+/// other traffic on the port.
+const LINE_PREFIX: &str = "sim";
+/// The most sent lines kept for display.
+const MAX_SENT: usize = 12;
+/// How far the knob is dragged for one step, in points.
+const DRAG_PER_STEP: f32 = 12.0;
+
+/// An input event, as the firmware sees it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimEvent {
+    TouchDown { x: u16, y: u16 },
+    TouchMove { x: u16, y: u16 },
+    TouchUp,
+    /// The encoder turned by some steps, clockwise if positive
+    Turn(i16),
+    Press,
+    Release,
+}
+
+impl fmt::Display for SimEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimEvent::TouchDown { x, y } => write!(f, "{} touch down {} {}", LINE_PREFIX, x, y),
+            SimEvent::TouchMove { x, y } => write!(f, "{} touch move {} {}", LINE_PREFIX, x, y),
+            SimEvent::TouchUp => write!(f, "{} touch up", LINE_PREFIX),
+            SimEvent::Turn(steps) => write!(f, "{} encoder {:+}", LINE_PREFIX, steps),
+            SimEvent::Press => write!(f, "{} encoder press", LINE_PREFIX),
+            SimEvent::Release => write!(f, "{} encoder release", LINE_PREFIX),
+        }
+    }
+}
+
+impl SimEvent {
+
+    /// Read an event from a line, the same way the generated module does.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_ascii_whitespace();
+        if words.next()? != LINE_PREFIX {
+            return None;
+        }
+        let event = match (words.next()?, words.next()?) {
+            ("touch", "down") => SimEvent::TouchDown { x: words.next()?.parse().ok()?, y: words.next()?.parse().ok()? },
+            ("touch", "move") => SimEvent::TouchMove { x: words.next()?.parse().ok()?, y: words.next()?.parse().ok()? },
+            ("touch", "up") => SimEvent::TouchUp,
+            ("encoder", "press") => SimEvent::Press,
+            ("encoder", "release") => SimEvent::Release,
+            ("encoder", steps) => SimEvent::Turn(steps.parse().ok()?),
+            _ => return None,
+        };
+        words.next().is_none().then_some(event)
+    }
+}
+
+/// The firmware side: a no_std parser for the simulator's lines.
+pub const MODULE: &str =
+"//! Input events from Iron Coder's input simulator, sent as text lines over the serial
+//! port. Feed the bytes from the port to a `LineReader`, and hand the events it returns
+//! to the input layer in place of the touch controller's or encoder's.
+//!
+//! ```ignore
+//! let mut reader = input_sim::LineReader::<32>::new();
+//! if let Some(event) = reader.push(byte) {
+//!     ui.handle(event);
+//! }
+//! ```
+
+#![allow(dead_code)]
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimEvent {
+    TouchDown { x: u16, y: u16 },
+    TouchMove { x: u16, y: u16 },
+    TouchUp,
+    /// The encoder turned by some steps, clockwise if positive
+    Turn(i16),
+    Press,
+    Release,
+}
+
+/// Read an event from a line without its newline. Other lines give None.
+pub fn parse(line: &str) -> Option<SimEvent> {
+    let mut words = line.split_ascii_whitespace();
+    if words.next()? != \"sim\" {
+        return None;
+    }
+    let event = match (words.next()?, words.next()?) {
+        (\"touch\", \"down\") => SimEvent::TouchDown { x: words.next()?.parse().ok()?, y: words.next()?.parse().ok()? },
+        (\"touch\", \"move\") => SimEvent::TouchMove { x: words.next()?.parse().ok()?, y: words.next()?.parse().ok()? },
+        (\"touch\", \"up\") => SimEvent::TouchUp,
+        (\"encoder\", \"press\") => SimEvent::Press,
+        (\"encoder\", \"release\") => SimEvent::Release,
+        (\"encoder\", steps) => SimEvent::Turn(steps.parse().ok()?),
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(event)
+}
+
+/// Collects bytes into lines of up to N bytes, and parses them. Longer lines are dropped.
+pub struct LineReader<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<const N: usize> LineReader<N> {
+    pub const fn new() -> Self {
+        Self { buffer: [0; N], len: 0, overflowed: false }
+    }
+
+    /// Take in a byte, returning the event it ends, if any.
+    pub fn push(&mut self, byte: u8) -> Option<SimEvent> {
+        match byte {
+            b'\\n' => {
+                let event = if self.overflowed {
+                    None
+                } else {
+                    core::str::from_utf8(&self.buffer[..self.len]).ok().and_then(parse)
+                };
+                self.len = 0;
+                self.overflowed = false;
+                event
+            },
+            b'\\r' => None,
+            byte if self.len < N => {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+                None
+            },
+            _ => {
+                self.overflowed = true;
+                None
+            },
+        }
+    }
+}
+";
+
+/// Write the parser module to the project's `src` directory.
+pub fn write_module(project_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(project_dir.join("src").join(MODULE_FILE_NAME), MODULE)
+}
+
+/// The point on a touch screen `width` by `height` pixels under `pos`, in a drawing of it
+/// that fills `rect`.
+pub fn touch_point(rect: egui::Rect, pos: egui::Pos2, width: u16, height: u16) -> (u16, u16) {
+    let scale = |t: f32, size: u16| (t.clamp(0.0, 1.0) * size as f32).min(size as f32 - 1.0) as u16;
+    let t = (pos - rect.min) / rect.size();
+    (scale(t.x, width), scale(t.y, height))
+}
+
+/// The state of the input simulator window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct InputSimPanel {
+    /// The serial port the lines go to, and the file it's open as
+    #[serde(skip)]
+    port: Option<(String, fs::File)>,
+    #[serde(skip)]
+    sent: VecDeque<String>,
+    /// Where each board's screen is being touched
+    #[serde(skip)]
+    touches: BTreeMap<String, (u16, u16)>,
+    /// The steps each board's encoder has turned through
+    #[serde(skip)]
+    positions: BTreeMap<String, i64>,
+    /// How far the knob has been dragged towards its next step
+    #[serde(skip)]
+    drag: f32,
+    /// Whether each board's encoder button is held down
+    #[serde(skip)]
+    pressed: BTreeMap<String, bool>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl InputSimPanel {
+
+    /// Send an event down `port`, opening it if it isn't open already.
+    fn send(&mut self, port: &str, event: SimEvent) {
+        let line = event.to_string();
+        let result = (|| -> io::Result<()> {
+            if self.port.as_ref().is_none_or(|(open, _)| open != port) {
+                self.port = Some((port.to_string(), fs::OpenOptions::new().write(true).open(port)?));
+            }
+            let (_, file) = self.port.as_mut().unwrap();
+            file.write_all(format!("{}\n", line).as_bytes())?;
+            file.flush()
+        })();
+        match result {
+            Ok(()) => self.error = None,
+            Err(e) => {
+                self.port = None;
+                self.error = Some(format!("couldn't send to {}: {}", port, e));
+            },
+        }
+        self.sent.push_back(line);
+        if self.sent.len() > MAX_SENT {
+            self.sent.pop_front();
+        }
+    }
+
+    fn display_touch(&mut self, ui: &mut egui::Ui, board: &str, (width, height): (u16, u16), port: &str) {
+        let fit = (ui.available_width().min(480.0) / width as f32).min(360.0 / height as f32);
+        let size = egui::vec2(width as f32, height as f32) * fit;
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+        let rect = response.rect;
+        let visuals = ui.visuals().clone();
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+        painter.rect_stroke(rect, 2.0, visuals.window_stroke);
+        let touching = self.touches.get(board).copied();
+        let down = response.is_pointer_button_down_on();
+        match (down, touching, response.interact_pointer_pos()) {
+            (true, None, Some(pos)) => {
+                let (x, y) = touch_point(rect, pos, width, height);
+                self.touches.insert(board.to_string(), (x, y));
+                self.send(port, SimEvent::TouchDown { x, y });
+            },
+            (true, Some(last), Some(pos)) => {
+                let (x, y) = touch_point(rect, pos, width, height);
+                if (x, y) != last {
+                    self.touches.insert(board.to_string(), (x, y));
+                    self.send(port, SimEvent::TouchMove { x, y });
+                }
+            },
+            (false, Some(_), _) => {
+                self.touches.remove(board);
+                self.send(port, SimEvent::TouchUp);
+            },
+            _ => (),
+        }
+        if let Some((x, y)) = self.touches.get(board) {
+            let at = rect.min + egui::vec2(*x as f32 + 0.5, *y as f32 + 0.5) * fit;
+            painter.circle_stroke(at, 10.0, egui::Stroke::new(2.0, visuals.selection.bg_fill));
+            painter.text(rect.left_top() + egui::vec2(4.0, 4.0), egui::Align2::LEFT_TOP, format!("{}, {}", x, y), egui::FontId::monospace(11.0), visuals.text_color());
+        }
+    }
+
+    fn display_encoder(&mut self, ui: &mut egui::Ui, board: &str, steps_per_turn: u16, button: bool, port: &str) {
+        let position = self.positions.get(board).copied().unwrap_or_default();
+        let mut turned = 0;
+        ui.horizontal(|ui| {
+            if ui.button("⟲").clicked() {
+                turned -= 1;
+            }
+            let (response, painter) = ui.allocate_painter(egui::vec2(64.0, 64.0), egui::Sense::drag());
+            let center = response.rect.center();
+            let visuals = ui.visuals();
+            painter.circle(center, 28.0, visuals.extreme_bg_color, visuals.window_stroke);
+            let angle = position as f32 / steps_per_turn as f32 * std::f32::consts::TAU;
+            let tip = center + egui::vec2(angle.sin(), -angle.cos()) * 22.0;
+            painter.line_segment([center, tip], egui::Stroke::new(3.0, visuals.selection.bg_fill));
+            // dragging sideways turns the knob, a step at a time
+            if response.dragged() {
+                self.drag += response.drag_delta().x;
+                let steps = (self.drag / DRAG_PER_STEP).trunc();
+                self.drag -= steps * DRAG_PER_STEP;
+                turned += steps as i16;
+            }
+            let response = response.on_hover_text("drag sideways to turn");
+            let scroll = if response.hovered() { ui.input(|i| i.raw_scroll_delta.y) } else { 0.0 };
+            turned += scroll.signum() as i16;
+            if ui.button("⟳").clicked() {
+                turned += 1;
+            }
+            ui.label(format!("step {}", position.rem_euclid(steps_per_turn as i64)));
+            if button {
+                let push = ui.add(egui::Button::new("push").sense(egui::Sense::drag()));
+                let held = push.is_pointer_button_down_on();
+                if held != self.pressed.get(board).copied().unwrap_or_default() {
+                    self.pressed.insert(board.to_string(), held);
+                    self.send(port, if held { SimEvent::Press } else { SimEvent::Release });
+                }
+            }
+        });
+        if turned != 0 {
+            self.positions.insert(board.to_string(), position + turned as i64);
+            self.send(port, SimEvent::Turn(turned));
+        }
+    }
+
+    /// Show a simulator for each of `boards` with an input device, sending to `port`.
+    pub fn display(&mut self, ui: &mut egui::Ui, boards: &[Board], port: &str, project_dir: Option<&Path>) {
+        let inputs: Vec<(&str, InputDevice)> = boards.iter().filter_map(|b| b.input().map(|i| (b.get_name(), i))).collect();
+        if inputs.is_empty() {
+            ui.label("add a board with a touch screen or rotary encoder to the system");
+            return;
+        }
+        ui.horizontal(|ui| {
+            if port.is_empty() {
+                ui.colored_label(ui.visuals().warn_fg_color, "pick a port in the serial monitor to send to");
+            } else {
+                ui.label(format!("sending to {}", port));
+            }
+            if ui.add_enabled(project_dir.is_some(), egui::Button::new(format!("write src/{}", MODULE_FILE_NAME))).clicked() {
+                if let Err(e) = write_module(project_dir.unwrap()) {
+                    self.error = Some(format!("couldn't write the module: {}", e));
+                }
+            }
+        });
+        if let Some(e) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        for (board, input) in inputs {
+            ui.separator();
+            ui.strong(board);
+            ui.add_enabled_ui(!port.is_empty(), |ui| match input {
+                InputDevice::Touch { width, height } => self.display_touch(ui, board, (width, height), port),
+                InputDevice::Encoder { steps_per_turn, button } => self.display_encoder(ui, board, steps_per_turn, button, port),
+            });
+        }
+        if !self.sent.is_empty() {
+            ui.separator();
+            ui.collapsing("sent", |ui| {
+                for line in &self.sent {
+                    ui.monospace(line);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Title: Iron Coder App Module - Jobs
+//! Description: This module defines the JobScheduler, which runs long-running work
+//!   (builds, flashes, fetches, scans) on background threads. Each job can report its
+//!   progress and be cancelled from the jobs panel, so the UI thread never blocks.
+
+use log::{info, warn};
+
+use std::any::Any;
+use std::io::BufRead;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// The key used to store the shared scheduler in the egui Context temp data.
+pub const JOBS_ID: &str = "jobs";
+
+pub type JobId = usize;
+
+/// The lifecycle of a job.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobState {
+    Running,
+    Finished,
+    Failed(String),
+    Cancelled,
+}
+
+/// A handle passed to the work function of a job, used to report progress and
+/// to check whether the user has asked to cancel it.
+#[derive(Clone)]
+pub struct JobContext {
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Mutex<Option<f32>>>,
+    status: Arc<Mutex<String>>,
+    egui_ctx: Option<egui::Context>,
+}
+
+impl JobContext {
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Report the fraction of work completed, from 0.0 to 1.0.
+    pub fn set_progress(&self, progress: f32) {
+        *self.progress.lock().unwrap() = Some(progress.clamp(0.0, 1.0));
+        self.request_repaint();
+    }
+
+    /// Report a short, human-readable description of what the job is doing.
+    pub fn set_status(&self, status: &str) {
+        *self.status.lock().unwrap() = status.to_string();
+        self.request_repaint();
+    }
+
+    pub fn request_repaint(&self) {
+        if let Some(ctx) = &self.egui_ctx {
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// The scheduler's record of a job.
+struct JobEntry {
+    id: JobId,
+    name: String,
+    state: Arc<Mutex<JobState>>,
+    context: JobContext,
+    started: Instant,
+    finished: Arc<Mutex<Option<Instant>>>,
+}
+
+/// A snapshot of a job, for display purposes.
+#[derive(Clone, Debug)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    pub state: JobState,
+    pub progress: Option<f32>,
+    pub status: String,
+    pub elapsed: Duration,
+}
+
+/// The JobScheduler is a cheaply cloneable handle; all clones share the same job list.
+#[derive(Clone, Default)]
+pub struct JobScheduler {
+    jobs: Arc<Mutex<Vec<JobEntry>>>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl JobScheduler {
+
+    /// Run `work` on a background thread. The returned JobId can be used to query
+    /// or cancel the job.
+    pub fn spawn<F>(&self, name: &str, egui_ctx: Option<&egui::Context>, work: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> Result<(), String> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let context = JobContext {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(Mutex::new(None)),
+            status: Arc::new(Mutex::new(String::new())),
+            egui_ctx: egui_ctx.cloned(),
+        };
+        let state = Arc::new(Mutex::new(JobState::Running));
+        let finished = Arc::new(Mutex::new(None));
+        self.jobs.lock().unwrap().push(JobEntry {
+            id,
+            name: name.to_string(),
+            state: state.clone(),
+            context: context.clone(),
+            started: Instant::now(),
+            finished: finished.clone(),
+        });
+        info!("starting job {} <{}>", id, name);
+        let name = name.to_string();
+        let _ = std::thread::spawn(move || {
+            // a job that panics has failed, rather than running forever
+            let result = panic::catch_unwind(AssertUnwindSafe(|| work(&context))).unwrap_or_else(|e| Err(panic_message(&*e)));
+            let final_state = match result {
+                _ if context.is_cancelled() => JobState::Cancelled,
+                Ok(()) => JobState::Finished,
+                Err(e) => {
+                    warn!("job {} <{}> failed: {}", id, name, e);
+                    JobState::Failed(e)
+                },
+            };
+            info!("job {} <{}> ended with state {:?}", id, name, final_state);
+            *state.lock().unwrap() = final_state;
+            *finished.lock().unwrap() = Some(Instant::now());
+            context.request_repaint();
+        });
+        id
+    }
+
+    /// Ask a job to stop. The job's work function is responsible for honoring this.
+    pub fn cancel(&self, id: JobId) {
+        if let Some(job) = self.jobs.lock().unwrap().iter().find(|j| j.id == id) {
+            info!("cancelling job {} <{}>", id, job.name);
+            job.context.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn state(&self, id: JobId) -> Option<JobState> {
+        self.jobs.lock().unwrap().iter().find(|j| j.id == id).map(|j| {
+            j.state.lock().unwrap().clone()
+        })
+    }
+
+    /// Return a snapshot of all the jobs known to the scheduler.
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.jobs.lock().unwrap().iter().map(|j| {
+            let end = j.finished.lock().unwrap().unwrap_or_else(Instant::now);
+            JobInfo {
+                id: j.id,
+                name: j.name.clone(),
+                state: j.state.lock().unwrap().clone(),
+                progress: *j.context.progress.lock().unwrap(),
+                status: j.context.status.lock().unwrap().clone(),
+                elapsed: end - j.started,
+            }
+        }).collect()
+    }
+
+    pub fn num_running(&self) -> usize {
+        self.jobs.lock().unwrap().iter().filter(|j| {
+            *j.state.lock().unwrap() == JobState::Running
+        }).count()
+    }
+
+    /// Forget about all the jobs that are no longer running.
+    pub fn clear_finished(&self) {
+        self.jobs.lock().unwrap().retain(|j| *j.state.lock().unwrap() == JobState::Running);
+    }
+}
+
+/// What a job panicked with, for its failed state.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => format!("panicked: {}", message),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => format!("panicked: {}", message),
+            None => "panicked".to_string(),
+        },
+    }
+}
+
+/// Fetch the app-wide job scheduler from the egui Context, creating it if needed.
+pub fn shared(ctx: &egui::Context) -> JobScheduler {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_insert_with(JOBS_ID.into(), JobScheduler::default).clone()
+    })
+}
+
+/// Run a series of commands sequentially, sending each line of output through the
+/// provided channel. If the job is cancelled, the running command is killed.
+pub fn run_commands(cmds: &[duct::Expression], tx: &Sender<String>, job: &JobContext) -> Result<(), String> {
+    for (i, cmd) in cmds.iter().enumerate() {
+        if job.is_cancelled() {
+            return Err("cancelled".to_string());
+        }
+        job.set_progress(i as f32 / cmds.len() as f32);
+        let reader = match cmd.stderr_to_stdout().unchecked().reader() {
+            Ok(reader) => Arc::new(reader),
+            Err(e) => return Err(format!("couldn't start command: {}", e)),
+        };
+        // watch for cancellation on a separate thread, since reading output blocks
+        let done = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (reader, done, job) = (reader.clone(), done.clone(), job.clone());
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    if job.is_cancelled() {
+                        let _ = reader.kill();
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            })
+        };
+        for line in std::io::BufReader::new(&*reader).lines() {
+            match line {
+                Ok(line) => {
+                    job.set_status(&line);
+                    // the receiving end may have been dropped, that's ok
+                    let _ = tx.send(line + "\n");
+                },
+                Err(_) => break,
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+        let _ = watcher.join();
+        if let Ok(Some(output)) = reader.try_wait() {
+            if !output.status.success() && !job.is_cancelled() {
+                return Err(format!("command exited with {}", output.status));
+            }
+        }
+    }
+    job.set_progress(1.0);
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Title: Iron Coder App Module - Manufacturer Logos
+//! Description: This module defines the LogoSet, the manufacturer logos that are loaded
+//!   once at startup and shared through the egui Context like the icons. Anywhere a
+//!   board's manufacturer is shown can use its logo, and show the name of manufacturers
+//!   without one.
+
+use log::{info, warn};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use egui::{ColorImage, Vec2};
+
+use crate::app::texture_cache;
+
+/// The key used to store the logos in the egui Context temp data.
+pub const LOGOS_ID: &str = "manufacturer_logos";
+pub const LOGO_DIR: &str = "assets/images";
+/// The height logos are shown at, next to text.
+pub const LOGO_HEIGHT: f32 = 16.0;
+
+/// The logo of each manufacturer, as written in the board files.
+const LOGO_FILES: [(&str, &str); 1] = [
+    ("Adafruit", "Adafruit_logo_small.png"),
+];
+
+/// The decoded logos, keyed by manufacturer.
+#[derive(Default)]
+pub struct LogoSet {
+    logos: HashMap<String, Arc<ColorImage>>,
+}
+
+impl LogoSet {
+
+    /// The logo of a manufacturer. Names are matched in any case, so "SparkFun" and
+    /// "Sparkfun" are the same manufacturer.
+    pub fn get(&self, manufacturer: &str) -> Option<Arc<ColorImage>> {
+        self.logos.get(&manufacturer.trim().to_lowercase()).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.logos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.logos.is_empty()
+    }
+}
+
+/// Decode the manufacturer logos in `logo_dir`. Logos that can't be read are left out,
+/// so their manufacturers are shown by name.
+pub fn load_logos(logo_dir: &Path) -> LogoSet {
+    let mut logos = HashMap::new();
+    for (manufacturer, file) in LOGO_FILES {
+        let path = logo_dir.join(file);
+        let image = match image::open(&path) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                warn!("couldn't load the {} logo from {}: {:?}", manufacturer, path.display(), e);
+                continue;
+            },
+        };
+        let size = [image.width() as usize, image.height() as usize];
+        let color_image = ColorImage::from_rgba_unmultiplied(size, image.as_flat_samples().as_slice());
+        logos.insert(manufacturer.to_lowercase(), Arc::new(color_image));
+    }
+    info!("loaded {} manufacturer logos", logos.len());
+    LogoSet { logos }
+}
+
+/// A manufacturer's logo from the app-wide logos, if it has one.
+pub fn logo(ctx: &egui::Context, manufacturer: &str) -> Option<Arc<ColorImage>> {
+    let logos: Option<Arc<LogoSet>> = ctx.data(|data| data.get_temp(LOGOS_ID.into()));
+    logos?.get(manufacturer)
+}
+
+/// Show a manufacturer's logo, tinted to the text color so it suits the color scheme.
+/// Returns None without showing anything if there's no logo for the manufacturer, so
+/// the caller can show its name instead.
+pub fn show(ui: &mut egui::Ui, manufacturer: &str, height: f32) -> Option<egui::Response> {
+    let logo = logo(ui.ctx(), manufacturer)?;
+    let cache = texture_cache::shared(ui.ctx());
+    let key = format!("{}#logo", manufacturer.trim().to_lowercase());
+    let handle = cache.lock().unwrap().get_or_load(ui.ctx(), &key, || (*logo).clone());
+    let [w, h] = logo.size;
+    let size = Vec2::new(w as f32 * height / h.max(1) as f32, height);
+    let image = egui::Image::from_texture(&handle).fit_to_exact_size(size).tint(ui.visuals().text_color());
+    Some(ui.add(image).on_hover_text(manufacturer))
+}
@@ -6,6 +6,7 @@
 
 use log::{error, warn, info};
 
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use clap::Parser;
@@ -19,6 +20,52 @@ use toml::macros::insert_toml;
 // Separate modules
 use crate::board;
 use crate::project::Project;
+use crate::project::compat;
+use crate::project::vcs;
+use crate::project::local_sources::{self, LocalSource};
+use crate::project::example;
+use crate::project::dfu::DfuPanel;
+use crate::project::fixture::FixturePanel;
+use crate::project::forwarding::ForwardingPanel;
+use crate::project::instruments::InstrumentPanel;
+use crate::project::embed::EmbedPanel;
+use crate::project::fonts::FontPanel;
+use crate::project::localization::LocalizationPanel;
+use crate::project::power::PowerPanel;
+use crate::project::pins::PinsPanel;
+use crate::project::cpu_usage::CpuUsagePanel;
+use crate::project::heap_usage::HeapUsagePanel;
+use crate::project::panic_dump::PanicDumpPanel;
+use crate::project::storage::StoragePanel;
+use crate::project::config_partition::ConfigPartitionPanel;
+use crate::project::partition_table::{self, PartitionTablePanel};
+use crate::project::gatt::GattPanel;
+use crate::project::lorawan::LoraWanPanel;
+use crate::project::mcumgr::ImagePanel;
+use crate::project::messages::MessagesPanel;
+use crate::project::state_machine::StateMachinePanel;
+use devices::DevicesPanel;
+use toolbox::ToolboxPanel;
+use signals::SignalsPanel;
+use export::ExportPanel;
+use assistant::AssistantPanel;
+use watchdog::WatchdogPanel;
+use rtc::RtcPanel;
+use trace::TracePanel;
+use input_sim::InputSimPanel;
+use detached::DetachedWindows;
+use focus::FocusMode;
+use overlay::SerialOverlay;
+use search::{SearchPanel, SearchTarget};
+use crate::board::wizard::BoardWizard;
+use crate::board::registry::RegistryPanel;
+use crate::board::diagnostics::BoardDiagnostic;
+use crate::board::export::ExportFormat;
+use crate::board::bundle::BoardBundle;
+use crate::board::favorites::BoardFavorites;
+use crate::board::sorting::BoardSorting;
+use crate::board::quickstart::QuickStartWindow;
+use crate::board::watcher::BoardWatcher;
 
 pub mod icons;
 use icons::{
@@ -30,8 +77,83 @@ pub mod colorscheme;
 use colorscheme::ColorScheme;
 
 pub mod code_editor;
+pub mod preview;
+pub mod ansi;
+pub mod texture_cache;
+pub mod logos;
+pub mod jobs;
+use jobs::JobState;
+pub mod events;
+pub mod serial_monitor;
+pub mod devices;
+pub mod toolbox;
+pub mod signals;
+pub mod export;
+pub mod assistant;
+pub mod watchdog;
+pub mod rtc;
+pub mod trace;
+pub mod input_sim;
+pub mod detached;
+pub mod focus;
+pub mod overlay;
+pub mod search;
+use serial_monitor::SerialMonitor;
 mod test;
 
+/// Where the boards are loaded from, and where new boards are added
+const BOARDS_DIR: &str = "./iron-coder-boards";
+
+/// The app's actions that can be searched for, besides opening the tool windows
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Command {
+    SaveProject,
+    OpenProject,
+    FocusMode,
+    Settings,
+    About,
+    ReloadBoards,
+    ExportBoardCatalog,
+    ImportBoardBundle,
+    NewFile,
+    NewFolder,
+}
+
+impl Command {
+    const ALL: [Command; 10] = [
+        Command::SaveProject,
+        Command::OpenProject,
+        Command::FocusMode,
+        Command::Settings,
+        Command::About,
+        Command::ReloadBoards,
+        Command::ExportBoardCatalog,
+        Command::ImportBoardBundle,
+        Command::NewFile,
+        Command::NewFolder,
+    ];
+
+    /// The command's name in the menus and the search
+    fn name(self) -> &'static str {
+        match self {
+            Command::SaveProject => "save project",
+            Command::OpenProject => "open project",
+            Command::FocusMode => "focus mode",
+            Command::Settings => "settings",
+            Command::About => "about Iron Coder",
+            Command::ReloadBoards => "reload boards",
+            Command::ExportBoardCatalog => "export board catalog",
+            Command::ImportBoardBundle => "import board bundle",
+            Command::NewFile => "new file",
+            Command::NewFolder => "new folder",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.name() == name)
+    }
+}
+
 /// Iron Coder CLI configuration options...
 #[derive(Parser, Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 #[command(version)]
@@ -45,6 +167,23 @@ pub struct IronCoderOptions {
     /// Turn app persistence on or off. Default is true.
     #[arg(short, long)]
     pub persistence: Option<bool>,
+    /// Write the boards to stdout in this format and exit, without opening the app.
+    #[arg(long, value_name = "FORMAT")]
+    pub export_boards: Option<ExportFormat>,
+    /// Write the board in a bundle into the boards directory and exit, without opening
+    /// the app. A board that's already there is replaced.
+    #[arg(long, value_name = "FILE")]
+    pub import_board: Option<PathBuf>,
+    /// Serve the boards as JSON on http://127.0.0.1:<PORT>/boards while the app runs.
+    #[arg(long, value_name = "PORT")]
+    pub serve_boards: Option<u16>,
+}
+
+impl IronCoderOptions {
+    /// Where to look for the boards, which is the given directory if there is one.
+    pub fn boards_dir(&self) -> PathBuf {
+        self.boards_directory.clone().unwrap_or_else(|| PathBuf::from(BOARDS_DIR))
+    }
 }
 
 // The current warning flags
@@ -68,12 +207,110 @@ pub struct Git {
     pub commit_message: String,
     #[serde(skip)]
     pub repo : Option<git2::Repository>,
+    /// The project's submodules and path dependencies, when the window was opened
+    #[serde(skip)]
+    pub local_sources: Vec<LocalSource>,
+}
+
+// The currently open tool windows
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+pub struct ToolWindows {
+    pub display_performance_hud: bool,
+    pub display_jobs: bool,
+    pub display_serial_monitor: bool,
+    pub display_ble_dfu: bool,
+    pub display_lorawan: bool,
+    pub display_device_images: bool,
+    pub display_devices: bool,
+    pub display_gatt_preview: bool,
+    pub display_toolbox: bool,
+    pub display_signals: bool,
+    pub display_state_machines: bool,
+    pub display_messages: bool,
+    pub display_export: bool,
+    pub display_forwarding: bool,
+    pub display_fixture: bool,
+    pub display_instruments: bool,
+    pub display_assistant: bool,
+    pub display_assets: bool,
+    pub display_fonts: bool,
+    pub display_translations: bool,
+    pub display_watchdog: bool,
+    pub display_rtc: bool,
+    pub display_power: bool,
+    pub display_cpu_usage: bool,
+    pub display_heap_usage: bool,
+    pub display_panic_dump: bool,
+    pub display_storage: bool,
+    pub display_config_partition: bool,
+    pub display_partition_table: bool,
+    pub display_trace: bool,
+    pub display_input_sim: bool,
+    pub display_pinout: bool,
+    pub display_board_wizard: bool,
+    pub display_serial_overlay: bool,
+    pub display_board_diagnostics: bool,
+    pub display_board_registry: bool,
+    pub display_pins: bool,
+}
+
+impl ToolWindows {
+
+    /// The tool windows by their names in the tools menu, for opening them from elsewhere
+    pub fn by_name(&mut self) -> Vec<(&'static str, &mut bool)> {
+        vec![
+            ("performance HUD", &mut self.display_performance_hud),
+            ("jobs", &mut self.display_jobs),
+            ("serial monitor", &mut self.display_serial_monitor),
+            ("serial overlay", &mut self.display_serial_overlay),
+            ("BLE firmware update", &mut self.display_ble_dfu),
+            ("LoRaWAN", &mut self.display_lorawan),
+            ("device images", &mut self.display_device_images),
+            ("network devices", &mut self.display_devices),
+            ("BLE GATT preview", &mut self.display_gatt_preview),
+            ("toolbox", &mut self.display_toolbox),
+            ("signal viewer", &mut self.display_signals),
+            ("state machines", &mut self.display_state_machines),
+            ("message schemas", &mut self.display_messages),
+            ("data export", &mut self.display_export),
+            ("live forwarding", &mut self.display_forwarding),
+            ("test fixture", &mut self.display_fixture),
+            ("instruments", &mut self.display_instruments),
+            ("assistant", &mut self.display_assistant),
+            ("firmware assets", &mut self.display_assets),
+            ("bitmap fonts", &mut self.display_fonts),
+            ("translations", &mut self.display_translations),
+            ("watchdog", &mut self.display_watchdog),
+            ("device clock", &mut self.display_rtc),
+            ("low-power planner", &mut self.display_power),
+            ("cpu usage", &mut self.display_cpu_usage),
+            ("heap usage", &mut self.display_heap_usage),
+            ("last panic", &mut self.display_panic_dump),
+            ("storage", &mut self.display_storage),
+            ("config partition", &mut self.display_config_partition),
+            ("partition table", &mut self.display_partition_table),
+            ("trace", &mut self.display_trace),
+            ("input simulator", &mut self.display_input_sim),
+            ("pinout", &mut self.display_pinout),
+            ("pin assignments", &mut self.display_pins),
+            ("new board", &mut self.display_board_wizard),
+            ("board diagnostics", &mut self.display_board_diagnostics),
+            ("board registry", &mut self.display_board_registry),
+        ]
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Settings {
     pub colorscheme: ColorScheme,
     pub ui_scale: f32,
+    /// The boards starred in the board selector
+    #[serde(default)]
+    pub board_favorites: BoardFavorites,
+    /// The order of the board selector, and the boards picked in it most recently
+    #[serde(default)]
+    pub board_sorting: BoardSorting,
 }
 
 /// The current GUI mode
@@ -97,18 +334,69 @@ pub struct IronCoderApp {
     mode: Mode,
     #[serde(skip)]
     boards: Vec<board::Board>,
+    /// What's wrong with the board files, found when they were loaded
+    #[serde(skip)]
+    board_diagnostics: Vec<BoardDiagnostic>,
     options: IronCoderOptions,
 
     warning_flags: Warnings,
     git_things: Git,
     settings: Settings,
+    tool_windows: ToolWindows,
+    serial_monitor: SerialMonitor,
+    ble_dfu: DfuPanel,
+    lorawan: LoraWanPanel,
+    device_images: ImagePanel,
+    devices: DevicesPanel,
+    gatt_preview: GattPanel,
+    toolbox: ToolboxPanel,
+    signals: SignalsPanel,
+    state_machines: StateMachinePanel,
+    messages: MessagesPanel,
+    export: ExportPanel,
+    forwarding: ForwardingPanel,
+    fixture: FixturePanel,
+    instruments: InstrumentPanel,
+    assistant: AssistantPanel,
+    assets: EmbedPanel,
+    fonts: FontPanel,
+    translations: LocalizationPanel,
+    watchdog: WatchdogPanel,
+    rtc: RtcPanel,
+    power: PowerPanel,
+    pins: PinsPanel,
+    cpu_usage: CpuUsagePanel,
+    heap_usage: HeapUsagePanel,
+    panic_dump: PanicDumpPanel,
+    storage: StoragePanel,
+    config_partition: ConfigPartitionPanel,
+    partition_table: PartitionTablePanel,
+    trace: TracePanel,
+    input_sim: InputSimPanel,
+    detached_windows: DetachedWindows,
+    focus: FocusMode,
+    board_wizard: BoardWizard,
+    board_registry: RegistryPanel,
+    serial_overlay: SerialOverlay,
+    #[serde(skip)]
+    search: SearchPanel,
+    quick_start: QuickStartWindow,
+    /// The app events the app itself acts on, i.e. examples picked in a board widget
+    #[serde(skip)]
+    events: Option<std::sync::mpsc::Receiver<events::Event>>,
+    /// Reloads the boards when their files change
+    #[serde(skip)]
+    board_watcher: Option<BoardWatcher>,
+    /// The board directories watched, or that couldn't be
+    #[serde(skip)]
+    watched_board_dirs: Vec<PathBuf>,
 }
 
 impl Default for IronCoderApp {
     fn default() -> Self {
         // Populate the boards
-        let boards_dir = Path::new("./iron-coder-boards"); // consider making this a global macro
-        let boards: Vec<board::Board> = board::get_boards(boards_dir);
+        let boards_dir = Path::new(BOARDS_DIR);
+        let (boards, board_diagnostics) = board::get_boards_with_diagnostics(boards_dir);
         Self {
             project: Project::default(),
             display_about: false,
@@ -117,6 +405,7 @@ impl Default for IronCoderApp {
             // modal: None,
             mode: Mode::EditProject,
             boards: boards,
+            board_diagnostics,
             options: IronCoderOptions::default(),
             // Warning Flags
             warning_flags: Warnings {
@@ -134,11 +423,55 @@ impl Default for IronCoderApp {
                 commit_email: String::new(),
                 commit_message: String::new(),
                 repo: None,
+                local_sources: Vec::new(),
             },
             settings: Settings {
                 colorscheme: colorscheme::INDUSTRIAL_DARK,
                 ui_scale: 1.0,
+                board_favorites: BoardFavorites::default(),
+                board_sorting: BoardSorting::default(),
             },
+            tool_windows: ToolWindows::default(),
+            serial_monitor: SerialMonitor::default(),
+            ble_dfu: DfuPanel::default(),
+            lorawan: LoraWanPanel::default(),
+            device_images: ImagePanel::default(),
+            devices: DevicesPanel::default(),
+            gatt_preview: GattPanel::default(),
+            toolbox: ToolboxPanel::default(),
+            signals: SignalsPanel::default(),
+            state_machines: StateMachinePanel::default(),
+            messages: MessagesPanel::default(),
+            export: ExportPanel::default(),
+            forwarding: ForwardingPanel::default(),
+            fixture: FixturePanel::default(),
+            instruments: InstrumentPanel::default(),
+            assistant: AssistantPanel::default(),
+            assets: EmbedPanel::default(),
+            fonts: FontPanel::default(),
+            translations: LocalizationPanel::default(),
+            watchdog: WatchdogPanel::default(),
+            rtc: RtcPanel::default(),
+            power: PowerPanel::default(),
+            pins: PinsPanel::default(),
+            cpu_usage: CpuUsagePanel::default(),
+            heap_usage: HeapUsagePanel::default(),
+            panic_dump: PanicDumpPanel::default(),
+            storage: StoragePanel::default(),
+            config_partition: ConfigPartitionPanel::default(),
+            partition_table: PartitionTablePanel::default(),
+            trace: TracePanel::default(),
+            input_sim: InputSimPanel::default(),
+            detached_windows: DetachedWindows::default(),
+            focus: FocusMode::default(),
+            board_wizard: BoardWizard::default(),
+            board_registry: RegistryPanel::default(),
+            serial_overlay: SerialOverlay::default(),
+            search: SearchPanel::default(),
+            quick_start: QuickStartWindow::default(),
+            events: None,
+            board_watcher: None,
+            watched_board_dirs: Vec::new(),
         }
     }
 }
@@ -164,6 +497,7 @@ impl IronCoderApp {
         info!("Reloading last project and assets...");
         app.set_colorscheme(&cc.egui_ctx);
         app.project.known_boards = app.boards.clone();
+        board::decode_images(&app.boards, &cc.egui_ctx);
         match app.project.reload() {
             Ok(_) => (),
             Err(e) => warn!("error reloading project from disk! {:?}", e),
@@ -184,8 +518,12 @@ impl IronCoderApp {
             display_settings,
             mode,
             project,
+            tool_windows,
+            focus,
             ..
         } = self;
+        let mut export_catalog = false;
+        let mut import_bundle = false;
         let icons_ref: Arc<IconSet> = ctx.data_mut(|data| {
             data.get_temp("icons".into()).expect("couldn't load icons!")
         });
@@ -260,7 +598,7 @@ impl IronCoderApp {
 
                         let ib = egui::widgets::Button::image_and_text(
                             icons.get("settings_icon").unwrap().clone(),
-                            "settings"
+                            Command::Settings.name()
                         );
                         if ui.add(ib).clicked() {
                             *display_settings = !*display_settings;
@@ -268,12 +606,27 @@ impl IronCoderApp {
 
                         let ib = egui::widgets::Button::image_and_text(
                             icons.get("about_icon").unwrap().clone(),
-                            "about Iron Coder"
+                            Command::About.name()
                         );
                         if ui.add(ib).clicked() {
                             *display_about = !*display_about;
                         }
 
+                        ui.menu_button("tools", |ui| {
+                            for (name, open) in tool_windows.by_name() {
+                                ui.toggle_value(open, name);
+                            }
+                            export_catalog = ui.button(Command::ExportBoardCatalog.name()).clicked();
+                            import_bundle = ui.button(Command::ImportBoardBundle.name()).clicked();
+                        });
+
+                        // Focus mode is for the code editor, so there has to be one
+                        let focus_button = egui::Button::new(Command::FocusMode.name()).shortcut_text("ctrl+shift+f");
+                        if ui.add_enabled(*mode == Mode::DevelopProject, focus_button).clicked() {
+                            focus.enter(tool_windows);
+                            ui.close_menu();
+                        }
+
                         let ib = egui::widgets::Button::image_and_text(
                             icons.get("quit_icon").unwrap().clone(),
                             "quit"
@@ -287,6 +640,12 @@ impl IronCoderApp {
                 });
             });
         });
+        if export_catalog {
+            self.export_board_catalog();
+        }
+        if import_bundle {
+            self.import_board_bundle(ctx);
+        }
     }
 
     /// Returns a copy of the list of available boards.
@@ -331,7 +690,7 @@ impl IronCoderApp {
     pub fn display_project_editor(&mut self, ctx: &egui::Context) {
         // first render the top panel with project name, buttons, etc.
         egui::TopBottomPanel::top("project_editor_top_panel").show(ctx, |ui| {
-            if let Some(mode) = self.project.display_system_editor_top_bar(ctx, ui, &mut self.warning_flags) {
+            if let Some(mode) = self.project.display_system_editor_top_bar(ctx, ui, &mut self.warning_flags, &mut self.settings.board_favorites, &mut self.settings.board_sorting) {
                 self.mode = mode;
             }
         });
@@ -373,7 +732,8 @@ impl IronCoderApp {
                 ctx.data_mut(|data| {
                     data.insert_temp(id, should_show_boards_window);
                 });
-                if let Some(b) = self.project.display_known_boards(ctx, &mut should_show_boards_window) {
+                if let Some(b) = self.project.display_known_boards(ctx, &mut should_show_boards_window, &mut self.settings.board_favorites, &mut self.settings.board_sorting) {
+                    events::shared(ctx).publish(events::Event::BoardSelected(b.key()));
                     self.project.add_board(b);
                 }
             };
@@ -399,6 +759,7 @@ impl IronCoderApp {
             settings: Settings{ 
                 colorscheme, 
                 ui_scale,
+                ..
             },
             ..
         } = self;
@@ -543,6 +904,890 @@ impl IronCoderApp {
         });
     }
 
+    /// Show the performance HUD, with frame timing and texture memory usage
+    pub fn display_performance_hud(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_performance_hud { return; }
+        let frame_time = ctx.input(|i| i.unstable_dt);
+        let cache = texture_cache::shared(ctx);
+        egui::Window::new("Performance")
+        .open(&mut self.tool_windows.display_performance_hud)
+        .collapsible(true)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            ui.label(format!("frame time: {:.1} ms", frame_time * 1000.0));
+            ui.separator();
+            let mut cache = cache.lock().unwrap();
+            const MB: f32 = 1024.0 * 1024.0;
+            ui.label(format!(
+                "texture memory: {:.1} / {:.1} MB",
+                cache.used_bytes() as f32 / MB,
+                cache.budget_bytes() as f32 / MB,
+            ));
+            ui.add(egui::ProgressBar::new(cache.used_bytes() as f32 / cache.budget_bytes().max(1) as f32));
+            ui.label(format!("cached textures: {}", cache.len()));
+            ui.label(format!("evictions: {}", cache.evictions()));
+            let mut budget_mb = cache.budget_bytes() / (1024 * 1024);
+            if ui.add(egui::Slider::new(&mut budget_mb, 8..=512).text("budget (MB)")).changed() {
+                cache.set_budget(budget_mb * 1024 * 1024);
+            }
+        });
+    }
+
+    /// Show the jobs panel, which lists the background jobs along with their progress,
+    /// and allows cancelling the ones that are still running.
+    pub fn display_jobs_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_jobs { return; }
+        let scheduler = jobs::shared(ctx);
+        egui::Window::new("Jobs")
+        .open(&mut self.tool_windows.display_jobs)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            let job_list = scheduler.jobs();
+            if job_list.is_empty() {
+                ui.label("no background jobs.");
+                return;
+            }
+            egui::Grid::new("jobs_grid").striped(true).show(ui, |ui| {
+                // show the most recent jobs first
+                for job in job_list.iter().rev() {
+                    ui.label(&job.name).on_hover_text(&job.status);
+                    match &job.state {
+                        JobState::Running => match job.progress {
+                            Some(progress) => ui.add(egui::ProgressBar::new(progress).desired_width(120.0)),
+                            None => ui.spinner(),
+                        },
+                        JobState::Finished => ui.label("done"),
+                        JobState::Failed(e) => {
+                            ui.label(RichText::new("failed").color(ui.visuals().error_fg_color)).on_hover_text(e)
+                        },
+                        JobState::Cancelled => ui.label("cancelled"),
+                    };
+                    ui.label(format!("{:.1}s", job.elapsed.as_secs_f32()));
+                    if job.state == JobState::Running && ui.button("cancel").clicked() {
+                        scheduler.cancel(job.id);
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            if ui.button("clear finished").clicked() {
+                scheduler.clear_finished();
+            }
+        });
+        // keep the elapsed times ticking while something is running
+        if scheduler.num_running() > 0 {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+    }
+
+    /// Show the serial monitor window
+    pub fn display_serial_monitor_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_serial_monitor {
+            self.serial_monitor.close(ctx);
+            return;
+        }
+        let serial_monitor = &mut self.serial_monitor;
+        let overlay = &mut self.serial_overlay;
+        let overlay_open = &mut self.tool_windows.display_serial_overlay;
+        let schema = &self.project.messages;
+        self.detached_windows.show(ctx, "Serial Monitor", &mut self.tool_windows.display_serial_monitor, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(overlay_open, "always-on-top overlay");
+                ui.add_enabled_ui(*overlay_open, |ui| overlay.display_settings(ui));
+            });
+            serial_monitor.display(ctx, ui, schema);
+        });
+    }
+
+    /// Show the last lines of output and the flashing status above the other apps
+    pub fn display_serial_overlay(&mut self, ctx: &egui::Context) {
+        self.serial_overlay.show(ctx, &mut self.tool_windows.display_serial_overlay, &self.project.messages);
+    }
+
+    /// Show the BLE firmware update window
+    pub fn display_ble_dfu_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_ble_dfu { return; }
+        let ble_dfu = &mut self.ble_dfu;
+        egui::Window::new("BLE Firmware Update")
+        .open(&mut self.tool_windows.display_ble_dfu)
+        .collapsible(true)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            ble_dfu.display(ctx, ui);
+        });
+    }
+
+    /// Show the LoRaWAN credentials and uplink window
+    pub fn display_lorawan_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_lorawan {
+            self.lorawan.close(ctx);
+            return;
+        }
+        let lorawan = &mut self.lorawan;
+        let project = &mut self.project;
+        egui::Window::new("LoRaWAN")
+        .open(&mut self.tool_windows.display_lorawan)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            if !project.has_lora_board() {
+                ui.label("None of the boards in this project have a LoRa radio.");
+                return;
+            }
+            let location = project.location().map(Path::to_path_buf);
+            lorawan.display(ctx, ui, &mut project.lorawan, location.as_deref());
+        });
+    }
+
+    /// Show the MCUboot image slots of a connected device
+    pub fn display_device_images_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_device_images {
+            self.device_images.close();
+            return;
+        }
+        let device_images = &mut self.device_images;
+        egui::Window::new("Device Images")
+        .open(&mut self.tool_windows.display_device_images)
+        .collapsible(true)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            device_images.display(ctx, ui);
+        });
+    }
+
+    /// Show the devices found on the network
+    pub fn display_devices_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_devices {
+            self.devices.close();
+            return;
+        }
+        let devices = &mut self.devices;
+        let location = self.project.location().map(Path::to_path_buf);
+        egui::Window::new("Network Devices")
+        .open(&mut self.tool_windows.display_devices)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            devices.display(ctx, ui, location.as_deref());
+        });
+    }
+
+    /// Preview the project's GATT services as a phone app would see them
+    pub fn display_gatt_preview_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_gatt_preview { return; }
+        let gatt_preview = &mut self.gatt_preview;
+        let location = self.project.location().map(Path::to_path_buf);
+        egui::Window::new("BLE GATT Preview")
+        .open(&mut self.tool_windows.display_gatt_preview)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            gatt_preview.display(ctx, ui, location.as_deref());
+        });
+    }
+
+    /// Show the electronics calculators, using the main board's ADC and timer clock
+    pub fn display_toolbox_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_toolbox { return; }
+        let toolbox = &mut self.toolbox;
+        let board = self.project.system.main_board.as_ref();
+        egui::Window::new("Electronics Toolbox")
+        .open(&mut self.tool_windows.display_toolbox)
+        .collapsible(true)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            toolbox.display(ui, board);
+        });
+    }
+
+    /// Plot the analog samples streamed from the board, and calibrate its channels
+    pub fn display_signals_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_signals {
+            self.signals.close();
+            return;
+        }
+        let signals = &mut self.signals;
+        let project = &mut self.project;
+        self.detached_windows.show(ctx, "Signal Viewer", &mut self.tool_windows.display_signals, |ui| {
+            let adc = project.system.main_board.as_ref().and_then(|b| b.adc());
+            let location = project.location().map(Path::to_path_buf);
+            signals.display(ctx, ui, &mut project.calibration, adc, location.as_deref());
+        });
+    }
+
+    /// Design the project's state machines, and generate their modules
+    pub fn display_state_machines_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_state_machines { return; }
+        let state_machines = &mut self.state_machines;
+        let project = &mut self.project;
+        egui::Window::new("State Machines")
+        .open(&mut self.tool_windows.display_state_machines)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            let location = project.location().map(Path::to_path_buf);
+            state_machines.display(ui, &mut project.state_machines, location.as_deref());
+        });
+    }
+
+    /// Edit the schemas of the messages between the device and the host
+    pub fn display_messages_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_messages { return; }
+        let messages = &mut self.messages;
+        let project = &mut self.project;
+        egui::Window::new("Message Schemas")
+        .open(&mut self.tool_windows.display_messages)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            let location = project.location().map(Path::to_path_buf);
+            messages.display(ui, &mut project.messages, location.as_deref());
+        });
+    }
+
+    /// Record telemetry and export it for analysis
+    pub fn display_export_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_export { return; }
+        let export = &mut self.export;
+        let schema = &self.project.messages;
+        egui::Window::new("Data Export")
+        .open(&mut self.tool_windows.display_export)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            export.display(ctx, ui, schema);
+        });
+    }
+
+    /// Forward telemetry to InfluxDB or a Prometheus endpoint while a device test runs
+    pub fn display_forwarding_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_forwarding { return; }
+        let forwarding = &mut self.forwarding;
+        let project = &mut self.project;
+        egui::Window::new("Live Forwarding")
+        .open(&mut self.tool_windows.display_forwarding)
+        .collapsible(true)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            forwarding.display(ctx, ui, &mut project.forwarding, &project.messages);
+        });
+    }
+
+    /// Show where the devices of the project's test bench are attached
+    pub fn display_fixture_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_fixture { return; }
+        let fixture = &mut self.fixture;
+        let location = self.project.location().map(Path::to_path_buf);
+        let mut monitor = None;
+        egui::Window::new("Test Fixture")
+        .open(&mut self.tool_windows.display_fixture)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            monitor = fixture.display(ctx, ui, location.as_deref());
+        });
+        if let Some(port) = monitor {
+            self.serial_monitor.port = port.display().to_string();
+            self.tool_windows.display_serial_monitor = true;
+        }
+    }
+
+    /// Drive the bench instruments named in the project's fixture
+    pub fn display_instruments_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_instruments { return; }
+        let instruments = &mut self.instruments;
+        let location = self.project.location().map(Path::to_path_buf);
+        let sequences = &mut self.project.instrument_sequences;
+        egui::Window::new("Instruments")
+        .open(&mut self.tool_windows.display_instruments)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            instruments.display(ctx, ui, sequences, location.as_deref());
+        });
+    }
+
+    /// Ask the configured assistant about the project
+    pub fn display_assistant_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_assistant { return; }
+        let assistant = &mut self.assistant;
+        let project = &self.project;
+        egui::Window::new("Assistant")
+        .open(&mut self.tool_windows.display_assistant)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            assistant.display(ctx, ui, project);
+        });
+    }
+
+    /// Choose the images, sounds and fonts embedded in the firmware
+    pub fn display_assets_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_assets { return; }
+        let panel = &mut self.assets;
+        let location = self.project.location().map(Path::to_path_buf);
+        let assets = &mut self.project.embedded_assets;
+        egui::Window::new("Firmware Assets")
+        .open(&mut self.tool_windows.display_assets)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, assets, location.as_deref());
+        });
+    }
+
+    /// Cut fonts down to the characters the firmware shows
+    pub fn display_fonts_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_fonts { return; }
+        let panel = &mut self.fonts;
+        let location = self.project.location().map(Path::to_path_buf);
+        let subsets = &mut self.project.font_subsets;
+        egui::Window::new("Bitmap Fonts")
+        .open(&mut self.tool_windows.display_fonts)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, subsets, location.as_deref());
+        });
+    }
+
+    /// Edit the firmware's string tables
+    pub fn display_translations_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_translations { return; }
+        let panel = &mut self.translations;
+        let location = self.project.location().map(Path::to_path_buf);
+        let config = &mut self.project.localization;
+        egui::Window::new("Translations")
+        .open(&mut self.tool_windows.display_translations)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, config, location.as_deref());
+        });
+    }
+
+    /// Set up the main board's watchdog and brown-out detector
+    pub fn display_watchdog_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_watchdog { return; }
+        let panel = &mut self.watchdog;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        egui::Window::new("Watchdog")
+        .open(&mut self.tool_windows.display_watchdog)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, board, location.as_deref());
+        });
+    }
+
+    /// Set up the main board's real-time clock, set the device's time and watch it drift
+    pub fn display_rtc_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_rtc {
+            self.rtc.close();
+            return;
+        }
+        let panel = &mut self.rtc;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let port = self.serial_monitor.port.clone();
+        egui::Window::new("Device Clock")
+        .open(&mut self.tool_windows.display_rtc)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, board, location.as_deref(), &port);
+        });
+    }
+
+    /// Plan the main board's sleep modes and estimate the current they draw
+    pub fn display_power_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_power { return; }
+        let panel = &mut self.power;
+        let board = self.project.system.main_board.as_ref();
+        let plan = &mut self.project.power_plan;
+        egui::Window::new("Low-Power Planner")
+        .open(&mut self.tool_windows.display_power)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, plan, board);
+        });
+    }
+
+    /// Time the firmware's tasks and show their CPU usage as the board reports it
+    pub fn display_cpu_usage_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_cpu_usage { return; }
+        let panel = &mut self.cpu_usage;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let config = &mut self.project.cpu_usage;
+        egui::Window::new("CPU Usage")
+        .open(&mut self.tool_windows.display_cpu_usage)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, config, board, location.as_deref());
+        });
+    }
+
+    /// Watch the firmware's heap as the board reports it
+    pub fn display_heap_usage_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_heap_usage { return; }
+        let panel = &mut self.heap_usage;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let config = &mut self.project.heap_usage;
+        egui::Window::new("Heap Usage")
+        .open(&mut self.tool_windows.display_heap_usage)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, config, board, location.as_deref());
+        });
+    }
+
+    /// Read the panic the firmware kept off the board, and follow it to the source
+    pub fn display_panic_dump_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_panic_dump { return; }
+        let panel = &mut self.panic_dump;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let mut open = None;
+        egui::Window::new("Last Panic")
+        .open(&mut self.tool_windows.display_panic_dump)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            open = panel.display(ctx, ui, board, location.as_deref());
+        });
+        if let Some(file_ref) = open {
+            let path = match &location {
+                Some(location) if file_ref.path.is_relative() => location.join(&file_ref.path),
+                _ => file_ref.path.clone(),
+            };
+            match self.project.code_editor.goto_line(&path, file_ref.line) {
+                Ok(()) => self.mode = Mode::DevelopProject,
+                Err(e) => warn!("couldn't open {}: {:?}", path.display(), e),
+            }
+        }
+    }
+
+    /// Look at what the firmware wrote to an SD card or littlefs image, and start the
+    /// filesystem in the firmware
+    pub fn display_storage_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_storage { return; }
+        let panel = &mut self.storage;
+        let location = self.project.location().map(Path::to_path_buf);
+        let system = &self.project.system;
+        egui::Window::new("Storage")
+        .open(&mut self.tool_windows.display_storage)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, system, location.as_deref());
+        });
+    }
+
+    /// Edit the settings kept in a partition of the main board's flash, and write them to it
+    pub fn display_config_partition_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_config_partition { return; }
+        let panel = &mut self.config_partition;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let table = partition_table::effective(self.project.partition_table.as_ref(), board);
+        let config = &mut self.project.config_partition;
+        egui::Window::new("Configuration Partition")
+        .open(&mut self.tool_windows.display_config_partition)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, config, board, table, location.as_deref());
+        });
+    }
+
+    /// Lay out the main board's flash for the project, and flash ESP chips with the table
+    pub fn display_partition_table_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_partition_table { return; }
+        let panel = &mut self.partition_table;
+        let location = self.project.location().map(Path::to_path_buf);
+        let board = self.project.system.main_board.as_ref();
+        let table = &mut self.project.partition_table;
+        egui::Window::new("Partition Table")
+        .open(&mut self.tool_windows.display_partition_table)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, table, board, location.as_deref());
+        });
+    }
+
+    /// Capture the main board's SWO trace and show it as a timeline
+    pub fn display_trace_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_trace { return; }
+        let panel = &mut self.trace;
+        let board = self.project.system.main_board.as_ref();
+        egui::Window::new("Trace")
+        .open(&mut self.tool_windows.display_trace)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ctx, ui, board);
+        });
+    }
+
+    /// Stand in for the touch screens and encoders of the system's boards
+    pub fn display_input_sim_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_input_sim { return; }
+        let panel = &mut self.input_sim;
+        let location = self.project.location().map(Path::to_path_buf);
+        let boards = &self.project.system.peripheral_boards;
+        let port = &self.serial_monitor.port;
+        egui::Window::new("Input Simulator")
+        .open(&mut self.tool_windows.display_input_sim)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            panel.display(ui, boards, port, location.as_deref());
+        });
+    }
+
+    /// Show the pins of the system's boards
+    pub fn display_pinout_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_pinout { return; }
+        let boards = self.project.system.get_all_boards();
+        self.detached_windows.show(ctx, "Pinout", &mut self.tool_windows.display_pinout, |ui| {
+            if boards.is_empty() {
+                ui.label("add a board to the system to see its pins");
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, board) in boards.iter().enumerate() {
+                    egui::CollapsingHeader::new(board.label()).id_source(("pinout", i)).default_open(true).show(ui, |ui| {
+                        ui.add(board::display::PinoutViewer(board));
+                        // the registers, for boards with an SVD file
+                        match board.svd() {
+                            Some(Ok(device)) => {
+                                egui::CollapsingHeader::new("registers").id_source(("svd", i)).show(ui, |ui| {
+                                    board::svd::display(ui, &device);
+                                });
+                            },
+                            Some(Err(e)) => {
+                                ui.colored_label(ui.visuals().warn_fg_color, format!("couldn't read the SVD file: {}", e));
+                            },
+                            None => (),
+                        }
+                    });
+                }
+            });
+        });
+    }
+
+    /// Show which pins the project's connections use, and export them
+    pub fn display_pins_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_pins { return; }
+        let panel = &mut self.pins;
+        let project = &mut self.project;
+        egui::Window::new("Pin Assignments")
+        .open(&mut self.tool_windows.display_pins)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            let project_dir = project.location().map(Path::to_path_buf);
+            panel.display(ui, &project.system, &mut project.pin_exports, project_dir.as_deref());
+        });
+    }
+
+    /// Walk through describing a new board, and add it to the boards directory
+    pub fn display_board_wizard_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_board_wizard { return; }
+        let wizard = &mut self.board_wizard;
+        let mut added = None;
+        egui::Window::new("New Board")
+        .open(&mut self.tool_windows.display_board_wizard)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            added = wizard.display(ui, Path::new(BOARDS_DIR));
+        });
+        if let Some(path) = added {
+            info!("added board {}", path.display());
+            self.reload_boards(ctx);
+        }
+    }
+
+    /// Do what a command is for
+    fn run_command(&mut self, ctx: &egui::Context, command: Command) {
+        match command {
+            Command::SaveProject => {
+                if let Err(e) = self.project.save() {
+                    error!("error saving project: {:?}", e);
+                }
+            },
+            Command::OpenProject => match self.project.open() {
+                Ok(_) => self.mode = Mode::DevelopProject,
+                Err(e) => error!("error opening project: {:?}", e),
+            },
+            Command::FocusMode => {
+                if self.mode == Mode::DevelopProject {
+                    self.focus.enter(&mut self.tool_windows);
+                }
+            },
+            Command::Settings => self.display_settings = true,
+            Command::About => self.display_about = true,
+            Command::ReloadBoards => self.reload_boards(ctx),
+            Command::ExportBoardCatalog => self.export_board_catalog(),
+            Command::ImportBoardBundle => self.import_board_bundle(ctx),
+            Command::NewFile | Command::NewFolder => {
+                self.mode = Mode::DevelopProject;
+                self.project.new_file(command == Command::NewFolder);
+            },
+        }
+    }
+
+    /// Do what a search result is for
+    fn open_search_result(&mut self, ctx: &egui::Context, target: SearchTarget) {
+        match target {
+            SearchTarget::Action(action) => match Command::from_name(&action) {
+                Some(command) => self.run_command(ctx, command),
+                None => {
+                    if let Some((_, open)) = self.tool_windows.by_name().into_iter().find(|(name, _)| *name == action) {
+                        *open = true;
+                    }
+                },
+            },
+            SearchTarget::Board(name) => {
+                self.mode = Mode::EditProject;
+                self.project.show_known_board(ctx, &name);
+            },
+            SearchTarget::File(path) => {
+                match self.project.code_editor.load_from_file(&path) {
+                    Ok(()) => self.mode = Mode::DevelopProject,
+                    Err(e) => warn!("couldn't open {}: {:?}", path.display(), e),
+                }
+            },
+            SearchTarget::Crate(name) => {
+                ctx.open_url(egui::OpenUrl::new_tab(compat::docs_url(&name)));
+            },
+        }
+    }
+
+    /// Act on the app events that change what's open, i.e. the board examples and quick
+    /// starts
+    fn handle_events(&mut self, ctx: &egui::Context) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        let events: Vec<events::Event> = rx.try_iter().collect();
+        for event in events {
+            match event {
+                events::Event::ExamplePicked(example) => {
+                    let Some(source) = example::example_source(&example) else {
+                        warn!("example {} has no source to open", example.display());
+                        continue;
+                    };
+                    match self.project.code_editor.load_from_file(&source) {
+                        Ok(()) => self.mode = Mode::DevelopProject,
+                        Err(e) => warn!("couldn't open {}: {:?}", source.display(), e),
+                    }
+                },
+                events::Event::BoardSelected(key) => {
+                    if let Some(board) = self.boards.iter().find(|b| b.key() == key) {
+                        self.quick_start.board_selected(board);
+                    }
+                },
+                events::Event::ShowQuickStart(key) => {
+                    if let Some(board) = self.boards.iter().find(|b| b.key() == key) {
+                        self.quick_start.show(board);
+                    }
+                },
+                events::Event::ProjectFromExample { board, example } => {
+                    let Some(board) = self.boards.iter().find(|b| b.key() == board) else {
+                        warn!("couldn't find board {} for the example", board);
+                        continue;
+                    };
+                    let Some(folder) = rfd::FileDialog::new().set_title("Where to put the new project").pick_folder() else {
+                        continue;
+                    };
+                    let destination = folder.join(&*crate::paths::display_name(&example));
+                    let mut project = Project::default();
+                    project.known_boards = self.boards.clone();
+                    match project.new_from_example(board, &example, &destination) {
+                        Ok(()) => {
+                            self.project = project;
+                            self.mode = Mode::DevelopProject;
+                        },
+                        Err(e) => error!("couldn't make a project from {}: {:?}", example.display(), e),
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Load the boards again, for when the board files have changed
+    /// Write the known boards to a JSON file, for scripts and other tools.
+    fn export_board_catalog(&self) {
+        let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).set_file_name("boards.json").save_file() else {
+            return;
+        };
+        let written = board::export::to_json(&self.boards)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            error!("couldn't export the boards to {}: {}", path.display(), e);
+        }
+    }
+
+    /// Write a board bundle someone shared into the boards directory, asking first if
+    /// there's already a board where it goes.
+    fn import_board_bundle(&mut self, ctx: &egui::Context) {
+        let Some(path) = rfd::FileDialog::new().add_filter("board bundle", &["json"]).pick_file() else {
+            return;
+        };
+        let bundle = match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|json| BoardBundle::parse(&json)) {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                error!("couldn't import {}: {}", path.display(), e);
+                return;
+            },
+        };
+        let destination = bundle.destination(Path::new(BOARDS_DIR));
+        let replace = destination.exists();
+        if replace {
+            let answer = rfd::MessageDialog::new()
+                .set_title("Import board bundle")
+                .set_description(format!("There's already a board at {}. Replace it?", destination.display()))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show();
+            if answer != rfd::MessageDialogResult::Yes {
+                return;
+            }
+        }
+        match bundle.install(Path::new(BOARDS_DIR), replace) {
+            Ok(dir) => {
+                info!("imported a board into {}", dir.display());
+                self.reload_boards(ctx);
+            },
+            Err(e) => error!("couldn't import {}: {}", path.display(), e),
+        }
+    }
+
+    /// Load the boards again, and drop the textures of the boards that were and are
+    /// loaded, so edited images and pictures are shown as they are now.
+    pub fn reload_boards(&mut self, ctx: &egui::Context) {
+        // the project's boards include the ones its workspace adds
+        let old_boards = std::mem::take(&mut self.project.known_boards);
+        (self.boards, self.board_diagnostics) = board::get_boards_with_diagnostics(Path::new(BOARDS_DIR));
+        self.project.known_boards = self.boards.clone();
+        self.project.add_workspace_boards();
+        let cache = texture_cache::shared(ctx);
+        let mut cache = cache.lock().unwrap();
+        for board in old_boards.iter().chain(self.project.known_boards.iter()) {
+            cache.forget_board(&board.key());
+        }
+        board::decode_images(&self.project.known_boards, ctx);
+    }
+
+    /// The directories the boards are loaded from: the boards directory, and the ones the
+    /// project's workspace adds.
+    fn board_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![PathBuf::from(BOARDS_DIR)];
+        dirs.extend(self.project.workspace().map(|w| w.board_dirs()).unwrap_or_default());
+        dirs
+    }
+
+    /// Reload the boards when their files have changed, so boards being written show up
+    /// without a restart. The watcher is started again when the directories change, i.e.
+    /// when a project with a workspace is opened.
+    fn watch_boards(&mut self, ctx: &egui::Context) {
+        let dirs = self.board_dirs();
+        if dirs != self.watched_board_dirs {
+            self.board_watcher = BoardWatcher::new(&dirs, Some(ctx.clone()))
+                .map_err(|e| warn!("boards won't be reloaded when they change: {}", e))
+                .ok();
+            self.watched_board_dirs = dirs;
+        }
+        let Some(changed) = self.board_watcher.as_mut().and_then(BoardWatcher::changed) else {
+            return;
+        };
+        let names: Vec<String> = changed.iter().filter_map(|p| p.file_name()).map(|n| n.to_string_lossy().to_string()).collect();
+        info!("reloading the boards, since {} changed", names.join(", "));
+        self.reload_boards(ctx);
+    }
+
+    /// Show what's wrong with the board files
+    pub fn display_board_diagnostics_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_board_diagnostics { return; }
+        let diagnostics = &self.board_diagnostics;
+        let mut reload = false;
+        egui::Window::new("Board Diagnostics")
+        .open(&mut self.tool_windows.display_board_diagnostics)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} boards loaded from {}", self.boards.len(), BOARDS_DIR));
+                reload = ui.button("reload").clicked();
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                board::diagnostics::display(ui, diagnostics);
+            });
+        });
+        if reload {
+            self.reload_boards(ctx);
+        }
+    }
+
+    /// Show the window for getting boards from a board registry
+    pub fn display_board_registry_window(&mut self, ctx: &egui::Context) {
+        if !self.tool_windows.display_board_registry { return; }
+        let registry = &mut self.board_registry;
+        let mut changed = false;
+        egui::Window::new("Board Registry")
+        .open(&mut self.tool_windows.display_board_registry)
+        .collapsible(true)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            changed = registry.display(ctx, ui, Path::new(BOARDS_DIR));
+        });
+        if changed {
+            self.reload_boards(ctx);
+        }
+    }
+
     // Displays the waring message that no main board has been selected for the project
     pub fn unselected_mainboard_warning(&mut self, ctx: &egui::Context) {
         egui::Window::new("Board Warning")
@@ -608,7 +1853,7 @@ impl IronCoderApp {
         let mut staged_to_remove: Vec<String> = Vec::new();
         let mut staged_to_add: Vec<String> = Vec::new();
         let mut unstaged_to_add: Vec<String> = Vec::new();
-
+        let secrets = self.project.effective_environment().secrets();
 
         egui::Window::new("Commit")
         .open(&mut display_git)
@@ -676,6 +1921,21 @@ impl IronCoderApp {
 
                 
 
+                if !self.git_things.local_sources.is_empty() {
+                    local_sources::display(ui, &self.git_things.local_sources);
+                }
+
+                // Warn about build output and secrets before they're committed
+                let repo_dir = repo.workdir().map(Path::to_path_buf).unwrap_or_default();
+                let warnings = vcs::check_commit(&repo_dir, &self.git_things.staged_changes, &secrets);
+                if !warnings.is_empty() {
+                    ui.separator();
+                    for warning in warnings.iter() {
+                        ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+                    }
+                    ui.weak("click a staged change to unstage it");
+                }
+
                 // Have a button to commit the changes
                 if ui.button("Commit").clicked() {
                     let name = self.git_things.commit_name.clone();
@@ -764,20 +2024,81 @@ impl eframe::App for IronCoderApp {
     //   self in each of these method calls separately, vs once in the beginning of this
     //   method? But I can't do it the latter way while still having these as method calls.
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        // render the title bar with main menu
-        self.display_title_and_menu(ctx, frame);
-        // depending on the Mode, render the proper main view
-        match self.mode {
-            Mode::EditProject => {
-                self.display_project_editor(ctx);
-            },
-            Mode::DevelopProject => {
-                self.display_project_developer(ctx);
-            },
+        self.handle_events(ctx);
+        self.watch_boards(ctx);
+        if ctx.input_mut(|i| i.consume_shortcut(&search::SHORTCUT)) {
+            let mut actions: Vec<&str> = Command::ALL.iter().map(|command| command.name()).collect();
+            actions.extend(self.tool_windows.by_name().into_iter().map(|(name, _)| name));
+            self.search.show(search::index(&actions, &self.boards, self.project.location()));
+        }
+        if let Some(target) = self.search.display(ctx) {
+            self.open_search_result(ctx, target);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&focus::SHORTCUT)) && self.mode == Mode::DevelopProject {
+            self.focus.toggle(&mut self.tool_windows);
         }
+        if self.focus.is_on() && self.mode != Mode::DevelopProject {
+            self.focus.exit(&mut self.tool_windows);
+        }
+        if self.focus.is_on() {
+            // only the code editor is shown
+            focus::display_editor(ctx, &mut self.project.code_editor);
+        } else {
+            // render the title bar with main menu
+            self.display_title_and_menu(ctx, frame);
+            // depending on the Mode, render the proper main view
+            match self.mode {
+                Mode::EditProject => {
+                    self.display_project_editor(ctx);
+                },
+                Mode::DevelopProject => {
+                    self.display_project_developer(ctx);
+                },
+            }
+        }
+        // the connections may have changed
+        self.project.sync_pin_exports();
         // optionally render these popup windows
         self.display_settings_window(ctx);
         self.display_about_window(ctx);
+        self.display_performance_hud(ctx);
+        self.display_jobs_window(ctx);
+        self.display_serial_monitor_window(ctx);
+        self.display_serial_overlay(ctx);
+        self.quick_start.display(ctx, &self.boards);
+        self.display_ble_dfu_window(ctx);
+        self.display_lorawan_window(ctx);
+        self.display_device_images_window(ctx);
+        self.display_devices_window(ctx);
+        self.display_gatt_preview_window(ctx);
+        self.display_toolbox_window(ctx);
+        self.display_signals_window(ctx);
+        self.display_state_machines_window(ctx);
+        self.display_messages_window(ctx);
+        self.display_export_window(ctx);
+        self.display_forwarding_window(ctx);
+        self.display_fixture_window(ctx);
+        self.display_instruments_window(ctx);
+        self.display_assistant_window(ctx);
+        self.display_assets_window(ctx);
+        self.display_fonts_window(ctx);
+        self.display_translations_window(ctx);
+        self.display_watchdog_window(ctx);
+        self.display_rtc_window(ctx);
+        self.display_power_window(ctx);
+        self.display_cpu_usage_window(ctx);
+        self.display_heap_usage_window(ctx);
+        self.display_panic_dump_window(ctx);
+        self.display_storage_window(ctx);
+        self.display_config_partition_window(ctx);
+        self.display_partition_table_window(ctx);
+        self.display_trace_window(ctx);
+        self.display_input_sim_window(ctx);
+        self.display_pinout_window(ctx);
+        self.display_pins_window(ctx);
+        self.display_board_wizard_window(ctx);
+        self.display_board_diagnostics_window(ctx);
+        self.display_board_registry_window(ctx);
         self.unselected_mainboard_warning(ctx);
         self.display_unnamed_project_warning(ctx);
         self.display_invalid_name_warning(ctx);
@@ -837,7 +2158,7 @@ impl eframe::App for IronCoderApp {
 }
 
 /// Sets up the fonts and style for the app
-fn setup_fonts_and_style(ctx: &egui::Context) {
+pub fn setup_fonts_and_style(ctx: &egui::Context) {
 
     let mut fonts = egui::FontDefinitions::default();
 
@@ -958,6 +2279,12 @@ fn setup_fonts_and_style(ctx: &egui::Context) {
         info!("Adding IconSet to egui Context temp data.");
         map.insert_temp("icons".into(), Arc::new(icons::load_icons(Path::new(icons::ICON_DIR))));
     });
+
+    // and the manufacturer logos
+    let logo_set = Arc::new(logos::load_logos(Path::new(logos::LOGO_DIR)));
+    ctx.data_mut(|map| {
+        map.insert_temp(logos::LOGOS_ID.into(), logo_set);
+    });
 }
 
 /// Displays a cool looking header in the Ui element, utilizing our custom fonts
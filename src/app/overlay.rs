@@ -0,0 +1,164 @@
+//! Title: Iron Coder App Module - Serial Overlay
+//! Description: A small always-on-top window with the last few lines from the serial
+//!   monitor and RTT, and how loading the firmware onto the board is going, for keeping
+//!   an eye on the hardware while another app has the focus. Like the serial monitor, it
+//!   takes its lines from the event bus.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::ansi;
+use crate::app::events::{self, Event};
+use crate::app::jobs::{self, JobInfo, JobState};
+use crate::app::serial_monitor::format_frame;
+use crate::project::LOAD_JOB_NAME;
+use crate::project::messages::MessageSchema;
+
+/// How many lines are shown if it isn't set.
+const DEFAULT_LINES: usize = 8;
+/// How often the overlay is redrawn while the main window isn't being used.
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// The state of the serial overlay.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialOverlay {
+    /// How many lines to show
+    pub lines: usize,
+    /// Where the overlay was last, in monitor space
+    pub position: Option<[f32; 2]>,
+    #[serde(skip)]
+    recent: VecDeque<String>,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+    /// Where the open overlay was created, so it isn't moved while it's dragged
+    #[serde(skip)]
+    created: Option<Option<[f32; 2]>>,
+}
+
+impl Default for SerialOverlay {
+    fn default() -> Self {
+        Self {
+            lines: DEFAULT_LINES,
+            position: None,
+            recent: VecDeque::new(),
+            events: None,
+            created: None,
+        }
+    }
+}
+
+/// The most recent load onto the board, if there's been one.
+pub fn flash_job(jobs: &[JobInfo]) -> Option<&JobInfo> {
+    jobs.iter().filter(|j| j.name == LOAD_JOB_NAME).max_by_key(|j| j.id)
+}
+
+/// A line about how a load onto the board went, or is going.
+pub fn flash_status(job: &JobInfo) -> String {
+    match &job.state {
+        JobState::Running => match job.progress {
+            Some(progress) => format!("flashing... {:.0}%", progress * 100.0),
+            None if job.status.is_empty() => "flashing...".to_string(),
+            None => format!("flashing... {}", job.status),
+        },
+        JobState::Finished => format!("flashed in {:.1} s", job.elapsed.as_secs_f32()),
+        JobState::Failed(e) => format!("flashing failed: {}", e),
+        JobState::Cancelled => "flashing cancelled".to_string(),
+    }
+}
+
+impl SerialOverlay {
+
+    /// Take in output, keeping the last lines of it. Output can have several lines in it,
+    /// or none.
+    pub fn push(&mut self, text: &str) {
+        for line in text.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+            self.recent.push_back(line.to_string());
+        }
+        while self.recent.len() > self.lines.max(1) {
+            self.recent.pop_front();
+        }
+    }
+
+    pub fn recent(&self) -> impl Iterator<Item = &str> {
+        self.recent.iter().map(String::as_str)
+    }
+
+    fn poll(&mut self, ctx: &egui::Context, schema: &MessageSchema) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        let mut text = String::new();
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                Event::SerialData(data) => text += &ansi::strip(&data),
+                Event::SerialFrame(frame) => text += &format_frame(&frame, schema),
+                Event::RttData(data) => text += &format!("rtt: {}\n", data.trim_end()),
+                _ => (),
+            }
+        }
+        self.push(&text);
+    }
+
+    /// Show the overlay in a window of its own, above the other apps. `open` is cleared
+    /// when it's closed.
+    pub fn show(&mut self, ctx: &egui::Context, open: &mut bool, schema: &MessageSchema) {
+        if !*open {
+            // stop listening, and start afresh next time
+            self.events = None;
+            self.created = None;
+            return;
+        }
+        self.poll(ctx, schema);
+        let flash = flash_job(&jobs::shared(ctx).jobs()).map(flash_status);
+        let position = *self.created.get_or_insert(self.position);
+        let mut builder = egui::ViewportBuilder::default()
+            .with_title("Iron Coder serial")
+            .with_inner_size([420.0, 40.0 + 16.0 * self.lines as f32])
+            .with_always_on_top();
+        if let Some(position) = position {
+            builder = builder.with_position(position);
+        }
+        let recent = &self.recent;
+        let mut moved = None;
+        let id = egui::ViewportId::from_hash_of("serial overlay");
+        ctx.show_viewport_immediate(id, builder, |ctx, class| {
+            let contents = |ui: &mut egui::Ui| {
+                if let Some(flash) = &flash {
+                    ui.label(egui::RichText::new(flash).strong());
+                }
+                if recent.is_empty() {
+                    ui.weak("no output yet");
+                }
+                for line in recent {
+                    ui.label(egui::RichText::new(line).monospace());
+                }
+            };
+            if class == egui::ViewportClass::Embedded {
+                // the backend can't open another window
+                egui::Window::new("Serial Overlay").open(open).resizable(false).show(ctx, contents);
+                return;
+            }
+            egui::CentralPanel::default().show(ctx, contents);
+            ctx.input(|input| {
+                if input.viewport().close_requested() {
+                    *open = false;
+                }
+                moved = input.viewport().outer_rect.map(|r| [r.min.x, r.min.y]);
+            });
+        });
+        if moved.is_some() {
+            self.position = moved;
+        }
+        ctx.request_repaint_after(REFRESH);
+    }
+
+    /// Show the overlay's settings in the Ui.
+    pub fn display_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("lines:");
+        if ui.add(egui::DragValue::new(&mut self.lines).clamp_range(1..=40)).changed() {
+            self.push("");
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Title: Iron Coder App Module - File Previews
+//! Description: Files opened from the project tree that aren't text, i.e. image assets,
+//!   firmware ELF files and raw binaries, are shown in a preview instead of the text
+//!   editor: images as themselves, ELF files as a summary of their segments and sections,
+//!   and anything else as a hex dump. Any of them can be shown as hex.
+
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use egui::{ColorImage, RichText};
+
+use crate::app::texture_cache;
+
+/// How much of the start of a file is looked at to tell text from binary.
+const SNIFF_BYTES: usize = 8 * 1024;
+/// The bytes on each line of a hex dump.
+pub const HEX_LINE_BYTES: usize = 16;
+
+/// What a file is, as far as opening it goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Text,
+    Image,
+    Elf,
+    Binary,
+}
+
+/// Tell what a file is from its extension and contents. Images go by extension, ELF files
+/// by their magic number, and anything with a NUL near the start or that isn't UTF-8 is
+/// binary.
+pub fn detect(path: &Path, bytes: &[u8]) -> FileKind {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    if ["png", "jpg", "jpeg", "gif", "bmp", "ico"].contains(&extension.as_str()) {
+        return FileKind::Image;
+    }
+    if bytes.starts_with(b"\x7fELF") {
+        return FileKind::Elf;
+    }
+    let start = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if start.contains(&0) || std::str::from_utf8(bytes).is_err() {
+        return FileKind::Binary;
+    }
+    FileKind::Text
+}
+
+/// A line of a hex dump: the offset, the bytes in hex, and the printable ones as text.
+pub fn hex_line(bytes: &[u8], offset: usize) -> String {
+    let line = bytes.get(offset..(offset + HEX_LINE_BYTES).min(bytes.len())).unwrap_or_default();
+    let hex: Vec<String> = (0..HEX_LINE_BYTES).map(|i| match line.get(i) {
+        Some(b) => format!("{:02x}", b),
+        None => "  ".to_string(),
+    }).collect();
+    let text: String = line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+    format!("{:08x}  {}  {}  |{}|", offset, hex[..8].join(" "), hex[8..].join(" "), text)
+}
+
+/// A segment of an ELF file, as loaded onto the board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElfSegment {
+    /// i.e. "LOAD"
+    pub kind: String,
+    pub vaddr: u64,
+    pub paddr: u64,
+    pub file_size: u64,
+    pub mem_size: u64,
+    /// i.e. "R X"
+    pub flags: String,
+}
+
+/// A section of an ELF file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElfSection {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+    /// Whether it takes up memory on the board
+    pub alloc: bool,
+}
+
+/// What's in an ELF file, like `readelf` and `size` show.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElfSummary {
+    /// 32 or 64
+    pub bits: u8,
+    /// i.e. "ARM"
+    pub machine: String,
+    /// i.e. "executable"
+    pub file_type: String,
+    pub entry: u64,
+    pub segments: Vec<ElfSegment>,
+    pub sections: Vec<ElfSection>,
+    /// Code and read-only data, data, and zeroed data, in bytes, like `size` counts them
+    pub text: u64,
+    pub data: u64,
+    pub bss: u64,
+}
+
+fn machine_name(machine: u16) -> String {
+    match machine {
+        0x03 => "x86".to_string(),
+        0x08 => "MIPS".to_string(),
+        0x28 => "ARM".to_string(),
+        0x3e => "x86-64".to_string(),
+        0x5e => "Xtensa".to_string(),
+        0xb7 => "AArch64".to_string(),
+        0xf3 => "RISC-V".to_string(),
+        other => format!("machine {:#x}", other),
+    }
+}
+
+fn segment_kind(kind: u32) -> String {
+    match kind {
+        0 => "NULL".to_string(),
+        1 => "LOAD".to_string(),
+        2 => "DYNAMIC".to_string(),
+        3 => "INTERP".to_string(),
+        4 => "NOTE".to_string(),
+        6 => "PHDR".to_string(),
+        7 => "TLS".to_string(),
+        0x6474e551 => "GNU_STACK".to_string(),
+        0x70000001 => "ARM_EXIDX".to_string(),
+        other => format!("{:#x}", other),
+    }
+}
+
+/// Read the headers of a little-endian ELF file, 32 or 64 bit.
+pub fn parse_elf(elf: &[u8]) -> Result<ElfSummary, String> {
+    if !elf.starts_with(b"\x7fELF") || elf.len() < 52 {
+        return Err("not an ELF file".to_string());
+    }
+    let bits = match elf[4] {
+        1 => 32,
+        2 => 64,
+        _ => return Err("unknown ELF class".to_string()),
+    };
+    if elf[5] != 1 {
+        return Err("only little-endian ELF files can be read".to_string());
+    }
+    let short = || "the ELF file is cut short".to_string();
+    let u16_at = |i: usize| elf.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).ok_or_else(short);
+    let u32_at = |i: usize| elf.get(i..i + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(short);
+    let u64_at = |i: usize| elf.get(i..i + 8).map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])).ok_or_else(short);
+    // addresses and offsets are the size of the class
+    let word_at = |i: usize| if bits == 32 { u32_at(i).map(u64::from) } else { u64_at(i) };
+    let (w, wide) = if bits == 32 { (4, false) } else { (8, true) };
+
+    let file_type = match u16_at(16)? {
+        1 => "relocatable",
+        2 => "executable",
+        3 => "shared object",
+        4 => "core dump",
+        _ => "unknown",
+    }.to_string();
+    let machine = machine_name(u16_at(18)?);
+    let entry = word_at(24)?;
+    let (phoff, shoff) = (word_at(24 + w)? as usize, word_at(24 + 2 * w)? as usize);
+    let sizes = 24 + 3 * w + 4;
+    let (phentsize, phnum) = (u16_at(sizes + 2)? as usize, u16_at(sizes + 4)? as usize);
+    let (shentsize, shnum, shstrndx) = (u16_at(sizes + 6)? as usize, u16_at(sizes + 8)? as usize, u16_at(sizes + 10)? as usize);
+
+    let mut segments = vec![];
+    for n in 0..phnum {
+        let p = phoff + n * phentsize;
+        // 64-bit program headers put the flags right after the type
+        let (flags, first) = match wide {
+            false => (u32_at(p + 24)?, p + 4),
+            true => (u32_at(p + 4)?, p + 8),
+        };
+        let at = |i: usize| first + i * w;
+        let flag = |bit: u32, c: char| if flags & bit != 0 { c } else { ' ' };
+        segments.push(ElfSegment {
+            kind: segment_kind(u32_at(p)?),
+            vaddr: word_at(at(1))?,
+            paddr: word_at(at(2))?,
+            file_size: word_at(at(3))?,
+            mem_size: word_at(at(4))?,
+            flags: format!("{}{}{}", flag(4, 'R'), flag(2, 'W'), flag(1, 'X')),
+        });
+    }
+
+    // (name offset, type, flags, addr, size) of each section
+    let mut headers = vec![];
+    for n in 0..shnum {
+        let s = shoff + n * shentsize;
+        headers.push((u32_at(s)? as usize, u32_at(s + 4)?, word_at(s + 8)?, word_at(s + 8 + w)?, word_at(s + 8 + 3 * w)?));
+    }
+    let names_at = match headers.get(shstrndx) {
+        Some(_) => word_at(shoff + shstrndx * shentsize + 8 + 2 * w)? as usize,
+        None => 0,
+    };
+    let name = |offset: usize| -> String {
+        let start = names_at + offset;
+        let bytes = elf.get(start..).unwrap_or_default();
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(0);
+        String::from_utf8_lossy(&bytes[..end]).to_string()
+    };
+    const SHF_WRITE: u64 = 0x1;
+    const SHF_ALLOC: u64 = 0x2;
+    const SHT_NOBITS: u32 = 8;
+    let (mut text, mut data, mut bss) = (0, 0, 0);
+    let mut sections = vec![];
+    for (name_offset, kind, flags, addr, size) in headers.into_iter().skip(1) {
+        let alloc = flags & SHF_ALLOC != 0;
+        if alloc {
+            match (kind == SHT_NOBITS, flags & SHF_WRITE != 0) {
+                (true, _) => bss += size,
+                (false, true) => data += size,
+                (false, false) => text += size,
+            }
+        }
+        sections.push(ElfSection { name: name(name_offset), addr, size, alloc });
+    }
+    Ok(ElfSummary { bits, machine, file_type, entry, segments, sections, text, data, bss })
+}
+
+/// What a preview shows.
+enum Content {
+    Image(Result<Arc<ColorImage>, String>),
+    Elf(Result<ElfSummary, String>),
+    Hex,
+}
+
+/// A file shown in a preview instead of the text editor.
+pub struct Preview {
+    pub kind: FileKind,
+    bytes: Vec<u8>,
+    content: Content,
+    /// Whether the bytes are shown as hex instead
+    show_hex: bool,
+}
+
+impl fmt::Debug for Preview {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Preview({:?}, {} bytes)", self.kind, self.bytes.len())
+    }
+}
+
+impl Preview {
+
+    /// The preview of a file that isn't text, or None if it is.
+    pub fn of(path: &Path, bytes: Vec<u8>) -> Option<Self> {
+        let kind = detect(path, &bytes);
+        let content = match kind {
+            FileKind::Text => return None,
+            FileKind::Image => Content::Image(image::load_from_memory(&bytes).map(|image| {
+                let image = image.to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                Arc::new(ColorImage::from_rgba_unmultiplied(size, image.as_flat_samples().as_slice()))
+            }).map_err(|e| e.to_string())),
+            FileKind::Elf => Content::Elf(parse_elf(&bytes)),
+            FileKind::Binary => Content::Hex,
+        };
+        Some(Self { kind, bytes, content, show_hex: false })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The ELF summary, if the file is an ELF file that could be read.
+    pub fn elf(&self) -> Option<&ElfSummary> {
+        match &self.content {
+            Content::Elf(Ok(summary)) => Some(summary),
+            _ => None,
+        }
+    }
+
+    /// Show the preview. `key` names the file's texture, if it's an image.
+    pub fn display(&mut self, ui: &mut egui::Ui, key: &str) {
+        ui.horizontal(|ui| {
+            let kind = match self.kind {
+                FileKind::Image => "image",
+                FileKind::Elf => "ELF file",
+                _ => "binary file",
+            };
+            ui.label(format!("{}, {} bytes", kind, self.bytes.len()));
+            if !matches!(self.content, Content::Hex) {
+                ui.toggle_value(&mut self.show_hex, "hex");
+            }
+        });
+        ui.separator();
+        if self.show_hex {
+            self.display_hex(ui);
+            return;
+        }
+        match &self.content {
+            Content::Image(Ok(image)) => {
+                ui.label(format!("{} × {} px", image.size[0], image.size[1]));
+                let cache = texture_cache::shared(ui.ctx());
+                let handle = cache.lock().unwrap().get_or_load(ui.ctx(), &format!("{}#preview", key), || (**image).clone());
+                egui::ScrollArea::both().auto_shrink([false; 2]).show(ui, |ui| {
+                    ui.add(egui::Image::from_texture(&handle).max_size(ui.available_size()).shrink_to_fit());
+                });
+            },
+            Content::Elf(Ok(summary)) => display_elf(ui, summary),
+            Content::Image(Err(e)) | Content::Elf(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, format!("couldn't read it: {}", e));
+                self.display_hex(ui);
+            },
+            Content::Hex => self.display_hex(ui),
+        }
+    }
+
+    /// The bytes as hex, laying out just the lines in view.
+    fn display_hex(&self, ui: &mut egui::Ui) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = self.bytes.len().div_ceil(HEX_LINE_BYTES);
+        egui::ScrollArea::both().id_source("hex_preview").auto_shrink([false; 2]).show_rows(ui, row_height, rows, |ui, rows| {
+            for row in rows {
+                ui.add(egui::Label::new(RichText::new(hex_line(&self.bytes, row * HEX_LINE_BYTES)).monospace()).wrap(false));
+            }
+        });
+    }
+}
+
+fn display_elf(ui: &mut egui::Ui, summary: &ElfSummary) {
+    egui::ScrollArea::both().id_source("elf_preview").auto_shrink([false; 2]).show(ui, |ui| {
+        egui::Grid::new("elf_summary").num_columns(2).show(ui, |ui| {
+            ui.label("type");
+            ui.label(format!("{}-bit {} {}", summary.bits, summary.machine, summary.file_type));
+            ui.end_row();
+            ui.label("entry point");
+            ui.monospace(format!("{:#010x}", summary.entry));
+            ui.end_row();
+            ui.label("size");
+            ui.label(format!("text {} B, data {} B, bss {} B", summary.text, summary.data, summary.bss));
+            ui.end_row();
+        });
+        ui.separator();
+        ui.strong("segments");
+        egui::Grid::new("elf_segments").striped(true).num_columns(6).show(ui, |ui| {
+            for heading in ["type", "virtual", "physical", "file size", "memory size", "flags"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for s in summary.segments.iter() {
+                ui.monospace(&s.kind);
+                ui.monospace(format!("{:#010x}", s.vaddr));
+                ui.monospace(format!("{:#010x}", s.paddr));
+                ui.monospace(s.file_size.to_string());
+                ui.monospace(s.mem_size.to_string());
+                ui.monospace(&s.flags);
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        ui.strong("sections on the board");
+        egui::Grid::new("elf_sections").striped(true).num_columns(3).show(ui, |ui| {
+            for heading in ["name", "address", "size"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for s in summary.sections.iter().filter(|s| s.alloc) {
+                ui.monospace(&s.name);
+                ui.monospace(format!("{:#010x}", s.addr));
+                ui.monospace(s.size.to_string());
+                ui.end_row();
+            }
+        });
+        let others: Vec<&str> = summary.sections.iter().filter(|s| !s.alloc && !s.name.is_empty()).map(|s| s.name.as_str()).collect();
+        if !others.is_empty() {
+            ui.weak(format!("and {} more sections: {}", others.len(), others.join(", ")));
+        }
+    });
+}
@@ -0,0 +1,370 @@
+//! Title: Iron Coder App Module - Device Clock
+//! Description: Setting the main board's real-time clock. The panel writes the code that
+//!   starts the RTC from the clock source in the board file, sets the device's time from
+//!   the host's over serial or the debug probe, and shows how far the device's time drifts
+//!   from the host's as the firmware reports it over serial.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event};
+use crate::app::jobs::{self, JobId, JobState};
+use crate::board::Board;
+use crate::board::rtc::{Rtc, RtcClockSource, RtcHal};
+use crate::project::{mcuboot, panic_dump};
+
+/// What the firmware sends before its time, in milliseconds since the unix epoch.
+pub const TIME_REPORT: &str = "IRONCODER TIME ";
+/// What comes before the time sent to the firmware over serial.
+pub const SET_TIME: &str = "IRONCODER SET-TIME ";
+/// The static the debug probe writes the time into.
+pub const SET_TIME_SYMBOL: &str = "IRON_CODER_SET_TIME";
+/// The most reports kept for working out the drift.
+const MAX_SAMPLES: usize = 600;
+/// How far apart the reports have to be for the drift to mean anything, in milliseconds.
+const MIN_DRIFT_SPAN_MS: u64 = 10_000;
+
+/// A time in milliseconds since the unix epoch.
+pub fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+/// The line that sets the device's time over serial.
+pub fn set_time_line(unix_ms: u64) -> String {
+    format!("{}{}\n", SET_TIME, unix_ms)
+}
+
+/// The device's time in a line it sent, which can come after a log prefix.
+pub fn parse_time_report(line: &str) -> Option<u64> {
+    let start = line.find(TIME_REPORT)?;
+    line[start + TIME_REPORT.len()..].split_whitespace().next()?.parse().ok()
+}
+
+/// The time as the two 32-bit words the probe writes, low word first.
+pub fn set_time_words(unix_ms: u64) -> [u32; 2] {
+    [unix_ms as u32, (unix_ms >> 32) as u32]
+}
+
+/// The prescaler of the nRF RTC for an 8 Hz tick, which its 24-bit counter takes 24 days
+/// to overflow at. The prescaler has 12 bits.
+fn nrf_prescaler(clock_hz: u64) -> u32 {
+    (clock_hz / 8).clamp(1, 4096) as u32 - 1
+}
+
+/// The code that starts the RTC from its clock source for the board's HAL.
+pub fn rtc_init_code(hal: RtcHal, rtc: &Rtc) -> String {
+    match hal {
+        RtcHal::NrfHal => {
+            // the low-frequency clock runs at 32.768 kHz from every source
+            let clock_hz = rtc.clock.map_or(32_768, |c| c.hz());
+            let prescaler = nrf_prescaler(clock_hz);
+            let source = match rtc.source {
+                RtcClockSource::Crystal => "    .set_lfclk_src_external(hal::clocks::LfOscConfiguration::NoExternalNoBypass)\n",
+                RtcClockSource::InternalRc => "    .set_lfclk_src_rc()\n",
+                // the synthesized clock needs the high-frequency crystal running
+                RtcClockSource::MainClock => "    .enable_ext_hfosc()\n    .set_lfclk_src_synth()\n",
+            };
+            format!(
+"// the RTC counts the low-frequency clock, started here from the {source_name}
+let clocks = hal::clocks::Clocks::new(p.CLOCK)
+{source}    .start_lfclk();
+// the counter ticks at {tick} Hz, and its overflows have to be counted to keep time
+let mut rtc = hal::rtc::Rtc::new(p.RTC0, {prescaler}).unwrap();
+rtc.enable_event(hal::rtc::RtcInterrupt::Overflow);
+rtc.enable_interrupt(hal::rtc::RtcInterrupt::Overflow, None);
+rtc.enable_counter();
+", source_name = rtc.source, tick = clock_hz / (prescaler as u64 + 1))
+        },
+        RtcHal::Rp2040Hal => {
+            let mut code = String::new();
+            if rtc.source != RtcClockSource::MainClock {
+                code += &format!("// init_clocks_and_plls clocks the RTC from the crystal oscillator; clocking it from the\n// {} means setting up clocks.rtc_clock by hand\n", rtc.source);
+            }
+            code + "// the RTC counts clocks.rtc_clock, a 46.875 kHz clock divided from the crystal oscillator
+let initial = hal::rtc::DateTime {
+    year: 2024, month: 1, day: 1, day_of_week: hal::rtc::DayOfWeek::Monday,
+    hour: 0, minute: 0, second: 0,
+};
+let rtc = hal::rtc::RealTimeClock::new(pac.RTC, clocks.rtc_clock, &mut pac.RESETS, initial).unwrap();
+"
+        },
+    }
+}
+
+/// The firmware's side of setting and reporting its time.
+pub fn sync_code() -> String {
+    format!(
+"/// Set by Iron Coder over the debug probe to the unix time in milliseconds, and back to 0
+/// once the firmware has taken it
+#[no_mangle]
+pub static mut {symbol}: u64 = 0;
+
+/// The time Iron Coder set over the debug probe, if it did since the last call.
+pub fn take_set_time() -> Option<u64> {{
+    let ms = unsafe {{ core::ptr::read_volatile(core::ptr::addr_of!({symbol})) }};
+    if ms == 0 {{
+        return None;
+    }}
+    unsafe {{ core::ptr::write_volatile(core::ptr::addr_of_mut!({symbol}), 0) }};
+    Some(ms)
+}}
+
+/// The time in a line Iron Coder sent over serial.
+pub fn parse_set_time(line: &str) -> Option<u64> {{
+    line.trim().strip_prefix(\"{set}\")?.parse().ok()
+}}
+
+/// Send the device's time, for Iron Coder to show how far it drifts.
+pub fn report_time(port: &mut impl core::fmt::Write, unix_ms: u64) {{
+    let _ = writeln!(port, \"{report}{{}}\", unix_ms);
+}}
+", symbol = SET_TIME_SYMBOL, set = SET_TIME, report = TIME_REPORT)
+}
+
+/// How the device's time compares with the host's, from the times it reported.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DriftTracker {
+    /// When each report came in on the host, and how far ahead of the host it was, in
+    /// milliseconds
+    samples: Vec<(u64, i64)>,
+}
+
+impl DriftTracker {
+
+    pub fn push(&mut self, host_ms: u64, device_ms: u64) {
+        if self.samples.len() >= MAX_SAMPLES {
+            self.samples.remove(0);
+        }
+        self.samples.push((host_ms, device_ms as i64 - host_ms as i64));
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// How far ahead of the host the device was in its last report, in milliseconds.
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.samples.last().map(|(_, offset)| *offset)
+    }
+
+    /// How fast the device's clock gains on the host's, in parts per million, fitted to
+    /// the reports. `None` until they're far enough apart to tell.
+    pub fn drift_ppm(&self) -> Option<f64> {
+        let (first, last) = (self.samples.first()?.0, self.samples.last()?.0);
+        if last.saturating_sub(first) < MIN_DRIFT_SPAN_MS {
+            return None;
+        }
+        let n = self.samples.len() as f64;
+        let points = self.samples.iter().map(|(host, offset)| (host.saturating_sub(first) as f64, *offset as f64));
+        let (sum_x, sum_y) = points.clone().fold((0.0, 0.0), |(x, y), (px, py)| (x + px, y + py));
+        let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+        let (cov, var) = points.fold((0.0, 0.0), |(c, v), (x, y)| (c + (x - mean_x) * (y - mean_y), v + (x - mean_x).powi(2)));
+        Some(cov / var * 1e6)
+    }
+}
+
+/// The state of the device clock window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RtcPanel {
+    /// The HAL to write code for, if not the board's
+    hal: Option<RtcHal>,
+    /// The serial port to set the time over, if not the serial monitor's
+    port: String,
+    /// The probe-rs chip name, if not the board's
+    chip: String,
+    #[serde(skip)]
+    drift: DriftTracker,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+/// Show some code with a button to copy it.
+fn code_block(ui: &mut egui::Ui, mut code: String) {
+    if ui.button("copy").clicked() {
+        ui.output_mut(|o| o.copied_text = code.clone());
+    }
+    ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+}
+
+impl RtcPanel {
+
+    /// The drift worked out from the reports so far.
+    pub fn drift(&self) -> &DriftTracker {
+        &self.drift
+    }
+
+    /// Take in a line the device sent over serial, at `host_ms` on the host.
+    pub fn receive(&mut self, line: &str, host_ms: u64) {
+        if let Some(device_ms) = parse_time_report(line) {
+            self.drift.push(host_ms, device_ms);
+        }
+    }
+
+    /// Send the host's time down a serial port.
+    fn set_over_serial(&mut self, port: &str) {
+        let line = set_time_line(unix_ms(SystemTime::now()));
+        let result = fs::OpenOptions::new().write(true).open(port)
+            .and_then(|mut file| file.write_all(line.as_bytes()).and_then(|_| file.flush()));
+        self.status = Some(match result {
+            Ok(()) => Ok(format!("sent the time to {}", port)),
+            Err(e) => Err(format!("couldn't send the time to {}: {}", port, e)),
+        });
+        // the old offsets were from before the time was set
+        self.drift.clear();
+    }
+
+    /// Write the host's time into the firmware's static with probe-rs.
+    fn set_over_probe(&mut self, ctx: &egui::Context, chip: &str, project_dir: &Path) -> Result<(), String> {
+        let firmware = mcuboot::find_firmware(project_dir).map_err(|e| format!("{}; build the firmware first", e))?;
+        let elf = fs::read(&firmware).map_err(|e| format!("couldn't read {}: {}", firmware.display(), e))?;
+        let address = panic_dump::symbol_address(&elf, SET_TIME_SYMBOL)?;
+        let [low, high] = set_time_words(unix_ms(SystemTime::now()));
+        let cmd = duct::cmd!("probe-rs", "write", "--chip", chip, "b32", format!("{:#x}", address), low.to_string(), high.to_string());
+        self.job = Some(jobs::shared(ctx).spawn("set the device time", Some(ctx), move |job| {
+            let (tx, _rx) = std::sync::mpsc::channel();
+            jobs::run_commands(&[cmd], &tx, job)
+        }));
+        self.drift.clear();
+        Ok(())
+    }
+
+    fn display_setting(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, board: &Board, project_dir: Option<&Path>, monitor_port: &str) {
+        if let Some(id) = self.job {
+            match jobs::shared(ctx).state(id) {
+                Some(JobState::Running) => (),
+                state => {
+                    self.status = match state {
+                        Some(JobState::Finished) => Some(Ok("set the time over the debug probe".to_string())),
+                        Some(JobState::Failed(e)) => Some(Err(format!("couldn't set the time: {}", e))),
+                        _ => None,
+                    };
+                    self.job = None;
+                },
+            }
+        }
+        let board_chip = board.chip_name().unwrap_or_default().to_string();
+        egui::Grid::new("rtc_set_time").num_columns(3).show(ui, |ui| {
+            ui.label("serial port:");
+            ui.add(egui::TextEdit::singleline(&mut self.port).hint_text(monitor_port).desired_width(140.0));
+            let port = if self.port.trim().is_empty() { monitor_port.to_string() } else { self.port.trim().to_string() };
+            if ui.add_enabled(!port.is_empty(), egui::Button::new("set time over serial")).clicked() {
+                self.set_over_serial(&port);
+            }
+            ui.end_row();
+            ui.label("chip:");
+            ui.add(egui::TextEdit::singleline(&mut self.chip).hint_text(&board_chip).desired_width(140.0));
+            let chip = if self.chip.trim().is_empty() { board_chip.clone() } else { self.chip.trim().to_string() };
+            let enabled = self.job.is_none() && !chip.is_empty() && project_dir.is_some();
+            if ui.add_enabled(enabled, egui::Button::new("set time over probe")).clicked() {
+                if let Err(e) = self.set_over_probe(ctx, &chip, project_dir.unwrap()) {
+                    self.status = Some(Err(e));
+                }
+            }
+            ui.end_row();
+        });
+        match &self.status {
+            Some(Ok(done)) => { ui.label(done); },
+            Some(Err(e)) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            None => (),
+        }
+    }
+
+    fn display_drift(&mut self, ui: &mut egui::Ui, rtc: &Rtc) {
+        let Some(offset) = self.drift.offset_ms() else {
+            ui.label(format!("the device's time shows here once the firmware sends \"{}<unix ms>\" lines over serial", TIME_REPORT));
+            return;
+        };
+        egui::Grid::new("rtc_drift").num_columns(2).show(ui, |ui| {
+            ui.label("device time:");
+            ui.label(format!("{:+} ms from the host", offset));
+            ui.end_row();
+            ui.label("drift:");
+            match self.drift.drift_ppm() {
+                Some(ppm) => {
+                    let text = format!("{:+.1} ppm ({:+.2} s a day)", ppm, ppm * 86_400e-6);
+                    if ppm.abs() > rtc.source.typical_ppm() {
+                        ui.colored_label(ui.style().visuals.warn_fg_color, text)
+                            .on_hover_text(format!("more than the {} ppm a {} is usually off by", rtc.source.typical_ppm(), rtc.source));
+                    } else {
+                        ui.label(text);
+                    }
+                },
+                None => { ui.weak(format!("{} reports, waiting for more", self.drift.len())); },
+            }
+            ui.end_row();
+        });
+        if ui.button("clear").clicked() {
+            self.drift.clear();
+        }
+    }
+
+    /// Stop listening for the device's time reports, for when the window is closed.
+    pub fn close(&mut self) {
+        self.events = None;
+    }
+
+    /// Show the panel for the main board. `monitor_port` is the serial monitor's port.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, board: Option<&Board>, project_dir: Option<&Path>, monitor_port: &str) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        let lines: Vec<String> = rx.try_iter().filter_map(|event| match event {
+            Event::SerialData(line) => Some(line),
+            _ => None,
+        }).collect();
+        let now = unix_ms(SystemTime::now());
+        for line in lines {
+            self.receive(&line, now);
+        }
+        let Some(board) = board else {
+            ui.label("pick a main board to set up its real-time clock");
+            return;
+        };
+        let Some(rtc) = board.rtc() else {
+            ui.label(format!("the {} doesn't describe its real-time clock", board.get_name()));
+            return;
+        };
+        let clock = rtc.clock.map(|c| format!(" at {}", c)).unwrap_or_default();
+        ui.label(format!("The {}'s RTC is clocked by its {}{}, which is usually off by up to {} ppm.", board.get_name(), rtc.source, clock, rtc.source.typical_ppm()));
+        ui.separator();
+        ui.heading("Setup");
+        let mut hal = self.hal.or(rtc.hal);
+        egui::ComboBox::from_id_source("rtc_hal")
+        .selected_text(hal.map(|h| h.to_string()).unwrap_or("pick a HAL".to_string()))
+        .show_ui(ui, |ui| {
+            for h in enum_iterator::all::<RtcHal>() {
+                if ui.selectable_value(&mut hal, Some(h), h.to_string()).clicked() {
+                    self.hal = (Some(h) != rtc.hal).then_some(h);
+                }
+            }
+        });
+        if let Some(hal) = hal {
+            code_block(ui, rtc_init_code(hal, &rtc));
+        }
+        egui::CollapsingHeader::new("firmware code for setting and reporting the time").show(ui, |ui| {
+            code_block(ui, sync_code());
+        });
+        ui.separator();
+        ui.heading("Set the time");
+        self.display_setting(ctx, ui, board, project_dir, monitor_port);
+        ui.separator();
+        ui.heading("Drift");
+        self.display_drift(ui, &rtc);
+    }
+}
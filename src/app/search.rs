@@ -0,0 +1,366 @@
+//! Title: Iron Coder App Module - Search
+//! Description: One search box for everything: the boards, their examples and crates,
+//!   the items in the project's code, and the things the app can do. Results are
+//!   matched fuzzily, grouped by kind, and picked with the keyboard.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+use enum_iterator::Sequence;
+
+use crate::board::Board;
+use crate::board::examples::Example;
+use crate::project::local_sources;
+
+/// Opens the search.
+pub const SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::P);
+/// The most results shown of each kind.
+const MAX_PER_CATEGORY: usize = 6;
+
+/// The kinds of things that can be found, in the order they're listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Sequence)]
+pub enum SearchCategory {
+    Action,
+    Board,
+    Example,
+    Symbol,
+    Crate,
+}
+
+impl fmt::Display for SearchCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchCategory::Action => write!(f, "Actions"),
+            SearchCategory::Board => write!(f, "Boards"),
+            SearchCategory::Example => write!(f, "Examples"),
+            SearchCategory::Symbol => write!(f, "Symbols"),
+            SearchCategory::Crate => write!(f, "Crates"),
+        }
+    }
+}
+
+/// What picking a result does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchTarget {
+    /// Do one of the app's actions, by its name
+    Action(String),
+    /// Show the board in the board selector
+    Board(String),
+    /// Open a file in the code editor
+    File(PathBuf),
+    /// Open the documentation of a crate
+    Crate(String),
+}
+
+/// Something that can be found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchItem {
+    pub category: SearchCategory,
+    pub title: String,
+    /// More about it, which is searched too
+    pub detail: String,
+    pub target: SearchTarget,
+}
+
+/// How well `query` matches `text`: its characters have to appear in `text` in order,
+/// in any case. Runs of characters and characters at the start of words score higher.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    let text: Vec<char> = text.chars().collect();
+    let Some(first) = query.first() else {
+        return Some(0);
+    };
+    // the rest of the query is matched as early as it can be, from each place the first
+    // character of it is
+    (0..text.len())
+        .filter(|&i| same_letter(text[i], *first))
+        .filter_map(|start| score_from(&query, &text, start))
+        .max()
+}
+
+fn same_letter(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase())
+}
+
+fn score_from(query: &[char], text: &[char], start: usize) -> Option<i32> {
+    // the further into the text the match starts, the less it's worth
+    let mut score = -(start.min(10) as i32);
+    let mut previous: Option<usize> = None;
+    let mut position = start;
+    for q in query {
+        let found = (position..text.len()).find(|&i| same_letter(text[i], *q))?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        let at_word_start = found == 0
+            || !text[found - 1].is_alphanumeric()
+            || (text[found - 1].is_lowercase() && text[found].is_uppercase());
+        if at_word_start {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// How well `query` matches an item. The title counts for more than the detail.
+fn item_score(query: &str, item: &SearchItem) -> Option<i32> {
+    let title = fuzzy_score(query, &item.title).map(|s| s * 2);
+    let detail = fuzzy_score(query, &item.detail);
+    title.max(detail)
+}
+
+/// The results for `query`, best first within each kind, and the kinds in order. An
+/// empty query lists the actions.
+pub fn search<'a>(items: &'a [SearchItem], query: &str) -> Vec<&'a SearchItem> {
+    let mut results = Vec::new();
+    for category in enum_iterator::all::<SearchCategory>() {
+        let mut found: Vec<(i32, &SearchItem)> = items.iter()
+            .filter(|item| item.category == category)
+            .filter_map(|item| {
+                if query.trim().is_empty() {
+                    (category == SearchCategory::Action).then_some((0, item))
+                } else {
+                    item_score(query, item).map(|score| (score, item))
+                }
+            })
+            .collect();
+        // stable, so equal scores stay in the order they were indexed in
+        found.sort_by_key(|(score, _)| -score);
+        results.extend(found.into_iter().take(MAX_PER_CATEGORY).map(|(_, item)| item));
+    }
+    results
+}
+
+/// The title of an example, and its description or else the first line of its README as
+/// what it does.
+fn example_item(board: &Board, example: &Example) -> SearchItem {
+    let path = &example.path;
+    let readme = fs::read_to_string(path.join("README.md")).unwrap_or_default();
+    let summary = example.description().map(str::to_string).unwrap_or_else(|| {
+        readme.lines().map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#') && !l.chars().all(|c| c == '=' || c == '-'))
+            .unwrap_or_default()
+            .to_string()
+    });
+    let main = [path.join("src").join("main.rs"), path.join("README.md")].into_iter().find(|p| p.exists());
+    SearchItem {
+        category: SearchCategory::Example,
+        title: example.title(),
+        detail: format!("{} {}", board.get_name(), summary).trim().to_string(),
+        target: SearchTarget::File(main.unwrap_or_else(|| path.to_path_buf())),
+    }
+}
+
+/// The named items in a Rust source file.
+pub fn symbols(code: &str) -> Vec<(String, &'static str)> {
+    let Ok(file) = syn::parse_file(code) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    fn collect(items: &[syn::Item], found: &mut Vec<(String, &'static str)>) {
+        for item in items {
+            let named = match item {
+                syn::Item::Fn(f) => Some((f.sig.ident.to_string(), "fn")),
+                syn::Item::Struct(s) => Some((s.ident.to_string(), "struct")),
+                syn::Item::Enum(e) => Some((e.ident.to_string(), "enum")),
+                syn::Item::Trait(t) => Some((t.ident.to_string(), "trait")),
+                syn::Item::Const(c) => Some((c.ident.to_string(), "const")),
+                syn::Item::Static(s) => Some((s.ident.to_string(), "static")),
+                syn::Item::Type(t) => Some((t.ident.to_string(), "type")),
+                syn::Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        collect(items, found);
+                    }
+                    Some((m.ident.to_string(), "mod"))
+                },
+                syn::Item::Impl(i) => {
+                    for item in &i.items {
+                        if let syn::ImplItem::Fn(f) = item {
+                            found.push((f.sig.ident.to_string(), "fn"));
+                        }
+                    }
+                    None
+                },
+                _ => None,
+            };
+            found.extend(named);
+        }
+    }
+    collect(&file.items, &mut found);
+    found
+}
+
+/// The Rust files under a directory, in a stable order, leaving out build output and
+/// hidden directories.
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name == "target" || name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+/// Gather everything that can be searched for.
+pub fn index(actions: &[&str], boards: &[Board], project_dir: Option<&Path>) -> Vec<SearchItem> {
+    let mut items: Vec<SearchItem> = actions.iter().map(|action| SearchItem {
+        category: SearchCategory::Action,
+        title: action.to_string(),
+        detail: String::new(),
+        target: SearchTarget::Action(action.to_string()),
+    }).collect();
+    let mut crates: Vec<(String, String)> = Vec::new();
+    for board in boards {
+        items.push(SearchItem {
+            category: SearchCategory::Board,
+            title: board.label(),
+            detail: [board.manufacturer(), board.cpu().unwrap_or_default()].join(" ").trim().to_string(),
+            target: SearchTarget::Board(board.get_name().to_string()),
+        });
+        items.extend(board.examples().iter().map(|e| example_item(board, e)));
+        for name in board.related_crates().into_iter().chain(board.required_crates()).flatten() {
+            match crates.iter_mut().find(|(c, _)| *c == name) {
+                Some((_, used_by)) => *used_by += &format!(", {}", board.get_name()),
+                None => crates.push((name, board.get_name().to_string())),
+            }
+        }
+    }
+    items.extend(crates.into_iter().map(|(name, used_by)| SearchItem {
+        category: SearchCategory::Crate,
+        title: name.clone(),
+        detail: format!("for {}", used_by),
+        target: SearchTarget::Crate(name),
+    }));
+    if let Some(project_dir) = project_dir {
+        // the project's own code, then the code of its submodules and path dependencies,
+        // which is shown under their names
+        let mut roots = vec![(project_dir.join("src"), project_dir.to_path_buf(), String::new())];
+        for source in local_sources::find(project_dir) {
+            // a path dependency in a submodule is searched with the submodule
+            if !roots.iter().any(|(dir, _, _)| source.path.starts_with(dir)) {
+                roots.push((source.path.clone(), source.path, format!("{}/", source.name)));
+            }
+        }
+        for (dir, base, prefix) in roots {
+            let mut files = Vec::new();
+            rust_files(&dir, &mut files);
+            for file in files {
+                let code = fs::read_to_string(&file).unwrap_or_default();
+                let relative = format!("{}{}", prefix, file.strip_prefix(&base).unwrap_or(&file).display());
+                items.extend(symbols(&code).into_iter().map(|(name, kind)| SearchItem {
+                    category: SearchCategory::Symbol,
+                    title: name,
+                    detail: format!("{} in {}", kind, relative),
+                    target: SearchTarget::File(file.clone()),
+                }));
+            }
+        }
+    }
+    items
+}
+
+/// The state of the search box.
+#[derive(Default)]
+pub struct SearchPanel {
+    pub open: bool,
+    query: String,
+    selected: usize,
+    items: Vec<SearchItem>,
+}
+
+impl SearchPanel {
+
+    /// Open the search over `items`, keeping the last query.
+    pub fn show(&mut self, items: Vec<SearchItem>) {
+        self.open = true;
+        self.items = items;
+        self.selected = 0;
+    }
+
+    /// Show the search box and its results. Returns what was picked, if anything.
+    pub fn display(&mut self, ctx: &egui::Context) -> Option<SearchTarget> {
+        if !self.open {
+            return None;
+        }
+        let mut picked = None;
+        egui::Window::new("Search")
+        .collapsible(false)
+        .resizable(false)
+        .title_bar(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+        .default_width(480.0)
+        .show(ctx, |ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut self.query)
+                .hint_text("search boards, examples, symbols, crates and actions")
+                .desired_width(f32::INFINITY));
+            response.request_focus();
+            if response.changed() {
+                self.selected = 0;
+            }
+            let results = search(&self.items, &self.query);
+            let (up, down, enter, escape) = ctx.input_mut(|i| (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.consume_key(Modifiers::NONE, Key::Enter),
+                i.consume_key(Modifiers::NONE, Key::Escape),
+            ));
+            if down && self.selected + 1 < results.len() {
+                self.selected += 1;
+            }
+            if up {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            if escape {
+                self.open = false;
+            }
+            if enter {
+                picked = results.get(self.selected).map(|item| item.target.clone());
+            }
+            ui.separator();
+            if results.is_empty() {
+                ui.weak("nothing found");
+            }
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                let mut category = None;
+                for (i, item) in results.iter().enumerate() {
+                    if category != Some(item.category) {
+                        category = Some(item.category);
+                        ui.label(egui::RichText::new(item.category.to_string()).small().weak());
+                    }
+                    let selected = i == self.selected;
+                    let text = egui::RichText::new(&item.title).strong();
+                    let response = ui.horizontal(|ui| {
+                        let response = ui.selectable_label(selected, text);
+                        ui.weak(&item.detail);
+                        response
+                    }).inner;
+                    if selected && (up || down) {
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() {
+                        picked = results.get(i).map(|item| item.target.clone());
+                    }
+                }
+            });
+        });
+        if picked.is_some() {
+            self.open = false;
+        }
+        picked
+    }
+}
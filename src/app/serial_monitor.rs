@@ -0,0 +1,292 @@
+//! Title: Iron Coder App Module - Serial Monitor
+//! Description: A minimal serial monitor. A background job reads lines from a serial
+//!   device and publishes them on the event bus as SerialData events, which this panel
+//!   subscribes to like any other consumer. Devices sending postcard messages instead
+//!   of text are read as COBS frames, which are decoded with the project's message schema.
+//!   Text is shown in the colors the firmware logs with ANSI escapes, and panics kept
+//!   over a reset by panic-persist are listed on their own.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use enum_iterator::Sequence;
+
+use crate::app::ansi::{self, AnsiParser, StyledLine};
+use crate::app::events::{self, Event, EventBus};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::project::logging;
+use crate::project::lorawan::to_hex;
+use crate::project::messages::{self, Direction, MessageSchema};
+
+/// The most bytes of serial output we keep around for display.
+const MAX_BUFFER_LEN: usize = 64 * 1024;
+
+/// The baud rate most boards' USB serial and UART examples use.
+const DEFAULT_BAUD: u32 = 115_200;
+
+/// How long a read waits for data before checking whether the job was cancelled.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How the data from the device is split up.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Framing {
+    /// Lines of text
+    #[default]
+    Lines,
+    /// postcard messages in COBS frames, ending in a zero byte
+    Postcard,
+}
+
+impl std::fmt::Display for Framing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Framing::Lines => write!(f, "text lines"),
+            Framing::Postcard => write!(f, "postcard messages"),
+        }
+    }
+}
+
+/// Show a frame as the message it holds, or as hex with the reason it didn't decode.
+pub fn format_frame(frame: &[u8], schema: &MessageSchema) -> String {
+    let decoded = messages::cobs_decode(frame).and_then(|bytes| schema.decode(Direction::ToHost, &bytes));
+    match decoded {
+        Ok(message) => format!("{}\n", message),
+        Err(e) => format!("[{}] ({})\n", to_hex(frame), e),
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct SerialMonitor {
+    /// The serial device to read from, i.e. /dev/ttyACM0 or COM3
+    pub port: String,
+    pub baud: u32,
+    pub framing: Framing,
+    /// The lines received, without their escape sequences
+    #[serde(skip)]
+    lines: VecDeque<StyledLine>,
+    /// The bytes of text in `lines`
+    #[serde(skip)]
+    len: usize,
+    /// What's been received of the line being sent
+    #[serde(skip)]
+    partial: String,
+    #[serde(skip)]
+    parser: AnsiParser,
+    /// The panics from before a reset that the firmware sent, oldest first
+    #[serde(skip)]
+    panics: Vec<String>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+}
+
+impl Default for SerialMonitor {
+    fn default() -> Self {
+        Self {
+            port: String::new(),
+            baud: DEFAULT_BAUD,
+            framing: Framing::default(),
+            lines: VecDeque::new(),
+            len: 0,
+            partial: String::new(),
+            parser: AnsiParser::default(),
+            panics: Vec::new(),
+            job: None,
+            events: None,
+        }
+    }
+}
+
+impl SerialMonitor {
+
+    fn is_connected(&self, ctx: &egui::Context) -> bool {
+        match self.job {
+            Some(id) => jobs::shared(ctx).state(id) == Some(JobState::Running),
+            None => false,
+        }
+    }
+
+    /// Start reading from the configured port on a background job.
+    pub fn connect(&mut self, ctx: &egui::Context) {
+        let port = self.port.trim().to_string();
+        let bus = events::shared(ctx);
+        let name = format!("serial monitor ({})", self.port);
+        let (baud, framing) = (self.baud, self.framing);
+        self.job = Some(jobs::shared(ctx).spawn(&name, Some(ctx), move |job| {
+            read_serial(&port, baud, framing, &bus, job)
+        }));
+    }
+
+    pub fn disconnect(&mut self, ctx: &egui::Context) {
+        if let Some(id) = self.job.take() {
+            jobs::shared(ctx).cancel(id);
+        }
+    }
+
+    /// Stop reading from the device and stop listening for its data, for when the
+    /// monitor is closed. Nothing piles up on the bus for a monitor that isn't shown.
+    pub fn close(&mut self, ctx: &egui::Context) {
+        self.disconnect(ctx);
+        self.events = None;
+    }
+
+    /// Take in some output from the device, which can be part of a line or several lines.
+    pub fn receive(&mut self, data: &str) {
+        self.partial += data;
+        let rest = match self.partial.rfind('\n') {
+            Some(end) => self.partial.split_off(end + 1),
+            // a device that never ends its lines still gets them shown
+            None if self.partial.len() > MAX_BUFFER_LEN => String::new(),
+            None => return,
+        };
+        for line in self.partial.lines() {
+            let line = self.parser.line(line);
+            if let Some(message) = line.text.strip_prefix(logging::PANIC_MARKER) {
+                self.panics.push(message.trim_end().to_string());
+            }
+            self.len += line.text.len();
+            self.lines.push_back(line);
+        }
+        self.partial = rest;
+        while self.len > MAX_BUFFER_LEN {
+            let Some(oldest) = self.lines.pop_front() else { break };
+            self.len -= oldest.text.len();
+        }
+    }
+
+    /// The lines received, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &StyledLine> {
+        self.lines.iter()
+    }
+
+    /// The line being received, as far as it's come.
+    pub fn partial_line(&self) -> Option<StyledLine> {
+        (!self.partial.is_empty()).then(|| self.parser.clone().line(&self.partial))
+    }
+
+    /// The panics from before a reset that panic-persist kept, as the firmware sent them.
+    pub fn panics(&self) -> &[String] {
+        &self.panics
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.len = 0;
+        self.partial.clear();
+        self.parser = AnsiParser::default();
+    }
+
+    /// Show the port selection and the received data in the Ui. Frames are decoded
+    /// with `schema`.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, schema: &MessageSchema) {
+        // drain the events we're interested in
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        let received: Vec<String> = rx.try_iter().filter_map(|event| match event {
+            Event::SerialData(data) => Some(data),
+            Event::SerialFrame(frame) => Some(format_frame(&frame, schema)),
+            _ => None,
+        }).collect();
+        for data in received {
+            self.receive(&data);
+        }
+
+        let connected = self.is_connected(ctx);
+        ui.horizontal(|ui| {
+            ui.label("port:");
+            ui.add_enabled(!connected, egui::TextEdit::singleline(&mut self.port).hint_text("/dev/ttyACM0"));
+            ui.add_enabled(!connected, egui::DragValue::new(&mut self.baud).clamp_range(300..=4_000_000).suffix(" baud"));
+            ui.add_enabled_ui(!connected, |ui| {
+                egui::ComboBox::from_id_source("serial_framing")
+                .selected_text(self.framing.to_string())
+                .show_ui(ui, |ui| {
+                    for framing in enum_iterator::all::<Framing>() {
+                        ui.selectable_value(&mut self.framing, framing, framing.to_string());
+                    }
+                });
+            });
+            if connected {
+                if ui.button("disconnect").clicked() {
+                    self.disconnect(ctx);
+                }
+            } else if ui.button("connect").clicked() {
+                self.connect(ctx);
+            }
+            if ui.button("clear").clicked() {
+                self.clear();
+            }
+        });
+        if !self.panics.is_empty() {
+            let mut clear = false;
+            egui::CollapsingHeader::new(format!("persisted panics ({})", self.panics.len())).default_open(true).show(ui, |ui| {
+                for panic in self.panics.iter() {
+                    ui.colored_label(ui.visuals().error_fg_color, panic);
+                }
+                clear = ui.button("clear panics").clicked();
+            });
+            if clear {
+                self.panics.clear();
+            }
+        }
+        ui.separator();
+        let partial = self.partial_line();
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = self.lines.len() + partial.is_some() as usize;
+        egui::ScrollArea::both()
+        .auto_shrink([false; 2])
+        .stick_to_bottom(true)
+        .show_rows(ui, row_height, rows, |ui, rows| {
+            for row in rows {
+                let Some(line) = self.lines.get(row).or(partial.as_ref()) else { continue };
+                let job = ansi::layout_job(line, 0..line.text.len(), font.clone(), ui.visuals().text_color(), ui.visuals());
+                ui.add(egui::Label::new(job).wrap(false));
+            }
+        });
+    }
+}
+
+/// Read lines or frames from the serial device until the job is cancelled or the device
+/// goes away. Reads time out now and then, so cancelling doesn't wait for data.
+fn read_serial(port: &str, baud: u32, framing: Framing, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    let serial = serialport::new(port, baud)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .map_err(|e| format!("couldn't open {}: {}", port, e))?;
+    let mut reader = BufReader::new(serial);
+    let mut line = Vec::new();
+    let delimiter = match framing {
+        Framing::Lines => b'\n',
+        Framing::Postcard => 0,
+    };
+    while !job.is_cancelled() {
+        // what's read before a timeout is kept, and the line carries on after it
+        match reader.read_until(delimiter, &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                let event = match framing {
+                    Framing::Lines => Event::SerialData(String::from_utf8_lossy(&line).to_string()),
+                    Framing::Postcard => {
+                        if line.last() == Some(&0) {
+                            line.pop();
+                        }
+                        // back to back delimiters are just padding
+                        if line.is_empty() {
+                            continue;
+                        }
+                        Event::SerialFrame(line.clone())
+                    },
+                };
+                line.clear();
+                bus.publish(event);
+                job.request_repaint();
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::WouldBlock) => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
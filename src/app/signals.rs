@@ -0,0 +1,304 @@
+//! Title: Iron Coder App Module - Signals
+//! Description: A viewer for analog signals streamed from a board, i.e. ADC samples that
+//!   helper firmware prints over the serial monitor, or lines from a network device's
+//!   telemetry stream. Each line holds a sample per channel, either as `name=value` pairs
+//!   ("adc0=2048 adc1=17") or as bare numbers ("2048, 17", read as channels ch0, ch1...).
+//!   Channels are plotted with their statistics, and can be calibrated against known
+//!   references into the project's calibration module.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::ansi;
+use crate::app::events::{self, Event};
+use crate::app::toolbox::{format_si, parse_si};
+use crate::board::Adc;
+use crate::project::calibration::{Calibration, CalibrationConfig};
+
+/// The most samples kept per channel.
+const MAX_SAMPLES: usize = 2000;
+/// The number of recent samples averaged into a calibration reading.
+const CAPTURE_SAMPLES: usize = 50;
+
+/// Read the samples in a line of telemetry. Words that aren't numbers or `name=value`
+/// pairs (i.e. log messages) make the whole line be ignored.
+pub fn parse_sample_line(line: &str) -> Vec<(String, f64)> {
+    let mut samples = vec![];
+    let words = line.split(|c: char| c.is_whitespace() || c == ',' || c == ';').filter(|w| !w.is_empty());
+    for (i, word) in words.enumerate() {
+        let sample: Option<(String, f64)> = match word.split_once(['=', ':']) {
+            Some((name, value)) if !name.is_empty() => value.parse().ok().map(|v| (name.to_string(), v)),
+            Some(_) => None,
+            None => word.parse().ok().map(|v| (format!("ch{}", i), v)),
+        };
+        match sample {
+            Some(sample) if sample.1.is_finite() => samples.push(sample),
+            _ => return vec![],
+        }
+    }
+    samples
+}
+
+/// Statistics of a run of samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// The root mean square, including any DC level
+    pub rms: f64,
+    pub std_dev: f64,
+}
+
+impl Stats {
+
+    pub fn of<'a>(samples: impl IntoIterator<Item = &'a f64>) -> Option<Self> {
+        let (mut count, mut min, mut max, mut sum, mut sum_squares) = (0, f64::INFINITY, f64::NEG_INFINITY, 0.0, 0.0);
+        for &s in samples {
+            count += 1;
+            min = min.min(s);
+            max = max.max(s);
+            sum += s;
+            sum_squares += s * s;
+        }
+        if count == 0 {
+            return None;
+        }
+        let mean = sum / count as f64;
+        let mean_square = sum_squares / count as f64;
+        Some(Self {
+            count, min, max, mean,
+            rms: mean_square.sqrt(),
+            std_dev: (mean_square - mean * mean).max(0.0).sqrt(),
+        })
+    }
+}
+
+/// Where the samples come from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum SignalSource {
+    #[default]
+    SerialMonitor,
+    NetworkTelemetry,
+}
+
+impl fmt::Display for SignalSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignalSource::SerialMonitor => write!(f, "serial monitor"),
+            SignalSource::NetworkTelemetry => write!(f, "network telemetry"),
+        }
+    }
+}
+
+/// The colors channels are plotted in, in order.
+const CHANNEL_COLORS: [egui::Color32; 6] = [
+    egui::Color32::from_rgb(0x4e, 0xa8, 0xde), egui::Color32::from_rgb(0xe8, 0x8a, 0x3a),
+    egui::Color32::from_rgb(0x6c, 0xc0, 0x5a), egui::Color32::from_rgb(0xd9, 0x5b, 0x8c),
+    egui::Color32::from_rgb(0xb5, 0x8c, 0xe0), egui::Color32::from_rgb(0xd8, 0xc8, 0x4a),
+];
+
+/// Plot the channels over the last MAX_SAMPLES samples, each scaled to the shared range.
+fn plot(ui: &mut egui::Ui, channels: &BTreeMap<String, VecDeque<f64>>) {
+    let width = ui.available_width().max(200.0);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 160.0), egui::Sense::hover());
+    let visuals = ui.style().visuals.clone();
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+    let Some(stats) = Stats::of(channels.values().flatten()) else {
+        return;
+    };
+    // leave some room above and below, and don't blow a flat line up to full height
+    let span = (stats.max - stats.min).max(stats.max.abs() * 1e-3).max(1e-9);
+    let (low, high) = (stats.min - span * 0.05, stats.max + span * 0.05);
+    let to_screen = |i: usize, v: f64| egui::pos2(
+        rect.left() + rect.width() * i as f32 / (MAX_SAMPLES - 1) as f32,
+        rect.bottom() - rect.height() * ((v - low) / (high - low)) as f32,
+    );
+    for (n, samples) in channels.values().enumerate() {
+        // the newest sample is at the right edge
+        let start = MAX_SAMPLES - samples.len();
+        let points: Vec<egui::Pos2> = samples.iter().enumerate().map(|(i, v)| to_screen(start + i, *v)).collect();
+        painter.add(egui::Shape::line(points, (1.5, CHANNEL_COLORS[n % CHANNEL_COLORS.len()])));
+    }
+    let font = egui::FontId::monospace(10.0);
+    painter.text(rect.left_top() + egui::vec2(4.0, 2.0), egui::Align2::LEFT_TOP, format!("{:.6}", high), font.clone(), visuals.weak_text_color());
+    painter.text(rect.left_bottom() + egui::vec2(4.0, -2.0), egui::Align2::LEFT_BOTTOM, format!("{:.6}", low), font, visuals.weak_text_color());
+}
+
+/// The state of the signal viewer window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SignalsPanel {
+    pub source: SignalSource,
+    /// The channel being calibrated
+    calibrating: Option<String>,
+    /// The known value of the signal being measured, as typed
+    reference: String,
+    #[serde(skip)]
+    channels: BTreeMap<String, VecDeque<f64>>,
+    #[serde(skip)]
+    paused: bool,
+    /// Pairs of (raw reading, reference value) for the channel being calibrated
+    #[serde(skip)]
+    points: Vec<(f64, f64)>,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl SignalsPanel {
+
+    /// Add the samples in a line of telemetry.
+    pub fn push_line(&mut self, line: &str) {
+        for (name, value) in parse_sample_line(line) {
+            let samples = self.channels.entry(name).or_default();
+            samples.push_back(value);
+            if samples.len() > MAX_SAMPLES {
+                samples.pop_front();
+            }
+        }
+    }
+
+    pub fn stats(&self, channel: &str) -> Option<Stats> {
+        Stats::of(self.channels.get(channel)?)
+    }
+
+    /// The average of a channel's most recent samples, as a calibration reading.
+    pub fn reading(&self, channel: &str) -> Option<f64> {
+        let samples = self.channels.get(channel)?;
+        Stats::of(samples.iter().skip(samples.len().saturating_sub(CAPTURE_SAMPLES))).map(|s| s.mean)
+    }
+
+    /// Stop listening for samples, for when the viewer is closed. Samples sent while
+    /// it's closed aren't kept for it.
+    pub fn close(&mut self) {
+        self.events = None;
+    }
+
+    fn receive(&mut self, ctx: &egui::Context) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        let lines: Vec<String> = rx.try_iter().filter_map(|event| match (event, self.source) {
+            (Event::SerialData(line), SignalSource::SerialMonitor) => Some(ansi::strip(&line)),
+            (Event::DeviceTelemetry { line, .. }, SignalSource::NetworkTelemetry) => Some(line),
+            _ => None,
+        }).collect();
+        if !self.paused {
+            for line in lines {
+                self.push_line(&line);
+            }
+        }
+    }
+
+    fn display_calibration(&mut self, ui: &mut egui::Ui, calibration: &mut CalibrationConfig, project_dir: Option<&Path>) {
+        ui.horizontal(|ui| {
+            ui.label("calibrate:");
+            let selected = self.calibrating.clone().unwrap_or_default();
+            egui::ComboBox::from_id_source("signals_calibrate")
+            .selected_text(selected.as_str())
+            .show_ui(ui, |ui| {
+                for name in self.channels.keys() {
+                    if ui.selectable_label(selected == *name, name).clicked() && selected != *name {
+                        self.calibrating = Some(name.clone());
+                        self.points.clear();
+                    }
+                }
+            });
+        });
+        let Some(channel) = self.calibrating.clone() else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("reference value:");
+            ui.add(egui::TextEdit::singleline(&mut self.reference).desired_width(80.0).hint_text("1.65V"));
+            let reading = self.reading(&channel);
+            let reference = parse_si(&self.reference);
+            if ui.add_enabled(reading.is_some() && reference.is_ok(), egui::Button::new("take reading")).clicked() {
+                if let (Some(raw), Ok(reference)) = (reading, reference) {
+                    self.points.push((raw, reference));
+                }
+            }
+            if !self.points.is_empty() && ui.button("clear").clicked() {
+                self.points.clear();
+            }
+        });
+        for (raw, reference) in self.points.iter() {
+            ui.monospace(format!("{:.3} → {}", raw, reference));
+        }
+        match Calibration::fit(&self.points) {
+            Ok(fit) => {
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("gain {:.6}, offset {:.6}", fit.gain, fit.offset));
+                    if ui.button("save to project").clicked() {
+                        calibration.channels.insert(channel.clone(), fit);
+                        self.message = Some(match project_dir.map(|dir| calibration.write_module(dir)) {
+                            Some(Ok(())) => "wrote src/calibration.rs".to_string(),
+                            Some(Err(e)) => e.to_string(),
+                            None => "saved; save the project to generate src/calibration.rs".to_string(),
+                        });
+                    }
+                });
+            },
+            Err(e) => {
+                ui.label(e);
+            },
+        }
+        if let Some(saved) = calibration.channels.get(&channel) {
+            ui.label(format!("in the project: gain {:.6}, offset {:.6}", saved.gain, saved.offset));
+        }
+        if let Some(message) = &self.message {
+            ui.label(message);
+        }
+    }
+
+    /// Show the plot, the channels' statistics and the calibration controls. `adc` is the
+    /// main board's ADC, used to show readings in volts.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, calibration: &mut CalibrationConfig, adc: Option<Adc>, project_dir: Option<&Path>) {
+        self.receive(ctx);
+        ui.horizontal(|ui| {
+            for source in enum_iterator::all::<SignalSource>() {
+                ui.radio_value(&mut self.source, source, source.to_string());
+            }
+            ui.separator();
+            ui.toggle_value(&mut self.paused, "pause");
+            if ui.button("clear").clicked() {
+                self.channels.clear();
+            }
+        });
+        if self.channels.is_empty() {
+            ui.label("Waiting for samples: lines like \"adc0=2048 adc1=17\" or \"2048, 17\".");
+            return;
+        }
+        plot(ui, &self.channels);
+        egui::Grid::new("signal_stats").num_columns(8).striped(true).show(ui, |ui| {
+            for heading in ["channel", "last", "min", "max", "mean", "RMS", "std dev", ""] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for (n, (name, samples)) in self.channels.iter().enumerate() {
+                let Some(stats) = Stats::of(samples) else { continue };
+                ui.colored_label(CHANNEL_COLORS[n % CHANNEL_COLORS.len()], name);
+                for value in [*samples.back().unwrap(), stats.min, stats.max, stats.mean, stats.rms, stats.std_dev] {
+                    ui.monospace(format!("{:.3}", value));
+                }
+                // the mean as the firmware would see it after calibration, or in volts
+                let corrected = match (calibration.channels.get(name), adc) {
+                    (Some(c), _) => format!("calibrated {:.4}", c.apply(stats.mean)),
+                    (None, Some(adc)) => format_si(stats.mean * adc.reference / 2f64.powi(adc.resolution as i32), "V"),
+                    (None, None) => String::new(),
+                };
+                ui.label(corrected);
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        self.display_calibration(ui, calibration, project_dir);
+    }
+}
@@ -2,6 +2,8 @@
 #[cfg(test)]
 mod app_tests {
     use crate::IronCoderApp;
+    use crate::app::events::{Event, EventBus};
+    use crate::app::jobs::{JobScheduler, JobState};
 
     #[test]
     fn test_initialization() {
@@ -15,4 +17,1153 @@ mod app_tests {
         assert_ne!(boards.len(), 0);
         assert_eq!(boards, app.boards);
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_event_bus() {
+        let bus = EventBus::default();
+        let rx1 = bus.subscribe();
+        let rx2 = bus.subscribe();
+        bus.publish(Event::BoardSelected("Feather RP2040".to_string()));
+        assert_eq!(rx1.try_recv().unwrap(), Event::BoardSelected("Feather RP2040".to_string()));
+        assert_eq!(rx2.try_recv().unwrap(), Event::BoardSelected("Feather RP2040".to_string()));
+        // a dropped subscriber shouldn't stop the others from getting events
+        drop(rx1);
+        bus.publish(Event::BuildFinished { success: true });
+        assert_eq!(rx2.try_recv().unwrap(), Event::BuildFinished { success: true });
+    }
+    #[test]
+    fn test_panicking_job() {
+        let scheduler = JobScheduler::default();
+        let id = scheduler.spawn("panics", None, |_job| panic!("out of bounds"));
+        while scheduler.state(id) == Some(JobState::Running) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(scheduler.state(id), Some(JobState::Failed("panicked: out of bounds".to_string())));
+        assert_eq!(scheduler.num_running(), 0);
+    }
+
+    mod devices_tests {
+        use std::net::{IpAddr, Ipv4Addr};
+        use crate::app::devices::*;
+
+        /// Append a DNS record with the given name bytes, type and data.
+        fn record(out: &mut Vec<u8>, name: &[u8], kind: u16, data: &[u8]) {
+            out.extend(name);
+            out.extend(kind.to_be_bytes());
+            out.extend([0x80, 0x01, 0, 0, 0x11, 0x94]);
+            out.extend((data.len() as u16).to_be_bytes());
+            out.extend(data);
+        }
+
+        /// A response like an ESP32 running mdns-sd sends for _iron-coder._tcp, using
+        /// name compression for everything after the first name.
+        fn mdns_response() -> Vec<u8> {
+            let mut msg = vec![0, 0, 0x84, 0, 0, 0, 0, 4, 0, 0, 0, 0];
+            let service = b"\x0b_iron-coder\x04_tcp\x05local\x00";
+            let local = 12 + 17;
+            // the instance name is the PTR target, "greenhouse" + a pointer to the service name
+            let instance = (12 + service.len() + 10) as u8;
+            record(&mut msg, service, 12, &[&b"\x0agreenhouse"[..], &[0xc0, 12]].concat());
+            let host = (msg.len() + 12 + 6) as u8;
+            record(&mut msg, &[0xc0, instance], 33, &[&[0, 0, 0, 0, 0x1f, 0x90][..], b"\x0aesp32-c3-1", &[0xc0, local]].concat());
+            record(&mut msg, &[0xc0, instance], 16, b"\x0etelemetry=4000\x0aota=update\x0bboard=esp32");
+            record(&mut msg, &[0xc0, host], 1, &[192, 168, 1, 42]);
+            msg
+        }
+
+        #[test]
+        fn test_mdns_query() {
+            let query = mdns_query(&["_http._tcp".to_string()]);
+            assert_eq!(query[..12], [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+            assert_eq!(&query[12..], b"\x05_http\x04_tcp\x05local\x00\x00\x0c\x80\x01");
+        }
+
+        #[test]
+        fn test_mdns_response() {
+            let records = parse_dns_response(&mdns_response()).unwrap();
+            assert_eq!(records.len(), 4);
+            let devices = devices_from_records(&records, &["_iron-coder._tcp".to_string()]);
+            assert_eq!(devices.len(), 1);
+            let device = &devices[0];
+            assert_eq!(device.name, "greenhouse");
+            assert_eq!(device.hostname, "esp32-c3-1.local");
+            assert_eq!(device.port, 8080);
+            assert_eq!(device.address(), Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 42))));
+            assert_eq!(device.telemetry_port(), Some(4000));
+            assert_eq!(device.ota_url().as_deref(), Some("http://192.168.1.42:8080/update"));
+            assert_eq!(device.key(), "_iron-coder._tcp/greenhouse");
+            // services we didn't ask about are left out
+            assert!(devices_from_records(&records, &["_http._tcp".to_string()]).is_empty());
+            // queries, truncated packets, and compression loops are rejected
+            assert!(parse_dns_response(&mdns_query(&["_http._tcp".to_string()])).is_err());
+            assert!(parse_dns_response(&mdns_response()[..60]).is_err());
+            let mut looped = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+            record(&mut looped, &[0xc0, 12], 12, &[0xc0, 12]);
+            assert!(parse_dns_response(&looped).is_err());
+        }
+
+        #[test]
+        fn test_ssdp_response() {
+            let from = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7));
+            let response = b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://10.0.0.7:49152/description.xml\r\nSERVER: FreeRTOS/10 UPnP/1.0 sensor/1.2\r\nST: upnp:rootdevice\r\nUSN: uuid:1234::upnp:rootdevice\r\n\r\n";
+            let device = parse_ssdp_response(response, from).unwrap();
+            assert_eq!(device.name, "FreeRTOS/10 UPnP/1.0 sensor/1.2");
+            assert_eq!(device.service, "upnp:rootdevice");
+            assert_eq!((device.address(), device.port), (Some(from), 49152));
+            assert_eq!(device.source, DiscoverySource::Ssdp);
+            let no_port = parse_ssdp_response(b"HTTP/1.1 200 OK\r\nLocation: http://[fe80::1]/desc.xml\r\n\r\n", from).unwrap();
+            assert_eq!((no_port.address(), no_port.port), (Some("fe80::1".parse().unwrap()), 80));
+            assert!(parse_ssdp_response(b"NOTIFY * HTTP/1.1\r\n\r\n", from).is_none());
+            assert!(String::from_utf8(ssdp_search("ssdp:all")).unwrap().starts_with("M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\n"));
+        }
+
+        #[test]
+        fn test_ping_times() {
+            let linux = "64 bytes from 192.168.1.42: icmp_seq=1 ttl=64 time=3.21 ms\n64 bytes from 192.168.1.42: icmp_seq=2 ttl=64 time=12.5 ms\n";
+            assert_eq!(parse_ping_times(linux), [3.21, 12.5]);
+            let windows = "Reply from 192.168.1.42: bytes=32 time<1ms TTL=64\nReply from 192.168.1.42: bytes=32 time=4ms TTL=64\nRequest timed out.\n";
+            assert_eq!(parse_ping_times(windows), [1.0, 4.0]);
+        }
+    }
+
+    mod toolbox_tests {
+        use crate::app::toolbox::*;
+        use crate::board::Adc;
+        use crate::board::pwm::{Prescaler, PwmHal};
+
+        fn close(a: f64, b: f64) -> bool {
+            (a - b).abs() < 1e-9 * a.abs().max(1.0)
+        }
+
+        #[test]
+        fn test_si_values() {
+            assert_eq!(parse_si("4.7k").unwrap(), 4700.0);
+            assert_eq!(parse_si("4k7").unwrap(), 4700.0);
+            assert_eq!(parse_si("3V3").unwrap(), 3.3);
+            assert!(close(parse_si(" 100 nF").unwrap(), 100e-9));
+            assert!(close(parse_si("2.2MΩ").unwrap(), 2.2e6));
+            assert_eq!(parse_si("-40").unwrap(), -40.0);
+            assert!(parse_si("4.7k7").is_err());
+            assert!(parse_si("k").is_err());
+            assert!(format_si(4700.0, "Ω").starts_with("4") && format_si(4700.0, "Ω").ends_with(" kΩ"));
+            assert_eq!(format_si(0.0, "V"), "0 V");
+            assert!(format_si(1e-6, "A").ends_with(" µA"));
+            assert!(close(Conversion::MilToMillimeter.convert(100.0), 2.54));
+            assert!(close(Conversion::CelsiusToFahrenheit.invert(212.0), 100.0));
+            assert!(close(Conversion::DbmToMilliwatt.convert(20.0), 100.0));
+            assert!(close(Conversion::DbmToMilliwatt.invert(1.0), 0.0));
+        }
+
+        #[test]
+        fn test_divider() {
+            assert!(close(divider_output(5.0, 10e3, 20e3), 5.0 * 2.0 / 3.0));
+            let r1 = divider_r1(5.0, 3.3, 10e3).unwrap();
+            assert!(close(r1, 10e3 * 1.7 / 3.3));
+            assert_eq!(nearest_e24(r1), 5100.0);
+            assert_eq!(nearest_e24(9.7e3), 10e3);
+            assert!(divider_r1(3.3, 5.0, 10e3).is_err());
+        }
+
+        #[test]
+        fn test_color_codes() {
+            use ColorBand::*;
+            assert_eq!(resistor_value(&[Yellow, Violet, Red, Gold]).unwrap(), (4700.0, 5.0));
+            assert_eq!(resistor_value(&[Brown, Black, Black, Red, Brown]).unwrap(), (10e3, 1.0));
+            assert_eq!(resistor_value(&[Brown, Black, Brown]).unwrap(), (100.0, 20.0));
+            assert!(close(resistor_value(&[Green, Blue, Gold, Gold]).unwrap().0, 5.6));
+            assert!(resistor_value(&[Gold, Black, Red, Gold]).is_err());
+            assert!(resistor_value(&[Red, Red, Red, Black]).is_err());
+            assert_eq!(resistor_bands(4700.0, 2).unwrap(), [Yellow, Violet, Red]);
+            assert_eq!(resistor_bands(10e3, 3).unwrap(), [Brown, Black, Black, Red]);
+            assert_eq!(resistor_bands(0.47, 2).unwrap(), [Yellow, Violet, Silver]);
+            // rounding carries into the next decade
+            assert_eq!(resistor_bands(9.96e3, 2).unwrap(), [Brown, Black, Orange]);
+            assert!(resistor_bands(0.001, 2).is_err());
+        }
+
+        #[test]
+        fn test_adc_and_pwm() {
+            let adc = Adc { resolution: 12, reference: 3.3 };
+            assert!(close(adc_voltage(2048, &adc), 1.65));
+            assert_eq!(adc_count(1.65, &adc), 2048);
+            assert_eq!(adc_count(5.0, &adc), 4095);
+            assert_eq!(adc_count(-1.0, &adc), 0);
+            let timer = PwmTimer { clock: 125e6, max_top: 65535, prescaler: Prescaler::Integer { max: 256 } };
+            let settings = timer.settings(1e3).unwrap();
+            assert_eq!((settings.divider, settings.top), (2, 62499));
+            assert!(close(settings.frequency, 1e3));
+            assert_eq!(settings.compare(50.0), 31250);
+            assert!(close(settings.duty(31250), 50.0));
+            let fast = timer.settings(25e6).unwrap();
+            assert_eq!((fast.divider, fast.top), (1, 4));
+            assert!(timer.settings(100e6).is_none());
+            // too slow for the largest divider and counter
+            assert!(timer.settings(1.0).is_none());
+        }
+
+        #[test]
+        fn test_pwm_solver() {
+            // an RP2040 slice: 8.4 fixed point divider
+            let rp2040 = PwmTimer { clock: 125e6, max_top: 65535, prescaler: Prescaler::Fractional { max: 255, fraction_bits: 4 } };
+            let settings = rp2040.solve(50.0, None).unwrap();
+            assert_eq!((settings.divider, settings.divider_frac, settings.top), (38, 3, 65465));
+            assert!((settings.frequency - 50.0).abs() < 0.01);
+            // an exact resolution picks the divider closest to the frequency
+            let settings = rp2040.solve(20e3, Some(10)).unwrap();
+            assert_eq!((settings.divider, settings.divider_frac, settings.top), (6, 2, 1023));
+            assert!(rp2040.solve(1e6, Some(8)).is_err());
+            assert!(rp2040.solve(1e3, Some(17)).is_err());
+            // an nRF52 prescaler only divides by powers of two
+            let nrf = PwmTimer { clock: 16e6, max_top: 32766, prescaler: Prescaler::PowerOfTwo { max: 128 } };
+            let settings = nrf.solve(100.0, None).unwrap();
+            assert_eq!((settings.divider, settings.top), (8, 19999));
+            let err = nrf.solve(1e3, Some(12)).unwrap_err();
+            assert!(err.contains("isn't reachable"), "{}", err);
+            assert!(nrf.solve(1.0, None).unwrap_err().starts_with("too slow"));
+        }
+
+        #[test]
+        fn test_pwm_code() {
+            let settings = PwmSettings { divider: 38, divider_frac: 3, top: 65465, frequency: 50.0 };
+            let code = pwm_code(PwmHal::Rp2040Hal, &settings, 7.5);
+            assert!(code.contains("pwm.set_div_int(38);") && code.contains("pwm.set_div_frac(3);"));
+            assert!(code.contains("pwm.set_top(65465);") && code.contains("channel.set_duty(4910);"));
+            let settings = PwmSettings { divider: 8, divider_frac: 0, top: 19999, frequency: 100.0 };
+            let code = pwm_code(PwmHal::NrfHal, &settings, 50.0);
+            assert!(code.contains("Prescaler::Div8") && code.contains("set_max_duty(20000)"));
+            let settings = PwmSettings { divider: 4, divider_frac: 0, top: 1023, frequency: 20e3 };
+            let code = pwm_code(PwmHal::EspHal, &settings, 25.0);
+            assert!(code.contains("Duty::Duty10Bit") && code.contains("20000u32.Hz()") && code.contains("duty_pct: 25,"));
+        }
+    }
+
+    mod watchdog_tests {
+        use crate::app::watchdog::*;
+        use crate::board::units::Frequency;
+        use crate::board::watchdog::{BrownOut, BrownOutAction, Watchdog, WatchdogHal};
+
+        fn watchdog(hz: u64, counter_bits: u32, min_count: u64, max_divider: u32) -> Watchdog {
+            Watchdog { clock: Frequency::from_hz(hz), counter_bits, min_count, max_divider, window: false, hal: None }
+        }
+
+        #[test]
+        fn test_watchdog_settings() {
+            let rp2040 = watchdog(1_000_000, 23, 0, 1);
+            let settings = rp2040.settings(0.5).unwrap();
+            assert_eq!((settings.divider, settings.count), (1, 500_000));
+            assert!(rp2040.settings(9.0).unwrap_err().starts_with("too long"));
+            let nrf = watchdog(32_768, 32, 16, 1);
+            assert_eq!(nrf.settings(2.0).unwrap().count, 65_536);
+            assert!(nrf.settings(1e-4).unwrap_err().starts_with("too short"));
+            // past what the counter holds the divider goes up
+            let esp = watchdog(80_000_000, 32, 0, 65_535);
+            let settings = esp.settings(100.0).unwrap();
+            assert_eq!(settings.divider, 2);
+            assert!((settings.timeout - 100.0).abs() < 1e-6);
+            let code = watchdog_code(WatchdogHal::NrfHal, &nrf.settings(1.0).unwrap(), None);
+            assert!(code.contains("wdt.set_lfosc_ticks(32767);"), "{}", code);
+            let code = watchdog_code(WatchdogHal::Rp2040Hal, &rp2040.settings(0.5).unwrap(), Some(0.01));
+            assert!(code.contains("MicrosDurationU32::micros(500000)") && code.contains("fed sooner than 10 ms"), "{}", code);
+            let brown_out = BrownOut { rail: "VDD".to_string(), levels: vec![1.7, 2.7, 2.8], default: None, action: BrownOutAction::Interrupt, hal: None };
+            assert!(brown_out_code(WatchdogHal::NrfHal, &brown_out, 1).contains("w.threshold().v27().pof().enabled()"));
+        }
+
+        #[test]
+        fn test_unfed_stretches() {
+            let code = r#"
+                fn main() {
+                    delay.delay_ms(5000);
+                    watchdog.start(1.secs());
+                    loop {
+                        led.set_high();
+                        delay.delay_ms(300);
+                        watchdog.feed();
+                        for _ in 0..4 {
+                            delay.delay_ms(100u32);
+                        }
+                    }
+                }
+                fn blink() {
+                    loop {
+                        cortex_m::asm::delay(12_500_000);
+                    }
+                }
+                fn wait_ready() {
+                    while !ready() {
+                        delay.delay_us(10);
+                    }
+                    if slow { delay.delay_ms(20) } else { delay.delay_ms(2) }
+                }
+                fn idle() {}
+            "#;
+            let stretches = unfed_stretches(code, Some(125e6));
+            // the wait before the watchdog starts doesn't count, and the loop wraps round
+            // from the four short waits to the long one
+            assert_eq!(stretches[0].0, "main");
+            assert!((stretches[0].1 - 0.7).abs() < 1e-9, "{:?}", stretches);
+            assert_eq!(stretches[1], ("blink".to_string(), f64::INFINITY));
+            assert_eq!(stretches[2].0, "wait_ready");
+            assert!((stretches[2].1 - 0.02001).abs() < 1e-9, "{:?}", stretches);
+            assert_eq!(stretches.len(), 3);
+        }
+    }
+
+    mod signals_tests {
+        use crate::app::signals::*;
+
+        #[test]
+        fn test_parse_samples() {
+            assert_eq!(parse_sample_line("adc0=2048 adc1:17"), [("adc0".to_string(), 2048.0), ("adc1".to_string(), 17.0)]);
+            assert_eq!(parse_sample_line("1.5, -2;3"), [("ch0".to_string(), 1.5), ("ch1".to_string(), -2.0), ("ch2".to_string(), 3.0)]);
+            // log messages aren't samples
+            assert!(parse_sample_line("booting v1.2").is_empty());
+            assert!(parse_sample_line("adc0=high").is_empty());
+            assert!(parse_sample_line("=3").is_empty());
+            assert!(parse_sample_line("").is_empty());
+        }
+
+        #[test]
+        fn test_stats() {
+            let stats = Stats::of(&[1.0, -1.0, 1.0, -1.0]).unwrap();
+            assert_eq!((stats.count, stats.min, stats.max, stats.mean), (4, -1.0, 1.0, 0.0));
+            assert_eq!((stats.rms, stats.std_dev), (1.0, 1.0));
+            let stats = Stats::of(&[3.0, 3.0]).unwrap();
+            assert_eq!((stats.rms, stats.std_dev), (3.0, 0.0));
+            assert!(Stats::of(&[]).is_none());
+            let mut panel = SignalsPanel::default();
+            for i in 0..3000 {
+                panel.push_line(&format!("adc0={}", i));
+            }
+            let stats = panel.stats("adc0").unwrap();
+            assert_eq!((stats.count, stats.min, stats.max), (2000, 1000.0, 2999.0));
+            // a reading averages the latest samples
+            assert_eq!(panel.reading("adc0").unwrap(), 2974.5);
+            assert!(panel.reading("adc1").is_none());
+        }
+    }
+
+    mod export_tests {
+        use crate::app::events::Event;
+        use crate::app::export::*;
+        use crate::project::messages::MessageSchema;
+        use crate::scratch::ScratchDir;
+
+        fn records() -> Vec<Record> {
+            let record = |time: f64, source: &str, values: &[(&str, f64)]| Record {
+                time,
+                source: source.to_string(),
+                values: values.iter().map(|(n, v)| (n.to_string(), *v)).collect(),
+            };
+            vec![
+                record(100.00, "serial", &[("adc0", 1.0), ("adc1", 10.0)]),
+                record(100.40, "serial", &[("adc0", 3.0)]),
+                record(100.45, "lab-node", &[("temp", 21.5)]),
+                record(101.20, "serial", &[("adc0", 5.0), ("adc1", 20.0)]),
+            ]
+        }
+
+        fn fields(names: &[&str]) -> Vec<String> {
+            names.iter().map(|n| n.to_string()).collect()
+        }
+
+        #[test]
+        fn test_sources() {
+            let schema = MessageSchema::default();
+            let sources = Sources::default();
+            let serial = sources.samples(Event::SerialData("adc0=5\n".to_string()), &schema).unwrap();
+            assert_eq!(serial, ("serial".to_string(), vec![("adc0".to_string(), 5.0)]));
+            let device = Event::DeviceTelemetry { device: "lab-node".to_string(), line: "temp=21".to_string() };
+            assert_eq!(sources.samples(device.clone(), &schema).unwrap().0, "lab-node");
+            assert!(sources.samples(Event::SerialData("booting".to_string()), &schema).is_none());
+            let off = Sources { network_telemetry: false, ..Sources::default() };
+            assert!(off.samples(device, &schema).is_none());
+        }
+
+        #[test]
+        fn test_table() {
+            let table = Table::new(&records(), &fields(&["adc0", "adc1"]), None);
+            // the record with only an unselected field is left out
+            assert_eq!(table.rows.len(), 3);
+            assert_eq!(table.rows[1].values, [Some(3.0), None]);
+            assert_eq!(table.rows[1].source.as_deref(), Some("serial"));
+            let resampled = |aggregation| Table::new(&records(), &fields(&["adc0", "adc1"]), Some(Resampling { period: 0.5, aggregation }));
+            let mean = resampled(Aggregation::Mean);
+            assert_eq!(mean.rows.iter().map(|r| r.time).collect::<Vec<_>>(), [100.0, 101.0]);
+            assert_eq!(mean.rows[0].values, [Some(2.0), Some(10.0)]);
+            assert_eq!(mean.rows[0].source, None);
+            assert_eq!(resampled(Aggregation::Max).rows[0].values[0], Some(3.0));
+            assert_eq!(resampled(Aggregation::Last).rows[1].values, [Some(5.0), Some(20.0)]);
+        }
+
+        #[test]
+        fn test_text_exports() {
+            let table = Table::new(&records(), &fields(&["adc0", "odd,name"]), None);
+            assert_eq!(to_csv(&table), "time,source,adc0,\"odd,name\"\n100.000000,serial,1,\n100.400000,serial,3,\n101.200000,serial,5,\n");
+            let table = Table::new(&records(), &fields(&["adc1", "temp"]), None);
+            assert_eq!(
+                to_influx(&table, "bench run"),
+                "bench\\ run,source=serial adc1=10 100000000000\n\
+                 bench\\ run,source=lab-node temp=21.5 100450000000\n\
+                 bench\\ run,source=serial adc1=20 101200000000\n",
+            );
+        }
+
+        #[test]
+        fn test_parquet_export() {
+            use parquet::file::reader::{FileReader, SerializedFileReader};
+            use parquet::record::Field;
+            let table = Table::new(&records(), &fields(&["adc0", "adc1"]), None);
+            let bytes = to_parquet(&table).unwrap();
+            let dir = ScratchDir::new("telemetry");
+            let path = dir.join("telemetry.parquet");
+            std::fs::write(&path, bytes).unwrap();
+            let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+            assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+            let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|r| r.unwrap()).collect();
+            let columns: Vec<_> = rows[1].get_column_iter().map(|(n, f)| (n.clone(), f.clone())).collect();
+            assert_eq!(columns[0], ("time".to_string(), Field::TimestampMicros(100_400_000)));
+            assert_eq!(columns[1], ("source".to_string(), Field::Str("serial".to_string())));
+            assert_eq!(columns[2], ("adc0".to_string(), Field::Double(3.0)));
+            assert_eq!(columns[3], ("adc1".to_string(), Field::Null));
+        }
+    }
+
+    mod assistant_tests {
+        use serde_json::json;
+        use crate::app::assistant::*;
+        use crate::board::Board;
+        use crate::project::Project;
+        use crate::project::backend::{Diagnostic, Severity};
+        use crate::scratch::ScratchDir;
+
+        fn prompt() -> Prompt {
+            Prompt {
+                system: "be brief".to_string(),
+                context: vec![("Cargo.toml".to_string(), "[package]\n".to_string())],
+                request: "why?".to_string(),
+            }
+        }
+
+        fn provider(kind: ProviderKind) -> HttpProvider {
+            let config = AssistantConfig { provider: kind, url: format!("{}/", kind.default_url()), model: "m".to_string(), ..Default::default() };
+            HttpProvider { config, key: "k".to_string() }
+        }
+
+        #[test]
+        fn test_provider_formats() {
+            assert_eq!(prompt().message(), "## Cargo.toml\n```\n[package]\n```\n\nwhy?");
+            let (url, headers, body) = provider(ProviderKind::OpenAiCompatible).request(&prompt());
+            assert_eq!(url, "https://api.openai.com/v1/chat/completions");
+            assert_eq!(headers, [("Authorization", "Bearer k".to_string())]);
+            assert_eq!(body["messages"][0], json!({ "role": "system", "content": "be brief" }));
+            assert_eq!(body["messages"][1]["content"], prompt().message());
+            let (url, headers, body) = provider(ProviderKind::Anthropic).request(&prompt());
+            assert_eq!(url, "https://api.anthropic.com/v1/messages");
+            assert_eq!(headers[0], ("x-api-key", "k".to_string()));
+            assert_eq!((body["system"].as_str(), body["max_tokens"].as_u64()), (Some("be brief"), Some(2048)));
+            let (url, _, body) = provider(ProviderKind::Ollama).request(&prompt());
+            assert_eq!((url.as_str(), &body["stream"]), ("http://localhost:11434/api/chat", &json!(false)));
+
+            let openai = json!({ "choices": [{ "message": { "role": "assistant", "content": "because" } }] });
+            assert_eq!(parse_response(ProviderKind::OpenAiCompatible, &openai), Ok("because".to_string()));
+            let anthropic = json!({ "content": [{ "type": "text", "text": "be" }, { "type": "tool_use" }, { "type": "text", "text": "cause" }] });
+            assert_eq!(parse_response(ProviderKind::Anthropic, &anthropic), Ok("because".to_string()));
+            let ollama = json!({ "message": { "content": "because" }, "done": true });
+            assert_eq!(parse_response(ProviderKind::Ollama, &ollama), Ok("because".to_string()));
+            let error = json!({ "type": "error", "error": { "type": "authentication_error", "message": "invalid x-api-key" } });
+            assert_eq!(parse_response(ProviderKind::Anthropic, &error), Err("invalid x-api-key".to_string()));
+            assert!(parse_response(ProviderKind::Ollama, &openai).is_err());
+        }
+
+        #[test]
+        fn test_answers_and_functions() {
+            let answer = "Try this:\n```rust\nlet x = 1;\n```\nand\n```\nlet y = 2;\n```\n";
+            assert_eq!(code_blocks(answer), ["let x = 1;\n", "let y = 2;\n"]);
+            let code = "fn main() { blink(); }\nstruct Led;\nimpl Led { pub fn on(&mut self) -> bool { true } }\n";
+            let found = functions(code);
+            assert_eq!(found.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>(), ["main", "Led::on"]);
+            assert!(found[1].1.contains("pub fn on(&mut self) -> bool"));
+            assert!(functions("fn broken(").is_empty());
+        }
+
+        #[test]
+        fn test_context_stays_in_project() {
+            let root = ScratchDir::new("assistant");
+            let dir = root.join("project");
+            std::fs::create_dir_all(dir.join("src")).unwrap();
+            let source: String = (1..=40).map(|i| format!("let line{} = {};\n", i, i)).collect();
+            std::fs::write(dir.join("src/main.rs"), source).unwrap();
+            std::fs::write(dir.join("Cargo.toml"), format!("[package]\nname = \"blinky\"\n# {}\n", "x".repeat(20_000))).unwrap();
+            std::fs::write(root.join("secret.rs"), "let key = 1;\n").unwrap();
+
+            let mut project = Project::default();
+            let peripheral = Board::from_toml_str("name = \"Sensor\"\nmanufacturer = \"Test\"\nis_main_board = false").unwrap();
+            assert!(driver_init(&project, &peripheral).is_err());
+            project.add_board(Board::from_toml_str("name = \"Main\"\nmanufacturer = \"Test\"\nis_main_board = true").unwrap());
+            project.save_to(&dir).unwrap();
+
+            let error = |file: &str| Diagnostic {
+                severity: Severity::Error,
+                file: Some(file.into()),
+                line: Some(20),
+                column: Some(5),
+                message: "cannot find value `led` in this scope".to_string(),
+            };
+            let prompt = explain_error(&project, &error("src/main.rs")).unwrap();
+            let excerpt = &prompt.context.iter().find(|(title, _)| title == "src/main.rs").unwrap().1;
+            assert_eq!(excerpt.lines().count(), 25);
+            assert!(excerpt.contains("   20> let line20 = 20;"));
+            assert!(!excerpt.contains("line7 "));
+            let manifest = &prompt.context.iter().find(|(title, _)| title == "Cargo.toml").unwrap().1;
+            assert!(manifest.len() < 17_000 && manifest.ends_with("(cut to 16384 bytes)"));
+            // nothing outside the project directory goes in a prompt
+            let prompt = explain_error(&project, &error("../secret.rs")).unwrap();
+            assert!(!prompt.message().contains("key = 1"));
+
+            let prompt = driver_init(&project, &peripheral).unwrap();
+            assert_eq!(prompt.context.iter().map(|(t, _)| t.as_str()).collect::<Vec<_>>(), ["main board: Main", "peripheral board: Sensor", "Cargo.toml"]);
+            assert!(prompt.request.contains("the Sensor on the Main"));
+        }
+    }
+
+    mod trace_tests {
+        use crate::app::trace::*;
+        use crate::board::Board;
+
+        #[test]
+        fn test_decode_itm() {
+            let bytes = [
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x80, // sync
+                0x0E, 0x10, 0x10,                   // IRQ 0 entered
+                0x03, b'h', b'i', b'!', b'\n',       // "hi!\n" on port 0
+                0xC0, 0x80, 0x01,                   // 128 ticks
+                0x70,                               // overflow
+                0x90,                               // reserved
+                0x15, 0x00,                         // a PC sample while asleep
+            ];
+            // packets split across pushes come out whole
+            let mut decoder = ItmDecoder::default();
+            let mut packets = decoder.push(&bytes[..8]);
+            packets.extend(decoder.push(&bytes[8..16]));
+            packets.extend(decoder.push(&bytes[16..]));
+            assert_eq!(packets, [
+                ItmPacket::Sync,
+                ItmPacket::Exception { number: 16, action: ExceptionAction::Enter },
+                ItmPacket::Stimulus { port: 0, data: b"hi!\n".to_vec() },
+                ItmPacket::LocalTimestamp(128),
+                ItmPacket::Overflow,
+                ItmPacket::PcSample(None),
+            ]);
+            assert_eq!(decoder.skipped, 1);
+            assert_eq!(exception_name(15), "SysTick");
+            assert_eq!(exception_name(21), "IRQ 5");
+        }
+
+        #[test]
+        fn test_timeline() {
+            // a 1 MHz timestamp clock, so a tick is a microsecond
+            let mut timeline = Timeline::new(1e6);
+            timeline.push(&[0x0E, 0x0F, 0x10, 0xC0, 0x0A]); // SysTick enters at 10
+            timeline.push(&[0x0E, 0x10, 0x10, 0xC0, 0x05]); // IRQ 0 interrupts it at 15
+            timeline.push(&[0x0E, 0x10, 0x20, 0xC0, 0x14]); // and exits at 35
+            timeline.push(&[0x01, b'o', 0x01, b'k', 0x01, b'\n', 0x0E, 0x0F, 0x20, 0xC0, 0x0A]); // SysTick exits at 45
+            assert_eq!(timeline.spans, [
+                Span { exception: 15, start: 10e-6, end: Some(45e-6), depth: 0 },
+                Span { exception: 16, start: 15e-6, end: Some(35e-6), depth: 1 },
+            ]);
+            assert_eq!(timeline.marks, [(45e-6, Mark::Text { port: 0, text: "ok".to_string() })]);
+            let stats = timeline.exception_stats();
+            assert_eq!(stats[&16].0, 1);
+            assert!((stats[&15].1 - 35e-6).abs() < 1e-12);
+            assert_eq!(timeline.extent(), Some((10e-6, 45e-6)));
+
+            assert_eq!(LogLine::parse("0.001250 WARN  queue full"), Some(LogLine { time: 0.00125, level: Some("WARN".to_string()), text: "queue full".to_string() }));
+            assert_eq!(LogLine::parse("[12.5] started").unwrap().level, None);
+            assert!(LogLine::parse("INFO no timestamp").is_none());
+
+            let board = |cpu: &str| Board::from_toml_str(&format!("name = \"Main\"\nmanufacturer = \"Test\"\nis_main_board = true\ncpu = \"{}\"", cpu)).unwrap();
+            assert!(board("Cortex-M4").has_itm());
+            assert!(!board("Cortex-M0").has_itm());
+        }
+    }
+
+    mod input_sim_tests {
+        use crate::app::input_sim::*;
+        use crate::board::Board;
+        use crate::board::input::InputDevice;
+
+        #[test]
+        fn test_sim_events() {
+            let events = [
+                SimEvent::TouchDown { x: 0, y: 239 },
+                SimEvent::TouchMove { x: 319, y: 12 },
+                SimEvent::TouchUp,
+                SimEvent::Turn(-3),
+                SimEvent::Turn(2),
+                SimEvent::Press,
+                SimEvent::Release,
+            ];
+            for event in events {
+                assert_eq!(SimEvent::parse(&event.to_string()), Some(event));
+            }
+            assert_eq!(SimEvent::Turn(2).to_string(), "sim encoder +2");
+            for line in ["touch up", "sim touch down 4", "sim touch up now", "sim encoder left"] {
+                assert_eq!(SimEvent::parse(line), None, "{}", line);
+            }
+            // the firmware's parser reads the same words
+            assert!(MODULE.contains("(\"encoder\", steps) => SimEvent::Turn(steps.parse().ok()?)"));
+
+            // a screen drawn at twice its size
+            let rect = egui::Rect::from_min_size(egui::pos2(10.0, 10.0), egui::vec2(640.0, 480.0));
+            assert_eq!(touch_point(rect, egui::pos2(10.0, 10.0), 320, 240), (0, 0));
+            assert_eq!(touch_point(rect, egui::pos2(331.0, 251.0), 320, 240), (160, 120));
+            assert_eq!(touch_point(rect, egui::pos2(900.0, 900.0), 320, 240), (319, 239));
+        }
+
+        #[test]
+        fn test_board_inputs() {
+            let toml = std::fs::read_to_string("./iron-coder-boards/Adafruit/PiTTF_32_cap_touch/pitft32-cap-touch.toml").unwrap();
+            let board = Board::from_toml_str(&toml).unwrap();
+            assert_eq!(board.input(), Some(InputDevice::Touch { width: 320, height: 240 }));
+            let board = |input: &str| Board::from_toml_str(&format!("name = \"Knob\"\nmanufacturer = \"Test\"\ninput = {}", input));
+            assert_eq!(board("{ kind = \"encoder\", steps_per_turn = 20 }").unwrap().input(), Some(InputDevice::Encoder { steps_per_turn: 20, button: false }));
+            assert!(board("{ kind = \"encoder\", steps_per_turn = 0 }").is_err());
+            assert!(board("{ kind = \"touch\", width = 320, height = 0 }").is_err());
+        }
+    }
+
+    mod detached_tests {
+        use crate::app::detached::*;
+
+        #[test]
+        fn test_detached_windows() {
+            let mut windows = DetachedWindows::default();
+            assert!(!windows.is_detached("Serial Monitor"));
+            // moving a window that's docked doesn't detach it
+            windows.moved("Serial Monitor", WindowPlacement { position: Some([10.0, 20.0]), size: [300.0, 200.0] });
+            assert!(!windows.is_detached("Serial Monitor"));
+
+            windows.detach("Serial Monitor");
+            assert_eq!(windows.placement("Serial Monitor"), Some(WindowPlacement::default()));
+            let moved = WindowPlacement { position: Some([1930.0, 40.0]), size: [800.0, 600.0] };
+            windows.moved("Serial Monitor", moved);
+            // detaching again keeps it where it was
+            windows.detach("Serial Monitor");
+            assert_eq!(windows.placement("Serial Monitor"), Some(moved));
+
+            // the layout is saved with the app
+            let saved = serde_json::to_string(&windows).unwrap();
+            let restored: DetachedWindows = serde_json::from_str(&saved).unwrap();
+            assert_eq!(restored.placement("Serial Monitor"), Some(moved));
+            assert_eq!(serde_json::from_str::<DetachedWindows>("{}").unwrap(), DetachedWindows::default());
+
+            windows.attach("Serial Monitor");
+            assert!(!windows.is_detached("Serial Monitor"));
+        }
+    }
+
+    mod focus_tests {
+        use crate::app::ToolWindows;
+        use crate::app::focus::*;
+
+        #[test]
+        fn test_focus_mode() {
+            let mut tool_windows = ToolWindows { display_serial_monitor: true, display_pinout: true, ..Default::default() };
+            let mut focus = FocusMode::default();
+            assert!(!focus.is_on());
+            focus.toggle(&mut tool_windows);
+            assert!(focus.is_on());
+            assert!(!tool_windows.display_serial_monitor && !tool_windows.display_pinout);
+            // entering twice doesn't lose the layout
+            focus.enter(&mut tool_windows);
+
+            // quitting in focus mode keeps the layout
+            let mut focus: FocusMode = serde_json::from_str(&serde_json::to_string(&focus).unwrap()).unwrap();
+            assert!(focus.is_on());
+            focus.toggle(&mut tool_windows);
+            assert!(!focus.is_on());
+            assert!(tool_windows.display_serial_monitor && tool_windows.display_pinout);
+            assert!(!tool_windows.display_signals);
+        }
+
+        #[test]
+        fn test_column_rect() {
+            let available = egui::Rect::from_min_size(egui::pos2(0.0, 20.0), egui::vec2(1600.0, 900.0));
+            let column = column_rect(available, 8.0);
+            assert_eq!(column.width(), COLUMN_CHARS * 8.0);
+            assert_eq!(column.center(), available.center());
+            assert_eq!(column.height(), available.height());
+            // a narrow window is all column
+            let narrow = egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(500.0, 900.0));
+            assert_eq!(column_rect(narrow, 8.0), narrow);
+        }
+    }
+
+    mod overlay_tests {
+        use std::time::Duration;
+        use crate::app::jobs::{JobInfo, JobState};
+        use crate::app::overlay::*;
+        use crate::project::LOAD_JOB_NAME;
+
+        #[test]
+        fn test_overlay_lines() {
+            let mut overlay = SerialOverlay::default();
+            overlay.lines = 3;
+            overlay.push("one\r\ntwo\n\n");
+            assert_eq!(overlay.recent().collect::<Vec<_>>(), ["one", "two"]);
+            overlay.push("three\nfour\nfive\n");
+            assert_eq!(overlay.recent().collect::<Vec<_>>(), ["three", "four", "five"]);
+            overlay.lines = 1;
+            overlay.push("");
+            assert_eq!(overlay.recent().collect::<Vec<_>>(), ["five"]);
+        }
+
+        #[test]
+        fn test_flash_status() {
+            let job = |id, name: &str, state| JobInfo {
+                id,
+                name: name.to_string(),
+                state,
+                progress: None,
+                status: String::new(),
+                elapsed: Duration::from_millis(4200),
+            };
+            assert!(flash_job(&[job(0, "cargo build", JobState::Running)]).is_none());
+            let jobs = [
+                job(1, LOAD_JOB_NAME, JobState::Failed("no probe".to_string())),
+                job(2, "cargo build", JobState::Running),
+                job(3, LOAD_JOB_NAME, JobState::Running),
+            ];
+            let latest = flash_job(&jobs).unwrap();
+            assert_eq!(latest.id, 3);
+            assert_eq!(flash_status(latest), "flashing...");
+            assert_eq!(flash_status(&JobInfo { progress: Some(0.5), ..latest.clone() }), "flashing... 50%");
+            assert_eq!(flash_status(&jobs[0]), "flashing failed: no probe");
+            assert_eq!(flash_status(&job(4, LOAD_JOB_NAME, JobState::Finished)), "flashed in 4.2 s");
+        }
+    }
+
+    mod search_tests {
+        use std::path::Path;
+        use crate::app::{Command, ToolWindows};
+        use crate::app::search::*;
+        use crate::board;
+
+        #[test]
+        fn test_fuzzy_score() {
+            assert!(fuzzy_score("srl", "serial monitor").is_some());
+            assert!(fuzzy_score("SM", "serial monitor").is_some());
+            assert_eq!(fuzzy_score("xyz", "serial monitor"), None);
+            assert_eq!(fuzzy_score("lairs", "serial"), None);
+            // runs and word starts beat scattered letters
+            assert!(fuzzy_score("mon", "serial monitor") > fuzzy_score("mon", "some other note"));
+            assert!(fuzzy_score("rp", "Feather RP2040") > fuzzy_score("rp", "trace report"));
+        }
+
+        #[test]
+        fn test_search() {
+            let mut tool_windows = ToolWindows::default();
+            let actions: Vec<&str> = tool_windows.by_name().into_iter().map(|(name, _)| name).collect();
+            assert!(actions.contains(&"serial monitor") && actions.contains(&"pinout"));
+            // the commands are found by their names, which no tool window shares
+            for command in Command::ALL {
+                assert_eq!(Command::from_name(command.name()), Some(command));
+                assert!(!actions.contains(&command.name()), "{}", command.name());
+            }
+            assert_eq!(Command::from_name("serial monitor"), None);
+            let boards = board::get_boards(Path::new("./iron-coder-boards")).unwrap_or_else(|report| report.boards);
+            let items = index(&actions, &boards, None);
+
+            // an empty query lists the actions
+            assert!(search(&items, " ").iter().all(|item| item.category == SearchCategory::Action));
+
+            let results = search(&items, "feather rp");
+            assert_eq!(results.iter().find(|item| item.category == SearchCategory::Board).unwrap().target, SearchTarget::Board("Feather RP2040".to_string()));
+            let blinky = search(&items, "blinky").into_iter().find(|item| item.category == SearchCategory::Example).unwrap();
+            // the example's manifest describes it ahead of its README
+            assert_eq!(blinky.title, "Blinky");
+            assert!(blinky.detail.starts_with("Feather RP2040 Blinks the red LED"), "{}", blinky.detail);
+            let neopixel = search(&items, "neopixel").into_iter().find(|item| item.category == SearchCategory::Example).unwrap();
+            assert_eq!(neopixel.title, "neopixel");
+            assert!(matches!(&blinky.target, SearchTarget::File(path) if path.ends_with("blinky/src/main.rs")));
+
+            // a crate used by several boards is listed once
+            let leds: Vec<_> = items.iter().filter(|item| item.title == "smart-leds").collect();
+            assert_eq!(leds.len(), 1);
+            assert!(leds[0].detail.contains("Feather RP2040") && leds[0].detail.contains("Feather nRF52832"));
+
+            // results come grouped, in the order of the kinds
+            let results = search(&items, "e");
+            assert!(results.windows(2).all(|pair| pair[0].category <= pair[1].category));
+        }
+
+        #[test]
+        fn test_symbols() {
+            let code = "mod leds { pub fn blink() {} }\nstruct Board;\nimpl Board { fn new() -> Self { Board } }\nconst RATE: u32 = 9600;\n";
+            assert_eq!(symbols(code), [
+                ("blink".to_string(), "fn"),
+                ("leds".to_string(), "mod"),
+                ("Board".to_string(), "struct"),
+                ("new".to_string(), "fn"),
+                ("RATE".to_string(), "const"),
+            ]);
+            assert!(symbols("fn broken(").is_empty());
+        }
+    }
+
+    mod texture_cache_tests {
+        use std::path::Path;
+        use crate::app::texture_cache::*;
+        use crate::board;
+
+        #[test]
+        fn test_board_texture() {
+            let ctx = egui::Context::default();
+            let boards = board::get_boards(Path::new("./iron-coder-boards")).unwrap_or_else(|report| report.boards);
+            let board = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+            let image = board.svg_board_info.as_ref().unwrap().image.clone();
+            // nothing is uploaded until the image is decoded
+            if !image.is_ready() {
+                assert!(board_texture(&ctx, board).is_none());
+            }
+            image.wait();
+            let first = board_texture(&ctx, board).unwrap();
+            // every view of the board, and its clones, share the one texture
+            let again = board_texture(&ctx, &board.clone()).unwrap();
+            assert_eq!(first.id(), again.id());
+            let cache = shared(&ctx);
+            assert_eq!(cache.lock().unwrap().len(), 1);
+            assert_eq!(cache.lock().unwrap().used_bytes(), image.wait().unwrap().pixels.len() * 4);
+        }
+
+        #[test]
+        fn test_forget_board() {
+            let ctx = egui::Context::default();
+            let mut cache = TextureCache::default();
+            assert!(cache.is_empty());
+            for key in ["Adafruit/Feather", "Adafruit/Feather#thumbnail", "Adafruit/Feather#top", "Adafruit/Feather RP2040", "Clones Inc/Feather"] {
+                let _ = cache.get_or_load(&ctx, key, egui::ColorImage::example);
+            }
+            let one_texture = cache.used_bytes() / cache.len();
+            cache.forget_board("Adafruit/Feather");
+            // boards whose names start the same are kept
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.used_bytes(), 2 * one_texture);
+            assert_eq!(cache.evictions(), 0);
+        }
+    }
+
+    mod logos_tests {
+        use std::path::Path;
+        use std::sync::Arc;
+        use crate::app::logos::*;
+
+        #[test]
+        fn test_load_logos() {
+            let logo_set = load_logos(Path::new(LOGO_DIR));
+            let adafruit = logo_set.get("Adafruit").unwrap();
+            assert!(adafruit.width() > 0 && adafruit.height() > 0);
+            assert_eq!(logo_set.get(" adafruit").unwrap().size, adafruit.size);
+            assert!(logo_set.get("Sparkfun").is_none());
+            // a missing logo doesn't stop the rest from loading
+            assert!(load_logos(Path::new("./no-such-dir")).is_empty());
+        }
+
+        #[test]
+        fn test_show_logo() {
+            // the heights of what was shown for each manufacturer, if anything was
+            fn frame(ctx: &egui::Context) -> (Option<f32>, Option<f32>) {
+                let mut shown = (None, None);
+                let _ = ctx.run(Default::default(), |ctx| {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        shown = (
+                            show(ui, "Adafruit", LOGO_HEIGHT).map(|r| r.rect.height()),
+                            show(ui, "Sparkfun", LOGO_HEIGHT).map(|r| r.rect.height()),
+                        );
+                    });
+                });
+                shown
+            }
+            let ctx = egui::Context::default();
+            // before the logos are loaded, every manufacturer goes by name
+            assert_eq!(frame(&ctx), (None, None));
+            ctx.data_mut(|data| data.insert_temp(LOGOS_ID.into(), Arc::new(load_logos(Path::new(LOGO_DIR)))));
+            assert_eq!(frame(&ctx), (Some(LOGO_HEIGHT), None));
+        }
+    }
+
+    mod code_editor_tests {
+        use std::fs;
+        use crate::app::code_editor::*;
+        use crate::scratch::ScratchDir;
+
+        #[test]
+        fn test_large_file_lines() {
+            assert!(LargeFile::detect("fn main() {}\n").is_none());
+            let code = format!("// generated\r\n{}\nlast", "x".repeat(LARGE_FILE_BYTES));
+            let large = LargeFile::detect(&code).unwrap();
+            assert!(large.read_only);
+            assert_eq!(large.line_count(), 3);
+            assert_eq!(large.line(&code, 0), "// generated");
+            assert_eq!(large.line(&code, 1).len(), LARGE_FILE_BYTES);
+            assert_eq!(large.line(&code, 2), "last");
+            assert_eq!(large.line(&code, 3), "");
+            assert_eq!(line_starts("a\nb\n"), [0, 2, 4]);
+        }
+
+        #[test]
+        fn test_open_large_file() {
+            let dir = ScratchDir::new("large-file");
+            let (small, large) = (dir.join("main.rs"), dir.join("pac.rs"));
+            fs::write(&small, "fn main() {}\n").unwrap();
+            fs::write(&large, "pub struct Register;\n".repeat(LARGE_FILE_BYTES / 10)).unwrap();
+            let mut editor = CodeEditor::default();
+            editor.load_from_file(&small).unwrap();
+            assert!(editor.active_large_file().is_none());
+            editor.load_from_file(&large).unwrap();
+            assert_eq!(editor.active_large_file().unwrap().line_count(), LARGE_FILE_BYTES / 10 + 1);
+
+            // showing it lays out only the lines in view
+            let ctx = egui::Context::default();
+            ctx.style_mut(|style| {
+                style.text_styles.insert(egui::TextStyle::Name("EditorFont".into()), egui::FontId::monospace(12.0));
+            });
+            let input = || egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(800.0, 600.0))),
+                ..Default::default()
+            };
+            let _ = ctx.run(input(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| editor.display_code(ctx, ui));
+            });
+            let output = ctx.run(input(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| editor.display_code(ctx, ui));
+            });
+            let shapes = output.shapes.len();
+            assert!(shapes > 1 && shapes < 100, "{} shapes", shapes);
+        }
+
+        #[test]
+        fn test_open_binary_file() {
+            use crate::app::preview::FileKind;
+            let logo = std::path::Path::new("assets/images/Adafruit_logo_small.png");
+            let before = fs::read(logo).unwrap();
+            let mut editor = CodeEditor::default();
+            editor.load_from_file(logo).unwrap();
+            assert_eq!(editor.active_preview().unwrap().kind, FileKind::Image);
+            assert!(editor.active_code().is_none());
+            // previews are never written back
+            editor.save_all().unwrap();
+            assert_eq!(fs::read(logo).unwrap(), before);
+        }
+
+        #[test]
+        fn test_goto_line() {
+            let dir = ScratchDir::new("goto");
+            let main = dir.join("main.rs");
+            fs::write(&main, "fn main() {\n    let x = 1;\n}\n").unwrap();
+            let mut editor = CodeEditor::default();
+            editor.goto_line(&main, 2).unwrap();
+            assert_eq!(editor.pending_goto(), Some(2));
+            assert!(editor.goto_line(&dir.join("missing.rs"), 1).is_err());
+
+            // the cursor is put on the line the next time the file's shown
+            let ctx = egui::Context::default();
+            crate::app::setup_fonts_and_style(&ctx);
+            let _ = ctx.run(Default::default(), |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| editor.display_code(ctx, ui));
+            });
+            assert_eq!(editor.pending_goto(), None);
+        }
+    }
+
+    mod preview_tests {
+        use std::path::Path;
+        use crate::app::preview::*;
+
+        /// A 32-bit ARM ELF file with one loadable segment and no sections.
+        fn arm_elf() -> Vec<u8> {
+            let mut elf = vec![0u8; 52];
+            elf[0..4].copy_from_slice(b"\x7fELF");
+            elf[4] = 1;
+            elf[5] = 1;
+            elf[16..18].copy_from_slice(&2u16.to_le_bytes());
+            elf[18..20].copy_from_slice(&0x28u16.to_le_bytes());
+            elf[24..28].copy_from_slice(&0x1000_0101u32.to_le_bytes());
+            elf[28..32].copy_from_slice(&52u32.to_le_bytes());
+            elf[42..44].copy_from_slice(&32u16.to_le_bytes());
+            elf[44..46].copy_from_slice(&1u16.to_le_bytes());
+            for field in [1u32, 84, 0x1000_0000, 0x1000_0000, 4, 8, 5, 4] {
+                elf.extend(field.to_le_bytes());
+            }
+            elf.extend([0xde, 0xad, 0xbe, 0xef]);
+            elf
+        }
+
+        #[test]
+        fn test_detect() {
+            assert_eq!(detect(Path::new("src/main.rs"), b"fn main() {}\n"), FileKind::Text);
+            assert_eq!(detect(Path::new("assets/logo.PNG"), b""), FileKind::Image);
+            assert_eq!(detect(Path::new("target/blink"), &arm_elf()), FileKind::Elf);
+            assert_eq!(detect(Path::new("firmware.bin"), &[0x20, 0x00, 0x04, 0x20]), FileKind::Binary);
+            assert_eq!(detect(Path::new("notes.txt"), &[b'a', 0xff, b'b']), FileKind::Binary);
+            assert!(Preview::of(Path::new("src/main.rs"), b"fn main() {}\n".to_vec()).is_none());
+        }
+
+        #[test]
+        fn test_hex_line() {
+            let bytes: Vec<u8> = b"Iron Coder\x00\x01\x7f".iter().copied().chain(0x10..0x20).collect();
+            assert_eq!(hex_line(&bytes, 0), "00000000  49 72 6f 6e 20 43 6f 64  65 72 00 01 7f 10 11 12  |Iron Coder......|");
+            assert_eq!(hex_line(&bytes, 16), format!("00000010  13 14 15 16 17 18 19 1a  1b 1c 1d 1e 1f{}  |.............|", "   ".repeat(3)));
+        }
+
+        #[test]
+        fn test_parse_elf() {
+            let summary = parse_elf(&arm_elf()).unwrap();
+            assert_eq!((summary.bits, summary.machine.as_str(), summary.file_type.as_str()), (32, "ARM", "executable"));
+            assert_eq!(summary.entry, 0x1000_0101);
+            assert_eq!(summary.segments, [ElfSegment {
+                kind: "LOAD".to_string(), vaddr: 0x1000_0000, paddr: 0x1000_0000, file_size: 4, mem_size: 8, flags: "R X".to_string(),
+            }]);
+            assert!(summary.sections.is_empty());
+            assert!(parse_elf(&arm_elf()[..40]).is_err());
+            assert!(parse_elf(b"not an elf file at all, but long enough to be one if it were").is_err());
+
+            // this test binary has sections, with their names
+            let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+            if exe.starts_with(b"\x7fELF") {
+                let summary = parse_elf(&exe).unwrap();
+                let text = summary.sections.iter().find(|s| s.name == ".text").unwrap();
+                assert!(text.alloc && text.size > 0 && summary.text >= text.size);
+                assert!(summary.segments.iter().any(|s| s.kind == "LOAD"));
+                let preview = Preview::of(Path::new("iron_coder"), exe).unwrap();
+                assert_eq!(preview.kind, FileKind::Elf);
+                assert!(preview.elf().is_some());
+            }
+        }
+    }
+
+    mod ansi_tests {
+        use crate::app::ansi::*;
+        use crate::app::serial_monitor::SerialMonitor;
+
+        #[test]
+        fn test_ansi_colors() {
+            let mut parser = AnsiParser::default();
+            let line = parser.line("\x1b[1m\x1b[31merror\x1b[0m\x1b[1m: expected `;`\x1b[0m");
+            assert_eq!(line.text, "error: expected `;`");
+            let red = Style { fg: Some(AnsiColor::Indexed(1)), bold: true, ..Default::default() };
+            assert_eq!(line.spans, [
+                Span { range: 0..5, style: red },
+                Span { range: 5..19, style: Style { bold: true, ..Default::default() } },
+            ]);
+            // colors carry on to the next line, until they're reset
+            let line = parser.line("\x1b[38;5;208mINFO \x1b[38;2;10;20;30mboot");
+            assert_eq!(line.spans.iter().map(|s| s.style.fg).collect::<Vec<_>>(), [Some(AnsiColor::Indexed(208)), Some(AnsiColor::Rgb(10, 20, 30))]);
+            assert_eq!(parser.line("done").spans[0].style.fg, Some(AnsiColor::Rgb(10, 20, 30)));
+            assert!(parser.line("\x1b[mplain").spans.is_empty());
+
+            let visuals = egui::Visuals::dark();
+            assert_eq!(color32(AnsiColor::Indexed(16), &visuals), egui::Color32::BLACK);
+            assert_eq!(color32(AnsiColor::Indexed(231), &visuals), egui::Color32::WHITE);
+            assert_eq!(color32(AnsiColor::Indexed(232), &visuals), egui::Color32::from_gray(8));
+            let job = layout_job(&parser.line("\x1b[32mok\x1b[0m then \x1b[4mmore"), 1..9, egui::FontId::monospace(12.0), egui::Color32::GRAY, &visuals);
+            assert_eq!(job.text, "k then m");
+            assert_eq!(job.sections.len(), 3);
+            assert!(job.sections[2].format.underline.width > 0.0);
+        }
+
+        #[test]
+        fn test_ansi_cursor() {
+            let mut parser = AnsiParser::default();
+            // progress bars write over themselves
+            assert_eq!(parser.line("   Building [=>  ] 1/40\r\x1b[K   Compiling blink").text, "   Compiling blink");
+            assert_eq!(parser.line("abcdef\rXY").text, "XYcdef");
+            assert_eq!(parser.line("abc\x08\x08Z\x1b[2CQ").text, "aZc Q");
+            assert_eq!(parser.line("abcdef\x1b[3G\x1b[1K").text, "   def");
+            // titles, bells and moving between lines aren't shown
+            assert_eq!(parser.line("\x1b]0;iron coder\x07\x1b[2A\x07ready\x1b(B").text, "ready");
+            assert_eq!(strip("\x1b[32mone\x1b[0m\r\ntwo\n\x1b[1mthree"), "one\r\ntwo\nthree");
+        }
+
+        #[test]
+        fn test_serial_monitor_lines() {
+            let mut monitor = SerialMonitor::default();
+            monitor.receive("\x1b[33mWARN\x1b[0m low ba");
+            assert_eq!(monitor.lines().count(), 0);
+            assert_eq!(monitor.partial_line().unwrap().text, "WARN low ba");
+            monitor.receive("ttery\r\n> ");
+            let lines: Vec<&StyledLine> = monitor.lines().collect();
+            assert_eq!(lines.len(), 1);
+            assert_eq!(lines[0].text, "WARN low battery");
+            assert_eq!(lines[0].spans[0].range, 0..4);
+            assert_eq!(monitor.partial_line().unwrap().text, "> ");
+            monitor.clear();
+            assert!(monitor.lines().next().is_none() && monitor.partial_line().is_none());
+            // what's kept is limited
+            for _ in 0..20_000 {
+                monitor.receive("0123456789\n");
+            }
+            assert!(monitor.lines().count() < 10_000);
+        }
+
+        #[test]
+        fn test_serial_monitor_panics() {
+            let mut monitor = SerialMonitor::default();
+            monitor.receive("booting\r\n\x1b[31mPERSISTED PANIC: panicked at src/main.rs:12:5 / index out of bounds\x1b[0m\r\n");
+            monitor.receive("PERSISTED PANIC: second\n");
+            assert_eq!(monitor.panics(), ["panicked at src/main.rs:12:5 / index out of bounds", "second"]);
+            // the lines are shown as well, and clearing the output keeps the panics
+            assert_eq!(monitor.lines().count(), 3);
+            monitor.clear();
+            assert_eq!(monitor.panics().len(), 2);
+        }
+    }
+
+    mod rtc_tests {
+        use crate::app::rtc::*;
+        use crate::board::rtc::{Rtc, RtcClockSource, RtcHal};
+        use crate::board::units::Frequency;
+
+        #[test]
+        fn test_rtc_init_code() {
+            let crystal = Rtc { source: RtcClockSource::Crystal, clock: Some(Frequency::from_hz(32_768)), hal: None };
+            let code = rtc_init_code(RtcHal::NrfHal, &crystal);
+            assert!(code.contains("set_lfclk_src_external"));
+            // an 8 Hz tick
+            assert!(code.contains("Rtc::new(p.RTC0, 4095)") && code.contains("ticks at 8 Hz"));
+            let rc = Rtc { source: RtcClockSource::InternalRc, clock: None, hal: None };
+            assert!(rtc_init_code(RtcHal::NrfHal, &rc).contains("set_lfclk_src_rc()"));
+            assert!(!rtc_init_code(RtcHal::Rp2040Hal, &Rtc { source: RtcClockSource::MainClock, ..rc }).contains("by hand"));
+            assert!(rtc_init_code(RtcHal::Rp2040Hal, &rc).contains("by hand"));
+            let sync = sync_code();
+            assert!(sync.contains(SET_TIME_SYMBOL) && sync.contains(SET_TIME) && sync.contains(TIME_REPORT));
+        }
+
+        #[test]
+        fn test_time_sync() {
+            assert_eq!(set_time_line(1_700_000_000_123), "IRONCODER SET-TIME 1700000000123\n");
+            assert_eq!(parse_time_report("INFO IRONCODER TIME 1700000000123\n"), Some(1_700_000_000_123));
+            assert_eq!(parse_time_report("IRONCODER TIME soon"), None);
+            assert_eq!(parse_time_report("hello"), None);
+            assert_eq!(set_time_words(0x1_0000_0002), [2, 1]);
+
+            // a device that gains 50 ms every 1000 s is 50 ppm fast
+            let mut drift = DriftTracker::default();
+            assert_eq!((drift.offset_ms(), drift.drift_ppm()), (None, None));
+            drift.push(1_000_000, 1_000_010);
+            assert_eq!(drift.offset_ms(), Some(10));
+            assert_eq!(drift.drift_ppm(), None);
+            for i in 1..=10 {
+                drift.push(1_000_000 + i * 100_000, 1_000_010 + i * 100_005);
+            }
+            assert!((drift.drift_ppm().unwrap() - 50.0).abs() < 1e-6);
+            assert_eq!(drift.offset_ms(), Some(60));
+
+            // the panel takes the reports out of what the device sends
+            let mut panel = RtcPanel::default();
+            panel.receive("booted", 5_000);
+            panel.receive("IRONCODER TIME 4000", 5_000);
+            assert_eq!(panel.drift().offset_ms(), Some(-1000));
+            assert_eq!(panel.drift().len(), 1);
+        }
+    }
+}
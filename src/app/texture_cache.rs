@@ -0,0 +1,195 @@
+//! Title: Iron Coder App Module - Texture Cache
+//! Description: This module defines the TextureCache, an app-wide store of GPU textures
+//!   with a memory budget. Board images and editor previews are uploaded through the
+//!   cache, and the least recently used textures are evicted whenever the budget is
+//!   exceeded, so long sessions don't grow memory without bound.
+
+use log::debug;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use egui::{ColorImage, Color32, TextureHandle, TextureOptions};
+
+use crate::board::Board;
+
+/// The key used to store the shared cache in the egui Context temp data.
+pub const TEXTURE_CACHE_ID: &str = "texture_cache";
+/// The default texture memory budget, in bytes.
+pub const DEFAULT_TEXTURE_BUDGET: usize = 64 * 1024 * 1024;
+/// The maximum side length, in px, of a board thumbnail.
+pub const THUMBNAIL_SIZE: u32 = 150;
+
+/// A texture that has been uploaded, along with its bookkeeping info.
+struct CachedTexture {
+    handle: TextureHandle,
+    size_bytes: usize,
+    last_used: u64,
+}
+
+/// A keyed texture store with least-recently-used eviction.
+pub struct TextureCache {
+    textures: HashMap<String, CachedTexture>,
+    budget_bytes: usize,
+    used_bytes: usize,
+    /// A monotonically increasing counter used to order texture accesses
+    tick: u64,
+    /// The number of textures that have been evicted since startup
+    evictions: usize,
+}
+
+pub type SharedTextureCache = Arc<Mutex<TextureCache>>;
+
+impl Default for TextureCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TEXTURE_BUDGET)
+    }
+}
+
+impl TextureCache {
+
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            textures: HashMap::new(),
+            budget_bytes,
+            used_bytes: 0,
+            tick: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Return the texture stored under `key`, calling `load` to produce and upload
+    /// the image if it isn't already cached.
+    pub fn get_or_load(&mut self, ctx: &egui::Context, key: &str, load: impl FnOnce() -> ColorImage) -> TextureHandle {
+        self.tick += 1;
+        if let Some(cached) = self.textures.get_mut(key) {
+            cached.last_used = self.tick;
+            return cached.handle.clone();
+        }
+        let image = load();
+        let size_bytes = image.pixels.len() * std::mem::size_of::<Color32>();
+        let handle = ctx.load_texture(key, image, TextureOptions::default());
+        self.textures.insert(key.to_string(), CachedTexture {
+            handle: handle.clone(),
+            size_bytes,
+            last_used: self.tick,
+        });
+        self.used_bytes += size_bytes;
+        self.evict();
+        handle
+    }
+
+    /// Drop the least recently used textures until we're back under budget. The most
+    /// recently used texture is always kept, even if it alone exceeds the budget.
+    fn evict(&mut self) {
+        while self.used_bytes > self.budget_bytes && self.textures.len() > 1 {
+            let lru_key = match self.textures.iter().min_by_key(|(_, t)| t.last_used) {
+                Some((k, _)) => k.clone(),
+                None => return,
+            };
+            if let Some(t) = self.textures.remove(&lru_key) {
+                debug!("evicting texture {} ({} bytes)", lru_key, t.size_bytes);
+                self.used_bytes -= t.size_bytes;
+                self.evictions += 1;
+            }
+        }
+    }
+
+    /// Drop a board's textures, i.e. its image, its thumbnail and its pictures, so they're
+    /// uploaded again from its files the next time it's shown.
+    pub fn forget_board(&mut self, board_key: &str) {
+        let prefix = format!("{}#", board_key);
+        self.textures.retain(|key, texture| {
+            let keep = key != board_key && !key.starts_with(&prefix);
+            if !keep {
+                self.used_bytes -= texture.size_bytes;
+            }
+            keep
+        });
+    }
+
+    pub fn set_budget(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+        self.evict();
+    }
+
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    pub fn evictions(&self) -> usize {
+        self.evictions
+    }
+
+}
+
+/// Fetch the app-wide texture cache from the egui Context, creating it if needed.
+pub fn shared(ctx: &egui::Context) -> SharedTextureCache {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_insert_with(TEXTURE_CACHE_ID.into(), || {
+            Arc::new(Mutex::new(TextureCache::default()))
+        }).clone()
+    })
+}
+
+/// The texture of a board's image, uploaded the first time it's shown and shared by every
+/// view of the board after that. There's none while the image is still being decoded, or
+/// if the board doesn't have one.
+pub fn board_texture(ctx: &egui::Context, board: &Board) -> Option<TextureHandle> {
+    let image = board.svg_board_info.as_ref()?.image.get(ctx)?;
+    let cache = shared(ctx);
+    let handle = cache.lock().unwrap().get_or_load(ctx, &board.key(), || (*image).clone());
+    Some(handle)
+}
+
+/// The texture of one of a board's pictures, by its place in `Board::image_views`, which
+/// starts with the board's own image.
+pub fn gallery_texture(ctx: &egui::Context, board: &Board, view: usize) -> Option<TextureHandle> {
+    let view = match board.svg_board_info.is_some() {
+        true if view == 0 => return board_texture(ctx, board),
+        true => view - 1,
+        false => view,
+    };
+    let picture = board.gallery().get(view)?;
+    let image = picture.image.get(ctx)?;
+    let cache = shared(ctx);
+    let handle = cache.lock().unwrap().get_or_load(ctx, &format!("{}#{}", board.key(), picture.view), || (*image).clone());
+    Some(handle)
+}
+
+/// Downscale an image so that its longest side is at most `max_side` px. Images
+/// that are already small enough are returned unchanged.
+pub fn thumbnail(image: &ColorImage, max_side: u32) -> ColorImage {
+    let [w, h] = image.size;
+    if w as u32 <= max_side && h as u32 <= max_side {
+        return image.clone();
+    }
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+    let rgba = match image::RgbaImage::from_raw(w as u32, h as u32, bytes) {
+        Some(rgba) => rgba,
+        None => return image.clone(),
+    };
+    let scale = max_side as f32 / w.max(h) as f32;
+    let (tw, th) = (((w as f32 * scale) as u32).max(1), ((h as f32 * scale) as u32).max(1));
+    let small = image::imageops::thumbnail(&rgba, tw, th);
+    // the pixels are already premultiplied, so copy them over as-is
+    let pixels = small.pixels().map(|p| {
+        Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3])
+    }).collect();
+    ColorImage {
+        size: [tw as usize, th as usize],
+        pixels,
+    }
+}
@@ -0,0 +1,813 @@
+//! Title: Iron Coder App Module - Toolbox
+//! Description: Calculators for the arithmetic that comes up while wiring up and
+//!   configuring a board: SI prefixes and unit conversions, voltage dividers, resistor
+//!   color codes, ADC counts and PWM timer settings. The ADC and PWM calculators start
+//!   from the main board's ADC reference and timer clock, which can be overridden.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::{Adc, Board};
+use crate::board::pwm::{Prescaler, PwmHal};
+use crate::board::units::current_locale;
+
+const SI_PREFIXES: [(char, f64); 9] = [
+    ('p', 1e-12), ('n', 1e-9), ('u', 1e-6), ('µ', 1e-6), ('m', 1e-3),
+    ('k', 1e3), ('K', 1e3), ('M', 1e6), ('G', 1e9),
+];
+
+/// Parse a value written with an optional SI prefix and unit, i.e. "4.7k", "100 nF" or
+/// "3.3V". As on schematics, the prefix or unit can stand in for the decimal point, so
+/// "4k7" is 4700 and "3V3" is 3.3.
+pub fn parse_si(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let invalid = || format!("\"{}\" isn't a number", s);
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(s.len());
+    let (number, rest) = s.split_at(split);
+    let mut chars = rest.trim_start().chars();
+    let multiplier = match chars.next() {
+        Some(c) => SI_PREFIXES.iter().find(|(p, _)| *p == c).map_or(1.0, |(_, m)| *m),
+        None => 1.0,
+    };
+    let decimals: String = chars.as_str().chars().take_while(|c| c.is_ascii_digit()).collect();
+    let number = if decimals.is_empty() {
+        number.to_string()
+    } else if number.contains('.') {
+        return Err(invalid());
+    } else {
+        format!("{}.{}", number, decimals)
+    };
+    number.parse::<f64>().map(|n| n * multiplier).map_err(|_| invalid())
+}
+
+/// Write a value with the SI prefix that keeps it between 1 and 1000, i.e. "4.7 kΩ".
+pub fn format_si(value: f64, unit: &str) -> String {
+    if !value.is_finite() {
+        return format!("- {}", unit);
+    }
+    let locale = current_locale();
+    let magnitude = value.abs();
+    for (prefix, multiplier) in [("G", 1e9), ("M", 1e6), ("k", 1e3), ("", 1.0), ("m", 1e-3), ("µ", 1e-6), ("n", 1e-9), ("p", 1e-12)] {
+        // compare with a little slack, so 999.999 rounds up to "1 k" rather than "1000"
+        if magnitude >= multiplier * 0.999995 {
+            let sign = if value < 0.0 { "-" } else { "" };
+            return format!("{}{} {}{}", sign, locale.format_number(magnitude / multiplier), prefix, unit);
+        }
+    }
+    format!("0 {}", unit)
+}
+
+/// Conversions between units that show up in datasheets.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Conversion {
+    #[default]
+    MilToMillimeter,
+    InchToMillimeter,
+    CelsiusToFahrenheit,
+    DbmToMilliwatt,
+    /// A frequency and its period
+    HertzToSecond,
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (from, to) = self.units();
+        write!(f, "{} → {}", from, to)
+    }
+}
+
+impl Conversion {
+
+    pub fn units(&self) -> (&'static str, &'static str) {
+        match self {
+            Conversion::MilToMillimeter => ("mil", "mm"),
+            Conversion::InchToMillimeter => ("in", "mm"),
+            Conversion::CelsiusToFahrenheit => ("°C", "°F"),
+            Conversion::DbmToMilliwatt => ("dBm", "mW"),
+            Conversion::HertzToSecond => ("Hz", "s"),
+        }
+    }
+
+    pub fn convert(&self, value: f64) -> f64 {
+        match self {
+            Conversion::MilToMillimeter => value * 0.0254,
+            Conversion::InchToMillimeter => value * 25.4,
+            Conversion::CelsiusToFahrenheit => value * 9.0 / 5.0 + 32.0,
+            Conversion::DbmToMilliwatt => 10f64.powf(value / 10.0),
+            Conversion::HertzToSecond => 1.0 / value,
+        }
+    }
+
+    /// The conversion in the other direction.
+    pub fn invert(&self, value: f64) -> f64 {
+        match self {
+            Conversion::MilToMillimeter => value / 0.0254,
+            Conversion::InchToMillimeter => value / 25.4,
+            Conversion::CelsiusToFahrenheit => (value - 32.0) * 5.0 / 9.0,
+            Conversion::DbmToMilliwatt => 10.0 * value.log10(),
+            Conversion::HertzToSecond => 1.0 / value,
+        }
+    }
+}
+
+/// The standard resistor values in a decade of the E24 series (5% tolerance).
+pub const E24: [f64; 24] = [
+    1.0, 1.1, 1.2, 1.3, 1.5, 1.6, 1.8, 2.0, 2.2, 2.4, 2.7, 3.0,
+    3.3, 3.6, 3.9, 4.3, 4.7, 5.1, 5.6, 6.2, 6.8, 7.5, 8.2, 9.1,
+];
+
+/// The E24 value closest to `ohms`.
+pub fn nearest_e24(ohms: f64) -> f64 {
+    if !(ohms.is_finite() && ohms > 0.0) {
+        return ohms;
+    }
+    let decade = 10f64.powf(ohms.log10().floor());
+    // the next decade's 1.0 can be the closest, i.e. for 9.7k
+    E24.iter().chain([10.0].iter())
+        .map(|v| v * decade)
+        .min_by(|a, b| (a - ohms).abs().total_cmp(&(b - ohms).abs()))
+        .unwrap_or(ohms)
+}
+
+/// The output of a divider with `r1` between the input and the output, and `r2` between
+/// the output and ground.
+pub fn divider_output(vin: f64, r1: f64, r2: f64) -> f64 {
+    vin * r2 / (r1 + r2)
+}
+
+/// The top resistor that divides `vin` down to `vout`, given the bottom one.
+pub fn divider_r1(vin: f64, vout: f64, r2: f64) -> Result<f64, String> {
+    if !(vout > 0.0 && vout < vin) {
+        return Err("the output must be between 0 and the input voltage".to_string());
+    }
+    Ok(r2 * (vin - vout) / vout)
+}
+
+/// The colors of the bands on a through-hole resistor.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub enum ColorBand {
+    Black,
+    Brown,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Violet,
+    Grey,
+    White,
+    Gold,
+    Silver,
+}
+
+impl fmt::Display for ColorBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+impl ColorBand {
+
+    /// The digit the color stands for in the value bands.
+    pub fn digit(&self) -> Option<u32> {
+        let digit = *self as u32;
+        (digit < 10).then_some(digit)
+    }
+
+    /// The power of ten the color stands for in the multiplier band.
+    pub fn exponent(&self) -> i32 {
+        match self {
+            ColorBand::Gold => -1,
+            ColorBand::Silver => -2,
+            band => *band as i32,
+        }
+    }
+
+    /// The tolerance, in percent, the color stands for in the last band.
+    pub fn tolerance(&self) -> Option<f64> {
+        match self {
+            ColorBand::Brown => Some(1.0),
+            ColorBand::Red => Some(2.0),
+            ColorBand::Green => Some(0.5),
+            ColorBand::Blue => Some(0.25),
+            ColorBand::Violet => Some(0.1),
+            ColorBand::Grey => Some(0.05),
+            ColorBand::Gold => Some(5.0),
+            ColorBand::Silver => Some(10.0),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            ColorBand::Black => egui::Color32::BLACK,
+            ColorBand::Brown => egui::Color32::from_rgb(0x8b, 0x45, 0x13),
+            ColorBand::Red => egui::Color32::from_rgb(0xe0, 0x20, 0x20),
+            ColorBand::Orange => egui::Color32::from_rgb(0xff, 0x8c, 0x00),
+            ColorBand::Yellow => egui::Color32::from_rgb(0xff, 0xe0, 0x00),
+            ColorBand::Green => egui::Color32::from_rgb(0x20, 0xa0, 0x20),
+            ColorBand::Blue => egui::Color32::from_rgb(0x20, 0x40, 0xe0),
+            ColorBand::Violet => egui::Color32::from_rgb(0x90, 0x30, 0xc0),
+            ColorBand::Grey => egui::Color32::GRAY,
+            ColorBand::White => egui::Color32::WHITE,
+            ColorBand::Gold => egui::Color32::from_rgb(0xcf, 0xb5, 0x3b),
+            ColorBand::Silver => egui::Color32::from_rgb(0xc0, 0xc0, 0xc0),
+        }
+    }
+}
+
+/// Read a resistor's bands: two or three digits, a multiplier and, unless there are
+/// only three bands, a tolerance. Returns the resistance and its tolerance in percent.
+pub fn resistor_value(bands: &[ColorBand]) -> Result<(f64, f64), String> {
+    let (digits, multiplier, tolerance) = match bands {
+        [a, b, m] => ([*a, *b].to_vec(), m, None),
+        [a, b, m, t] => ([*a, *b].to_vec(), m, Some(t)),
+        [a, b, c, m, t] => ([*a, *b, *c].to_vec(), m, Some(t)),
+        _ => return Err("a resistor has 3, 4 or 5 bands".to_string()),
+    };
+    let mut value = 0;
+    for band in digits {
+        let digit = band.digit().ok_or_else(|| format!("{} isn't a digit", band))?;
+        value = value * 10 + digit;
+    }
+    let tolerance = match tolerance {
+        Some(t) => t.tolerance().ok_or_else(|| format!("{} isn't a tolerance", t))?,
+        // without a tolerance band, it's 20%
+        None => 20.0,
+    };
+    Ok((value as f64 * 10f64.powi(multiplier.exponent()), tolerance))
+}
+
+/// The value and multiplier bands for a resistance, with `digits` (2 or 3) value bands.
+/// The resistance is rounded to that many significant digits.
+pub fn resistor_bands(ohms: f64, digits: u32) -> Result<Vec<ColorBand>, String> {
+    if !(ohms.is_finite() && ohms > 0.0) {
+        return Err("the resistance must be positive".to_string());
+    }
+    let mut exponent = ohms.log10().floor() as i32 - (digits as i32 - 1);
+    let mut significand = (ohms / 10f64.powi(exponent)).round() as u32;
+    // rounding can carry into another digit, i.e. 9.99k with two digits
+    if significand >= 10u32.pow(digits) {
+        significand /= 10;
+        exponent += 1;
+    }
+    let multiplier = match exponent {
+        -2 => ColorBand::Silver,
+        -1 => ColorBand::Gold,
+        0..=9 => enum_iterator::all::<ColorBand>().nth(exponent as usize).unwrap(),
+        _ => return Err(format!("{} can't be written with color bands", format_si(ohms, "Ω"))),
+    };
+    let mut bands: Vec<ColorBand> = (0..digits).rev()
+        .map(|i| enum_iterator::all::<ColorBand>().nth((significand / 10u32.pow(i) % 10) as usize).unwrap())
+        .collect();
+    bands.push(multiplier);
+    Ok(bands)
+}
+
+/// The voltage an ADC reading stands for. One count is `reference / 2^resolution`.
+pub fn adc_voltage(count: u32, adc: &Adc) -> f64 {
+    count as f64 * adc.reference / 2f64.powi(adc.resolution as i32)
+}
+
+/// The reading an ideal ADC gives for a voltage, clamped to its range.
+pub fn adc_count(voltage: f64, adc: &Adc) -> u32 {
+    let full_scale = 2f64.powi(adc.resolution as i32);
+    (voltage / adc.reference * full_scale).round().clamp(0.0, full_scale - 1.0) as u32
+}
+
+/// A PWM timer: a counter driven by `clock` through a prescaler, which wraps after
+/// counting to `top`, giving a frequency of `clock / (divider * (top + 1))`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PwmTimer {
+    pub clock: f64,
+    /// The largest top the counter can count to
+    pub max_top: u32,
+    pub prescaler: Prescaler,
+}
+
+/// The timer settings for a PWM frequency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PwmSettings {
+    /// The whole part of the divider
+    pub divider: u32,
+    /// The fractional part of the divider, in steps of the prescaler's fraction
+    pub divider_frac: u32,
+    pub top: u32,
+    /// The frequency the settings actually give
+    pub frequency: f64,
+}
+
+impl PwmSettings {
+
+    /// The number of distinct duty cycles, in bits.
+    pub fn resolution(&self) -> f64 {
+        (self.top as f64 + 1.0).log2()
+    }
+
+    /// The compare value for a duty cycle in percent.
+    pub fn compare(&self, duty: f64) -> u32 {
+        (duty.clamp(0.0, 100.0) / 100.0 * (self.top as f64 + 1.0)).round() as u32
+    }
+
+    /// The duty cycle, in percent, of a compare value.
+    pub fn duty(&self, compare: u32) -> f64 {
+        (compare as f64 / (self.top as f64 + 1.0) * 100.0).min(100.0)
+    }
+}
+
+/// How far off a frequency the settings for a given resolution can be before they're
+/// flagged as unreachable.
+const MAX_FREQUENCY_ERROR: f64 = 0.01;
+
+impl PwmTimer {
+
+    /// A board's PWM timer, if the board describes it.
+    pub fn from_board(board: &Board) -> Option<Self> {
+        let pwm = board.pwm()?;
+        Some(Self { clock: board.timer_clock()?.hz() as f64, max_top: pwm.max_top(), prescaler: pwm.prescaler })
+    }
+
+    fn settings_with(&self, (divider, divider_frac): (u32, u32), counts: f64) -> PwmSettings {
+        let frequency = self.clock / (self.prescaler.value(divider, divider_frac) * counts);
+        PwmSettings { divider, divider_frac, top: counts as u32 - 1, frequency }
+    }
+
+    /// The settings closest to `frequency`, with the smallest divider that reaches it,
+    /// since that leaves the most counts for the duty cycle.
+    pub fn settings(&self, frequency: f64) -> Option<PwmSettings> {
+        self.solve(frequency, None).ok()
+    }
+
+    /// The settings for `frequency`, either with as much resolution as possible, or with
+    /// exactly `resolution` bits. Explains why if the timer can't get there.
+    pub fn solve(&self, frequency: f64, resolution: Option<u32>) -> Result<PwmSettings, String> {
+        if !(frequency > 0.0 && self.clock > 0.0) {
+            return Err("the frequency and timer clock must be positive".to_string());
+        }
+        let max_counts = self.max_top as f64 + 1.0;
+        let Some(bits) = resolution else {
+            let ideal = self.clock / (frequency * max_counts);
+            let [_, divider] = self.prescaler.neighbours(ideal);
+            let value = self.prescaler.value(divider.0, divider.1);
+            if value < ideal {
+                let slowest = self.clock / (value * max_counts);
+                return Err(format!("too slow: the timer can't go below {}", format_si(slowest, "Hz")));
+            }
+            let counts = (self.clock / (value * frequency)).round().min(max_counts);
+            if counts < 2.0 {
+                return Err(format!("too fast: the timer clock is only {}", format_si(self.clock, "Hz")));
+            }
+            return Ok(self.settings_with(divider, counts));
+        };
+        let counts = 2f64.powi(bits as i32);
+        if bits == 0 || counts > max_counts {
+            return Err(format!("the counter only has room for {} bits", max_counts.log2().floor()));
+        }
+        let error = |s: &PwmSettings| (s.frequency - frequency).abs() / frequency;
+        let best = self.prescaler.neighbours(self.clock / (frequency * counts)).iter()
+            .map(|d| self.settings_with(*d, counts))
+            .min_by(|a, b| error(a).total_cmp(&error(b)))
+            .unwrap();
+        if error(&best) > MAX_FREQUENCY_ERROR {
+            return Err(format!(
+                "{} bits at {} isn't reachable; the closest is {}",
+                bits, format_si(frequency, "Hz"), format_si(best.frequency, "Hz"),
+            ));
+        }
+        Ok(best)
+    }
+}
+
+/// The code that sets up a PWM output with `settings` and a duty cycle in percent,
+/// for the board's HAL. Pins and peripheral instances are placeholders to fill in.
+pub fn pwm_code(hal: PwmHal, settings: &PwmSettings, duty: f64) -> String {
+    let compare = settings.compare(duty);
+    let header = format!(
+        "// {} PWM, {:.1} bits of resolution, {:.2}% duty\n",
+        format_si(settings.frequency, "Hz"), settings.resolution(), settings.duty(compare),
+    );
+    let body = match hal {
+        PwmHal::Rp2040Hal => format!(
+"let mut pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+let pwm = &mut pwm_slices.pwm0;
+pwm.set_div_int({});
+pwm.set_div_frac({});
+pwm.set_top({});
+pwm.enable();
+let channel = &mut pwm.channel_a;
+channel.output_to(pins.gpio0);
+channel.set_duty({});
+", settings.divider, settings.divider_frac, settings.top, compare),
+        // COUNTERTOP is the number of counts in a period
+        PwmHal::NrfHal => format!(
+"let pwm = hal::pwm::Pwm::new(p.PWM0);
+pwm.set_output_pin(hal::pwm::Channel::C0, pin);
+pwm.set_prescaler(hal::pwm::Prescaler::Div{});
+pwm.set_max_duty({});
+pwm.set_duty_on(hal::pwm::Channel::C0, {});
+", settings.divider, settings.top + 1, compare),
+        // the LEDC driver works out the divider itself from the frequency and resolution
+        PwmHal::EspHal => format!(
+"let mut ledc = LEDC::new(peripherals.LEDC, &clocks, &mut system.peripheral_clock_control);
+let mut hstimer0 = ledc.get_timer::<HighSpeed>(timer::Number::Timer0);
+hstimer0.configure(timer::config::Config {{
+    duty: timer::config::Duty::Duty{}Bit,
+    clock_source: timer::HSClockSource::APBClk,
+    frequency: {}u32.Hz(),
+}}).unwrap();
+let mut channel0 = ledc.get_channel(channel::Number::Channel0, pin);
+channel0.configure(channel::config::Config {{
+    timer: &hstimer0,
+    duty_pct: {},
+    pin_config: channel::config::PinConfig::PushPull,
+}}).unwrap();
+", settings.resolution().floor(), settings.frequency.round(), duty.round()),
+    };
+    header + &body
+}
+
+/// The calculators in the toolbox.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Tool {
+    #[default]
+    Units,
+    Divider,
+    ColorCode,
+    Adc,
+    Pwm,
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tool::Units => write!(f, "units"),
+            Tool::Divider => write!(f, "voltage divider"),
+            Tool::ColorCode => write!(f, "color code"),
+            Tool::Adc => write!(f, "ADC"),
+            Tool::Pwm => write!(f, "PWM"),
+        }
+    }
+}
+
+/// The state of the toolbox window. Quantities are kept as the text the user typed,
+/// so they can be written with SI prefixes.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolboxPanel {
+    tool: Tool,
+    conversion: Conversion,
+    conversion_inverted: bool,
+    conversion_value: String,
+    si_value: String,
+    divider_vin: String,
+    divider_r1: String,
+    divider_r2: String,
+    divider_vout: String,
+    bands: Vec<ColorBand>,
+    band_resistance: String,
+    /// Overrides for the board's ADC, empty to use the board's
+    adc_reference: String,
+    adc_resolution: String,
+    adc_count: u32,
+    adc_voltage: String,
+    /// An override for the board's timer clock, empty to use the board's
+    pwm_clock: String,
+    pwm_counter_bits: u32,
+    pwm_max_divider: u32,
+    pwm_frequency: String,
+    /// The resolution to aim for, empty for the finest the timer can do
+    pwm_resolution: String,
+    pwm_duty: f64,
+    /// The HAL to write code for, if not the board's
+    pwm_hal: Option<PwmHal>,
+}
+
+impl Default for ToolboxPanel {
+    fn default() -> Self {
+        Self {
+            tool: Tool::default(),
+            conversion: Conversion::default(),
+            conversion_inverted: false,
+            conversion_value: "10".to_string(),
+            si_value: "4k7".to_string(),
+            divider_vin: "5V".to_string(),
+            divider_r1: "10k".to_string(),
+            divider_r2: "20k".to_string(),
+            divider_vout: "3.3V".to_string(),
+            bands: vec![ColorBand::Yellow, ColorBand::Violet, ColorBand::Red, ColorBand::Gold],
+            band_resistance: "4.7k".to_string(),
+            adc_reference: String::new(),
+            adc_resolution: String::new(),
+            adc_count: 2048,
+            adc_voltage: "1.65V".to_string(),
+            pwm_clock: String::new(),
+            pwm_counter_bits: 16,
+            pwm_max_divider: 256,
+            pwm_frequency: "1 kHz".to_string(),
+            pwm_resolution: String::new(),
+            pwm_duty: 50.0,
+            pwm_hal: None,
+        }
+    }
+}
+
+/// Show a quantity, or why it couldn't be computed.
+fn result_label(ui: &mut egui::Ui, result: Result<String, String>) {
+    match result {
+        Ok(s) => ui.strong(s),
+        Err(e) => ui.colored_label(ui.style().visuals.error_fg_color, e),
+    };
+}
+
+impl ToolboxPanel {
+
+    fn display_units(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("toolbox_units").num_columns(2).show(ui, |ui| {
+            ui.label("conversion:");
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("toolbox_conversion")
+                .selected_text(self.conversion.to_string())
+                .show_ui(ui, |ui| {
+                    for c in enum_iterator::all::<Conversion>() {
+                        ui.selectable_value(&mut self.conversion, c, c.to_string());
+                    }
+                });
+                ui.toggle_value(&mut self.conversion_inverted, "⇄").on_hover_text("convert the other way");
+            });
+            ui.end_row();
+            let (mut from, mut to) = self.conversion.units();
+            if self.conversion_inverted {
+                std::mem::swap(&mut from, &mut to);
+            }
+            ui.label(format!("{}:", from));
+            ui.text_edit_singleline(&mut self.conversion_value);
+            ui.end_row();
+            ui.label(format!("{}:", to));
+            result_label(ui, parse_si(&self.conversion_value).map(|v| {
+                let converted = if self.conversion_inverted { self.conversion.invert(v) } else { self.conversion.convert(v) };
+                format_si(converted, to)
+            }));
+            ui.end_row();
+            ui.label("SI value:");
+            ui.add(egui::TextEdit::singleline(&mut self.si_value).hint_text("4k7, 100n, 2.2M"));
+            ui.end_row();
+            ui.label("");
+            result_label(ui, parse_si(&self.si_value).map(|v| format!("{} = {}", format_si(v, ""), v)));
+            ui.end_row();
+        });
+    }
+
+    fn display_divider(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("toolbox_divider").num_columns(2).show(ui, |ui| {
+            for (label, value) in [("Vin:", &mut self.divider_vin), ("R1 (top):", &mut self.divider_r1), ("R2 (bottom):", &mut self.divider_r2)] {
+                ui.label(label);
+                ui.text_edit_singleline(value);
+                ui.end_row();
+            }
+            let inputs = (parse_si(&self.divider_vin), parse_si(&self.divider_r1), parse_si(&self.divider_r2));
+            ui.label("Vout:");
+            let (vin, r1, r2) = match inputs {
+                (Ok(vin), Ok(r1), Ok(r2)) if r1 >= 0.0 && r2 >= 0.0 && r1 + r2 > 0.0 => (vin, r1, r2),
+                (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                    result_label(ui, Err(e));
+                    return;
+                },
+                _ => {
+                    result_label(ui, Err("the resistors can't be negative".to_string()));
+                    return;
+                },
+            };
+            result_label(ui, Ok(format_si(divider_output(vin, r1, r2), "V")));
+            ui.end_row();
+            ui.label("current:");
+            result_label(ui, Ok(format_si(vin / (r1 + r2), "A")));
+            ui.end_row();
+            ui.separator();
+            ui.end_row();
+            ui.label("target Vout:");
+            ui.text_edit_singleline(&mut self.divider_vout);
+            ui.end_row();
+            ui.label("R1 for target:");
+            result_label(ui, parse_si(&self.divider_vout).and_then(|vout| divider_r1(vin, vout, r2)).map(|r1| {
+                let standard = nearest_e24(r1);
+                format!("{} (E24: {}, giving {})", format_si(r1, "Ω"), format_si(standard, "Ω"), format_si(divider_output(vin, standard, r2), "V"))
+            }));
+            ui.end_row();
+        });
+    }
+
+    fn display_color_code(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("bands:");
+            for n in [3, 4, 5] {
+                if ui.selectable_label(self.bands.len() == n, n.to_string()).clicked() {
+                    self.bands.resize(n, ColorBand::Gold);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            for (i, band) in self.bands.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("toolbox_band", i))
+                .selected_text(egui::RichText::new("■").color(band.color()))
+                .width(40.0)
+                .show_ui(ui, |ui| {
+                    for c in enum_iterator::all::<ColorBand>() {
+                        ui.selectable_value(band, c, egui::RichText::new(format!("■ {}", c)).color(c.color()));
+                    }
+                });
+            }
+        });
+        result_label(ui, resistor_value(&self.bands).map(|(ohms, tolerance)| format!("{} ±{}%", format_si(ohms, "Ω"), tolerance)));
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("resistance:");
+            ui.text_edit_singleline(&mut self.band_resistance);
+        });
+        for digits in [2, 3] {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} bands:", digits + 2));
+                match parse_si(&self.band_resistance).and_then(|ohms| resistor_bands(ohms, digits)) {
+                    Ok(bands) => for band in bands {
+                        ui.label(egui::RichText::new(format!("■ {}", band)).color(band.color()));
+                    },
+                    Err(e) => result_label(ui, Err(e)),
+                }
+                // the tolerance band is the user's pick
+                ui.label("+ tolerance");
+            });
+        }
+    }
+
+    /// The ADC to calculate with: the board's, with the user's overrides.
+    fn adc(&self, board: Option<&Board>) -> Result<Adc, String> {
+        let board_adc = board.and_then(Board::adc);
+        let reference = match (self.adc_reference.trim(), board_adc) {
+            ("", Some(adc)) => adc.reference,
+            ("", None) => return Err("the board doesn't say what its ADC reference is".to_string()),
+            (s, _) => parse_si(s)?,
+        };
+        let resolution = match (self.adc_resolution.trim(), board_adc) {
+            ("", Some(adc)) => adc.resolution,
+            ("", None) => 12,
+            (s, _) => s.parse().map_err(|_| format!("\"{}\" isn't a number of bits", s))?,
+        };
+        if !(reference > 0.0 && (1..=24).contains(&resolution)) {
+            return Err("the ADC needs a positive reference and 1 to 24 bits".to_string());
+        }
+        Ok(Adc { resolution, reference })
+    }
+
+    fn display_adc(&mut self, ui: &mut egui::Ui, board: Option<&Board>) {
+        let board_adc = board.and_then(Board::adc);
+        egui::Grid::new("toolbox_adc").num_columns(2).show(ui, |ui| {
+            ui.label("reference:");
+            let hint = board_adc.map(|a| format_si(a.reference, "V")).unwrap_or_default();
+            ui.add(egui::TextEdit::singleline(&mut self.adc_reference).hint_text(hint));
+            ui.end_row();
+            ui.label("resolution (bits):");
+            let hint = board_adc.map(|a| a.resolution).unwrap_or(12).to_string();
+            ui.add(egui::TextEdit::singleline(&mut self.adc_resolution).hint_text(hint));
+            ui.end_row();
+            let adc = match self.adc(board) {
+                Ok(adc) => adc,
+                Err(e) => {
+                    result_label(ui, Err(e));
+                    return;
+                },
+            };
+            ui.label("step:");
+            result_label(ui, Ok(format_si(adc_voltage(1, &adc), "V")));
+            ui.end_row();
+            ui.label("count:");
+            let max = (1u32 << adc.resolution) - 1;
+            ui.add(egui::DragValue::new(&mut self.adc_count).clamp_range(0..=max));
+            ui.end_row();
+            ui.label("voltage:");
+            result_label(ui, Ok(format_si(adc_voltage(self.adc_count, &adc), "V")));
+            ui.end_row();
+            ui.separator();
+            ui.end_row();
+            ui.label("voltage:");
+            ui.text_edit_singleline(&mut self.adc_voltage);
+            ui.end_row();
+            ui.label("count:");
+            result_label(ui, parse_si(&self.adc_voltage).map(|v| adc_count(v, &adc).to_string()));
+            ui.end_row();
+        });
+    }
+
+    fn display_pwm(&mut self, ui: &mut egui::Ui, board: Option<&Board>) {
+        let board_clock = board.and_then(Board::timer_clock);
+        let board_pwm = board.and_then(Board::pwm);
+        let mut settings = Err(String::new());
+        egui::Grid::new("toolbox_pwm").num_columns(2).show(ui, |ui| {
+            ui.label("timer clock:");
+            let hint = board_clock.map(|c| c.to_string()).unwrap_or_default();
+            ui.add(egui::TextEdit::singleline(&mut self.pwm_clock).hint_text(hint));
+            ui.end_row();
+            // the board's timers, or the user's description of them
+            let (max_top, prescaler) = match board_pwm {
+                Some(pwm) => {
+                    ui.label("timer:");
+                    ui.label(format!("{} bit counter, {}", pwm.counter_bits, pwm.prescaler));
+                    ui.end_row();
+                    (pwm.max_top(), pwm.prescaler)
+                },
+                None => {
+                    ui.label("counter bits:");
+                    ui.add(egui::DragValue::new(&mut self.pwm_counter_bits).clamp_range(2..=32));
+                    ui.end_row();
+                    ui.label("largest divider:");
+                    ui.add(egui::DragValue::new(&mut self.pwm_max_divider).clamp_range(1..=65536));
+                    ui.end_row();
+                    (((1u64 << self.pwm_counter_bits) - 1) as u32, Prescaler::Integer { max: self.pwm_max_divider })
+                },
+            };
+            ui.label("frequency:");
+            ui.text_edit_singleline(&mut self.pwm_frequency);
+            ui.end_row();
+            ui.label("resolution (bits):");
+            ui.add(egui::TextEdit::singleline(&mut self.pwm_resolution).hint_text("as fine as possible"));
+            ui.end_row();
+            ui.label("duty (%):");
+            ui.add(egui::Slider::new(&mut self.pwm_duty, 0.0..=100.0));
+            ui.end_row();
+            let clock = match (self.pwm_clock.trim(), board_clock) {
+                ("", Some(clock)) => Ok(clock.hz() as f64),
+                ("", None) => Err("the board doesn't say what its timer clock is".to_string()),
+                (s, _) => parse_si(s),
+            };
+            let resolution = match self.pwm_resolution.trim() {
+                "" => Ok(None),
+                s => s.parse().map(Some).map_err(|_| format!("\"{}\" isn't a number of bits", s)),
+            };
+            settings = clock.and_then(|clock| {
+                let timer = PwmTimer { clock, max_top, prescaler };
+                timer.solve(parse_si(&self.pwm_frequency)?, resolution?)
+            });
+            ui.label("settings:");
+            match &settings {
+                Ok(settings) => {
+                    let divider = prescaler.value(settings.divider, settings.divider_frac);
+                    result_label(ui, Ok(format!("divider {}, top {}", divider, settings.top)));
+                    ui.end_row();
+                    ui.label("actual frequency:");
+                    result_label(ui, Ok(format_si(settings.frequency, "Hz")));
+                    ui.end_row();
+                    ui.label("resolution:");
+                    result_label(ui, Ok(format!("{:.1} bits", settings.resolution())));
+                    ui.end_row();
+                    ui.label("compare value:");
+                    let compare = settings.compare(self.pwm_duty);
+                    result_label(ui, Ok(format!("{} ({:.2}%)", compare, settings.duty(compare))));
+                },
+                Err(e) => result_label(ui, Err(e.clone())),
+            }
+            ui.end_row();
+        });
+        let Ok(settings) = settings else {
+            return;
+        };
+        ui.separator();
+        // the board's HAL, unless the user picked another
+        let hal = self.pwm_hal.or(board_pwm.and_then(|p| p.hal));
+        ui.horizontal(|ui| {
+            ui.label("code for:");
+            egui::ComboBox::from_id_source("toolbox_pwm_hal")
+            .selected_text(hal.map(|h| h.to_string()).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for h in enum_iterator::all::<PwmHal>() {
+                    ui.selectable_value(&mut self.pwm_hal, Some(h), h.to_string());
+                }
+            });
+            if let Some(hal) = hal {
+                if ui.button("copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = pwm_code(hal, &settings, self.pwm_duty));
+                }
+            }
+        });
+        if let Some(hal) = hal {
+            let mut code = pwm_code(hal, &settings, self.pwm_duty);
+            ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+        }
+    }
+
+    /// Show the calculators. `board` is the project's main board, if it has one.
+    pub fn display(&mut self, ui: &mut egui::Ui, board: Option<&Board>) {
+        ui.horizontal(|ui| {
+            for tool in enum_iterator::all::<Tool>() {
+                ui.selectable_value(&mut self.tool, tool, tool.to_string());
+            }
+        });
+        ui.separator();
+        match self.tool {
+            Tool::Units => self.display_units(ui),
+            Tool::Divider => self.display_divider(ui),
+            Tool::ColorCode => self.display_color_code(ui),
+            Tool::Adc => self.display_adc(ui, board),
+            Tool::Pwm => self.display_pwm(ui, board),
+        }
+    }
+}
@@ -0,0 +1,710 @@
+//! Title: Iron Coder App Module - Trace
+//! Description: A timeline of what the main board's cpu does, from the ITM packets it
+//!   sends over SWO. The raw SWO stream is read from a trace server (i.e. Orbuculum, or
+//!   OpenOCD serving its TPIU output on a port) or from a capture file, and decoded into
+//!   writes to the stimulus ports and the exception trace. Interrupt handlers are drawn as
+//!   spans from entry to exit, next to the stimulus writes and the lines of a defmt log.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use rfd::FileDialog;
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::app::toolbox::format_si;
+use crate::board::Board;
+
+/// The port Orbuculum serves the raw SWO stream on.
+const DEFAULT_SERVER: &str = "localhost:2332";
+/// The most events kept, after which the oldest are dropped.
+const MAX_EVENTS: usize = 100_000;
+/// How long connecting to the trace server may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What an exception trace packet says the cpu did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExceptionAction {
+    Enter,
+    Exit,
+    /// Returned to the exception (or thread mode, as exception 0) it had interrupted
+    Return,
+}
+
+/// An ITM packet, as described in appendix D4 of the ARMv7-M Architecture Reference
+/// Manual.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ItmPacket {
+    Sync,
+    /// The ITM dropped packets because the SWO pin couldn't keep up
+    Overflow,
+    /// The timestamp clock ticks since the last local timestamp
+    LocalTimestamp(u32),
+    GlobalTimestamp,
+    Extension,
+    /// A write to a stimulus port, in the order the bytes were written
+    Stimulus { port: u8, data: Vec<u8> },
+    Exception { number: u16, action: ExceptionAction },
+    EventCounter(u8),
+    /// A periodic sample of the program counter, or None if the cpu was asleep
+    PcSample(Option<u32>),
+    /// A packet from the DWT comparators
+    DataTrace { id: u8, data: Vec<u8> },
+}
+
+/// Read the bytes after a header that continue while their top bit is set, as in
+/// timestamps and extensions. None if the packet isn't all there yet.
+fn continuation(bytes: &[u8], max: usize) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for (i, byte) in bytes.iter().take(max).enumerate() {
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 || i + 1 == max {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Decode the packet at the start of `bytes`, returning it and the bytes it took. The
+/// packet is None for bytes that don't start a packet, which are skipped. None
+/// altogether if the packet isn't all there yet.
+fn decode_packet(bytes: &[u8]) -> Option<(Option<ItmPacket>, usize)> {
+    let header = *bytes.first()?;
+    match header {
+        // a synchronization packet is at least 47 zero bits then a one
+        0x00 => {
+            let zeros = bytes.iter().take_while(|b| **b == 0).count();
+            let next = *bytes.get(zeros)?;
+            if next == 0x80 && zeros >= 5 {
+                Some((Some(ItmPacket::Sync), zeros + 1))
+            } else {
+                Some((None, zeros))
+            }
+        },
+        0x70 => Some((Some(ItmPacket::Overflow), 1)),
+        // a local timestamp small enough to fit in its header
+        h if h & 0x8F == 0 => Some((Some(ItmPacket::LocalTimestamp(((h >> 4) & 0x07) as u32)), 1)),
+        h if h & 0xCF == 0xC0 => {
+            let (delta, len) = continuation(&bytes[1..], 4)?;
+            Some((Some(ItmPacket::LocalTimestamp(delta)), len + 1))
+        },
+        0x94 | 0xB4 => {
+            let (_, len) = continuation(&bytes[1..], 6)?;
+            Some((Some(ItmPacket::GlobalTimestamp), len + 1))
+        },
+        h if h & 0x0B == 0x08 => {
+            let len = if h & 0x80 != 0 { continuation(&bytes[1..], 4)?.1 } else { 0 };
+            Some((Some(ItmPacket::Extension), len + 1))
+        },
+        h if h & 0x03 != 0 => {
+            let size = [0, 1, 2, 4][(h & 0x03) as usize];
+            let data = bytes.get(1..size + 1)?.to_vec();
+            let address = h >> 3;
+            let packet = if h & 0x04 == 0 {
+                ItmPacket::Stimulus { port: address, data }
+            } else {
+                match address {
+                    0 => ItmPacket::EventCounter(data[0]),
+                    1 if size == 2 => {
+                        let number = data[0] as u16 | ((data[1] as u16 & 0x01) << 8);
+                        match (data[1] >> 4) & 0x03 {
+                            1 => ItmPacket::Exception { number, action: ExceptionAction::Enter },
+                            2 => ItmPacket::Exception { number, action: ExceptionAction::Exit },
+                            3 => ItmPacket::Exception { number, action: ExceptionAction::Return },
+                            _ => return Some((None, size + 1)),
+                        }
+                    },
+                    2 if size == 4 => ItmPacket::PcSample(Some(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))),
+                    2 => ItmPacket::PcSample(None),
+                    _ => ItmPacket::DataTrace { id: address, data },
+                }
+            };
+            Some((Some(packet), size + 1))
+        },
+        // reserved headers
+        _ => Some((None, 1)),
+    }
+}
+
+/// Decodes ITM packets from a stream of SWO bytes that may split packets anywhere.
+#[derive(Default)]
+pub struct ItmDecoder {
+    buffer: Vec<u8>,
+    /// The bytes skipped because they didn't start a packet
+    pub skipped: usize,
+}
+
+impl ItmDecoder {
+
+    /// Take in some bytes and return the packets they finish.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<ItmPacket> {
+        self.buffer.extend_from_slice(bytes);
+        let mut packets = Vec::new();
+        let mut at = 0;
+        while let Some((packet, len)) = decode_packet(&self.buffer[at..]) {
+            match packet {
+                Some(packet) => packets.push(packet),
+                None => self.skipped += len,
+            }
+            at += len;
+        }
+        self.buffer.drain(..at);
+        packets
+    }
+}
+
+/// The name of an exception number, i.e. "SysTick" or "IRQ 3".
+pub fn exception_name(number: u16) -> String {
+    match number {
+        0 => "thread".to_string(),
+        1 => "Reset".to_string(),
+        2 => "NMI".to_string(),
+        3 => "HardFault".to_string(),
+        4 => "MemManage".to_string(),
+        5 => "BusFault".to_string(),
+        6 => "UsageFault".to_string(),
+        7 => "SecureFault".to_string(),
+        11 => "SVCall".to_string(),
+        12 => "DebugMonitor".to_string(),
+        14 => "PendSV".to_string(),
+        15 => "SysTick".to_string(),
+        n if n >= 16 => format!("IRQ {}", n - 16),
+        n => format!("exception {}", n),
+    }
+}
+
+/// A time an exception handler ran, in seconds from the start of the trace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub exception: u16,
+    pub start: f64,
+    /// None while the handler is still running
+    pub end: Option<f64>,
+    /// How many handlers it interrupted
+    pub depth: usize,
+}
+
+/// A line written to a stimulus port, or a mark where packets were lost.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mark {
+    Text { port: u8, text: String },
+    Overflow,
+}
+
+/// The decoded trace, timed by its local timestamps.
+#[derive(Default)]
+pub struct Timeline {
+    decoder: ItmDecoder,
+    /// The timestamp clock, in Hz
+    clock: f64,
+    ticks: u64,
+    /// Whether the trace has timestamps; without them, everything happens at once
+    timestamped: bool,
+    /// The packets waiting for the local timestamp that follows them
+    pending: Vec<ItmPacket>,
+    pub spans: Vec<Span>,
+    /// The spans still running, innermost last
+    open: Vec<usize>,
+    pub marks: Vec<(f64, Mark)>,
+    /// Text written to each port since its last newline
+    partial: BTreeMap<u8, String>,
+    pub pc_samples: usize,
+}
+
+impl Timeline {
+
+    /// An empty timeline for a timestamp clock of `clock` Hz, which is the cpu clock
+    /// divided by the ITM's timestamp prescaler.
+    pub fn new(clock: f64) -> Self {
+        Self { clock, ..Default::default() }
+    }
+
+    /// The time of the latest timestamp, in seconds.
+    pub fn now(&self) -> f64 {
+        self.ticks as f64 / self.clock
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty() && self.marks.is_empty()
+    }
+
+    /// The first and last times anything happened.
+    pub fn extent(&self) -> Option<(f64, f64)> {
+        let starts = self.spans.iter().map(|s| s.start).chain(self.marks.iter().map(|m| m.0));
+        let ends = self.spans.iter().map(|s| s.end.unwrap_or(s.start)).chain(self.marks.iter().map(|m| m.0));
+        let start = starts.min_by(f64::total_cmp)?;
+        let end = ends.max_by(f64::total_cmp)?;
+        Some((start, end))
+    }
+
+    /// Take in some SWO bytes.
+    pub fn push(&mut self, bytes: &[u8]) {
+        for packet in self.decoder.push(bytes) {
+            match packet {
+                ItmPacket::LocalTimestamp(delta) => {
+                    self.timestamped = true;
+                    self.ticks += delta as u64;
+                    self.flush();
+                },
+                ItmPacket::Sync | ItmPacket::GlobalTimestamp | ItmPacket::Extension => (),
+                packet => self.pending.push(packet),
+            }
+        }
+        if !self.timestamped {
+            self.flush();
+        }
+        if self.marks.len() > MAX_EVENTS {
+            self.marks.drain(..self.marks.len() - MAX_EVENTS);
+        }
+        // drop old spans, keeping the ones still running
+        if self.spans.len() > MAX_EVENTS && self.open.first().is_none_or(|first| *first >= self.spans.len() - MAX_EVENTS) {
+            let dropped = self.spans.len() - MAX_EVENTS;
+            self.spans.drain(..dropped);
+            self.open.iter_mut().for_each(|i| *i -= dropped);
+        }
+    }
+
+    /// Put the pending packets on the timeline at the latest timestamp.
+    fn flush(&mut self) {
+        let now = self.now();
+        for packet in std::mem::take(&mut self.pending) {
+            match packet {
+                ItmPacket::Exception { number, action: ExceptionAction::Enter } => {
+                    self.open.push(self.spans.len());
+                    self.spans.push(Span { exception: number, start: now, end: None, depth: self.open.len() - 1 });
+                },
+                // close the handler that exited, and any nested ones whose exits were lost
+                ItmPacket::Exception { number, action: ExceptionAction::Exit } => {
+                    if let Some(at) = self.open.iter().rposition(|i| self.spans[*i].exception == number) {
+                        for i in self.open.drain(at..) {
+                            self.spans[i].end = Some(now);
+                        }
+                    }
+                },
+                ItmPacket::Exception { action: ExceptionAction::Return, .. } => (),
+                ItmPacket::Stimulus { port, data } => {
+                    let partial = self.partial.entry(port).or_default();
+                    for c in String::from_utf8_lossy(&data).chars() {
+                        match c {
+                            '\n' => self.marks.push((now, Mark::Text { port, text: std::mem::take(partial) })),
+                            '\r' | '\0' => (),
+                            c => partial.push(c),
+                        }
+                    }
+                },
+                ItmPacket::Overflow => self.marks.push((now, Mark::Overflow)),
+                ItmPacket::PcSample(_) => self.pc_samples += 1,
+                _ => (),
+            }
+        }
+    }
+
+    /// How often each exception ran, its longest run and its total time, for the spans
+    /// that ended.
+    pub fn exception_stats(&self) -> BTreeMap<u16, (usize, f64, f64)> {
+        let mut stats: BTreeMap<u16, (usize, f64, f64)> = BTreeMap::new();
+        for span in &self.spans {
+            if let Some(end) = span.end {
+                let (count, longest, total) = stats.entry(span.exception).or_default();
+                *count += 1;
+                *longest = longest.max(end - span.start);
+                *total += end - span.start;
+            }
+        }
+        stats
+    }
+}
+
+/// A line of a defmt log as probe-rs prints it with timestamps, i.e.
+/// `0.012345 INFO radio ready`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// The firmware's timestamp, in seconds
+    pub time: f64,
+    pub level: Option<String>,
+    pub text: String,
+}
+
+impl LogLine {
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (time, rest) = line.split_once(char::is_whitespace)?;
+        let time: f64 = time.trim_matches(|c| c == '[' || c == ']').parse().ok().filter(|t: &f64| t.is_finite())?;
+        let rest = rest.trim_start();
+        let (level, text) = match rest.split_once(char::is_whitespace) {
+            Some((level, text)) if ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"].contains(&level) => (Some(level.to_string()), text.trim_start()),
+            _ => (None, rest),
+        };
+        Some(LogLine { time, level, text: text.to_string() })
+    }
+
+    pub fn is_problem(&self) -> bool {
+        matches!(self.level.as_deref(), Some("WARN") | Some("ERROR"))
+    }
+}
+
+impl fmt::Display for LogLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.level {
+            Some(level) => write!(f, "{} {}", level, self.text),
+            None => write!(f, "{}", self.text),
+        }
+    }
+}
+
+/// The code that turns on the ITM with local timestamps and the exception trace, for
+/// the cortex-m crate.
+pub const FIRMWARE_SETUP: &str =
+"let mut core = cortex_m::Peripherals::take().unwrap();
+core.DCB.enable_trace();
+core.DWT.enable_exception_tracing();
+unsafe {
+    // unlock the ITM, then turn it on with local timestamps and DWT packets
+    core.ITM.lar.write(0xC5AC_CE55);
+    core.ITM.tcr.write((1 << 16) | (1 << 3) | (1 << 1) | 1);
+    core.ITM.ter[0].write(0xFFFF_FFFF);
+}
+cortex_m::iprintln!(&mut core.ITM.stim[0], \"started\");";
+
+/// Read raw SWO bytes from a trace server until cancelled or disconnected.
+fn stream_swo(server: &str, tx: &Sender<Vec<u8>>, job: &JobContext) -> Result<(), String> {
+    let address = server.to_socket_addrs().map_err(|e| format!("couldn't find {}: {}", server, e))?
+        .next().ok_or(format!("couldn't find {}", server))?;
+    let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| format!("couldn't connect to {}: {}", server, e))?;
+    stream.set_read_timeout(Some(Duration::from_millis(250))).map_err(|e| e.to_string())?;
+    job.set_status(&format!("reading SWO from {}", server));
+    let mut buffer = [0u8; 4096];
+    let mut total = 0;
+    while !job.is_cancelled() {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                total += n;
+                if tx.send(buffer[..n].to_vec()).is_err() {
+                    break;
+                }
+                job.set_status(&format!("{} bytes from {}", total, server));
+            },
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// The state of the trace window.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracePanel {
+    /// The trace server to read SWO from
+    server: String,
+    /// The ITM's timestamp prescaler
+    prescaler: u32,
+    /// What to add to the log's timestamps to line them up with the trace, in seconds
+    log_offset: f64,
+    #[serde(skip)]
+    timeline: Option<Timeline>,
+    #[serde(skip)]
+    logs: Vec<LogLine>,
+    /// The part of the timeline shown, in seconds, or all of it if None
+    #[serde(skip)]
+    view: Option<(f64, f64)>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    bytes: Option<Receiver<Vec<u8>>>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl Default for TracePanel {
+    fn default() -> Self {
+        Self {
+            server: DEFAULT_SERVER.to_string(),
+            prescaler: 1,
+            log_offset: 0.0,
+            timeline: None,
+            logs: Vec::new(),
+            view: None,
+            job: None,
+            bytes: None,
+            error: None,
+        }
+    }
+}
+
+impl TracePanel {
+
+    fn new_timeline(&mut self, board: &Board) {
+        let clock = board.clock().map_or(1.0, |c| c.hz() as f64) / self.prescaler.max(1) as f64;
+        self.timeline = Some(Timeline::new(clock));
+        self.view = None;
+        self.error = None;
+    }
+
+    fn open_capture(&mut self, board: &Board) {
+        let Some(path) = FileDialog::new().set_title("open a raw SWO capture").pick_file() else {
+            return;
+        };
+        match fs::read(&path) {
+            Ok(bytes) => {
+                self.new_timeline(board);
+                if let Some(timeline) = &mut self.timeline {
+                    timeline.push(&bytes);
+                }
+            },
+            Err(e) => self.error = Some(format!("couldn't read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn open_log(&mut self) {
+        let Some(path) = FileDialog::new().set_title("open a defmt log").pick_file() else {
+            return;
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                self.logs = text.lines().filter_map(LogLine::parse).collect();
+                if self.logs.is_empty() {
+                    self.error = Some("the log has no timestamped lines".to_string());
+                }
+            },
+            Err(e) => self.error = Some(format!("couldn't read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Line the first log line up with the first stimulus write, for firmware that logs
+    /// the same thing to both.
+    fn align_log(&mut self) {
+        let first_text = self.timeline.as_ref().and_then(|t| t.marks.iter().find(|m| matches!(m.1, Mark::Text { .. })).map(|m| m.0));
+        if let (Some(text), Some(log)) = (first_text, self.logs.first()) {
+            self.log_offset = text - log.time;
+        }
+    }
+
+    fn capture(&mut self, ctx: &egui::Context, board: &Board) {
+        self.new_timeline(board);
+        let (tx, rx) = channel();
+        self.bytes = Some(rx);
+        let server = self.server.trim().to_string();
+        self.job = Some(jobs::shared(ctx).spawn("SWO trace", Some(ctx), move |job| stream_swo(&server, &tx, job)));
+    }
+
+    /// Draw the spans, stimulus lines and log lines between `start` and `end` seconds,
+    /// a lane each.
+    fn display_timeline(&self, ui: &mut egui::Ui, timeline: &Timeline, (start, end): (f64, f64)) {
+        let exceptions: Vec<u16> = timeline.exception_stats().keys().copied()
+            .chain(timeline.spans.iter().filter(|s| s.end.is_none()).map(|s| s.exception))
+            .collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+        let ports: Vec<u8> = timeline.marks.iter().filter_map(|m| match m.1 {
+            Mark::Text { port, .. } => Some(port),
+            Mark::Overflow => None,
+        }).collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+        let lanes = exceptions.len() + ports.len() + usize::from(!self.logs.is_empty()) + 1;
+        let lane_height = 18.0;
+        let label_width = 90.0;
+        let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), lanes as f32 * lane_height), egui::Sense::hover());
+        let rect = response.rect;
+        let visuals = ui.visuals().clone();
+        let font = egui::FontId::monospace(11.0);
+        let plot_left = rect.left() + label_width;
+        let length = (end - start).max(1e-9);
+        let x = |t: f64| plot_left + ((t - start) / length) as f32 * (rect.right() - plot_left);
+        let lane_rect = |lane: usize| egui::Rect::from_min_size(
+            egui::pos2(plot_left, rect.top() + lane as f32 * lane_height + 2.0),
+            egui::vec2(rect.right() - plot_left, lane_height - 4.0),
+        );
+        let pointer = response.hover_pos();
+        let mut hover: Option<String> = None;
+        let label = |lane: usize, text: String| {
+            painter.text(egui::pos2(rect.left(), lane_rect(lane).center().y), egui::Align2::LEFT_CENTER, text, font.clone(), visuals.text_color());
+        };
+        for (lane, exception) in exceptions.iter().enumerate() {
+            label(lane, exception_name(*exception));
+        }
+        for (i, port) in ports.iter().enumerate() {
+            label(exceptions.len() + i, format!("port {}", port));
+        }
+        let log_lane = exceptions.len() + ports.len();
+        if !self.logs.is_empty() {
+            label(log_lane, "log".to_string());
+        }
+        label(lanes - 1, format_si(length, "s"));
+        // spans, darker the deeper they're nested
+        for span in &timeline.spans {
+            let span_end = span.end.unwrap_or_else(|| timeline.now());
+            if span_end < start || span.start > end {
+                continue;
+            }
+            let Some(lane) = exceptions.iter().position(|e| *e == span.exception) else {
+                continue;
+            };
+            let lane = lane_rect(lane);
+            let left = x(span.start).max(lane.left());
+            let right = x(span_end).min(lane.right()).max(left + 1.0);
+            let bar = egui::Rect::from_x_y_ranges(left..=right, lane.y_range());
+            let fill = visuals.selection.bg_fill.linear_multiply(1.0 / (span.depth as f32 + 1.0));
+            painter.rect_filled(bar, 1.0, fill);
+            if pointer.is_some_and(|p| bar.expand2(egui::vec2(2.0, 0.0)).contains(p)) {
+                let duration = span.end.map_or("still running".to_string(), |end| format_si(end - span.start, "s"));
+                hover = Some(format!("{} at {}: {}", exception_name(span.exception), format_si(span.start, "s"), duration));
+            }
+        }
+        let mut tick = |lane: egui::Rect, t: f64, color: egui::Color32, text: String| {
+            let at = x(t);
+            painter.line_segment([egui::pos2(at, lane.top()), egui::pos2(at, lane.bottom())], egui::Stroke::new(1.5, color));
+            if pointer.is_some_and(|p| (p.x - at).abs() < 3.0 && lane.y_range().contains(p.y)) {
+                hover = Some(format!("{}: {}", format_si(t, "s"), text));
+            }
+        };
+        for (t, mark) in timeline.marks.iter().filter(|m| (start..=end).contains(&m.0)) {
+            match mark {
+                Mark::Text { port, text } => {
+                    let lane = exceptions.len() + ports.iter().position(|p| p == port).unwrap_or_default();
+                    tick(lane_rect(lane), *t, visuals.text_color(), text.clone());
+                },
+                // lost packets are marked across every lane
+                Mark::Overflow => tick(egui::Rect::from_x_y_ranges(lane_rect(0).x_range(), rect.y_range()), *t, visuals.error_fg_color, "packets lost".to_string()),
+            }
+        }
+        for log in &self.logs {
+            let t = log.time + self.log_offset;
+            if (start..=end).contains(&t) {
+                let color = if log.is_problem() { visuals.warn_fg_color } else { visuals.weak_text_color() };
+                tick(lane_rect(log_lane), t, color, log.to_string());
+            }
+        }
+        if let Some(text) = hover {
+            response.on_hover_text_at_pointer(text);
+        }
+    }
+
+    /// Show the capture controls, the timeline and the time spent in each handler.
+    /// `board` is the main board, whose clock times the trace.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, board: Option<&Board>) {
+        let Some(board) = board else {
+            ui.label("select a main board to trace");
+            return;
+        };
+        if !board.has_itm() {
+            ui.label(format!("{} has no ITM, so it can't send trace over SWO", board.get_name()));
+            return;
+        }
+        if let (Some(rx), Some(timeline)) = (&self.bytes, &mut self.timeline) {
+            for bytes in rx.try_iter() {
+                timeline.push(&bytes);
+            }
+        }
+        let running = self.job.is_some_and(|id| jobs::shared(ctx).state(id) == Some(JobState::Running));
+        ui.horizontal(|ui| {
+            ui.label("trace server:");
+            ui.add_enabled(!running, egui::TextEdit::singleline(&mut self.server).desired_width(160.0));
+            if running {
+                if ui.button("stop").clicked() {
+                    jobs::shared(ctx).cancel(self.job.unwrap());
+                }
+            } else if ui.button("capture").clicked() {
+                self.capture(ctx, board);
+            }
+            if ui.add_enabled(!running, egui::Button::new("open capture...")).clicked() {
+                self.open_capture(board);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("timestamp prescaler:");
+            egui::ComboBox::from_id_source("trace_prescaler")
+            .selected_text(self.prescaler.to_string())
+            .show_ui(ui, |ui| {
+                for prescaler in [1, 4, 16, 64] {
+                    ui.selectable_value(&mut self.prescaler, prescaler, prescaler.to_string());
+                }
+            });
+            if board.clock().is_none() {
+                ui.colored_label(ui.visuals().warn_fg_color, "the board has no clock, so times are in timestamp ticks");
+            }
+        });
+        ui.collapsing("setting up the firmware", |ui| {
+            ui.label("turn on the ITM, its timestamps and the exception trace at startup:");
+            let mut code = FIRMWARE_SETUP.to_string();
+            ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+            ui.label("then serve the SWO output on a port, i.e. with Orbuculum, or OpenOCD's tpiu configure -output :2332");
+        });
+        ui.horizontal(|ui| {
+            if ui.button("open defmt log...").clicked() {
+                self.open_log();
+            }
+            if !self.logs.is_empty() {
+                ui.label(format!("{} lines, offset", self.logs.len()));
+                ui.add(egui::DragValue::new(&mut self.log_offset).speed(0.001).suffix(" s"));
+                if ui.button("line up").on_hover_text("line the first log line up with the first stimulus write").clicked() {
+                    self.align_log();
+                }
+            }
+        });
+        if let Some(e) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        let Some(timeline) = &self.timeline else {
+            return;
+        };
+        let Some(extent) = timeline.extent() else {
+            ui.label(if running { "waiting for trace packets" } else { "the capture has no trace packets" });
+            return;
+        };
+        ui.separator();
+        let (mut start, mut end) = self.view.unwrap_or(extent);
+        let speed = length_speed(end - start);
+        ui.horizontal(|ui| {
+            ui.label("from");
+            ui.add(egui::DragValue::new(&mut start).speed(speed).suffix(" s"));
+            ui.label("to");
+            ui.add(egui::DragValue::new(&mut end).speed(speed).suffix(" s"));
+            if ui.button("zoom in").clicked() {
+                let quarter = (end - start) / 4.0;
+                (start, end) = (start + quarter, end - quarter);
+            }
+            if ui.button("zoom out").clicked() {
+                let half = (end - start) / 2.0;
+                (start, end) = (start - half, end + half);
+            }
+            if ui.button("fit").clicked() {
+                (start, end) = extent;
+            }
+        });
+        if end <= start {
+            end = start + 1e-6;
+        }
+        self.display_timeline(ui, timeline, (start, end));
+        let stats = timeline.exception_stats();
+        if !stats.is_empty() {
+            egui::Grid::new("trace_exceptions").num_columns(4).striped(true).show(ui, |ui| {
+                for heading in ["handler", "runs", "longest", "total"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (exception, (count, longest, total)) in stats {
+                    ui.label(exception_name(exception));
+                    ui.label(count.to_string());
+                    ui.label(format_si(longest, "s"));
+                    ui.label(format_si(total, "s"));
+                    ui.end_row();
+                }
+            });
+        }
+        if timeline.decoder.skipped > 0 {
+            ui.label(format!("{} bytes didn't decode; check the SWO baud rate", timeline.decoder.skipped));
+        }
+        // follow a live capture until the view is moved
+        self.view = if running && self.view.is_none() && (start, end) == extent { None } else { Some((start, end)) };
+    }
+}
+
+/// How far a drag moves a time, for a view `length` seconds long.
+fn length_speed(length: f64) -> f64 {
+    (length / 200.0).max(1e-9)
+}
@@ -0,0 +1,557 @@
+//! Title: Iron Coder App Module - Watchdog
+//! Description: Setting up the main board's watchdog and brown-out detector. The panel
+//!   explains what each does on the board's chip, turns a timeout into counter settings,
+//!   writes the code to start and feed the watchdog and to set the brown-out level, and
+//!   checks the project's code for waits that run longer than the watchdog allows.
+
+use std::fs;
+use std::path::Path;
+
+use quote::ToTokens;
+use serde::{Serialize, Deserialize};
+use syn::visit::Visit;
+
+use crate::app::toolbox::{format_si, parse_si};
+use crate::board::Board;
+use crate::board::watchdog::{BrownOut, BrownOutAction, Watchdog, WatchdogHal, WatchdogSettings};
+use crate::project::fonts;
+
+/// How much of the timeout a wait can take before it's flagged as cutting it close.
+const MARGIN: f64 = 0.8;
+
+/// The code that starts the watchdog with `settings` for the board's HAL. With a
+/// window, feeding sooner than `window_open` seconds after the last feed resets the
+/// chip too.
+pub fn watchdog_code(hal: WatchdogHal, settings: &WatchdogSettings, window_open: Option<f64>) -> String {
+    let mut header = format!("// the watchdog resets the chip {} after the last feed\n", format_si(settings.timeout, "s"));
+    if let Some(open) = window_open {
+        header += &format!("// and when it's fed sooner than {} after the last feed\n", format_si(open, "s"));
+    }
+    let body = match hal {
+        // the HAL takes microseconds and doubles them for erratum RP2040-E1 itself
+        WatchdogHal::Rp2040Hal => format!(
+"// the `watchdog` handed to init_clocks_and_plls, which also starts its 1 MHz tick
+watchdog.pause_on_debug(true);
+watchdog.start(fugit::MicrosDurationU32::micros({}));
+", settings.count),
+        // CRV holds one less than the number of counts
+        WatchdogHal::NrfHal => format!(
+"// the watchdog keeps running through a soft reset, so it may already be running
+let mut watchdog = match hal::wdt::Watchdog::try_new(p.WDT) {{
+    Ok(mut wdt) => {{
+        wdt.set_lfosc_ticks({});
+        wdt.run_during_sleep(true);
+        wdt.halt_during_debug(true);
+        wdt.activate::<hal::wdt::count::One>()
+    }},
+    Err(wdt) => wdt.try_recover::<hal::wdt::count::One>().unwrap_or_else(|_| panic!(\"the watchdog is set up differently\")),
+}};
+", settings.count - 1),
+        // the driver picks its own prescaler for the timeout
+        WatchdogHal::EspHal => format!(
+"let timg0 = TimerGroup::new(peripherals.TIMG0);
+let mut wdt = timg0.wdt;
+wdt.set_timeout(MwdtStage::Stage0, {}u64.micros());
+wdt.enable();
+", (settings.timeout * 1e6).round()),
+    };
+    header + &body
+}
+
+/// The code that feeds the watchdog started by [`watchdog_code`].
+pub fn feed_code(hal: WatchdogHal) -> &'static str {
+    match hal {
+        WatchdogHal::Rp2040Hal => "watchdog.feed();\n",
+        WatchdogHal::NrfHal => "watchdog.handles.0.pet();\n",
+        WatchdogHal::EspHal => "wdt.feed();\n",
+    }
+}
+
+/// The code that sets the brown-out detector to the level with register value `level`.
+pub fn brown_out_code(hal: WatchdogHal, brown_out: &BrownOut, level: usize) -> String {
+    let volts = brown_out.levels[level];
+    let action = match brown_out.action {
+        BrownOutAction::Reset => "reset the chip",
+        BrownOutAction::Interrupt => "raise an event",
+    };
+    let header = format!("// {} when {} drops below {} V\n", action, brown_out.rail, volts);
+    let body = match hal {
+        WatchdogHal::Rp2040Hal => format!(
+"pac.VREG_AND_CHIP_RESET.bod().write(|w| unsafe {{ w.vsel().bits({}) }}.en().set_bit());
+", level),
+        WatchdogHal::NrfHal => format!(
+"// enable the POWER interrupt to hear about the POFWARN event in time to save state
+p.POWER.pofcon.write(|w| w.threshold().v{}().pof().enabled());
+", (volts * 10.0).round()),
+        // esp-hal has no driver for it, so the registers are written directly
+        WatchdogHal::EspHal => format!(
+"let rtc_cntl = unsafe {{ &*esp32::RTC_CNTL::ptr() }};
+rtc_cntl.brown_out().modify(|_, w| unsafe {{ w.dbrown_out_thres().bits({}) }}.brown_out_rst_ena().set_bit().brown_out_ena().set_bit());
+", level),
+    };
+    header + &body
+}
+
+/// How long some code can run without feeding the watchdog, in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Stretch {
+    /// The time before the first feed, or all of it if it doesn't feed
+    lead: f64,
+    /// The time after the last feed, or all of it if it doesn't feed
+    tail: f64,
+    /// The longest time between feeds anywhere in it
+    longest: f64,
+    fed: bool,
+    /// Whether it starts the watchdog, so anything before doesn't count
+    starts: bool,
+}
+
+impl Stretch {
+
+    fn wait(seconds: f64) -> Self {
+        Self { lead: seconds, tail: seconds, longest: seconds, ..Default::default() }
+    }
+
+    fn feed(starts: bool) -> Self {
+        Self { fed: true, starts, ..Default::default() }
+    }
+
+    /// This, followed by `next`.
+    fn then(self, next: Stretch) -> Stretch {
+        if next.starts && !self.starts {
+            return next;
+        }
+        Stretch {
+            lead: if self.fed { self.lead } else { self.lead + next.lead },
+            tail: if next.fed { next.tail } else { self.tail + next.tail },
+            longest: self.longest.max(next.longest).max(self.tail + next.lead),
+            fed: self.fed || next.fed,
+            starts: self.starts || next.starts,
+        }
+    }
+
+    /// Either this or `other`, taking the worse of the two.
+    fn or(self, other: Stretch) -> Stretch {
+        let longest = self.longest.max(other.longest);
+        let starts = self.starts || other.starts;
+        if self.fed && other.fed {
+            return Stretch { lead: self.lead.max(other.lead), tail: self.tail.max(other.tail), longest, fed: true, starts };
+        }
+        // a branch that feeds is counted as if it didn't, from its longer end
+        let through = |s: Stretch| if s.fed { s.lead.max(s.tail) } else { s.lead };
+        let time = through(self).max(through(other));
+        Stretch { lead: time, tail: time, longest: longest.max(time), fed: false, starts }
+    }
+
+    /// This, repeated `times` times, or an unknown number of times.
+    fn repeat(self, times: Option<u64>) -> Stretch {
+        match times {
+            Some(0) => Stretch::default(),
+            Some(n) if !self.fed => {
+                let time = self.lead * n as f64;
+                Stretch { lead: time, tail: time, longest: self.longest.max(time), ..self }
+            },
+            Some(1) => self,
+            // the first and last stretches run into each other between rounds
+            _ if self.fed => Stretch { longest: self.longest.max(self.tail + self.lead), ..self },
+            _ => self,
+        }
+    }
+
+    /// This, repeated until the chip resets.
+    fn forever(self) -> Stretch {
+        if self.fed {
+            return Stretch { tail: 0.0, ..self.repeat(None) };
+        }
+        if self.lead > 0.0 {
+            return Stretch { lead: f64::INFINITY, tail: f64::INFINITY, longest: f64::INFINITY, ..self };
+        }
+        self
+    }
+}
+
+/// The number in a call's first argument, if it's written out.
+fn literal_argument<'a>(mut args: impl Iterator<Item = &'a syn::Expr>) -> Option<f64> {
+    match args.next()? {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => i.base10_parse::<u64>().ok().map(|n| n as f64),
+        _ => None,
+    }
+}
+
+/// The number of times a `for` loop over a range of literals goes round.
+fn range_length(expr: &syn::Expr) -> Option<u64> {
+    let syn::Expr::Range(range) = expr else {
+        return match expr {
+            syn::Expr::Paren(p) => range_length(&p.expr),
+            _ => None,
+        };
+    };
+    let bound = |e: &Option<Box<syn::Expr>>| literal_argument(e.as_deref().into_iter()).map(|n| n as u64);
+    let (start, end) = (bound(&range.start).unwrap_or(0), bound(&range.end)?);
+    match range.limits {
+        syn::RangeLimits::HalfOpen(_) => Some(end.saturating_sub(start)),
+        syn::RangeLimits::Closed(_) => Some((end + 1).saturating_sub(start)),
+    }
+}
+
+/// Whether a loop body can break out of the loop.
+fn breaks(block: &syn::Block) -> bool {
+    struct Breaks(bool);
+    impl<'ast> Visit<'ast> for Breaks {
+        fn visit_expr_break(&mut self, _: &'ast syn::ExprBreak) {
+            self.0 = true;
+        }
+        fn visit_expr_return(&mut self, _: &'ast syn::ExprReturn) {
+            self.0 = true;
+        }
+        // the breaks of inner loops and closures stay in there
+        fn visit_expr_loop(&mut self, _: &'ast syn::ExprLoop) {}
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+    }
+    let mut visitor = Breaks(false);
+    visitor.visit_block(block);
+    visitor.0
+}
+
+/// Works out the stretches of the functions in some code.
+struct Waits {
+    cpu_clock: Option<f64>,
+    functions: Vec<(String, f64)>,
+}
+
+impl Waits {
+
+    fn block(&self, block: &syn::Block) -> Stretch {
+        block.stmts.iter().fold(Stretch::default(), |s, stmt| s.then(self.stmt(stmt)))
+    }
+
+    fn stmt(&self, stmt: &syn::Stmt) -> Stretch {
+        match stmt {
+            syn::Stmt::Local(local) => local.init.as_ref().map_or_else(Stretch::default, |init| self.expr(&init.expr)),
+            syn::Stmt::Expr(expr, _) => self.expr(expr),
+            _ => Stretch::default(),
+        }
+    }
+
+    fn exprs<'a>(&self, exprs: impl Iterator<Item = &'a syn::Expr>) -> Stretch {
+        exprs.fold(Stretch::default(), |s, e| s.then(self.expr(e)))
+    }
+
+    /// The wait or feed a call by this name stands for.
+    fn call(&self, name: &str, path: &str, args: &syn::punctuated::Punctuated<syn::Expr, syn::Token![,]>) -> Stretch {
+        let scale = match name {
+            "feed" | "pet" => return Stretch::feed(false),
+            "delay_ms" | "delay_millis" | "after_millis" => 1e-3,
+            "delay_us" | "delay_micros" | "after_micros" => 1e-6,
+            "delay_ns" | "delay_nanos" => 1e-9,
+            "after_secs" => 1.0,
+            // cortex_m::asm::delay counts cpu cycles
+            "delay" if path.contains("asm") => match self.cpu_clock {
+                Some(clock) => 1.0 / clock,
+                None => return Stretch::default(),
+            },
+            _ => return Stretch::default(),
+        };
+        literal_argument(args.iter()).map_or_else(Stretch::default, |n| Stretch::wait(n * scale))
+    }
+
+    fn expr(&self, expr: &syn::Expr) -> Stretch {
+        use syn::Expr;
+        match expr {
+            Expr::Block(b) => self.block(&b.block),
+            Expr::Unsafe(u) => self.block(&u.block),
+            Expr::If(i) => {
+                let otherwise = i.else_branch.as_ref().map_or_else(Stretch::default, |(_, e)| self.expr(e));
+                self.expr(&i.cond).then(self.block(&i.then_branch).or(otherwise))
+            },
+            Expr::Match(m) => {
+                let arms = m.arms.iter().map(|a| self.expr(&a.body)).reduce(Stretch::or).unwrap_or_default();
+                self.expr(&m.expr).then(arms)
+            },
+            Expr::Loop(l) if breaks(&l.body) => self.block(&l.body).repeat(None),
+            Expr::Loop(l) => self.block(&l.body).forever(),
+            Expr::While(w) => self.expr(&w.cond).then(self.block(&w.body)).repeat(None),
+            Expr::ForLoop(f) => self.expr(&f.expr).then(self.block(&f.body).repeat(range_length(&f.expr))),
+            Expr::MethodCall(m) => {
+                let name = m.method.to_string();
+                let receiver = m.receiver.to_token_stream().to_string().to_lowercase();
+                if matches!(name.as_str(), "start" | "enable" | "activate") && (receiver.contains("watchdog") || receiver.contains("wdt")) {
+                    return Stretch::feed(true);
+                }
+                self.expr(&m.receiver).then(self.exprs(m.args.iter())).then(self.call(&name, "", &m.args))
+            },
+            Expr::Call(c) => {
+                let call = match &*c.func {
+                    Expr::Path(p) => {
+                        let path = p.path.to_token_stream().to_string();
+                        let name = p.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+                        self.call(&name, &path, &c.args)
+                    },
+                    _ => Stretch::default(),
+                };
+                self.exprs(c.args.iter()).then(call)
+            },
+            Expr::Await(a) => self.expr(&a.base),
+            Expr::Paren(p) => self.expr(&p.expr),
+            Expr::Group(g) => self.expr(&g.expr),
+            Expr::Try(t) => self.expr(&t.expr),
+            Expr::Reference(r) => self.expr(&r.expr),
+            Expr::Unary(u) => self.expr(&u.expr),
+            Expr::Field(f) => self.expr(&f.base),
+            Expr::Cast(c) => self.expr(&c.expr),
+            Expr::Let(l) => self.expr(&l.expr),
+            Expr::Assign(a) => self.expr(&a.right),
+            Expr::Binary(b) => self.expr(&b.left).then(self.expr(&b.right)),
+            Expr::Return(r) => r.expr.as_ref().map_or_else(Stretch::default, |e| self.expr(e)),
+            Expr::Break(b) => b.expr.as_ref().map_or_else(Stretch::default, |e| self.expr(e)),
+            _ => Stretch::default(),
+        }
+    }
+
+    fn function(&mut self, name: &syn::Ident, block: &syn::Block) {
+        let stretch = self.block(block);
+        if stretch.longest > 0.0 {
+            self.functions.push((name.to_string(), stretch.longest));
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Waits {
+    fn visit_item_fn(&mut self, f: &'ast syn::ItemFn) {
+        self.function(&f.sig.ident, &f.block);
+        syn::visit::visit_item_fn(self, f);
+    }
+
+    fn visit_impl_item_fn(&mut self, f: &'ast syn::ImplItemFn) {
+        self.function(&f.sig.ident, &f.block);
+        syn::visit::visit_impl_item_fn(self, f);
+    }
+}
+
+/// The longest each function in some code can go without feeding the watchdog, in
+/// seconds, for the functions that wait at all. A function with a loop that waits but
+/// never feeds gets infinity. Only waits with a written-out length are counted, a loop
+/// over a range of literals counts each round, and other loops are counted once. Feeds
+/// in other functions aren't followed.
+pub fn unfed_stretches(code: &str, cpu_clock: Option<f64>) -> Vec<(String, f64)> {
+    let mut waits = Waits { cpu_clock, functions: Vec::new() };
+    if let Ok(file) = syn::parse_file(code) {
+        waits.visit_file(&file);
+    }
+    waits.functions
+}
+
+/// Explain the watchdog of a board's chip.
+fn explain_watchdog(watchdog: &Watchdog) -> String {
+    let mut text = format!(
+        "The watchdog resets the chip unless the firmware feeds it within its timeout, which gets a hung board \
+        running again. This one counts {} and can wait {} to {}.",
+        watchdog.clock, format_si(watchdog.min_timeout(), "s"), format_si(watchdog.max_timeout(), "s"),
+    );
+    if watchdog.window {
+        text += " It has a window: feeding it too soon after the last feed resets the chip as well, which catches \
+            code stuck in a loop that keeps feeding.";
+    } else {
+        text += " It has no window, so feeding it early is always fine.";
+    }
+    text
+}
+
+/// Explain the brown-out detector of a board's chip.
+fn explain_brown_out(brown_out: &BrownOut) -> String {
+    let action = match brown_out.action {
+        BrownOutAction::Reset => "holds the chip in reset until it recovers, so it never runs on a supply too low to run reliably",
+        BrownOutAction::Interrupt => "raises an event, giving the firmware a moment to save its state",
+    };
+    let default = match brown_out.default {
+        Some(volts) => format!(" Out of reset it's set to {} V.", volts),
+        None => " It's off out of reset.".to_string(),
+    };
+    format!("The brown-out detector watches {} and, when it drops below the set level, {}.{}", brown_out.rail, action, default)
+}
+
+/// The state of the watchdog window. The timeout is kept as the text the user typed.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogPanel {
+    timeout: String,
+    /// How soon after a feed the window opens, empty for no window
+    window_open: String,
+    /// The HAL to write code for, if not the board's
+    hal: Option<WatchdogHal>,
+    /// The register value of the brown-out level to set
+    brown_out_level: Option<usize>,
+    /// The stretches found the last time the code was checked
+    #[serde(skip)]
+    stretches: Option<Vec<(String, f64)>>,
+}
+
+impl Default for WatchdogPanel {
+    fn default() -> Self {
+        Self {
+            timeout: "1 s".to_string(),
+            window_open: String::new(),
+            hal: None,
+            brown_out_level: None,
+            stretches: None,
+        }
+    }
+}
+
+/// Show some code with a button to copy it.
+fn code_block(ui: &mut egui::Ui, mut code: String) {
+    if ui.button("copy").clicked() {
+        ui.output_mut(|o| o.copied_text = code.clone());
+    }
+    ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+}
+
+impl WatchdogPanel {
+
+    /// Look through the project's sources for the functions that wait.
+    fn check_code(&mut self, project_dir: &Path, cpu_clock: Option<f64>) {
+        let mut files = Vec::new();
+        fonts::source_files(&project_dir.join("src"), &mut files);
+        let mut stretches = Vec::new();
+        for file in files {
+            if let Ok(code) = fs::read_to_string(&file) {
+                stretches.extend(unfed_stretches(&code, cpu_clock));
+            }
+        }
+        stretches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        self.stretches = Some(stretches);
+    }
+
+    fn display_watchdog(&mut self, ui: &mut egui::Ui, board: &Board, watchdog: Watchdog, project_dir: Option<&Path>) {
+        ui.label(explain_watchdog(&watchdog));
+        let mut settings = Err(String::new());
+        let mut window_open = None;
+        egui::Grid::new("watchdog_settings").num_columns(2).show(ui, |ui| {
+            ui.label("timeout:");
+            ui.add(egui::TextEdit::singleline(&mut self.timeout).hint_text("500 ms"));
+            ui.end_row();
+            settings = parse_si(&self.timeout).and_then(|t| watchdog.settings(t));
+            ui.label("settings:");
+            match &settings {
+                Ok(s) if s.divider > 1 => { ui.strong(format!("divider {}, count {}", s.divider, s.count)); },
+                Ok(s) => { ui.strong(format!("count {}", s.count)); },
+                Err(e) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            }
+            ui.end_row();
+            if let Ok(s) = &settings {
+                ui.label("actual timeout:");
+                ui.strong(format_si(s.timeout, "s"));
+                ui.end_row();
+            }
+            if watchdog.window {
+                ui.label("window opens after:");
+                ui.add(egui::TextEdit::singleline(&mut self.window_open).hint_text("no window"));
+                ui.end_row();
+                match (self.window_open.trim(), &settings) {
+                    ("", _) => (),
+                    (s, Ok(settings)) => match parse_si(s) {
+                        Ok(open) if open > 0.0 && open < settings.timeout => window_open = Some(open),
+                        Ok(_) => {
+                            ui.label("");
+                            ui.colored_label(ui.style().visuals.error_fg_color, "the window has to open before the timeout");
+                            ui.end_row();
+                        },
+                        Err(e) => {
+                            ui.label("");
+                            ui.colored_label(ui.style().visuals.error_fg_color, e);
+                            ui.end_row();
+                        },
+                    },
+                    _ => (),
+                }
+            }
+        });
+        let Ok(settings) = settings else {
+            return;
+        };
+        // the board's HAL, unless the user picked another
+        let hal = self.hal.or(watchdog.hal);
+        ui.horizontal(|ui| {
+            ui.label("code for:");
+            egui::ComboBox::from_id_source("watchdog_hal")
+            .selected_text(hal.map(|h| h.to_string()).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for h in enum_iterator::all::<WatchdogHal>() {
+                    ui.selectable_value(&mut self.hal, Some(h), h.to_string());
+                }
+            });
+        });
+        if let Some(hal) = hal {
+            code_block(ui, watchdog_code(hal, &settings, window_open) + "\n// in the main loop\n" + feed_code(hal));
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("waits between feeds:");
+            let button = ui.add_enabled(project_dir.is_some(), egui::Button::new("check the code"));
+            if let (true, Some(dir)) = (button.clicked(), project_dir) {
+                self.check_code(dir, board.clock().map(|c| c.hz() as f64));
+            }
+        });
+        let Some(stretches) = &self.stretches else {
+            return;
+        };
+        let mut flagged = 0;
+        for (function, longest) in stretches.iter().filter(|(_, l)| *l >= settings.timeout * MARGIN) {
+            flagged += 1;
+            let (color, text) = if longest.is_infinite() {
+                (ui.style().visuals.error_fg_color, format!("{}: a loop waits but never feeds the watchdog", function))
+            } else if *longest >= settings.timeout {
+                (ui.style().visuals.error_fg_color, format!("{}: waits {} between feeds, past the timeout", function, format_si(*longest, "s")))
+            } else {
+                (ui.style().visuals.warn_fg_color, format!("{}: waits {} between feeds, close to the timeout", function, format_si(*longest, "s")))
+            };
+            ui.colored_label(color, text);
+        }
+        if flagged == 0 {
+            ui.label(format!("no wait comes within {:.0}% of the timeout", MARGIN * 100.0));
+        }
+    }
+
+    fn display_brown_out(&mut self, ui: &mut egui::Ui, brown_out: &BrownOut) {
+        ui.label(explain_brown_out(brown_out));
+        let default = brown_out.default.and_then(|d| brown_out.level_at(d));
+        let level = self.brown_out_level.or(default).filter(|l| *l < brown_out.levels.len());
+        ui.horizontal(|ui| {
+            ui.label("level:");
+            egui::ComboBox::from_id_source("watchdog_brown_out")
+            .selected_text(level.map(|l| format!("{} V", brown_out.levels[l])).unwrap_or("off".to_string()))
+            .show_ui(ui, |ui| {
+                for (i, volts) in brown_out.levels.iter().enumerate() {
+                    ui.selectable_value(&mut self.brown_out_level, Some(i), format!("{} V", volts));
+                }
+            });
+        });
+        let (Some(level), Some(hal)) = (level, self.hal.or(brown_out.hal)) else {
+            return;
+        };
+        if Some(level) == default {
+            ui.label("that's the level out of reset, so there's nothing to set");
+        } else {
+            code_block(ui, brown_out_code(hal, brown_out, level));
+        }
+    }
+
+    /// Show the panel for the main board, and check the code of the project in `project_dir`.
+    pub fn display(&mut self, ui: &mut egui::Ui, board: Option<&Board>, project_dir: Option<&Path>) {
+        let Some(board) = board else {
+            ui.label("pick a main board to set up its watchdog");
+            return;
+        };
+        ui.heading("Watchdog");
+        match board.watchdog() {
+            Some(watchdog) => self.display_watchdog(ui, board, watchdog, project_dir),
+            None => { ui.label(format!("the {} doesn't describe its watchdog", board.get_name())); },
+        }
+        ui.separator();
+        ui.heading("Brown-out");
+        match board.brown_out() {
+            Some(brown_out) => self.display_brown_out(ui, brown_out),
+            None => { ui.label(format!("the {} doesn't describe its brown-out detector", board.get_name())); },
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Title: Iron Coder Board Module - Board Bundles
+//! Description: A board and the files next to it (its image, examples, template and
+//!   local BSP) in one JSON file, for sharing a board that isn't in the boards directory
+//!   of whoever gets it. Importing a bundle writes its files back into the boards
+//!   directory, under the manufacturer and directory it came from.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use base64::Engine;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::registry::is_plain_name;
+
+/// Bumped when the shape of a bundle changes in a way that breaks importing it.
+pub const BUNDLE_VERSION: u32 = 1;
+/// The most bytes of files that go into a bundle.
+const MAX_BUNDLE_SIZE: u64 = 32 * 1024 * 1024;
+/// Directories in a board's directory that are left out of its bundle.
+const SKIPPED_DIRS: [&str; 1] = ["target"];
+
+/// A file of the board's directory, with its path relative to it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleFile {
+    pub path: String,
+    /// The file's contents, in base64
+    pub contents: String,
+}
+
+/// A board and its files, i.e.
+/// ```json
+/// {
+///   "format_version": 1,
+///   "manufacturer": "Adafruit",
+///   "dir": "Feather_RP2040",
+///   "board": { "name": "Feather RP2040", ... },
+///   "files": [{ "path": "feather_rp2040.toml", "contents": "..." }]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BoardBundle {
+    pub format_version: u32,
+    pub manufacturer: String,
+    /// The board's directory, under its manufacturer's
+    pub dir: String,
+    /// The board as it's exported, for tools that only want to read the bundle
+    pub board: serde_json::Value,
+    pub files: Vec<BundleFile>,
+}
+
+/// Whether a path of a bundled file stays inside the board's directory.
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty() && !path.contains('\\') && Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Add the files of a directory to the bundle, leaving out hidden files and build output.
+fn add_files(root: &Path, dir: &Path, files: &mut Vec<BundleFile>, size: &mut u64) -> Result<(), String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("couldn't read {}: {}", dir.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            if !SKIPPED_DIRS.contains(&name.as_str()) {
+                add_files(root, &path, files, size)?;
+            }
+            continue;
+        }
+        let bytes = fs::read(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        *size += bytes.len() as u64;
+        if *size > MAX_BUNDLE_SIZE {
+            return Err(format!("the board's files are more than {} MB", MAX_BUNDLE_SIZE / 1024 / 1024));
+        }
+        let relative = path.strip_prefix(root).map_err(|_| format!("{} isn't in the board's directory", path.display()))?;
+        let relative: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+        files.push(BundleFile {
+            path: relative.join("/"),
+            contents: base64::engine::general_purpose::STANDARD.encode(bytes),
+        });
+    }
+    Ok(())
+}
+
+impl BoardBundle {
+
+    /// Bundle a board with the files of its directory. Only boards loaded from a file have
+    /// a directory to bundle.
+    pub fn new(board: &Board) -> Result<Self, String> {
+        let file = board.file().ok_or_else(|| format!("{} wasn't loaded from a file", board.label()))?;
+        let dir = file.parent().ok_or_else(|| format!("{} has no directory", file.display()))?;
+        let dir_name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let manufacturer = dir.parent().and_then(|p| p.file_name()).map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let board_json = board.to_json().map_err(|e| format!("couldn't export {}: {}", board.label(), e))?;
+        let mut files = vec![];
+        add_files(dir, dir, &mut files, &mut 0)?;
+        Ok(Self {
+            format_version: BUNDLE_VERSION,
+            manufacturer,
+            dir: dir_name,
+            board: serde_json::from_str(&board_json).map_err(|e| e.to_string())?,
+            files,
+        })
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Read a bundle, checking that its files can only be written where they belong and
+    /// that it has a board file that loads.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let bundle: BoardBundle = serde_json::from_str(json).map_err(|e| format!("the bundle isn't valid: {}", e))?;
+        if bundle.format_version > BUNDLE_VERSION {
+            return Err(format!("the bundle is version {}, and this version of Iron Coder reads up to version {}", bundle.format_version, BUNDLE_VERSION));
+        }
+        for (what, name) in [("manufacturer", &bundle.manufacturer), ("dir", &bundle.dir)] {
+            if !is_plain_name(name) {
+                return Err(format!("the {} \"{}\" isn't a plain file name", what, name));
+            }
+        }
+        for (i, file) in bundle.files.iter().enumerate() {
+            if !is_relative_path(&file.path) {
+                return Err(format!("the file \"{}\" isn't inside the board's directory", file.path));
+            }
+            if bundle.files[..i].iter().any(|f| f.path == file.path) {
+                return Err(format!("the file {} is in the bundle twice", file.path));
+            }
+        }
+        let mut tomls = 0;
+        for file in bundle.files.iter().filter(|f| is_plain_name(&f.path) && f.path.ends_with(".toml")) {
+            let bytes = file.decode()?;
+            Board::from_toml_str(&String::from_utf8_lossy(&bytes)).map_err(|e| format!("{} isn't a valid board: {:?}", file.path, e))?;
+            tomls += 1;
+        }
+        if tomls == 0 {
+            return Err("the bundle has no board file".to_string());
+        }
+        Ok(bundle)
+    }
+
+    /// Where the board goes in a boards directory.
+    pub fn destination(&self, boards_dir: &Path) -> PathBuf {
+        boards_dir.join(&self.manufacturer).join(&self.dir)
+    }
+
+    /// Write the bundle's files into the boards directory, and return the board's
+    /// directory. A board that's already there is only written over with `replace`.
+    pub fn install(&self, boards_dir: &Path, replace: bool) -> Result<PathBuf, String> {
+        let dir = self.destination(boards_dir);
+        if dir.exists() {
+            if !replace {
+                return Err(format!("there's already a board at {}", dir.display()));
+            }
+            fs::remove_dir_all(&dir).map_err(|e| format!("couldn't remove the old board at {}: {}", dir.display(), e))?;
+        }
+        let files = self.files.iter()
+            .map(|file| Ok((dir.join(&file.path), file.decode()?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        for (path, bytes) in files {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("couldn't create {}: {}", parent.display(), e))?;
+            }
+            fs::write(&path, bytes).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+        }
+        Ok(dir)
+    }
+}
+
+impl BundleFile {
+    fn decode(&self) -> Result<Vec<u8>, String> {
+        base64::engine::general_purpose::STANDARD.decode(&self.contents)
+            .map_err(|e| format!("the contents of {} aren't base64: {}", self.path, e))
+    }
+}
+
+/// Bundle a board into a file.
+pub fn export(board: &Board, bundle_file: &Path) -> Result<(), String> {
+    let json = BoardBundle::new(board)?.to_json().map_err(|e| e.to_string())?;
+    fs::write(bundle_file, json).map_err(|e| format!("couldn't write {}: {}", bundle_file.display(), e))
+}
+
+/// Read a bundle file and write its board into the boards directory.
+pub fn import(bundle_file: &Path, boards_dir: &Path, replace: bool) -> Result<PathBuf, String> {
+    let json = fs::read_to_string(bundle_file).map_err(|e| format!("couldn't read {}: {}", bundle_file.display(), e))?;
+    BoardBundle::parse(&json)?.install(boards_dir, replace)
+}
@@ -0,0 +1,130 @@
+//! Title: Iron Coder Board Module - Board Comparison
+//! Description: Putting two or three boards side by side to pick the one that fits a
+//!   project. The boards are picked in the board selector, and their specs are lined up
+//!   in a table with the best of each marked.
+
+use std::collections::BTreeSet;
+
+use crate::board::Board;
+
+/// The most boards compared at once, which still fit side by side.
+pub const MAX_COMPARED: usize = 3;
+
+/// One spec of the compared boards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpecRow {
+    pub spec: &'static str,
+    /// Each board's value, in the order of the boards, with "-" for unknown
+    pub values: Vec<String>,
+    /// The board with the most of it, for specs where more is better and the boards differ
+    pub best: Option<usize>,
+}
+
+impl SpecRow {
+    fn new(spec: &'static str, values: Vec<Option<String>>) -> Self {
+        let values = values.into_iter().map(|v| v.unwrap_or_else(|| "-".to_string())).collect();
+        Self { spec, values, best: None }
+    }
+
+    /// Mark the board with the most of the spec, if just one has the most.
+    fn best_of(mut self, amounts: &[Option<u64>]) -> Self {
+        let most = amounts.iter().flatten().max();
+        let with_most: Vec<usize> = (0..amounts.len()).filter(|&i| most.is_some() && amounts[i].as_ref() == most).collect();
+        if let [best] = with_most[..] {
+            self.best = Some(best);
+        }
+        self
+    }
+}
+
+/// The board's peripherals if it lists them, or else the kinds of interface its pins can
+/// be used for, i.e. "I2C, SPI, UART".
+fn peripherals(board: &Board) -> String {
+    if let Some(peripherals) = board.peripherals().filter(|p| !p.is_empty()) {
+        return peripherals.to_string();
+    }
+    let from_pinout = board.get_pinout().into_iter().map(|m| m.interface.iface_type);
+    let from_pins = board.pins().iter().flat_map(|p| p.capabilities());
+    let kinds: BTreeSet<String> = from_pinout.chain(from_pins).map(|t| t.to_string()).filter(|t| t != "NONE").collect();
+    kinds.into_iter().collect::<Vec<_>>().join(", ")
+}
+
+/// The specs of the boards, side by side.
+pub fn spec_rows(boards: &[&Board]) -> Vec<SpecRow> {
+    let column = |f: &dyn Fn(&Board) -> Option<String>| boards.iter().map(|b| f(b)).collect::<Vec<_>>();
+    let amounts = |f: &dyn Fn(&Board) -> Option<u64>| boards.iter().map(|b| f(b)).collect::<Vec<_>>();
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    vec![
+        SpecRow::new("manufacturer", column(&|b| non_empty(b.manufacturer().to_string()))),
+        SpecRow::new("kind", column(&|b| Some(b.kind().to_string()))),
+        SpecRow::new("CPU", column(&|b| b.cpu().map(str::to_string))),
+        SpecRow::new("chip", column(&|b| b.chip_name().map(str::to_string))),
+        SpecRow::new("clock", column(&|b| b.clock().map(|c| c.to_string())))
+            .best_of(&amounts(&|b| b.clock().map(|c| c.hz()))),
+        SpecRow::new("RAM", column(&|b| b.ram().map(|m| m.to_string())))
+            .best_of(&amounts(&|b| b.ram().map(|m| m.bytes()))),
+        SpecRow::new("flash", column(&|b| b.flash().map(|m| m.to_string())))
+            .best_of(&amounts(&|b| b.flash().map(|m| m.bytes()))),
+        SpecRow::new("standard", column(&|b| b.standard().map(|s| s.to_string()))),
+        SpecRow::new("programmable", column(&|b| Some(if b.is_main_board() { "yes" } else { "no" }.to_string()))),
+        SpecRow::new("flashing", column(&|b| b.flash_method().map(|m| m.to_string()))),
+        SpecRow::new("peripherals", column(&|b| non_empty(peripherals(b)))),
+        SpecRow::new("pins", column(&|b| (!b.pins().is_empty()).then(|| b.pins().len().to_string())))
+            .best_of(&amounts(&|b| (!b.pins().is_empty()).then(|| b.pins().len() as u64))),
+        SpecRow::new("ADC", column(&|b| b.adc().map(|a| format!("{} bit, {} V", a.resolution, a.reference))))
+            .best_of(&amounts(&|b| b.adc().map(|a| a.resolution as u64))),
+        SpecRow::new("LoRa", column(&|b| Some(if b.is_lora_capable() { "yes" } else { "no" }.to_string()))),
+    ]
+}
+
+/// The boards picked for comparing, by key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoardComparison {
+    picked: Vec<String>,
+    /// Whether the comparison is being shown
+    pub open: bool,
+}
+
+impl BoardComparison {
+
+    pub fn is_picked(&self, board: &Board) -> bool {
+        self.picked.iter().any(|name| *name == board.key())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.picked.len() >= MAX_COMPARED
+    }
+
+    pub fn len(&self) -> usize {
+        self.picked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.picked.is_empty()
+    }
+
+    /// Pick a board, or put it back. Returns false if it can't be picked because there
+    /// are as many boards picked as can be compared.
+    pub fn toggle(&mut self, board: &Board) -> bool {
+        if self.is_picked(board) {
+            self.picked.retain(|name| *name != board.key());
+            return true;
+        }
+        if self.is_full() {
+            return false;
+        }
+        self.picked.push(board.key());
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.picked.clear();
+        self.open = false;
+    }
+
+    /// The picked boards, in the order they were picked. Boards that have gone, i.e.
+    /// after the boards are reloaded, are left out.
+    pub fn boards<'a>(&self, known_boards: &'a [Board]) -> Vec<&'a Board> {
+        self.picked.iter().filter_map(|name| known_boards.iter().find(|b| b.key() == *name)).collect()
+    }
+}
@@ -0,0 +1,201 @@
+//! Title: Iron Coder Board Module - Config
+//! Description: The compile-time configuration options that a board declares in its toml
+//!   file, a bit like Kconfig symbols. Each option has a type (bool, int or enum) and a
+//!   default, and can turn on a cargo feature or depend on another (bool) option. The
+//!   values chosen for a project live in the project; see project::config.
+
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+/// The types a config option can have.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigType {
+    Bool,
+    Int,
+    Enum,
+}
+
+/// The value of a config option.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(i64),
+    Choice(String),
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigValue::Bool(b) => write!(f, "{}", b),
+            ConfigValue::Int(i) => write!(f, "{}", i),
+            ConfigValue::Choice(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+/// The Rust integer types an int option can be emitted as, and their ranges.
+const INT_TYPES: [(&str, i128, i128); 9] = [
+    ("u8", 0, u8::MAX as i128), ("u16", 0, u16::MAX as i128), ("u32", 0, u32::MAX as i128),
+    ("u64", 0, i64::MAX as i128), ("usize", 0, u32::MAX as i128),
+    ("i8", i8::MIN as i128, i8::MAX as i128), ("i16", i16::MIN as i128, i16::MAX as i128),
+    ("i32", i32::MIN as i128, i32::MAX as i128), ("i64", i64::MIN as i128, i64::MAX as i128),
+];
+
+/// One option in a board's config schema, i.e.
+/// ```toml
+/// [[config]]
+/// name = "NEOPIXEL_BRIGHTNESS"
+/// type = "int"
+/// default = 32
+/// min = 0
+/// max = 255
+/// rust_type = "u8"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConfigOption {
+    /// The name of the generated constant, in SCREAMING_SNAKE_CASE
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(rename = "type")]
+    pub kind: ConfigType,
+    pub default: ConfigValue,
+    /// The range of an int option (inclusive), if it's narrower than its type
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// The type an int option is emitted as; i32 if not given
+    pub rust_type: Option<String>,
+    /// The variants of an enum option
+    pub choices: Option<Vec<String>>,
+    /// For bool options, a cargo feature that is on when the option is. For enum
+    /// options, a prefix: choice `Debug` of an option with feature "log" turns on
+    /// the feature "log-debug".
+    pub feature: Option<String>,
+    /// The name of a bool option that has to be on for this option to apply
+    pub depends_on: Option<String>,
+}
+
+fn is_screaming_snake(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn is_feature_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl ConfigOption {
+
+    /// The Rust type of the generated constant.
+    pub fn rust_type(&self) -> String {
+        match self.kind {
+            ConfigType::Bool => "bool".to_string(),
+            ConfigType::Int => self.rust_type.clone().unwrap_or("i32".to_string()),
+            ConfigType::Enum => self.type_name(),
+        }
+    }
+
+    /// The name of the enum generated for an enum option, i.e. LogLevel for LOG_LEVEL.
+    pub fn type_name(&self) -> String {
+        self.name.split('_').filter(|w| !w.is_empty()).map(|word| {
+            let word = word.to_lowercase();
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        }).collect()
+    }
+
+    /// The inclusive range of an int option, from its bounds and its type.
+    pub fn range(&self) -> (i64, i64) {
+        let rust_type = self.rust_type();
+        let (_, type_min, type_max) = INT_TYPES.iter().find(|(t, _, _)| *t == rust_type).copied().unwrap_or(INT_TYPES[7]);
+        let min = self.min.map_or(type_min, |m| (m as i128).max(type_min));
+        let max = self.max.map_or(type_max, |m| (m as i128).min(type_max));
+        (min as i64, max as i64)
+    }
+
+    /// Check that a value fits this option.
+    pub fn check_value(&self, value: &ConfigValue) -> Result<(), String> {
+        match (self.kind, value) {
+            (ConfigType::Bool, ConfigValue::Bool(_)) => Ok(()),
+            (ConfigType::Int, ConfigValue::Int(i)) => {
+                let (min, max) = self.range();
+                if *i < min || *i > max {
+                    return Err(format!("{} must be between {} and {}", self.name, min, max));
+                }
+                Ok(())
+            },
+            (ConfigType::Enum, ConfigValue::Choice(c)) => {
+                if !self.choices.iter().flatten().any(|choice| choice == c) {
+                    return Err(format!("{} isn't one of the choices for {}", c, self.name));
+                }
+                Ok(())
+            },
+            _ => Err(format!("{} has the wrong type for {}", value, self.name)),
+        }
+    }
+
+    /// Check the option itself, including its default.
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_screaming_snake(&self.name) {
+            return Err(format!("config option name {:?} must be SCREAMING_SNAKE_CASE", self.name));
+        }
+        match self.kind {
+            ConfigType::Int => {
+                if let Some(t) = &self.rust_type {
+                    if !INT_TYPES.iter().any(|(name, _, _)| name == t) {
+                        return Err(format!("{} isn't an integer type (in {})", t, self.name));
+                    }
+                }
+                let (min, max) = self.range();
+                if min > max {
+                    return Err(format!("{} has an empty range", self.name));
+                }
+            },
+            ConfigType::Enum => {
+                let choices = self.choices.as_deref().unwrap_or_default();
+                if choices.is_empty() {
+                    return Err(format!("{} needs at least one choice", self.name));
+                }
+                for (i, choice) in choices.iter().enumerate() {
+                    if syn::parse_str::<syn::Ident>(choice).is_err() || choices[..i].contains(choice) {
+                        return Err(format!("choice {:?} of {} isn't a unique identifier", choice, self.name));
+                    }
+                }
+            },
+            ConfigType::Bool => (),
+        }
+        if let Some(feature) = &self.feature {
+            if !is_feature_name(feature) {
+                return Err(format!("feature {:?} of {} isn't a valid feature name", feature, self.name));
+            }
+        }
+        if self.depends_on.as_ref() == Some(&self.name) {
+            return Err(format!("{} can't depend on itself", self.name));
+        }
+        self.check_value(&self.default)
+    }
+
+    /// The cargo features that a value of this option turns on.
+    pub fn features(&self, value: &ConfigValue) -> Vec<String> {
+        match (&self.feature, value) {
+            (Some(feature), ConfigValue::Bool(true)) => vec![feature.clone()],
+            (Some(prefix), ConfigValue::Choice(c)) => vec![format!("{}-{}", prefix, c.to_lowercase())],
+            _ => vec![],
+        }
+    }
+
+    /// Every cargo feature this option can turn on, so they can be declared.
+    pub fn all_features(&self) -> Vec<String> {
+        match self.kind {
+            ConfigType::Bool => self.features(&ConfigValue::Bool(true)),
+            ConfigType::Enum => self.choices.iter().flatten().flat_map(|c| self.features(&ConfigValue::Choice(c.clone()))).collect(),
+            ConfigType::Int => vec![],
+        }
+    }
+}
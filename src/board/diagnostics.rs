@@ -0,0 +1,281 @@
+//! Title: Iron Coder Board Module - Board Diagnostics
+//! Description: Checking board toml files in detail, so board authors can fix them. A
+//!   board that doesn't load gets the line and field of what's wrong with it, and one
+//!   that does is checked for keys that aren't part of a board, which serde would
+//!   otherwise ignore without a word.
+
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+use crate::board::{Board, BoardLoadError};
+use crate::project::backend::Severity;
+
+/// Something wrong with a board file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardDiagnostic {
+    pub severity: Severity,
+    pub path: PathBuf,
+    /// Where in the file, counting from 1
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The key the problem is with, i.e. "ram" or "pinout"
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for BoardDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}: ", self.severity)?;
+        if let Some(field) = &self.field {
+            write!(f, "{}: ", field)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl BoardDiagnostic {
+
+    fn new(severity: Severity, path: &Path, message: impl Into<String>) -> Self {
+        Self { severity, path: path.to_path_buf(), line: None, column: None, field: None, message: message.into() }
+    }
+
+    /// Point the diagnostic at a place in the file, and the key on that line.
+    fn at(mut self, toml_str: &str, offset: usize) -> Self {
+        let before = &toml_str[..offset.min(toml_str.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        self.line = Some(before.matches('\n').count() + 1);
+        self.column = Some(before[line_start..].chars().count() + 1);
+        if self.field.is_none() {
+            let line = toml_str[line_start..].lines().next().unwrap_or_default();
+            self.field = line_key(line);
+        }
+        self
+    }
+
+    fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+}
+
+/// The key a line of toml sets, or the table it starts.
+fn line_key(line: &str) -> Option<String> {
+    let line = line.trim();
+    let key = match line.strip_prefix('[') {
+        Some(table) => table.trim_start_matches('[').split(']').next()?,
+        None => line.split_once('=')?.0,
+    };
+    let key = key.trim().trim_matches('"');
+    (!key.is_empty()).then(|| key.to_string())
+}
+
+/// Where a key is set at the top of a toml file, or a table of that name starts.
+fn key_offset(toml_str: &str, key: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in toml_str.split_inclusive('\n') {
+        if line_key(line).is_some_and(|k| k == key || k.starts_with(&format!("{}.", key))) {
+            return Some(offset + line.len() - line.trim_start().len());
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Where a quoted string first appears in a toml file.
+fn string_offset(toml_str: &str, value: &str) -> Option<usize> {
+    toml_str.find(&format!("\"{}\"", value))
+}
+
+/// The names of the fields a struct is deserialized from. Serde only gives these out to
+/// a deserializer, so this is one that takes them and stops.
+fn struct_fields<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct Fields<'a>(&'a mut &'static [&'static str]);
+    impl<'de> Deserializer<'de> for Fields<'_> {
+        type Error = de::value::Error;
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+        fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], _visitor: V) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("only the fields are needed"))
+        }
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+            unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Fields(&mut fields));
+    fields
+}
+
+/// Check the text of a board file. Nothing is returned for a board without problems.
+pub fn check_toml(path: &Path, toml_str: &str) -> Vec<BoardDiagnostic> {
+    let board: Board = match toml::from_str(toml_str) {
+        Ok(board) => board,
+        Err(e) => {
+            let mut diagnostic = BoardDiagnostic::new(Severity::Error, path, e.message().trim());
+            if let Some(Range { start, .. }) = e.span() {
+                diagnostic = diagnostic.at(toml_str, start);
+            }
+            return vec![diagnostic];
+        },
+    };
+    check_board(path, toml_str, &board)
+}
+
+/// Check a board that's been parsed from `toml_str` for what parsing it doesn't catch.
+pub fn check_board(path: &Path, toml_str: &str, board: &Board) -> Vec<BoardDiagnostic> {
+    let mut diagnostics = Vec::new();
+    if let Ok(table) = toml_str.parse::<toml::Table>() {
+        let fields = struct_fields::<Board>();
+        for key in table.keys().filter(|k| !fields.contains(&k.as_str())) {
+            let diagnostic = BoardDiagnostic::new(Severity::Warning, path, "isn't a board field, so it's ignored").field(key.as_str());
+            diagnostics.push(match key_offset(toml_str, key) {
+                Some(offset) => diagnostic.at(toml_str, offset),
+                None => diagnostic,
+            });
+        }
+        if !table.contains_key("name") {
+            diagnostics.push(BoardDiagnostic::new(Severity::Error, path, "every board needs a name").field("name"));
+        }
+        if !table.contains_key("manufacturer") {
+            diagnostics.push(BoardDiagnostic::new(Severity::Warning, path, "the board selector groups boards by manufacturer").field("manufacturer"));
+        }
+    }
+    if board.svg_board_info.is_none() {
+        let svg = path.with_extension("svg");
+        let message = format!("there's no {} with the board image, so the board isn't drawn", svg.file_name().unwrap_or_default().to_string_lossy());
+        diagnostics.push(BoardDiagnostic::new(Severity::Warning, path, message));
+    }
+    match board.validate() {
+        Err(BoardLoadError::InvalidPin(pin)) => {
+            let diagnostic = BoardDiagnostic::new(Severity::Error, path, format!("pin \"{}\" is blank, has spaces in it, or is listed twice", pin));
+            diagnostics.push(match string_offset(toml_str, &pin) {
+                Some(offset) => diagnostic.at(toml_str, offset),
+                None => diagnostic,
+            });
+        },
+        // the blank name is reported above if it's missing altogether
+        Err(BoardLoadError::InvalidField(message)) if !(message.starts_with("name") && diagnostics.iter().any(|d| d.field.as_deref() == Some("name"))) => {
+            let field = message.split_whitespace().next().unwrap_or_default().to_string();
+            let mut diagnostic = BoardDiagnostic::new(Severity::Error, path, message);
+            if let Some(offset) = key_offset(toml_str, &field) {
+                diagnostic = diagnostic.at(toml_str, offset);
+            }
+            diagnostics.push(diagnostic);
+        },
+        _ => (),
+    }
+    diagnostics.sort_by_key(|d| (d.severity, d.line));
+    diagnostics
+}
+
+/// A diagnostic for a board file that couldn't be loaded, with the details from its text
+/// if it could be read and there's anything wrong with it.
+pub fn load_error(path: &Path, error: &BoardLoadError, toml_str: Option<&str>) -> Vec<BoardDiagnostic> {
+    let message = match error {
+        BoardLoadError::FsError(e) => format!("couldn't read the file: {}", e),
+        BoardLoadError::SvgError => "the board image isn't an SVG with a PNG in it".to_string(),
+        _ => {
+            let diagnostics = toml_str.map(|text| check_toml(path, text)).unwrap_or_default();
+            if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                return diagnostics;
+            }
+            format!("{:?}", error)
+        },
+    };
+    vec![BoardDiagnostic::new(Severity::Error, path, message)]
+}
+
+/// A diagnostic for a directory, or an entry in it, that couldn't be read while looking
+/// for board files.
+pub fn unreadable(path: &Path, error: &io::Error) -> BoardDiagnostic {
+    BoardDiagnostic::new(Severity::Error, path, format!("couldn't read the directory: {}", error))
+}
+
+/// The boards that could be loaded from a boards directory, and what's wrong with the
+/// files and directories that couldn't.
+#[derive(Debug, Default)]
+pub struct BoardLoadReport {
+    pub boards: Vec<Board>,
+    pub diagnostics: Vec<BoardDiagnostic>,
+}
+
+impl BoardLoadReport {
+
+    /// The board files and directories that couldn't be loaded.
+    pub fn errors(&self) -> impl Iterator<Item = &BoardDiagnostic> {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Error)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+}
+
+impl fmt::Display for BoardLoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "loaded {} boards, with {} errors", self.boards.len(), self.errors().count())?;
+        for error in self.errors() {
+            write!(f, "\n{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// A diagnostic for a board file that's for a board that was already loaded from
+/// another file. Revisions of a board need a `revision` to tell them apart.
+pub fn duplicate(path: &Path, board: &Board) -> BoardDiagnostic {
+    let message = match board.revision() {
+        Some(_) => format!("{} is already described by another file, so this one is skipped", board.label()),
+        None => format!("{} is already described by another file, so this one is skipped. Give each a revision if they're different revisions of the board", board.label()),
+    };
+    BoardDiagnostic::new(Severity::Warning, path, message).field("name")
+}
+
+/// A diagnostic for a board with the same name and revision as one by another
+/// manufacturer. Both are loaded, but they're only told apart by their manufacturer.
+pub fn same_name(path: &Path, board: &Board, other: &Board) -> BoardDiagnostic {
+    let message = format!("{} by {} has the same name as the one by {}, so they're only told apart by their manufacturer", board.label(), board.manufacturer(), other.manufacturer());
+    BoardDiagnostic::new(Severity::Warning, path, message).field("name")
+}
+
+/// Show the diagnostics in the Ui, worst first.
+pub fn display(ui: &mut egui::Ui, diagnostics: &[BoardDiagnostic]) {
+    if diagnostics.is_empty() {
+        ui.label("the board files have no problems.");
+        return;
+    }
+    for diagnostic in diagnostics {
+        let color = match diagnostic.severity {
+            Severity::Error => ui.visuals().error_fg_color,
+            Severity::Warning => ui.visuals().warn_fg_color,
+            Severity::Note => ui.visuals().text_color(),
+        };
+        ui.horizontal_wrapped(|ui| {
+            ui.colored_label(color, diagnostic.severity.to_string());
+            let place = match (diagnostic.line, diagnostic.column) {
+                (Some(line), Some(column)) => format!("{}:{}:{}", diagnostic.path.display(), line, column),
+                (Some(line), None) => format!("{}:{}", diagnostic.path.display(), line),
+                _ => diagnostic.path.display().to_string(),
+            };
+            ui.monospace(place);
+            if let Some(field) = &diagnostic.field {
+                ui.strong(field);
+            }
+            ui.label(&diagnostic.message);
+        });
+    }
+}
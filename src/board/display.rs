@@ -1,9 +1,15 @@
 //! This module contains code related to displaying Boards and related types in egui.
 
 use log::{info, debug};
-use crate::board::Board;
+use crate::board::{Board, compare, gallery, units};
+use crate::board::flash_layout::FlashLayout;
+use crate::board::partitions::PartitionTable;
+use crate::paths;
+use std::path::Path;
 use egui::{
     Color32,
+    Label,
+    RichText,
     Ui,
     Response,
     FontFamily,
@@ -14,7 +20,10 @@ use egui::text::{
     LayoutJob,
 };
 use egui::widgets::Widget;
-use egui_extras::RetainedImage;
+
+use crate::app::texture_cache;
+use crate::app::logos;
+use crate::app::events::{self, Event};
 
 /// Construct a LayoutJob with a bold heading, followed by a colon,
 /// followed by some content, all with custom colors.
@@ -44,131 +53,342 @@ fn make_field_widget_text(heading: &str,
     return job;
 }
 
-/// Normal view for the board widget
-impl Widget for Board {
-    // How to display a board as a widget
-    fn ui(self, ui: &mut Ui) -> Response {
-        let response: egui::Response;
-        if let Some(svg_board_info) = self.svg_board_info {
-            // Use a frame to display multiple widgets within our widget,
-            // with an inner margin
-            response = egui::Frame::none()
-            .inner_margin(egui::Margin::same(10.0))
-            .show(ui, |ui| {
-                // center all text
-                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    // let label = egui::RichText::new(self.name).strong();
-                    ui.label(make_field_widget_text(
-                        "Board: ",
-                        ui.style().visuals.warn_fg_color,
-                        self.name.as_str(),
-                        ui.style().visuals.window_stroke.color,
-                    ));
-                    // ui.label(label);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    retained_image.show_max_size(ui, egui::vec2(150.0, 150.0));
-                });
-                ui.horizontal(|ui| {
-                    ui.label(make_field_widget_text(
-                        "Manufacturer: ",
-                        ui.style().visuals.warn_fg_color,
-                        self.manufacturer.as_str(),
-                        ui.style().visuals.window_stroke.color,
-                    ));
-                // TODO -- make the manufacturer logos an app-wide resource
-                    // let p = Path::new("./assets/images/Adafruit_logo_small.png");
-                    // let image = image::io::Reader::open(p).unwrap().decode().unwrap();
-                    // let size = [image.width() as _, image.height() as _];
-                    // let image_buffer = image.to_rgba8();
-                    // let pixels = image_buffer.as_flat_samples();
-                    // let color_image = egui::ColorImage::from_rgba_unmultiplied(
-                    //     size,
-                    //     pixels.as_slice(),
-                    // );
-                    // let ri = egui_extras::RetainedImage::from_color_image("logo", color_image);
-                    // let image = egui::widgets::Image::new(
-                    //     ri.texture_id(ui.ctx()),
-                    //     egui::Vec2::new(47.0, 16.0)
-                    // ).tint(egui::Color32::GREEN);   // TODO: replace with a val from current colorscheme
-                    // ui.add(image);
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Ecosystem: ");
-                    if let Some(standard) = self.standard {
-                        ui.label(standard.to_string());
-                    } else {
-                        ui.label("none");
-                    }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("CPU: ");
-                    if let Some(cpu) = self.cpu {
-                        ui.label(cpu);
-                    } else {
-                        ui.label("unknown");
-                    }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("RAM Amount (in kb): ");
-                    if let Some(ram) = self.ram {
-                        ui.label(ram.to_string());
-                    } else {
-                        ui.label("unknown");
-                    }
-                });
-                ui.horizontal(|ui| {
-                    ui.label("Flash Amount (in kb): ");
-                    if let Some(flash) = self.flash {
-                        ui.label(flash.to_string());
-                    } else {
-                        ui.label("unknown");
-                    }
-                });
-                ui.separator();
-                // Show the examples
-                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    let label = egui::RichText::new("Examples").underline();
-                    ui.label(label);
-                });
-                for e in self.examples {
-                    ui.horizontal(|ui| {
-                        if ui.link(e.file_name().unwrap().to_str().unwrap()).clicked() {
-                            info!("TODO - open the example!")
-                        };
-                    });
-                }
-                ui.separator();
-                // show the interfaces
-                ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    let label = egui::RichText::new("Pinout").underline();
-                    ui.label(label);
-                });
-                ui.label(format!("{:?}", self.pinout));
+/// The manufacturer of a board, as its logo if there's one, or else its name.
+fn manufacturer_field(ui: &mut Ui, manufacturer: &str) {
+    let (hcolor, ccolor) = (ui.style().visuals.warn_fg_color, ui.style().visuals.window_stroke.color);
+    if logos::logo(ui.ctx(), manufacturer).is_none() {
+        ui.label(make_field_widget_text("Manufacturer: ", hcolor, manufacturer, ccolor));
+        return;
+    }
+    ui.label(make_field_widget_text("Manufacturer: ", hcolor, "", ccolor));
+    logos::show(ui, manufacturer, logos::LOGO_HEIGHT);
+}
 
-            }).response.interact(egui::Sense::click());
+/// Show a downscaled copy of the board image, uploaded through the app-wide texture cache
+/// so that browsing many boards stays within the texture memory budget. A spinner is shown
+/// while the image is being decoded.
+fn show_thumbnail(ui: &mut Ui, board: &Board, max_size: egui::Vec2) -> Response {
+    let image = match &board.svg_board_info {
+        Some(svg_board_info) => match svg_board_info.image.get(ui.ctx()) {
+            Some(image) => Some(image),
+            None => return ui.add(egui::Spinner::new().size(max_size.min_elem().min(32.0))),
+        },
+        None => None,
+    };
+    let cache = texture_cache::shared(ui.ctx());
+    let key = format!("{}#thumbnail", board.key());
+    let handle = cache.lock().unwrap().get_or_load(ui.ctx(), &key, || {
+        match image {
+            Some(image) => texture_cache::thumbnail(&image, texture_cache::THUMBNAIL_SIZE),
+            None => egui::ColorImage::example(),
+        }
+    });
+    ui.add(egui::Image::from_texture(&handle).max_size(max_size))
+}
 
-            if ui.rect_contains_pointer(response.rect) {
-                // draw a bounding box
-                ui.painter().rect_stroke(response.rect, 0.0, (1.0, egui::Color32::WHITE));
+/// How wide a compact board card is.
+pub const CARD_WIDTH: f32 = 120.0;
+/// The most a selected card opens up to.
+const DETAILS_WIDTH: f32 = 420.0;
+
+/// How a board widget shows a board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BoardDisplayMode {
+    /// Everything about the board: its specs, examples and pinout
+    Detailed,
+    /// A card with the board's image and name, for browsing many boards. The details are
+    /// shown when the card is hovered, or under it once it's selected.
+    #[default]
+    Compact,
+}
+
+/// A file as a url the system can open, i.e. `file:///C:/boards/datasheet.pdf`.
+/// Characters that would break the url, like spaces, `#` and `%`, and any that aren't
+/// ASCII are percent-encoded.
+pub fn file_url(path: &Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    match encoded.starts_with('/') {
+        true => format!("file://{}", encoded),
+        false => format!("file:///{}", encoded),
+    }
+}
+
+/// Links to the board's datasheet and documentation, which open in the system's browser
+/// or PDF viewer.
+fn documentation_links(ui: &mut Ui, board: &Board) {
+    if let Some(url) = board.datasheet_url() {
+        ui.hyperlink_to("datasheet", url).on_hover_text(url);
+    }
+    if let Some(file) = board.datasheet_file().filter(|f| f.is_file()) {
+        let file = paths::canonicalize(&file).unwrap_or(file);
+        if ui.link("datasheet (offline)").on_hover_text(file.display().to_string()).clicked() {
+            ui.ctx().open_url(egui::OpenUrl::new_tab(file_url(&file)));
+        }
+    }
+    if let Some(url) = board.docs_url() {
+        ui.hyperlink_to("documentation", url).on_hover_text(url);
+    }
+}
+
+/// The details of a board, for the detailed view and the compact card.
+fn show_details(ui: &mut Ui, board: &Board) {
+    // center all text
+    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+        // let label = egui::RichText::new(board.name).strong();
+        ui.label(make_field_widget_text(
+            "Board: ",
+            ui.style().visuals.warn_fg_color,
+            &board.label(),
+            ui.style().visuals.window_stroke.color,
+        ));
+        // ui.label(label);
+        gallery::display(ui, board, egui::vec2(150.0, 150.0));
+    });
+    ui.horizontal(|ui| {
+        manufacturer_field(ui, &board.manufacturer);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Kind: ");
+        ui.label(board.kind().to_string());
+    });
+    ui.horizontal(|ui| {
+        ui.label("Ecosystem: ");
+        if let Some(standard) = &board.standard {
+            ui.label(standard.to_string());
+        } else {
+            ui.label("none");
+        }
+    });
+    if !board.tags.is_empty() {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Tags: ");
+            for tag in board.tags.iter() {
+                ui.weak(tag);
             }
-            
-            //another way of doing it; clicking works but scaling is off
-            // let th = ui.ctx().load_texture(
-            //     "pic",
-            //     color_image,
-            //     Default::default(),
-            // );
-            // let i = egui::Image::new(&th, egui::vec2(128.0, 128.0)).sense(egui::Sense::click());
-            // response = ui.add(i);
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("CPU: ");
+        if let Some(cpu) = &board.cpu {
+            ui.label(cpu);
         } else {
-            response = ui.allocate_response(egui::vec2(128.0, 128.0), egui::Sense::click());
+            ui.label("unknown");
         }
-        return response;
+    });
+    if let Some(method) = board.flash_method {
+        ui.horizontal(|ui| {
+            ui.label("Flashing: ");
+            let label = ui.label(method.to_string());
+            if let Some(chip) = board.chip_name() {
+                label.on_hover_text(format!("chip: {}", chip));
+            }
+        });
+    }
+    if board.datasheet_url().is_some() || board.docs_url().is_some() || board.datasheet_file().is_some() {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Docs: ");
+            documentation_links(ui, board);
+        });
+    }
+    let locale = units::current_locale();
+    ui.horizontal(|ui| {
+        ui.label("Clock Speed: ");
+        if let Some(clock) = board.clock {
+            ui.label(clock.format(&locale));
+        } else {
+            ui.label("unknown");
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("RAM: ");
+        if let Some(ram) = board.ram {
+            ui.label(ram.format(&locale));
+        } else {
+            ui.label("unknown");
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Flash: ");
+        if let Some(flash) = board.flash {
+            ui.label(flash.format(&locale));
+        } else {
+            ui.label("unknown");
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Peripherals: ");
+        if let Some(peripherals) = board.peripherals {
+            ui.label(peripherals.to_string());
+        } else {
+            ui.label("unknown");
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Power: ");
+        let electrical = electrical_summary(board, &locale);
+        if electrical.is_empty() {
+            ui.label("unknown");
+        } else {
+            ui.label(electrical);
+        }
+    });
+    if board.flash_layout.is_some() || board.partition_table.is_some() {
+        ui.add(FlashMapWidget { layout: board.flash_layout.as_ref(), partitions: board.partition_table.as_ref(), flash: board.flash, selected: None });
+    }
+    ui.separator();
+    // Show the examples
+    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+        let label = egui::RichText::new("Examples").underline();
+        ui.label(label);
+    });
+    for e in &board.examples {
+        ui.horizontal(|ui| {
+            let hover = format!("open {} in the editor", paths::display_name(&e.path));
+            if ui.link(e.title()).on_hover_text(hover).clicked() {
+                info!("opening example {}", e.path.display());
+                events::shared(ui.ctx()).publish(Event::ExamplePicked(e.path.clone()));
+            };
+            if ui.small_button("new project").on_hover_text("start a project from a copy of this example").clicked() {
+                events::shared(ui.ctx()).publish(Event::ProjectFromExample {
+                    board: board.key(),
+                    example: e.path.clone(),
+                });
+            }
+        });
+        if let Some(description) = e.description() {
+            ui.add(Label::new(RichText::new(description).weak()).wrap(true));
+        }
+        if !e.required_features().is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.weak("needs features:");
+                for feature in e.required_features() {
+                    ui.code(feature);
+                }
+            });
+        }
+    }
+    ui.separator();
+    // show the interfaces
+    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+        let label = egui::RichText::new("Pinout").underline();
+        ui.label(label);
+    });
+    ui.add(PinoutViewer(board));
+}
+
+/// The board's supply voltage, logic level and typical current draw, those of them it
+/// declares, i.e. "3.3 V, 3.3 V logic, 20 mA typical".
+pub fn electrical_summary(board: &Board, locale: &units::NumberLocale) -> String {
+    let mut parts = vec![];
+    if let Some(voltage) = board.operating_voltage {
+        parts.push(voltage.format(locale));
+    }
+    if let Some(level) = board.logic_level {
+        parts.push(format!("{} logic", level.format(locale)));
+    }
+    if let Some(current) = board.typical_current {
+        parts.push(format!("{} typical", current.format(locale)));
+    }
+    parts.join(", ")
+}
+
+/// A board, shown in one of the display modes
+pub struct BoardWidget<'a> {
+    board: &'a Board,
+    mode: BoardDisplayMode,
+    selected: bool,
+}
+
+impl<'a> BoardWidget<'a> {
+
+    pub fn new(board: &'a Board) -> Self {
+        Self { board, mode: BoardDisplayMode::Detailed, selected: false }
+    }
+
+    pub fn mode(mut self, mode: BoardDisplayMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether the board is selected, which opens a compact card up to the details.
+    pub fn selected(mut self, selected: bool) -> Self {
+        self.selected = selected;
+        self
     }
 
+    fn show_card(&self, ui: &mut Ui) -> Response {
+        let response = egui::Frame::none()
+        .inner_margin(egui::Margin::same(5.0))
+        .show(ui, |ui| {
+            ui.vertical(|ui| {
+                // the cards line up in rows, until one is opened up
+                if self.selected {
+                    ui.set_max_width(DETAILS_WIDTH);
+                } else {
+                    ui.set_width(CARD_WIDTH);
+                }
+                ui.vertical_centered(|ui| {
+                    show_thumbnail(ui, self.board, egui::vec2(96.0, 96.0));
+                    ui.label(self.board.label());
+                });
+                if self.selected {
+                    ui.separator();
+                    show_details(ui, self.board);
+                }
+            });
+        }).response.interact(egui::Sense::click());
+        if self.selected {
+            ui.painter().rect_stroke(response.rect, 2.0, ui.visuals().selection.stroke);
+        } else if ui.rect_contains_pointer(response.rect) {
+            ui.painter().rect_stroke(response.rect, 2.0, (1.0, egui::Color32::WHITE));
+        }
+        if self.selected {
+            return response;
+        }
+        let board = self.board;
+        response.on_hover_ui(|ui| {
+            ui.set_max_width(DETAILS_WIDTH);
+            show_details(ui, board);
+        })
+    }
+}
+
+impl Widget for BoardWidget<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        if self.board.svg_board_info.is_none() {
+            return ui.allocate_response(egui::vec2(128.0, 128.0), egui::Sense::click());
+        }
+        if self.mode == BoardDisplayMode::Compact {
+            return self.show_card(ui);
+        }
+        // Use a frame to display multiple widgets within our widget,
+        // with an inner margin
+        let response = egui::Frame::none()
+        .inner_margin(egui::Margin::same(10.0))
+        .show(ui, |ui| {
+            show_details(ui, self.board);
+        }).response.interact(egui::Sense::click());
+        if self.selected || ui.rect_contains_pointer(response.rect) {
+            // draw a bounding box
+            ui.painter().rect_stroke(response.rect, 0.0, (1.0, egui::Color32::WHITE));
+        }
+        response
+    }
+}
+
+/// Normal view for the board widget
+impl Widget for Board {
+    // How to display a board as a widget
+    fn ui(self, ui: &mut Ui) -> Response {
+        ui.add(BoardWidget::new(&self))
+    }
 }
 
 /// Display the board for use in the Board selector window
@@ -177,7 +397,7 @@ impl Widget for BoardSelectorWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         let this_board = self.0;
         let response: egui::Response;
-        if let Some(svg_board_info) = this_board.clone().svg_board_info {
+        if this_board.svg_board_info.is_some() {
             // Use a frame to display multiple widgets within our widget,
             // with an inner margin
             response = egui::Frame::none()
@@ -188,24 +408,15 @@ impl Widget for BoardSelectorWidget {
                     ui.label(make_field_widget_text(
                         "Board: ",
                         ui.style().visuals.warn_fg_color,
-                        this_board.name.as_str(),
+                        &this_board.label(),
                         ui.style().visuals.window_stroke.color,
                     ));
                     // ui.label(label);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    let _ = retained_image.show_max_size(ui, egui::vec2(150.0, 150.0));
+                    let _ = show_thumbnail(ui, &this_board, egui::vec2(150.0, 150.0));
 
                 });
                 ui.horizontal(|ui| {
-                    ui.label(make_field_widget_text(
-                        "Manufacturer: ",
-                        ui.style().visuals.warn_fg_color,
-                        this_board.manufacturer.as_str(),
-                        ui.style().visuals.window_stroke.color,
-                    ));
+                    manufacturer_field(ui, &this_board.manufacturer);
                 });
                 ui.horizontal(|ui| {
                     ui.label("Ecosystem: ");
@@ -230,25 +441,50 @@ impl Widget for BoardSelectorWidget {
     }
 }
 
+/// Display boards side by side, with their specs in rows and the best of each marked
+pub struct BoardComparisonWidget<'a>(pub &'a [&'a Board]);
+impl Widget for BoardComparisonWidget<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let boards = self.0;
+        let best_color = ui.style().visuals.warn_fg_color;
+        egui::Grid::new("board_comparison").num_columns(boards.len() + 1).striped(true).spacing([24.0, 6.0]).show(ui, |ui| {
+            ui.label("");
+            for board in boards {
+                ui.vertical(|ui| {
+                    ui.strong(board.label());
+                    show_thumbnail(ui, board, egui::vec2(96.0, 96.0));
+                });
+            }
+            ui.end_row();
+            for row in compare::spec_rows(boards) {
+                ui.label(row.spec);
+                for (i, value) in row.values.iter().enumerate() {
+                    match row.best == Some(i) {
+                        true => ui.label(egui::RichText::new(value).strong().color(best_color)),
+                        false => ui.label(value),
+                    };
+                }
+                ui.end_row();
+            }
+        }).response
+    }
+}
+
 /// Display the Board as a "mini widget"
 pub struct BoardMiniWidget(pub Board);
 impl Widget for BoardMiniWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         let this_board = self.0;
         let response: egui::Response;
-        if let Some(svg_board_info) = this_board.clone().svg_board_info {
+        if this_board.svg_board_info.is_some() {
             // Use a frame to display multiple widgets within our widget,
             // with an inner margin
             response = egui::Frame::none()
             .inner_margin(egui::Margin::same(5.0))
             .show(ui, |ui| {
                 ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                    ui.label(this_board.clone().name);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    retained_image.show_max_size(ui, egui::vec2(96.0, 96.0));
+                    ui.label(this_board.label());
+                    show_thumbnail(ui, &this_board, egui::vec2(96.0, 96.0));
                 });
             }).response.interact(egui::Sense::click());
             if this_board.clone().is_main_board() {
@@ -262,3 +498,180 @@ impl Widget for BoardMiniWidget {
         return response;
     }
 }
+
+/// A bar showing how flash is split between the bootloader, image slots and partitions,
+/// with the selected slot or partition (if any) highlighted.
+pub struct FlashMapWidget<'a> {
+    pub layout: Option<&'a FlashLayout>,
+    pub partitions: Option<&'a PartitionTable>,
+    pub flash: Option<units::Memory>,
+    pub selected: Option<&'a str>,
+}
+
+impl Widget for FlashMapWidget<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let regions = self.layout.map(FlashLayout::regions).unwrap_or_default();
+        let partitions = self.partitions.map(|t| t.partitions.as_slice()).unwrap_or_default();
+        let total = self.flash.map(|f| f.bytes())
+            .or(regions.iter().map(|r| r.end()).chain(partitions.iter().map(|p| p.end())).max())
+            .unwrap_or(1)
+            .max(1) as f32;
+        let width = ui.available_width().clamp(120.0, 400.0);
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(width, 24.0), egui::Sense::hover());
+        let visuals = ui.style().visuals.clone();
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+        let x = |bytes: u64| rect.left() + rect.width() * (bytes as f32 / total).min(1.0);
+        let locale = units::current_locale();
+        let mut hovered = None;
+        let mut draw = |r: egui::Rect, name: &str, fill: Color32, hover: String| {
+            painter.rect(r.shrink(1.0), 2.0, fill, visuals.widgets.noninteractive.bg_stroke);
+            let galley = painter.layout_no_wrap(name.to_string(), egui::FontId::monospace(10.0), visuals.text_color());
+            if galley.size().x < r.width() - 4.0 {
+                painter.galley(r.center() - galley.size() / 2.0, galley, visuals.text_color());
+            }
+            if response.hovered() && ui.rect_contains_pointer(r) {
+                hovered = Some(hover);
+            }
+        };
+        if let Some(layout) = self.layout {
+            for region in regions.iter() {
+                let r = egui::Rect::from_x_y_ranges(x(region.offset.bytes())..=x(region.end()), rect.y_range());
+                let is_slot = layout.slots.iter().any(|s| std::ptr::eq(s, *region));
+                let fill = if self.selected == Some(region.name.as_str()) && is_slot {
+                    visuals.selection.bg_fill
+                } else if is_slot {
+                    visuals.widgets.inactive.bg_fill
+                } else {
+                    visuals.widgets.noninteractive.bg_fill
+                };
+                let name = layout.region_name(region);
+                draw(r, name, fill, format!("{}: 0x{:08x}, {}", name, layout.address(region), region.size.format(&locale)));
+            }
+        }
+        if let Some(table) = self.partitions {
+            for partition in partitions.iter() {
+                let r = egui::Rect::from_x_y_ranges(x(partition.offset.bytes())..=x(partition.end()), rect.y_range());
+                let fill = if self.selected == Some(partition.name.as_str()) {
+                    visuals.selection.bg_fill
+                } else if partition.kind.is_app() {
+                    visuals.widgets.inactive.bg_fill
+                } else {
+                    visuals.widgets.hovered.weak_bg_fill
+                };
+                let hover = format!("{} ({}): 0x{:08x}, {}", partition.name, partition.kind, table.address(partition), partition.size.format(&locale));
+                draw(r, &partition.name, fill, hover);
+            }
+        }
+        match hovered {
+            Some(text) => response.on_hover_text(text),
+            None => response,
+        }
+    }
+}
+
+/// The board image with its pins outlined, for looking up what a pin can do without the
+/// datasheet. Clicking a pin selects it and lists its functions and capabilities under
+/// the image; the selection is kept in egui's memory for each board.
+pub struct PinoutViewer<'a>(pub &'a Board);
+
+impl PinoutViewer<'_> {
+
+    /// The functions and capabilities of a pin.
+    fn show_pin(&self, ui: &mut Ui, name: &str) {
+        let board = self.0;
+        ui.label(make_field_widget_text("Pin: ", ui.style().visuals.warn_fg_color, name, ui.style().visuals.text_color()));
+        match board.pin(name) {
+            Some(pin) => {
+                ui.label(format!("Function: {}", if pin.function.is_empty() { "unknown" } else { &pin.function }));
+                if !pin.alternate_functions.is_empty() {
+                    ui.label(format!("Alternate functions: {}", pin.alternate_functions.join(", ")));
+                }
+            },
+            None => {
+                ui.weak("the board file doesn't describe this pin");
+            },
+        }
+        let capabilities = board.pin_capabilities(name);
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Capabilities:");
+            if capabilities.is_empty() {
+                ui.label("none");
+            }
+            for capability in capabilities {
+                egui::Frame::none()
+                    .fill(ui.visuals().widgets.inactive.bg_fill)
+                    .rounding(3.0)
+                    .inner_margin(egui::Margin::symmetric(4.0, 1.0))
+                    .show(ui, |ui| ui.monospace(capability.to_string()));
+            }
+        });
+    }
+}
+
+impl Widget for PinoutViewer<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let board = self.0;
+        let Some(svg_board_info) = &board.svg_board_info else {
+            return ui.weak("no board image to show the pins on");
+        };
+        let id = ui.make_persistent_id(("pinout viewer", board.label()));
+        let mut selected: Option<String> = ui.data(|data| data.get_temp(id)).flatten();
+        let size = svg_board_info.physical_size;
+        let scale = ui.available_width().clamp(120.0, 400.0) / size.x.max(1.0);
+        let (rect, response) = ui.allocate_exact_size(size * scale, egui::Sense::click());
+        let painter = ui.painter_at(rect);
+        match texture_cache::board_texture(ui.ctx(), board) {
+            Some(handle) => {
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                painter.image(handle.id(), rect, uv, Color32::WHITE);
+            },
+            // the pins can be used while the image is decoded
+            None => {
+                painter.rect_filled(rect, 4.0, ui.visuals().faint_bg_color);
+            },
+        }
+
+        let visuals = ui.visuals().clone();
+        let pointer = response.hover_pos();
+        let mut hovered = None;
+        for (name, pin_rect) in svg_board_info.pin_rects.iter() {
+            let r = egui::Rect::from_min_max(rect.min + pin_rect.min.to_vec2() * scale, rect.min + pin_rect.max.to_vec2() * scale);
+            let is_selected = selected.as_deref() == Some(name.as_str());
+            let is_hovered = pointer.is_some_and(|p| r.expand(2.0).contains(p));
+            if is_hovered {
+                hovered = Some(name.clone());
+            }
+            let stroke = match (is_selected, is_hovered) {
+                (true, _) => egui::Stroke::new(2.0, visuals.selection.stroke.color),
+                (false, true) => egui::Stroke::new(1.5, visuals.warn_fg_color),
+                (false, false) => egui::Stroke::new(1.0, visuals.weak_text_color()),
+            };
+            painter.rect_stroke(r, 1.0, stroke);
+            // label the pin next to its pad, on whichever side has room
+            let galley = painter.layout_no_wrap(name.clone(), FontId::monospace(9.0), visuals.text_color());
+            let on_left = r.center().x > rect.center().x;
+            let anchor = if on_left { r.left_center() - egui::vec2(galley.size().x + 2.0, 0.0) } else { r.right_center() + egui::vec2(2.0, 0.0) };
+            let label_rect = egui::Rect::from_min_size(anchor - egui::vec2(0.0, galley.size().y / 2.0), galley.size());
+            let fill = if is_selected { visuals.selection.bg_fill } else { visuals.extreme_bg_color.gamma_multiply(0.8) };
+            painter.rect_filled(label_rect.expand(1.0), 2.0, fill);
+            painter.galley(label_rect.min, galley, visuals.text_color());
+        }
+
+        if response.clicked() {
+            selected = match (&hovered, &selected) {
+                (Some(pin), Some(current)) if pin == current => None,
+                (Some(pin), _) => Some(pin.clone()),
+                (None, _) => None,
+            };
+            ui.data_mut(|data| data.insert_temp(id, selected.clone()));
+        }
+        if let Some(name) = &selected {
+            self.show_pin(ui, name);
+        }
+        match hovered {
+            Some(name) if selected.as_ref() != Some(&name) => response.on_hover_text(format!("{} (click for details)", name)),
+            _ => response,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Title: Iron Coder Board Module - Examples
+//! Description: The example projects in a board's `examples` directory. An example can
+//!   describe itself in an `example.toml` next to its Cargo.toml, with a title, what it
+//!   shows and the features it needs; one without is shown by its directory name.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+use crate::paths;
+
+pub const MANIFEST_FILE_NAME: &str = "example.toml";
+
+/// What an example's `example.toml` says about it, i.e.
+/// ```toml
+/// title = "NeoPixel rainbow"
+/// description = "Cycles the on-board NeoPixel through the colors of the rainbow."
+/// required_features = ["neopixel"]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExampleManifest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// The features the example has to be built with
+    pub required_features: Vec<String>,
+}
+
+/// One of a board's examples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Example {
+    pub path: PathBuf,
+    pub manifest: Option<ExampleManifest>,
+}
+
+impl Example {
+
+    /// Read an example's manifest, if it has one. A manifest that can't be read is
+    /// reported, and the example is shown as if it had none.
+    pub fn load(path: PathBuf) -> Self {
+        let manifest_path = path.join(MANIFEST_FILE_NAME);
+        let manifest = match fs::read_to_string(&manifest_path) {
+            Ok(manifest) => toml::from_str(&manifest)
+                .map_err(|e| warn!("couldn't read example manifest {}: {}", manifest_path.display(), e))
+                .ok(),
+            Err(_) => None,
+        };
+        Self { path, manifest }
+    }
+
+    /// The example's directory name, which quick start steps refer to it by.
+    pub fn name(&self) -> String {
+        paths::display_name(&self.path).to_string()
+    }
+
+    /// The title from the manifest, or else the directory name.
+    pub fn title(&self) -> String {
+        self.manifest.as_ref()
+            .and_then(|m| m.title.as_deref())
+            .filter(|t| !t.trim().is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| self.name())
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.manifest.as_ref()?.description.as_deref().filter(|d| !d.trim().is_empty())
+    }
+
+    pub fn required_features(&self) -> &[String] {
+        self.manifest.as_ref().map(|m| m.required_features.as_slice()).unwrap_or_default()
+    }
+}
+
+/// The examples in a board's `examples` directory, by title.
+pub fn find_examples(board_file: &Path) -> Vec<Example> {
+    let examples_dir = board_file.parent().and_then(|p| paths::canonicalize(&p.join("examples")).ok());
+    let Some(Ok(entries)) = examples_dir.map(fs::read_dir) else {
+        return vec![];
+    };
+    let mut examples: Vec<Example> = entries.flatten().map(|e| Example::load(e.path())).collect();
+    examples.sort_by_key(|e| e.title().to_lowercase());
+    examples
+}
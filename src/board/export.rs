@@ -0,0 +1,171 @@
+//! Title: Iron Coder Board Module - Board Export
+//! Description: The board database as JSON, for scripts, documentation generators and
+//!   other tools. It can be written out once with `--export-boards json`, or served on
+//!   a local HTTP endpoint with `--serve-boards <port>` while the app runs.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+
+/// Bumped when the shape of the export changes in a way that breaks its readers.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The formats the boards can be exported in.
+#[derive(clap::ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+}
+
+/// A board as it's exported: everything in its toml file, and what was found next to it.
+#[derive(Serialize)]
+struct BoardRecord<'a> {
+    #[serde(flatten)]
+    board: &'a Board,
+    /// The names of the board's examples
+    examples: Vec<String>,
+    has_image: bool,
+}
+
+impl<'a> BoardRecord<'a> {
+    fn new(board: &'a Board) -> Self {
+        let mut examples: Vec<String> = board.examples().iter().map(|e| e.name()).collect();
+        examples.sort();
+        Self { board, examples, has_image: board.svg_board_info.is_some() }
+    }
+}
+
+#[derive(Serialize)]
+struct BoardDatabase<'a> {
+    format_version: u32,
+    boards: Vec<BoardRecord<'a>>,
+}
+
+/// The boards as a JSON document, sorted by manufacturer and name.
+pub fn to_json(boards: &[Board]) -> serde_json::Result<String> {
+    let mut boards: Vec<&Board> = boards.iter().collect();
+    boards.sort_by(|a, b| (a.manufacturer(), a.get_name()).cmp(&(b.manufacturer(), b.get_name())));
+    let database = BoardDatabase {
+        format_version: FORMAT_VERSION,
+        boards: boards.into_iter().map(BoardRecord::new).collect(),
+    };
+    serde_json::to_string_pretty(&database)
+}
+
+/// One board as a JSON document.
+pub fn board_to_json(board: &Board) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&BoardRecord::new(board))
+}
+
+/// Undo the %-escapes of a url path, i.e. "Feather%20RP2040".
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The boards rendered for serving. Boards can't be sent to another thread, so their
+/// JSON is made up front.
+pub struct BoardPages {
+    database: String,
+    boards: BTreeMap<String, String>,
+}
+
+impl BoardPages {
+    pub fn new(boards: &[Board]) -> serde_json::Result<Self> {
+        let database = to_json(boards)?;
+        let boards = boards.iter()
+            .map(|board| Ok((board.key(), board_to_json(board)?)))
+            .collect::<serde_json::Result<_>>()?;
+        Ok(Self { database, boards })
+    }
+
+    /// The status, content type and body of the answer to a request. `/boards` is the
+    /// whole database, and `/boards/<manufacturer>/<name>[/<revision>]` is one board.
+    pub fn respond(&self, method: &str, path: &str) -> (&'static str, &'static str, String) {
+        const JSON: &str = "application/json";
+        const TEXT: &str = "text/plain; charset=utf-8";
+        if method != "GET" {
+            return ("405 Method Not Allowed", TEXT, "only GET is supported\n".to_string());
+        }
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        if path == "/boards" {
+            return ("200 OK", JSON, self.database.clone());
+        }
+        // the segments are decoded one by one, so an escaped '/' stays in its segment
+        let segments: Option<Vec<String>> = path.strip_prefix("/boards/")
+            .map(|route| route.split('/').map(percent_decode).collect());
+        let key = match segments.as_deref() {
+            Some([manufacturer, name]) => Board::identity_key(manufacturer, name, None),
+            Some([manufacturer, name, revision]) => Board::identity_key(manufacturer, name, Some(revision)),
+            _ => return ("404 Not Found", TEXT, "try /boards or /boards/<manufacturer>/<name>[/<revision>]\n".to_string()),
+        };
+        match self.boards.get(&key) {
+            Some(json) => ("200 OK", JSON, json.clone()),
+            None => ("404 Not Found", TEXT, format!("there's no board \"{}\"\n", key)),
+        }
+    }
+}
+
+/// Read a request from the stream and answer it.
+fn answer(mut stream: TcpStream, pages: &BoardPages) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.split_whitespace();
+    let (status, content_type, body) = pages.respond(words.next().unwrap_or_default(), words.next().unwrap_or_default());
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body)
+}
+
+/// Serve the boards on a local address from a thread of its own, for as long as the app
+/// runs. The boards are the ones given, so boards reloaded later aren't served. Returns
+/// the address that's listened on, which has the port picked if port 0 was asked for.
+pub fn serve(address: SocketAddr, boards: &[Board]) -> io::Result<SocketAddr> {
+    let pages = BoardPages::new(boards).map_err(io::Error::other)?;
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+    info!("serving {} boards on http://{}/boards", boards.len(), address);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                // a client hanging up early isn't our problem
+                Ok(stream) => {
+                    let _ = answer(stream, &pages);
+                },
+                Err(e) => warn!("error accepting a board request: {:?}", e),
+            }
+        }
+    });
+    Ok(address)
+}
@@ -0,0 +1,47 @@
+//! Title: Iron Coder Board Module - Board Favorites
+//! Description: The boards starred in the board selector, which are shown in a section of
+//!   their own at the top of it. They're kept in the app settings, so they're the same in
+//!   every project and across sessions.
+
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+
+/// The starred boards, by key, in the order they were starred. Boards starred before they
+/// were told apart by manufacturer are kept by label.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct BoardFavorites {
+    names: Vec<String>,
+}
+
+impl BoardFavorites {
+
+    pub fn is_favorite(&self, board: &Board) -> bool {
+        self.names.iter().any(|name| *name == board.key() || *name == board.label())
+    }
+
+    /// Star a board, or take its star away. Returns whether it's a favorite now.
+    pub fn toggle(&mut self, board: &Board) -> bool {
+        if self.is_favorite(board) {
+            self.names.retain(|name| *name != board.key() && *name != board.label());
+            return false;
+        }
+        self.names.push(board.key());
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Split the boards shown, as indices into `boards`, into the favorites and the rest,
+    /// keeping the order they're shown in.
+    pub fn split(&self, shown: &[usize], boards: &[Board]) -> (Vec<usize>, Vec<usize>) {
+        shown.iter().partition(|&&i| self.is_favorite(&boards[i]))
+    }
+}
@@ -0,0 +1,160 @@
+//! Title: Iron Coder Board Module - Board Filter
+//! Description: Narrowing the boards in the board selector down by name, manufacturer,
+//!   CPU, form factor, tags and the RAM and flash a project needs, so a boards directory
+//!   with dozens of boards stays usable. The choices offered are taken from the boards
+//!   themselves, with the tags shown as a cloud to browse by.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::board::{Board, BoardStandards};
+use crate::board::units::Memory;
+
+/// What the boards in the selector are narrowed down to. Empty fields match every board.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoardFilter {
+    /// Text to find in the board name or revision, in any case
+    pub name: String,
+    pub manufacturer: Option<String>,
+    pub cpu: Option<String>,
+    pub standard: Option<BoardStandards>,
+    /// The tags the board needs to have, all of them
+    pub tags: BTreeSet<String>,
+    /// The least RAM the board needs, i.e. "64 KiB" or "≥ 64 KiB"
+    pub min_ram: String,
+    /// The least flash the board needs
+    pub min_flash: String,
+}
+
+/// Whether a tag is lowercase letters, digits and dashes, i.e. "low-power".
+pub fn valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && !tag.starts_with('-') && !tag.ends_with('-')
+        && tag.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// How many boards have each tag.
+pub fn tag_counts(boards: &[Board]) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for tag in boards.iter().flat_map(|b| b.tags()) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    counts
+}
+
+/// Read a minimum amount of memory. Empty means there's no minimum.
+pub fn parse_minimum(text: &str) -> Result<Option<Memory>, String> {
+    let text = text.trim().trim_start_matches(['≥', '>', '=']).trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    text.parse().map(Some)
+}
+
+/// Whether a board has at least the memory asked for. A board that doesn't say how much it
+/// has doesn't match, and a minimum that can't be read matches every board.
+fn has_at_least(available: Option<Memory>, minimum: &str) -> bool {
+    match parse_minimum(minimum) {
+        Ok(Some(minimum)) => available.is_some_and(|a| a >= minimum),
+        _ => true,
+    }
+}
+
+impl BoardFilter {
+
+    pub fn is_empty(&self) -> bool {
+        self.name.trim().is_empty() && self.manufacturer.is_none() && self.cpu.is_none() && self.standard.is_none() && self.tags.is_empty()
+            && self.min_ram.trim().is_empty() && self.min_flash.trim().is_empty()
+    }
+
+    pub fn matches(&self, board: &Board) -> bool {
+        let name = self.name.trim().to_lowercase();
+        (name.is_empty() || board.label().to_lowercase().contains(&name))
+            && self.manufacturer.as_ref().is_none_or(|m| board.manufacturer() == m)
+            && self.cpu.as_ref().is_none_or(|c| board.cpu() == Some(c.as_str()))
+            && self.standard.as_ref().is_none_or(|s| board.standard() == Some(s))
+            && self.tags.iter().all(|t| board.tags().contains(t))
+            && has_at_least(board.ram(), &self.min_ram)
+            && has_at_least(board.flash(), &self.min_flash)
+    }
+
+    /// The indices of the boards that match, in order.
+    pub fn apply(&self, boards: &[Board]) -> Vec<usize> {
+        boards.iter().enumerate().filter(|(_, b)| self.matches(b)).map(|(i, _)| i).collect()
+    }
+
+    /// Pick a tag to narrow the boards down to, or take it away if it's picked.
+    pub fn toggle_tag(&mut self, tag: &str) {
+        if !self.tags.remove(tag) {
+            self.tags.insert(tag.to_string());
+        }
+    }
+
+    /// Show the boards' tags as a cloud, each sized by how many boards have it, to pick
+    /// from. A picked tag is highlighted.
+    pub fn display_tags(&mut self, ui: &mut egui::Ui, boards: &[Board]) {
+        let counts = tag_counts(boards);
+        let Some(most) = counts.values().copied().max() else {
+            return;
+        };
+        let size = ui.style().text_styles.get(&egui::TextStyle::Body).map_or(14.0, |f| f.size);
+        let mut clicked = None;
+        ui.horizontal_wrapped(|ui| {
+            for (tag, count) in counts.iter() {
+                let scale = if most > 1 { (count - 1) as f32 / (most - 1) as f32 } else { 0.0 };
+                let text = egui::RichText::new(*tag).size(size * (0.85 + 0.6 * scale));
+                if ui.selectable_label(self.tags.contains(*tag), text).on_hover_text(format!("{} boards", count)).clicked() {
+                    clicked = Some(tag.to_string());
+                }
+            }
+        });
+        if let Some(tag) = clicked {
+            self.toggle_tag(&tag);
+        }
+    }
+
+    /// Show the filter above a list of boards. The choices are the ones the boards have.
+    pub fn display(&mut self, ui: &mut egui::Ui, boards: &[Board]) {
+        let manufacturers: BTreeSet<&str> = boards.iter().map(Board::manufacturer).filter(|m| !m.is_empty()).collect();
+        let cpus: BTreeSet<&str> = boards.iter().filter_map(Board::cpu).collect();
+        ui.horizontal_wrapped(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.name).hint_text("search boards").desired_width(140.0));
+            choice(ui, "manufacturer", &mut self.manufacturer, manufacturers.into_iter().map(str::to_string));
+            choice(ui, "cpu", &mut self.cpu, cpus.into_iter().map(str::to_string));
+            choice(ui, "form factor", &mut self.standard, enum_iterator::all::<BoardStandards>());
+            minimum(ui, "RAM", &mut self.min_ram);
+            minimum(ui, "flash", &mut self.min_flash);
+            if ui.add_enabled(!self.is_empty(), egui::Button::new("clear")).clicked() {
+                *self = Self::default();
+            }
+        });
+        if !tag_counts(boards).is_empty() {
+            egui::CollapsingHeader::new(match self.tags.len() {
+                0 => "tags".to_string(),
+                n => format!("tags ({} picked)", n),
+            }).id_source("board_filter_tags").show(ui, |ui| {
+                self.display_tags(ui, boards);
+            });
+        }
+    }
+}
+
+/// A text field for a minimum amount of memory, marked when it can't be read.
+fn minimum(ui: &mut egui::Ui, label: &str, text: &mut String) {
+    let response = ui.add(egui::TextEdit::singleline(text).hint_text(format!("≥ {}", label)).desired_width(70.0));
+    if let Err(e) = parse_minimum(text) {
+        ui.colored_label(ui.visuals().error_fg_color, "⚠").on_hover_text(e);
+    } else {
+        response.on_hover_text(format!("the least {} the board needs, i.e. 256 KB", label));
+    }
+}
+
+/// A combo box for one field of the filter, with "any" for no choice.
+fn choice<T: Clone + PartialEq + ToString>(ui: &mut egui::Ui, label: &str, value: &mut Option<T>, options: impl Iterator<Item = T>) {
+    let selected = value.as_ref().map_or(format!("any {}", label), T::to_string);
+    egui::ComboBox::from_id_source(label).selected_text(selected).show_ui(ui, |ui| {
+        ui.selectable_value(value, None, format!("any {}", label));
+        for option in options {
+            let text = option.to_string();
+            ui.selectable_value(value, Some(option), text);
+        }
+    });
+}
@@ -0,0 +1,90 @@
+//! Title: Iron Coder Board Module - Fitting Boards
+//! Description: Which boards fit together because they're made to the same standard,
+//!   i.e. a FeatherWing stacks on any Feather, and a MicroMod processor board plugs into
+//!   any MicroMod carrier. The board selector suggests the boards that fit the ones
+//!   already in the project.
+
+use std::fmt;
+
+use crate::board::{Board, BoardKind, BoardStandards};
+
+/// How two boards go together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fit {
+    /// An add-on stacks on the main board's header, i.e. a FeatherWing on a Feather
+    Stacks,
+    /// The main board plugs into the add-on, i.e. a MicroMod processor into a carrier
+    PlugsIn,
+    /// The boards are made to different standards with the same footprint, so they fit,
+    /// but the pins should be checked
+    SameFootprint,
+}
+
+impl fmt::Display for Fit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stacks => write!(f, "stacks"),
+            Self::PlugsIn => write!(f, "plugs in"),
+            Self::SameFootprint => write!(f, "same footprint"),
+        }
+    }
+}
+
+/// Why a main board and an add-on go together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub fit: Fit,
+    pub reason: String,
+}
+
+/// Whether two standards have the same footprint and pinout, going by the header.
+fn same_footprint(a: &BoardStandards, b: &BoardStandards) -> bool {
+    use BoardStandards::*;
+    matches!((a, b), (Feather, ThingPlus) | (ThingPlus, Feather))
+}
+
+/// How an add-on goes with a main board, going by their standards. `None` if they don't
+/// go together, or either doesn't say what it's made to.
+pub fn check(main: &Board, add_on: &Board) -> Option<Match> {
+    if main.kind() != BoardKind::MainBoard || add_on.kind() == BoardKind::MainBoard {
+        return None;
+    }
+    let (Some(main_standard), Some(add_on_standard)) = (main.standard(), add_on.standard()) else {
+        return None;
+    };
+    if main_standard == add_on_standard {
+        let (fit, reason) = match main_standard {
+            BoardStandards::MicroMod => (Fit::PlugsIn, "MicroMod processor boards plug into any MicroMod carrier board".to_string()),
+            standard => (Fit::Stacks, format!("{}s fit the {} of any {} board", standard.add_on_name(), standard.connector(), standard)),
+        };
+        return Some(Match { fit, reason });
+    }
+    if same_footprint(main_standard, add_on_standard) {
+        let reason = format!("{} boards have the footprint of {} boards, but check the pins {}s use",
+                             main_standard, add_on_standard, add_on_standard.add_on_name());
+        return Some(Match { fit: Fit::SameFootprint, reason });
+    }
+    None
+}
+
+/// The boards that fit the project, as indices into `candidates` with why. With a main
+/// board, they're the add-ons that go with it; without one, the main boards that go with
+/// every add-on in the project that says what standard it's made to. Boards already in
+/// the project aren't suggested.
+pub fn suggestions(main: Option<&Board>, add_ons: &[Board], candidates: &[Board]) -> Vec<(usize, Match)> {
+    let in_project = |board: &Board| main == Some(board) || add_ons.contains(board);
+    candidates.iter().enumerate().filter(|(_, c)| !in_project(c)).filter_map(|(i, candidate)| {
+        let found = match main {
+            Some(main) => check(main, candidate),
+            None => {
+                let standard_add_ons: Vec<&Board> = add_ons.iter().filter(|a| a.standard().is_some()).collect();
+                let matches: Vec<Match> = standard_add_ons.iter().filter_map(|a| check(candidate, a)).collect();
+                // the least certain fit is the one that counts
+                (!standard_add_ons.is_empty() && matches.len() == standard_add_ons.len())
+                    .then(|| matches.into_iter().max_by_key(|m| m.fit == Fit::SameFootprint))
+                    .flatten()
+            },
+        };
+        found.map(|m| (i, m))
+    }).collect()
+}
@@ -0,0 +1,127 @@
+//! Title: Iron Coder Board Module - Flash Layout
+//! Description: The partitioning of a board's flash for a bootloader with image slots
+//!   (i.e. MCUboot's primary/secondary A/B slots). Boards that declare a layout get
+//!   slot-aware flashing and a flash map in the board view.
+
+use serde::{Serialize, Deserialize};
+
+use super::units::Memory;
+
+/// A region of flash, relative to the start of flash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FlashRegion {
+    #[serde(default)]
+    pub name: String,
+    pub offset: Memory,
+    pub size: Memory,
+}
+
+impl FlashRegion {
+    pub fn end(&self) -> u64 {
+        self.offset.bytes().saturating_add(self.size.bytes())
+    }
+}
+
+/// The flash layout of a board with a slot-based bootloader, i.e.
+/// ```toml
+/// [flash_layout]
+/// base = 0x0
+/// chip = "nRF52832_xxAA"
+/// bootloader = { offset = "0 B", size = "48 KiB" }
+/// slots = [
+///     { name = "primary", offset = "48 KiB", size = "224 KiB" },
+///     { name = "secondary", offset = "272 KiB", size = "224 KiB" },
+/// ]
+/// ```
+/// Firmware is always linked to run from the first slot. Writing an image to another slot
+/// has the bootloader swap it in on the next reset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FlashLayout {
+    /// The address flash is mapped at
+    #[serde(default)]
+    pub base: u64,
+    /// The probe-rs name of the chip, used to flash slot images
+    pub chip: Option<String>,
+    pub bootloader: Option<FlashRegion>,
+    pub slots: Vec<FlashRegion>,
+    pub scratch: Option<FlashRegion>,
+    /// The size of the image header the bootloader expects at the start of a slot
+    #[serde(default = "default_header_size")]
+    pub header_size: Memory,
+}
+
+fn default_header_size() -> Memory {
+    Memory::from_bytes(0x200)
+}
+
+/// Space at the end of a slot kept free for the bootloader's trailer (swap status,
+/// image_ok flag and magic).
+pub const MIN_TRAILER_SIZE: u64 = 0x1000;
+
+impl FlashLayout {
+
+    /// Find a slot by name.
+    pub fn slot(&self, name: &str) -> Option<&FlashRegion> {
+        self.slots.iter().find(|s| s.name == name)
+    }
+
+    /// The slot that firmware is linked for.
+    pub fn primary(&self) -> Option<&FlashRegion> {
+        self.slots.first()
+    }
+
+    /// The absolute address of a region.
+    pub fn address(&self, region: &FlashRegion) -> u64 {
+        self.base.saturating_add(region.offset.bytes())
+    }
+
+    /// The name to show for a region. The bootloader and scratch regions don't need one.
+    pub fn region_name<'a>(&self, region: &'a FlashRegion) -> &'a str {
+        if !region.name.is_empty() {
+            &region.name
+        } else if self.scratch.as_ref().is_some_and(|s| std::ptr::eq(s, region)) {
+            "scratch"
+        } else {
+            "bootloader"
+        }
+    }
+
+    /// All of the regions, in flash order.
+    pub fn regions(&self) -> Vec<&FlashRegion> {
+        let mut regions: Vec<&FlashRegion> = self.bootloader.iter().chain(self.slots.iter()).chain(self.scratch.iter()).collect();
+        regions.sort_by_key(|r| r.offset);
+        regions
+    }
+
+    /// Check that the layout makes sense, and fits in `flash` if the board's flash size is known.
+    pub fn validate(&self, flash: Option<Memory>) -> Result<(), String> {
+        if self.slots.is_empty() {
+            return Err("flash layout needs at least one slot".to_string());
+        }
+        for (i, slot) in self.slots.iter().enumerate() {
+            if slot.name.trim().is_empty() || self.slots[..i].iter().any(|s| s.name == slot.name) {
+                return Err(format!("flash slot {:?} needs a unique name", slot.name));
+            }
+            if slot.size.bytes() <= self.header_size.bytes().saturating_add(MIN_TRAILER_SIZE) {
+                return Err(format!("flash slot {} is too small for an image", slot.name));
+            }
+        }
+        let regions = self.regions();
+        for pair in regions.windows(2) {
+            if pair[0].end() > pair[1].offset.bytes() {
+                return Err(format!("flash regions {} and {} overlap", self.region_name(pair[0]), self.region_name(pair[1])));
+            }
+        }
+        if let (Some(flash), Some(last)) = (flash, regions.last()) {
+            if last.end() > flash.bytes() {
+                return Err(format!("flash region {} runs past the end of flash", self.region_name(last)));
+            }
+        }
+        if let Some(chip) = &self.chip {
+            if chip.is_empty() || chip.chars().any(char::is_whitespace) {
+                return Err("flash layout chip must be a single word".to_string());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+//! Title: Iron Coder Board Module - Flashing
+//! Description: How firmware gets onto a board: over a debug probe with probe-rs, by
+//!   copying a UF2 file to the board's USB drive, or through a bootloader on the chip.
+//!   With the chip's name and target triple from the board file, the tools that build,
+//!   load and debug the firmware know what to do for each board.
+//!
+//! ```toml
+//! target_triple = "thumbv6m-none-eabi"
+//! chip_name = "RP2040"
+//! flash_method = "probe-rs"
+//! ```
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+/// How firmware is loaded onto a board.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlashMethod {
+    /// Over SWD or JTAG with a debug probe, which can also debug the firmware
+    ProbeRs,
+    /// Copied as a UF2 file to the drive the board's bootloader shows over USB
+    Uf2,
+    /// Over the serial port to the ROM bootloader of an ESP chip
+    Espflash,
+    /// Over USB to a DFU bootloader
+    DfuUtil,
+    /// Over USB to the SAM-BA bootloader of a SAMD chip
+    Bossac,
+}
+
+impl fmt::Display for FlashMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProbeRs => write!(f, "probe-rs"),
+            Self::Uf2 => write!(f, "UF2"),
+            Self::Espflash => write!(f, "espflash"),
+            Self::DfuUtil => write!(f, "dfu-util"),
+            Self::Bossac => write!(f, "bossac"),
+        }
+    }
+}
+
+impl FlashMethod {
+
+    /// Whether the chip's name has to be known to flash it this way.
+    pub fn needs_chip(&self) -> bool {
+        matches!(self, Self::ProbeRs)
+    }
+
+    /// Whether the firmware can be debugged with the same connection it's flashed over.
+    pub fn can_debug(&self) -> bool {
+        matches!(self, Self::ProbeRs)
+    }
+
+    /// The cargo runner that loads the firmware onto the board, for `cargo run`. `None`
+    /// when the firmware has to be turned into another format first.
+    pub fn runner(&self, chip: Option<&str>) -> Option<String> {
+        match self {
+            Self::ProbeRs => Some(match chip {
+                Some(chip) => format!("probe-rs run --chip {}", chip),
+                None => "probe-rs run".to_string(),
+            }),
+            Self::Uf2 => Some("elf2uf2-rs -d".to_string()),
+            Self::Espflash => Some("espflash flash --monitor".to_string()),
+            Self::DfuUtil | Self::Bossac => None,
+        }
+    }
+}
+
+/// Whether a target triple looks like one, i.e. `thumbv7em-none-eabihf`: at least three
+/// parts between dashes, with nothing but letters, digits, dots and underscores in them.
+pub fn valid_target_triple(triple: &str) -> bool {
+    let parts: Vec<&str> = triple.split('-').collect();
+    parts.len() >= 3 && parts.iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+}
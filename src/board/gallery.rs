@@ -0,0 +1,81 @@
+//! Title: Iron Coder Board Module - Image Gallery
+//! Description: The other pictures of a board, i.e. its top, bottom or pinout diagram,
+//!   found next to its board file as `<name>.<view>.png`. They're shown after the board's
+//!   own image in a small gallery, and decoded the first time they're looked at.
+
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+
+use crate::app::texture_cache;
+use crate::board::Board;
+use super::svg_reader::BoardImage;
+
+/// What the board's own image, from its SVG, is called in the gallery.
+pub const MAIN_VIEW: &str = "board";
+
+/// A picture of a board, i.e. its "bottom" from `feather_rp2040.bottom.png`.
+#[derive(Clone)]
+pub struct GalleryImage {
+    pub view: String,
+    pub image: BoardImage,
+}
+
+/// The pictures next to a board file, by the name of their view. A file called like the
+/// board file, with a view and `.png` after it, is one, i.e. `feather_rp2040.top.png`.
+pub fn find_images(board_file: &Path) -> Vec<GalleryImage> {
+    let (Some(dir), Some(stem)) = (board_file.parent(), board_file.file_stem().and_then(|s| s.to_str())) else {
+        return vec![];
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut images: Vec<GalleryImage> = entries.flatten().filter_map(|entry| {
+        let file_name = entry.file_name().to_str()?.to_string();
+        let view = file_name.strip_prefix(stem)?.strip_prefix('.')?.strip_suffix(".png")?;
+        if view.is_empty() || view.contains('.') || view == MAIN_VIEW {
+            return None;
+        }
+        match fs::read(entry.path()) {
+            Ok(png) => Some(GalleryImage { view: view.to_string(), image: BoardImage::from_png(png) }),
+            Err(e) => {
+                warn!("couldn't read board image {}: {:?}", entry.path().display(), e);
+                None
+            },
+        }
+    }).collect();
+    images.sort_by(|a, b| a.view.cmp(&b.view));
+    images
+}
+
+/// Show one of a board's pictures, with buttons to go through the others. Which one is
+/// shown is kept for each board while the app runs.
+pub fn display(ui: &mut egui::Ui, board: &Board, max_size: egui::Vec2) {
+    let views = board.image_views();
+    if views.is_empty() {
+        return;
+    }
+    let id = egui::Id::new(("board gallery", board.key()));
+    let mut shown = ui.data(|d| d.get_temp::<usize>(id)).unwrap_or(0).min(views.len() - 1);
+    match texture_cache::gallery_texture(ui.ctx(), board, shown) {
+        Some(handle) => {
+            ui.add(egui::Image::from_texture(&handle).max_size(max_size));
+        },
+        None => {
+            ui.spinner();
+        },
+    }
+    if views.len() > 1 {
+        ui.horizontal(|ui| {
+            if ui.small_button("◀").clicked() {
+                shown = (shown + views.len() - 1) % views.len();
+            }
+            ui.label(format!("{} ({} of {})", views[shown], shown + 1, views.len()));
+            if ui.small_button("▶").clicked() {
+                shown = (shown + 1) % views.len();
+            }
+        });
+        ui.data_mut(|d| d.insert_temp(id, shown));
+    }
+}
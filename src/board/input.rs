@@ -0,0 +1,38 @@
+//! Title: Iron Coder Board Module - Input Devices
+//! Description: The input devices a peripheral board puts in front of the user, i.e. a
+//!   touch screen or a rotary encoder. With this the input simulator knows what to draw
+//!   for a board and the range of the events it sends.
+
+use serde::{Serialize, Deserialize};
+
+/// An input device on a board, i.e.
+/// ```toml
+/// input = { kind = "touch", width = 320, height = 240 }
+/// input = { kind = "encoder", steps_per_turn = 20, button = true }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputDevice {
+    /// A touch screen, measured in the pixels of the display under it
+    Touch { width: u16, height: u16 },
+    /// A rotary encoder, with the detents it steps through in a turn
+    Encoder {
+        steps_per_turn: u16,
+        /// Whether the knob can be pushed in as a button
+        #[serde(default)]
+        button: bool,
+    },
+}
+
+impl InputDevice {
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            InputDevice::Touch { width, height } if *width == 0 || *height == 0 =>
+                Err("a touch screen must have a width and height".to_string()),
+            InputDevice::Encoder { steps_per_turn: 0, .. } =>
+                Err("an encoder must have at least one step per turn".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
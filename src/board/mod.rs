@@ -2,14 +2,18 @@
 
 use log::{warn, info, debug};
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs;
 use std::vec::Vec;
 use std::fmt;
 use std::cmp;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 use serde::{Serialize, Deserialize};
+use enum_iterator::Sequence;
+
+use crate::paths;
 
 use ra_ap_ide;
 
@@ -19,16 +23,86 @@ use svg_reader::SvgBoardInfo;
 pub mod display;
 
 pub mod pinout;
-use pinout::Pinout;
+use pinout::{InterfaceType, PinDefinition, Pinout};
 
 pub mod parsing;
 mod test;
 
+pub mod units;
+use units::{Current, Frequency, Memory, Voltage};
+
+pub mod config;
+use config::ConfigOption;
+
+pub mod flash_layout;
+use flash_layout::FlashLayout;
+
+pub mod pwm;
+use pwm::PwmTimers;
+
+pub mod watchdog;
+use watchdog::{BrownOut, Watchdog};
+
+pub mod rtc;
+use rtc::Rtc;
+
+pub mod partitions;
+use partitions::PartitionTable;
+
+pub mod sleep;
+use sleep::SleepModes;
+
+pub mod input;
+use input::InputDevice;
+
+pub mod filter;
+
+pub mod compare;
+
+pub mod favorites;
+
+pub mod sorting;
+
+pub mod peripherals;
+use peripherals::Peripherals;
+
+pub mod wizard;
+
+pub mod diagnostics;
+use diagnostics::{BoardDiagnostic, BoardLoadReport};
+
+pub mod export;
+
+pub mod bundle;
+
+pub mod registry;
+
+pub mod schematic;
+
+pub mod quickstart;
+use quickstart::QuickStart;
+
+pub mod svd;
+use svd::LazySvd;
+
+pub mod gallery;
+use gallery::GalleryImage;
+
+pub mod examples;
+use examples::Example;
+
+pub mod fitting;
+
+pub mod watcher;
+
+pub mod flashing;
+use flashing::FlashMethod;
+
 use parsing::BspParseInfo;
 
 /// These are the various standard development board form factors
 #[non_exhaustive]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Sequence)]
 pub enum BoardStandards {
     Feather,
     Arduino,
@@ -50,29 +124,161 @@ impl fmt::Display for BoardStandards {
     }
 }
 
+impl BoardStandards {
+
+    /// The connector the board's pins are on.
+    pub fn connector(&self) -> &'static str {
+        match self {
+            BoardStandards::Feather => "Feather header",
+            BoardStandards::Arduino => "Arduino header",
+            BoardStandards::RaspberryPi => "40-pin GPIO header",
+            BoardStandards::ThingPlus => "Thing Plus header",
+            BoardStandards::MicroMod => "MicroMod M.2 edge connector",
+        }
+    }
+
+    /// What the boards that go with a main board of the standard are called.
+    pub fn add_on_name(&self) -> &'static str {
+        match self {
+            BoardStandards::Feather => "FeatherWing",
+            BoardStandards::Arduino => "shield",
+            BoardStandards::RaspberryPi => "HAT",
+            BoardStandards::ThingPlus => "FeatherWing",
+            BoardStandards::MicroMod => "carrier board",
+        }
+    }
+}
+
+/// What a board is for: running the project's code, or adding something to a board that
+/// does. The board selector shows each kind in a section of its own.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardKind {
+    /// A development board with a processor that runs the project's code
+    MainBoard,
+    /// A breakout with a sensor, radio or other part on it
+    #[serde(alias = "breakout")]
+    Peripheral,
+    /// A screen, with or without touch
+    Display,
+}
+
+impl fmt::Display for BoardKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardKind::MainBoard => write!(f, "main board"),
+            BoardKind::Peripheral => write!(f, "peripheral"),
+            BoardKind::Display => write!(f, "display"),
+        }
+    }
+}
+
+impl BoardKind {
+
+    /// How the kind is written in a board file.
+    pub fn key(&self) -> &'static str {
+        match self {
+            BoardKind::MainBoard => "main_board",
+            BoardKind::Peripheral => "peripheral",
+            BoardKind::Display => "display",
+        }
+    }
+
+    /// The heading of the kind's section of the board selector.
+    pub fn heading(&self) -> &'static str {
+        match self {
+            BoardKind::MainBoard => "Main boards",
+            BoardKind::Peripheral => "Peripherals",
+            BoardKind::Display => "Displays",
+        }
+    }
+}
+
+/// The analog-to-digital converter of a board's chip, i.e.
+/// `adc = { resolution = 12, reference = 3.3 }`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Adc {
+    /// The number of bits in a conversion
+    pub resolution: u32,
+    /// The voltage of a full-scale reading, in volts
+    pub reference: f64,
+}
+
+/// The ways that loading a board from its toml description can fail
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum BoardLoadError {
+    FsError(std::io::Error),
+    TomlError(toml::de::Error),
+    JsonError(serde_json::Error),
+    /// A field is present but out of bounds, i.e. an empty name
+    InvalidField(String),
+    /// A pin label is empty, contains whitespace, or is repeated within an interface
+    InvalidPin(String),
+    SvgError,
+}
+
 /// The board struct defines a board type
 #[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct Board {
     /// The name of the board
     name: String,
+    /// Which revision of the board this is, i.e. "rev B", for boards that have had more
+    /// than one. Also read from `version`.
+    #[serde(alias = "version", skip_serializing_if = "Option::is_none")]
+    revision: Option<String>,
     /// The board manufacturer
     manufacturer: String,
-    /// Whether or not the board has a processor that can run code
-    is_main_board: bool,
+    /// What the board is for. Boards described before there were kinds say whether they
+    /// have a processor that can run code with `is_main_board` instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<BoardKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_main_board: Option<bool>,
     /// A possible form factor that the board adheres to
     standard: Option<BoardStandards>,
     cpu: Option<String>,
-    ram: Option<isize>,
-    flash: Option<isize>,
+    /// The maximum clock speed of the cpu
+    clock: Option<Frequency>,
+    /// The clock that drives the PWM timers, if it isn't the cpu clock
+    timer_clock: Option<Frequency>,
+    /// The chip's PWM timers, driven by the timer clock
+    pwm: Option<PwmTimers>,
+    /// The chip's watchdog and brown-out detector
+    watchdog: Option<Watchdog>,
+    brown_out: Option<BrownOut>,
+    /// The chip's real-time clock and what clocks it
+    rtc: Option<Rtc>,
+    /// The chip's low-power modes
+    sleep: Option<SleepModes>,
+    /// The touch screen or encoder the board has for the user
+    input: Option<InputDevice>,
+    adc: Option<Adc>,
+    /// How many UARTs, I2C and SPI buses and ADC channels the chip has, and its radios
+    peripherals: Option<Peripherals>,
+    ram: Option<Memory>,
+    flash: Option<Memory>,
+    /// The voltage the board runs from, i.e. the regulated rail most of its parts are on
+    operating_voltage: Option<Voltage>,
+    /// The high level of the board's logic signals, which what's wired to them has to match
+    logic_level: Option<Voltage>,
+    /// What the board typically draws while it's running
+    typical_current: Option<Current>,
     /// A list of the interfaces available on the board
     pub pinout: Pinout,
+    /// What each pin of the board can do
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pins: Vec<PinDefinition>,
     /// A list of the Syntax Nodes of the BSP calculated by Rust Analyzer
     #[serde(skip)]
     pub ra_values: Vec<ra_ap_ide::StructureNode>,
-    /// A list of examples
+    /// The toml file the board was loaded from
+    #[serde(skip)]
+    file: Option<PathBuf>,
+    /// The example projects next to the board file, with what their manifests say
     #[serde(skip)]
-    examples: Vec<PathBuf>,
+    examples: Vec<Example>,
     /// An local path of a project template
     #[serde(skip)]
     template_dir: Option<PathBuf>,
@@ -87,16 +293,59 @@ pub struct Board {
     /// Possible image loaded from an SVG file, along with size info and pin locations
     #[serde(skip)]
     pub svg_board_info: Option<SvgBoardInfo>,
+    /// Other pictures of the board, i.e. its bottom or a pinout diagram
+    #[serde(skip)]
+    gallery: Vec<GalleryImage>,
+    /// What the board has or is good for, i.e. "wifi" or "low-power", to browse boards by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
     /// A list of required crates
     required_crates: Option<Vec<String>>,
     /// A list of related, optional crates
     related_crates: Option<Vec<String>>,
+    /// The board's name in Zephyr, if it can be built with west
+    zephyr_board: Option<String>,
+    /// The chip's ESP-IDF target name, if it can be built with idf.py
+    esp_idf_target: Option<String>,
+    /// The crates.io BSP crate for the board, added to projects made for it
+    bsp_crate: Option<String>,
+    /// The HAL crate of the board's chip, which the starter code is written for
+    hal_crate: Option<String>,
+    /// The pin the board's user LED is on, which the starter code blinks
+    led: Option<String>,
+    /// What rustc builds the firmware for, i.e. "thumbv6m-none-eabi"
+    target_triple: Option<String>,
+    /// The chip's name as probe-rs knows it, i.e. "RP2040" or "nRF52840_xxAA"
+    chip_name: Option<String>,
+    /// How firmware is loaded onto the board
+    flash_method: Option<FlashMethod>,
+    /// The bootloader and image slots in flash, for boards with a slot-based bootloader
+    flash_layout: Option<FlashLayout>,
+    /// The data partitions in flash, i.e. where the firmware keeps its settings
+    partition_table: Option<PartitionTable>,
+    /// The compile-time configuration options of the board
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    config: Vec<ConfigOption>,
+    /// The steps to get the board going the first time
+    quickstart: Option<QuickStart>,
+    /// The chip's CMSIS-SVD file, relative to the board file
+    svd: Option<PathBuf>,
+    /// Where the datasheet of the board or its chip is online
+    datasheet_url: Option<String>,
+    /// Where the board's documentation is online, i.e. its guide on the manufacturer's site
+    docs_url: Option<String>,
+    /// A copy of the datasheet next to the board file, for reading it offline. It's kept
+    /// in the board's directory, so it goes along when the board is shared.
+    datasheet: Option<PathBuf>,
+    /// The chip's peripherals and registers, from the SVD file
+    #[serde(skip)]
+    svd_device: LazySvd,
 }
 
 impl fmt::Debug for Board {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Board {}\n", self.name)?;
-        write!(f, "  is main board? {}\n", self.is_main_board)?;
+        writeln!(f, "  kind: {}", self.kind())?;
         write!(f, "  num examples: {}\n", self.examples.len())?;
         write!(f, "  num required crates: {}\n", self.required_crates.clone().unwrap_or_default().len())?;
         write!(f, "  num related crates: {}\n", self.related_crates.clone().unwrap_or_default().len())?;
@@ -109,18 +358,22 @@ impl fmt::Debug for Board {
     }
 }
 
-/// Boards are uniquely identified by their name, and thus comparable.
+/// Boards are uniquely identified by their manufacturer, name and revision, so clones of a
+/// board by other manufacturers are told apart, and thus comparable.
 impl cmp::PartialEq for Board {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.manufacturer == other.manufacturer && self.name == other.name && self.revision == other.revision
     }
 }
 impl cmp::Eq for Board {}
 
-/// Boards are uniquely identified by their name, and thus hashable.
+/// Boards are uniquely identified by their manufacturer, name and revision, and thus
+/// hashable.
 impl Hash for Board {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        self.manufacturer.hash(state);
         self.name.hash(state);
+        self.revision.hash(state);
     }
 }
 
@@ -128,44 +381,184 @@ impl Hash for Board {
 /// information about them.
 impl Board {
 
-    /// Loads a board from its toml description
-    fn load_from_toml(path: &Path) -> std::io::Result<Self> {
-        
-        let toml_str = fs::read_to_string(path)?;
-        let mut b: Board = match toml::from_str(&toml_str) {
-            Ok(b) => b,
-            Err(_) => {
-                return Err(std::io::Error::other("load from toml failed"));
-            },
-        };
+    /// Parse and validate a board from the contents of its toml description. This never
+    /// touches the filesystem, and returns an error for any malformed input.
+    pub fn from_toml_str(toml_str: &str) -> Result<Self, BoardLoadError> {
+        let b: Board = toml::from_str(toml_str).map_err(BoardLoadError::TomlError)?;
+        b.validate()?;
+        Ok(b)
+    }
+
+    /// Parse and validate a board from JSON, either a board on its own or one as it's
+    /// exported by `to_json`.
+    pub fn from_json(json: &str) -> Result<Self, BoardLoadError> {
+        let b: Board = serde_json::from_str(json).map_err(BoardLoadError::JsonError)?;
+        b.validate()?;
+        Ok(b)
+    }
+
+    /// The board as JSON, with the names of its examples and whether it has an image.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        export::board_to_json(self)
+    }
+
+    /// Check the fields that serde can't check for us.
+    fn validate(&self) -> Result<(), BoardLoadError> {
+        if self.name.trim().is_empty() {
+            return Err(BoardLoadError::InvalidField("name can't be empty".to_string()));
+        }
+        if let (Some(kind), Some(is_main_board)) = (self.kind, self.is_main_board) {
+            if (kind == BoardKind::MainBoard) != is_main_board {
+                return Err(BoardLoadError::InvalidField(format!("kind is {}, but is_main_board is {}", kind, is_main_board)));
+            }
+        }
+        if self.revision.as_ref().is_some_and(|r| r.trim().is_empty()) {
+            return Err(BoardLoadError::InvalidField("revision can't be empty".to_string()));
+        }
+        for (field, value) in [("zephyr_board", &self.zephyr_board), ("esp_idf_target", &self.esp_idf_target), ("bsp_crate", &self.bsp_crate), ("hal_crate", &self.hal_crate), ("chip_name", &self.chip_name)] {
+            if value.as_ref().is_some_and(|v| v.is_empty() || v.chars().any(|c| c.is_whitespace() || c.is_control())) {
+                return Err(BoardLoadError::InvalidField(format!("{} must be a single word", field)));
+            }
+        }
+        if let Some(triple) = self.target_triple.as_ref().filter(|t| !flashing::valid_target_triple(t)) {
+            return Err(BoardLoadError::InvalidField(format!("target_triple {} isn't a target triple, i.e. thumbv7em-none-eabihf", triple)));
+        }
+        for (field, value) in [("datasheet_url", &self.datasheet_url), ("docs_url", &self.docs_url)] {
+            if value.as_ref().is_some_and(|url| !url.starts_with("https://") && !url.starts_with("http://")) {
+                return Err(BoardLoadError::InvalidField(format!("{} must be an http or https url", field)));
+            }
+        }
+        if let Some(datasheet) = &self.datasheet {
+            if !datasheet.components().all(|c| matches!(c, Component::Normal(_))) || datasheet.as_os_str().is_empty() {
+                return Err(BoardLoadError::InvalidField(format!("datasheet {} must be a path in the board's directory", datasheet.display())));
+            }
+        }
+        if let Some(method) = self.flash_method.filter(|m| m.needs_chip() && self.chip_name().is_none()) {
+            return Err(BoardLoadError::InvalidField(format!("flashing with {} needs the chip_name", method)));
+        }
+        for (i, tag) in self.tags.iter().enumerate() {
+            if !filter::valid_tag(tag) {
+                return Err(BoardLoadError::InvalidField(format!("tag \"{}\" should be lowercase letters, digits and dashes", tag)));
+            }
+            if self.tags[..i].contains(tag) {
+                return Err(BoardLoadError::InvalidField(format!("tag {} is given twice", tag)));
+            }
+        }
+        if let Some(led) = &self.led {
+            if !self.pins.iter().any(|p| p.name == *led) {
+                return Err(BoardLoadError::InvalidField(format!("led {} isn't one of the board's pins", led)));
+            }
+        }
+        for (field, voltage) in [("operating_voltage", self.operating_voltage), ("logic_level", self.logic_level)] {
+            if voltage.is_some_and(|v| v.millivolts() == 0) {
+                return Err(BoardLoadError::InvalidField(format!("{} must be a positive voltage", field)));
+            }
+        }
+        if let Some(adc) = &self.adc {
+            if adc.resolution == 0 || adc.resolution > 24 {
+                return Err(BoardLoadError::InvalidField(format!("adc resolution must be 1 to 24 bits (got {})", adc.resolution)));
+            }
+            if !(adc.reference.is_finite() && adc.reference > 0.0) {
+                return Err(BoardLoadError::InvalidField("adc reference must be a positive voltage".to_string()));
+            }
+        }
+        if let Some(pwm) = &self.pwm {
+            pwm.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(brown_out) = &self.brown_out {
+            brown_out.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(rtc) = &self.rtc {
+            rtc.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(sleep) = &self.sleep {
+            sleep.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(input) = &self.input {
+            input.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(quickstart) = &self.quickstart {
+            quickstart.validate().map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(layout) = &self.flash_layout {
+            layout.validate(self.flash).map_err(BoardLoadError::InvalidField)?;
+        }
+        if let Some(table) = &self.partition_table {
+            table.validate(self.flash, self.flash_layout.as_ref()).map_err(BoardLoadError::InvalidField)?;
+        }
+        for (i, option) in self.config.iter().enumerate() {
+            option.validate().map_err(BoardLoadError::InvalidField)?;
+            if self.config[..i].iter().any(|o| o.name == option.name) {
+                return Err(BoardLoadError::InvalidField(format!("config option {} is declared twice", option.name)));
+            }
+        }
+        for mapping in self.pinout.iter() {
+            for (i, pin) in mapping.pins.iter().enumerate() {
+                if pin.is_empty() || pin.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                    return Err(BoardLoadError::InvalidPin(pin.clone()));
+                }
+                if mapping.pins[..i].contains(pin) {
+                    return Err(BoardLoadError::InvalidPin(pin.clone()));
+                }
+            }
+        }
+        for (i, pin) in self.pins.iter().enumerate() {
+            if pin.name.is_empty() || pin.name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(BoardLoadError::InvalidPin(pin.name.clone()));
+            }
+            if self.pins[..i].iter().any(|p| p.name == pin.name) {
+                return Err(BoardLoadError::InvalidPin(pin.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a board from its toml description, read from `path`
+    fn load_from_toml(path: &Path, toml_str: &str) -> Result<Self, BoardLoadError> {
+
+        let mut b = Board::from_toml_str(toml_str)?;
+        b.file = Some(path.to_path_buf());
 
         // See if there is an image
         if let Ok(pic_path) = path.with_extension("svg").canonicalize() {
             // BASED ON SVG WORK
             match SvgBoardInfo::from_path(&pic_path) {
                 Ok(svg_board_info) => {
-                    info!("successfully decoded SVG for board {}. Board has physical size: {:?}", b.get_name(), svg_board_info.physical_size);
+                    info!("successfully parsed SVG for board {}. Board has physical size: {:?}", b.get_name(), svg_board_info.physical_size);
                     b.svg_board_info = Some(svg_board_info);
                 },
                 Err(e) => {
                     warn!("error with svg parsing! {:?}", e);
-                    return Err(std::io::Error::other("unable to parse board SVG file."));
+                    return Err(BoardLoadError::SvgError);
                 },
             };
         } else {
+            // the board can still be used, it just isn't drawn
             warn!("no svg file for board {}", b.get_name());
-            return Err(std::io::Error::other("no SVG file for board."));
-
         }
 
-        // See if there are any examples
-        if let Ok(examples_path) = path.parent().unwrap().join("examples").canonicalize() {
-            for (_i, e) in examples_path.read_dir().unwrap().enumerate() {
-                let example_path = e.unwrap().path();
-                b.examples.push(example_path);
+        // See if there are other pictures of the board next to it
+        b.gallery = gallery::find_images(path);
+
+        // The SVD file is read when it's first needed, since they can be large
+        if let Some(svd) = &b.svd {
+            let svd_path = path.parent().unwrap_or(Path::new("")).join(svd);
+            if !svd_path.is_file() {
+                warn!("board {} points to SVD file {}, which isn't there", b.get_name(), svd_path.display());
             }
+            b.svd_device = LazySvd::new(svd_path);
+        }
+
+        if let Some(datasheet) = b.datasheet_file().filter(|d| !d.is_file()) {
+            warn!("board {} points to datasheet {}, which isn't there", b.get_name(), datasheet.display());
         }
 
+        // See if there are any examples
+        b.examples = examples::find_examples(path);
+
         return Ok(b);
     }
 
@@ -173,10 +566,77 @@ impl Board {
         self.name.as_str()
     }
 
+    pub fn revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    /// The name to show the board by, which tells its revisions apart, i.e.
+    /// "Feather RP2040 (rev B)".
+    pub fn label(&self) -> String {
+        match &self.revision {
+            Some(revision) => format!("{} ({})", self.name, revision),
+            None => self.name.clone(),
+        }
+    }
+
+    /// What tells the board apart from every other board, i.e.
+    /// "Adafruit/Feather RP2040 (rev B)". The same name can be used by more than one
+    /// manufacturer, so the label isn't enough.
+    pub fn key(&self) -> String {
+        Self::identity_key(&self.manufacturer, &self.name, self.revision())
+    }
+
+    /// The key of the board with the given manufacturer, name and revision, for looking a
+    /// board up without having it at hand.
+    pub fn identity_key(manufacturer: &str, name: &str, revision: Option<&str>) -> String {
+        match revision {
+            Some(revision) => format!("{}/{} ({})", manufacturer, name, revision),
+            None => format!("{}/{}", manufacturer, name),
+        }
+    }
+
+    pub fn manufacturer(&self) -> &str {
+        &self.manufacturer
+    }
+
+    pub fn cpu(&self) -> Option<&str> {
+        self.cpu.as_deref()
+    }
+
+    pub fn standard(&self) -> Option<&BoardStandards> {
+        self.standard.as_ref()
+    }
+
     pub fn get_pinout(&self) -> Pinout {
         self.pinout.clone()
     }
 
+    pub fn pins(&self) -> &[PinDefinition] {
+        &self.pins
+    }
+
+    pub fn pin(&self, name: &str) -> Option<&PinDefinition> {
+        self.pins.iter().find(|p| p.name == name)
+    }
+
+    /// What a pin can be used for: what its functions say, and the interfaces in the pinout
+    /// it's part of.
+    pub fn pin_capabilities(&self, name: &str) -> Vec<InterfaceType> {
+        let mut capabilities = self.pin(name).map(PinDefinition::capabilities).unwrap_or_default();
+        for mapping in self.pinout.iter().filter(|m| m.pins.iter().any(|p| p == name)) {
+            let iface_type = mapping.interface.iface_type;
+            if iface_type != InterfaceType::NONE && !capabilities.contains(&iface_type) {
+                capabilities.push(iface_type);
+            }
+        }
+        capabilities.sort_by_key(|t| enum_iterator::all::<InterfaceType>().position(|u| u == *t));
+        capabilities
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
     pub fn required_crates(&self) -> Option<Vec<String>> {
         self.required_crates.clone()
     }
@@ -185,8 +645,189 @@ impl Board {
         self.related_crates.clone()
     }
 
+    pub fn zephyr_board(&self) -> Option<&str> {
+        self.zephyr_board.as_deref()
+    }
+
+    pub fn esp_idf_target(&self) -> Option<&str> {
+        self.esp_idf_target.as_deref()
+    }
+
+    pub fn bsp_crate(&self) -> Option<&str> {
+        self.bsp_crate.as_deref()
+    }
+
+    pub fn hal_crate(&self) -> Option<&str> {
+        self.hal_crate.as_deref()
+    }
+
+    pub fn target_triple(&self) -> Option<&str> {
+        self.target_triple.as_deref()
+    }
+
+    /// The chip's name for probe-rs, which is the flash layout's if the board doesn't give
+    /// one of its own.
+    pub fn chip_name(&self) -> Option<&str> {
+        self.chip_name.as_deref().or_else(|| self.flash_layout.as_ref()?.chip.as_deref())
+    }
+
+    pub fn flash_method(&self) -> Option<FlashMethod> {
+        self.flash_method
+    }
+
+    /// The pin the board's user LED is on.
+    pub fn led(&self) -> Option<&PinDefinition> {
+        let led = self.led.as_ref()?;
+        self.pins.iter().find(|p| p.name == *led)
+    }
+
+    pub fn clock(&self) -> Option<Frequency> {
+        self.clock
+    }
+
+    /// Whether the cpu has an ITM, so it can send trace over SWO. The Cortex-M0 and M0+
+    /// have none.
+    pub fn has_itm(&self) -> bool {
+        const ITM_CORES: [&str; 6] = ["Cortex-M3", "Cortex-M4", "Cortex-M7", "Cortex-M33", "Cortex-M55", "Cortex-M85"];
+        self.cpu.as_deref().is_some_and(|cpu| ITM_CORES.iter().any(|core| cpu.starts_with(core)))
+    }
+
+    /// The clock of the PWM timers, which is the cpu clock unless the board says otherwise
+    pub fn timer_clock(&self) -> Option<Frequency> {
+        self.timer_clock.or(self.clock)
+    }
+
+    pub fn pwm(&self) -> Option<PwmTimers> {
+        self.pwm
+    }
+
+    pub fn watchdog(&self) -> Option<Watchdog> {
+        self.watchdog
+    }
+
+    pub fn brown_out(&self) -> Option<&BrownOut> {
+        self.brown_out.as_ref()
+    }
+
+    pub fn rtc(&self) -> Option<Rtc> {
+        self.rtc
+    }
+
+    pub fn sleep_modes(&self) -> Option<&SleepModes> {
+        self.sleep.as_ref()
+    }
+
+    pub fn input(&self) -> Option<InputDevice> {
+        self.input
+    }
+
+    pub fn adc(&self) -> Option<Adc> {
+        self.adc
+    }
+
+    pub fn peripherals(&self) -> Option<Peripherals> {
+        self.peripherals
+    }
+
+    pub fn ram(&self) -> Option<Memory> {
+        self.ram
+    }
+
+    pub fn flash(&self) -> Option<Memory> {
+        self.flash
+    }
+
+    pub fn operating_voltage(&self) -> Option<Voltage> {
+        self.operating_voltage
+    }
+
+    pub fn logic_level(&self) -> Option<Voltage> {
+        self.logic_level
+    }
+
+    pub fn typical_current(&self) -> Option<Current> {
+        self.typical_current
+    }
+
+    pub fn flash_layout(&self) -> Option<&FlashLayout> {
+        self.flash_layout.as_ref()
+    }
+
+    pub fn partition_table(&self) -> Option<&PartitionTable> {
+        self.partition_table.as_ref()
+    }
+
+    /// The compile-time configuration options the board declares
+    pub fn config_options(&self) -> &[ConfigOption] {
+        &self.config
+    }
+
+    /// Whether the board has a LoRa radio, going by its name and its crates
+    pub fn is_lora_capable(&self) -> bool {
+        let crates = self.required_crates.iter().chain(self.related_crates.iter()).flatten();
+        self.name.to_lowercase().contains("lora") || crates.into_iter().any(|c| c.contains("lora"))
+    }
+
+    pub fn kind(&self) -> BoardKind {
+        match (self.kind, self.is_main_board) {
+            (Some(kind), _) => kind,
+            (None, Some(true)) => BoardKind::MainBoard,
+            (None, _) => BoardKind::Peripheral,
+        }
+    }
+
     pub fn is_main_board(&self) -> bool {
-        self.is_main_board
+        self.kind() == BoardKind::MainBoard
+    }
+
+    /// The chip's peripherals and registers, read from the board's SVD file the first
+    /// time they're asked for. None if the board doesn't have one.
+    pub fn svd(&self) -> Option<Result<Arc<svd::Device>, String>> {
+        self.svd_device.get()
+    }
+
+    pub fn svd_path(&self) -> Option<&Path> {
+        self.svd_device.path()
+    }
+
+    pub fn datasheet_url(&self) -> Option<&str> {
+        self.datasheet_url.as_deref()
+    }
+
+    pub fn docs_url(&self) -> Option<&str> {
+        self.docs_url.as_deref()
+    }
+
+    /// The copy of the datasheet next to the board file, if it has one.
+    pub fn datasheet_file(&self) -> Option<PathBuf> {
+        let dir = self.file.as_ref()?.parent()?;
+        Some(dir.join(self.datasheet.as_ref()?))
+    }
+
+    pub fn quickstart(&self) -> Option<&QuickStart> {
+        self.quickstart.as_ref()
+    }
+
+    /// The board's other pictures, after its own image.
+    pub fn gallery(&self) -> &[GalleryImage] {
+        &self.gallery
+    }
+
+    /// The names of all the board's pictures, its own image first.
+    pub fn image_views(&self) -> Vec<&str> {
+        self.svg_board_info.iter().map(|_| gallery::MAIN_VIEW)
+            .chain(self.gallery.iter().map(|g| g.view.as_str()))
+            .collect()
+    }
+
+    /// The toml file the board was loaded from, if it was.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// The example projects that come with the board
+    pub fn examples(&self) -> &[Example] {
+        &self.examples
     }
 
     pub fn get_template_dir(&self) -> Option<PathBuf> {
@@ -195,62 +836,131 @@ impl Board {
 
 }
 
-/// Iteratively gather the Boards from the filesystem.
-pub fn get_boards(boards_dir: &Path) -> Vec<Board> {
+/// Gather the Boards from the filesystem. If a board file or directory couldn't be read
+/// or loaded, the report has what's wrong with it, along with the boards that could be.
+pub fn get_boards(boards_dir: &Path) -> Result<Vec<Board>, BoardLoadReport> {
+    let (boards, diagnostics) = get_boards_with_diagnostics(boards_dir);
+    let report = BoardLoadReport { boards, diagnostics };
+    match report.has_errors() {
+        true => Err(report),
+        false => Ok(report.boards),
+    }
+}
+
+/// The entries of a directory, with a diagnostic in place of the directory or an entry
+/// that can't be read.
+fn dir_entries(dir: &Path, problems: &mut Vec<BoardDiagnostic>) -> Vec<fs::DirEntry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("couldn't read directory {}: {:?}", dir.display(), e);
+            problems.push(diagnostics::unreadable(dir, &e));
+            return vec![];
+        },
+    };
+    let mut entries: Vec<fs::DirEntry> = entries.filter_map(|entry| match entry {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+            warn!("couldn't read an entry of directory {}: {:?}", dir.display(), e);
+            problems.push(diagnostics::unreadable(dir, &e));
+            None
+        },
+    }).collect();
+    // the order of read_dir depends on the filesystem, and which of two files for the
+    // same board wins shouldn't
+    entries.sort_by_key(|entry| entry.file_name());
+    entries
+}
+
+/// Gather the Boards from the filesystem, along with what's wrong with the board files,
+/// including the ones that couldn't be loaded.
+pub fn get_boards_with_diagnostics(boards_dir: &Path) -> (Vec<Board>, Vec<BoardDiagnostic>) {
     let mut r = Vec::new();
-    if let Ok(manufacturers) = fs::read_dir(boards_dir) {
-        // first tier of organization is by manufacturer
-        for manufacturer in manufacturers {
-            let manufacturer = manufacturer.expect("error with manufacturer directory");
-            if manufacturer.file_type().expect("error parsing file type").is_file() {
+    let mut problems = Vec::new();
+    // first tier of organization is by manufacturer
+    for manufacturer in dir_entries(boards_dir, &mut problems) {
+        if !manufacturer.path().is_dir() {
+            continue;
+        }
+        for board in dir_entries(&manufacturer.path(), &mut problems) {
+            let board_dir = board.path();
+            if !board_dir.is_dir() {
                 continue;
             }
-            let boards = fs::read_dir(manufacturer.path()).expect("error iterating over files in manufacturer directory");
-            for board in boards {
-                let board = board.expect("error with Board directory");
-                if board.file_type().expect("error parsing file type within board dir").is_file() {
+            for file in dir_entries(&board_dir, &mut problems) {
+                if file.path().extension() != Some(std::ffi::OsStr::new("toml")) {
                     continue;
                 }
-                let files = fs::read_dir(board.path()).expect("error iterating over files in board directory");
-                for file in files {
-                    let file = file.expect("error reading file within board directory");
-                    if file.path().extension().unwrap_or_default() == "toml" {
-                        match Board::load_from_toml(&file.path()) {
-                            Ok(mut board) => {
-                                let parent = file.path().parent().unwrap().canonicalize().unwrap();
-                                // look for a template directory
-                                let template_dir = parent.join("template");
-                                if let Ok(true) = template_dir.try_exists() {
-                                    debug!("found template dir for board <{}> at {:?}", board.name.clone(), file.path().parent().unwrap().canonicalize().unwrap().join("template"));
-                                    board.template_dir = Some(template_dir);
-                                } else {
-                                    debug!("no template directory found for board <{}>", board.name.clone());
-                                }
-                                // look for a local BSP, and do things related to it if needed
-                                let bsp_dir = parent.join("bsp");
-                                if let Ok(true) = bsp_dir.try_exists() {
-                                    info!("found local bsp crate for board {}", board.name.clone());
-                                    board.bsp_path = Some(bsp_dir.clone());
-                                    // let bsp_string = fs::read_to_string(bsp_dir.join("src/lib.rs")).unwrap();
-                                    // let (analysis, fid) = ra_ap_ide::Analysis::from_single_file(bsp_string);
-                                    // board.ra_values = analysis.file_structure(fid).unwrap();
-                                    match board.load_bsp_info() {
-                                        Ok(_) => (),
-                                        Err(e) => warn!("error parsing BSP for board {}: {:?}", board.get_name(), e),
-                                    };
-                                } else {
-                                    debug!("no bsp directory found for board <{}>", board.name.clone());
-                                }
-                                r.push(board);
-                            },
+                let toml_str = match fs::read_to_string(file.path()) {
+                    Ok(toml_str) => toml_str,
+                    Err(e) => {
+                        warn!("couldn't read board file {}: {:?}", file.path().display(), e);
+                        problems.extend(diagnostics::load_error(&file.path(), &BoardLoadError::FsError(e), None));
+                        continue;
+                    },
+                };
+                match Board::load_from_toml(&file.path(), &toml_str) {
+                    Ok(mut board) => {
+                        problems.extend(diagnostics::check_board(&file.path(), &toml_str, &board));
+                        let parent = match paths::canonicalize(&board_dir) {
+                            Ok(parent) => parent,
                             Err(e) => {
-                                warn!("error loading board from {}: {:?}", file.path().display().to_string(), e);
+                                warn!("couldn't resolve board directory {}: {:?}", board_dir.display(), e);
+                                problems.push(diagnostics::unreadable(&board_dir, &e));
+                                continue;
                             },
+                        };
+                        // look for a template directory
+                        let template_dir = parent.join("template");
+                        if let Ok(true) = template_dir.try_exists() {
+                            debug!("found template dir for board <{}> at {:?}", board.name.clone(), template_dir);
+                            board.template_dir = Some(template_dir);
+                        } else {
+                            debug!("no template directory found for board <{}>", board.name.clone());
+                        }
+                        // look for a local BSP, and do things related to it if needed
+                        let bsp_dir = parent.join("bsp");
+                        if let Ok(true) = bsp_dir.try_exists() {
+                            info!("found local bsp crate for board {}", board.name.clone());
+                            board.bsp_path = Some(bsp_dir.clone());
+                            // let bsp_string = fs::read_to_string(bsp_dir.join("src/lib.rs")).unwrap();
+                            // let (analysis, fid) = ra_ap_ide::Analysis::from_single_file(bsp_string);
+                            // board.ra_values = analysis.file_structure(fid).unwrap();
+                            match board.load_bsp_info() {
+                                Ok(_) => (),
+                                Err(e) => warn!("error parsing BSP for board {}: {:?}", board.get_name(), e),
+                            };
+                        } else {
+                            debug!("no bsp directory found for board <{}>", board.name.clone());
+                        }
+                        // the first of two files for the same board revision wins
+                        if r.contains(&board) {
+                            warn!("board {} is in more than one file", board.label());
+                            problems.push(diagnostics::duplicate(&file.path(), &board));
+                            continue;
                         }
-                    }
+                        // boards by other manufacturers with the same name both load
+                        if let Some(other) = r.iter().find(|b| b.label() == board.label()) {
+                            warn!("board {} is made by {} and {}", board.label(), other.manufacturer(), board.manufacturer());
+                            problems.push(diagnostics::same_name(&file.path(), &board, other));
+                        }
+                        r.push(board);
+                    },
+                    Err(e) => {
+                        warn!("error loading board from {}: {:?}", file.path().display().to_string(), e);
+                        problems.extend(diagnostics::load_error(&file.path(), &e, Some(&toml_str)));
+                    },
                 }
             }
         }
     }
-    return r;
+    (r, problems)
+}
+
+/// Decode the boards' images one after another in a job, so they're ready by the time
+/// they're shown. The Ui is repainted as each one is decoded. If the job is cancelled,
+/// the rest are decoded when they're first shown.
+pub fn decode_images(boards: &[Board], ctx: &egui::Context) {
+    let images: Vec<_> = boards.iter().filter_map(|b| b.svg_board_info.as_ref()).map(|info| info.image.clone()).collect();
+    svg_reader::BoardImage::decode_all(&images, ctx);
 }
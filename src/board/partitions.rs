@@ -0,0 +1,304 @@
+//! Title: Iron Coder Board Module - Partition Table
+//! Description: The partitions of a board's flash: where its settings and filesystems are
+//!   kept and, on ESP32-class chips, its firmware too. Tools that make a partition image,
+//!   like the configuration partition editor, write it to the offset declared here, and
+//!   ESP chips are flashed with the table as an ESP-IDF partitions.csv.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use super::flash_layout::FlashLayout;
+use super::units::Memory;
+
+/// The size of a flash erase sector, which partitions start and end on.
+pub const SECTOR_SIZE: u64 = 4096;
+/// ESP chips map firmware into memory in 64 KiB pages, so app partitions start on one.
+pub const ESP_APP_ALIGN: u64 = 0x10000;
+/// Where ESP chips look for the partition table. The bootloader is below it.
+pub const ESP_TABLE_OFFSET: u64 = 0x8000;
+/// The ESP partition table is one sector long.
+const ESP_FIRST_PARTITION: u64 = ESP_TABLE_OFFSET + SECTOR_SIZE;
+/// ESP-IDF numbers OTA app partitions ota_0 to ota_15.
+const ESP_MAX_OTA_APPS: usize = 16;
+
+/// What a partition holds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionKind {
+    /// ESP-IDF's non-volatile storage, a key-value store
+    Nvs,
+    /// A littlefs filesystem
+    Littlefs,
+    /// Anything else the firmware reads and writes itself
+    Data,
+    /// The firmware, on chips that load it from a partition
+    App,
+    /// One of the firmware slots over-the-air updates are written to
+    OtaApp,
+    /// Which OTA slot boots
+    OtaData,
+    /// The radio's calibration data
+    Phy,
+    /// Where the firmware writes a core dump when it crashes
+    Coredump,
+    /// A FAT filesystem
+    Fat,
+}
+
+impl fmt::Display for PartitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartitionKind::Nvs => write!(f, "NVS"),
+            PartitionKind::Littlefs => write!(f, "littlefs"),
+            PartitionKind::Data => write!(f, "data"),
+            PartitionKind::App => write!(f, "app"),
+            PartitionKind::OtaApp => write!(f, "OTA app"),
+            PartitionKind::OtaData => write!(f, "OTA data"),
+            PartitionKind::Phy => write!(f, "PHY init"),
+            PartitionKind::Coredump => write!(f, "core dump"),
+            PartitionKind::Fat => write!(f, "FAT"),
+        }
+    }
+}
+
+impl PartitionKind {
+
+    /// Whether the partition holds firmware.
+    pub fn is_app(&self) -> bool {
+        matches!(self, PartitionKind::App | PartitionKind::OtaApp)
+    }
+
+    /// The type and subtype of the partition in an ESP-IDF partitions.csv. OTA apps are
+    /// numbered in the order they're in.
+    fn esp_type(&self, ota_index: usize) -> (&'static str, String) {
+        let subtype = match self {
+            PartitionKind::Nvs => "nvs",
+            PartitionKind::Littlefs => "littlefs",
+            PartitionKind::Data => "undefined",
+            PartitionKind::App => "factory",
+            PartitionKind::OtaApp => return ("app", format!("ota_{}", ota_index)),
+            PartitionKind::OtaData => "ota",
+            PartitionKind::Phy => "phy",
+            PartitionKind::Coredump => "coredump",
+            PartitionKind::Fat => "fat",
+        };
+        (if self.is_app() { "app" } else { "data" }, subtype.to_string())
+    }
+
+    /// The kind of a partition from its type and subtype in a partitions.csv.
+    fn from_esp_type(kind: &str, subtype: &str) -> Result<Self, String> {
+        match (kind, subtype) {
+            ("app", "factory") | ("app", "test") => Ok(PartitionKind::App),
+            ("app", ota) if ota.starts_with("ota_") => Ok(PartitionKind::OtaApp),
+            ("data", "nvs") => Ok(PartitionKind::Nvs),
+            ("data", "littlefs") | ("data", "spiffs") => Ok(PartitionKind::Littlefs),
+            ("data", "ota") => Ok(PartitionKind::OtaData),
+            ("data", "phy") => Ok(PartitionKind::Phy),
+            ("data", "coredump") => Ok(PartitionKind::Coredump),
+            ("data", "fat") => Ok(PartitionKind::Fat),
+            ("data", _) => Ok(PartitionKind::Data),
+            (kind, subtype) => Err(format!("unknown partition type {}, {}", kind, subtype)),
+        }
+    }
+}
+
+/// Parse an offset or size in a partitions.csv: hex, or decimal bytes with an optional
+/// K or M suffix.
+fn parse_esp_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let parsed = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if let Some(k) = s.strip_suffix(['K', 'k']) {
+        k.trim().parse::<u64>().ok().map(|k| k * 1024)
+    } else if let Some(m) = s.strip_suffix(['M', 'm']) {
+        m.trim().parse::<u64>().ok().map(|m| m * 1024 * 1024)
+    } else {
+        s.parse::<u64>().ok()
+    };
+    parsed.ok_or_else(|| format!("invalid size or offset \"{}\"", s))
+}
+
+/// Write a size the way partitions.csv files usually do, i.e. "1M" or "24K".
+fn esp_size(bytes: u64) -> String {
+    if bytes > 0 && bytes.is_multiple_of(1024 * 1024) {
+        format!("{}M", bytes / (1024 * 1024))
+    } else if bytes > 0 && bytes.is_multiple_of(1024) {
+        format!("{}K", bytes / 1024)
+    } else {
+        format!("{:#x}", bytes)
+    }
+}
+
+/// A partition, relative to the start of flash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Partition {
+    pub name: String,
+    pub kind: PartitionKind,
+    pub offset: Memory,
+    pub size: Memory,
+}
+
+impl Partition {
+    pub fn end(&self) -> u64 {
+        self.offset.bytes().saturating_add(self.size.bytes())
+    }
+}
+
+/// The partitions of a board's flash, i.e.
+/// ```toml
+/// [partition_table]
+/// base = 0x10000000
+/// partitions = [
+///     { name = "config", kind = "nvs", offset = "7936 KiB", size = "64 KiB" },
+///     { name = "storage", kind = "littlefs", offset = "7 MiB", size = "768 KiB" },
+/// ]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PartitionTable {
+    /// The address flash is mapped at, which a debug probe writes to
+    #[serde(default)]
+    pub base: u64,
+    pub partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+
+    /// Find a partition by name.
+    pub fn partition(&self, name: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+
+    /// The absolute address of a partition.
+    pub fn address(&self, partition: &Partition) -> u64 {
+        self.base.saturating_add(partition.offset.bytes())
+    }
+
+    /// Check that the partitions make sense, fit in `flash` if the board's flash size is
+    /// known, and stay out of the bootloader's slots if it has a flash layout.
+    pub fn validate(&self, flash: Option<Memory>, layout: Option<&FlashLayout>) -> Result<(), String> {
+        for (i, partition) in self.partitions.iter().enumerate() {
+            if partition.name.trim().is_empty() || self.partitions[..i].iter().any(|p| p.name == partition.name) {
+                return Err(format!("partition {:?} needs a unique name", partition.name));
+            }
+            if partition.size.bytes() == 0 {
+                return Err(format!("partition {} can't be empty", partition.name));
+            }
+            if !partition.offset.bytes().is_multiple_of(SECTOR_SIZE) || !partition.size.bytes().is_multiple_of(SECTOR_SIZE) {
+                return Err(format!("partition {} has to start and end on a 4 KiB flash sector", partition.name));
+            }
+            if let Some(flash) = flash.filter(|f| partition.end() > f.bytes()) {
+                return Err(format!("partition {} runs past the end of the {} of flash", partition.name, flash));
+            }
+        }
+        let mut partitions: Vec<&Partition> = self.partitions.iter().collect();
+        partitions.sort_by_key(|p| p.offset);
+        for pair in partitions.windows(2) {
+            if pair[0].end() > pair[1].offset.bytes() {
+                return Err(format!("partitions {} and {} overlap", pair[0].name, pair[1].name));
+            }
+        }
+        if let Some(layout) = layout {
+            for region in layout.regions() {
+                let start = layout.address(region);
+                let end = start.saturating_add(region.size.bytes());
+                if let Some(p) = self.partitions.iter().find(|p| self.address(p) < end && start < self.address(p).saturating_add(p.size.bytes())) {
+                    return Err(format!("partition {} overlaps the flash layout's {}", p.name, layout.region_name(region)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The table ESP-IDF uses for a single app without OTA updates.
+    pub fn esp_default() -> Self {
+        let partition = |name: &str, kind, offset, size| Partition {
+            name: name.to_string(),
+            kind,
+            offset: Memory::from_bytes(offset),
+            size: Memory::from_bytes(size),
+        };
+        Self {
+            base: 0,
+            partitions: vec![
+                partition("nvs", PartitionKind::Nvs, 0x9000, 0x6000),
+                partition("phy_init", PartitionKind::Phy, 0xf000, 0x1000),
+                partition("factory", PartitionKind::App, 0x10000, 0x100000),
+            ],
+        }
+    }
+
+    /// Check the rules ESP chips add: partitions go after the bootloader and the table
+    /// itself, app partitions start on a 64 KiB page, and there's firmware to boot.
+    pub fn validate_esp(&self) -> Result<(), String> {
+        for partition in self.partitions.iter() {
+            if partition.offset.bytes() < ESP_FIRST_PARTITION {
+                return Err(format!("partition {} is in the bootloader or the partition table, which end at {:#x}", partition.name, ESP_FIRST_PARTITION));
+            }
+            if partition.kind.is_app() && !partition.offset.bytes().is_multiple_of(ESP_APP_ALIGN) {
+                return Err(format!("app partition {} has to start on a 64 KiB boundary", partition.name));
+            }
+        }
+        let apps = self.partitions.iter().filter(|p| p.kind == PartitionKind::App).count();
+        let ota_apps = self.partitions.iter().filter(|p| p.kind == PartitionKind::OtaApp).count();
+        if apps + ota_apps == 0 {
+            return Err("there's no app partition for the firmware".to_string());
+        }
+        if apps > 1 || ota_apps > ESP_MAX_OTA_APPS {
+            return Err(format!("there can be one factory app and up to {} OTA apps", ESP_MAX_OTA_APPS));
+        }
+        let ota_data = self.partitions.iter().any(|p| p.kind == PartitionKind::OtaData);
+        if ota_apps > 0 && !ota_data {
+            return Err("OTA apps need an OTA data partition to say which one boots".to_string());
+        }
+        Ok(())
+    }
+
+    /// The table as an ESP-IDF partitions.csv, for espflash and idf.py.
+    pub fn to_esp_csv(&self) -> Result<String, String> {
+        self.validate_esp()?;
+        let mut csv = String::from("# Name, Type, SubType, Offset, Size, Flags\n");
+        let mut ota_index = 0;
+        for partition in self.partitions.iter() {
+            let (kind, subtype) = partition.kind.esp_type(ota_index);
+            if partition.kind == PartitionKind::OtaApp {
+                ota_index += 1;
+            }
+            csv += &format!("{}, {}, {}, {:#x}, {},\n", partition.name, kind, subtype, partition.offset.bytes(), esp_size(partition.size.bytes()));
+        }
+        Ok(csv)
+    }
+
+    /// Read an ESP-IDF partitions.csv. A partition without an offset goes right after the
+    /// one before it.
+    pub fn from_esp_csv(csv: &str) -> Result<Self, String> {
+        let mut partitions: Vec<Partition> = vec![];
+        let mut next = ESP_FIRST_PARTITION;
+        for (i, line) in csv.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 5 {
+                return Err(format!("line {} needs a name, type, subtype, offset and size", i + 1));
+            }
+            let kind = PartitionKind::from_esp_type(fields[1], fields[2]).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            let align = if kind.is_app() { ESP_APP_ALIGN } else { SECTOR_SIZE };
+            let offset = match fields[3] {
+                "" => next.div_ceil(align) * align,
+                offset => parse_esp_size(offset).map_err(|e| format!("line {}: {}", i + 1, e))?,
+            };
+            let size = parse_esp_size(fields[4]).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            next = offset.saturating_add(size);
+            partitions.push(Partition {
+                name: fields[0].to_string(),
+                kind,
+                offset: Memory::from_bytes(offset),
+                size: Memory::from_bytes(size),
+            });
+        }
+        Ok(Self { base: 0, partitions })
+    }
+}
@@ -0,0 +1,67 @@
+//! Title: Iron Coder Board Module - Peripherals
+//! Description: How many of each peripheral a board's chip has, i.e. its UARTs and I2C
+//!   buses, and whether it has USB and radios. Unlike the pinout, which says what the
+//!   board's pins are wired for, these are counts a project's needs can be matched against.
+
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+/// The peripherals of a board, i.e.
+/// ```toml
+/// [peripherals]
+/// uart = 2
+/// i2c = 2
+/// spi = 2
+/// adc_channels = 4
+/// usb = true
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Peripherals {
+    pub uart: u32,
+    pub i2c: u32,
+    pub spi: u32,
+    /// The ADC inputs that can be reached from the board's pins
+    pub adc_channels: u32,
+    /// A USB device the firmware can use, rather than a USB-serial chip
+    pub usb: bool,
+    pub wifi: bool,
+    pub ble: bool,
+}
+
+impl Peripherals {
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The peripherals the board has, i.e. ["2 UART", "2 I2C", "USB"].
+    pub fn summary(&self) -> Vec<String> {
+        let counts = [(self.uart, "UART"), (self.i2c, "I2C"), (self.spi, "SPI"), (self.adc_channels, "ADC channels")];
+        let flags = [(self.usb, "USB"), (self.wifi, "WiFi"), (self.ble, "BLE")];
+        counts.into_iter().filter(|(n, _)| *n > 0).map(|(n, name)| format!("{} {}", n, name))
+            .chain(flags.into_iter().filter(|(has, _)| *has).map(|(_, name)| name.to_string()))
+            .collect()
+    }
+
+    /// Whether there are at least as many of each peripheral as `needed` has.
+    pub fn covers(&self, needed: &Peripherals) -> bool {
+        self.uart >= needed.uart
+            && self.i2c >= needed.i2c
+            && self.spi >= needed.spi
+            && self.adc_channels >= needed.adc_channels
+            && (self.usb || !needed.usb)
+            && (self.wifi || !needed.wifi)
+            && (self.ble || !needed.ble)
+    }
+}
+
+impl fmt::Display for Peripherals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", self.summary().join(", ")),
+        }
+    }
+}
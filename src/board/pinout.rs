@@ -105,4 +105,30 @@ pub type Pinout = Vec<InterfaceMapping>;
 //     fn next(&self) -> Option<Self::Item> {
 //         self.pinout.next();
 //     }
-// }
\ No newline at end of file
+// }
+/// One pin of a Board: its name (the same as its element in the board SVG), what it does
+/// by default, and what else it can be switched to.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PinDefinition {
+    pub name: String,
+    /// i.e. "GPIO26", or "ground" for pins that aren't connected to the chip
+    pub function: String,
+    /// i.e. ["SPI1 SCK", "UART1 CTS", "I2C1 SDA", "PWM5 A", "ADC0"]
+    pub alternate_functions: Vec<String>,
+}
+
+impl PinDefinition {
+
+    /// The kinds of interface the pin can be used for, going by the names of its functions.
+    pub fn capabilities(&self) -> Vec<InterfaceType> {
+        let functions: Vec<String> = std::iter::once(&self.function)
+            .chain(self.alternate_functions.iter())
+            .map(|f| f.trim().to_ascii_uppercase())
+            .collect();
+        enum_iterator::all::<InterfaceType>()
+            .filter(|t| *t != InterfaceType::NONE)
+            .filter(|t| functions.iter().any(|f| f.starts_with(&t.to_string())))
+            .collect()
+    }
+}
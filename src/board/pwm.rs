@@ -0,0 +1,143 @@
+//! Title: Iron Coder Board Module - PWM
+//! Description: The PWM timers of a board's chip: how wide their counter is, how the
+//!   timer clock can be divided down for them, and which HAL drives them. With this the
+//!   toolbox can work out the settings for a PWM frequency and write the code to apply them.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+/// The dividers a timer's prescaler can apply to the timer clock.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Prescaler {
+    /// Any whole divider from 1 to `max`
+    Integer { max: u32 },
+    /// A divider from 1 to just under `max + 1`, in steps of 1/2^`fraction_bits`
+    Fractional { max: u32, fraction_bits: u32 },
+    /// A power of two from 1 to `max`
+    PowerOfTwo { max: u32 },
+}
+
+impl Prescaler {
+
+    pub fn fraction_bits(&self) -> u32 {
+        match self {
+            Prescaler::Fractional { fraction_bits, .. } => *fraction_bits,
+            _ => 0,
+        }
+    }
+
+    /// The divider a whole and fractional part stand for. The fractional part is in
+    /// steps of 1/2^fraction_bits.
+    pub fn value(&self, divider: u32, frac: u32) -> f64 {
+        divider as f64 + frac as f64 / (1u64 << self.fraction_bits()) as f64
+    }
+
+    /// The dividers closest to `ideal` from below and from above, as whole and fractional
+    /// parts, kept within the prescaler's range.
+    pub fn neighbours(&self, ideal: f64) -> [(u32, u32); 2] {
+        match *self {
+            Prescaler::PowerOfTwo { max } => {
+                let max_exponent = max.max(1).ilog2();
+                let exponent = |e: f64| (e.max(0.0) as u32).min(max_exponent);
+                let log = ideal.max(1.0).log2();
+                [(1 << exponent(log.floor()), 0), (1 << exponent(log.ceil()), 0)]
+            },
+            _ => {
+                let bits = self.fraction_bits();
+                let max = match *self {
+                    Prescaler::Integer { max } => max.max(1) as u64,
+                    _ => ((self.max() as u64) << bits) + (1 << bits) - 1,
+                };
+                let steps = ideal * (1u64 << bits) as f64;
+                let clamp = |s: f64| (s.max(0.0) as u64).clamp(1 << bits, max);
+                let split = |s: u64| ((s >> bits) as u32, (s & ((1 << bits) - 1)) as u32);
+                [split(clamp(steps.floor())), split(clamp(steps.ceil()))]
+            },
+        }
+    }
+
+    /// The largest whole divider.
+    pub fn max(&self) -> u32 {
+        match *self {
+            Prescaler::Integer { max } | Prescaler::Fractional { max, .. } | Prescaler::PowerOfTwo { max } => max.max(1),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            Prescaler::Integer { max: 0 } => Err("pwm prescaler max must be at least 1".to_string()),
+            Prescaler::Fractional { max, fraction_bits } if max == 0 || fraction_bits > 16 =>
+                Err("pwm prescaler needs a max of at least 1 and at most 16 fraction bits".to_string()),
+            Prescaler::PowerOfTwo { max } if !max.is_power_of_two() => Err("pwm prescaler max must be a power of two".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Prescaler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Prescaler::Integer { max } => write!(f, "divider 1 to {}", max),
+            Prescaler::Fractional { max, fraction_bits } => write!(f, "divider 1 to {} in steps of 1/{}", max, 1u64 << fraction_bits),
+            Prescaler::PowerOfTwo { max } => write!(f, "divider 1, 2, 4 ... {}", max),
+        }
+    }
+}
+
+/// The HALs the toolbox can write PWM setup code for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum PwmHal {
+    Rp2040Hal,
+    NrfHal,
+    EspHal,
+}
+
+impl fmt::Display for PwmHal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PwmHal::Rp2040Hal => write!(f, "rp2040-hal"),
+            PwmHal::NrfHal => write!(f, "nrf-hal"),
+            PwmHal::EspHal => write!(f, "esp-hal (LEDC)"),
+        }
+    }
+}
+
+/// The PWM timers of a board's chip, i.e.
+/// ```toml
+/// [pwm]
+/// counter_bits = 16
+/// prescaler = { kind = "fractional", max = 255, fraction_bits = 4 }
+/// hal = "rp2040-hal"
+/// ```
+/// The timers are driven by the board's `timer_clock`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PwmTimers {
+    pub counter_bits: u32,
+    pub prescaler: Prescaler,
+    pub hal: Option<PwmHal>,
+}
+
+impl PwmTimers {
+
+    /// The largest value the counter can count up to before wrapping. The nRF's
+    /// COUNTERTOP register holds the number of counts rather than the last one, so there
+    /// the top is one less than the register can hold.
+    pub fn max_top(&self) -> u32 {
+        let max = ((1u64 << self.counter_bits) - 1) as u32;
+        match self.hal {
+            Some(PwmHal::NrfHal) => max - 1,
+            _ => max,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2..=32).contains(&self.counter_bits) {
+            return Err(format!("pwm counter must be 2 to 32 bits (got {})", self.counter_bits));
+        }
+        self.prescaler.validate()
+    }
+}
@@ -0,0 +1,200 @@
+//! Title: Iron Coder Board Module - Quick Start
+//! Description: The steps to get a board going for the first time, i.e. installing the
+//!   target, plugging it in, putting it in its bootloader and running an example. They're
+//!   described in the `quickstart` section of the board file, and shown as a checklist
+//!   the first time the board is picked. Which steps are done is kept with the app's
+//!   state, for each board.
+//!
+//! ```toml
+//! [[quickstart.steps]]
+//! title = "Install the target"
+//! command = "rustup target add thumbv6m-none-eabi"
+//!
+//! [[quickstart.steps]]
+//! title = "Run blinky"
+//! detail = "The red LED next to the USB port blinks."
+//! example = "blinky"
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event};
+use crate::board::Board;
+
+/// A step of a quick start.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct QuickStartStep {
+    pub title: String,
+    /// More about the step, i.e. which buttons to press
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A command to run for the step, which can be copied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// The name of one of the board's examples to open for the step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+}
+
+/// The steps to get a board going, in order.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct QuickStart {
+    pub steps: Vec<QuickStartStep>,
+}
+
+impl QuickStart {
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("quickstart needs at least one step".to_string());
+        }
+        for step in self.steps.iter() {
+            if step.title.trim().is_empty() {
+                return Err("quickstart steps need a title".to_string());
+            }
+            if step.command.as_ref().is_some_and(|c| c.trim().is_empty()) || step.example.as_ref().is_some_and(|e| e.trim().is_empty()) {
+                return Err(format!("quickstart step \"{}\" has an empty command or example", step.title));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which quick start steps are done, for each board by its key. A board is in here
+/// once its quick start has been shown.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct QuickStartProgress {
+    done: BTreeMap<String, Vec<bool>>,
+}
+
+impl QuickStartProgress {
+
+    pub fn has_seen(&self, board: &Board) -> bool {
+        self.done.contains_key(&board.key())
+    }
+
+    pub fn is_done(&self, board: &Board, step: usize) -> bool {
+        self.done.get(&board.key()).and_then(|done| done.get(step).copied()).unwrap_or(false)
+    }
+
+    /// Tick a step off, or take the tick away.
+    pub fn set_done(&mut self, board: &Board, step: usize, done: bool) {
+        let steps = self.done.entry(board.key()).or_default();
+        if steps.len() <= step {
+            steps.resize(step + 1, false);
+        }
+        steps[step] = done;
+    }
+
+    /// How many of the board's steps are done.
+    pub fn done_count(&self, board: &Board) -> usize {
+        let steps = board.quickstart().map_or(0, |q| q.steps.len());
+        self.done.get(&board.key()).map_or(0, |done| done.iter().take(steps).filter(|d| **d).count())
+    }
+
+    /// Mark the board's quick start as shown, without ticking anything off.
+    pub fn mark_seen(&mut self, board: &Board) {
+        self.done.entry(board.key()).or_default();
+    }
+
+    /// Start the board's quick start over.
+    pub fn reset(&mut self, board: &Board) {
+        self.done.insert(board.key(), vec![]);
+    }
+}
+
+/// The example a step opens, out of the board's examples.
+pub fn step_example(board: &Board, step: &QuickStartStep) -> Option<PathBuf> {
+    let name = step.example.as_deref()?;
+    board.examples().iter().find(|e| e.name() == name).map(|e| e.path.clone())
+}
+
+/// The quick start window, with the progress of every board.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct QuickStartWindow {
+    pub progress: QuickStartProgress,
+    /// The label of the board whose quick start is shown
+    #[serde(skip)]
+    showing: Option<String>,
+}
+
+impl QuickStartWindow {
+
+    /// Show a board's quick start, if it has one.
+    pub fn show(&mut self, board: &Board) {
+        if board.quickstart().is_some() {
+            self.progress.mark_seen(board);
+            self.showing = Some(board.key());
+        }
+    }
+
+    /// Show a board's quick start if it has one that hasn't been shown before.
+    pub fn board_selected(&mut self, board: &Board) {
+        if !self.progress.has_seen(board) {
+            self.show(board);
+        }
+    }
+
+    pub fn showing(&self) -> Option<&str> {
+        self.showing.as_deref()
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, boards: &[Board]) {
+        let Some(board) = self.showing.as_ref().and_then(|key| boards.iter().find(|b| b.key() == *key)) else {
+            self.showing = None;
+            return;
+        };
+        let Some(quickstart) = board.quickstart() else { return };
+        let mut open = true;
+        let mut reset = false;
+        egui::Window::new(format!("Quick start: {}", board.label())).open(&mut open).collapsible(false).show(ctx, |ui| {
+            let done = self.progress.done_count(board);
+            ui.add(egui::ProgressBar::new(done as f32 / quickstart.steps.len() as f32)
+                .text(format!("{} of {} steps done", done, quickstart.steps.len())));
+            ui.separator();
+            for (i, step) in quickstart.steps.iter().enumerate() {
+                let mut done = self.progress.is_done(board, i);
+                if ui.checkbox(&mut done, egui::RichText::new(format!("{}. {}", i + 1, step.title)).strong()).changed() {
+                    self.progress.set_done(board, i, done);
+                }
+                ui.indent(("quickstart step", i), |ui| {
+                    if let Some(detail) = &step.detail {
+                        ui.label(detail);
+                    }
+                    if let Some(command) = &step.command {
+                        ui.horizontal(|ui| {
+                            ui.code(command);
+                            if ui.small_button("copy").clicked() {
+                                ui.output_mut(|o| o.copied_text = command.clone());
+                            }
+                        });
+                    }
+                    if let Some(name) = &step.example {
+                        match step_example(board, step) {
+                            Some(example) => if ui.button(format!("open the {} example", name)).clicked() {
+                                events::shared(ui.ctx()).publish(Event::ExamplePicked(example));
+                            },
+                            None => {
+                                ui.weak(format!("the {} example isn't installed", name));
+                            },
+                        }
+                    }
+                });
+            }
+            ui.separator();
+            reset = ui.button("start over").clicked();
+        });
+        if reset {
+            self.progress.reset(board);
+        }
+        if !open {
+            self.showing = None;
+        }
+    }
+}
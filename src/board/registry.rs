@@ -0,0 +1,363 @@
+//! Title: Iron Coder Board Module - Board Registry
+//! Description: Getting community boards without waiting for a release. A registry is an
+//!   index of board definitions at a url, with the files of each board beside it. Boards
+//!   that are new, or have a new version, are downloaded, checked like any board file,
+//!   and written into the boards directory. The boards directory remembers the version
+//!   of each board it got from the registry, and boards that didn't come from it are
+//!   left alone.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::board::Board;
+
+/// The file in the boards directory with the versions of the boards from the registry.
+pub const INSTALLED_FILE: &str = "registry.json";
+/// The largest file that will be downloaded.
+const MAX_DOWNLOAD: u64 = 16 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A board in the registry, i.e.
+/// ```json
+/// {
+///   "name": "Feather RP2040",
+///   "manufacturer": "Adafruit",
+///   "dir": "Feather_RP2040",
+///   "version": "3",
+///   "files": ["feather_rp2040.toml", "feather_rp2040.svg"]
+/// }
+/// ```
+/// Its files are at `<manufacturer>/<dir>/<file>` next to the index.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub manufacturer: String,
+    /// The board's directory, under its manufacturer's
+    pub dir: String,
+    /// Any change of version counts as an update
+    pub version: String,
+    pub files: Vec<String>,
+}
+
+/// Whether a name is a single file name, which can't reach outside of its directory.
+pub(crate) fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none() && !name.contains(['/', '\\'])
+}
+
+impl RegistryEntry {
+
+    /// Where the board goes in the boards directory, i.e. "Adafruit/Feather_RP2040".
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.manufacturer, self.dir)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let names = [("manufacturer", &self.manufacturer), ("dir", &self.dir)].into_iter()
+            .chain(self.files.iter().map(|file| ("file", file)));
+        for (what, name) in names {
+            if !is_plain_name(name) {
+                return Err(format!("{}: the {} \"{}\" isn't a plain file name", self.name, what, name));
+            }
+        }
+        let tomls: Vec<&String> = self.files.iter().filter(|f| f.ends_with(".toml")).collect();
+        let [toml] = tomls.as_slice() else {
+            return Err(format!("{}: a board needs exactly one toml file", self.name));
+        };
+        let svg = Path::new(toml.as_str()).with_extension("svg");
+        if !self.files.iter().any(|f| Path::new(f) == svg) {
+            return Err(format!("{}: there's no {} with the board image", self.name, svg.display()));
+        }
+        Ok(())
+    }
+}
+
+/// The boards a registry has.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RegistryIndex {
+    pub boards: Vec<RegistryEntry>,
+}
+
+impl RegistryIndex {
+
+    /// Read an index, checking that its boards can only be written where they belong.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let index: RegistryIndex = serde_json::from_str(json).map_err(|e| format!("the index isn't valid: {}", e))?;
+        for (i, entry) in index.boards.iter().enumerate() {
+            entry.check()?;
+            if index.boards[..i].iter().any(|e| e.key() == entry.key()) {
+                return Err(format!("{} is in the index twice", entry.key()));
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// The versions of the boards that came from the registry, by their key.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Installed {
+    pub boards: BTreeMap<String, String>,
+}
+
+impl Installed {
+
+    pub fn load(boards_dir: &Path) -> Self {
+        fs::read_to_string(boards_dir.join(INSTALLED_FILE)).ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, boards_dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(boards_dir.join(INSTALLED_FILE), json).map_err(|e| format!("couldn't save the installed boards: {}", e))
+    }
+}
+
+/// How a board in the registry compares with the boards directory.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryStatus {
+    /// It isn't in the boards directory
+    New,
+    /// It came from the registry, at the version given
+    Outdated(String),
+    UpToDate,
+    /// It's in the boards directory, but didn't come from the registry
+    Local,
+}
+
+impl fmt::Display for EntryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EntryStatus::New => write!(f, "new"),
+            EntryStatus::Outdated(version) => write!(f, "update from {}", version),
+            EntryStatus::UpToDate => write!(f, "up to date"),
+            EntryStatus::Local => write!(f, "local board, left alone"),
+        }
+    }
+}
+
+impl EntryStatus {
+    /// Whether a sync downloads the board.
+    pub fn needs_sync(&self) -> bool {
+        matches!(self, EntryStatus::New | EntryStatus::Outdated(_))
+    }
+}
+
+pub fn status(entry: &RegistryEntry, boards_dir: &Path, installed: &Installed) -> EntryStatus {
+    match installed.boards.get(&entry.key()) {
+        Some(version) if *version == entry.version => EntryStatus::UpToDate,
+        Some(version) => EntryStatus::Outdated(version.clone()),
+        None if boards_dir.join(&entry.manufacturer).join(&entry.dir).exists() => EntryStatus::Local,
+        None => EntryStatus::New,
+    }
+}
+
+fn is_http(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Get a file from a url, or from the disk for a path or a file:// url, which is handy
+/// for a registry in a local checkout.
+pub fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    if !is_http(url) {
+        let path = url.strip_prefix("file://").unwrap_or(url);
+        return fs::read(path).map_err(|e| format!("couldn't read {}: {}", path, e));
+    }
+    let response = ureq::get(url).timeout(TIMEOUT).call().map_err(|e| format!("couldn't get {}: {}", url, e))?;
+    let mut bytes = vec![];
+    response.into_reader().take(MAX_DOWNLOAD).read_to_end(&mut bytes).map_err(|e| format!("couldn't get {}: {}", url, e))?;
+    Ok(bytes)
+}
+
+pub fn fetch_index(url: &str) -> Result<RegistryIndex, String> {
+    let bytes = fetch(url.trim())?;
+    RegistryIndex::parse(&String::from_utf8_lossy(&bytes))
+}
+
+/// Where a file of a board is, next to the index at `index_url`.
+pub fn file_url(index_url: &str, entry: &RegistryEntry, file: &str) -> String {
+    let index_url = index_url.trim();
+    let base = &index_url[..index_url.rfind('/').map_or(0, |i| i + 1)];
+    let encode = |name: &str| match is_http(index_url) {
+        true => name.replace('%', "%25").replace(' ', "%20"),
+        false => name.to_string(),
+    };
+    format!("{}{}/{}/{}", base, encode(&entry.manufacturer), encode(&entry.dir), encode(file))
+}
+
+/// Download a board and write it into the boards directory, if its toml file loads.
+fn install(index_url: &str, entry: &RegistryEntry, boards_dir: &Path) -> Result<(), String> {
+    let files = entry.files.iter()
+        .map(|file| Ok((file, fetch(&file_url(index_url, entry, file))?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    for (file, bytes) in files.iter().filter(|(file, _)| file.ends_with(".toml")) {
+        Board::from_toml_str(&String::from_utf8_lossy(bytes)).map_err(|e| format!("{} isn't a valid board: {:?}", file, e))?;
+    }
+    let dir = boards_dir.join(&entry.manufacturer).join(&entry.dir);
+    fs::create_dir_all(&dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+    for (file, bytes) in files {
+        fs::write(dir.join(file), bytes).map_err(|e| format!("couldn't write {}: {}", dir.join(file).display(), e))?;
+    }
+    Ok(())
+}
+
+/// What a sync did.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// The boards that couldn't be downloaded, and why
+    pub failed: Vec<(String, String)>,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} added, {} updated", self.added.len(), self.updated.len())?;
+        if !self.failed.is_empty() {
+            write!(f, ", {} failed", self.failed.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Download the boards that are new or have a new version. A board that fails doesn't
+/// stop the others.
+pub fn sync(index_url: &str, index: &RegistryIndex, boards_dir: &Path, job: &JobContext) -> Result<SyncReport, String> {
+    let mut installed = Installed::load(boards_dir);
+    let pending: Vec<(&RegistryEntry, EntryStatus)> = index.boards.iter()
+        .map(|entry| (entry, status(entry, boards_dir, &installed)))
+        .filter(|(_, status)| status.needs_sync())
+        .collect();
+    let mut report = SyncReport::default();
+    for (i, (entry, status)) in pending.iter().enumerate() {
+        if job.is_cancelled() {
+            break;
+        }
+        job.set_status(&format!("downloading {}", entry.name));
+        job.set_progress(i as f32 / pending.len() as f32);
+        match install(index_url, entry, boards_dir) {
+            Ok(()) => {
+                installed.boards.insert(entry.key(), entry.version.clone());
+                // saved as it goes, so a sync that's stopped part way knows what it got
+                installed.save(boards_dir)?;
+                match status {
+                    EntryStatus::New => report.added.push(entry.name.clone()),
+                    _ => report.updated.push(entry.name.clone()),
+                }
+            },
+            Err(e) => report.failed.push((entry.name.clone(), e)),
+        }
+    }
+    Ok(report)
+}
+
+/// The state of the board registry window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RegistryPanel {
+    /// The url of the registry's index
+    pub url: String,
+    #[serde(skip)]
+    index: Arc<Mutex<Option<Result<RegistryIndex, String>>>>,
+    #[serde(skip)]
+    report: Arc<Mutex<Option<Result<SyncReport, String>>>>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    /// Whether the job is a sync, which changes the boards
+    #[serde(skip)]
+    syncing: bool,
+}
+
+impl RegistryPanel {
+
+    /// Show the registry's boards and what a sync would do. Returns true when a sync has
+    /// changed the boards directory, so the boards should be reloaded.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, boards_dir: &Path) -> bool {
+        let scheduler = jobs::shared(ctx);
+        let running = self.job.is_some_and(|id| scheduler.state(id) == Some(JobState::Running));
+        let mut changed = false;
+        if !running && self.job.take().is_some() && self.syncing {
+            self.syncing = false;
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("index:");
+            ui.add(egui::TextEdit::singleline(&mut self.url).hint_text("https://.../index.json, or a path").desired_width(320.0));
+            if ui.add_enabled(!running && !self.url.trim().is_empty(), egui::Button::new("check")).clicked() {
+                let (url, index) = (self.url.clone(), self.index.clone());
+                *self.report.lock().unwrap() = None;
+                self.job = Some(scheduler.spawn("board registry", Some(ctx), move |_job| {
+                    let result = fetch_index(&url);
+                    let outcome = result.as_ref().map(|_| ()).map_err(Clone::clone);
+                    *index.lock().unwrap() = Some(result);
+                    outcome
+                }));
+            }
+            if running {
+                ui.spinner();
+            }
+        });
+        let index = self.index.lock().unwrap().clone();
+        match index {
+            None => {
+                ui.label("check a registry to see the boards it has.");
+            },
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            },
+            Some(Ok(index)) => {
+                let installed = Installed::load(boards_dir);
+                let statuses: Vec<EntryStatus> = index.boards.iter().map(|e| status(e, boards_dir, &installed)).collect();
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("registry_boards").num_columns(3).striped(true).show(ui, |ui| {
+                        for (entry, status) in index.boards.iter().zip(statuses.iter()) {
+                            ui.label(&entry.name);
+                            ui.weak(&entry.manufacturer);
+                            let text = egui::RichText::new(status.to_string());
+                            ui.label(if status.needs_sync() { text.strong() } else { text.weak() });
+                            ui.end_row();
+                        }
+                    });
+                });
+                let pending = statuses.iter().filter(|s| s.needs_sync()).count();
+                let text = match pending {
+                    0 => "everything is up to date".to_string(),
+                    n => format!("download {} boards", n),
+                };
+                if ui.add_enabled(!running && pending > 0, egui::Button::new(text)).clicked() {
+                    let (url, dir, report) = (self.url.trim().to_string(), boards_dir.to_path_buf(), self.report.clone());
+                    self.syncing = true;
+                    self.job = Some(scheduler.spawn("board registry sync", Some(ctx), move |job| {
+                        let result = sync(&url, &index, &dir, job);
+                        let outcome = result.as_ref().map(|_| ()).map_err(Clone::clone);
+                        *report.lock().unwrap() = Some(result);
+                        outcome
+                    }));
+                }
+            },
+        }
+        let report = self.report.lock().unwrap().clone();
+        match report {
+            Some(Ok(report)) => {
+                ui.label(report.to_string());
+                for (name, e) in report.failed.iter() {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("{}: {}", name, e));
+                }
+            },
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            },
+            None => (),
+        }
+        changed
+    }
+}
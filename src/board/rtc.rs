@@ -0,0 +1,91 @@
+//! Title: Iron Coder Board Module - Real-Time Clock
+//! Description: The real-time clock of a board's chip: what clocks it, and how fast that
+//!   clock runs. A crystal keeps good time, while an internal RC oscillator can be off by
+//!   a few percent, so the clock source decides how the RTC is started and how far the
+//!   device's time drifts from the host's.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use super::units::Frequency;
+
+/// What clocks a board's RTC.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum RtcClockSource {
+    /// A low-frequency crystal on the board, usually 32.768 kHz
+    Crystal,
+    /// The chip's own low-frequency RC oscillator
+    InternalRc,
+    /// Divided down from the chip's main clock
+    MainClock,
+}
+
+impl fmt::Display for RtcClockSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RtcClockSource::Crystal => write!(f, "crystal"),
+            RtcClockSource::InternalRc => write!(f, "internal RC oscillator"),
+            RtcClockSource::MainClock => write!(f, "main clock"),
+        }
+    }
+}
+
+impl RtcClockSource {
+
+    /// How far the clock can be off, in parts per million, as a rule of thumb.
+    pub fn typical_ppm(&self) -> f64 {
+        match self {
+            RtcClockSource::Crystal => 20.0,
+            RtcClockSource::InternalRc => 5000.0,
+            RtcClockSource::MainClock => 50.0,
+        }
+    }
+}
+
+/// The HALs the device clock panel can write RTC setup code for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum RtcHal {
+    Rp2040Hal,
+    NrfHal,
+}
+
+impl fmt::Display for RtcHal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RtcHal::Rp2040Hal => write!(f, "rp2040-hal"),
+            RtcHal::NrfHal => write!(f, "nrf-hal"),
+        }
+    }
+}
+
+/// The RTC of a board's chip, i.e.
+/// ```toml
+/// [rtc]
+/// source = "crystal"
+/// clock = "32.768 kHz"
+/// hal = "nrf-hal"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rtc {
+    pub source: RtcClockSource,
+    /// The frequency of the clock the RTC counts
+    pub clock: Option<Frequency>,
+    pub hal: Option<RtcHal>,
+}
+
+impl Rtc {
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.source == RtcClockSource::Crystal && self.clock.is_none() {
+            return Err("an rtc clocked by a crystal needs the crystal's clock".to_string());
+        }
+        if self.clock.is_some_and(|c| c.hz() == 0) {
+            return Err("the rtc clock can't be 0 Hz".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Title: Iron Coder Board Module - Schematic Symbols
+//! Description: A schematic-style symbol for a board, made from its pin metadata: a box
+//!   with the board's name, power pins down the left side and the rest split between the
+//!   sides. The system editor can draw boards as these instead of their photos, which
+//!   keeps the wiring readable when the photos are busy.
+
+use egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, Vec2};
+
+use crate::board::Board;
+
+/// The distance between pins, in mm like the board images.
+const PITCH: f32 = 2.54;
+/// How far a pin sticks out of the body.
+const STUB: f32 = 2.54;
+/// The size of the spot at the end of a pin, which connections are drawn to.
+const PIN_SIZE: f32 = 1.6;
+/// How wide a character of a label is taken to be.
+const CHAR_WIDTH: f32 = 1.2;
+/// The height of the label text.
+const TEXT_SIZE: f32 = 1.8;
+
+/// Which side of the symbol a pin is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A pin of a symbol.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolPin {
+    /// The name the board's connections use
+    pub name: String,
+    pub side: Side,
+    /// Where the pin ends, relative to the top left of the symbol
+    pub rect: Rect,
+}
+
+/// A board drawn as a schematic symbol. Sizes are in mm, so the symbol is scaled the same
+/// way as the board image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchematicSymbol {
+    pub title: String,
+    pub size: Vec2,
+    /// The box the pins come out of
+    pub body: Rect,
+    pub pins: Vec<SymbolPin>,
+}
+
+/// Whether a pin supplies or takes power, going by its name and function.
+pub fn is_power_pin(name: &str, function: &str) -> bool {
+    const NAMES: [&str; 11] = ["gnd", "ground", "3v3", "3.3v", "5v", "vbus", "vin", "vcc", "vdd", "vbat", "bat"];
+    let name = name.trim().to_ascii_lowercase();
+    let function = function.trim().to_ascii_lowercase();
+    NAMES.contains(&name.as_str())
+        || function == "ground"
+        || function.contains(" v ") || function.ends_with(" v")
+        || function.starts_with("battery")
+}
+
+impl SchematicSymbol {
+
+    /// The symbol of a board. Its pins are the ones in the board image, so connections
+    /// made on either view line up, or the pins in its toml file if it has no image.
+    pub fn new(board: &Board) -> Self {
+        let names: Vec<String> = match &board.svg_board_info {
+            Some(info) if !info.pin_rects.is_empty() => info.pin_rects.iter().map(|(name, _)| name.clone()).collect(),
+            _ => board.pins().iter().map(|p| p.name.clone()).collect(),
+        };
+        let function = |name: &str| board.pin(name).map(|p| p.function.clone()).unwrap_or_default();
+        let (mut left, signals): (Vec<String>, Vec<String>) = names.into_iter().partition(|n| is_power_pin(n, &function(n)));
+        // the signals fill up the left side after the power pins, so both sides are as
+        // long as they can be
+        let on_left = ((left.len() + signals.len()).div_ceil(2)).saturating_sub(left.len()).min(signals.len());
+        let right = signals[on_left..].to_vec();
+        left.extend_from_slice(&signals[..on_left]);
+
+        let widest = |pins: &[String]| pins.iter().map(|p| p.chars().count()).max().unwrap_or(0) as f32 * CHAR_WIDTH;
+        let title = board.get_name().to_string();
+        let width = (widest(&left) + widest(&right) + 2.0 * PITCH)
+            .max(title.chars().count() as f32 * CHAR_WIDTH + PITCH)
+            .max(4.0 * PITCH);
+        // a row for the title, then one per pin, and half a pitch at the bottom
+        let rows = left.len().max(right.len()).max(1) as f32;
+        let body = Rect::from_min_size(Pos2::new(STUB, 0.0), Vec2::new(width, (rows + 1.5) * PITCH));
+
+        let pin = |name: String, side: Side, row: usize| {
+            let y = body.top() + (row as f32 + 1.5) * PITCH;
+            let x = match side {
+                Side::Left => body.left() - STUB,
+                Side::Right => body.right() + STUB,
+            };
+            SymbolPin { name, side, rect: Rect::from_center_size(Pos2::new(x, y), Vec2::splat(PIN_SIZE)) }
+        };
+        let pins = left.into_iter().enumerate().map(|(row, name)| pin(name, Side::Left, row))
+            .chain(right.into_iter().enumerate().map(|(row, name)| pin(name, Side::Right, row)))
+            .collect();
+        Self { title, size: Vec2::new(width + 2.0 * STUB, body.height()), body, pins }
+    }
+
+    /// The pins and where they end, like the pin rects of a board image.
+    pub fn pin_rects(&self) -> Vec<(String, Rect)> {
+        self.pins.iter().map(|p| (p.name.clone(), p.rect)).collect()
+    }
+
+    /// Draw the symbol with its top left at `origin`, scaled from mm to points.
+    pub fn paint(&self, painter: &Painter, origin: Pos2, scale: f32, stroke_color: Color32, fill: Color32) {
+        let place = |p: Pos2| origin + p.to_vec2() * scale;
+        let stroke = Stroke::new((0.25 * scale).max(1.0), stroke_color);
+        let body = Rect::from_min_max(place(self.body.min), place(self.body.max));
+        painter.rect(body, 0.0, fill, stroke);
+        let font = FontId::proportional(TEXT_SIZE * scale);
+        painter.text(place(Pos2::new(self.body.center().x, self.body.top() + 0.6 * PITCH)), Align2::CENTER_CENTER, &self.title, font.clone(), stroke_color);
+        for pin in self.pins.iter() {
+            let end = pin.rect.center();
+            let (edge, label, align) = match pin.side {
+                Side::Left => (self.body.left(), self.body.left() + 0.5 * PITCH, Align2::LEFT_CENTER),
+                Side::Right => (self.body.right(), self.body.right() - 0.5 * PITCH, Align2::RIGHT_CENTER),
+            };
+            painter.line_segment([place(end), place(Pos2::new(edge, end.y))], stroke);
+            painter.circle_filled(place(end), PIN_SIZE / 4.0 * scale, stroke_color);
+            painter.text(place(Pos2::new(label, end.y)), align, &pin.name, font.clone(), stroke_color);
+        }
+    }
+}
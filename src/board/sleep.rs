@@ -0,0 +1,153 @@
+//! Title: Iron Coder Board Module - Sleep Modes
+//! Description: The low-power modes of a board's chip: what each draws, what can wake the
+//!   chip from it, and what it keeps while asleep. With this the low-power planner can
+//!   estimate the average current of a plan and write the code to enter and leave each
+//!   mode.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use super::units::Current;
+
+/// The HALs the low-power planner can write sleep code for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum SleepHal {
+    Rp2040Hal,
+    NrfHal,
+    EspHal,
+}
+
+impl fmt::Display for SleepHal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SleepHal::Rp2040Hal => write!(f, "rp2040-hal"),
+            SleepHal::NrfHal => write!(f, "nrf-hal"),
+            SleepHal::EspHal => write!(f, "esp-hal"),
+        }
+    }
+}
+
+/// How a mode puts the chip to sleep, which decides the code that enters it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepKind {
+    /// The core waits for an interrupt; everything else keeps running
+    Wait,
+    /// The core waits for an interrupt with its deep sleep bit set, which stops the
+    /// clocks that aren't kept running
+    DeepWait,
+    /// The oscillators stop until a pin or the RTC starts them again
+    Dormant,
+    /// The chip is paused with its state kept, and carries on where it was
+    Light,
+    /// All but the wake-up logic is off, and waking up is a reset
+    Off,
+}
+
+impl SleepKind {
+
+    /// Whether the firmware starts again from reset when the chip wakes up.
+    pub fn wakes_through_reset(&self) -> bool {
+        matches!(self, SleepKind::Off)
+    }
+}
+
+/// The things that can wake a chip up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum WakeSource {
+    Gpio,
+    Rtc,
+    Timer,
+    Uart,
+    Usb,
+    Radio,
+    Touch,
+}
+
+impl WakeSource {
+
+    /// Whether the source wakes the chip after a set time, rather than on something
+    /// happening outside it.
+    pub fn is_timed(&self) -> bool {
+        matches!(self, WakeSource::Rtc | WakeSource::Timer)
+    }
+}
+
+impl fmt::Display for WakeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WakeSource::Gpio => write!(f, "pin"),
+            WakeSource::Rtc => write!(f, "RTC"),
+            WakeSource::Timer => write!(f, "timer"),
+            WakeSource::Uart => write!(f, "UART"),
+            WakeSource::Usb => write!(f, "USB"),
+            WakeSource::Radio => write!(f, "radio"),
+            WakeSource::Touch => write!(f, "touch pad"),
+        }
+    }
+}
+
+/// One of a chip's low-power modes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SleepMode {
+    /// The name the datasheet uses, i.e. "System OFF"
+    pub name: String,
+    pub kind: SleepKind,
+    /// What the chip draws in the mode
+    pub current: Current,
+    pub wake_sources: Vec<WakeSource>,
+    /// What keeps its contents or state while asleep, i.e. "RAM" or "GPIO levels"
+    #[serde(default)]
+    pub retains: Vec<String>,
+}
+
+/// The low-power modes of a board's chip, i.e.
+/// ```toml
+/// [sleep]
+/// active_current = "7.4 mA"
+/// hal = "nrf-hal"
+///
+/// [[sleep.modes]]
+/// name = "System OFF"
+/// kind = "off"
+/// current = "0.3 uA"
+/// wake_sources = ["gpio"]
+/// retains = ["GPIO levels"]
+/// ```
+/// Currents are for the chip alone; a board's regulator and LEDs add to them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SleepModes {
+    /// What the chip draws while running
+    pub active_current: Current,
+    pub hal: Option<SleepHal>,
+    pub modes: Vec<SleepMode>,
+}
+
+impl SleepModes {
+
+    pub fn mode(&self, name: &str) -> Option<&SleepMode> {
+        self.modes.iter().find(|m| m.name == name)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, mode) in self.modes.iter().enumerate() {
+            if mode.name.trim().is_empty() {
+                return Err("sleep modes need a name".to_string());
+            }
+            if self.modes[..i].iter().any(|m| m.name == mode.name) {
+                return Err(format!("sleep mode {} is declared twice", mode.name));
+            }
+            if mode.wake_sources.is_empty() {
+                return Err(format!("nothing wakes the chip from sleep mode {}", mode.name));
+            }
+            if mode.current >= self.active_current {
+                return Err(format!("sleep mode {} draws as much as running", mode.name));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Title: Iron Coder Board Module - Board Sorting
+//! Description: The orders the board selector can show the boards in, i.e. by name or by
+//!   how much RAM they have, along with the boards picked most recently to sort those
+//!   first. Both are kept in the app settings, so they're the same in every project.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::units::Memory;
+
+/// How many of the boards picked most recently are remembered.
+pub const MAX_RECENT: usize = 20;
+
+/// What the boards in the board selector are sorted by.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardOrder {
+    #[default]
+    Name,
+    /// By manufacturer, then by name
+    Manufacturer,
+    /// The most RAM first
+    Ram,
+    /// The most flash first
+    Flash,
+    /// The boards picked most recently first, then the rest by name
+    RecentlyUsed,
+}
+
+impl fmt::Display for BoardOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name => write!(f, "name"),
+            Self::Manufacturer => write!(f, "manufacturer"),
+            Self::Ram => write!(f, "RAM"),
+            Self::Flash => write!(f, "flash"),
+            Self::RecentlyUsed => write!(f, "recently used"),
+        }
+    }
+}
+
+/// The order of the board selector, and the boards picked in it, by key, the most recent
+/// first. Boards picked before they were told apart by manufacturer are kept by label.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct BoardSorting {
+    pub order: BoardOrder,
+    recent: Vec<String>,
+}
+
+/// Boards with more memory first, and the ones that don't say how much last.
+fn most_memory(a: Option<Memory>, b: Option<Memory>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl BoardSorting {
+
+    /// Remember that a board was picked.
+    pub fn used(&mut self, board: &Board) {
+        let (key, label) = (board.key(), board.label());
+        self.recent.retain(|name| *name != key && *name != label);
+        self.recent.insert(0, key);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// The keys of the boards picked most recently, the most recent first.
+    pub fn recent(&self) -> &[String] {
+        &self.recent
+    }
+
+    /// Sort the boards shown, as indices into `boards`. Boards that come out the same are
+    /// sorted by name.
+    pub fn sort(&self, shown: &mut [usize], boards: &[Board]) {
+        let by_name = |a: &Board, b: &Board| a.label().to_lowercase().cmp(&b.label().to_lowercase());
+        let recency = |board: &Board| {
+            let (key, label) = (board.key(), board.label());
+            self.recent.iter().position(|name| *name == key || *name == label).unwrap_or(usize::MAX)
+        };
+        shown.sort_by(|&a, &b| {
+            let (a, b) = (&boards[a], &boards[b]);
+            let order = match self.order {
+                BoardOrder::Name => Ordering::Equal,
+                BoardOrder::Manufacturer => a.manufacturer().to_lowercase().cmp(&b.manufacturer().to_lowercase()),
+                BoardOrder::Ram => most_memory(a.ram(), b.ram()),
+                BoardOrder::Flash => most_memory(a.flash(), b.flash()),
+                BoardOrder::RecentlyUsed => recency(a).cmp(&recency(b)),
+            };
+            order.then_with(|| by_name(a, b))
+        });
+    }
+
+    /// Show a combo box to pick the order from.
+    pub fn display(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("sort by");
+            egui::ComboBox::from_id_source("board_sorting").selected_text(self.order.to_string()).show_ui(ui, |ui| {
+                for order in enum_iterator::all::<BoardOrder>() {
+                    ui.selectable_value(&mut self.order, order, order.to_string());
+                }
+            });
+        });
+    }
+}
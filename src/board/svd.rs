@@ -0,0 +1,404 @@
+//! Title: Iron Coder Board Module - SVD
+//! Description: Reading the CMSIS-SVD file a board can point to, which describes the
+//!   chip's peripherals, their registers and the fields in them. Derived peripherals,
+//!   clusters and register arrays are expanded, and the size, access and reset value a
+//!   register gets from its peripheral or the device are filled in, so the rest of the
+//!   app gets a flat list of registers with their addresses. SVD files can be large, so
+//!   a board only reads its file the first time it's asked for it.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use roxmltree::Node;
+
+/// The most elements an array in an SVD file can have.
+pub const MAX_DIM: u64 = 4096;
+
+/// How a register or field can be accessed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+    WriteOnce,
+    ReadWriteOnce,
+}
+
+impl Access {
+    fn parse(access: &str) -> Result<Self, String> {
+        match access.trim() {
+            "read-only" => Ok(Access::ReadOnly),
+            "write-only" => Ok(Access::WriteOnly),
+            "read-write" => Ok(Access::ReadWrite),
+            "writeOnce" => Ok(Access::WriteOnce),
+            "read-writeOnce" => Ok(Access::ReadWriteOnce),
+            other => Err(format!("unknown access \"{}\"", other)),
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::ReadOnly => write!(f, "read-only"),
+            Access::WriteOnly => write!(f, "write-only"),
+            Access::ReadWrite => write!(f, "read-write"),
+            Access::WriteOnce => write!(f, "write-once"),
+            Access::ReadWriteOnce => write!(f, "read-write-once"),
+        }
+    }
+}
+
+/// Some bits of a register.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub description: Option<String>,
+    pub bit_offset: u32,
+    pub bit_width: u32,
+    pub access: Option<Access>,
+}
+
+impl Field {
+    /// The bits of the field, in place.
+    pub fn mask(&self) -> u64 {
+        let bits = if self.bit_width >= 64 { u64::MAX } else { (1u64 << self.bit_width) - 1 };
+        bits << self.bit_offset
+    }
+}
+
+/// A register, with its offset from the start of its peripheral. Registers in clusters
+/// are named `CLUSTER_REGISTER`, with the cluster's offset added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Register {
+    pub name: String,
+    pub description: Option<String>,
+    pub address_offset: u64,
+    /// In bits
+    pub size: u32,
+    pub access: Option<Access>,
+    pub reset_value: Option<u64>,
+    pub fields: Vec<Field>,
+}
+
+/// An interrupt a peripheral raises.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interrupt {
+    pub name: String,
+    pub description: Option<String>,
+    pub value: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peripheral {
+    pub name: String,
+    pub description: Option<String>,
+    /// i.e. "UART" for UART0 and UART1
+    pub group_name: Option<String>,
+    pub base_address: u64,
+    pub registers: Vec<Register>,
+    pub interrupts: Vec<Interrupt>,
+}
+
+impl Peripheral {
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers.iter().find(|r| r.name == name)
+    }
+}
+
+/// The chip described by an SVD file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub description: Option<String>,
+    /// The default register width, in bits
+    pub width: u32,
+    pub peripherals: Vec<Peripheral>,
+}
+
+impl Device {
+
+    pub fn peripheral(&self, name: &str) -> Option<&Peripheral> {
+        self.peripherals.iter().find(|p| p.name == name)
+    }
+
+    /// The address of a peripheral's register.
+    pub fn register_address(&self, peripheral: &str, register: &str) -> Option<u64> {
+        let peripheral = self.peripheral(peripheral)?;
+        Some(peripheral.base_address + peripheral.register(register)?.address_offset)
+    }
+
+    /// All the interrupts, by number.
+    pub fn interrupts(&self) -> Vec<&Interrupt> {
+        let mut interrupts: Vec<&Interrupt> = self.peripherals.iter().flat_map(|p| p.interrupts.iter()).collect();
+        interrupts.sort_by_key(|i| i.value);
+        interrupts.dedup_by_key(|i| i.value);
+        interrupts
+    }
+}
+
+/// A number in an SVD file: decimal, hex with 0x or binary with #.
+pub fn parse_number(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(binary) = text.strip_prefix('#') {
+        // "don't care" bits are read as 0
+        u64::from_str_radix(&binary.replace(['x', 'X'], "0"), 2)
+    } else {
+        text.parse()
+    };
+    parsed.map_err(|_| format!("\"{}\" isn't a number", text))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name).and_then(|n| n.text()).map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn child_number(node: Node, name: &str) -> Result<Option<u64>, String> {
+    child_text(node, name).map(|t| parse_number(&t).map_err(|e| format!("{}: {}", name, e))).transpose()
+}
+
+fn required_text(node: Node, name: &str) -> Result<String, String> {
+    child_text(node, name).ok_or_else(|| format!("<{}> is missing a <{}>", node.tag_name().name(), name))
+}
+
+/// The size, access and reset value registers get from what they're in.
+#[derive(Clone, Copy, Default)]
+struct Properties {
+    size: Option<u32>,
+    access: Option<Access>,
+    reset_value: Option<u64>,
+}
+
+impl Properties {
+    fn read(self, node: Node) -> Result<Self, String> {
+        Ok(Properties {
+            size: child_number(node, "size")?.map(|s| s as u32).or(self.size),
+            access: child_text(node, "access").map(|a| Access::parse(&a)).transpose()?.or(self.access),
+            reset_value: child_number(node, "resetValue")?.or(self.reset_value),
+        })
+    }
+}
+
+/// The names and offsets of an element, which is an array if it has a `dim`.
+fn expand(node: Node, name: &str, offset: u64) -> Result<Vec<(String, u64)>, String> {
+    let Some(dim) = child_number(node, "dim")? else {
+        return Ok(vec![(name.to_string(), offset)]);
+    };
+    if dim > MAX_DIM {
+        return Err(format!("{} is an array of {}, more than the {} that can be read", name, dim, MAX_DIM));
+    }
+    let increment = child_number(node, "dimIncrement")?.ok_or_else(|| format!("{} has a dim but no dimIncrement", name))?;
+    let indices: Vec<String> = match child_text(node, "dimIndex") {
+        Some(index) if index.contains('-') => {
+            let (first, last) = index.split_once('-').unwrap();
+            match (first.trim().parse::<u64>(), last.trim().parse::<u64>()) {
+                (Ok(first), Ok(last)) => (first..=last).map(|i| i.to_string()).collect(),
+                // letters, i.e. A-D
+                _ => {
+                    let (first, last) = (first.trim().chars().next().unwrap_or('A'), last.trim().chars().next().unwrap_or('A'));
+                    (first..=last).map(|c| c.to_string()).collect()
+                },
+            }
+        },
+        Some(index) => index.split(',').map(|i| i.trim().to_string()).collect(),
+        None => (0..dim).map(|i| i.to_string()).collect(),
+    };
+    if indices.len() as u64 != dim {
+        return Err(format!("{} has {} indices for a dim of {}", name, indices.len(), dim));
+    }
+    Ok(indices.iter().enumerate().map(|(i, index)| {
+        let name = if name.contains("[%s]") { name.replace("[%s]", index) } else { name.replace("%s", index) };
+        (name, offset + i as u64 * increment)
+    }).collect())
+}
+
+fn read_field(node: Node) -> Result<Field, String> {
+    let name = required_text(node, "name")?;
+    let (bit_offset, bit_width) = if let Some(offset) = child_number(node, "bitOffset")? {
+        (offset, child_number(node, "bitWidth")?.unwrap_or(1))
+    } else if let (Some(lsb), Some(msb)) = (child_number(node, "lsb")?, child_number(node, "msb")?) {
+        (lsb, msb + 1 - lsb.min(msb + 1))
+    } else if let Some(range) = child_text(node, "bitRange") {
+        let (msb, lsb) = range.trim_matches(['[', ']']).split_once(':').ok_or_else(|| format!("field {} has a bad bitRange", name))?;
+        let (msb, lsb) = (parse_number(msb)?, parse_number(lsb)?);
+        (lsb, msb + 1 - lsb.min(msb + 1))
+    } else {
+        return Err(format!("field {} doesn't say which bits it is", name));
+    };
+    Ok(Field {
+        description: child_text(node, "description"),
+        bit_offset: bit_offset as u32,
+        bit_width: bit_width as u32,
+        access: child_text(node, "access").map(|a| Access::parse(&a)).transpose()?,
+        name,
+    })
+}
+
+/// Read the registers and clusters in `node`, with names starting with `prefix` and
+/// offsets from `offset`.
+fn read_registers(node: Node, properties: Properties, prefix: &str, offset: u64, registers: &mut Vec<Register>) -> Result<(), String> {
+    for element in node.children().filter(|n| n.is_element()) {
+        let is_cluster = element.has_tag_name("cluster");
+        if !is_cluster && !element.has_tag_name("register") {
+            continue;
+        }
+        let name = required_text(element, "name")?;
+        let address_offset = child_number(element, "addressOffset")?.ok_or_else(|| format!("{} has no addressOffset", name))?;
+        let properties = properties.read(element)?;
+        for (name, element_offset) in expand(element, &name, offset + address_offset)? {
+            let name = format!("{}{}", prefix, name);
+            if is_cluster {
+                read_registers(element, properties, &format!("{}_", name), element_offset, registers)?;
+                continue;
+            }
+            let fields = match child(element, "fields") {
+                Some(fields) => fields.children().filter(|n| n.has_tag_name("field")).map(read_field).collect::<Result<_, _>>()?,
+                None => vec![],
+            };
+            registers.push(Register {
+                description: child_text(element, "description"),
+                address_offset: element_offset,
+                size: properties.size.unwrap_or(32),
+                access: properties.access,
+                reset_value: properties.reset_value,
+                fields,
+                name,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn read_peripheral(node: Node, properties: Properties, base: Option<&Peripheral>) -> Result<Peripheral, String> {
+    let name = required_text(node, "name")?;
+    let base_address = child_number(node, "baseAddress")?.ok_or_else(|| format!("peripheral {} has no baseAddress", name))?;
+    let mut registers = vec![];
+    if let Some(registers_node) = child(node, "registers") {
+        read_registers(registers_node, properties.read(node)?, "", 0, &mut registers)?;
+    }
+    let mut interrupts = vec![];
+    for interrupt in node.children().filter(|n| n.has_tag_name("interrupt")) {
+        interrupts.push(Interrupt {
+            name: required_text(interrupt, "name")?,
+            description: child_text(interrupt, "description"),
+            value: child_number(interrupt, "value")?.ok_or_else(|| format!("an interrupt of {} has no value", name))? as u32,
+        });
+    }
+    // a derived peripheral is a copy of another one at another address
+    if let Some(base) = base {
+        if registers.is_empty() {
+            registers = base.registers.clone();
+        }
+    }
+    Ok(Peripheral {
+        description: child_text(node, "description").or_else(|| base.and_then(|b| b.description.clone())),
+        group_name: child_text(node, "groupName").or_else(|| base.and_then(|b| b.group_name.clone())),
+        base_address,
+        registers,
+        interrupts,
+        name,
+    })
+}
+
+/// Read the text of an SVD file.
+pub fn parse(text: &str) -> Result<Device, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let device = document.root_element();
+    if !device.has_tag_name("device") {
+        return Err(format!("expected a <device>, found <{}>", device.tag_name().name()));
+    }
+    let properties = Properties::default().read(device)?;
+    let mut peripherals: Vec<Peripheral> = vec![];
+    if let Some(node) = child(device, "peripherals") {
+        for peripheral in node.children().filter(|n| n.has_tag_name("peripheral")) {
+            let base = match peripheral.attribute("derivedFrom") {
+                Some(from) => Some(peripherals.iter().find(|p| p.name == from)
+                    .ok_or_else(|| format!("peripheral {} is derived from {}, which comes later or isn't there", child_text(peripheral, "name").unwrap_or_default(), from))?),
+                None => None,
+            };
+            let peripheral = read_peripheral(peripheral, properties, base)?;
+            peripherals.push(peripheral);
+        }
+    }
+    Ok(Device {
+        name: required_text(device, "name")?,
+        description: child_text(device, "description"),
+        width: child_number(device, "width")?.unwrap_or(32) as u32,
+        peripherals,
+    })
+}
+
+pub fn load(path: &Path) -> Result<Device, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// A board's SVD file, read the first time it's needed. Clones share what's been read.
+#[derive(Clone, Default)]
+pub struct LazySvd {
+    path: Option<PathBuf>,
+    device: Arc<OnceLock<Result<Arc<Device>, String>>>,
+}
+
+impl LazySvd {
+
+    pub fn new(path: PathBuf) -> Self {
+        Self { path: Some(path), device: Arc::default() }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The device, reading the file if it hasn't been read yet. None if there's no file.
+    pub fn get(&self) -> Option<Result<Arc<Device>, String>> {
+        let path = self.path.as_ref()?;
+        Some(self.device.get_or_init(|| load(path).map(Arc::new)).clone())
+    }
+}
+
+/// Show a device's peripherals, with their registers and fields, to be looked through.
+pub fn display(ui: &mut egui::Ui, device: &Device) {
+    ui.label(format!("{}: {} peripherals, {} interrupts", device.name, device.peripherals.len(), device.interrupts().len()));
+    for peripheral in device.peripherals.iter() {
+        let title = format!("{} @ 0x{:08x}", peripheral.name, peripheral.base_address);
+        egui::CollapsingHeader::new(title).id_source(("svd peripheral", &peripheral.name)).show(ui, |ui| {
+            if let Some(description) = &peripheral.description {
+                ui.weak(description);
+            }
+            for register in peripheral.registers.iter() {
+                let address = peripheral.base_address + register.address_offset;
+                egui::CollapsingHeader::new(format!("{} @ 0x{:08x}", register.name, address))
+                    .id_source(("svd register", &peripheral.name, &register.name))
+                    .show(ui, |ui| {
+                        let mut about = vec![format!("{} bits", register.size)];
+                        about.extend(register.access.map(|a| a.to_string()));
+                        about.extend(register.reset_value.map(|r| format!("resets to 0x{:x}", r)));
+                        ui.weak(about.join(", "));
+                        if let Some(description) = &register.description {
+                            ui.label(description);
+                        }
+                        egui::Grid::new(("svd fields", &peripheral.name, &register.name)).striped(true).show(ui, |ui| {
+                            for field in register.fields.iter() {
+                                ui.monospace(match field.bit_width {
+                                    1 => format!("[{}]", field.bit_offset),
+                                    width => format!("[{}:{}]", field.bit_offset + width - 1, field.bit_offset),
+                                });
+                                ui.label(&field.name);
+                                ui.weak(field.description.as_deref().unwrap_or_default());
+                                ui.end_row();
+                            }
+                        });
+                    });
+            }
+        });
+    }
+}
@@ -13,11 +13,20 @@ use usvg::{
     NodeKind,
     ImageKind,
 };
+use log::warn;
+
+use std::collections::VecDeque;
 use std::io::Cursor;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 use std::fs;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
 use std::vec::Vec;
 
+use crate::app::jobs::{self, JobContext};
+
 use std::borrow::Borrow;
 
 use image;
@@ -28,13 +37,205 @@ use egui::{
     Vec2,
 };
 
-/// A struct that holds the decoded SVG for use in egui.
+/// Where a board image is in being decoded.
+#[derive(Default)]
+enum ImageState {
+    #[default]
+    Missing,
+    /// The PNG, which hasn't been decoded yet
+    Encoded(Vec<u8>),
+    Decoding,
+    Ready(Arc<ColorImage>),
+    Failed,
+}
+
+/// A board image, which is decoded from its PNG the first time it's needed, in a job, so
+/// loading the boards doesn't wait for every image to be decoded. The clones of a board
+/// share its image.
+#[derive(Default, Clone)]
+pub struct BoardImage {
+    state: Arc<Mutex<ImageState>>,
+}
+
+impl BoardImage {
+
+    pub fn from_png(png: Vec<u8>) -> Self {
+        Self { state: Arc::new(Mutex::new(ImageState::Encoded(png))) }
+    }
+
+    /// The image, if it's been decoded. If it hasn't, it's queued to be decoded in the
+    /// job that decodes the board images, and the Ui is repainted when it's ready.
+    pub fn get(&self, ctx: &egui::Context) -> Option<Arc<ColorImage>> {
+        if let ImageState::Ready(image) = &*self.state.lock().unwrap() {
+            return Some(image.clone());
+        }
+        // it's marked as being decoded when it's queued, so it's only queued once
+        let png = self.start()?;
+        DecodeQueue::shared(ctx).push(ctx, vec![(self.clone(), png)]);
+        None
+    }
+
+    /// Queue images to be decoded in the job that decodes the board images, unless
+    /// they've been started on already.
+    pub fn decode_all(images: &[BoardImage], ctx: &egui::Context) {
+        let started: Vec<_> = images.iter().filter_map(|image| Some((image.clone(), image.start()?))).collect();
+        if !started.is_empty() {
+            DecodeQueue::shared(ctx).push(ctx, started);
+        }
+    }
+
+    /// Mark the image as being decoded, and take its PNG, unless it's been started on
+    /// already.
+    fn start(&self) -> Option<Vec<u8>> {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, ImageState::Encoded(_)) {
+            return None;
+        }
+        match std::mem::replace(&mut *state, ImageState::Decoding) {
+            ImageState::Encoded(png) => Some(png),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Decode the PNG taken by `start`. If the job was cancelled before it got to it, the
+    /// image is left to be decoded the next time it's needed.
+    fn finish(&self, png: Vec<u8>, job: Option<&JobContext>) -> Result<(), String> {
+        if job.is_some_and(JobContext::is_cancelled) {
+            *self.state.lock().unwrap() = ImageState::Encoded(png);
+            return Ok(());
+        }
+        let decoded = decode_png(&png);
+        let result = decoded.as_ref().map(|_| ()).map_err(|e| format!("couldn't decode a board image: {:?}", e));
+        *self.state.lock().unwrap() = match decoded {
+            Ok(image) => ImageState::Ready(Arc::new(image)),
+            Err(_) => ImageState::Failed,
+        };
+        if let Some(job) = job {
+            job.request_repaint();
+        }
+        result
+    }
+
+    /// Decode the image on this thread, unless it's been started on already. Returns
+    /// whether it was decoded here.
+    pub fn decode(&self) -> bool {
+        let Some(png) = self.start() else {
+            return false;
+        };
+        if let Err(e) = self.finish(png, None) {
+            warn!("{}", e);
+        }
+        true
+    }
+
+    /// The image, decoding it on this thread if needed, or waiting for it if it's being
+    /// decoded elsewhere.
+    pub fn wait(&self) -> Option<Arc<ColorImage>> {
+        self.decode();
+        loop {
+            match &*self.state.lock().unwrap() {
+                ImageState::Ready(image) => return Some(image.clone()),
+                ImageState::Decoding => (),
+                _ => return None,
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), ImageState::Ready(_))
+    }
+}
+
+const DECODE_QUEUE_ID: &str = "board image decode queue";
+
+/// The images waiting to be decoded, and whether there's a job decoding them. There's
+/// one for the app, kept in the egui Context, so all the images asked for while the job
+/// runs are decoded by it, rather than each getting a job of its own.
+#[derive(Clone, Default)]
+struct DecodeQueue {
+    state: Arc<Mutex<(VecDeque<(BoardImage, Vec<u8>)>, bool)>>,
+}
+
+impl DecodeQueue {
+
+    fn shared(ctx: &egui::Context) -> Self {
+        ctx.data_mut(|data| {
+            data.get_temp_mut_or_insert_with(DECODE_QUEUE_ID.into(), DecodeQueue::default).clone()
+        })
+    }
+
+    /// Queue images with the PNGs taken from them, and start the job if it isn't running.
+    fn push(&self, ctx: &egui::Context, images: Vec<(BoardImage, Vec<u8>)>) {
+        let mut state = self.state.lock().unwrap();
+        state.0.extend(images);
+        if state.1 {
+            return;
+        }
+        state.1 = true;
+        let queue = self.clone();
+        jobs::shared(ctx).spawn("decode the board images", Some(ctx), move |job| {
+            // the job only ends without clearing the flag if it panics, and then the next
+            // images queued need a job of their own
+            panic::catch_unwind(AssertUnwindSafe(|| queue.run(job))).unwrap_or_else(|e| {
+                queue.state.lock().unwrap_or_else(PoisonError::into_inner).1 = false;
+                panic::resume_unwind(e)
+            })
+        });
+    }
+
+    /// Decode the queued images until there are none left. The job stops once the queue
+    /// is empty, under the same lock images are queued with, so none are left behind. If
+    /// it's cancelled, the rest are decoded when they're next asked for.
+    fn run(&self, job: &JobContext) -> Result<(), String> {
+        let mut decoded = 0;
+        loop {
+            let (image, png, remaining) = {
+                let mut state = self.state.lock().unwrap();
+                if job.is_cancelled() {
+                    for (image, png) in state.0.drain(..) {
+                        *image.state.lock().unwrap() = ImageState::Encoded(png);
+                    }
+                }
+                match state.0.pop_front() {
+                    Some((image, png)) => (image, png, state.0.len()),
+                    None => {
+                        state.1 = false;
+                        return Ok(());
+                    },
+                }
+            };
+            job.set_progress(decoded as f32 / (decoded + remaining + 1) as f32);
+            // an image the decoder panics on has failed, rather than being waited on forever
+            match panic::catch_unwind(AssertUnwindSafe(|| image.finish(png, Some(job)))) {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => warn!("{}", e),
+                Err(_) => {
+                    warn!("the decoder panicked on a board image");
+                    *image.state.lock().unwrap_or_else(PoisonError::into_inner) = ImageState::Failed;
+                },
+            }
+            decoded += 1;
+        }
+    }
+}
+
+/// Decode a PNG into an egui ColorImage.
+fn decode_png(png: &[u8]) -> Result<ColorImage, Error> {
+    let reader = image::io::Reader::new(Cursor::new(png)).with_guessed_format().map_err(|_e| Error::ImageDecodeError)?;
+    let image = reader.decode().map_err(|_e| Error::ImageDecodeError)?;
+    // get the image size from the PNG itself
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(ColorImage::from_rgba_unmultiplied(size, &image.to_rgba8()))
+}
+
+/// A struct that holds the parsed SVG for use in egui.
 #[derive(Default, Clone)]
 pub struct SvgBoardInfo {
     /// The SVG size (should be in mm)
     pub physical_size: Vec2,
-    /// The egui ColorImage of the board. This can be any size in px.
-    pub image: ColorImage,
+    /// The image of the board. This can be any size in px.
+    pub image: BoardImage,
     /// A vector of egui Rects that represent the pin locations on the Board
     pub pin_rects: Vec<(String, Rect)>,
 }
@@ -65,7 +266,7 @@ impl SvgBoardInfo {
         };
     
         // iterate through the svg looking for elements
-        let mut board_image: Option<ColorImage> = None;
+        let mut board_image: Option<BoardImage> = None;
         for node in tree.root.descendants() {
             // first, look for the image
             match node.borrow().clone() {
@@ -73,19 +274,8 @@ impl SvgBoardInfo {
                     if let ImageKind::PNG(png_bytes) = img.kind.clone() {
                         //let size = [img.view_box.rect.width().round() as usize, img.view_box.rect.height() as usize];
                         let borrowed_bytes: &Vec<u8> = png_bytes.borrow();
-                        let png = match image::io::Reader::new(Cursor::new(borrowed_bytes)).with_guessed_format() {
-                            Ok(ok) => ok,
-                            Err(_e) => return Err(Error::ImageDecodeError),
-                        };
-                        let image = png.decode().unwrap();
-                        // get the image size from the PNG itself
-                        let size = [image.width() as usize, image.height() as usize];
-                        let image_bytes = image.to_rgba8();
-                        let color_image = ColorImage::from_rgba_unmultiplied(
-                            size,
-                            &image_bytes,
-                        );
-                        board_image = Some(color_image);
+                        // decoded when it's first shown
+                        board_image = Some(BoardImage::from_png(borrowed_bytes.clone()));
                     }
                 },
                 NodeKind::Path(path) => {
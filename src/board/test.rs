@@ -17,16 +17,22 @@ fn cli_cmd(str: &str) {
 mod board_tests {
     use std::collections::HashSet;
     use std::path::Path;
-    use eframe::glow::FALSE;
     use egui::TextBuffer;
     use crate::board;
-    use crate::board::get_boards;
+    use crate::board::{get_boards, Board, BoardLoadError};
+    use crate::board::units::{Current, Frequency, Memory, NumberLocale, Voltage};
     use crate::board::test::cli_cmd;
+    use crate::scratch::ScratchDir;
+
+    /// The boards that come with the app.
+    fn shipped_boards() -> Vec<Board> {
+        get_boards(Path::new("./iron-coder-boards")).unwrap_or_else(|report| panic!("{}", report))
+    }
 
     #[test]
     pub fn test_get_boards() {
-        let mut board_names: HashSet<&str> = HashSet::from(["Feather nRF52832", "Feather RP2040", "OLED Featherwing (128x64)", "PropMaker Featherwing", "PiTFT 3.2 with Capacitive Touch Screen", "MicroMod ESP32 Processor"]);
-        let boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let mut board_names: HashSet<&str> = HashSet::from(["Feather nRF52832", "Feather RP2040", "OLED Featherwing (128x64)", "PropMaker Featherwing", "PiTFT 3.2 with Capacitive Touch Screen", "MicroMod ESP32 Processor", "MicroMod RP2040"]);
+        let boards = shipped_boards();
         for board in boards {
             assert!(board_names.contains(board.get_name()));
             board_names.remove(board.get_name());
@@ -34,17 +40,37 @@ mod board_tests {
         assert!(board_names.is_empty());
     }
     #[test]
+    pub fn test_get_boards_report() {
+        // stray files and a missing directory don't stop the others loading
+        use std::fs;
+        let dir = ScratchDir::new("get-boards");
+        let board_dir = dir.join("Adafruit").join("Feather_RP2040");
+        fs::create_dir_all(&board_dir).unwrap();
+        fs::write(dir.join("README"), "boards by manufacturer").unwrap();
+        fs::write(dir.join("Adafruit").join("LICENSE"), "").unwrap();
+        fs::write(board_dir.join("NOTES"), "no extension").unwrap();
+        fs::write(board_dir.join("feather_rp2040.toml"), "name = \"Feather RP2040\"\nmanufacturer = \"Adafruit\"\n").unwrap();
+        fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("feather_rp2040.svg")).unwrap();
+        assert_eq!(get_boards(&dir).unwrap().len(), 1);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("nowhere"), dir.join("Adafruit").join("Broken")).unwrap();
+        assert_eq!(get_boards(&dir).unwrap().len(), 1);
+        let report = get_boards(&dir.join("nowhere")).unwrap_err();
+        assert!(report.boards.is_empty());
+        assert!(report.errors().next().unwrap().message.starts_with("couldn't read the directory"));
+    }
+    #[test]
     pub fn test_board_info() {
         // Ensure boards have crates associated with them.
-        let mut boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let mut boards = shipped_boards();
         for board in boards {
-            assert!(board.related_crates().unwrap().is_empty() == FALSE);
+            assert!(!board.related_crates().unwrap().is_empty());
         }
     }
     #[test]
     pub fn test_board_crates() {
         // Ensure crates don't have any errors.
-        let mut boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let mut boards = shipped_boards();
         let mut cmd = "cargo check --features".to_owned();
         for board in boards {
             for crates in board.related_crates().unwrap() {
@@ -53,4 +79,1344 @@ mod board_tests {
             }
         }
     }
+
+    const VALID_BOARD: &str = r#"
+        name = "Test Board"
+        manufacturer = "TestCo"
+        is_main_board = true
+        standard = "Feather"
+        ram = 264
+        flash = 2048
+        [[pinout]]
+        pins = ["sda", "scl"]
+        interface = { iface_type = "I2C", direction = "Bidirectional" }
+    "#;
+
+    #[test]
+    pub fn test_board_toml_bounds() {
+        assert!(Board::from_toml_str(VALID_BOARD).is_ok());
+        let negative_ram = VALID_BOARD.replace("ram = 264", "ram = -1");
+        assert!(matches!(Board::from_toml_str(&negative_ram), Err(BoardLoadError::TomlError(_))));
+        let bad_unit = VALID_BOARD.replace("ram = 264", r#"ram = "264 furlongs""#);
+        assert!(matches!(Board::from_toml_str(&bad_unit), Err(BoardLoadError::TomlError(_))));
+        let blank_name = VALID_BOARD.replace("Test Board", " ");
+        assert!(matches!(Board::from_toml_str(&blank_name), Err(BoardLoadError::InvalidField(_))));
+        let duplicate_pin = VALID_BOARD.replace(r#"["sda", "scl"]"#, r#"["sda", "sda"]"#);
+        assert!(matches!(Board::from_toml_str(&duplicate_pin), Err(BoardLoadError::InvalidPin(_))));
+        let blank_pin = VALID_BOARD.replace(r#""scl""#, r#"" ""#);
+        assert!(matches!(Board::from_toml_str(&blank_pin), Err(BoardLoadError::InvalidPin(_))));
+        assert!(matches!(Board::from_toml_str("name = 3"), Err(BoardLoadError::TomlError(_))));
+        let zephyr_board = format!("zephyr_board = \"nrf52_adafruit_feather\"\n{}", VALID_BOARD);
+        assert_eq!(Board::from_toml_str(&zephyr_board).unwrap().zephyr_board(), Some("nrf52_adafruit_feather"));
+        let spaced_target = format!("esp_idf_target = \"esp 32\"\n{}", VALID_BOARD);
+        assert!(matches!(Board::from_toml_str(&spaced_target), Err(BoardLoadError::InvalidField(_))));
+        let with_adc = |adc: &str| Board::from_toml_str(&format!("adc = {}\n{}", adc, VALID_BOARD));
+        let board = with_adc("{ resolution = 12, reference = 3.3 }").unwrap();
+        assert_eq!(board.adc().unwrap().resolution, 12);
+        assert!(matches!(with_adc("{ resolution = 0, reference = 3.3 }"), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with_adc("{ resolution = 12, reference = -1.0 }"), Err(BoardLoadError::InvalidField(_))));
+        // the timer clock falls back to the cpu clock
+        let timer_clock = format!("timer_clock = \"16 MHz\"\n{}", VALID_BOARD);
+        assert_eq!(Board::from_toml_str(&timer_clock).unwrap().timer_clock(), Some(Frequency::from_hz(16_000_000)));
+        let with_pwm = |pwm: &str| Board::from_toml_str(&format!("pwm = {}\n{}", pwm, VALID_BOARD));
+        let pwm = with_pwm(r#"{ counter_bits = 15, prescaler = { kind = "power_of_two", max = 128 }, hal = "nrf-hal" }"#).unwrap().pwm().unwrap();
+        assert_eq!(pwm.max_top(), 32766);
+        assert_eq!(pwm.prescaler.neighbours(5.0), [(4, 0), (8, 0)]);
+        assert!(matches!(with_pwm(r#"{ counter_bits = 15, prescaler = { kind = "power_of_two", max = 100 } }"#), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with_pwm(r#"{ counter_bits = 40, prescaler = { kind = "integer", max = 256 } }"#), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with_pwm(r#"{ counter_bits = 16, prescaler = { kind = "octal" } }"#), Err(BoardLoadError::TomlError(_))));
+        let with_watchdog = |watchdog: &str| Board::from_toml_str(&format!("watchdog = {}\n{}", watchdog, VALID_BOARD));
+        let watchdog = with_watchdog(r#"{ clock = "32.768 kHz", counter_bits = 32, min_count = 16, hal = "nrf-hal" }"#).unwrap().watchdog().unwrap();
+        assert_eq!((watchdog.max_divider, watchdog.window), (1, false));
+        assert!(matches!(with_watchdog(r#"{ clock = "1 MHz", counter_bits = 4 }"#), Err(BoardLoadError::InvalidField(_))));
+        let with_brown_out = |brown_out: &str| Board::from_toml_str(&format!("brown_out = {}\n{}", brown_out, VALID_BOARD));
+        let board = with_brown_out(r#"{ rail = "VDD", levels = [1.7, 1.8, 1.9], action = "interrupt" }"#).unwrap();
+        assert_eq!(board.brown_out().unwrap().level_at(1.75), Some(1));
+        assert!(matches!(with_brown_out(r#"{ rail = "VDD", levels = [1.8, 1.7] }"#), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with_brown_out(r#"{ rail = "VDD", levels = [1.7, 1.8], default = 2.0 }"#), Err(BoardLoadError::InvalidField(_))));
+        let with_sleep = |modes: &str| Board::from_toml_str(&format!("{}[sleep]\nactive_current = \"5 mA\"\n{}", VALID_BOARD, modes));
+        let board = with_sleep("[[sleep.modes]]\nname = \"off\"\nkind = \"off\"\ncurrent = \"1 uA\"\nwake_sources = [\"gpio\"]").unwrap();
+        assert!(board.sleep_modes().unwrap().modes[0].kind.wakes_through_reset());
+        assert!(matches!(with_sleep("[[sleep.modes]]\nname = \"off\"\nkind = \"off\"\ncurrent = \"1 uA\"\nwake_sources = []"), Err(BoardLoadError::InvalidField(_))));
+        let cpu_clock = format!("clock = \"125 MHz\"\n{}", VALID_BOARD);
+        assert_eq!(Board::from_toml_str(&cpu_clock).unwrap().timer_clock(), Some(Frequency::from_hz(125_000_000)));
+    }
+
+    #[test]
+    pub fn test_board_config_schema() {
+        let with_config = |config: &str| Board::from_toml_str(&format!("{}[[config]]\n{}", VALID_BOARD, config));
+        let board = with_config("name = \"LED_MODE\"\ntype = \"enum\"\nchoices = [\"Solid\", \"Blink\"]\ndefault = \"Blink\"").unwrap();
+        let option = &board.config_options()[0];
+        assert_eq!(option.type_name(), "LedMode");
+        assert_eq!(option.rust_type(), "LedMode");
+        // the default has to fit the option
+        assert!(with_config("name = \"LED_MODE\"\ntype = \"enum\"\nchoices = [\"Solid\"]\ndefault = \"Blink\"").is_err());
+        assert!(with_config("name = \"COUNT\"\ntype = \"int\"\ndefault = 300\nrust_type = \"u8\"").is_err());
+        assert!(with_config("name = \"COUNT\"\ntype = \"int\"\ndefault = true").is_err());
+        assert!(with_config("name = \"count\"\ntype = \"bool\"\ndefault = true").is_err());
+        assert!(with_config("name = \"COUNT\"\ntype = \"int\"\ndefault = 1\nrust_type = \"f32\"").is_err());
+        assert!(with_config("name = \"LED\"\ntype = \"bool\"\ndefault = true\nfeature = \"led strip\"").is_err());
+        assert!(matches!(with_config("name = \"LED\"\ntype = \"bool\"\ndefault = true\n[[config]]\nname = \"LED\"\ntype = \"bool\"\ndefault = false"),
+                         Err(BoardLoadError::InvalidField(_))));
+    }
+
+    #[test]
+    pub fn test_flash_layout() {
+        let layout = r#"
+            [flash_layout]
+            chip = "nRF52832_xxAA"
+            bootloader = { offset = "0 B", size = "48 KiB" }
+            slots = [
+                { name = "primary", offset = "48 KiB", size = "224 KiB" },
+                { name = "secondary", offset = "272 KiB", size = "224 KiB" },
+            ]
+            scratch = { offset = "496 KiB", size = "16 KiB" }
+        "#;
+        let with_layout = |layout: &str| Board::from_toml_str(&VALID_BOARD.replace("flash = 2048", "flash = \"512 KiB\"").replace("[[pinout]]", &format!("{}\n[[pinout]]", layout)));
+        let board = with_layout(layout).unwrap();
+        let flash_layout = board.flash_layout().unwrap();
+        assert_eq!(flash_layout.primary().unwrap().name, "primary");
+        assert_eq!(flash_layout.address(flash_layout.slot("secondary").unwrap()), 272 * 1024);
+        assert_eq!(flash_layout.header_size.bytes(), 0x200);
+        let names: Vec<&str> = flash_layout.regions().into_iter().map(|r| flash_layout.region_name(r)).collect();
+        assert_eq!(names, ["bootloader", "primary", "secondary", "scratch"]);
+        // overlapping regions, regions past the end of flash, and unnamed slots are rejected
+        assert!(matches!(with_layout(&layout.replace("\"272 KiB\"", "\"256 KiB\"")), Err(BoardLoadError::InvalidField(_))));
+        assert!(with_layout(&layout.replace("\"16 KiB\" }", "\"32 KiB\" }")).is_err());
+        assert!(with_layout(&layout.replace("name = \"secondary\"", "name = \"primary\"")).is_err());
+        assert!(with_layout(&layout.replace("slots = [", "slots = [\n{ offset = \"1 MiB\", size = \"0 B\" },")).is_err());
+    }
+
+    /// A small xorshift generator, so the property test is reproducible without extra deps.
+    struct XorShift(u64);
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    pub fn test_board_toml_never_panics() {
+        // mutate the valid board and the real board descriptions, and make sure every
+        // result is either an error or a board that passes validation
+        let mut seeds = vec![VALID_BOARD.to_string()];
+        for b in shipped_boards() {
+            seeds.push(toml::to_string(&b).unwrap());
+        }
+        let fragments = ["=", "[", "]", "[[pinout]]", "\"", "-", "ram = -9223372036854775808",
+                         "flash = 1e400", "pins = [\"\"]", "{", "}", ",", "\n", "\u{0}", "é", "#"];
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..4000 {
+            let mut input = seeds[rng.below(seeds.len())].clone().into_bytes();
+            for _ in 0..=rng.below(4) {
+                let at = rng.below(input.len() + 1);
+                match rng.below(3) {
+                    0 => input.truncate(at),
+                    1 => if at < input.len() { input[at] = rng.next() as u8 },
+                    _ => {
+                        let f = fragments[rng.below(fragments.len())];
+                        input.splice(at..at, f.bytes());
+                    },
+                }
+            }
+            let input = String::from_utf8_lossy(&input).to_string();
+            let result = std::panic::catch_unwind(|| Board::from_toml_str(&input));
+            match result {
+                Ok(Ok(b)) => {
+                    assert!(!b.name.trim().is_empty());
+                    assert!(b.pinout.iter().all(|m| m.pins.iter().all(|p| !p.is_empty())));
+                },
+                Ok(Err(_)) => (),
+                Err(_) => panic!("parsing panicked on input:\n{}", input),
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_units() {
+        // bare numbers are kilobytes, as in older board files
+        assert_eq!("264".parse::<Memory>().unwrap().bytes(), 264_000);
+        assert_eq!("264 KiB".parse::<Memory>().unwrap().bytes(), 264 * 1024);
+        assert_eq!("1.5mb".parse::<Memory>().unwrap().bytes(), 1_500_000);
+        assert!("-1 KB".parse::<Memory>().is_err());
+        assert!("8 MHz".parse::<Memory>().is_err());
+        assert_eq!("32.768 kHz".parse::<Frequency>().unwrap().hz(), 32_768);
+        // display is exact, so that it round trips
+        for s in ["264 KiB", "8 MB", "2000 KiB", "1 B"] {
+            assert_eq!(s.parse::<Memory>().unwrap().to_string(), s);
+        }
+        assert_eq!(Frequency::from_hz(125_000_000).to_string(), "125 MHz");
+        // formatting picks a readable unit and follows the locale
+        let en = NumberLocale::from_name("en_US.UTF-8");
+        let de = NumberLocale::from_name("de_DE.UTF-8");
+        assert_eq!(Memory::from_bytes(264 * 1024).format(&en), "264 KiB");
+        assert_eq!(Memory::from_bytes(1_500_000).format(&de), "1,5 MB");
+        assert_eq!(Memory::from_bytes(1_048_576_000).format(&en), "1,000 MiB");
+        assert_eq!(Frequency::from_hz(32_768).format(&de), "32,77 kHz");
+        assert_eq!("1.9 uA".parse::<Current>().unwrap().nanoamps(), 1_900);
+        assert_eq!("7.4mA".parse::<Current>().unwrap().to_string(), "7400 µA");
+        assert_eq!(Current::from_nanoamps(180_000).format(&en), "180 µA");
+        assert_eq!("3.3 V".parse::<Voltage>().unwrap().millivolts(), 3_300);
+        assert_eq!("1800mV".parse::<Voltage>().unwrap().to_string(), "1800 mV");
+        assert_eq!(Voltage::from_millivolts(3_300).format(&de), "3,3 V");
+    }
+
+    #[test]
+    pub fn test_board_electrical() {
+        use crate::board::display::electrical_summary;
+
+        let with = |fields: &str| Board::from_toml_str(&format!("name = \"Board\"\n{}", fields));
+        let board = with("operating_voltage = \"3.3 V\"\nlogic_level = 3.3\ntypical_current = \"20 mA\"").unwrap();
+        assert_eq!(board.operating_voltage(), Some(Voltage::from_millivolts(3_300)));
+        assert_eq!(board.logic_level(), Some(Voltage::from_millivolts(3_300)));
+        assert_eq!(board.typical_current(), Some(Current::from_nanoamps(20_000_000)));
+        let en = NumberLocale::from_name("en_US.UTF-8");
+        assert_eq!(electrical_summary(&board, &en), "3.3 V, 3.3 V logic, 20 mA typical");
+        assert_eq!(electrical_summary(&with("logic_level = \"5 V\"").unwrap(), &en), "5 V logic");
+        assert!(matches!(with("logic_level = \"0 V\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(with("operating_voltage = -3.3").is_err());
+        assert!(with("operating_voltage = \"3.3 A\"").is_err());
+        // the shipped boards all say what level their logic is at
+        for board in shipped_boards() {
+            assert!(board.logic_level().is_some(), "{} has no logic level", board.get_name());
+        }
+    }
+
+    #[test]
+    pub fn test_pin_definitions() {
+        use crate::board::pinout::InterfaceType::*;
+        let toml = "name = \"pins\"\n\n[[pinout]]\npins = [\"tx\", \"rx\"]\ninterface = { iface_type = \"UART\", direction = \"Bidirectional\" }\n\n\
+            [[pins]]\nname = \"a0\"\nfunction = \"GPIO26\"\nalternate_functions = [\"SPI1 SCK\", \"i2c1 sda\", \"PWM5 A\", \"ADC0\"]\n\n\
+            [[pins]]\nname = \"tx\"\nfunction = \"GPIO0\"\n\n[[pins]]\nname = \"gnd\"\nfunction = \"ground\"\n";
+        let board = Board::from_toml_str(toml).unwrap();
+        assert_eq!(board.pins().len(), 3);
+        assert_eq!(board.pin_capabilities("a0"), [GPIO, ADC, PWM, I2C, SPI]);
+        // the pinout adds the interfaces the pin is mapped to
+        assert_eq!(board.pin_capabilities("tx"), [GPIO, UART]);
+        assert_eq!(board.pin_capabilities("rx"), [UART]);
+        assert!(board.pin_capabilities("gnd").is_empty());
+        assert!(board.pin("d13").is_none());
+
+        let twice = "name = \"pins\"\n[[pins]]\nname = \"a0\"\n[[pins]]\nname = \"a0\"\n";
+        assert!(matches!(Board::from_toml_str(twice), Err(BoardLoadError::InvalidPin(_))));
+        assert!(matches!(Board::from_toml_str("name = \"pins\"\n[[pins]]\nname = \"\"\n"), Err(BoardLoadError::InvalidPin(_))));
+
+        // the shipped board describes the pins in its image
+        let boards = shipped_boards();
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let pin_rects = &feather.svg_board_info.as_ref().unwrap().pin_rects;
+        for pin in feather.pins() {
+            assert!(pin_rects.iter().any(|(name, _)| *name == pin.name), "{} isn't in the board image", pin.name);
+        }
+        assert_eq!(feather.pin("a3").unwrap().function, "GPIO29");
+        assert!(feather.pin_capabilities("a3").contains(&ADC));
+    }
+
+    #[test]
+    pub fn test_board_filter() {
+        use crate::board::filter::BoardFilter;
+        use crate::board::BoardStandards;
+        let boards: Vec<Board> = [
+            "name = \"Feather RP2040\"\nmanufacturer = \"Adafruit\"\nstandard = \"Feather\"\ncpu = \"Cortex-M0\"",
+            "name = \"Feather nRF52832\"\nmanufacturer = \"Adafruit\"\nstandard = \"Feather\"\ncpu = \"Cortex-M4\"",
+            "name = \"MicroMod RP2040\"\nmanufacturer = \"Sparkfun\"\nstandard = \"MicroMod\"\ncpu = \"Cortex-M0\"",
+            "name = \"PiTFT 3.2\"\nmanufacturer = \"Adafruit\"",
+        ].iter().map(|toml| Board::from_toml_str(toml).unwrap()).collect();
+
+        let mut filter = BoardFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(filter.apply(&boards), [0, 1, 2, 3]);
+        filter.name = " rp2040 ".to_string();
+        assert_eq!(filter.apply(&boards), [0, 2]);
+        filter.manufacturer = Some("Sparkfun".to_string());
+        assert_eq!(filter.apply(&boards), [2]);
+
+        let filter = BoardFilter { cpu: Some("Cortex-M0".to_string()), ..Default::default() };
+        assert_eq!(filter.apply(&boards), [0, 2]);
+        let filter = BoardFilter { standard: Some(BoardStandards::Feather), ..Default::default() };
+        assert_eq!(filter.apply(&boards), [0, 1]);
+        let filter = BoardFilter { standard: Some(BoardStandards::Arduino), ..Default::default() };
+        assert!(filter.apply(&boards).is_empty());
+    }
+
+    #[test]
+    pub fn test_board_tags() {
+        use crate::board::filter::{BoardFilter, tag_counts, valid_tag};
+        let boards: Vec<Board> = [
+            "name = \"Feather nRF52832\"\ntags = [\"bluetooth\", \"low-power\"]",
+            "name = \"MicroMod ESP32\"\ntags = [\"wifi\", \"bluetooth\"]",
+            "name = \"PiTFT 3.2\"",
+        ].iter().map(|toml| Board::from_toml_str(toml).unwrap()).collect();
+        assert_eq!(tag_counts(&boards).into_iter().collect::<Vec<_>>(), [("bluetooth", 2), ("low-power", 1), ("wifi", 1)]);
+
+        let mut filter = BoardFilter::default();
+        filter.toggle_tag("bluetooth");
+        assert!(!filter.is_empty());
+        assert_eq!(filter.apply(&boards), [0, 1]);
+        filter.toggle_tag("wifi");
+        assert_eq!(filter.apply(&boards), [1]);
+        filter.toggle_tag("wifi");
+        filter.toggle_tag("bluetooth");
+        assert!(filter.is_empty());
+
+        assert!(valid_tag("low-power") && valid_tag("lora"));
+        for bad in ["", "Wifi", "low power", "-lora", "display-"] {
+            assert!(!valid_tag(bad), "{}", bad);
+        }
+        assert!(matches!(Board::from_toml_str("name = \"Bare\"\ntags = [\"Low Power\"]"), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(Board::from_toml_str("name = \"Bare\"\ntags = [\"wifi\", \"wifi\"]"), Err(BoardLoadError::InvalidField(_))));
+        // every board that comes with Iron Coder is tagged
+        assert!(shipped_boards().iter().all(|b| !b.tags().is_empty()));
+    }
+
+    #[test]
+    pub fn test_board_filter_memory() {
+        use crate::board::filter::{BoardFilter, parse_minimum};
+        use crate::board::units::Memory;
+        let boards: Vec<Board> = [
+            "name = \"Feather RP2040\"\nmanufacturer = \"Adafruit\"\nram = \"264 KiB\"\nflash = \"8 MiB\"",
+            "name = \"Feather nRF52832\"\nmanufacturer = \"Adafruit\"\nram = \"64 KiB\"\nflash = \"512 KiB\"",
+            "name = \"PiTFT 3.2\"\nmanufacturer = \"Adafruit\"",
+        ].iter().map(|toml| Board::from_toml_str(toml).unwrap()).collect();
+
+        assert_eq!(parse_minimum(" "), Ok(None));
+        assert_eq!(parse_minimum("≥256 KB"), Ok(Some(Memory::from_bytes(256_000))));
+        assert_eq!(parse_minimum(">= 1 MiB"), Ok(Some(Memory::from_bytes(1 << 20))));
+        assert!(parse_minimum("lots").is_err());
+
+        let filter = BoardFilter { min_flash: "≥256 KB".to_string(), ..Default::default() };
+        assert!(!filter.is_empty());
+        assert_eq!(filter.apply(&boards), [0, 1]);
+        let filter = BoardFilter { min_ram: "128 KiB".to_string(), min_flash: "256 KB".to_string(), ..Default::default() };
+        assert_eq!(filter.apply(&boards), [0]);
+        let filter = BoardFilter { min_ram: "64 KiB".to_string(), ..Default::default() };
+        assert_eq!(filter.apply(&boards), [0, 1]);
+        // a minimum that's still being typed doesn't hide anything
+        let filter = BoardFilter { min_ram: "64 K".to_string(), ..Default::default() };
+        assert_eq!(filter.apply(&boards), [0, 1, 2]);
+    }
+
+    #[test]
+    pub fn test_board_wizard() {
+        use crate::board::wizard::{BoardDraft, WizardStep, file_safe};
+        use crate::board::BoardStandards;
+        assert_eq!(file_safe(" Feather  RP2040 (rev B) "), "Feather_RP2040_rev_B");
+        assert_eq!(file_safe("../.."), "");
+
+        let mut draft = BoardDraft::default();
+        assert!(draft.check_step(WizardStep::Identity).is_err());
+        draft.name = "Blinky Board".to_string();
+        draft.manufacturer = "Test Co".to_string();
+        draft.standard = Some(BoardStandards::Feather);
+        draft.cpu = "Cortex-M0".to_string();
+        draft.ram = "264 KiB".to_string();
+        draft.flash = "eight megs".to_string();
+        assert!(draft.check_step(WizardStep::Identity).is_ok());
+        assert!(draft.check_step(WizardStep::Hardware).is_err());
+        draft.flash = "8 MiB".to_string();
+        draft.related_crates = "smart-leds, ws2812-pio\nembedded-hal".to_string();
+        assert!(draft.check_step(WizardStep::Image).is_err());
+        assert!(draft.check_step(WizardStep::Review).is_err());
+
+        // the toml is the board that was described
+        let board = Board::from_toml_str(&draft.to_toml().unwrap()).unwrap();
+        assert_eq!(board.get_name(), "Blinky Board");
+        assert_eq!(board.manufacturer(), "Test Co");
+        assert_eq!(board.standard(), Some(&BoardStandards::Feather));
+        assert_eq!(board.ram(), Some(Memory::from_bytes(264 * 1024)));
+        assert_eq!(board.flash(), Some(Memory::from_bytes(8 << 20)));
+        assert_eq!(board.related_crates().unwrap(), ["smart-leds", "ws2812-pio", "embedded-hal"]);
+
+        // a photo is put in an SVG the board loads with
+        let dir = ScratchDir::new("board-wizard");
+        let png = dir.join("photo.png");
+        image::RgbaImage::from_pixel(40, 20, image::Rgba([0, 128, 0, 255])).save(&png).unwrap();
+        draft.image = Some(png);
+        draft.width_mm = 60.0;
+        assert!(draft.check_step(WizardStep::Review).is_ok());
+        let boards_dir = dir.join("boards");
+        let path = draft.write(&boards_dir).unwrap();
+        assert_eq!(path, boards_dir.join("Test_Co").join("Blinky_Board").join("blinky_board.toml"));
+        assert!(draft.write(&boards_dir).is_err());
+        let boards = get_boards(&boards_dir).unwrap();
+        assert_eq!(boards.len(), 1);
+        let info = boards[0].svg_board_info.as_ref().unwrap();
+        assert_eq!(info.physical_size, egui::vec2(60.0, 30.0));
+    }
+
+    #[test]
+    pub fn test_board_diagnostics() {
+        use crate::board::diagnostics::check_toml;
+        use crate::project::backend::Severity;
+        let path = Path::new("boards/Test/Blinky/blinky.toml");
+        assert!(check_toml(path, "name = \"Blinky\"\nmanufacturer = \"Test\"\nram = \"264 KiB\"").is_empty());
+
+        // a wrong type is pointed at
+        let wrong_type = check_toml(path, "name = \"Blinky\"\nmanufacturer = \"Test\"\nis_main_board = \"yes\"\n");
+        assert_eq!(wrong_type.len(), 1);
+        assert_eq!(wrong_type[0].severity, Severity::Error);
+        assert_eq!((wrong_type[0].line, wrong_type[0].column), (Some(3), Some(17)));
+        assert_eq!(wrong_type[0].field.as_deref(), Some("is_main_board"));
+        assert!(wrong_type[0].to_string().starts_with("boards/Test/Blinky/blinky.toml:3:17: error: is_main_board: "), "{}", wrong_type[0]);
+        let syntax = check_toml(path, "name = \"Blinky\"\n[pinout\n");
+        assert_eq!(syntax[0].line, Some(2));
+
+        // unknown keys and missing fields
+        let found = check_toml(path, "nmae = \"Blinky\"\ncpu = \"Cortex-M0\"\n\n[extras]\nleds = 2\n");
+        let summary: Vec<_> = found.iter().map(|d| (d.severity, d.field.as_deref(), d.line)).collect();
+        assert_eq!(summary, [
+            (Severity::Error, Some("name"), None),
+            (Severity::Warning, Some("manufacturer"), None),
+            (Severity::Warning, Some("nmae"), Some(1)),
+            (Severity::Warning, Some("extras"), Some(4)),
+        ]);
+
+        // a bad pin is found in the file
+        let pins = check_toml(path, "name = \"Blinky\"\nmanufacturer = \"Test\"\n[[pinout]]\npins = [\"tx\", \"tx\"]\ninterface = { iface_type = \"UART\", direction = \"Output\" }\n");
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].line, Some(4));
+
+        // the boards that come with the app are fine
+        let (boards, problems) = board::get_boards_with_diagnostics(Path::new("./iron-coder-boards"));
+        assert_eq!(boards.len(), shipped_boards().len());
+        // the two without a drawing load, with a warning
+        assert!(problems.iter().all(|p| p.severity == Severity::Warning && p.message.ends_with("so the board isn't drawn")), "{:?}", problems);
+        let mut undrawn: Vec<String> = problems.iter().map(|p| p.path.file_name().unwrap().to_string_lossy().to_string()).collect();
+        undrawn.sort();
+        assert_eq!(undrawn, ["micromod_esp32.toml", "prop-maker.toml"]);
+    }
+
+    #[test]
+    pub fn test_board_export() {
+        use std::io::{Read, Write};
+        use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+        use crate::board::export::{self, FORMAT_VERSION};
+        let boards = shipped_boards();
+        let json: serde_json::Value = serde_json::from_str(&export::to_json(&boards).unwrap()).unwrap();
+        assert_eq!(json["format_version"], FORMAT_VERSION);
+        let exported = json["boards"].as_array().unwrap();
+        assert_eq!(exported.len(), boards.len());
+        let rp2040 = exported.iter().find(|b| b["name"] == "Feather RP2040").unwrap();
+        assert_eq!(rp2040["manufacturer"], "Adafruit");
+        assert_eq!(rp2040["has_image"], true);
+        assert!(rp2040["examples"].is_array());
+        // the things only the app needs aren't exported
+        assert!(rp2040.get("bsp_path").is_none() && rp2040.get("svg_board_info").is_none());
+
+        let pages = export::BoardPages::new(&boards).unwrap();
+        let (status, content_type, body) = pages.respond("GET", "/boards/Adafruit/Feather%20RP2040");
+        assert_eq!((status, content_type), ("200 OK", "application/json"));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap()["name"], "Feather RP2040");
+        assert_eq!(pages.respond("GET", "/boards/Feather%20RP2040").0, "404 Not Found");
+        assert_eq!(pages.respond("GET", "/boards/Adafruit/Nope").0, "404 Not Found");
+        assert_eq!(pages.respond("GET", "/").0, "404 Not Found");
+        assert_eq!(pages.respond("POST", "/boards").0, "405 Method Not Allowed");
+
+        // boards that share a name are each served under their own route
+        let twins = [
+            Board::from_toml_str("name = \"Pico\"\nmanufacturer = \"Raspberry Pi\"").unwrap(),
+            Board::from_toml_str("name = \"Pico\"\nmanufacturer = \"Clone Co\"").unwrap(),
+            Board::from_toml_str("name = \"Pico\"\nmanufacturer = \"Raspberry Pi\"\nrevision = \"rev 2\"").unwrap(),
+        ];
+        let twin_pages = export::BoardPages::new(&twins).unwrap();
+        for (route, manufacturer, revision) in [
+            ("/boards/Raspberry%20Pi/Pico", "Raspberry Pi", serde_json::Value::Null),
+            ("/boards/Clone%20Co/Pico", "Clone Co", serde_json::Value::Null),
+            ("/boards/Raspberry%20Pi/Pico/rev%202", "Raspberry Pi", serde_json::Value::from("rev 2")),
+        ] {
+            let (status, _, body) = twin_pages.respond("GET", route);
+            assert_eq!(status, "200 OK", "{}", route);
+            let board: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!((&board["manufacturer"], board.get("revision").unwrap_or(&serde_json::Value::Null)), (&serde_json::Value::from(manufacturer), &revision));
+        }
+
+        let address = export::serve(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), &boards).unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET /boards HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(body).unwrap()["boards"].as_array().unwrap().len(), boards.len());
+    }
+
+    #[test]
+    pub fn test_board_registry() {
+        use std::fs;
+        use std::sync::{Arc, Mutex};
+        use crate::app::jobs::{JobScheduler, JobState};
+        use crate::board::registry::*;
+        let entry = |name: &str, dir: &str, version: &str, files: &[&str]| RegistryEntry {
+            name: name.to_string(),
+            manufacturer: "Adafruit".to_string(),
+            dir: dir.to_string(),
+            version: version.to_string(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        };
+        // boards can't be written outside of their directory, and need a toml and svg
+        let parse = |entries: Vec<RegistryEntry>| RegistryIndex::parse(&serde_json::to_string(&RegistryIndex { boards: entries }).unwrap());
+        assert!(parse(vec![entry("Evil", "..", "1", &["evil.toml", "evil.svg"])]).is_err());
+        assert!(parse(vec![entry("Evil", "Evil", "1", &["../evil.toml", "evil.svg"])]).is_err());
+        assert!(parse(vec![entry("Bare", "Bare", "1", &["bare.toml"])]).is_err());
+        let rp2040 = entry("Feather RP2040", "Feather RP2040", "2", &["feather_rp2040.toml", "feather_rp2040.svg"]);
+        assert!(parse(vec![rp2040.clone(), rp2040.clone()]).is_err());
+        assert_eq!(file_url("https://example.com/boards/index.json", &rp2040, "feather_rp2040.toml"),
+                   "https://example.com/boards/Adafruit/Feather%20RP2040/feather_rp2040.toml");
+
+        // a registry in a local directory
+        let dir = ScratchDir::new("board-registry");
+        let (remote, boards_dir) = (dir.join("remote"), dir.join("boards"));
+        let source = Path::new("./iron-coder-boards/Adafruit/Feather_RP2040");
+        fs::create_dir_all(remote.join("Adafruit").join("Feather RP2040")).unwrap();
+        for file in ["feather_rp2040.toml", "feather_rp2040.svg"] {
+            fs::copy(source.join(file), remote.join("Adafruit").join("Feather RP2040").join(file)).unwrap();
+        }
+        fs::create_dir_all(remote.join("Adafruit").join("Broken")).unwrap();
+        fs::write(remote.join("Adafruit").join("Broken").join("broken.toml"), "name = \"\"").unwrap();
+        fs::write(remote.join("Adafruit").join("Broken").join("broken.svg"), "").unwrap();
+        fs::create_dir_all(boards_dir.join("Adafruit").join("Mine")).unwrap();
+        let index = RegistryIndex {
+            boards: vec![
+                rp2040,
+                entry("Broken", "Broken", "1", &["broken.toml", "broken.svg"]),
+                entry("Mine", "Mine", "1", &["mine.toml", "mine.svg"]),
+            ],
+        };
+        let index_url = remote.join("index.json").display().to_string();
+        fs::write(&index_url, serde_json::to_string(&index).unwrap()).unwrap();
+        let index = fetch_index(&index_url).unwrap();
+        let statuses: Vec<EntryStatus> = index.boards.iter().map(|e| status(e, &boards_dir, &Installed::load(&boards_dir))).collect();
+        assert_eq!(statuses, [EntryStatus::New, EntryStatus::New, EntryStatus::Local]);
+
+        let run_sync = |index: RegistryIndex| {
+            let result = Arc::new(Mutex::new(None));
+            let (url, boards, out) = (index_url.clone(), boards_dir.clone(), result.clone());
+            let scheduler = JobScheduler::default();
+            let id = scheduler.spawn("sync", None, move |job| {
+                *out.lock().unwrap() = Some(sync(&url, &index, &boards, job));
+                Ok(())
+            });
+            while scheduler.state(id) == Some(JobState::Running) {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            let report = result.lock().unwrap().take();
+            report.unwrap().unwrap()
+        };
+        let report = run_sync(index.clone());
+        assert_eq!(report.added, ["Feather RP2040"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "Broken");
+        assert!(!boards_dir.join("Adafruit").join("Broken").exists());
+        assert_eq!(get_boards(&boards_dir).unwrap().iter().map(|b| b.get_name()).collect::<Vec<_>>(), ["Feather RP2040"]);
+        assert_eq!(Installed::load(&boards_dir).boards.get("Adafruit/Feather RP2040").map(String::as_str), Some("2"));
+
+        // a new version is an update, and nothing else is downloaded again
+        let mut newer = index.clone();
+        newer.boards[0].version = "3".to_string();
+        assert_eq!(status(&newer.boards[0], &boards_dir, &Installed::load(&boards_dir)), EntryStatus::Outdated("2".to_string()));
+        let report = run_sync(newer);
+        assert_eq!(report.updated, ["Feather RP2040"]);
+        assert!(report.added.is_empty());
+    }
+
+    #[test]
+    pub fn test_board_image_decoding() {
+        use std::fs;
+        use crate::app::jobs;
+        use crate::board::svg_reader::BoardImage;
+        let boards = shipped_boards();
+        let board = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let image = &board.svg_board_info.as_ref().unwrap().image;
+        // loading the boards doesn't decode their images
+        assert!(!image.is_ready());
+
+        // the first look starts decoding in the background, which clones of the board share
+        let ctx = egui::Context::default();
+        let copy = board.clone();
+        assert!(image.get(&ctx).is_none());
+        let decoded = copy.svg_board_info.as_ref().unwrap().image.wait().unwrap();
+        assert!(decoded.width() > 0 && decoded.height() > 0);
+        assert!(image.is_ready());
+        assert_eq!(image.get(&ctx).unwrap().size, decoded.size);
+        assert!(!image.decode());
+
+        // an image that isn't a PNG just doesn't show
+        let broken = BoardImage::from_png(b"not a png".to_vec());
+        assert!(broken.wait().is_none());
+        assert!(broken.get(&ctx).is_none() && !broken.is_ready());
+        assert!(BoardImage::default().wait().is_none());
+
+        // decoding runs as a job, which is only started once however often it's asked for
+        let ctx = egui::Context::default();
+        let image = BoardImage::from_png(fs::read("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.png").unwrap());
+        assert!(image.get(&ctx).is_none() && image.get(&ctx).is_none());
+        image.wait();
+        let decoding: Vec<_> = jobs::shared(&ctx).jobs().into_iter().filter(|j| j.name == "decode the board images").collect();
+        assert_eq!(decoding.len(), 1);
+        assert!(image.get(&ctx).is_some());
+
+        // a board load's images are decoded in one job, which the board widgets share
+        let ctx = egui::Context::default();
+        let boards = shipped_boards();
+        board::decode_images(&boards, &ctx);
+        let images: Vec<_> = boards.iter().filter_map(|b| b.svg_board_info.as_ref()).map(|info| &info.image).collect();
+        assert!(images.len() > 1);
+        for image in &images {
+            image.get(&ctx);
+        }
+        assert!(images.iter().all(|image| image.wait().is_some()));
+        let decoding: Vec<_> = jobs::shared(&ctx).jobs().into_iter().filter(|j| j.name == "decode the board images").collect();
+        assert_eq!(decoding.len(), 1);
+    }
+    #[test]
+    pub fn test_schematic_symbol() {
+        use crate::board::schematic::{is_power_pin, SchematicSymbol, Side};
+        assert!(is_power_pin("gnd", "") && is_power_pin("3v3", "3.3 V out") && is_power_pin("usb", "USB 5 V"));
+        assert!(!is_power_pin("d5", "GPIO7") && !is_power_pin("rst", "reset"));
+
+        let boards = shipped_boards();
+        let board = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let symbol = SchematicSymbol::new(board);
+        // every pin of the image is on the symbol, so connections show on either view
+        let mut image_pins: Vec<String> = board.svg_board_info.as_ref().unwrap().pin_rects.iter().map(|(n, _)| n.clone()).collect();
+        let mut symbol_pins: Vec<String> = symbol.pin_rects().into_iter().map(|(n, _)| n).collect();
+        image_pins.sort();
+        symbol_pins.sort();
+        assert_eq!(image_pins, symbol_pins);
+        // power pins are at the top left, and the sides are balanced
+        assert_eq!(symbol.pins[0].side, Side::Left);
+        assert!(["gnd", "3v3", "bat", "usb"].contains(&symbol.pins[0].name.as_str()));
+        let left = symbol.pins.iter().filter(|p| p.side == Side::Left).count();
+        assert!(left.abs_diff(symbol.pins.len() - left) <= 1);
+        // the pins end outside the body, and inside the symbol
+        let whole = egui::Rect::from_min_size(egui::Pos2::ZERO, symbol.size);
+        for pin in symbol.pins.iter() {
+            assert!(!symbol.body.contains(pin.rect.center()));
+            assert!(whole.expand(1.0).contains_rect(pin.rect));
+        }
+        // no two pins are in the same place
+        for (i, a) in symbol.pins.iter().enumerate() {
+            assert!(symbol.pins[i + 1..].iter().all(|b| !a.rect.intersects(b.rect)));
+        }
+    }
+    #[test]
+    pub fn test_board_comparison() {
+        use crate::board::compare::{spec_rows, BoardComparison, MAX_COMPARED};
+        let boards = shipped_boards();
+        let find = |name: &str| boards.iter().find(|b| b.get_name() == name).unwrap();
+        let (rp2040, nrf52832, micromod) = (find("Feather RP2040"), find("Feather nRF52832"), find("MicroMod RP2040"));
+        let rows = spec_rows(&[rp2040, nrf52832]);
+        let row = |spec: &str| rows.iter().find(|r| r.spec == spec).unwrap().clone();
+        assert_eq!(row("CPU").values, ["Cortex-M0", "Cortex-M4"]);
+        assert_eq!(row("RAM").values, ["264 KiB", "64 KiB"]);
+        assert_eq!(row("RAM").best, Some(0));
+        assert_eq!(row("clock").best, Some(0));
+        // the same ADC resolution isn't better on either
+        assert_eq!(row("ADC").best, None);
+        assert_eq!(row("pins").values[1], "-");
+        assert!(row("peripherals").values[0].contains("I2C"));
+        // a tie for the most isn't a best
+        let rows = spec_rows(&[rp2040, nrf52832, micromod]);
+        assert_eq!(rows.iter().find(|r| r.spec == "clock").unwrap().best, None);
+
+        let mut comparison = BoardComparison::default();
+        for board in boards.iter().take(MAX_COMPARED) {
+            assert!(comparison.toggle(board));
+        }
+        assert!(comparison.is_full());
+        assert!(!comparison.toggle(&boards[MAX_COMPARED]));
+        assert!(comparison.toggle(&boards[0]) && !comparison.is_picked(&boards[0]));
+        assert_eq!(comparison.boards(&boards).len(), MAX_COMPARED - 1);
+        // boards that are gone aren't compared
+        assert_eq!(comparison.boards(&boards[..1]).len(), 0);
+    }
+    #[test]
+    pub fn test_board_favorites() {
+        use crate::board::favorites::BoardFavorites;
+        let boards = shipped_boards();
+        let mut favorites = BoardFavorites::default();
+        assert!(favorites.toggle(&boards[2]) && favorites.toggle(&boards[0]));
+        assert!(favorites.is_favorite(&boards[0]) && !favorites.is_favorite(&boards[1]));
+        // favorites come out of the boards shown, in the order they're shown
+        let shown: Vec<usize> = (0..boards.len()).collect();
+        let (starred, rest) = favorites.split(&shown, &boards);
+        assert_eq!(starred, [0, 2]);
+        assert_eq!(rest.len(), boards.len() - 2);
+        assert_eq!(favorites.split(&[1, 2], &boards), (vec![2], vec![1]));
+        // they're kept between sessions
+        let saved = toml::to_string(&favorites).unwrap();
+        assert_eq!(toml::from_str::<BoardFavorites>(&saved).unwrap(), favorites);
+        assert!(toml::from_str::<BoardFavorites>("").unwrap().is_empty());
+        assert!(!favorites.toggle(&boards[2]));
+        assert_eq!(favorites.len(), 1);
+    }
+    #[test]
+    pub fn test_board_sorting() {
+        use crate::board::sorting::{BoardOrder, BoardSorting, MAX_RECENT};
+        let boards = [
+            Board::from_toml_str("name = \"b\"\nmanufacturer = \"Zeta\"\nram = \"264 KB\"\nflash = \"8 MB\"").unwrap(),
+            Board::from_toml_str("name = \"C\"\nmanufacturer = \"Acme\"").unwrap(),
+            Board::from_toml_str("name = \"a\"\nmanufacturer = \"acme\"\nram = \"64 KB\"\nflash = \"512 KB\"").unwrap(),
+        ];
+        let sorted = |sorting: &BoardSorting| {
+            let mut shown = vec![0, 1, 2];
+            sorting.sort(&mut shown, &boards);
+            shown
+        };
+        let mut sorting = BoardSorting::default();
+        assert_eq!(sorted(&sorting), [2, 0, 1]);
+        sorting.order = BoardOrder::Manufacturer;
+        assert_eq!(sorted(&sorting), [2, 1, 0]);
+        // the boards that don't say how much memory they have go last
+        sorting.order = BoardOrder::Ram;
+        assert_eq!(sorted(&sorting), [0, 2, 1]);
+        sorting.order = BoardOrder::Flash;
+        assert_eq!(sorted(&sorting), [0, 2, 1]);
+        sorting.order = BoardOrder::RecentlyUsed;
+        assert_eq!(sorted(&sorting), [2, 0, 1]);
+        sorting.used(&boards[1]);
+        sorting.used(&boards[0]);
+        sorting.used(&boards[1]);
+        assert_eq!(sorting.recent(), ["Acme/C", "Zeta/b"]);
+        assert_eq!(sorted(&sorting), [1, 0, 2]);
+        // boards with the same name from different manufacturers are told apart
+        let other_b = Board::from_toml_str("name = \"b\"\nmanufacturer = \"Acme\"").unwrap();
+        sorting.used(&other_b);
+        assert_eq!(sorting.recent(), ["Acme/b", "Acme/C", "Zeta/b"]);
+        assert_eq!(sorted(&sorting), [1, 0, 2]);
+        // boards picked before keys were used are still recent
+        let mut old = toml::from_str::<BoardSorting>("order = \"recently_used\"\nrecent = [\"b\"]").unwrap();
+        let mut shown = vec![0, 1, 2];
+        old.sort(&mut shown, &boards);
+        assert_eq!(shown, [0, 2, 1]);
+        old.used(&boards[0]);
+        assert_eq!(old.recent(), ["Zeta/b"]);
+
+        // the order and the recent boards are kept between sessions
+        let saved = toml::to_string(&sorting).unwrap();
+        assert!(saved.contains("order = \"recently_used\""), "{}", saved);
+        assert_eq!(toml::from_str::<BoardSorting>(&saved).unwrap(), sorting);
+        assert_eq!(toml::from_str::<BoardSorting>("").unwrap(), BoardSorting::default());
+        for i in 0..MAX_RECENT + 5 {
+            sorting.used(&Board::from_toml_str(&format!("name = \"board {}\"", i)).unwrap());
+        }
+        assert_eq!(sorting.recent().len(), MAX_RECENT);
+        assert_eq!(sorting.recent()[0], format!("/board {}", MAX_RECENT + 4));
+    }
+    #[test]
+    pub fn test_board_peripherals() {
+        use crate::board::peripherals::Peripherals;
+        use crate::board::wizard::BoardDraft;
+        let boards = shipped_boards();
+        let rp2040 = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap().peripherals().unwrap();
+        assert_eq!(rp2040, Peripherals { uart: 2, i2c: 2, spi: 2, adc_channels: 4, usb: true, wifi: false, ble: false });
+        assert_eq!(rp2040.to_string(), "2 UART, 2 I2C, 2 SPI, 4 ADC channels, USB");
+        assert_eq!(Peripherals::default().to_string(), "none");
+        // a project that needs a radio can't use the RP2040
+        let nrf52832 = boards.iter().find(|b| b.get_name() == "Feather nRF52832").unwrap().peripherals().unwrap();
+        let needs = Peripherals { i2c: 1, ble: true, ..Default::default() };
+        assert!(nrf52832.covers(&needs) && !rp2040.covers(&needs));
+        assert!(rp2040.covers(&Peripherals::default()));
+        assert!(Board::from_toml_str("name = \"Blank\"\nmanufacturer = \"Test\"\nis_main_board = true").unwrap().peripherals().is_none());
+
+        // the wizard writes them in their own table
+        let draft = BoardDraft {
+            name: "Radio Board".to_string(),
+            manufacturer: "Test Co".to_string(),
+            related_crates: "embedded-hal".to_string(),
+            peripherals: Peripherals { uart: 1, spi: 1, wifi: true, ..Default::default() },
+            ..Default::default()
+        };
+        let toml = draft.to_toml().unwrap();
+        assert!(toml.contains("[peripherals]\nuart = 1\nspi = 1\nwifi = true\n"));
+        assert_eq!(Board::from_toml_str(&toml).unwrap().peripherals(), Some(draft.peripherals));
+    }
+    #[test]
+    pub fn test_board_revisions() {
+        use std::fs;
+        use crate::board::diagnostics::check_toml;
+        use crate::board::favorites::BoardFavorites;
+        let rev_a = Board::from_toml_str("name = \"Feather RP2040\"\nrevision = \"rev A\"").unwrap();
+        let rev_b = Board::from_toml_str("name = \"Feather RP2040\"\nversion = \"rev B\"").unwrap();
+        let unrevised = Board::from_toml_str("name = \"Feather RP2040\"").unwrap();
+        assert_eq!(rev_b.revision(), Some("rev B"));
+        assert_eq!((rev_a.label(), unrevised.label()), ("Feather RP2040 (rev A)".to_string(), "Feather RP2040".to_string()));
+        assert!(rev_a != rev_b && rev_a != unrevised);
+        assert_eq!(rev_a, Board::from_toml_str("name = \"Feather RP2040\"\nrevision = \"rev A\"").unwrap());
+        assert_eq!(HashSet::from([rev_a.clone(), rev_b.clone(), unrevised.clone()]).len(), 3);
+        assert!(matches!(Board::from_toml_str("name = \"Feather RP2040\"\nrevision = \" \""), Err(BoardLoadError::InvalidField(_))));
+        assert!(check_toml(Path::new("b.toml"), "name = \"Feather RP2040\"\nmanufacturer = \"Adafruit\"\nversion = \"2\"").is_empty());
+        // revisions are starred apart
+        let mut favorites = BoardFavorites::default();
+        favorites.toggle(&rev_a);
+        assert!(favorites.is_favorite(&rev_a) && !favorites.is_favorite(&rev_b) && !favorites.is_favorite(&unrevised));
+
+        // two files for the same board load it once, and two revisions load both
+        let dir = ScratchDir::new("board-revisions");
+        let source = Path::new("./iron-coder-boards/Adafruit/Feather_RP2040");
+        let write = |folder: &str, revision: Option<&str>| {
+            let board_dir = dir.join("Adafruit").join(folder);
+            fs::create_dir_all(&board_dir).unwrap();
+            let mut toml = "name = \"Feather RP2040\"\nmanufacturer = \"Adafruit\"\n".to_string();
+            if let Some(revision) = revision {
+                toml += &format!("revision = \"{}\"\n", revision);
+            }
+            fs::write(board_dir.join("feather_rp2040.toml"), toml).unwrap();
+            fs::copy(source.join("feather_rp2040.svg"), board_dir.join("feather_rp2040.svg")).unwrap();
+        };
+        write("Feather_RP2040", None);
+        write("Feather_RP2040_copy", None);
+        let (boards, problems) = board::get_boards_with_diagnostics(&dir);
+        assert_eq!(boards.len(), 1);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("already described by another file"), "{}", problems[0]);
+        // the files are walked in order of their folders, so the first folder's file wins
+        assert!(boards[0].file().unwrap().starts_with(dir.join("Adafruit/Feather_RP2040/")));
+        assert!(problems[0].path.starts_with(dir.join("Adafruit/Feather_RP2040_copy")));
+        write("Feather_RP2040_copy", Some("rev B"));
+        let (boards, problems) = board::get_boards_with_diagnostics(&dir);
+        let mut labels: Vec<String> = boards.iter().map(|b| b.label()).collect();
+        labels.sort();
+        assert_eq!(labels, ["Feather RP2040", "Feather RP2040 (rev B)"]);
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+    #[test]
+    pub fn test_board_identity() {
+        use std::fs;
+        use crate::board::favorites::BoardFavorites;
+        // clones of a board by other manufacturers aren't the same board
+        let adafruit = Board::from_toml_str("name = \"Feather\"\nmanufacturer = \"Adafruit\"").unwrap();
+        let clone = Board::from_toml_str("name = \"Feather\"\nmanufacturer = \"Clones Inc\"").unwrap();
+        assert_eq!(adafruit.label(), clone.label());
+        assert!(adafruit != clone);
+        assert_eq!(adafruit, Board::from_toml_str("name = \"Feather\"\nmanufacturer = \"Adafruit\"\ncpu = \"RP2040\"").unwrap());
+        assert_eq!(HashSet::from([adafruit.clone(), clone.clone(), adafruit.clone()]).len(), 2);
+        assert_eq!(adafruit.key(), "Adafruit/Feather");
+        // they're starred apart, and a star from before they were told apart still counts
+        let mut favorites = BoardFavorites::default();
+        favorites.toggle(&adafruit);
+        assert!(favorites.is_favorite(&adafruit) && !favorites.is_favorite(&clone));
+        let old: BoardFavorites = toml::from_str("names = [\"Feather\"]").unwrap();
+        assert!(old.is_favorite(&adafruit) && old.is_favorite(&clone));
+
+        // both load, with a warning that they're only told apart by their manufacturer
+        let dir = ScratchDir::new("board-identity");
+        for manufacturer in ["Adafruit", "Clones Inc"] {
+            let board_dir = dir.join(manufacturer).join("Feather");
+            fs::create_dir_all(&board_dir).unwrap();
+            fs::write(board_dir.join("feather.toml"), format!("name = \"Feather\"\nmanufacturer = \"{}\"\n", manufacturer)).unwrap();
+            fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("feather.svg")).unwrap();
+        }
+        let (boards, problems) = board::get_boards_with_diagnostics(&dir);
+        assert_eq!(boards.len(), 2);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("only told apart by their manufacturer"), "{}", problems[0]);
+        // and each has a texture of its own
+        let ctx = egui::Context::default();
+        let textures: Vec<egui::TextureId> = boards.iter().map(|b| {
+            b.svg_board_info.as_ref().unwrap().image.wait();
+            crate::app::texture_cache::board_texture(&ctx, b).unwrap().id()
+        }).collect();
+        assert_ne!(textures[0], textures[1]);
+    }
+    #[test]
+    pub fn test_board_quickstart() {
+        use crate::board::quickstart::*;
+        let boards = shipped_boards();
+        let rp2040 = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let quickstart = rp2040.quickstart().unwrap();
+        assert_eq!(quickstart.steps[0].command.as_deref(), Some("rustup target add thumbv6m-none-eabi"));
+        let blinky = quickstart.steps.iter().find(|s| s.example.is_some()).unwrap();
+        assert!(step_example(rp2040, blinky).unwrap().ends_with("blinky"));
+        let missing = QuickStartStep { title: "Run it".to_string(), example: Some("nothing".to_string()), ..Default::default() };
+        assert_eq!(step_example(rp2040, &missing), None);
+        assert!(matches!(Board::from_toml_str("name = \"Bare\"\nquickstart = { steps = [] }"), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(Board::from_toml_str("name = \"Bare\"\n[[quickstart.steps]]\ntitle = \" \""), Err(BoardLoadError::InvalidField(_))));
+
+        // the quick start is shown the first time the board is picked, and keeps its ticks
+        let mut window = QuickStartWindow::default();
+        let other = boards.iter().find(|b| b.quickstart().is_none()).unwrap();
+        window.board_selected(other);
+        assert_eq!(window.showing(), None);
+        window.board_selected(rp2040);
+        assert_eq!(window.showing(), Some("Adafruit/Feather RP2040"));
+        window.progress.set_done(rp2040, 2, true);
+        window.progress.set_done(rp2040, 0, true);
+        assert!(window.progress.is_done(rp2040, 2) && !window.progress.is_done(rp2040, 1));
+        assert_eq!(window.progress.done_count(rp2040), 2);
+        let saved = toml::to_string(&window.progress).unwrap();
+        let progress: QuickStartProgress = toml::from_str(&saved).unwrap();
+        assert_eq!(progress, window.progress);
+        let mut window = QuickStartWindow::default();
+        window.progress = progress;
+        window.board_selected(rp2040);
+        assert_eq!(window.showing(), None);
+        window.progress.reset(rp2040);
+        assert!(window.progress.has_seen(rp2040));
+        assert_eq!(window.progress.done_count(rp2040), 0);
+    }
+    #[test]
+    pub fn test_board_svd() {
+        use std::fs;
+        use crate::board::svd::*;
+        const SVD: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<device schemaVersion="1.1">
+  <name>TESTCHIP</name>
+  <width>32</width>
+  <size>32</size>
+  <access>read-write</access>
+  <resetValue>0x00000000</resetValue>
+  <peripherals>
+    <peripheral>
+      <name>UART0</name>
+      <description>Serial port</description>
+      <groupName>UART</groupName>
+      <baseAddress>0x40034000</baseAddress>
+      <interrupt><name>UART0_IRQ</name><value>20</value></interrupt>
+      <registers>
+        <register>
+          <name>DR</name>
+          <description>Data
+            register</description>
+          <addressOffset>0x0</addressOffset>
+          <fields>
+            <field><name>DATA</name><bitOffset>0</bitOffset><bitWidth>8</bitWidth></field>
+            <field><name>FE</name><lsb>8</lsb><msb>8</msb><access>read-only</access></field>
+          </fields>
+        </register>
+        <register>
+          <name>FR</name>
+          <addressOffset>0x18</addressOffset>
+          <access>read-only</access>
+          <resetValue>0x90</resetValue>
+          <fields><field><name>BUSY</name><bitRange>[3:3]</bitRange></field></fields>
+        </register>
+        <cluster>
+          <dim>2</dim>
+          <dimIncrement>0x8</dimIncrement>
+          <name>CH%s</name>
+          <addressOffset>0x100</addressOffset>
+          <register><name>CTRL</name><addressOffset>0x4</addressOffset><size>16</size></register>
+        </cluster>
+        <register>
+          <dim>3</dim>
+          <dimIncrement>4</dimIncrement>
+          <dimIndex>A-C</dimIndex>
+          <name>PAD[%s]</name>
+          <addressOffset>0x200</addressOffset>
+        </register>
+      </registers>
+    </peripheral>
+    <peripheral derivedFrom="UART0">
+      <name>UART1</name>
+      <baseAddress>0x40038000</baseAddress>
+      <interrupt><name>UART1_IRQ</name><value>21</value></interrupt>
+    </peripheral>
+  </peripherals>
+</device>"#;
+        let device = parse(SVD).unwrap();
+        assert_eq!((device.name.as_str(), device.width, device.peripherals.len()), ("TESTCHIP", 32, 2));
+        let uart0 = device.peripheral("UART0").unwrap();
+        let names: Vec<&str> = uart0.registers.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["DR", "FR", "CH0_CTRL", "CH1_CTRL", "PADA", "PADB", "PADC"]);
+        let dr = uart0.register("DR").unwrap();
+        assert_eq!(dr.description.as_deref(), Some("Data register"));
+        assert_eq!((dr.size, dr.access, dr.reset_value), (32, Some(Access::ReadWrite), Some(0)));
+        assert_eq!(dr.fields[0].mask(), 0xff);
+        assert_eq!((dr.fields[1].bit_offset, dr.fields[1].bit_width, dr.fields[1].access), (8, 1, Some(Access::ReadOnly)));
+        let fr = uart0.register("FR").unwrap();
+        assert_eq!((fr.access, fr.reset_value, fr.fields[0].mask()), (Some(Access::ReadOnly), Some(0x90), 0x8));
+        assert_eq!((uart0.register("CH1_CTRL").unwrap().address_offset, uart0.register("CH1_CTRL").unwrap().size), (0x10c, 16));
+        assert_eq!(uart0.register("PADC").unwrap().address_offset, 0x208);
+        // a derived peripheral has the registers of the one it's derived from
+        let uart1 = device.peripheral("UART1").unwrap();
+        assert_eq!(uart1.group_name.as_deref(), Some("UART"));
+        assert_eq!(device.register_address("UART1", "FR"), Some(0x40038018));
+        assert_eq!(device.interrupts().iter().map(|i| i.value).collect::<Vec<_>>(), [20, 21]);
+
+        assert_eq!(parse_number("#1x1"), Ok(0b101));
+        assert!(parse("<device><name>X</name><peripherals><peripheral derivedFrom=\"Y\"><name>X</name><baseAddress>0</baseAddress></peripheral></peripherals></device>").is_err());
+        assert!(parse("<device><name>X</name><peripherals><peripheral><name>P</name><baseAddress>0</baseAddress><registers><register><name>R%s</name><addressOffset>0</addressOffset><dim>100000</dim><dimIncrement>4</dimIncrement></register></registers></peripheral></peripherals></device>").is_err());
+        assert!(parse("<board/>").is_err());
+
+        // a board reads its SVD file the first time it's asked for it
+        let dir = ScratchDir::new("board-svd");
+        let board_dir = dir.join("Test").join("Chip");
+        fs::create_dir_all(&board_dir).unwrap();
+        fs::write(board_dir.join("chip.toml"), "name = \"Chip\"\nmanufacturer = \"Test\"\nsvd = \"chip.svd\"\n").unwrap();
+        fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("chip.svg")).unwrap();
+        let boards = get_boards(&dir).unwrap();
+        assert!(boards[0].svd_path().unwrap().ends_with("chip.svd"));
+        fs::write(board_dir.join("chip.svd"), SVD).unwrap();
+        let clone = boards[0].clone();
+        assert_eq!(boards[0].svd().unwrap().unwrap().name, "TESTCHIP");
+        // clones share what was read
+        fs::remove_file(board_dir.join("chip.svd")).unwrap();
+        assert!(clone.svd().unwrap().is_ok());
+        assert!(get_boards(&dir).unwrap()[0].svd().unwrap().is_err());
+        assert!(Board::from_toml_str("name = \"Bare\"").unwrap().svd().is_none());
+    }
+    #[test]
+    pub fn test_board_gallery() {
+        use std::fs;
+        use std::path::PathBuf;
+        let dir = ScratchDir::new("board-gallery");
+        let board_dir = dir.join("Test").join("Chip");
+        fs::create_dir_all(&board_dir).unwrap();
+        fs::write(board_dir.join("chip.toml"), "name = \"Chip\"\nmanufacturer = \"Test\"\n").unwrap();
+        fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("chip.svg")).unwrap();
+        let boards = get_boards(&dir).unwrap();
+        assert!(boards[0].gallery().is_empty());
+        assert_eq!(boards[0].image_views(), ["board"]);
+
+        // pictures named after the board file are found, and anything else is left alone
+        let png = |path: PathBuf| image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255])).save(path).unwrap();
+        png(board_dir.join("chip.pinout.png"));
+        png(board_dir.join("chip.bottom.png"));
+        png(board_dir.join("chip.png"));
+        png(board_dir.join("other.top.png"));
+        png(board_dir.join("chip.board.png"));
+        fs::write(board_dir.join("chip.notes.txt"), "not a picture").unwrap();
+        let boards = get_boards(&dir).unwrap();
+        assert_eq!(boards[0].image_views(), ["board", "bottom", "pinout"]);
+        let decoded = boards[0].gallery()[0].image.wait().unwrap();
+        assert_eq!(decoded.size, [4, 2]);
+    }
+    #[test]
+    pub fn test_board_kinds() {
+        use crate::board::BoardKind;
+        use crate::board::wizard::BoardDraft;
+        let kind = |toml: &str| Board::from_toml_str(&format!("name = \"Part\"\n{}", toml)).map(|b| b.kind());
+        assert_eq!(kind("kind = \"display\"").unwrap(), BoardKind::Display);
+        assert_eq!(kind("kind = \"breakout\"").unwrap(), BoardKind::Peripheral);
+        // older board files only say whether they're a main board
+        assert_eq!(kind("is_main_board = true").unwrap(), BoardKind::MainBoard);
+        assert_eq!(kind("is_main_board = false").unwrap(), BoardKind::Peripheral);
+        assert_eq!(kind("").unwrap(), BoardKind::Peripheral);
+        assert!(kind("kind = \"main_board\"\nis_main_board = true").is_ok());
+        assert!(matches!(kind("kind = \"display\"\nis_main_board = true"), Err(BoardLoadError::InvalidField(_))));
+        assert!(kind("kind = \"sensor\"").is_err());
+
+        let boards = shipped_boards();
+        let shipped = |name: &str| boards.iter().find(|b| b.get_name() == name).unwrap().kind();
+        assert_eq!(shipped("Feather RP2040"), BoardKind::MainBoard);
+        assert_eq!(shipped("OLED Featherwing (128x64)"), BoardKind::Display);
+
+        let draft = BoardDraft { name: "OLED".to_string(), manufacturer: "Test Co".to_string(), kind: BoardKind::Display, ..Default::default() };
+        let board = Board::from_toml_str(&draft.to_toml().unwrap()).unwrap();
+        assert_eq!(board.kind(), BoardKind::Display);
+        assert!(!board.is_main_board());
+    }
+    #[test]
+    pub fn test_board_fitting() {
+        use crate::board::fitting::{check, suggestions, Fit};
+        let board = |name: &str, kind: &str, standard: &str| {
+            let standard = if standard.is_empty() { String::new() } else { format!("standard = \"{}\"\n", standard) };
+            Board::from_toml_str(&format!("name = \"{}\"\nkind = \"{}\"\n{}", name, kind, standard)).unwrap()
+        };
+        let feather = board("Feather", "main_board", "Feather");
+        let wing = board("Wing", "display", "Feather");
+        let thing_plus = board("Thing Plus", "main_board", "ThingPlus");
+        let micromod = board("Processor", "main_board", "MicroMod");
+        let carrier = board("Carrier", "peripheral", "MicroMod");
+        let shield = board("Shield", "peripheral", "Arduino");
+        let loose = board("Breakout", "peripheral", "");
+        assert_eq!(check(&feather, &wing).unwrap().fit, Fit::Stacks);
+        assert!(check(&feather, &wing).unwrap().reason.contains("FeatherWings"));
+        assert_eq!(check(&micromod, &carrier).unwrap().fit, Fit::PlugsIn);
+        assert_eq!(check(&thing_plus, &wing).unwrap().fit, Fit::SameFootprint);
+        assert!(check(&feather, &shield).is_none());
+        assert!(check(&feather, &loose).is_none());
+        assert!(check(&micromod, &wing).is_none());
+        // an add-on doesn't go on an add-on
+        assert!(check(&wing, &feather).is_none());
+
+        let candidates = vec![feather.clone(), wing.clone(), thing_plus.clone(), micromod.clone(), carrier.clone(), shield.clone(), loose.clone()];
+        let names = |found: Vec<(usize, crate::board::fitting::Match)>| found.into_iter().map(|(i, _)| candidates[i].get_name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(suggestions(Some(&feather), &[], &candidates)), ["Wing"]);
+        assert!(suggestions(Some(&feather), std::slice::from_ref(&wing), &candidates).is_empty());
+        // without a main board, the ones that fit the add-ons are suggested
+        let found = suggestions(None, &[wing.clone(), loose.clone()], &candidates);
+        assert_eq!(found[1].1.fit, Fit::SameFootprint);
+        assert_eq!(names(found), ["Feather", "Thing Plus"]);
+        assert!(suggestions(None, &[wing.clone(), carrier.clone()], &candidates).is_empty());
+        assert!(suggestions(None, &[loose], &candidates).is_empty());
+
+        let boards = shipped_boards();
+        let rp2040 = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let found = suggestions(Some(rp2040), &[], &boards);
+        assert!(found.iter().any(|(i, _)| boards[*i].get_name() == "OLED Featherwing (128x64)"));
+    }
+
+    #[test]
+    pub fn test_board_watcher() {
+        use std::fs;
+        use std::path::{Path, PathBuf};
+        use std::time::{Duration, Instant};
+        use crate::board::watcher::*;
+
+        assert!(is_board_file(Path::new("boards/Us/board.toml")));
+        assert!(!is_board_file(Path::new("boards/Us/.board.toml.swp")));
+        assert!(!is_board_file(Path::new("boards/Us/board.toml~")));
+
+        let dir = ScratchDir::new("watch");
+        fs::create_dir_all(dir.join("Us")).unwrap();
+        let missing = dir.join("missing");
+        let mut watcher = BoardWatcher::new(&[dir.to_path_buf(), missing], None).unwrap();
+        assert_eq!(watcher.dirs(), [dir.to_path_buf()]);
+        assert_eq!(watcher.changed(), None);
+
+        let board = dir.join("Us").join("board.toml");
+        fs::write(&board, "name = \"Board\"").unwrap();
+        fs::write(dir.join("Us").join(".board.toml.swp"), "").unwrap();
+        let started = Instant::now();
+        let changed: Vec<PathBuf> = loop {
+            if let Some(changed) = watcher.changed() {
+                break changed;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "the change wasn't seen");
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        // reloaded once, after it's quiet, for the board file alone
+        assert!(started.elapsed() >= QUIET_TIME);
+        assert!(changed.iter().all(|p| p.ends_with("Us/board.toml")) && !changed.is_empty());
+        assert_eq!(watcher.changed(), None);
+    }
+
+    #[test]
+    pub fn test_flash_metadata() {
+        use crate::board::flashing::*;
+
+        let with = |fields: &str| Board::from_toml_str(&format!("name = \"Board\"\n{}", fields));
+        let board = with("target_triple = \"thumbv6m-none-eabi\"\nchip_name = \"RP2040\"\nflash_method = \"probe-rs\"").unwrap();
+        assert_eq!(board.target_triple(), Some("thumbv6m-none-eabi"));
+        assert_eq!(board.chip_name(), Some("RP2040"));
+        assert_eq!(board.flash_method(), Some(FlashMethod::ProbeRs));
+        assert_eq!(FlashMethod::ProbeRs.runner(board.chip_name()).unwrap(), "probe-rs run --chip RP2040");
+        assert_eq!(FlashMethod::DfuUtil.runner(None), None);
+        assert!(with("").unwrap().target_triple().is_none());
+
+        // the flash layout's chip is used if the board doesn't name one
+        let layout = with("flash_method = \"probe-rs\"\n[flash_layout]\nchip = \"nRF52840_xxAA\"\nslots = [{ name = \"primary\", offset = \"48 KiB\", size = \"224 KiB\" }]").unwrap();
+        assert_eq!(layout.chip_name(), Some("nRF52840_xxAA"));
+
+        assert!(with("flash_method = \"uf2\"").is_ok());
+        assert!(matches!(with("flash_method = \"probe-rs\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("flash_method = \"jlink\""), Err(BoardLoadError::TomlError(_))));
+        assert!(matches!(with("target_triple = \"thumbv6m\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("chip_name = \"RP 2040\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(valid_target_triple("riscv32imac-unknown-none-elf") && valid_target_triple("thumbv8m.main-none-eabihf"));
+        assert!(!valid_target_triple("x86_64--linux") && !valid_target_triple("thumbv7em none eabihf"));
+    }
+
+    #[test]
+    pub fn test_board_bundle() {
+        use std::fs;
+        use crate::board::bundle::*;
+
+        // a board goes to JSON and back
+        let rp2040 = shipped_boards().into_iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        assert!(rp2040.file().unwrap().ends_with("Feather_RP2040/feather_rp2040.toml"));
+        let back = Board::from_json(&rp2040.to_json().unwrap()).unwrap();
+        assert_eq!(back, rp2040);
+        assert_eq!(back.manufacturer(), "Adafruit");
+        assert!(back.file().is_none());
+        assert!(matches!(Board::from_json("{\"name\": \"\"}"), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(Board::from_json("not json"), Err(BoardLoadError::JsonError(_))));
+
+        // a bundled board is imported into another boards directory, and loads from it
+        let dir = ScratchDir::new("board-bundle");
+        let board_dir = dir.join("from").join("Us").join("Board");
+        fs::create_dir_all(board_dir.join("examples").join("blink")).unwrap();
+        fs::create_dir_all(board_dir.join("target")).unwrap();
+        fs::write(board_dir.join("board.toml"), "name = \"Our Board\"\nmanufacturer = \"Us\"\n").unwrap();
+        fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("board.svg")).unwrap();
+        fs::write(board_dir.join("examples").join("blink").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(board_dir.join("target").join("big.bin"), "build output").unwrap();
+        fs::write(board_dir.join(".board.toml.swp"), "").unwrap();
+        let ours = get_boards(&dir.join("from")).unwrap().remove(0);
+        let file = dir.join("our_board.json");
+        export(&ours, &file).unwrap();
+        let bundle = BoardBundle::parse(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!((bundle.manufacturer.as_str(), bundle.dir.as_str()), ("Us", "Board"));
+        assert_eq!(bundle.board["name"], "Our Board");
+        let paths: Vec<&str> = bundle.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["board.svg", "board.toml", "examples/blink/main.rs"]);
+
+        let to = dir.join("to");
+        assert_eq!(import(&file, &to, false).unwrap(), to.join("Us").join("Board"));
+        let imported = get_boards(&to).unwrap();
+        assert_eq!(imported, vec![ours.clone()]);
+        assert_eq!(imported[0].examples().len(), 1);
+        assert!(import(&file, &to, false).unwrap_err().starts_with("there's already a board"));
+        assert!(import(&file, &to, true).is_ok());
+
+        // bundles can't write outside of the board's directory, and need a board that loads
+        let mut bad = bundle.clone();
+        bad.files[0].path = "../../escaped.svg".to_string();
+        assert!(BoardBundle::parse(&bad.to_json().unwrap()).is_err());
+        let mut bad = bundle.clone();
+        bad.dir = "..".to_string();
+        assert!(BoardBundle::parse(&bad.to_json().unwrap()).is_err());
+        let mut bad = bundle.clone();
+        bad.files.retain(|f| f.path != "board.toml");
+        assert_eq!(BoardBundle::parse(&bad.to_json().unwrap()).unwrap_err(), "the bundle has no board file");
+        let mut bad = bundle.clone();
+        bad.format_version = BUNDLE_VERSION + 1;
+        assert!(BoardBundle::parse(&bad.to_json().unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn test_board_rtc() {
+        use crate::board::rtc::*;
+        let boards = shipped_boards();
+        let nrf = boards.iter().find(|b| b.get_name() == "Feather nRF52832").unwrap().rtc().unwrap();
+        assert_eq!((nrf.source, nrf.hal), (RtcClockSource::Crystal, Some(RtcHal::NrfHal)));
+        assert_eq!(nrf.clock.unwrap().hz(), 32_768);
+        let rp2040 = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap().rtc().unwrap();
+        assert_eq!(rp2040.source, RtcClockSource::MainClock);
+
+        let with = |rtc: &str| Board::from_toml_str(&format!("name = \"Board\"\n[rtc]\n{}", rtc));
+        assert_eq!(with("source = \"internal-rc\"").unwrap().rtc().unwrap().clock, None);
+        // a crystal's frequency has to be given
+        assert!(matches!(with("source = \"crystal\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("source = \"main-clock\"\nclock = \"0 Hz\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("source = \"sundial\""), Err(BoardLoadError::TomlError(_))));
+        assert!(RtcClockSource::InternalRc.typical_ppm() > RtcClockSource::Crystal.typical_ppm());
+    }
+
+    #[test]
+    pub fn test_board_documentation() {
+        use std::fs;
+        use crate::board::display::file_url;
+        let boards = shipped_boards();
+        let rp2040 = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        assert_eq!(rp2040.datasheet_url(), Some("https://datasheets.raspberrypi.com/rp2040/rp2040-datasheet.pdf"));
+        assert!(rp2040.docs_url().unwrap().starts_with("https://learn.adafruit.com/"));
+        assert_eq!(rp2040.datasheet_file(), None);
+
+        let with = |field: &str| Board::from_toml_str(&format!("name = \"Board\"\n{}", field));
+        assert!(matches!(with("docs_url = \"learn.adafruit.com\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("datasheet_url = \"javascript:alert(1)\""), Err(BoardLoadError::InvalidField(_))));
+        // the offline copy has to be in the board's directory, so it's shared along with it
+        assert!(matches!(with("datasheet = \"../other/datasheet.pdf\""), Err(BoardLoadError::InvalidField(_))));
+        assert!(matches!(with("datasheet = \"/home/me/datasheet.pdf\""), Err(BoardLoadError::InvalidField(_))));
+        // with no board file, there's nowhere to find the copy
+        assert_eq!(with("datasheet = \"docs/datasheet.pdf\"").unwrap().datasheet_file(), None);
+
+        let dir = ScratchDir::new("board-documentation");
+        let board_dir = dir.join("Acme").join("Widget");
+        fs::create_dir_all(board_dir.join("docs")).unwrap();
+        fs::write(board_dir.join("widget.toml"), "name = \"Widget\"\nmanufacturer = \"Acme\"\ndatasheet = \"docs/widget.pdf\"\n").unwrap();
+        fs::write(board_dir.join("docs").join("widget.pdf"), b"%PDF-1.4").unwrap();
+        fs::copy("./iron-coder-boards/Adafruit/Feather_RP2040/feather_rp2040.svg", board_dir.join("widget.svg")).unwrap();
+        let boards = get_boards(&dir).unwrap();
+        let widget = &boards[0];
+        assert_eq!(widget.datasheet_file(), Some(board_dir.join("docs").join("widget.pdf")));
+        let bundle = crate::board::bundle::BoardBundle::new(widget).unwrap();
+        assert!(bundle.files.iter().any(|f| f.path == "docs/widget.pdf"));
+
+        assert_eq!(file_url(Path::new("/boards/widget.pdf")), "file:///boards/widget.pdf");
+        assert_eq!(file_url(Path::new("C:\\boards\\widget.pdf")), "file:///C:/boards/widget.pdf");
+        // each segment is percent-encoded, so local datasheets with any name open
+        assert_eq!(file_url(Path::new("/my boards/#1 100%/Größe.pdf")), "file:///my%20boards/%231%20100%25/Gr%C3%B6%C3%9Fe.pdf");
+        assert_eq!(file_url(Path::new("C:\\My Boards\\data sheet.pdf")), "file:///C:/My%20Boards/data%20sheet.pdf");
+    }
+
+    #[test]
+    pub fn test_board_partitions() {
+        use crate::board::partitions::PartitionKind;
+
+        let feather = shipped_boards().into_iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let table = feather.partition_table().unwrap();
+        let config = table.partition("config").unwrap();
+        assert_eq!(config.kind, PartitionKind::Nvs);
+        assert_eq!(table.address(config), 0x10000000 + 7680 * 1024);
+
+        let with = |fields: &str| Board::from_toml_str(&format!("name = \"Board\"\nflash = \"1 MiB\"\n{}", fields));
+        let table = |partitions: &str| format!("[partition_table]\npartitions = [{}]", partitions);
+        assert!(with(&table("{ name = \"config\", kind = \"nvs\", offset = \"960 KiB\", size = \"64 KiB\" }")).is_ok());
+        // past the end of flash, overlapping, unnamed and empty partitions are rejected
+        assert!(matches!(with(&table("{ name = \"config\", kind = \"nvs\", offset = \"1000 KiB\", size = \"64 KiB\" }")), Err(BoardLoadError::InvalidField(_))));
+        assert!(with(&table("{ name = \"a\", kind = \"nvs\", offset = \"0 B\", size = \"8 KiB\" }, { name = \"b\", kind = \"data\", offset = \"4 KiB\", size = \"8 KiB\" }")).is_err());
+        assert!(with(&table("{ name = \"\", kind = \"nvs\", offset = \"0 B\", size = \"8 KiB\" }")).is_err());
+        assert!(with(&table("{ name = \"a\", kind = \"littlefs\", offset = \"0 B\", size = \"0 B\" }")).is_err());
+        // and so are partitions in the bootloader's slots
+        let layout = "[flash_layout]\nslots = [{ name = \"primary\", offset = \"0 B\", size = \"512 KiB\" }]\n";
+        assert!(with(&format!("{}{}", layout, table("{ name = \"config\", kind = \"nvs\", offset = \"512 KiB\", size = \"64 KiB\" }"))).is_ok());
+        assert!(with(&format!("{}{}", layout, table("{ name = \"config\", kind = \"nvs\", offset = \"448 KiB\", size = \"128 KiB\" }"))).is_err());
+    }
+
+    #[test]
+    pub fn test_esp_partition_csv() {
+        use crate::board::partitions::*;
+
+        let table = PartitionTable::esp_default();
+        let csv = table.to_esp_csv().unwrap();
+        assert_eq!(csv, "# Name, Type, SubType, Offset, Size, Flags\nnvs, data, nvs, 0x9000, 24K,\nphy_init, data, phy, 0xf000, 4K,\nfactory, app, factory, 0x10000, 1M,\n");
+        assert_eq!(PartitionTable::from_esp_csv(&csv).unwrap(), table);
+
+        // partitions without an offset go after the one before, apps on a 64 KiB page
+        let ota = PartitionTable::from_esp_csv("nvs, data, nvs, , 0x4000\notadata, data, ota, , 8K\nphy_init, data, phy, , 4K\nota_0, app, ota_0, , 1M\nota_1, app, ota_1, , 1M\n").unwrap();
+        let offsets: Vec<u64> = ota.partitions.iter().map(|p| p.offset.bytes()).collect();
+        assert_eq!(offsets, [0x9000, 0xd000, 0xf000, 0x10000, 0x110000]);
+        assert_eq!(ota.partitions[3].kind, PartitionKind::OtaApp);
+        assert!(ota.to_esp_csv().unwrap().contains("ota_1, app, ota_1, 0x110000, 1M,"));
+        assert!(PartitionTable::from_esp_csv("nvs, data, nvs, 0x9000").is_err());
+        assert!(PartitionTable::from_esp_csv("nvs, blob, nvs, 0x9000, 24K").is_err());
+
+        // the chip has to be able to boot from the table
+        let mut bad = table.clone();
+        bad.partitions[0].offset = Memory::from_bytes(0x8000);
+        assert!(bad.validate_esp().is_err());
+        let mut bad = table.clone();
+        bad.partitions[2].offset = Memory::from_bytes(0x18000);
+        assert!(bad.validate_esp().is_err());
+        let mut bad = table.clone();
+        bad.partitions.pop();
+        assert!(bad.validate_esp().is_err());
+        let mut bad = ota.clone();
+        bad.partitions.remove(1);
+        assert!(bad.validate_esp().is_err());
+        // and partitions start and end on flash sectors
+        let mut bad = table.clone();
+        bad.partitions[0].size = Memory::from_bytes(0x5800);
+        assert!(bad.validate(None, None).is_err());
+    }
+
+    #[test]
+    pub fn test_example_manifests() {
+        use std::fs;
+        use crate::board::examples::*;
+
+        let feather = shipped_boards().into_iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let titles: Vec<String> = feather.examples().iter().map(|e| e.title()).collect();
+        assert_eq!(titles, ["Blinky", "neopixel"]);
+        let blinky = &feather.examples()[0];
+        assert_eq!(blinky.name(), "blinky");
+        assert!(blinky.description().unwrap().contains("red LED"));
+        // an example without a manifest goes by its directory name
+        assert_eq!(feather.examples()[1].manifest, None);
+
+        let dir = ScratchDir::new("examples");
+        for example in ["rainbow", "broken"] {
+            fs::create_dir_all(dir.join("examples").join(example)).unwrap();
+        }
+        fs::write(dir.join("examples").join("rainbow").join(MANIFEST_FILE_NAME),
+            "title = \"NeoPixel rainbow\"\ndescription = \"Cycles the NeoPixel through the rainbow.\"\nrequired_features = [\"neopixel\"]\n").unwrap();
+        // a misspelled field makes the manifest unreadable, rather than being ignored
+        fs::write(dir.join("examples").join("broken").join(MANIFEST_FILE_NAME), "titel = \"Broken\"\n").unwrap();
+        let examples = find_examples(&dir.join("board.toml"));
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[0].title(), "broken");
+        assert_eq!(examples[0].manifest, None);
+        assert_eq!(examples[1].title(), "NeoPixel rainbow");
+        assert_eq!(examples[1].required_features(), ["neopixel"]);
+    }
 }
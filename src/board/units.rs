@@ -0,0 +1,438 @@
+//! Title: Iron Coder Board Module - Units
+//! Description: Quantities with units that show up in board descriptions, i.e. memory
+//!   sizes, clock frequencies, supply currents and voltages. In a board toml file they can
+//!   be written with a unit suffix ("264 KiB", "8 MB", "125 MHz", "1.5 uA", "3.3 V"), and in the Ui
+//!   they are formatted with the most readable unit and the user's number format.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// How to write numbers for the user, i.e. "1,234.5" or "1.234,5".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberLocale {
+    pub decimal_separator: char,
+    pub group_separator: char,
+}
+
+impl Default for NumberLocale {
+    fn default() -> Self {
+        Self { decimal_separator: '.', group_separator: ',' }
+    }
+}
+
+impl NumberLocale {
+
+    /// Pick the number format for a POSIX locale name, i.e. "de_DE.UTF-8".
+    pub fn from_name(name: &str) -> Self {
+        let language = name.split(['_', '.', '-', '@']).next().unwrap_or_default();
+        match language {
+            "de" | "da" | "es" | "id" | "it" | "nl" | "pt" | "tr" | "el" =>
+                Self { decimal_separator: ',', group_separator: '.' },
+            "fr" | "ru" | "pl" | "cs" | "sv" | "fi" | "nb" | "nn" | "uk" | "hu" | "sk" =>
+                Self { decimal_separator: ',', group_separator: '\u{a0}' },
+            _ => Self::default(),
+        }
+    }
+
+    /// Pick the number format from the environment, the same way the C library does.
+    pub fn from_env() -> Self {
+        for var in ["LC_ALL", "LC_NUMERIC", "LANG"] {
+            if let Ok(name) = std::env::var(var) {
+                if !name.is_empty() {
+                    return Self::from_name(&name);
+                }
+            }
+        }
+        Self::default()
+    }
+
+    /// Format a number with at most two decimal places, dropping trailing zeros.
+    pub fn format_number(&self, value: f64) -> String {
+        let fixed = format!("{:.2}", value);
+        let (int_part, frac_part) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let frac_part = frac_part.trim_end_matches('0');
+        let mut s = String::new();
+        for (i, c) in int_part.chars().enumerate() {
+            if i > 0 && (int_part.len() - i) % 3 == 0 {
+                s.push(self.group_separator);
+            }
+            s.push(c);
+        }
+        if !frac_part.is_empty() {
+            s.push(self.decimal_separator);
+            s.push_str(frac_part);
+        }
+        s
+    }
+}
+
+static LOCALE: OnceLock<NumberLocale> = OnceLock::new();
+
+/// The number format of the current user, read from the environment once.
+pub fn current_locale() -> NumberLocale {
+    *LOCALE.get_or_init(NumberLocale::from_env)
+}
+
+/// Format numbers with `locale` instead of the user's, i.e. so snapshots look the same on
+/// every machine. Only works before the first number is formatted, and returns the
+/// number format that's used from then on.
+pub fn pin_locale(locale: NumberLocale) -> NumberLocale {
+    *LOCALE.get_or_init(|| locale)
+}
+
+/// Split "1.5 MB" into 1.5 and "MB". A missing unit gives an empty string.
+fn split_quantity(s: &str) -> Result<(f64, &str), String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid quantity \"{}\"", s))?;
+    Ok((number, unit.trim()))
+}
+
+/// Convert a parsed quantity to an integer amount of the base unit.
+fn to_base(number: f64, multiplier: u64, s: &str) -> Result<u64, String> {
+    let value = (number * multiplier as f64).round();
+    if !value.is_finite() || value >= u64::MAX as f64 {
+        return Err(format!("quantity \"{}\" is too large", s));
+    }
+    Ok(value as u64)
+}
+
+const MEMORY_UNITS: [(&str, u64); 7] = [
+    ("GiB", 1 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10),
+    ("GB", 1_000_000_000), ("MB", 1_000_000), ("KB", 1_000),
+    ("B", 1),
+];
+
+/// An amount of memory, i.e. a board's RAM or flash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Memory {
+    bytes: u64,
+}
+
+impl Memory {
+
+    pub fn from_bytes(bytes: u64) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Format the amount for display, in binary units if it's a multiple of a KiB and
+    /// in decimal units otherwise.
+    pub fn format(&self, locale: &NumberLocale) -> String {
+        let units: &[(&str, u64)] = if self.bytes.is_multiple_of(1024) && self.bytes > 0 {
+            &MEMORY_UNITS[0..3]
+        } else {
+            &MEMORY_UNITS[3..6]
+        };
+        for (name, size) in units {
+            if self.bytes >= *size {
+                return format!("{} {}", locale.format_number(self.bytes as f64 / *size as f64), name);
+            }
+        }
+        format!("{} B", locale.format_number(self.bytes as f64))
+    }
+}
+
+/// Parses "264 KiB", "8MB", "512 B", etc. Units are case insensitive, and a bare number
+/// is in kilobytes, which is what board files used before units were supported.
+impl FromStr for Memory {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_quantity(s)?;
+        let multiplier = if unit.is_empty() {
+            1_000
+        } else {
+            match MEMORY_UNITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)) {
+                Some((_, size)) => *size,
+                None => return Err(format!("unknown memory unit \"{}\"", unit)),
+            }
+        };
+        Ok(Self { bytes: to_base(number, multiplier, s)? })
+    }
+}
+
+/// Writes the amount exactly, in the largest unit that divides it, i.e. "264 KiB".
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, size) in MEMORY_UNITS.iter() {
+            if self.bytes > 0 && self.bytes.is_multiple_of(*size) {
+                return write!(f, "{} {}", self.bytes / size, name);
+            }
+        }
+        write!(f, "0 B")
+    }
+}
+
+const FREQUENCY_UNITS: [(&str, u64); 4] = [
+    ("GHz", 1_000_000_000), ("MHz", 1_000_000), ("kHz", 1_000), ("Hz", 1),
+];
+
+/// A clock frequency.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Frequency {
+    hz: u64,
+}
+
+impl Frequency {
+
+    pub fn from_hz(hz: u64) -> Self {
+        Self { hz }
+    }
+
+    pub fn hz(&self) -> u64 {
+        self.hz
+    }
+
+    /// Format the frequency for display in the largest unit that keeps it above one.
+    pub fn format(&self, locale: &NumberLocale) -> String {
+        for (name, size) in FREQUENCY_UNITS.iter() {
+            if self.hz >= *size {
+                return format!("{} {}", locale.format_number(self.hz as f64 / *size as f64), name);
+            }
+        }
+        "0 Hz".to_string()
+    }
+}
+
+/// Parses "125 MHz", "32.768kHz", etc. Units are case insensitive, and a bare number is in Hz.
+impl FromStr for Frequency {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_quantity(s)?;
+        let multiplier = if unit.is_empty() {
+            1
+        } else {
+            match FREQUENCY_UNITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)) {
+                Some((_, size)) => *size,
+                None => return Err(format!("unknown frequency unit \"{}\"", unit)),
+            }
+        };
+        Ok(Self { hz: to_base(number, multiplier, s)? })
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, size) in FREQUENCY_UNITS.iter() {
+            if self.hz > 0 && self.hz.is_multiple_of(*size) {
+                return write!(f, "{} {}", self.hz / size, name);
+            }
+        }
+        write!(f, "0 Hz")
+    }
+}
+
+const CURRENT_UNITS: [(&str, u64); 5] = [
+    ("A", 1_000_000_000), ("mA", 1_000_000), ("µA", 1_000), ("uA", 1_000), ("nA", 1),
+];
+
+/// A supply current, i.e. what a chip draws in one of its sleep modes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Current {
+    nanoamps: u64,
+}
+
+impl Current {
+
+    pub fn from_nanoamps(nanoamps: u64) -> Self {
+        Self { nanoamps }
+    }
+
+    pub fn nanoamps(&self) -> u64 {
+        self.nanoamps
+    }
+
+    pub fn amps(&self) -> f64 {
+        self.nanoamps as f64 * 1e-9
+    }
+
+    /// Format the current for display in the largest unit that keeps it above one.
+    pub fn format(&self, locale: &NumberLocale) -> String {
+        for (name, size) in CURRENT_UNITS.iter() {
+            if self.nanoamps >= *size {
+                return format!("{} {}", locale.format_number(self.nanoamps as f64 / *size as f64), name);
+            }
+        }
+        "0 A".to_string()
+    }
+}
+
+/// Parses "12 mA", "1.5uA", "350 nA", etc. Units are case insensitive, and a bare number
+/// is in amps.
+impl FromStr for Current {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_quantity(s)?;
+        let multiplier = if unit.is_empty() {
+            CURRENT_UNITS[0].1
+        } else {
+            match CURRENT_UNITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)) {
+                Some((_, size)) => *size,
+                None => return Err(format!("unknown current unit \"{}\"", unit)),
+            }
+        };
+        Ok(Self { nanoamps: to_base(number, multiplier, s)? })
+    }
+}
+
+/// Writes the current exactly, in the largest unit that divides it, i.e. "1500 nA" or "2 µA".
+impl fmt::Display for Current {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, size) in CURRENT_UNITS.iter() {
+            if self.nanoamps > 0 && self.nanoamps.is_multiple_of(*size) {
+                return write!(f, "{} {}", self.nanoamps / size, name);
+            }
+        }
+        write!(f, "0 A")
+    }
+}
+
+const VOLTAGE_UNITS: [(&str, u64); 2] = [("V", 1_000), ("mV", 1)];
+
+/// A voltage, i.e. what a board runs from or the level of its logic signals.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Voltage {
+    millivolts: u64,
+}
+
+impl Voltage {
+
+    pub fn from_millivolts(millivolts: u64) -> Self {
+        Self { millivolts }
+    }
+
+    pub fn millivolts(&self) -> u64 {
+        self.millivolts
+    }
+
+    pub fn volts(&self) -> f64 {
+        self.millivolts as f64 * 1e-3
+    }
+
+    /// Format the voltage for display in the largest unit that keeps it above one.
+    pub fn format(&self, locale: &NumberLocale) -> String {
+        for (name, size) in VOLTAGE_UNITS.iter() {
+            if self.millivolts >= *size {
+                return format!("{} {}", locale.format_number(self.millivolts as f64 / *size as f64), name);
+            }
+        }
+        "0 V".to_string()
+    }
+}
+
+/// Parses "3.3 V", "1800mV", etc. Units are case insensitive, and a bare number is in volts.
+impl FromStr for Voltage {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, unit) = split_quantity(s)?;
+        let multiplier = if unit.is_empty() {
+            VOLTAGE_UNITS[0].1
+        } else {
+            match VOLTAGE_UNITS.iter().find(|(name, _)| name.eq_ignore_ascii_case(unit)) {
+                Some((_, size)) => *size,
+                None => return Err(format!("unknown voltage unit \"{}\"", unit)),
+            }
+        };
+        Ok(Self { millivolts: to_base(number, multiplier, s)? })
+    }
+}
+
+/// Writes the voltage exactly, i.e. "5 V", or "3300 mV" when it isn't a whole number of volts.
+impl fmt::Display for Voltage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, size) in VOLTAGE_UNITS.iter() {
+            if self.millivolts > 0 && self.millivolts.is_multiple_of(*size) {
+                return write!(f, "{} {}", self.millivolts / size, name);
+            }
+        }
+        write!(f, "0 V")
+    }
+}
+
+/// Deserialize a quantity written either as a string with a unit, or as a bare number.
+struct QuantityVisitor<T>(std::marker::PhantomData<T>);
+
+impl<'de, T: FromStr<Err = String>> de::Visitor<'de> for QuantityVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a non-negative number, optionally with a unit")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::from_str(&v.to_string()).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        if v < 0 {
+            return Err(E::custom(format!("quantity can't be negative (got {})", v)));
+        }
+        self.visit_u64(v as u64)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        if v < 0.0 {
+            return Err(E::custom(format!("quantity can't be negative (got {})", v)));
+        }
+        T::from_str(&v.to_string()).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        T::from_str(v).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Memory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(QuantityVisitor(std::marker::PhantomData))
+    }
+}
+
+impl Serialize for Memory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Frequency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(QuantityVisitor(std::marker::PhantomData))
+    }
+}
+
+impl Serialize for Frequency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Current {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(QuantityVisitor(std::marker::PhantomData))
+    }
+}
+
+impl Serialize for Current {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Voltage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(QuantityVisitor(std::marker::PhantomData))
+    }
+}
+
+impl Serialize for Voltage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
@@ -0,0 +1,169 @@
+//! Title: Iron Coder Board Module - Watchdog
+//! Description: The watchdog and brown-out detector of a board's chip: what clocks the
+//!   watchdog's counter, how far it can count, whether it has a window, and which supply
+//!   levels the brown-out detector can trip at. With this the watchdog panel can turn a
+//!   timeout into counter settings and write the code to start and feed the watchdog.
+
+use std::fmt;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use super::units::Frequency;
+
+/// The HALs the watchdog panel can write setup code for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum WatchdogHal {
+    Rp2040Hal,
+    NrfHal,
+    EspHal,
+}
+
+impl fmt::Display for WatchdogHal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchdogHal::Rp2040Hal => write!(f, "rp2040-hal"),
+            WatchdogHal::NrfHal => write!(f, "nrf-hal"),
+            WatchdogHal::EspHal => write!(f, "esp-hal (MWDT)"),
+        }
+    }
+}
+
+/// The watchdog of a board's chip, i.e.
+/// ```toml
+/// [watchdog]
+/// clock = "32.768 kHz"
+/// counter_bits = 32
+/// min_count = 15
+/// hal = "nrf-hal"
+/// ```
+/// The counter counts `clock`, divided by anything from 1 to `max_divider`, and resets
+/// the chip when it reaches the count it was started with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchdog {
+    pub clock: Frequency,
+    pub counter_bits: u32,
+    /// The smallest count the counter can be started with
+    #[serde(default)]
+    pub min_count: u64,
+    #[serde(default = "default_divider")]
+    pub max_divider: u32,
+    /// Whether the watchdog can also reset the chip when it's fed too early
+    #[serde(default)]
+    pub window: bool,
+    pub hal: Option<WatchdogHal>,
+}
+
+fn default_divider() -> u32 {
+    1
+}
+
+/// The counter settings for a timeout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchdogSettings {
+    pub divider: u32,
+    /// The count the counter starts with
+    pub count: u64,
+    /// The timeout the count gives, in seconds
+    pub timeout: f64,
+}
+
+impl Watchdog {
+
+    pub fn max_count(&self) -> u64 {
+        (1u64 << self.counter_bits) - 1
+    }
+
+    pub fn min_timeout(&self) -> f64 {
+        self.min_count.max(1) as f64 / self.clock.hz() as f64
+    }
+
+    pub fn max_timeout(&self) -> f64 {
+        self.max_count() as f64 * self.max_divider as f64 / self.clock.hz() as f64
+    }
+
+    /// The settings closest to `timeout` seconds, with the smallest divider that reaches
+    /// it, since that gives the finest steps.
+    pub fn settings(&self, timeout: f64) -> Result<WatchdogSettings, String> {
+        if !(timeout > 0.0 && timeout.is_finite()) {
+            return Err("the timeout must be positive".to_string());
+        }
+        let ticks = timeout * self.clock.hz() as f64;
+        let divider = (ticks / self.max_count() as f64).ceil().max(1.0);
+        if divider > self.max_divider as f64 {
+            return Err(format!("too long: the watchdog can wait at most {:.3} s", self.max_timeout()));
+        }
+        let count = (ticks / divider).round() as u64;
+        if count < self.min_count.max(1) {
+            return Err(format!("too short: the watchdog needs at least {:.6} s", self.min_timeout()));
+        }
+        Ok(WatchdogSettings { divider: divider as u32, count, timeout: count as f64 * divider / self.clock.hz() as f64 })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(8..=32).contains(&self.counter_bits) {
+            return Err(format!("watchdog counter must be 8 to 32 bits (got {})", self.counter_bits));
+        }
+        if self.clock.hz() == 0 || self.max_divider == 0 {
+            return Err("watchdog clock and divider must be positive".to_string());
+        }
+        if self.min_count >= self.max_count() {
+            return Err("watchdog min_count must be less than the counter can hold".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What the brown-out detector does when the supply drops below its level.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BrownOutAction {
+    /// Hold the chip in reset until the supply recovers
+    #[default]
+    Reset,
+    /// Raise an interrupt, so the firmware can save its state
+    Interrupt,
+}
+
+/// The brown-out detector of a board's chip, i.e.
+/// ```toml
+/// [brown_out]
+/// rail = "core (DVDD)"
+/// levels = [0.473, 0.516, 0.559]
+/// default = 0.516
+/// hal = "rp2040-hal"
+/// ```
+/// The levels are in volts, in the order of the register values that select them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BrownOut {
+    /// The supply the detector watches
+    pub rail: String,
+    pub levels: Vec<f64>,
+    /// The level the detector trips at out of reset, if it's on out of reset
+    pub default: Option<f64>,
+    #[serde(default)]
+    pub action: BrownOutAction,
+    pub hal: Option<WatchdogHal>,
+}
+
+impl BrownOut {
+
+    /// The register value of the lowest level at or above `volts`.
+    pub fn level_at(&self, volts: f64) -> Option<usize> {
+        self.levels.iter().position(|l| *l >= volts - 1e-6)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.levels.is_empty() || self.levels.iter().any(|l| !(l.is_finite() && *l > 0.0)) {
+            return Err("brown-out levels must be positive voltages".to_string());
+        }
+        if self.levels.windows(2).any(|w| w[0] >= w[1]) {
+            return Err("brown-out levels must go up".to_string());
+        }
+        if self.default.is_some_and(|d| !self.levels.iter().any(|l| (l - d).abs() < 1e-6)) {
+            return Err("brown-out default must be one of its levels".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Title: Iron Coder Board Module - Board Watcher
+//! Description: Watches the boards directory while the app runs, so a board file that's
+//!   added or edited shows up in the board selector without a restart. Editors write a
+//!   file in several steps, so the boards are only reloaded once the changes have stopped
+//!   for a moment.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use notify::{EventKind, RecursiveMode, RecommendedWatcher, Watcher};
+
+/// How long the files have to stay the same before the boards are reloaded.
+pub const QUIET_TIME: Duration = Duration::from_millis(300);
+
+/// Whether a changed file could change the boards, leaving out hidden files and the ones
+/// editors keep while a file is open, i.e. `.board.toml.swp` or `board.toml~`.
+pub fn is_board_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    !(name.starts_with('.') || name.ends_with('~') || name.ends_with(".swp") || name.ends_with(".tmp"))
+}
+
+/// Watches board directories, and says when the boards should be reloaded.
+pub struct BoardWatcher {
+    // the watcher stops when it's dropped
+    _watcher: RecommendedWatcher,
+    rx: Receiver<(PathBuf, Instant)>,
+    ctx: Option<egui::Context>,
+    dirs: Vec<PathBuf>,
+    /// The files changed since the last reload, and when the last one changed
+    pending: Vec<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl BoardWatcher {
+
+    /// Start watching the directories, and everything in them. Directories that aren't
+    /// there are skipped. With a context, the app is woken up when a file changes.
+    pub fn new(dirs: &[PathBuf], ctx: Option<egui::Context>) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let waker = ctx.clone();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("error watching the boards: {}", e);
+                    return;
+                },
+            };
+            // reading the boards to reload them mustn't set off another reload
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) | EventKind::Any) {
+                return;
+            }
+            for path in event.paths.into_iter().filter(|p| is_board_file(p)) {
+                let _ = tx.send((path, Instant::now()));
+            }
+            if let Some(ctx) = &waker {
+                ctx.request_repaint();
+            }
+        }).map_err(|e| format!("couldn't watch the boards: {}", e))?;
+        let mut watched = vec![];
+        for dir in dirs.iter().filter(|d| d.is_dir()) {
+            watcher.watch(dir, RecursiveMode::Recursive).map_err(|e| format!("couldn't watch {}: {}", dir.display(), e))?;
+            watched.push(dir.clone());
+        }
+        Ok(Self { _watcher: watcher, rx, ctx, dirs: watched, pending: vec![], last_change: None })
+    }
+
+    /// The directories being watched.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// The files that changed, once they've stayed the same for `QUIET_TIME`, and `None`
+    /// until then.
+    pub fn changed(&mut self) -> Option<Vec<PathBuf>> {
+        for (path, when) in self.rx.try_iter() {
+            if !self.pending.contains(&path) {
+                self.pending.push(path);
+            }
+            self.last_change = Some(when);
+        }
+        let quiet_for = self.last_change?.elapsed();
+        if quiet_for < QUIET_TIME {
+            // come back when it's been quiet long enough
+            if let Some(ctx) = &self.ctx {
+                ctx.request_repaint_after(QUIET_TIME - quiet_for);
+            }
+            return None;
+        }
+        self.last_change = None;
+        Some(std::mem::take(&mut self.pending))
+    }
+}
@@ -0,0 +1,409 @@
+//! Title: Iron Coder Board Module - Board Wizard
+//! Description: A guided way of adding a board to the boards directory from inside the
+//!   app. It asks for the board's details a step at a time, checks them the same way a
+//!   board file is checked when it's loaded, and writes the board's toml file and image
+//!   where `get_boards` will find them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use enum_iterator::Sequence;
+use rfd::FileDialog;
+use serde::{Serialize, Deserialize};
+
+use crate::board::{Board, BoardKind, BoardStandards};
+use crate::board::svg_reader::SvgBoardInfo;
+use crate::board::peripherals::Peripherals;
+use crate::board::units::Memory;
+
+/// The size a board image is drawn at when its size isn't given, in mm.
+const DEFAULT_WIDTH_MM: f32 = 50.0;
+
+/// The steps of the wizard, in order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum WizardStep {
+    #[default]
+    Identity,
+    Hardware,
+    Image,
+    Crates,
+    Review,
+}
+
+impl WizardStep {
+    fn title(&self) -> &'static str {
+        match self {
+            WizardStep::Identity => "Name",
+            WizardStep::Hardware => "Hardware",
+            WizardStep::Image => "Image",
+            WizardStep::Crates => "Crates",
+            WizardStep::Review => "Review",
+        }
+    }
+}
+
+/// A board being described in the wizard, before it's written to the boards directory.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct BoardDraft {
+    pub name: String,
+    pub manufacturer: String,
+    pub kind: BoardKind,
+    pub standard: Option<BoardStandards>,
+    pub cpu: String,
+    /// The amounts of memory, i.e. "264 KiB"
+    pub ram: String,
+    pub flash: String,
+    pub peripherals: Peripherals,
+    /// An SVG drawing of the board, or a PNG photo of it
+    pub image: Option<PathBuf>,
+    /// How wide the board is, in mm, when the image is a PNG
+    pub width_mm: f32,
+    /// The names of crates that are useful with the board, separated by commas or spaces
+    pub related_crates: String,
+}
+
+impl Default for BoardDraft {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            manufacturer: String::new(),
+            kind: BoardKind::MainBoard,
+            standard: None,
+            cpu: String::new(),
+            ram: String::new(),
+            flash: String::new(),
+            peripherals: Peripherals::default(),
+            image: None,
+            width_mm: DEFAULT_WIDTH_MM,
+            related_crates: String::new(),
+        }
+    }
+}
+
+/// Make a name safe to use as a file or directory name, i.e. "Feather RP2040" becomes
+/// "Feather_RP2040".
+pub fn file_safe(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+        .trim_matches('.')
+        .to_string()
+}
+
+/// An optional amount of memory. Empty means the board doesn't say.
+fn parse_memory(label: &str, text: &str) -> Result<Option<Memory>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    text.parse().map(Some).map_err(|e| format!("{}: {}", label, e))
+}
+
+impl BoardDraft {
+
+    /// Check the answers given on one step of the wizard.
+    pub fn check_step(&self, step: WizardStep) -> Result<(), String> {
+        match step {
+            WizardStep::Identity => {
+                if file_safe(&self.name).is_empty() {
+                    return Err("the board needs a name".to_string());
+                }
+                if file_safe(&self.manufacturer).is_empty() {
+                    return Err("the board needs a manufacturer".to_string());
+                }
+            },
+            WizardStep::Hardware => {
+                parse_memory("RAM", &self.ram)?;
+                parse_memory("flash", &self.flash)?;
+            },
+            WizardStep::Image => {
+                let Some(image) = &self.image else {
+                    return Err("pick an image of the board".to_string());
+                };
+                match extension(image).as_str() {
+                    "svg" => (),
+                    "png" => {
+                        if !(self.width_mm.is_finite() && self.width_mm > 0.0) {
+                            return Err("the board's width has to be more than 0 mm".to_string());
+                        }
+                    },
+                    _ => return Err("the image has to be an SVG or a PNG".to_string()),
+                }
+            },
+            WizardStep::Crates => {
+                if let Some(name) = self.crates().iter().find(|c| !c.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+                    return Err(format!("{} isn't a crate name", name));
+                }
+            },
+            WizardStep::Review => {
+                self.check_step(WizardStep::Image)?;
+                self.to_toml()?;
+            },
+        }
+        Ok(())
+    }
+
+    pub fn crates(&self) -> Vec<String> {
+        self.related_crates.split(|c: char| c == ',' || c.is_whitespace()).filter(|c| !c.is_empty()).map(str::to_string).collect()
+    }
+
+    /// The directory the board goes in, relative to the boards directory.
+    pub fn dir(&self) -> PathBuf {
+        Path::new(&file_safe(&self.manufacturer)).join(file_safe(&self.name))
+    }
+
+    /// The name of the board's files, without the extension.
+    pub fn file_stem(&self) -> String {
+        file_safe(&self.name).to_lowercase()
+    }
+
+    /// The board's toml file. It's checked the same way it will be when it's loaded.
+    pub fn to_toml(&self) -> Result<String, String> {
+        for step in [WizardStep::Identity, WizardStep::Hardware, WizardStep::Crates] {
+            self.check_step(step)?;
+        }
+        let mut doc = toml_edit::DocumentMut::new();
+        doc["name"] = toml_edit::value(self.name.trim());
+        doc["manufacturer"] = toml_edit::value(self.manufacturer.trim());
+        doc["kind"] = toml_edit::value(self.kind.key());
+        if let Some(standard) = &self.standard {
+            doc["standard"] = toml_edit::value(standard.to_string());
+        }
+        if !self.cpu.trim().is_empty() {
+            doc["cpu"] = toml_edit::value(self.cpu.trim());
+        }
+        if let Some(ram) = parse_memory("RAM", &self.ram)? {
+            doc["ram"] = toml_edit::value(ram.to_string());
+        }
+        if let Some(flash) = parse_memory("flash", &self.flash)? {
+            doc["flash"] = toml_edit::value(flash.to_string());
+        }
+        let crates = self.crates();
+        if !crates.is_empty() {
+            doc["related_crates"] = toml_edit::value(crates.iter().collect::<toml_edit::Array>());
+        }
+        let p = &self.peripherals;
+        if !p.is_empty() {
+            let mut table = toml_edit::Table::new();
+            for (key, count) in [("uart", p.uart), ("i2c", p.i2c), ("spi", p.spi), ("adc_channels", p.adc_channels)] {
+                if count > 0 {
+                    table[key] = toml_edit::value(count as i64);
+                }
+            }
+            for (key, has) in [("usb", p.usb), ("wifi", p.wifi), ("ble", p.ble)] {
+                if has {
+                    table[key] = toml_edit::value(true);
+                }
+            }
+            doc["peripherals"] = toml_edit::Item::Table(table);
+        }
+        let mut toml = doc.to_string();
+        toml.push_str("\n# Each interface of the board goes in a [[pinout]] table, i.e.\n");
+        toml.push_str("# [[pinout]]\n# pins = [\"scl\", \"sda\"]\n# interface = { iface_type = \"I2C\", direction = \"Output\" }\n");
+        Board::from_toml_str(&toml).map_err(|e| format!("the board file isn't valid: {:?}", e))?;
+        Ok(toml)
+    }
+
+    /// The board's SVG file. An SVG is used as it is, and a PNG is put in an SVG the
+    /// size of the board.
+    pub fn to_svg(&self) -> Result<String, String> {
+        self.check_step(WizardStep::Image)?;
+        let path = self.image.as_deref().unwrap_or_else(|| Path::new(""));
+        if extension(path) == "svg" {
+            let info = SvgBoardInfo::from_path(path).map_err(|e| format!("{} can't be used: {:?}", path.display(), e))?;
+            info.image.wait().ok_or_else(|| format!("the image in {} can't be decoded", path.display()))?;
+            return fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e));
+        }
+        let (width, height) = image::image_dimensions(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        let png = fs::read(path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        Ok(png_svg(&png, width, height, self.width_mm))
+    }
+
+    /// Write the board into the boards directory. Returns the path of its toml file.
+    pub fn write(&self, boards_dir: &Path) -> Result<PathBuf, String> {
+        let toml = self.to_toml()?;
+        let svg = self.to_svg()?;
+        let dir = boards_dir.join(self.dir());
+        let toml_path = dir.join(self.file_stem()).with_extension("toml");
+        if toml_path.exists() {
+            return Err(format!("there's already a board at {}", toml_path.display()));
+        }
+        fs::create_dir_all(&dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+        fs::write(toml_path.with_extension("svg"), svg).map_err(|e| format!("couldn't write the board image: {}", e))?;
+        fs::write(&toml_path, toml).map_err(|e| format!("couldn't write {}: {}", toml_path.display(), e))?;
+        Ok(toml_path)
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().unwrap_or_default().to_string_lossy().to_lowercase()
+}
+
+/// An SVG holding a PNG image of a board `width_mm` wide, with a layer for the pins to be
+/// drawn on later.
+pub fn png_svg(png: &[u8], width: u32, height: u32, width_mm: f32) -> String {
+    let height_mm = width_mm * height as f32 / width.max(1) as f32;
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    format!(
+r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<svg width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}" version="1.1" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <image width="{w}" height="{h}" preserveAspectRatio="none" xlink:href="data:image/png;base64,{data}" />
+  <g id="pins" />
+</svg>
+"#,
+        w = width_mm, h = height_mm, data = data)
+}
+
+/// The state of the board wizard window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BoardWizard {
+    pub draft: BoardDraft,
+    step: WizardStep,
+    #[serde(skip)]
+    message: Option<Result<String, String>>,
+}
+
+impl BoardWizard {
+
+    /// Show the current step of the wizard. Returns the path of the board's toml file
+    /// when a board has been written to `boards_dir`.
+    pub fn display(&mut self, ui: &mut egui::Ui, boards_dir: &Path) -> Option<PathBuf> {
+        ui.horizontal(|ui| {
+            for step in enum_iterator::all::<WizardStep>() {
+                let text = egui::RichText::new(step.title());
+                ui.label(if step == self.step { text.strong() } else { text.weak() });
+            }
+        });
+        ui.separator();
+        let draft = &mut self.draft;
+        match self.step {
+            WizardStep::Identity => {
+                egui::Grid::new("board wizard identity").num_columns(2).show(ui, |ui| {
+                    ui.label("name:");
+                    ui.text_edit_singleline(&mut draft.name);
+                    ui.end_row();
+                    ui.label("manufacturer:");
+                    ui.text_edit_singleline(&mut draft.manufacturer);
+                    ui.end_row();
+                    ui.label("form factor:");
+                    let selected = draft.standard.as_ref().map_or("none".to_string(), BoardStandards::to_string);
+                    egui::ComboBox::from_id_source("board wizard standard").selected_text(selected).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut draft.standard, None, "none");
+                        for standard in enum_iterator::all::<BoardStandards>() {
+                            let text = standard.to_string();
+                            ui.selectable_value(&mut draft.standard, Some(standard), text);
+                        }
+                    });
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("kind:");
+                    egui::ComboBox::from_id_source("board wizard kind").selected_text(draft.kind.to_string()).show_ui(ui, |ui| {
+                        for kind in enum_iterator::all::<BoardKind>() {
+                            ui.selectable_value(&mut draft.kind, kind, kind.to_string());
+                        }
+                    });
+                });
+            },
+            WizardStep::Hardware => {
+                egui::Grid::new("board wizard hardware").num_columns(2).show(ui, |ui| {
+                    ui.label("cpu:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.cpu).hint_text("Cortex-M0"));
+                    ui.end_row();
+                    ui.label("RAM:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.ram).hint_text("264 KiB"));
+                    ui.end_row();
+                    ui.label("flash:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.flash).hint_text("8 MiB"));
+                    ui.end_row();
+                    let p = &mut draft.peripherals;
+                    for (label, count) in [("UARTs:", &mut p.uart), ("I2C buses:", &mut p.i2c), ("SPI buses:", &mut p.spi), ("ADC channels:", &mut p.adc_channels)] {
+                        ui.label(label);
+                        ui.add(egui::DragValue::new(count).clamp_range(0..=64));
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let p = &mut draft.peripherals;
+                    ui.checkbox(&mut p.usb, "USB");
+                    ui.checkbox(&mut p.wifi, "WiFi");
+                    ui.checkbox(&mut p.ble, "BLE");
+                });
+            },
+            WizardStep::Image => {
+                ui.label("An SVG drawing of the board, with a path for each pin, or a PNG photo of it.");
+                ui.horizontal(|ui| {
+                    if ui.button("choose image...").clicked() {
+                        if let Some(path) = FileDialog::new().add_filter("board image", &["svg", "png"]).pick_file() {
+                            draft.image = Some(path);
+                        }
+                    }
+                    match &draft.image {
+                        Some(image) => ui.label(image.display().to_string()),
+                        None => ui.weak("no image"),
+                    };
+                });
+                if draft.image.as_deref().is_some_and(|i| extension(i) == "png") {
+                    ui.horizontal(|ui| {
+                        ui.label("board width:");
+                        ui.add(egui::DragValue::new(&mut draft.width_mm).clamp_range(1.0..=500.0).suffix(" mm"));
+                    });
+                }
+            },
+            WizardStep::Crates => {
+                ui.label("related crates:");
+                ui.add(egui::TextEdit::multiline(&mut draft.related_crates).hint_text("smart-leds, ws2812-pio").desired_rows(3));
+            },
+            WizardStep::Review => {
+                if let Ok(toml) = draft.to_toml() {
+                    ui.label(format!("{}.toml will be written to {}", draft.file_stem(), boards_dir.join(draft.dir()).display()));
+                    egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                        ui.label(egui::RichText::new(toml).monospace());
+                    });
+                }
+            },
+        }
+        let checked = self.draft.check_step(self.step);
+        if let Err(e) = &checked {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        ui.separator();
+        let mut written = None;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.step.previous().is_some(), egui::Button::new("back")).clicked() {
+                self.step = self.step.previous().unwrap_or_default();
+            }
+            match self.step.next() {
+                Some(next) => {
+                    if ui.add_enabled(checked.is_ok(), egui::Button::new("next")).clicked() {
+                        self.step = next;
+                    }
+                },
+                None => {
+                    if ui.add_enabled(checked.is_ok(), egui::Button::new("add board")).clicked() {
+                        match self.draft.write(boards_dir) {
+                            Ok(path) => {
+                                self.message = Some(Ok(format!("added {}", self.draft.name.trim())));
+                                self.draft = BoardDraft::default();
+                                self.step = WizardStep::default();
+                                written = Some(path);
+                            },
+                            Err(e) => self.message = Some(Err(e)),
+                        }
+                    }
+                },
+            }
+        });
+        match &self.message {
+            Some(Ok(message)) => { ui.label(message); },
+            Some(Err(e)) => { ui.colored_label(ui.visuals().error_fg_color, e); },
+            None => (),
+        }
+        written
+    }
+}
@@ -1,4 +1,7 @@
-mod app;
-mod board;
-mod project;
+pub mod app;
+pub mod board;
+pub mod project;
+pub mod paths;
+#[cfg(test)]
+mod scratch;
 pub use app::{IronCoderOptions, IronCoderApp};
\ No newline at end of file
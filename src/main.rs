@@ -1,8 +1,22 @@
-use log::info;
+use log::{info, warn};
 use clap::Parser;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
 use std::str::FromStr;
 
 use iron_coder::IronCoderOptions;
+use iron_coder::board::{self, Board};
+use iron_coder::board::bundle;
+use iron_coder::board::export::{self, ExportFormat};
+
+/// The boards in a boards directory, with what's wrong with the ones that couldn't be
+/// loaded on stderr.
+fn load_boards(boards_dir: &Path) -> Vec<Board> {
+    board::get_boards(boards_dir).unwrap_or_else(|report| {
+        eprintln!("{}", report);
+        report.boards
+    })
+}
 
 fn main() -> eframe::Result<()> {
 
@@ -11,6 +25,29 @@ fn main() -> eframe::Result<()> {
         app_options.persistence = Some(true);
     }
 
+    // Exporting happens before logging is set up, so only the boards go to stdout.
+    if let Some(ExportFormat::Json) = app_options.export_boards {
+        let boards = load_boards(&app_options.boards_dir());
+        match export::to_json(&boards) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("couldn't export the boards: {}", e);
+                std::process::exit(1);
+            },
+        }
+        return Ok(());
+    }
+    if let Some(bundle) = &app_options.import_board {
+        match bundle::import(bundle, &app_options.boards_dir(), true) {
+            Ok(dir) => println!("imported the board into {}", dir.display()),
+            Err(e) => {
+                eprintln!("couldn't import the board: {}", e);
+                std::process::exit(1);
+            },
+        }
+        return Ok(());
+    }
+
     // Setup the subscriber with a logging level.
     let debug_level: tracing::Level = if let Some(verbosity) = app_options.clone().verbosity {
         tracing::Level::from_str(&verbosity).unwrap_or_else(|_| {
@@ -24,6 +61,13 @@ fn main() -> eframe::Result<()> {
 
     info!("Running Iron Coder with options:\n{:?}", app_options);
 
+    if let Some(port) = app_options.serve_boards {
+        let boards = load_boards(&app_options.boards_dir());
+        if let Err(e) = export::serve(SocketAddr::from((Ipv4Addr::LOCALHOST, port)), &boards) {
+            warn!("couldn't serve the boards on port {}: {:?}", port, e);
+        }
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "Iron Coder",
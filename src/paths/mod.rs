@@ -0,0 +1,73 @@
+//! Title: Iron Coder Paths Module
+//! Description: Helpers for handling paths without assuming they are valid UTF-8.
+//!   Paths should be kept as Path/OsStr for as long as possible (std fs calls and
+//!   external commands all accept them), and only converted (lossily) for display.
+//!   Long paths are handled by std for its own fs calls, but external tools like
+//!   cargo and git often choke on the `\\?\` verbatim paths that canonicalize
+//!   returns on Windows, so those get simplified where that's possible.
+
+use std::borrow::Cow;
+use std::io;
+use std::path::{Path, PathBuf};
+
+mod test;
+
+/// The longest path that Windows APIs accept without the verbatim prefix.
+const MAX_PATH: usize = 260;
+
+/// The name to show for a path in the Ui, i.e. the file name of a tab or a folder.
+/// Falls back to the whole path (i.e. for `/` or `C:\`).
+pub fn display_name(path: &Path) -> Cow<'_, str> {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => path.to_string_lossy(),
+    }
+}
+
+/// Canonicalize a path, but don't return a verbatim path unless it's needed.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    Ok(simplified(&path.canonicalize()?))
+}
+
+/// Turn a Windows verbatim path (`\\?\C:\foo` or `\\?\UNC\server\share\foo`) back into
+/// its ordinary form, as long as that doesn't change its meaning. Paths that aren't
+/// verbatim, or that need to stay verbatim, are returned as-is.
+pub fn simplified(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    match path.to_str().and_then(strip_verbatim) {
+        Some(s) => PathBuf::from(s),
+        None => path.to_path_buf(),
+    }
+}
+
+/// The string half of `simplified`, kept separate so it can be tested on every platform.
+fn strip_verbatim(s: &str) -> Option<String> {
+    let (simple, rest) = if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
+        (format!(r"\\{}", rest), rest)
+    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
+        // only drive paths, i.e. not volume GUIDs
+        let drive = rest.as_bytes();
+        if drive.len() < 3 || !drive[0].is_ascii_alphabetic() || drive[1] != b':' || drive[2] != b'\\' {
+            return None;
+        }
+        (rest.to_string(), rest)
+    } else {
+        return None;
+    };
+    // verbatim paths skip normalization, so components like these mean something different
+    // (or are invalid) in the ordinary form
+    let reserved = ["CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "LPT1", "LPT2", "LPT3"];
+    for component in rest.split('\\') {
+        let stem = component.split('.').next().unwrap_or_default().to_ascii_uppercase();
+        if component == "." || component == ".." || component.ends_with(['.', ' '])
+            || component.contains('/') || reserved.contains(&stem.as_str()) {
+            return None;
+        }
+    }
+    if simple.len() >= MAX_PATH {
+        return None;
+    }
+    Some(simple)
+}
@@ -0,0 +1,33 @@
+#[cfg(test)]
+mod paths_tests {
+    use std::path::Path;
+    use crate::paths::*;
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(strip_verbatim(r"\\?\C:\Users\me\project").as_deref(), Some(r"C:\Users\me\project"));
+        assert_eq!(strip_verbatim(r"\\?\UNC\server\share\project").as_deref(), Some(r"\\server\share\project"));
+        // not verbatim, or can't be simplified safely
+        assert_eq!(strip_verbatim(r"C:\Users\me"), None);
+        assert_eq!(strip_verbatim(r"\\?\Volume{1234}\dir"), None);
+        assert_eq!(strip_verbatim(r"\\?\C:\dir\aux.txt"), None);
+        assert_eq!(strip_verbatim(r"\\?\C:\dir\trailing."), None);
+        assert_eq!(strip_verbatim(r"\\?\C:\a/b"), None);
+        let long = format!(r"\\?\C:\{}", "d\\".repeat(MAX_PATH / 2));
+        assert_eq!(strip_verbatim(&long), None);
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name(Path::new("/home/me/main.rs")), "main.rs");
+        assert_eq!(display_name(Path::new("/")), "/");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_name_non_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.rs");
+        assert_eq!(display_name(&Path::new("/tmp").join(name)), "caf\u{fffd}.rs");
+    }
+}
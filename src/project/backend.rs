@@ -0,0 +1,564 @@
+//! Title: Iron Coder Project Module - Build Backends
+//! Description: Projects are normally built with Cargo, but a lot of boards are best
+//!   supported through a vendor build system, with the Rust code as a component of a
+//!   Zephyr (west) or ESP-IDF (idf.py) application. This module picks the commands and
+//!   environment for each backend, and turns the build output into diagnostics.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::app::ansi;
+use crate::app::jobs::{self, JobContext};
+use crate::project::explain::KnowledgeBase;
+use crate::project::reproducible;
+
+/// The build systems a project can be built with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum BackendKind {
+    /// Plain `cargo build` / `cargo run`
+    #[default]
+    Cargo,
+    /// Rust-on-Zephyr, built and flashed with west
+    Zephyr,
+    /// An ESP-IDF application (i.e. using esp-idf-sys), built and flashed with idf.py
+    EspIdf,
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendKind::Cargo => write!(f, "Cargo"),
+            BackendKind::Zephyr => write!(f, "Zephyr (west)"),
+            BackendKind::EspIdf => write!(f, "ESP-IDF (idf.py)"),
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum BackendError {
+    /// The build tool couldn't be found on the PATH
+    ToolNotFound(String),
+    /// A setting needed by the backend isn't set, and the main board doesn't provide it
+    MissingSetting(String),
+    /// The backend's environment script failed
+    EnvironmentError(String),
+    /// The backend can't do what was asked, i.e. check the code with clippy
+    Unsupported(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendError::ToolNotFound(tool) => write!(f, "couldn't find {} on the PATH", tool),
+            BackendError::MissingSetting(setting) => write!(f, "{} isn't set, and the main board doesn't specify it", setting),
+            BackendError::EnvironmentError(e) => write!(f, "couldn't set up the build environment: {}", e),
+            BackendError::Unsupported(what) => write!(f, "{}", what),
+        }
+    }
+}
+
+/// Settings for Zephyr builds. Empty strings mean "use the default".
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ZephyrSettings {
+    /// The Zephyr board name, if it's different from the main board's
+    pub board: String,
+    /// The Zephyr tree, if it isn't found through the west workspace
+    pub zephyr_base: Option<PathBuf>,
+    /// i.e. "zephyr" for the Zephyr SDK, or "gnuarmemb"
+    pub toolchain_variant: String,
+}
+
+/// Settings for ESP-IDF builds. Empty strings mean "use the default".
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EspIdfSettings {
+    /// The IDF target chip (i.e. "esp32c3"), if it's different from the main board's
+    pub target: String,
+    /// The ESP-IDF install, if IDF_PATH isn't set
+    pub idf_path: Option<PathBuf>,
+    /// The serial port to flash through, if idf.py can't find it by itself
+    pub port: String,
+}
+
+/// The build backend of a project, along with the settings for each kind.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct BuildBackend {
+    pub kind: BackendKind,
+    pub zephyr: ZephyrSettings,
+    pub esp_idf: EspIdfSettings,
+    /// The cargo features turned on by the project config, passed to cargo builds
+    #[serde(skip)]
+    pub features: Vec<String>,
+    /// Build reproducibly, from the lock file with the machine's paths remapped. Only
+    /// cargo builds can be.
+    #[serde(skip)]
+    pub reproducible: bool,
+    /// The project's environment variables, set over the backend's own
+    #[serde(skip)]
+    pub env: Vec<(String, String)>,
+}
+
+/// What a backend command is for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Build,
+    Flash,
+    Check,
+}
+
+/// Guess the backend of an existing project from the files in it.
+pub fn detect(project_dir: &Path) -> BackendKind {
+    let has = |name: &str| project_dir.join(name).exists();
+    if has("prj.conf") || has("west.yml") {
+        BackendKind::Zephyr
+    } else if has("sdkconfig") || has("sdkconfig.defaults") || (has("CMakeLists.txt") && has("main")) {
+        BackendKind::EspIdf
+    } else {
+        BackendKind::Cargo
+    }
+}
+
+/// Look for an executable on a PATH, the way the shell would.
+fn find_in_path(tool: &str, path: Option<&OsString>) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(windows) {
+        let pathext = env::var("PATHEXT").unwrap_or(".EXE;.CMD;.BAT".to_string());
+        pathext.split(';').map(|e| e.to_lowercase()).chain([String::new()]).collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(path?).find_map(|dir| {
+        extensions.iter().map(|ext| dir.join(format!("{}{}", tool, ext))).find(|p| p.is_file())
+    })
+}
+
+/// Parse the output of `env -0` (or `set` on Windows) into a map.
+fn parse_environment(output: &str) -> HashMap<OsString, OsString> {
+    let separator = if output.contains('\0') { '\0' } else { '\n' };
+    output.split(separator)
+        .filter_map(|entry| entry.trim_end_matches('\r').split_once('='))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect()
+}
+
+/// Run an environment setup script (i.e. ESP-IDF's export.sh) and return the environment it leaves behind.
+pub fn capture_environment(script: &Path) -> Result<HashMap<OsString, OsString>, BackendError> {
+    if !script.is_file() {
+        return Err(BackendError::EnvironmentError(format!("{} doesn't exist", script.display())));
+    }
+    let cmd = if cfg!(windows) {
+        duct::cmd!("cmd", "/C", "call", script, ">nul", "&&", "set")
+    } else {
+        duct::cmd!("sh", "-c", r#". "$1" >/dev/null 2>&1 && env -0"#, "sh", script)
+    };
+    let output = cmd.stderr_null().stdout_capture().unchecked().run()
+        .map_err(|e| BackendError::EnvironmentError(e.to_string()))?;
+    if !output.status.success() {
+        return Err(BackendError::EnvironmentError(format!("{} exited with {}", script.display(), output.status)));
+    }
+    Ok(parse_environment(&String::from_utf8_lossy(&output.stdout)))
+}
+
+impl BuildBackend {
+
+    /// Fill in any blank settings from the main board. The board itself can't be sent to
+    /// a job, so this is done before building.
+    pub fn with_board_defaults(&self, main_board: Option<&Board>) -> Self {
+        let mut backend = self.clone();
+        if let Some(board) = main_board {
+            if backend.zephyr.board.trim().is_empty() {
+                backend.zephyr.board = board.zephyr_board().unwrap_or_default().to_string();
+            }
+            if backend.esp_idf.target.trim().is_empty() {
+                backend.esp_idf.target = board.esp_idf_target().unwrap_or_default().to_string();
+            }
+        }
+        backend
+    }
+
+    /// The Zephyr board name.
+    fn zephyr_board(&self) -> Result<&str, BackendError> {
+        match self.zephyr.board.trim() {
+            "" => Err(BackendError::MissingSetting("the Zephyr board".to_string())),
+            board => Ok(board),
+        }
+    }
+
+    /// The IDF target chip.
+    fn idf_target(&self) -> Result<&str, BackendError> {
+        match self.esp_idf.target.trim() {
+            "" => Err(BackendError::MissingSetting("the IDF target".to_string())),
+            target => Ok(target),
+        }
+    }
+
+    /// Work out the environment the build tool runs in. This can be slow for ESP-IDF,
+    /// since its export script checks the whole install, so call it from a job.
+    pub fn environment(&self) -> Result<HashMap<OsString, OsString>, BackendError> {
+        let mut vars: HashMap<OsString, OsString> = env::vars_os().collect();
+        match self.kind {
+            // the console shows Cargo's colors, unless they've been turned off
+            BackendKind::Cargo => {
+                vars.entry("CARGO_TERM_COLOR".into()).or_insert("always".into());
+            },
+            BackendKind::Zephyr => {
+                if let Some(base) = &self.zephyr.zephyr_base {
+                    vars.insert("ZEPHYR_BASE".into(), base.into());
+                }
+                if !self.zephyr.toolchain_variant.trim().is_empty() {
+                    vars.insert("ZEPHYR_TOOLCHAIN_VARIANT".into(), self.zephyr.toolchain_variant.trim().into());
+                }
+            },
+            BackendKind::EspIdf => {
+                let idf_path = match (&self.esp_idf.idf_path, env::var_os("IDF_PATH")) {
+                    (Some(path), _) => path.clone(),
+                    (None, Some(path)) => PathBuf::from(path),
+                    (None, None) => return Err(BackendError::MissingSetting("IDF_PATH".to_string())),
+                };
+                let script = if cfg!(windows) { "export.bat" } else { "export.sh" };
+                vars = capture_environment(&idf_path.join(script))?;
+                vars.insert("IDF_PATH".into(), idf_path.into());
+                vars.insert("IDF_TARGET".into(), self.idf_target()?.into());
+            },
+        }
+        vars.extend(self.env.iter().map(|(key, value)| (key.into(), value.into())));
+        Ok(vars)
+    }
+
+    /// The name of the tool that drives the build.
+    pub fn tool(&self) -> &str {
+        match self.kind {
+            BackendKind::Cargo => "cargo",
+            BackendKind::Zephyr => "west",
+            BackendKind::EspIdf => "idf.py",
+        }
+    }
+
+    /// Build the command for the given action, checking that the tool is there.
+    fn command(&self, project_dir: &Path, action: Action) -> Result<duct::Expression, BackendError> {
+        if action == Action::Check && self.kind != BackendKind::Cargo {
+            return Err(BackendError::Unsupported(format!("{} projects can't be checked with clippy", self.kind)));
+        }
+        let mut vars = self.environment()?;
+        let build_dir = project_dir.join("build");
+        let flash = action == Action::Flash;
+        let cmd = match (self.kind, flash) {
+            (BackendKind::Cargo, _) => {
+                let mut args: Vec<OsString> = vec!["-Z".into(), "unstable-options".into(), "-C".into(), project_dir.into()];
+                args.push(match action {
+                    Action::Build => "build",
+                    Action::Flash => "run",
+                    Action::Check => "clippy",
+                }.into());
+                if !self.features.is_empty() {
+                    args.extend(["--features".into(), self.features.join(",").into()]);
+                }
+                if self.reproducible {
+                    args.push("--locked".into());
+                    vars.extend(reproducible::environment(project_dir).into_iter().map(|(k, v)| (k.into(), v.into())));
+                }
+                duct::cmd("cargo", args)
+            },
+            (BackendKind::Zephyr, false) => {
+                duct::cmd!("west", "build", "-b", self.zephyr_board()?, "-d", &build_dir, project_dir)
+            },
+            (BackendKind::Zephyr, true) => duct::cmd!("west", "flash", "-d", &build_dir),
+            (BackendKind::EspIdf, false) => duct::cmd!("idf.py", "-C", project_dir, "-B", &build_dir, "build"),
+            (BackendKind::EspIdf, true) => {
+                let mut args: Vec<OsString> = vec!["-C".into(), project_dir.into(), "-B".into(), build_dir.into()];
+                if !self.esp_idf.port.trim().is_empty() {
+                    args.extend(["-p".into(), self.esp_idf.port.trim().into()]);
+                }
+                args.push("flash".into());
+                duct::cmd("idf.py", args)
+            },
+        };
+        let tool = self.tool();
+        if find_in_path(tool, vars.get(&OsString::from("PATH"))).is_none() {
+            return Err(BackendError::ToolNotFound(tool.to_string()));
+        }
+        Ok(cmd.full_env(vars))
+    }
+
+    /// The command that builds the project.
+    pub fn build_command(&self, project_dir: &Path) -> Result<duct::Expression, BackendError> {
+        self.command(project_dir, Action::Build)
+    }
+
+    /// The command that loads the project onto the board.
+    pub fn flash_command(&self, project_dir: &Path) -> Result<duct::Expression, BackendError> {
+        self.command(project_dir, Action::Flash)
+    }
+
+    /// The command that checks the project with clippy, which only Cargo projects can be.
+    pub fn check_command(&self, project_dir: &Path) -> Result<duct::Expression, BackendError> {
+        self.command(project_dir, Action::Check)
+    }
+
+    /// Show the backend settings in the Ui. The main board fills in any blank settings.
+    pub fn display_settings(&mut self, ui: &mut egui::Ui, main_board: Option<&Board>) {
+        for kind in enum_iterator::all::<BackendKind>() {
+            ui.radio_value(&mut self.kind, kind, kind.to_string());
+        }
+        ui.separator();
+        let hint = |value: Option<&str>| value.unwrap_or("required").to_string();
+        egui::Grid::new("build_backend_settings").num_columns(2).show(ui, |ui| {
+            match self.kind {
+                BackendKind::Cargo => {
+                    ui.label("no settings");
+                    ui.end_row();
+                },
+                BackendKind::Zephyr => {
+                    ui.label("board:");
+                    let board_hint = hint(main_board.and_then(|b| b.zephyr_board()));
+                    ui.add(egui::TextEdit::singleline(&mut self.zephyr.board).hint_text(board_hint));
+                    ui.end_row();
+                    ui.label("toolchain variant:");
+                    ui.add(egui::TextEdit::singleline(&mut self.zephyr.toolchain_variant).hint_text("zephyr"));
+                    ui.end_row();
+                    ui.label("ZEPHYR_BASE:");
+                    path_picker(ui, &mut self.zephyr.zephyr_base, "from west workspace");
+                    ui.end_row();
+                },
+                BackendKind::EspIdf => {
+                    ui.label("target:");
+                    let target_hint = hint(main_board.and_then(|b| b.esp_idf_target()));
+                    ui.add(egui::TextEdit::singleline(&mut self.esp_idf.target).hint_text(target_hint));
+                    ui.end_row();
+                    ui.label("port:");
+                    ui.add(egui::TextEdit::singleline(&mut self.esp_idf.port).hint_text("auto"));
+                    ui.end_row();
+                    ui.label("IDF_PATH:");
+                    path_picker(ui, &mut self.esp_idf.idf_path, "from environment");
+                    ui.end_row();
+                },
+            }
+        });
+    }
+}
+
+/// A label and folder picker for an optional directory setting.
+fn path_picker(ui: &mut egui::Ui, path: &mut Option<PathBuf>, default: &str) {
+    ui.horizontal(|ui| {
+        match path {
+            Some(p) => ui.label(p.display().to_string()),
+            None => ui.weak(default),
+        };
+        if ui.button("choose").clicked() {
+            if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                *path = Some(dir);
+            }
+        }
+        if path.is_some() && ui.button("reset").clicked() {
+            *path = None;
+        }
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A problem reported by the compiler or build system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Split "path/to/file.c:12:5" into its parts. The path may contain colons (i.e. a Windows drive).
+fn parse_location(location: &str) -> (PathBuf, Option<u32>, Option<u32>) {
+    let number = |s: &str| s.trim().parse::<u32>().ok();
+    if let Some((rest, last)) = location.rsplit_once(':') {
+        if let Some(last) = number(last) {
+            if let Some((file, line)) = rest.rsplit_once(':') {
+                if let Some(line) = number(line) {
+                    return (PathBuf::from(file), Some(line), Some(last));
+                }
+            }
+            return (PathBuf::from(rest), Some(last), None);
+        }
+    }
+    (PathBuf::from(location), None, None)
+}
+
+/// Pull the errors and warnings out of build output. This understands GCC/Clang
+/// (which Zephyr and ESP-IDF compile C with), rustc, CMake and Ninja.
+pub fn parse_diagnostics(output: &[String]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    // rustc gives the location on a following line, so remember where to put it
+    let mut pending_rustc: Option<usize> = None;
+    let mut lines = output.iter().map(|l| l.trim_end()).peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(location) = trimmed.strip_prefix("--> ") {
+            if let Some(i) = pending_rustc.take() {
+                let (file, line, column) = parse_location(location);
+                diagnostics[i].file = Some(file);
+                diagnostics[i].line = line;
+                diagnostics[i].column = column;
+            }
+            continue;
+        }
+        // GCC and Clang: "file:line:col: error: message"
+        let gcc = [(": fatal error: ", Severity::Error), (": error: ", Severity::Error), (": warning: ", Severity::Warning), (": note: ", Severity::Note)]
+            .into_iter()
+            .find_map(|(marker, severity)| line.split_once(marker).map(|(location, message)| (location, message, severity)));
+        if let Some((location, message, severity)) = gcc {
+            let (file, line, column) = parse_location(location);
+            diagnostics.push(Diagnostic { severity, file: Some(file), line, column, message: message.to_string() });
+            continue;
+        }
+        // CMake: "CMake Error at file:line (function):" followed by an indented message
+        let cmake = [("CMake Error", Severity::Error), ("CMake Warning", Severity::Warning)]
+            .into_iter()
+            .find_map(|(prefix, severity)| line.strip_prefix(prefix).map(|rest| (rest, severity)));
+        if let Some((rest, severity)) = cmake {
+            let (file, line) = match rest.strip_prefix(" at ").and_then(|r| r.split_once(' ')) {
+                Some((location, _)) => {
+                    let (file, line, _) = parse_location(location);
+                    (Some(file), line)
+                },
+                None => (None, None),
+            };
+            let mut message = String::new();
+            while let Some(next) = lines.peek() {
+                if next.trim().is_empty() && message.is_empty() {
+                    lines.next();
+                } else if next.starts_with(' ') {
+                    message = (message + " " + next.trim()).trim().to_string();
+                    lines.next();
+                } else {
+                    break;
+                }
+            }
+            diagnostics.push(Diagnostic { severity, file, line, column: None, message });
+            continue;
+        }
+        // Ninja
+        if let Some(target) = line.strip_prefix("FAILED: ") {
+            diagnostics.push(Diagnostic { severity: Severity::Error, file: None, line: None, column: None, message: format!("failed to build {}", target) });
+            continue;
+        }
+        // rustc (and Kconfig, which uses the same prefix): "error[E0425]: message"
+        let rustc = [("error", Severity::Error), ("warning", Severity::Warning)]
+            .into_iter()
+            .find_map(|(prefix, severity)| {
+                let rest = line.strip_prefix(prefix)?;
+                let rest = match rest.strip_prefix('[') {
+                    Some(coded) => coded.split_once(']')?.1,
+                    None => rest,
+                };
+                rest.strip_prefix(": ").map(|message| (message, severity))
+            });
+        if let Some((message, severity)) = rustc {
+            // cargo's summaries repeat what's already been reported
+            if message.contains("generated") && message.contains("warning") || message.starts_with("could not compile") || message.starts_with("aborting due to") {
+                continue;
+            }
+            diagnostics.push(Diagnostic { severity, file: None, line: None, column: None, message: message.to_string() });
+            pending_rustc = Some(diagnostics.len() - 1);
+        }
+    }
+    // the same header can be included (and complained about) many times
+    let mut unique: Vec<Diagnostic> = vec![];
+    for d in diagnostics {
+        if !unique.contains(&d) {
+            unique.push(d);
+        }
+    }
+    unique
+}
+
+/// Run a backend command on a job, sending its output through `tx` as it arrives, and
+/// then a summary of the errors and warnings in it.
+pub fn run_with_diagnostics(cmd: Result<duct::Expression, BackendError>, tx: &Sender<String>, job: &JobContext) -> Result<(), String> {
+    run_collecting_diagnostics(cmd, tx, job).0
+}
+
+/// Like run_with_diagnostics, also returning the diagnostics.
+pub fn run_collecting_diagnostics(cmd: Result<duct::Expression, BackendError>, tx: &Sender<String>, job: &JobContext) -> (Result<(), String>, Vec<Diagnostic>) {
+    let cmd = match cmd {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            let _ = tx.send(format!("{}\n", e));
+            return (Err(e.to_string()), vec![]);
+        },
+    };
+    // keep a copy of the output on its way to the terminal
+    let (line_tx, line_rx) = channel::<String>();
+    let forwarder = {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let mut output = vec![];
+            for line in line_rx {
+                let _ = tx.send(line.clone());
+                output.push(ansi::strip(&line));
+            }
+            output
+        })
+    };
+    let result = jobs::run_commands(&[cmd], &line_tx, job);
+    drop(line_tx);
+    let diagnostics = parse_diagnostics(&forwarder.join().unwrap_or_default());
+    if !diagnostics.is_empty() {
+        let _ = tx.send(format!("{}\n", summarize(&diagnostics)));
+        for d in diagnostics.iter().filter(|d| d.severity != Severity::Note) {
+            let _ = tx.send(format!("  {}\n", d));
+            if let Some(entry) = KnowledgeBase::builtin().explain(&d.message) {
+                let _ = tx.send(format!("    = help: {} (explained below)\n", entry.title));
+            }
+        }
+    }
+    (result, diagnostics)
+}
+
+/// A one-line summary of the diagnostics, i.e. "2 errors, 1 warning".
+pub fn summarize(diagnostics: &[Diagnostic]) -> String {
+    let count = |severity: Severity| diagnostics.iter().filter(|d| d.severity == severity).count();
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    format!("{}, {}", plural(count(Severity::Error), "error"), plural(count(Severity::Warning), "warning"))
+}
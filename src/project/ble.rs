@@ -0,0 +1,177 @@
+//! Title: Iron Coder Project Module - BLE
+//! Description: The link to BLE devices that the firmware update and GATT preview
+//!   windows share. It drives btleplug, which talks to the system's Bluetooth stack
+//!   (BlueZ, CoreBluetooth or WinRT), from blocking calls that the windows' jobs make.
+
+use log::info;
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use btleplug::api::{Central, Characteristic, Manager as _, Peripheral as _, ScanFilter, Service, ValueNotification, WriteType};
+use btleplug::platform::{Manager, Peripheral};
+use futures::{Stream, StreamExt};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+/// How long to scan for a device before giving up.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the devices found so far are checked while scanning.
+const SCAN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum BleError {
+    /// The background runtime for the Bluetooth stack couldn't start
+    RuntimeError(io::Error),
+    /// The system has no Bluetooth adapter
+    NoAdapter,
+    /// No device with the address or name was found while scanning
+    NotFound(String),
+    /// The device doesn't have the characteristic
+    NoCharacteristic(Uuid),
+    /// The device didn't answer in time
+    Timeout,
+    Disconnected,
+    /// The Bluetooth stack reported an error
+    StackError(btleplug::Error),
+}
+
+impl fmt::Display for BleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BleError::RuntimeError(e) => write!(f, "couldn't start the Bluetooth runtime: {}", e),
+            BleError::NoAdapter => write!(f, "no Bluetooth adapter found"),
+            BleError::NotFound(device) => write!(f, "{} didn't show up in a scan", device),
+            BleError::NoCharacteristic(uuid) => write!(f, "the device has no characteristic {}", uuid),
+            BleError::Timeout => write!(f, "the device didn't answer in time"),
+            BleError::Disconnected => write!(f, "the device disconnected"),
+            BleError::StackError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<btleplug::Error> for BleError {
+    fn from(e: btleplug::Error) -> Self {
+        BleError::StackError(e)
+    }
+}
+
+/// Whether a device found in a scan is the one asked for, by its address, its id (macOS
+/// hides addresses, and gives each device a UUID instead) or the name it advertises.
+pub fn is_device(wanted: &str, address: &str, id: &str, name: Option<&str>) -> bool {
+    let wanted = wanted.trim();
+    !wanted.is_empty() && (wanted.eq_ignore_ascii_case(address) || wanted.eq_ignore_ascii_case(id) || name == Some(wanted))
+}
+
+/// Scan with the first adapter until the device shows up.
+async fn find(wanted: &str) -> Result<Peripheral, BleError> {
+    let manager = Manager::new().await?;
+    let adapter = manager.adapters().await?.into_iter().next().ok_or(BleError::NoAdapter)?;
+    adapter.start_scan(ScanFilter::default()).await?;
+    let started = Instant::now();
+    let mut found = None;
+    while found.is_none() && started.elapsed() < SCAN_TIMEOUT {
+        tokio::time::sleep(SCAN_INTERVAL).await;
+        for peripheral in adapter.peripherals().await? {
+            let name = peripheral.properties().await?.and_then(|p| p.local_name);
+            if is_device(wanted, &peripheral.address().to_string(), &peripheral.id().to_string(), name.as_deref()) {
+                found = Some(peripheral);
+                break;
+            }
+        }
+    }
+    adapter.stop_scan().await?;
+    found.ok_or_else(|| BleError::NotFound(wanted.to_string()))
+}
+
+/// A connected device, with its services discovered. It disconnects when dropped.
+pub struct BleDevice {
+    runtime: Runtime,
+    peripheral: Peripheral,
+    notifications: Pin<Box<dyn Stream<Item = ValueNotification> + Send>>,
+}
+
+impl BleDevice {
+
+    /// Find the device with the address or name, connect, and discover its services.
+    pub fn connect(wanted: &str) -> Result<Self, BleError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(BleError::RuntimeError)?;
+        let (peripheral, notifications) = runtime.block_on(async {
+            let peripheral = find(wanted).await?;
+            peripheral.connect().await?;
+            peripheral.discover_services().await?;
+            let notifications = peripheral.notifications().await?;
+            Ok::<_, BleError>((peripheral, notifications))
+        })?;
+        info!("connected to {} ({})", wanted, peripheral.address());
+        Ok(Self { runtime, peripheral, notifications })
+    }
+
+    fn run<T>(&self, operation: impl Future<Output = btleplug::Result<T>>) -> Result<T, BleError> {
+        self.runtime.block_on(operation).map_err(BleError::from)
+    }
+
+    fn characteristic(&self, service: Uuid, characteristic: Uuid) -> Result<Characteristic, BleError> {
+        self.peripheral.characteristics().into_iter()
+            .find(|c| c.service_uuid == service && c.uuid == characteristic)
+            .ok_or(BleError::NoCharacteristic(characteristic))
+    }
+
+    pub fn services(&self) -> Vec<Service> {
+        self.peripheral.services().into_iter().collect()
+    }
+
+    pub fn read(&self, service: Uuid, characteristic: Uuid) -> Result<Vec<u8>, BleError> {
+        let c = self.characteristic(service, characteristic)?;
+        self.run(self.peripheral.read(&c))
+    }
+
+    pub fn write(&self, service: Uuid, characteristic: Uuid, data: &[u8], with_response: bool) -> Result<(), BleError> {
+        let c = self.characteristic(service, characteristic)?;
+        let write_type = if with_response { WriteType::WithResponse } else { WriteType::WithoutResponse };
+        self.run(self.peripheral.write(&c, data, write_type))
+    }
+
+    /// Turn notifications (or indications) from a characteristic on or off.
+    pub fn set_notify(&self, service: Uuid, characteristic: Uuid, enable: bool) -> Result<(), BleError> {
+        let c = self.characteristic(service, characteristic)?;
+        if enable {
+            self.run(self.peripheral.subscribe(&c))
+        } else {
+            self.run(self.peripheral.unsubscribe(&c))
+        }
+    }
+
+    /// Wait up to `timeout` for a notification, returning its service, characteristic
+    /// and value.
+    pub fn notification(&mut self, timeout: Duration) -> Result<Option<(Uuid, Uuid, Vec<u8>)>, BleError> {
+        let notifications = &mut self.notifications;
+        let next = self.runtime.block_on(async move { tokio::time::timeout(timeout, notifications.next()).await });
+        match next {
+            Err(_) => Ok(None),
+            Ok(None) => Err(BleError::Disconnected),
+            Ok(Some(notification)) => {
+                let service = self.peripheral.characteristics().into_iter()
+                    .find(|c| c.uuid == notification.uuid)
+                    .map_or(Uuid::nil(), |c| c.service_uuid);
+                Ok(Some((service, notification.uuid, notification.value)))
+            },
+        }
+    }
+}
+
+impl Drop for BleDevice {
+    fn drop(&mut self) {
+        if let Err(e) = self.runtime.block_on(self.peripheral.disconnect()) {
+            info!("couldn't disconnect cleanly: {}", e);
+        }
+    }
+}
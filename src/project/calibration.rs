@@ -0,0 +1,119 @@
+//! Title: Iron Coder Project Module - Calibration
+//! Description: Linear calibrations of a project's analog channels (ADC inputs or DAC
+//!   outputs), fitted from readings taken against known references in the signal viewer.
+//!   They're kept in the project file and emitted as a generated `src/calibration.rs`
+//!   module of offset and gain constants, so firmware can correct its raw readings with
+//!   `value = raw * GAIN + OFFSET`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use quote::{format_ident, quote};
+use serde::{Serialize, Deserialize};
+
+/// The correction for one channel: `value = raw * gain + offset`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub offset: f64,
+    pub gain: f64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { offset: 0.0, gain: 1.0 }
+    }
+}
+
+impl Calibration {
+
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.gain + self.offset
+    }
+
+    /// Fit the calibration to pairs of (raw reading, known reference value) by least
+    /// squares. With one pair, only the offset is fitted.
+    pub fn fit(points: &[(f64, f64)]) -> Result<Self, String> {
+        match points {
+            [] => Err("take a reading against a known reference first".to_string()),
+            [(raw, reference)] => Ok(Self { offset: reference - raw, gain: 1.0 }),
+            _ => {
+                let n = points.len() as f64;
+                let mean_raw = points.iter().map(|p| p.0).sum::<f64>() / n;
+                let mean_reference = points.iter().map(|p| p.1).sum::<f64>() / n;
+                let covariance: f64 = points.iter().map(|(r, v)| (r - mean_raw) * (v - mean_reference)).sum();
+                let variance: f64 = points.iter().map(|(r, _)| (r - mean_raw).powi(2)).sum();
+                if variance <= f64::EPSILON * mean_raw.abs().max(1.0) {
+                    return Err("the readings need to be at different levels to fit a gain".to_string());
+                }
+                let gain = covariance / variance;
+                Ok(Self { offset: mean_reference - gain * mean_raw, gain })
+            },
+        }
+    }
+}
+
+/// Turn a channel name into the prefix of its constants, i.e. "adc0" into "ADC0".
+fn constant_prefix(channel: &str) -> String {
+    let mut prefix: String = channel.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if !prefix.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        prefix.insert_str(0, "CH_");
+    }
+    prefix
+}
+
+/// The calibrations of a project, by channel name.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct CalibrationConfig {
+    pub channels: BTreeMap<String, Calibration>,
+}
+
+impl CalibrationConfig {
+
+    /// Generate the Rust source of the calibration module.
+    pub fn generate_module(&self) -> Result<String, String> {
+        let mut items = vec![];
+        let mut prefixes: Vec<String> = vec![];
+        for (channel, calibration) in self.channels.iter() {
+            let prefix = constant_prefix(channel);
+            if prefixes.contains(&prefix) {
+                return Err(format!("channels named like {} would share constants", channel));
+            }
+            if !(calibration.offset.is_finite() && calibration.gain.is_finite()) {
+                return Err(format!("the calibration of {} isn't a number", channel));
+            }
+            let offset = format_ident!("{}_OFFSET", prefix);
+            let gain = format_ident!("{}_GAIN", prefix);
+            let (offset_value, gain_value) = (calibration.offset as f32, calibration.gain as f32);
+            let offset_doc = format!(" Added to {} readings after the gain", channel);
+            let gain_doc = format!(" Multiplies raw {} readings", channel);
+            items.push(quote! {
+                #[doc = #gain_doc]
+                pub const #gain: f32 = #gain_value;
+                #[doc = #offset_doc]
+                pub const #offset: f32 = #offset_value;
+            });
+            prefixes.push(prefix);
+        }
+        let tokens = quote! {
+            //! Analog channel calibrations, generated by Iron Coder from the signal viewer.
+            //! Don't edit by hand. Correct a raw reading with `raw * GAIN + OFFSET`.
+            #![allow(dead_code)]
+            #(#items)*
+        };
+        let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+        Ok(prettyplease::unparse(&file))
+    }
+
+    /// Write the calibration module into the project's source directory.
+    pub fn write_module(&self, project_dir: &Path) -> io::Result<()> {
+        let code = self.generate_module().map_err(io::Error::other)?;
+        let src = project_dir.join("src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("calibration.rs"), code)
+    }
+}
@@ -0,0 +1,241 @@
+//! Title: Iron Coder Project Module - Compatibility
+//! Description: Which releases of HALs, embedded-hal and driver crates work together.
+//!   When a driver is added to a project, the newest release that works with the crates
+//!   already in its Cargo.toml is picked, and builds warn about crates that can't work
+//!   together before Cargo or rustc fails on them. The matrix is kept in
+//!   `assets/knowledge/compatibility.toml`, so adding a crate doesn't take any code.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::{Serialize, Deserialize};
+
+/// The matrix that ships with Iron Coder.
+const BUILTIN: &str = include_str!("../../assets/knowledge/compatibility.toml");
+
+/// The part of a version Cargo keeps fixed when it picks a compatible one, i.e. "1" for
+/// 1.4.2, "0.9" for 0.9.1 and "0.0.3" for 0.0.3. None if it isn't a version.
+pub fn caret_key(version: &str) -> Option<String> {
+    let version = version.trim().trim_start_matches(['^', '=', '~']).trim();
+    let mut parts = version.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().transpose().ok()?;
+    let patch = parts.next().transpose().ok()?;
+    match (major, minor, patch) {
+        (0, Some(0), Some(patch)) => Some(format!("0.0.{}", patch)),
+        (0, Some(minor), _) => Some(format!("0.{}", minor)),
+        (0, None, _) => None,
+        (major, _, _) => Some(major.to_string()),
+    }
+}
+
+/// Crate names are compared the way crates.io does, ignoring case and `-` versus `_`.
+fn same_crate(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.chars().zip(b.chars()).all(|(a, b)| {
+        a.eq_ignore_ascii_case(&b) || (matches!(a, '-' | '_') && matches!(b, '-' | '_'))
+    })
+}
+
+/// A release of a crate, and the versions of the interface crates it implements and
+/// uses.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Release {
+    pub version: String,
+    #[serde(default)]
+    pub implements: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub uses: BTreeMap<String, Vec<String>>,
+}
+
+/// The known releases of a crate, newest first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CrateReleases {
+    pub name: String,
+    pub releases: Vec<Release>,
+}
+
+/// A crate in a project, at the release of it the matrix knows.
+#[derive(Clone, Debug)]
+struct Known<'a> {
+    name: &'a str,
+    release: &'a Release,
+}
+
+impl Known<'_> {
+
+    /// The versions of `interface` the release implements, which include its own.
+    fn implements(&self, interface: &str) -> Option<&[String]> {
+        if same_crate(self.name, interface) {
+            return Some(std::slice::from_ref(&self.release.version));
+        }
+        versions_of(&self.release.implements, interface)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {}", self.name, self.release.version)
+    }
+}
+
+fn versions_of<'a>(versions: &'a BTreeMap<String, Vec<String>>, interface: &str) -> Option<&'a [String]> {
+    versions.iter().find(|(name, _)| same_crate(name, interface)).map(|(_, versions)| versions.as_slice())
+}
+
+/// The releases of crates known to work together.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Matrix {
+    #[serde(default, rename = "crate")]
+    pub crates: Vec<CrateReleases>,
+}
+
+impl Matrix {
+
+    /// Parse and check a matrix.
+    pub fn from_toml_str(toml: &str) -> Result<Self, String> {
+        let matrix: Self = toml::from_str(toml).map_err(|e| e.to_string())?;
+        for (i, entry) in matrix.crates.iter().enumerate() {
+            if entry.name.is_empty() || matrix.crates[..i].iter().any(|c| same_crate(&c.name, &entry.name)) {
+                return Err(format!("crate {} needs a name of its own", i + 1));
+            }
+            if entry.releases.is_empty() {
+                return Err(format!("{} has no releases", entry.name));
+            }
+            for release in &entry.releases {
+                let mut versions = std::iter::once(&release.version)
+                    .chain(release.implements.values().flatten())
+                    .chain(release.uses.values().flatten());
+                if let Some(bad) = versions.find(|v| caret_key(v).is_none()) {
+                    return Err(format!("{} {} has a version that isn't one: {}", entry.name, release.version, bad));
+                }
+            }
+        }
+        Ok(matrix)
+    }
+
+    /// The matrix that ships with Iron Coder.
+    pub fn builtin() -> &'static Self {
+        static MATRIX: OnceLock<Matrix> = OnceLock::new();
+        MATRIX.get_or_init(|| Self::from_toml_str(BUILTIN).expect("the built-in compatibility matrix is checked by its tests"))
+    }
+
+    pub fn releases(&self, name: &str) -> Option<&CrateReleases> {
+        self.crates.iter().find(|c| same_crate(&c.name, name))
+    }
+
+    /// The dependencies the matrix knows the release of.
+    fn known<'a>(&'a self, dependencies: &'a [(String, String)]) -> Vec<Known<'a>> {
+        dependencies.iter().filter_map(|(name, version)| {
+            let entry = self.releases(name)?;
+            let key = caret_key(version)?;
+            let release = entry.releases.iter().find(|r| caret_key(&r.version).as_ref() == Some(&key))?;
+            Some(Known { name: &entry.name, release })
+        }).collect()
+    }
+
+    /// Why the crates in `dependencies`, as (name, version) pairs, can't work together:
+    /// a crate uses an interface that a crate implementing it doesn't share a version of.
+    /// Crates the matrix doesn't know are left out.
+    pub fn conflicts(&self, dependencies: &[(String, String)]) -> Vec<String> {
+        let known = self.known(dependencies);
+        let mut conflicts = Vec::new();
+        for user in &known {
+            for (interface, used) in &user.release.uses {
+                for implementer in known.iter().filter(|k| k.name != user.name) {
+                    let Some(implemented) = implementer.implements(interface) else {
+                        continue;
+                    };
+                    if used.iter().any(|v| implemented.iter().any(|w| caret_key(v) == caret_key(w))) {
+                        continue;
+                    }
+                    let uses = format!("{} uses {} {}", user.describe(), interface, used.join(" or "));
+                    conflicts.push(match same_crate(implementer.name, interface) {
+                        true => format!("{}, but the project has {}", uses, implementer.describe()),
+                        false => format!("{}, but {} implements {} {}", uses, implementer.describe(), interface, implemented.join(" or ")),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// The newest release of `name` that works with `dependencies`. None if the matrix
+    /// doesn't know the crate, and the reason if no release of it works.
+    pub fn pick(&self, dependencies: &[(String, String)], name: &str) -> Result<Option<String>, String> {
+        let Some(entry) = self.releases(name) else {
+            return Ok(None);
+        };
+        let existing = self.conflicts(dependencies);
+        let mut reason = None;
+        for release in &entry.releases {
+            let mut with = dependencies.to_vec();
+            with.push((entry.name.clone(), release.version.clone()));
+            let new: Vec<String> = self.conflicts(&with).into_iter().filter(|c| !existing.contains(c)).collect();
+            match new.into_iter().next() {
+                None => return Ok(Some(release.version.clone())),
+                Some(conflict) => {
+                    reason.get_or_insert(conflict);
+                },
+            }
+        }
+        Err(format!("no release of {} works with the project's crates: {}", entry.name, reason.unwrap_or_default()))
+    }
+}
+
+/// The (name, version) pairs of a manifest's dependencies, under the names they're
+/// published as. Dependencies from a path or git repository without a version are left
+/// out.
+pub fn dependencies(manifest: &str) -> Result<Vec<(String, String)>, String> {
+    let manifest: toml::Table = manifest.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let Some(table) = manifest.get("dependencies").and_then(toml::Value::as_table) else {
+        return Ok(Vec::new());
+    };
+    Ok(table.iter().filter_map(|(name, value)| match value {
+        toml::Value::String(version) => Some((name.clone(), version.clone())),
+        toml::Value::Table(details) => {
+            let version = details.get("version")?.as_str()?;
+            let name = details.get("package").and_then(toml::Value::as_str).unwrap_or(name);
+            Some((name.to_string(), version.to_string()))
+        },
+        _ => None,
+    }).collect())
+}
+
+/// Whether the project's Cargo.toml has a crate, under any name it's published as.
+pub fn has_dependency(project_dir: &Path, name: &str) -> bool {
+    fs::read_to_string(project_dir.join("Cargo.toml")).ok()
+        .and_then(|manifest| dependencies(&manifest).ok())
+        .is_some_and(|deps| deps.iter().any(|(dep, _)| same_crate(dep, name)))
+}
+
+/// The documentation of a crate on docs.rs.
+pub fn docs_url(name: &str) -> String {
+    format!("https://docs.rs/{}", name.trim())
+}
+
+/// The crates in the project's Cargo.toml that can't work together. Nothing if it can't
+/// be read.
+pub fn check_project(project_dir: &Path) -> Vec<String> {
+    fs::read_to_string(project_dir.join("Cargo.toml")).ok()
+        .and_then(|manifest| dependencies(&manifest).ok())
+        .map(|deps| Matrix::builtin().conflicts(&deps))
+        .unwrap_or_default()
+}
+
+/// What to hand `cargo add` to add `name` to the project: the crate at the newest release
+/// that works with the project's crates, or just the crate if the matrix doesn't know it
+/// or it's already there. An error says why no release works.
+pub fn crate_to_add(project_dir: &Path, name: &str) -> Result<String, String> {
+    let deps = fs::read_to_string(project_dir.join("Cargo.toml")).ok()
+        .and_then(|manifest| dependencies(&manifest).ok())
+        .unwrap_or_default();
+    if deps.iter().any(|(dep, _)| same_crate(dep, name)) {
+        return Ok(name.to_string());
+    }
+    Ok(match Matrix::builtin().pick(&deps, name)? {
+        Some(version) => format!("{}@{}", name, version),
+        None => name.to_string(),
+    })
+}
@@ -0,0 +1,231 @@
+//! Title: Iron Coder Project Module - Config
+//! Description: The values a project picks for its boards' compile-time configuration
+//!   options (see board::config). They're edited in a form, and emitted as a generated
+//!   `src/config.rs` module of constants plus the cargo features they turn on, so
+//!   firmware doesn't need hand-edited constants.
+
+use log::warn;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use quote::{format_ident, quote};
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::config::{ConfigOption, ConfigType, ConfigValue};
+
+/// An option from the schema, along with the name of the board that declared it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaEntry {
+    pub board: String,
+    pub option: ConfigOption,
+}
+
+/// Collect the config options of all of the given boards. If two boards declare an
+/// option with the same name, the first one wins.
+pub fn schema(boards: &[Board]) -> Vec<SchemaEntry> {
+    let mut entries: Vec<SchemaEntry> = vec![];
+    for board in boards {
+        for option in board.config_options() {
+            if let Some(existing) = entries.iter().find(|e| e.option.name == option.name) {
+                warn!("config option {} of {} is already declared by {}", option.name, board.get_name(), existing.board);
+                continue;
+            }
+            entries.push(SchemaEntry { board: board.get_name().to_string(), option: option.clone() });
+        }
+    }
+    entries
+}
+
+/// The chosen config values of a project, by option name. Options without a value
+/// here (or with a value that no longer fits the schema) use their default.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct ProjectConfig {
+    values: BTreeMap<String, ConfigValue>,
+}
+
+impl ProjectConfig {
+
+    /// The value of an option.
+    pub fn value(&self, option: &ConfigOption) -> ConfigValue {
+        match self.values.get(&option.name) {
+            Some(value) if option.check_value(value).is_ok() => value.clone(),
+            _ => option.default.clone(),
+        }
+    }
+
+    /// Set the value of an option, if it fits.
+    pub fn set(&mut self, option: &ConfigOption, value: ConfigValue) -> Result<(), String> {
+        option.check_value(&value)?;
+        if value == option.default {
+            self.values.remove(&option.name);
+        } else {
+            self.values.insert(option.name.clone(), value);
+        }
+        Ok(())
+    }
+
+    /// Whether an option applies, i.e. the options it depends on are all turned on.
+    pub fn is_enabled(&self, option: &ConfigOption, schema: &[SchemaEntry]) -> bool {
+        let mut current = option;
+        // follow the chain of dependencies, giving up on cycles
+        for _ in 0..=schema.len() {
+            let Some(dependency) = &current.depends_on else {
+                return true;
+            };
+            match schema.iter().find(|e| &e.option.name == dependency) {
+                Some(e) if e.option.kind == ConfigType::Bool && self.value(&e.option) == ConfigValue::Bool(true) => current = &e.option,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// The cargo features turned on by the enabled options.
+    pub fn enabled_features(&self, schema: &[SchemaEntry]) -> Vec<String> {
+        let mut features: Vec<String> = schema.iter()
+            .filter(|e| self.is_enabled(&e.option, schema))
+            .flat_map(|e| e.option.features(&self.value(&e.option)))
+            .collect();
+        features.sort();
+        features.dedup();
+        features
+    }
+
+    /// Generate the Rust source of the config module. Options that don't apply still get
+    /// their default, so code that refers to them keeps compiling.
+    pub fn generate_module(&self, schema: &[SchemaEntry]) -> Result<String, String> {
+        let mut items = vec![];
+        for SchemaEntry { option, .. } in schema {
+            option.validate()?;
+            let name = format_ident!("{}", option.name);
+            let value = if self.is_enabled(option, schema) { self.value(option) } else { option.default.clone() };
+            let doc = if option.description.is_empty() { format!(" {}", option.name) } else { format!(" {}", option.description) };
+            let item = match value {
+                ConfigValue::Bool(b) => quote! {
+                    #[doc = #doc]
+                    pub const #name: bool = #b;
+                },
+                ConfigValue::Int(i) => {
+                    let rust_type: syn::Type = syn::parse_str(&option.rust_type()).map_err(|e| e.to_string())?;
+                    let literal: syn::Expr = syn::parse_str(&i.to_string()).map_err(|e| e.to_string())?;
+                    quote! {
+                        #[doc = #doc]
+                        pub const #name: #rust_type = #literal;
+                    }
+                },
+                ConfigValue::Choice(choice) => {
+                    let type_name = format_ident!("{}", option.type_name());
+                    let variants = option.choices.iter().flatten().map(|c| format_ident!("{}", c));
+                    let choice = format_ident!("{}", choice);
+                    quote! {
+                        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+                        pub enum #type_name {
+                            #(#variants),*
+                        }
+                        #[doc = #doc]
+                        pub const #name: #type_name = #type_name::#choice;
+                    }
+                },
+            };
+            items.push(item);
+        }
+        let tokens = quote! {
+            //! Project configuration, generated by Iron Coder from the project settings.
+            //! Don't edit by hand; change the values in the config view instead.
+            #![allow(dead_code)]
+            #(#items)*
+        };
+        let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+        Ok(prettyplease::unparse(&file))
+    }
+
+    /// Write `src/config.rs`, and declare any features the schema can turn on that the
+    /// project's Cargo.toml doesn't have yet.
+    pub fn write(&self, schema: &[SchemaEntry], project_dir: &Path) -> io::Result<()> {
+        let code = self.generate_module(schema).map_err(io::Error::other)?;
+        let src = project_dir.join("src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("config.rs"), code)?;
+        let manifest_path = project_dir.join("Cargo.toml");
+        if manifest_path.exists() {
+            let manifest = fs::read_to_string(&manifest_path)?;
+            let features: Vec<String> = schema.iter().flat_map(|e| e.option.all_features()).collect();
+            if let Some(updated) = declare_features(&manifest, &features).map_err(io::Error::other)? {
+                fs::write(&manifest_path, updated)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Show the config form for the schema, grouped by board. Returns true if a value changed.
+    pub fn display(&mut self, ui: &mut egui::Ui, schema: &[SchemaEntry]) -> bool {
+        let mut changed = false;
+        let mut boards: Vec<&str> = schema.iter().map(|e| e.board.as_str()).collect();
+        boards.dedup();
+        for board in boards {
+            egui::CollapsingHeader::new(board).default_open(true).show(ui, |ui| {
+                egui::Grid::new(("config_options", board)).num_columns(2).striped(true).show(ui, |ui| {
+                    for SchemaEntry { option, .. } in schema.iter().filter(|e| e.board == board) {
+                        let enabled = self.is_enabled(option, schema);
+                        let label = ui.add_enabled(enabled, egui::Label::new(&option.name));
+                        if !option.description.is_empty() {
+                            label.on_hover_text(&option.description);
+                        }
+                        let mut value = self.value(option);
+                        ui.add_enabled_ui(enabled, |ui| {
+                            ui.horizontal(|ui| {
+                                match &mut value {
+                                    ConfigValue::Bool(b) => {
+                                        ui.checkbox(b, "");
+                                    },
+                                    ConfigValue::Int(i) => {
+                                        let (min, max) = option.range();
+                                        ui.add(egui::DragValue::new(i).clamp_range(min..=max));
+                                    },
+                                    ConfigValue::Choice(c) => {
+                                        egui::ComboBox::from_id_source(("config_choice", &option.name))
+                                        .selected_text(c.as_str())
+                                        .show_ui(ui, |ui| {
+                                            for choice in option.choices.iter().flatten() {
+                                                ui.selectable_value(c, choice.clone(), choice);
+                                            }
+                                        });
+                                    },
+                                }
+                                if value != option.default && ui.small_button("reset").clicked() {
+                                    value = option.default.clone();
+                                }
+                            });
+                        });
+                        if value != self.value(option) && self.set(option, value).is_ok() {
+                            changed = true;
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        }
+        changed
+    }
+}
+
+/// Add `feature = []` entries to a Cargo manifest for any of the features it doesn't
+/// declare, keeping its formatting. Returns None if nothing needed adding.
+pub fn declare_features(manifest: &str, features: &[String]) -> Result<Option<String>, String> {
+    let mut doc: toml_edit::DocumentMut = manifest.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+    let table = doc.entry("features").or_insert(toml_edit::table())
+        .as_table_mut().ok_or("[features] in Cargo.toml isn't a table")?;
+    let mut added = false;
+    for feature in features {
+        if !table.contains_key(feature) {
+            table.insert(feature, toml_edit::value(toml_edit::Array::new()));
+            added = true;
+        }
+    }
+    Ok(added.then(|| doc.to_string()))
+}
@@ -0,0 +1,539 @@
+//! Title: Iron Coder Project Module - Configuration Partition
+//! Description: Settings the firmware reads from flash instead of having built in, like
+//!   a device name or a server address. The settings are defined with a type and edited
+//!   in a grid, then made into an image of one of the board's NVS or littlefs partitions
+//!   and written to the partition's offset, on their own or before the firmware.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobId, JobState};
+use crate::board::Board;
+use crate::board::flashing::FlashMethod;
+use crate::board::partitions::{Partition, PartitionKind, PartitionTable};
+use crate::project::dfu::crc32;
+use crate::project::storage::LittlefsGeometry;
+
+pub const NVS_PAGE_SIZE: usize = 4096;
+const NVS_ENTRY_SIZE: usize = 32;
+const NVS_ENTRIES_PER_PAGE: usize = 126;
+/// Where a page's entries start, after its header and its entry state bitmap.
+const NVS_FIRST_ENTRY: usize = 64;
+const NVS_PAGE_ACTIVE: u32 = 0xffff_fffe;
+const NVS_PAGE_FULL: u32 = 0xffff_fffc;
+/// Version 2 of the NVS format, the one ESP-IDF 4 and later read
+const NVS_VERSION: u8 = 0xfe;
+const NVS_TYPE_STRING: u8 = 0x21;
+/// The longest key or namespace NVS takes.
+pub const MAX_KEY_LEN: usize = 15;
+/// The longest string NVS keeps in one page, with its terminating NUL.
+const MAX_STRING_LEN: usize = 4000;
+/// The directory the settings are written to on a littlefs partition, one file a key.
+pub const LITTLEFS_DIR: &str = "config";
+
+/// The types a setting can have.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingType {
+    U8,
+    I8,
+    U16,
+    I16,
+    #[default]
+    U32,
+    I32,
+    U64,
+    I64,
+    Bool,
+    String,
+}
+
+impl fmt::Display for SettingType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SettingType::U8 => "u8",
+            SettingType::I8 => "i8",
+            SettingType::U16 => "u16",
+            SettingType::I16 => "i16",
+            SettingType::U32 => "u32",
+            SettingType::I32 => "i32",
+            SettingType::U64 => "u64",
+            SettingType::I64 => "i64",
+            SettingType::Bool => "bool",
+            SettingType::String => "string",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl SettingType {
+
+    /// The type NVS stores it as. A bool is kept as a u8.
+    fn nvs_type(&self) -> u8 {
+        match self {
+            SettingType::U8 | SettingType::Bool => 0x01,
+            SettingType::I8 => 0x11,
+            SettingType::U16 => 0x02,
+            SettingType::I16 => 0x12,
+            SettingType::U32 => 0x04,
+            SettingType::I32 => 0x14,
+            SettingType::U64 => 0x08,
+            SettingType::I64 => 0x18,
+            SettingType::String => NVS_TYPE_STRING,
+        }
+    }
+
+    /// The range of the integer types, as (min, max).
+    fn range(&self) -> Option<(i128, i128)> {
+        match self {
+            SettingType::U8 => Some((0, u8::MAX as i128)),
+            SettingType::I8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            SettingType::U16 => Some((0, u16::MAX as i128)),
+            SettingType::I16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            SettingType::U32 => Some((0, u32::MAX as i128)),
+            SettingType::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            SettingType::U64 => Some((0, u64::MAX as i128)),
+            SettingType::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            SettingType::Bool | SettingType::String => None,
+        }
+    }
+}
+
+/// A setting, with its value as it's typed in the editor.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Setting {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub kind: SettingType,
+    pub value: String,
+    pub description: String,
+}
+
+/// Parse an integer in decimal, or in hex with a `0x` in front.
+fn parse_integer(value: &str) -> Option<i128> {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value),
+    };
+    let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+impl Setting {
+
+    /// The value's bytes as the firmware reads them: little-endian integers, a bool as one
+    /// byte, and a string's UTF-8 without a terminating NUL.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self.kind {
+            SettingType::String => {
+                if self.value.len() + 1 > MAX_STRING_LEN {
+                    return Err(format!("{} is longer than {} bytes", self.key, MAX_STRING_LEN - 1));
+                }
+                Ok(self.value.as_bytes().to_vec())
+            },
+            SettingType::Bool => match self.value.trim() {
+                "true" | "1" => Ok(vec![1]),
+                "false" | "0" => Ok(vec![0]),
+                v => Err(format!("{} needs true or false, not {:?}", self.key, v)),
+            },
+            kind => {
+                let (min, max) = kind.range().unwrap_or_default();
+                let value = parse_integer(&self.value)
+                    .filter(|v| (min..=max).contains(v))
+                    .ok_or_else(|| format!("{} needs a {} from {} to {}, not {:?}", self.key, kind, min, max, self.value))?;
+                let width = match kind {
+                    SettingType::U8 | SettingType::I8 => 1,
+                    SettingType::U16 | SettingType::I16 => 2,
+                    SettingType::U32 | SettingType::I32 => 4,
+                    _ => 8,
+                };
+                Ok(value.to_le_bytes()[..width].to_vec())
+            },
+        }
+    }
+}
+
+/// The settings the project keeps in a configuration partition, and which partition of
+/// the project's partition table.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ConfigPartition {
+    /// The partition the settings go in
+    pub partition: String,
+    /// The NVS namespace the firmware opens to read them
+    pub namespace: String,
+    pub settings: Vec<Setting>,
+    /// Write the partition before the firmware each time it's loaded onto the board
+    pub flash_with_firmware: bool,
+}
+
+impl Default for ConfigPartition {
+    fn default() -> Self {
+        Self {
+            partition: String::new(),
+            namespace: "config".to_string(),
+            settings: vec![],
+            flash_with_firmware: false,
+        }
+    }
+}
+
+fn validate_key(what: &str, key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN || !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(format!("the {} {:?} needs 1 to {} ASCII letters, digits or symbols", what, key, MAX_KEY_LEN));
+    }
+    Ok(())
+}
+
+impl ConfigPartition {
+
+    /// Check the namespace, that the keys are valid and different, and that every value
+    /// is one of its type.
+    pub fn validate(&self) -> Result<(), String> {
+        validate_key("namespace", &self.namespace)?;
+        for (i, setting) in self.settings.iter().enumerate() {
+            validate_key("key", &setting.key)?;
+            if self.settings[..i].iter().any(|s| s.key == setting.key) {
+                return Err(format!("there's more than one setting called {}", setting.key));
+            }
+            setting.bytes()?;
+        }
+        Ok(())
+    }
+
+    /// The partition the settings go in, which has to be an NVS or littlefs one.
+    pub fn target<'a>(&self, table: &'a PartitionTable) -> Result<&'a Partition, String> {
+        let partition = table.partition(&self.partition)
+            .ok_or_else(|| format!("the board has no partition called {:?}", self.partition))?;
+        match partition.kind {
+            PartitionKind::Nvs | PartitionKind::Littlefs => Ok(partition),
+            kind => Err(format!("partition {} is a {} partition, not an NVS or littlefs one", partition.name, kind)),
+        }
+    }
+
+    /// Where the partition's image is made.
+    pub fn image_path(&self, project_dir: &Path) -> PathBuf {
+        project_dir.join("target").join("iron-coder").join(format!("config-{}.bin", self.partition))
+    }
+
+    /// Make the partition's image, and return the commands that finish it, if any, and
+    /// then write it to the board. An NVS image is written straight away; a littlefs one
+    /// has its files staged, and is packed by mklittlefs. The table is the project's.
+    pub fn commands(&self, board: &Board, table: &PartitionTable, project_dir: &Path, flash: bool) -> Result<Vec<duct::Expression>, String> {
+        self.validate()?;
+        let partition = self.target(table)?;
+        let image = self.image_path(project_dir);
+        let dir = image.parent().unwrap_or(project_dir);
+        fs::create_dir_all(dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+        let mut commands = vec![];
+        match partition.kind {
+            PartitionKind::Littlefs => {
+                let staging = dir.join(format!("config-{}", self.partition));
+                let _ = fs::remove_dir_all(&staging);
+                stage_littlefs(&self.settings, &staging).map_err(|e| format!("couldn't stage the settings: {}", e))?;
+                let geometry = LittlefsGeometry::default();
+                let geometry = LittlefsGeometry { blocks: (partition.size.bytes() / geometry.block_size as u64) as u32, ..geometry };
+                commands.push(pack_littlefs(&staging, &geometry, &image));
+            },
+            _ => {
+                let bytes = nvs_image(&self.namespace, &self.settings, partition.size.bytes() as usize)?;
+                fs::write(&image, bytes).map_err(|e| format!("couldn't write {}: {}", image.display(), e))?;
+            },
+        }
+        if flash {
+            commands.push(flash_command(board, table, partition, &image)?);
+        }
+        Ok(commands)
+    }
+}
+
+/// Write each setting to its own file under [`LITTLEFS_DIR`] in `dir`, as its bytes.
+pub fn stage_littlefs(settings: &[Setting], dir: &Path) -> std::io::Result<()> {
+    let dir = dir.join(LITTLEFS_DIR);
+    fs::create_dir_all(&dir)?;
+    for setting in settings {
+        let bytes = setting.bytes().map_err(std::io::Error::other)?;
+        fs::write(dir.join(&setting.key), bytes)?;
+    }
+    Ok(())
+}
+
+/// The command that packs a directory into a littlefs image.
+pub fn pack_littlefs(dir: &Path, geometry: &LittlefsGeometry, image: &Path) -> duct::Expression {
+    duct::cmd!(
+        "mklittlefs", "-c", dir,
+        "-b", geometry.block_size.to_string(),
+        "-p", geometry.page_size.to_string(),
+        "-s", geometry.size().to_string(),
+        image,
+    )
+}
+
+/// The command that writes a partition image to the board, the way its firmware is
+/// flashed.
+pub fn flash_command(board: &Board, table: &PartitionTable, partition: &Partition, image: &Path) -> Result<duct::Expression, String> {
+    let address = format!("{:#x}", table.address(partition));
+    let offset = format!("{:#x}", partition.offset.bytes());
+    let chip = board.chip_name().map(str::to_string);
+    match board.flash_method() {
+        Some(FlashMethod::ProbeRs) => {
+            let chip = chip.ok_or_else(|| format!("{} needs a chip name to flash it with probe-rs", board.get_name()))?;
+            Ok(duct::cmd!("probe-rs", "download", "--chip", chip, "--binary-format", "bin", "--base-address", address, image))
+        },
+        Some(FlashMethod::Espflash) => Ok(duct::cmd!("espflash", "write-bin", offset, image)),
+        Some(FlashMethod::Uf2) if chip.as_deref().is_some_and(|c| c.to_ascii_uppercase().starts_with("RP2040")) => {
+            Ok(duct::cmd!("picotool", "load", "-t", "bin", "-o", address, image))
+        },
+        Some(FlashMethod::DfuUtil) => Ok(duct::cmd!("dfu-util", "-a", "0", "-s", address, "-D", image)),
+        Some(FlashMethod::Bossac) => Ok(duct::cmd!("bossac", format!("--offset={}", offset), "-w", image)),
+        Some(method) => Err(format!("a partition can't be written to {} with {}", board.get_name(), method)),
+        None => Err(format!("{} has no flash method", board.get_name())),
+    }
+}
+
+/// One 32-byte NVS entry, with its CRC over everything but the CRC itself.
+fn nvs_entry(namespace: u8, kind: u8, span: u8, key: &str, data: [u8; 8]) -> [u8; NVS_ENTRY_SIZE] {
+    let mut entry = [0u8; NVS_ENTRY_SIZE];
+    entry[0] = namespace;
+    entry[1] = kind;
+    entry[2] = span;
+    entry[3] = 0xff;
+    entry[8..8 + key.len()].copy_from_slice(key.as_bytes());
+    entry[24..32].copy_from_slice(&data);
+    let mut covered = entry[0..4].to_vec();
+    covered.extend_from_slice(&entry[8..32]);
+    entry[4..8].copy_from_slice(&crc32(0xffff_ffff, &covered).to_le_bytes());
+    entry
+}
+
+/// Fills pages with entries, starting a page whenever the next item doesn't fit.
+struct NvsWriter {
+    pages: Vec<Vec<u8>>,
+    next: usize,
+}
+
+impl NvsWriter {
+
+    fn new_page(&mut self) {
+        if let Some(page) = self.pages.last_mut() {
+            page[0..4].copy_from_slice(&NVS_PAGE_FULL.to_le_bytes());
+        }
+        let mut page = vec![0xff; NVS_PAGE_SIZE];
+        page[0..4].copy_from_slice(&NVS_PAGE_ACTIVE.to_le_bytes());
+        page[4..8].copy_from_slice(&(self.pages.len() as u32).to_le_bytes());
+        page[8] = NVS_VERSION;
+        let crc = crc32(0xffff_ffff, &page[4..28]);
+        page[28..32].copy_from_slice(&crc.to_le_bytes());
+        self.pages.push(page);
+        self.next = 0;
+    }
+
+    /// Write an item's entries, which never straddle two pages.
+    fn write(&mut self, entries: &[[u8; NVS_ENTRY_SIZE]]) {
+        if self.pages.is_empty() || self.next + entries.len() > NVS_ENTRIES_PER_PAGE {
+            self.new_page();
+        }
+        let page = self.pages.last_mut().expect("a page was just started");
+        for entry in entries {
+            let start = NVS_FIRST_ENTRY + self.next * NVS_ENTRY_SIZE;
+            page[start..start + NVS_ENTRY_SIZE].copy_from_slice(entry);
+            // Two bits an entry: 0b11 is empty and 0b10 is written
+            page[32 + self.next / 4] &= !(1 << ((self.next % 4) * 2));
+            self.next += 1;
+        }
+    }
+}
+
+/// Make an NVS partition image of `size` bytes, as ESP-IDF's nvs_partition_gen.py does,
+/// with the settings in a namespace of their own. The partition keeps its last page
+/// erased, which NVS needs free to move entries into.
+pub fn nvs_image(namespace: &str, settings: &[Setting], size: usize) -> Result<Vec<u8>, String> {
+    if !size.is_multiple_of(NVS_PAGE_SIZE) || size < 3 * NVS_PAGE_SIZE {
+        return Err(format!("an NVS partition needs a multiple of 4 KiB, and at least 12 KiB, not {} bytes", size));
+    }
+    let mut writer = NvsWriter { pages: vec![], next: 0 };
+    writer.write(&[nvs_entry(0, SettingType::U8.nvs_type(), 1, namespace, [1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff])]);
+    for setting in settings {
+        let bytes = setting.bytes()?;
+        let kind = setting.kind.nvs_type();
+        if setting.kind == SettingType::String {
+            let mut string = bytes;
+            string.push(0);
+            let mut data = [0xff; 8];
+            data[0..2].copy_from_slice(&(string.len() as u16).to_le_bytes());
+            data[4..8].copy_from_slice(&crc32(0xffff_ffff, &string).to_le_bytes());
+            let chunks = string.chunks(NVS_ENTRY_SIZE).map(|chunk| {
+                let mut entry = [0xff; NVS_ENTRY_SIZE];
+                entry[..chunk.len()].copy_from_slice(chunk);
+                entry
+            });
+            let mut entries = vec![nvs_entry(1, kind, 1 + string.len().div_ceil(NVS_ENTRY_SIZE) as u8, &setting.key, data)];
+            entries.extend(chunks);
+            writer.write(&entries);
+        } else {
+            let mut data = [0xff; 8];
+            data[..bytes.len()].copy_from_slice(&bytes);
+            writer.write(&[nvs_entry(1, kind, 1, &setting.key, data)]);
+        }
+    }
+    if writer.pages.len() >= size / NVS_PAGE_SIZE {
+        return Err(format!("the settings need {} pages, more than the partition has to spare", writer.pages.len()));
+    }
+    let mut image = writer.pages.concat();
+    image.resize(size, 0xff);
+    Ok(image)
+}
+
+/// The state of the configuration partition window. The settings themselves are the
+/// project's.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ConfigPartitionPanel {
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    lines: Option<Receiver<String>>,
+    #[serde(skip)]
+    output: Vec<String>,
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl ConfigPartitionPanel {
+
+    /// Make the image, and write it to the board if `flash`, on a job.
+    fn run(&mut self, ctx: &egui::Context, config: &ConfigPartition, board: &Board, table: &PartitionTable, project_dir: &Path, flash: bool) {
+        self.output.clear();
+        let commands = match config.commands(board, table, project_dir, flash) {
+            Ok(commands) => commands,
+            Err(e) => {
+                self.status = Some(Err(e));
+                return;
+            },
+        };
+        let (tx, rx) = channel();
+        let name = if flash { "flash the configuration partition" } else { "make the configuration partition" };
+        self.job = Some(jobs::shared(ctx).spawn(name, Some(ctx), move |job| {
+            jobs::run_commands(&commands, &tx, job)
+        }));
+        self.lines = Some(rx);
+        let image = config.image_path(project_dir);
+        self.status = Some(Ok(if flash { format!("flashed {}", image.display()) } else { format!("made {}", image.display()) }));
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.lines {
+            self.output.extend(rx.try_iter());
+        }
+        let Some(job) = self.job else { return };
+        match jobs::shared(ctx).state(job) {
+            Some(JobState::Running) => return,
+            Some(JobState::Failed(e)) => self.status = Some(Err(format!("{} {}", e, self.output.join(" ")))),
+            Some(JobState::Cancelled) => self.status = None,
+            Some(JobState::Finished) | None => (),
+        }
+        self.job = None;
+        self.lines = None;
+    }
+
+    fn display_settings(&mut self, ui: &mut egui::Ui, config: &mut ConfigPartition) {
+        let mut remove = None;
+        egui::Grid::new("config_partition_settings").striped(true).show(ui, |ui| {
+            ui.strong("key");
+            ui.strong("type");
+            ui.strong("value");
+            ui.strong("description");
+            ui.end_row();
+            for (i, setting) in config.settings.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut setting.key).char_limit(MAX_KEY_LEN).desired_width(110.0));
+                egui::ComboBox::from_id_source(("config_setting_type", i)).selected_text(setting.kind.to_string()).show_ui(ui, |ui| {
+                    for kind in enum_iterator::all::<SettingType>() {
+                        ui.selectable_value(&mut setting.kind, kind, kind.to_string());
+                    }
+                });
+                let value = ui.add(egui::TextEdit::singleline(&mut setting.value).desired_width(140.0));
+                if let Err(e) = setting.bytes() {
+                    value.on_hover_text(e);
+                    ui.colored_label(ui.visuals().error_fg_color, "⚠");
+                }
+                ui.add(egui::TextEdit::singleline(&mut setting.description).desired_width(180.0));
+                if ui.small_button("✖").on_hover_text("remove the setting").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            config.settings.remove(i);
+        }
+        if ui.button("add a setting").clicked() {
+            config.settings.push(Setting::default());
+        }
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, config: &mut ConfigPartition, board: Option<&Board>, table: Option<&PartitionTable>, project_dir: Option<&Path>) {
+        self.poll(ctx);
+        let Some(board) = board else {
+            ui.label("add a main board to the project to keep its settings in flash");
+            return;
+        };
+        let Some(table) = table else {
+            ui.label(format!("{} has no partition table for the settings to go in", board.get_name()));
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("partition:");
+            egui::ComboBox::from_id_source("config_partition").selected_text(config.partition.clone()).show_ui(ui, |ui| {
+                for partition in table.partitions.iter().filter(|p| matches!(p.kind, PartitionKind::Nvs | PartitionKind::Littlefs)) {
+                    let text = format!("{} ({}, {} at {:#x})", partition.name, partition.kind, partition.size, table.address(partition));
+                    ui.selectable_value(&mut config.partition, partition.name.clone(), text);
+                }
+            });
+        });
+        if table.partition(&config.partition).is_some_and(|p| p.kind == PartitionKind::Nvs) {
+            ui.horizontal(|ui| {
+                ui.label("namespace:");
+                ui.add(egui::TextEdit::singleline(&mut config.namespace).char_limit(MAX_KEY_LEN).desired_width(110.0));
+            });
+        }
+        ui.separator();
+        self.display_settings(ui, config);
+        ui.separator();
+        let valid = config.validate().and_then(|_| config.target(table).map(|_| ()));
+        if let Err(e) = &valid {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        ui.checkbox(&mut config.flash_with_firmware, "flash with the firmware")
+            .on_hover_text("write the partition before the firmware each time it's loaded onto the board");
+        let running = self.job.is_some();
+        ui.horizontal(|ui| {
+            let enabled = !running && valid.is_ok() && project_dir.is_some();
+            if ui.add_enabled(enabled, egui::Button::new("make image")).clicked() {
+                self.run(ctx, config, board, table, project_dir.unwrap_or(Path::new(".")), false);
+            }
+            if ui.add_enabled(enabled, egui::Button::new("flash")).on_hover_text("make the image and write it to the board").clicked() {
+                self.run(ctx, config, board, table, project_dir.unwrap_or(Path::new(".")), true);
+            }
+            if running {
+                ui.spinner();
+            }
+        });
+        if !running {
+            match &self.status {
+                Some(Ok(done)) => { ui.label(done); },
+                Some(Err(e)) => { ui.colored_label(ui.visuals().error_fg_color, e); },
+                None => (),
+            }
+        }
+    }
+}
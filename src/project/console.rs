@@ -0,0 +1,414 @@
+//! Title: Iron Coder Project Module - Console
+//! Description: The project's console, where the output of builds, flashes and scripts
+//!   goes. Each invocation gets its own collapsible section with its own channel, so jobs
+//!   running at once don't get mixed up, and each kind of output has its own color. File
+//!   references like `src/main.rs:12:5` can be clicked to open the file at that line, and
+//!   the colors tools write with ANSI escapes are shown. The project's secrets are taken
+//!   out of the output as it comes in.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+
+use egui::{Color32, RichText};
+
+use crate::app::ansi::{self, AnsiParser, Span, StyledLine};
+use crate::project::environment;
+
+/// The most sections kept. The oldest finished ones go first.
+pub const MAX_SECTIONS: usize = 50;
+/// The most lines kept in a section. The oldest go first.
+pub const MAX_LINES: usize = 10_000;
+
+/// Where some output comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    /// Messages from Iron Coder itself
+    IronCoder,
+    /// Cargo, or the build system the project's built with
+    Cargo,
+    /// Whatever loads the code onto the board
+    Flasher,
+    /// Commands and scripts run for the project
+    Script,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stream::IronCoder => write!(f, "iron coder"),
+            Stream::Cargo => write!(f, "cargo"),
+            Stream::Flasher => write!(f, "flasher"),
+            Stream::Script => write!(f, "script"),
+        }
+    }
+}
+
+impl Stream {
+
+    /// The color the stream's output is shown in, readable on a dark or light background.
+    pub fn color(self, visuals: &egui::Visuals) -> Color32 {
+        let dark = visuals.dark_mode;
+        match self {
+            Stream::IronCoder => visuals.weak_text_color(),
+            Stream::Cargo => visuals.text_color(),
+            Stream::Flasher if dark => Color32::from_rgb(110, 180, 240),
+            Stream::Flasher => Color32::from_rgb(20, 90, 170),
+            Stream::Script if dark => Color32::from_rgb(200, 150, 230),
+            Stream::Script => Color32::from_rgb(120, 50, 150),
+        }
+    }
+}
+
+/// A reference to a place in a file, in a line of output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileRef {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: Option<usize>,
+    /// Where it is in the line of output, in bytes
+    pub span: Range<usize>,
+}
+
+fn file_ref(token: &str, offset: usize) -> Option<FileRef> {
+    let token = token.trim_end_matches([':', '.', ',', ';']);
+    let (rest, last) = token.rsplit_once(':')?;
+    let last: usize = last.parse().ok()?;
+    let (path, line, column) = match rest.rsplit_once(':').and_then(|(path, line)| Some((path, line.parse::<usize>().ok()?))) {
+        Some((path, line)) => (path, line, Some(last)),
+        None => (rest, last, None),
+    };
+    // a file with an extension, so times and addresses aren't taken for files
+    let extension = Path::new(path).extension()?.to_str()?;
+    if line == 0 || path.contains("://") || !extension.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(FileRef { path: PathBuf::from(path), line, column, span: offset..offset + token.len() })
+}
+
+/// The file references in a line of output, i.e. `--> src/main.rs:12:5`.
+pub fn file_refs(line: &str) -> Vec<FileRef> {
+    let is_delimiter = |c: char| c.is_whitespace() || "()[]<>,'\"`".contains(c);
+    let mut refs = vec![];
+    let mut start = 0;
+    for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        if !is_delimiter(c) {
+            continue;
+        }
+        refs.extend(file_ref(&line[start..i], start));
+        start = i + c.len_utf8();
+    }
+    refs
+}
+
+/// The output of one invocation, i.e. a build.
+pub struct Section {
+    pub id: usize,
+    pub title: String,
+    pub stream: Stream,
+    /// The lines, without their escape sequences
+    pub lines: Vec<String>,
+    /// How each line is styled
+    styles: Vec<Vec<Span>>,
+    parser: AnsiParser,
+    /// Whether its lines are shown
+    pub open: bool,
+    /// The end of the output that isn't a whole line yet
+    partial: String,
+    /// Where the output comes from, until everything sending it has finished
+    receiver: Option<Receiver<String>>,
+}
+
+impl Section {
+
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Add some output, which can be part of a line or several lines, without the
+    /// secrets in it. Returns the lines it finished.
+    fn push(&mut self, output: &str, secrets: &[String]) -> Vec<String> {
+        self.partial += output;
+        let Some(end) = self.partial.rfind('\n') else {
+            return vec![];
+        };
+        let rest = self.partial.split_off(end + 1);
+        let finished: Vec<StyledLine> = self.partial.lines().map(|line| self.parser.line(&environment::redact(line, secrets))).collect();
+        self.partial = rest;
+        for line in finished.iter() {
+            self.lines.push(line.text.clone());
+            self.styles.push(line.spans.clone());
+        }
+        if self.lines.len() > MAX_LINES {
+            self.lines.drain(..self.lines.len() - MAX_LINES);
+            self.styles.drain(..self.styles.len() - MAX_LINES);
+        }
+        finished.into_iter().map(|line| line.text).collect()
+    }
+
+    /// A line with how it's styled.
+    fn styled_line(&self, i: usize) -> StyledLine {
+        StyledLine { text: self.lines[i].clone(), spans: self.styles[i].clone() }
+    }
+}
+
+/// A thing to do that was asked for in the console.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsoleAction {
+    /// Open a file at a line
+    Open(FileRef),
+}
+
+/// A row of the console: the header of a section, or a line of it.
+enum Row {
+    Header(usize),
+    Line(usize, usize),
+}
+
+/// The output of everything run for the project.
+#[derive(Default)]
+pub struct Console {
+    sections: Vec<Section>,
+    next_id: usize,
+    /// What's taken out of the output
+    secrets: Vec<String>,
+}
+
+impl Console {
+
+    fn add_section(&mut self, title: &str, stream: Stream, receiver: Option<Receiver<String>>) -> &mut Section {
+        // older sections are collapsed, so the new one is in view
+        for section in self.sections.iter_mut().filter(|s| !s.is_running()) {
+            section.open = false;
+        }
+        while self.sections.len() >= MAX_SECTIONS {
+            match self.sections.iter().position(|s| !s.is_running()) {
+                Some(oldest) => self.sections.remove(oldest),
+                None => break,
+            };
+        }
+        self.next_id += 1;
+        self.sections.push(Section {
+            id: self.next_id,
+            title: title.to_string(),
+            stream,
+            lines: vec![],
+            styles: vec![],
+            parser: AnsiParser::default(),
+            open: true,
+            partial: String::new(),
+            receiver,
+        });
+        self.sections.last_mut().unwrap()
+    }
+
+    /// Start a section for an invocation. What's sent through the returned channel goes
+    /// in it, and it's finished once everything holding the channel is dropped.
+    pub fn start(&mut self, title: &str, stream: Stream) -> Sender<String> {
+        let (tx, rx) = channel();
+        self.add_section(title, stream, Some(rx));
+        tx
+    }
+
+    /// Take these out of the output from now on, i.e. the project's secret environment
+    /// variables.
+    pub fn redact(&mut self, secrets: Vec<String>) {
+        self.secrets = secrets;
+    }
+
+    /// Show a message from Iron Coder, in the last section if it has Iron Coder's messages.
+    pub fn log(&mut self, message: &str) {
+        let message = if message.ends_with('\n') { message.to_string() } else { format!("{}\n", message) };
+        match self.sections.last_mut() {
+            Some(last) if last.stream == Stream::IronCoder && !last.is_running() => last.push(&message, &self.secrets),
+            _ => {
+                let secrets = self.secrets.clone();
+                self.add_section("iron coder", Stream::IronCoder, None).push(&message, &secrets)
+            },
+        };
+    }
+
+    /// Take in what's been sent to the running sections. Returns the new lines.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut new_lines = vec![];
+        for section in self.sections.iter_mut() {
+            let Some(receiver) = &section.receiver else { continue };
+            let mut output = String::new();
+            let finished = loop {
+                match receiver.try_recv() {
+                    Ok(s) => output += &s,
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            };
+            new_lines.extend(section.push(&output, &self.secrets));
+            if finished {
+                section.receiver = None;
+                if !section.partial.is_empty() {
+                    let rest = std::mem::take(&mut section.partial) + "\n";
+                    new_lines.extend(section.push(&rest, &self.secrets));
+                }
+            }
+        }
+        new_lines
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// All the lines of output, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().flat_map(|s| s.lines.iter().map(String::as_str))
+    }
+
+    /// All of the output as text, with a heading for each section.
+    pub fn text(&self) -> String {
+        self.sections.iter().map(|s| {
+            let lines: String = s.lines.iter().map(|l| format!("{}\n", l)).collect();
+            format!("== {} ({}) ==\n{}", s.title, s.stream, lines)
+        }).collect::<Vec<_>>().join("\n")
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.text())
+    }
+
+    /// Clear the console. Sections that are still running are kept, so their output
+    /// isn't lost.
+    pub fn clear(&mut self) {
+        self.sections.retain(|s| s.is_running());
+        for section in self.sections.iter_mut() {
+            section.lines.clear();
+            section.styles.clear();
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        self.sections.iter().map(|s| 1 + if s.open { s.lines.len() } else { 0 }).sum()
+    }
+
+    fn row(&self, mut i: usize) -> Option<Row> {
+        for (s, section) in self.sections.iter().enumerate() {
+            if i == 0 {
+                return Some(Row::Header(s));
+            }
+            let shown = if section.open { section.lines.len() } else { 0 };
+            if i <= shown {
+                return Some(Row::Line(s, i - 1));
+            }
+            i -= 1 + shown;
+        }
+        None
+    }
+
+    /// Show the console. Just the rows in view are laid out, so long builds don't slow
+    /// it down.
+    pub fn display(&mut self, ui: &mut egui::Ui) -> Option<ConsoleAction> {
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui.small_button("copy").clicked() {
+                ui.output_mut(|o| o.copied_text = self.text());
+            }
+            if ui.small_button("save...").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("text", &["txt", "log"]).set_file_name("console.log").save_file() {
+                    if let Err(e) = self.save(&path) {
+                        self.log(&format!("couldn't save the console to {}: {}", path.display(), e));
+                    }
+                }
+            }
+            if ui.small_button("clear").clicked() {
+                self.clear();
+            }
+            if ui.small_button("collapse all").clicked() {
+                self.sections.iter_mut().for_each(|s| s.open = false);
+            }
+        });
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace).max(ui.spacing().interact_size.y);
+        let mut toggled = None;
+        egui::ScrollArea::both()
+            .id_source("console")
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, self.row_count(), |ui, rows| {
+                for row in rows.filter_map(|i| self.row(i)) {
+                    match row {
+                        Row::Header(s) => {
+                            if display_header(ui, &self.sections[s]).clicked() {
+                                toggled = Some(s);
+                            }
+                        },
+                        Row::Line(s, l) => {
+                            let section = &self.sections[s];
+                            if let Some(file_ref) = display_line(ui, &section.styled_line(l), section.stream) {
+                                action = Some(ConsoleAction::Open(file_ref));
+                            }
+                        },
+                    }
+                }
+            });
+        if let Some(s) = toggled {
+            self.sections[s].open = !self.sections[s].open;
+        }
+        action
+    }
+}
+
+fn display_header(ui: &mut egui::Ui, section: &Section) -> egui::Response {
+    ui.horizontal(|ui| {
+        let arrow = if section.open { "⏷" } else { "⏵" };
+        let color = section.stream.color(ui.visuals());
+        let text = format!("{} {} ", arrow, section.title);
+        let response = ui.add(egui::Label::new(RichText::new(text).monospace().strong()).sense(egui::Sense::click()));
+        ui.label(RichText::new(format!("[{}]", section.stream)).monospace().color(color));
+        if section.is_running() {
+            ui.spinner();
+        } else if !section.open {
+            ui.weak(format!("{} lines", section.lines.len()));
+        }
+        response
+    }).inner
+}
+
+/// Show a line of output, with its file references as links. Returns the one clicked.
+fn display_line(ui: &mut egui::Ui, line: &StyledLine, stream: Stream) -> Option<FileRef> {
+    let trimmed = line.text.trim_start();
+    let color = if trimmed.starts_with("error") {
+        ui.visuals().error_fg_color
+    } else if trimmed.starts_with("warning") {
+        ui.visuals().warn_fg_color
+    } else {
+        stream.color(ui.visuals())
+    };
+    let font = egui::TextStyle::Monospace.resolve(ui.style());
+    let text = |ui: &egui::Ui, range: Range<usize>| {
+        egui::Label::new(ansi::layout_job(line, range, font.clone(), color, ui.visuals())).wrap(false)
+    };
+    let refs = file_refs(&line.text);
+    if refs.is_empty() {
+        ui.add(text(ui, 0..line.text.len()));
+        return None;
+    }
+    let mut clicked = None;
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        let mut end = 0;
+        for file_ref in refs {
+            ui.add(text(ui, end..file_ref.span.start));
+            let link = ui.link(RichText::new(&line.text[file_ref.span.clone()]).monospace()).on_hover_text("open in the editor");
+            end = file_ref.span.end;
+            if link.clicked() {
+                clicked = Some(file_ref);
+            }
+        }
+        ui.add(text(ui, end..line.text.len()));
+    });
+    clicked
+}
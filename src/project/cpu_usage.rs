@@ -0,0 +1,582 @@
+//! Title: Iron Coder Project Module - CPU Usage
+//! Description: Measuring where the firmware spends its time. For the tasks the project
+//!   names, a module is written to `src/cpu_usage.rs` that times each task run, the idle
+//!   time and the longest time round the main loop (or, with Embassy, the longest poll),
+//!   and sends the totals over RTT once a report period. The panel attaches to the board
+//!   with probe-rs, reads the reports and shows each task's share of the CPU and its
+//!   longest run against its deadline.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::project::explain;
+use crate::project::rtt::RttAttachment;
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "cpu_usage.rs";
+/// The word a report line starts with.
+const REPORT_PREFIX: &str = "cpu ";
+/// The names the report uses for itself, which tasks can't have.
+const RESERVED_NAMES: [&str; 3] = ["window", "idle", "loop"];
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum CpuUsageError {
+    /// The module or Cargo.toml couldn't be written
+    IoError(io::Error),
+    /// A task can't be named that
+    BadTask(String),
+    /// Cargo.toml couldn't be updated
+    BadManifest(String),
+}
+
+impl fmt::Display for CpuUsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuUsageError::IoError(e) => write!(f, "{}", e),
+            CpuUsageError::BadTask(e) => write!(f, "{}", e),
+            CpuUsageError::BadManifest(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for CpuUsageError {
+    fn from(e: io::Error) -> Self {
+        CpuUsageError::IoError(e)
+    }
+}
+
+/// How the firmware schedules its tasks, which decides how idle time is found.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum Framework {
+    /// A main loop that calls each task in turn
+    #[default]
+    Superloop,
+    Rtic,
+    Embassy,
+}
+
+impl fmt::Display for Framework {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Framework::Superloop => write!(f, "main loop"),
+            Framework::Rtic => write!(f, "RTIC"),
+            Framework::Embassy => write!(f, "Embassy"),
+        }
+    }
+}
+
+impl Framework {
+
+    /// How to hook the generated module into firmware written this way.
+    pub fn instructions(&self) -> &'static str {
+        match self {
+            Framework::Superloop =>
+"rtt_target::rtt_init_print!();
+cpu_usage::init(|| timer.get_counter_low());
+loop {
+    cpu_usage::loop_tick();
+    {
+        let _running = cpu_usage::start(cpu_usage::Task::Blink);
+        // ...
+    }
+    cpu_usage::idle(cortex_m::asm::wfi);
+}",
+            Framework::Rtic =>
+"// in init
+rtt_target::rtt_init_print!();
+cpu_usage::init(|| monotonic_micros());
+
+// in each task
+let _running = cpu_usage::start(cpu_usage::Task::Blink);
+
+#[idle]
+fn idle(_: idle::Context) -> ! {
+    loop {
+        cpu_usage::idle(cortex_m::asm::wfi);
+    }
+}",
+            Framework::Embassy =>
+"// turn on embassy-executor's \"trace\" feature, which calls the module on every poll
+rtt_target::rtt_init_print!();
+cpu_usage::init(|| embassy_time::Instant::now().as_micros() as u32);
+
+// around the work between awaits in each task
+{
+    let _running = cpu_usage::start(cpu_usage::Task::Blink);
+    // ...
+}",
+        }
+    }
+}
+
+/// A task to time, and how long one run of it may take.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TaskConfig {
+    pub name: String,
+    /// The longest a run may take, in microseconds
+    pub deadline_us: Option<u32>,
+}
+
+/// The tasks to time and how often to report on them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CpuUsageConfig {
+    pub enabled: bool,
+    pub framework: Framework,
+    pub tasks: Vec<TaskConfig>,
+    pub report_ms: u32,
+    /// The longest the main loop (or an Embassy poll) may take, in microseconds
+    pub loop_deadline_us: Option<u32>,
+}
+
+impl Default for CpuUsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            framework: Framework::default(),
+            tasks: Vec::new(),
+            report_ms: 1000,
+            loop_deadline_us: None,
+        }
+    }
+}
+
+/// The enum variant of a task name, i.e. `led_blink` becomes `LedBlink`.
+fn variant_name(task: &str) -> String {
+    task.split('_').filter(|w| !w.is_empty()).map(|w| {
+        let mut chars = w.chars();
+        chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+    }).collect()
+}
+
+/// Check that a task name can be a report word and an enum variant.
+pub fn check_task(name: &str) -> Result<(), CpuUsageError> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(CpuUsageError::BadTask(format!("task \"{}\" should be lowercase letters, digits and underscores", name)));
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(CpuUsageError::BadTask(format!("\"{}\" is used by the report itself", name)));
+    }
+    Ok(())
+}
+
+/// The instrumentation module for the project's tasks.
+pub fn generate_module(config: &CpuUsageConfig) -> Result<String, CpuUsageError> {
+    for (i, task) in config.tasks.iter().enumerate() {
+        check_task(&task.name)?;
+        if config.tasks[..i].iter().any(|t| t.name == task.name) {
+            return Err(CpuUsageError::BadTask(format!("task {} is listed twice", task.name)));
+        }
+    }
+    let count = config.tasks.len();
+    let variants: String = config.tasks.iter().enumerate().map(|(i, t)| format!("    {} = {},\n", variant_name(&t.name), i)).collect();
+    let names = config.tasks.iter().map(|t| format!("{:?}", t.name)).collect::<Vec<_>>().join(", ");
+    let zeros = vec!["ZERO"; count].join(", ");
+    let mut code = format!(
+"//! CPU usage counters, generated by Iron Coder from the project's task list. Don't edit
+//! this file: it's written again before each build.
+//!
+//! Each counter is written from one place only (a task, the idle loop or the report),
+//! so plain loads and stores are enough, even on cores without atomic read-modify-write.
+
+#![allow(dead_code)]
+
+use core::sync::atomic::{{AtomicPtr, AtomicU32, Ordering}};
+
+/// How often a report goes out over RTT, in microseconds.
+const REPORT_US: u32 = {report_us};
+
+/// The tasks that are timed.
+#[derive(Clone, Copy)]
+pub enum Task {{
+{variants}}}
+
+const NAMES: [&str; {count}] = [{names}];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+static CLOCK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+static BUSY: [AtomicU32; {count}] = [{zeros}];
+static LONGEST: [AtomicU32; {count}] = [{zeros}];
+static IDLE: AtomicU32 = ZERO;
+static LOOP_LONGEST: AtomicU32 = ZERO;
+static LOOP_LAST: AtomicU32 = ZERO;
+static WINDOW_START: AtomicU32 = ZERO;
+
+/// Start counting, with a clock in microseconds that wraps round at `u32::MAX`.
+pub fn init(clock: fn() -> u32) {{
+    CLOCK.store(clock as *mut (), Ordering::Relaxed);
+    let now = clock();
+    WINDOW_START.store(now, Ordering::Relaxed);
+    LOOP_LAST.store(now, Ordering::Relaxed);
+}}
+
+fn now() -> u32 {{
+    let clock = CLOCK.load(Ordering::Relaxed);
+    if clock.is_null() {{
+        return 0;
+    }}
+    // SAFETY: only `init` stores to CLOCK, and it stores a `fn() -> u32`
+    let clock: fn() -> u32 = unsafe {{ core::mem::transmute(clock) }};
+    clock()
+}}
+
+fn add(counter: &AtomicU32, us: u32) {{
+    counter.store(counter.load(Ordering::Relaxed).wrapping_add(us), Ordering::Relaxed);
+}}
+
+fn raise(counter: &AtomicU32, us: u32) {{
+    if us > counter.load(Ordering::Relaxed) {{
+        counter.store(us, Ordering::Relaxed);
+    }}
+}}
+
+fn busy_total() -> u32 {{
+    BUSY.iter().fold(0, |total, busy| total.wrapping_add(busy.load(Ordering::Relaxed)))
+}}
+
+/// A task run, timed until it's dropped.
+pub struct Running {{
+    task: Task,
+    start: u32,
+}}
+
+/// Time a run of `task`, until the returned value is dropped.
+pub fn start(task: Task) -> Running {{
+    Running {{ task, start: now() }}
+}}
+
+impl Drop for Running {{
+    fn drop(&mut self) {{
+        let us = now().wrapping_sub(self.start);
+        add(&BUSY[self.task as usize], us);
+        raise(&LONGEST[self.task as usize], us);
+    }}
+}}
+
+/// Wait with `wait` (i.e. WFI) and count the time as idle, less the time tasks ran while
+/// it waited. Sends a report when one is due.
+pub fn idle<R>(wait: impl FnOnce() -> R) -> R {{
+    let (start, busy) = (now(), busy_total());
+    let result = wait();
+    let ran = busy_total().wrapping_sub(busy);
+    add(&IDLE, now().wrapping_sub(start).saturating_sub(ran));
+    poll();
+    result
+}}
+
+/// Mark the top of the main loop, to find the longest time round it. Sends a report
+/// when one is due.
+pub fn loop_tick() {{
+    let now = now();
+    raise(&LOOP_LONGEST, now.wrapping_sub(LOOP_LAST.load(Ordering::Relaxed)));
+    LOOP_LAST.store(now, Ordering::Relaxed);
+    poll();
+}}
+
+/// Send a report if the report period is up.
+pub fn poll() {{
+    if now().wrapping_sub(WINDOW_START.load(Ordering::Relaxed)) >= REPORT_US {{
+        report();
+    }}
+}}
+
+/// Send the counts since the last report over RTT, and start counting again. A task that
+/// ends while this runs can have its time counted in either report.
+pub fn report() {{
+    let now = now();
+    let window = now.wrapping_sub(WINDOW_START.load(Ordering::Relaxed));
+    WINDOW_START.store(now, Ordering::Relaxed);
+    rtt_target::rprint!(\"cpu window={{}} idle={{}}\", window, IDLE.load(Ordering::Relaxed));
+    IDLE.store(0, Ordering::Relaxed);
+    for (i, name) in NAMES.iter().enumerate() {{
+        rtt_target::rprint!(\" {{}}={{}}/{{}}\", name, BUSY[i].load(Ordering::Relaxed), LONGEST[i].load(Ordering::Relaxed));
+        BUSY[i].store(0, Ordering::Relaxed);
+        LONGEST[i].store(0, Ordering::Relaxed);
+    }}
+    rtt_target::rprintln!(\" loop={{}}\", LOOP_LONGEST.load(Ordering::Relaxed));
+    LOOP_LONGEST.store(0, Ordering::Relaxed);
+}}
+", report_us = config.report_ms.saturating_mul(1000), variants = variants, count = count, names = names, zeros = zeros);
+    // the executor's trace hooks find the idle time and the longest poll
+    if config.framework == Framework::Embassy {
+        code += "
+static IDLE_START: AtomicU32 = ZERO;
+static POLL_START: AtomicU32 = ZERO;
+
+#[no_mangle]
+fn _embassy_trace_executor_idle(_executor_id: u32) {
+    IDLE_START.store(now().max(1), Ordering::Relaxed);
+}
+
+#[no_mangle]
+fn _embassy_trace_poll_start(_executor_id: u32) {
+    let start = IDLE_START.load(Ordering::Relaxed);
+    if start != 0 {
+        add(&IDLE, now().wrapping_sub(start));
+        IDLE_START.store(0, Ordering::Relaxed);
+    }
+    poll();
+}
+
+#[no_mangle]
+fn _embassy_trace_task_exec_begin(_executor_id: u32, _task_id: u32) {
+    POLL_START.store(now(), Ordering::Relaxed);
+}
+
+#[no_mangle]
+fn _embassy_trace_task_exec_end(_executor_id: u32, _task_id: u32) {
+    raise(&LOOP_LONGEST, now().wrapping_sub(POLL_START.load(Ordering::Relaxed)));
+}
+
+#[no_mangle]
+fn _embassy_trace_task_new(_executor_id: u32, _task_id: u32) {}
+
+#[no_mangle]
+fn _embassy_trace_task_end(_executor_id: u32, _task_id: u32) {}
+
+#[no_mangle]
+fn _embassy_trace_task_ready_begin(_executor_id: u32, _task_id: u32) {}
+";
+    }
+    Ok(code)
+}
+
+/// Regenerate the module if the task list changed, and make sure the project depends on
+/// rtt-target. Returns whether it was written. Nothing is written unless it's enabled.
+pub fn regenerate(config: &CpuUsageConfig, project_dir: &Path) -> Result<bool, CpuUsageError> {
+    if !config.enabled {
+        return Ok(false);
+    }
+    let code = generate_module(config)?;
+    let path = project_dir.join("src").join(MODULE_FILE_NAME);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == code) {
+        return Ok(false);
+    }
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(&path, code)?;
+    let manifest_path = project_dir.join("Cargo.toml");
+    if let Ok(manifest) = fs::read_to_string(&manifest_path) {
+        let updated = explain::add_dependency(&manifest, "rtt-target", "0.6", &[]).map_err(CpuUsageError::BadManifest)?;
+        if updated != manifest {
+            fs::write(&manifest_path, updated)?;
+        }
+    }
+    Ok(true)
+}
+
+/// The time one task took in a report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskTime {
+    pub name: String,
+    /// The total time it ran, in microseconds
+    pub busy_us: u32,
+    /// Its longest run, in microseconds
+    pub longest_us: u32,
+}
+
+/// A report sent by the generated module, i.e.
+/// `cpu window=1000000 idle=812000 blink=1200/300 radio=9000/2100 loop=930`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageReport {
+    pub window_us: u32,
+    pub idle_us: u32,
+    pub tasks: Vec<TaskTime>,
+    /// The longest time round the main loop or the longest poll, if there is one
+    pub loop_us: Option<u32>,
+}
+
+impl UsageReport {
+
+    /// Read a report out of a line of RTT output, which probe-rs may have put a prefix
+    /// in front of.
+    pub fn parse(line: &str) -> Option<Self> {
+        let start = line.find(REPORT_PREFIX)?;
+        let mut report = UsageReport { window_us: 0, idle_us: 0, tasks: Vec::new(), loop_us: None };
+        for word in line[start + REPORT_PREFIX.len()..].split_whitespace() {
+            let (key, value) = word.split_once('=')?;
+            match key {
+                "window" => report.window_us = value.parse().ok()?,
+                "idle" => report.idle_us = value.parse().ok()?,
+                "loop" => report.loop_us = Some(value.parse().ok()?).filter(|us| *us > 0),
+                name => {
+                    let (busy, longest) = value.split_once('/')?;
+                    report.tasks.push(TaskTime { name: name.to_string(), busy_us: busy.parse().ok()?, longest_us: longest.parse().ok()? });
+                },
+            }
+        }
+        (report.window_us > 0).then_some(report)
+    }
+
+    /// A time as a share of the report's window, in percent.
+    pub fn percent(&self, us: u32) -> f64 {
+        us as f64 / self.window_us as f64 * 100.0
+    }
+}
+
+/// The state of the CPU usage window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CpuUsagePanel {
+    #[serde(flatten)]
+    rtt: RttAttachment,
+    #[serde(skip)]
+    latest: Option<UsageReport>,
+    /// The longest run of each task since attaching, with "loop" for the main loop
+    #[serde(skip)]
+    worst: BTreeMap<String, u32>,
+}
+
+impl CpuUsagePanel {
+
+    /// Take in a line of RTT output.
+    pub fn push_line(&mut self, line: &str) {
+        let Some(report) = UsageReport::parse(line) else {
+            return;
+        };
+        for task in &report.tasks {
+            let worst = self.worst.entry(task.name.clone()).or_default();
+            *worst = (*worst).max(task.longest_us);
+        }
+        if let Some(us) = report.loop_us {
+            let worst = self.worst.entry("loop".to_string()).or_default();
+            *worst = (*worst).max(us);
+        }
+        self.latest = Some(report);
+    }
+
+    /// The longest run seen for a task, or for the main loop.
+    pub fn worst(&self, name: &str) -> Option<u32> {
+        self.worst.get(name).copied()
+    }
+
+    fn display_config(&mut self, ui: &mut egui::Ui, config: &mut CpuUsageConfig) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut config.enabled, "write src/cpu_usage.rs before each build");
+        });
+        egui::Grid::new("cpu_usage_config").num_columns(3).show(ui, |ui| {
+            ui.label("firmware:");
+            egui::ComboBox::from_id_source("cpu_usage_framework")
+            .selected_text(config.framework.to_string())
+            .show_ui(ui, |ui| {
+                for f in enum_iterator::all::<Framework>() {
+                    ui.selectable_value(&mut config.framework, f, f.to_string());
+                }
+            });
+            ui.end_row();
+            ui.label("report every:");
+            ui.add(egui::DragValue::new(&mut config.report_ms).clamp_range(10..=60_000).suffix(" ms"));
+            ui.end_row();
+            let mut remove = None;
+            for (i, task) in config.tasks.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut task.name).desired_width(120.0));
+                deadline_editor(ui, &mut task.deadline_us);
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+                if let Err(e) = check_task(&task.name) {
+                    ui.label("");
+                    ui.colored_label(ui.style().visuals.error_fg_color, e.to_string());
+                    ui.end_row();
+                }
+            }
+            if let Some(i) = remove {
+                config.tasks.remove(i);
+            }
+            ui.label("main loop:");
+            deadline_editor(ui, &mut config.loop_deadline_us);
+            ui.end_row();
+        });
+        if ui.button("add task").clicked() {
+            config.tasks.push(TaskConfig { name: format!("task{}", config.tasks.len() + 1), deadline_us: None });
+        }
+        ui.collapsing("how to hook it up", |ui| {
+            let mut code = config.framework.instructions().to_string();
+            ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+        });
+    }
+
+    fn display_report(&self, ui: &mut egui::Ui, config: &CpuUsageConfig) {
+        let Some(report) = &self.latest else {
+            ui.label("waiting for a report from the firmware");
+            return;
+        };
+        let error = ui.style().visuals.error_fg_color;
+        let deadline_label = |ui: &mut egui::Ui, worst: Option<u32>, deadline: Option<u32>| {
+            let text = worst.map(|us| format!("{} µs", us)).unwrap_or_default();
+            match (worst, deadline) {
+                (Some(worst), Some(deadline)) if worst > deadline =>
+                    ui.colored_label(error, text).on_hover_text(format!("past its {} µs deadline", deadline)),
+                _ => ui.label(text),
+            };
+        };
+        egui::Grid::new("cpu_usage_report").num_columns(4).striped(true).show(ui, |ui| {
+            for heading in ["task", "CPU", "longest run", "worst"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            for task in &report.tasks {
+                ui.label(&task.name);
+                let share = report.percent(task.busy_us);
+                ui.add(egui::ProgressBar::new((share / 100.0) as f32).text(format!("{:.1}%", share)).desired_width(140.0));
+                ui.label(format!("{} µs", task.longest_us));
+                let deadline = config.tasks.iter().find(|t| t.name == task.name).and_then(|t| t.deadline_us);
+                deadline_label(ui, self.worst(&task.name), deadline);
+                ui.end_row();
+            }
+            ui.label("idle");
+            let share = report.percent(report.idle_us);
+            ui.add(egui::ProgressBar::new((share / 100.0) as f32).text(format!("{:.1}%", share)).desired_width(140.0));
+            ui.end_row();
+            if let Some(us) = report.loop_us {
+                ui.label(if config.framework == Framework::Embassy { "longest poll" } else { "main loop" });
+                ui.label("");
+                ui.label(format!("{} µs", us));
+                deadline_label(ui, self.worst("loop"), config.loop_deadline_us);
+                ui.end_row();
+            }
+        });
+    }
+
+    /// Show the task list and what the firmware reports. `board` is the main board,
+    /// which names the chip for probe-rs.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, config: &mut CpuUsageConfig, board: Option<&Board>, project_dir: Option<&Path>) {
+        for line in self.rtt.poll(ctx) {
+            self.push_line(&line);
+        }
+        self.display_config(ui, config);
+        ui.separator();
+        if self.rtt.display(ctx, ui, "RTT CPU usage", board, project_dir) {
+            self.latest = None;
+            self.worst.clear();
+        }
+        if self.rtt.is_running(ctx) || self.latest.is_some() {
+            self.display_report(ui, config);
+        }
+    }
+}
+
+/// Edit an optional deadline in microseconds.
+fn deadline_editor(ui: &mut egui::Ui, deadline: &mut Option<u32>) {
+    ui.horizontal(|ui| {
+        let mut set = deadline.is_some();
+        if ui.checkbox(&mut set, "deadline").changed() {
+            *deadline = set.then_some(1000);
+        }
+        if let Some(us) = deadline {
+            ui.add(egui::DragValue::new(us).clamp_range(1..=u32::MAX).suffix(" µs"));
+        }
+    });
+}
@@ -0,0 +1,527 @@
+//! Title: Iron Coder Project Module - BLE DFU
+//! Description: Wireless firmware updates for BLE boards. This implements the Nordic
+//!   Secure DFU protocol (nRF5 SDK bootloaders) and Espressif's BLE OTA protocol
+//!   (the esp-iot-solution `ble_ota` component, used with NimBLE) on top of the
+//!   BleTransport trait, which devices are reached through with the shared BLE link.
+
+use log::info;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use enum_iterator::Sequence;
+use rfd::FileDialog;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::project::ble::{BleDevice, BleError};
+
+/// How long to wait for the device to answer a request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest write that fits the default ATT MTU, since the MTU the device agrees to
+/// isn't known.
+const DEFAULT_WRITE_LEN: usize = 20;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum DfuError {
+    FsError(io::Error),
+    /// The firmware package is missing a file or is malformed
+    PackageError(String),
+    /// The link to the device failed
+    BleError(BleError),
+    /// The device sent something we didn't expect
+    ProtocolError(String),
+    /// The device refused a request, with the reason it gave
+    Rejected(String),
+    Cancelled,
+}
+
+impl fmt::Display for DfuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DfuError::FsError(e) => write!(f, "couldn't read package: {}", e),
+            DfuError::PackageError(s) => write!(f, "bad firmware package: {}", s),
+            DfuError::BleError(e) => write!(f, "BLE error: {}", e),
+            DfuError::ProtocolError(s) => write!(f, "unexpected response: {}", s),
+            DfuError::Rejected(s) => write!(f, "device rejected the update: {}", s),
+            DfuError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl From<BleError> for DfuError {
+    fn from(e: BleError) -> Self {
+        DfuError::BleError(e)
+    }
+}
+
+/// The wireless update protocols we know how to speak.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum DfuProtocol {
+    #[default]
+    NordicSecureDfu,
+    EspBleOta,
+}
+
+impl fmt::Display for DfuProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DfuProtocol::NordicSecureDfu => write!(f, "Nordic Secure DFU"),
+            DfuProtocol::EspBleOta => write!(f, "ESP BLE OTA"),
+        }
+    }
+}
+
+/// The two characteristics each protocol uses. For Nordic DFU these are the control
+/// point and the packet characteristic, for ESP OTA the command and firmware ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Characteristic {
+    Control,
+    Packet,
+}
+
+/// A connection to a BLE device's update service.
+pub trait BleTransport {
+    /// Write a value to a characteristic (without waiting for a response).
+    fn write(&mut self, characteristic: Characteristic, data: &[u8]) -> Result<(), DfuError>;
+    /// Wait for the next notification or indication on a characteristic.
+    fn notification(&mut self, characteristic: Characteristic) -> Result<Vec<u8>, DfuError>;
+    /// The largest value that fits in a single write.
+    fn max_write_len(&self) -> usize;
+}
+
+impl DfuProtocol {
+
+    /// The UUIDs of the update service and its control and packet characteristics.
+    fn uuids(&self) -> (Uuid, Uuid, Uuid) {
+        match self {
+            DfuProtocol::NordicSecureDfu => (
+                Uuid::from_u128(0x0000fe59_0000_1000_8000_00805f9b34fb),
+                Uuid::from_u128(0x8ec90001_f315_4f60_9fb8_838830daea50),
+                Uuid::from_u128(0x8ec90002_f315_4f60_9fb8_838830daea50),
+            ),
+            DfuProtocol::EspBleOta => (
+                Uuid::from_u128(0x00008018_0000_1000_8000_00805f9b34fb),
+                Uuid::from_u128(0x00008022_0000_1000_8000_00805f9b34fb),
+                Uuid::from_u128(0x00008020_0000_1000_8000_00805f9b34fb),
+            ),
+        }
+    }
+}
+
+/// A device's update service, over the shared BLE link.
+struct DeviceTransport {
+    device: BleDevice,
+    service: Uuid,
+    control: Uuid,
+    packet: Uuid,
+    /// Notifications that arrived while waiting for the other characteristic
+    pending: Vec<(Uuid, Vec<u8>)>,
+}
+
+impl DeviceTransport {
+    fn uuid(&self, characteristic: Characteristic) -> Uuid {
+        match characteristic {
+            Characteristic::Control => self.control,
+            Characteristic::Packet => self.packet,
+        }
+    }
+}
+
+impl BleTransport for DeviceTransport {
+    fn write(&mut self, characteristic: Characteristic, data: &[u8]) -> Result<(), DfuError> {
+        // requests wait for the device, firmware packets are streamed
+        let with_response = characteristic == Characteristic::Control;
+        Ok(self.device.write(self.service, self.uuid(characteristic), data, with_response)?)
+    }
+
+    fn notification(&mut self, characteristic: Characteristic) -> Result<Vec<u8>, DfuError> {
+        let uuid = self.uuid(characteristic);
+        if let Some(i) = self.pending.iter().position(|(from, _)| *from == uuid) {
+            return Ok(self.pending.remove(i).1);
+        }
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            match self.device.notification(deadline.saturating_duration_since(Instant::now()))? {
+                Some((_, from, value)) if from == uuid => return Ok(value),
+                Some((_, from, value)) => self.pending.push((from, value)),
+                None => return Err(BleError::Timeout.into()),
+            }
+        }
+    }
+
+    fn max_write_len(&self) -> usize {
+        DEFAULT_WRITE_LEN
+    }
+}
+
+/// Connect to the device with the address or name, and subscribe to the protocol's
+/// notifications. Nordic DFU only notifies on its control point; ESP OTA acknowledges
+/// sectors on the firmware characteristic too.
+pub fn connect(address: &str, protocol: DfuProtocol) -> Result<Box<dyn BleTransport>, DfuError> {
+    let device = BleDevice::connect(address)?;
+    let (service, control, packet) = protocol.uuids();
+    device.set_notify(service, control, true)?;
+    if protocol == DfuProtocol::EspBleOta {
+        device.set_notify(service, packet, true)?;
+    }
+    info!("connected to the {} service of {}", protocol, address);
+    Ok(Box::new(DeviceTransport { device, service, control, packet, pending: vec![] }))
+}
+
+/// The files that make up a firmware update.
+#[derive(Clone, Debug, Default)]
+pub struct DfuPackage {
+    /// The signed init packet (Nordic only)
+    pub init_packet: Option<Vec<u8>>,
+    pub firmware: Vec<u8>,
+}
+
+impl DfuPackage {
+
+    /// Load a package from either an extracted nrfutil package (a folder containing a
+    /// manifest.json), or a firmware .bin with an optional .dat init packet next to it.
+    pub fn load(path: &Path) -> Result<Self, DfuError> {
+        let (bin_path, dat_path) = if path.is_dir() {
+            let manifest = fs::read_to_string(path.join("manifest.json")).map_err(DfuError::FsError)?;
+            let manifest: serde_json::Value = serde_json::from_str(&manifest)
+                .map_err(|e| DfuError::PackageError(e.to_string()))?;
+            let app = &manifest["manifest"]["application"];
+            let bin = app["bin_file"].as_str().ok_or(DfuError::PackageError("manifest has no bin_file".to_string()))?;
+            (path.join(bin), app["dat_file"].as_str().map(|dat| path.join(dat)))
+        } else {
+            let dat = path.with_extension("dat");
+            (path.to_path_buf(), dat.exists().then_some(dat))
+        };
+        let firmware = fs::read(bin_path).map_err(DfuError::FsError)?;
+        if firmware.is_empty() {
+            return Err(DfuError::PackageError("firmware is empty".to_string()));
+        }
+        let init_packet = match dat_path {
+            Some(dat) => Some(fs::read(dat).map_err(DfuError::FsError)?),
+            None => None,
+        };
+        Ok(Self { init_packet, firmware })
+    }
+}
+
+/// Push a firmware package to a device. `job` is used for progress and cancellation.
+pub fn update(protocol: DfuProtocol, transport: &mut dyn BleTransport, package: &DfuPackage, job: Option<&JobContext>) -> Result<(), DfuError> {
+    match protocol {
+        DfuProtocol::NordicSecureDfu => nordic::update(transport, package, job),
+        DfuProtocol::EspBleOta => esp::update(transport, package, job),
+    }
+}
+
+fn report(job: Option<&JobContext>, done: usize, total: usize) -> Result<(), DfuError> {
+    if let Some(job) = job {
+        if job.is_cancelled() {
+            return Err(DfuError::Cancelled);
+        }
+        job.set_progress(done as f32 / total as f32);
+    }
+    Ok(())
+}
+
+/// CRC-32 (IEEE), as used by the Nordic bootloader.
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// CRC-16/CCITT, as computed by the ESP ROM's `crc16_le(0, ...)`.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for byte in data {
+        crc ^= *byte as u16;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0x8408 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, DfuError> {
+    match data.get(at..at + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(DfuError::ProtocolError(format!("response too short: {:02x?}", data))),
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16, DfuError> {
+    match data.get(at..at + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(DfuError::ProtocolError(format!("response too short: {:02x?}", data))),
+    }
+}
+
+/// Nordic Secure DFU. Each object (the init packet, then the firmware in chunks of the
+/// device's max object size) is created, streamed, checked against a running CRC-32,
+/// and executed.
+pub mod nordic {
+    use super::*;
+
+    pub const OP_CREATE: u8 = 0x01;
+    pub const OP_SET_PRN: u8 = 0x02;
+    pub const OP_CALC_CRC: u8 = 0x03;
+    pub const OP_EXECUTE: u8 = 0x04;
+    pub const OP_SELECT: u8 = 0x06;
+    pub const OP_RESPONSE: u8 = 0x60;
+    pub const OBJ_COMMAND: u8 = 0x01;
+    pub const OBJ_DATA: u8 = 0x02;
+    pub const RES_SUCCESS: u8 = 0x01;
+
+    fn result_name(code: u8) -> &'static str {
+        match code {
+            0x02 => "op code not supported",
+            0x03 => "invalid parameter",
+            0x04 => "insufficient resources",
+            0x05 => "invalid object",
+            0x07 => "unsupported type",
+            0x08 => "operation not permitted",
+            0x0a => "operation failed",
+            0x0b => "extended error",
+            _ => "unknown error",
+        }
+    }
+
+    /// Send a control point request and return the payload of the response.
+    fn request(transport: &mut dyn BleTransport, req: &[u8]) -> Result<Vec<u8>, DfuError> {
+        transport.write(Characteristic::Control, req)?;
+        let res = transport.notification(Characteristic::Control)?;
+        if res.len() < 3 || res[0] != OP_RESPONSE || res[1] != req[0] {
+            return Err(DfuError::ProtocolError(format!("{:02x?} in reply to {:02x?}", res, req)));
+        }
+        if res[2] != RES_SUCCESS {
+            return Err(DfuError::Rejected(format!("{} (0x{:02x})", result_name(res[2]), res[2])));
+        }
+        Ok(res[3..].to_vec())
+    }
+
+    /// Stream one object, then check the device's offset and CRC against ours.
+    fn send_object(transport: &mut dyn BleTransport, obj_type: u8, data: &[u8], sent_before: &[u8]) -> Result<(), DfuError> {
+        let mut create = vec![OP_CREATE, obj_type];
+        create.extend((data.len() as u32).to_le_bytes());
+        request(transport, &create)?;
+        for packet in data.chunks(transport.max_write_len().max(1)) {
+            transport.write(Characteristic::Packet, packet)?;
+        }
+        let res = request(transport, &[OP_CALC_CRC])?;
+        let (offset, crc) = (read_u32(&res, 0)?, read_u32(&res, 4)?);
+        let expected_crc = crc32(crc32(0, sent_before), data);
+        let expected_offset = sent_before.len() + data.len();
+        if offset as usize != expected_offset || crc != expected_crc {
+            return Err(DfuError::ProtocolError(format!(
+                "device has offset {} crc {:08x}, expected offset {} crc {:08x}", offset, crc, expected_offset, expected_crc
+            )));
+        }
+        request(transport, &[OP_EXECUTE])?;
+        Ok(())
+    }
+
+    pub fn update(transport: &mut dyn BleTransport, package: &DfuPackage, job: Option<&JobContext>) -> Result<(), DfuError> {
+        let init_packet = package.init_packet.as_ref()
+            .ok_or(DfuError::PackageError("Nordic DFU needs an init packet (.dat)".to_string()))?;
+        // no packet receipt notifications; we check the CRC after each object instead
+        request(transport, &[OP_SET_PRN, 0, 0])?;
+
+        let res = request(transport, &[OP_SELECT, OBJ_COMMAND])?;
+        if (read_u32(&res, 0)? as usize) < init_packet.len() {
+            return Err(DfuError::Rejected("init packet is larger than the device allows".to_string()));
+        }
+        send_object(transport, OBJ_COMMAND, init_packet, &[])?;
+
+        let res = request(transport, &[OP_SELECT, OBJ_DATA])?;
+        let max_size = read_u32(&res, 0)? as usize;
+        if max_size == 0 {
+            return Err(DfuError::ProtocolError("device reported a max object size of 0".to_string()));
+        }
+        let firmware = &package.firmware;
+        let mut sent = 0;
+        for chunk in firmware.chunks(max_size) {
+            report(job, sent, firmware.len())?;
+            send_object(transport, OBJ_DATA, chunk, &firmware[..sent])?;
+            sent += chunk.len();
+        }
+        report(job, sent, firmware.len())
+    }
+}
+
+/// Espressif BLE OTA. Commands are 20 byte packets ending in a CRC-16, and the firmware
+/// is sent in 4 KiB sectors, each acknowledged by the device after its last packet.
+pub mod esp {
+    use super::*;
+
+    pub const CMD_START: u16 = 0x0001;
+    pub const CMD_STOP: u16 = 0x0002;
+    pub const CMD_ACK: u16 = 0x0003;
+    pub const SECTOR_SIZE: usize = 4096;
+    /// The sequence number marking the last packet of a sector.
+    pub const LAST_PACKET: u8 = 0xff;
+
+    /// Build a command packet: the id, up to 16 bytes of payload, and a CRC-16.
+    pub fn command(id: u16, payload: &[u8]) -> [u8; 20] {
+        let mut cmd = [0u8; 20];
+        cmd[0..2].copy_from_slice(&id.to_le_bytes());
+        cmd[2..2 + payload.len()].copy_from_slice(payload);
+        let crc = crc16(&cmd[..18]);
+        cmd[18..20].copy_from_slice(&crc.to_le_bytes());
+        cmd
+    }
+
+    fn send_command(transport: &mut dyn BleTransport, id: u16, payload: &[u8]) -> Result<(), DfuError> {
+        transport.write(Characteristic::Control, &command(id, payload))?;
+        let ack = transport.notification(Characteristic::Control)?;
+        if read_u16(&ack, 0)? != CMD_ACK || read_u16(&ack, 2)? != id {
+            return Err(DfuError::ProtocolError(format!("{:02x?} in reply to command {}", ack, id)));
+        }
+        if ack.len() >= 20 && crc16(&ack[..18]) != read_u16(&ack, 18)? {
+            return Err(DfuError::ProtocolError("bad CRC on command ack".to_string()));
+        }
+        match read_u16(&ack, 4)? {
+            0 => Ok(()),
+            status => Err(DfuError::Rejected(format!("command {} refused (status {})", id, status))),
+        }
+    }
+
+    pub fn update(transport: &mut dyn BleTransport, package: &DfuPackage, job: Option<&JobContext>) -> Result<(), DfuError> {
+        let firmware = &package.firmware;
+        send_command(transport, CMD_START, &(firmware.len() as u32).to_le_bytes())?;
+        // each packet carries the sector index and a sequence number
+        let payload_len = transport.max_write_len().saturating_sub(3).max(1);
+        for (index, sector) in firmware.chunks(SECTOR_SIZE).enumerate() {
+            report(job, index * SECTOR_SIZE, firmware.len())?;
+            let mut data = sector.to_vec();
+            data.extend(crc16(sector).to_le_bytes());
+            let packets: Vec<&[u8]> = data.chunks(payload_len).collect();
+            for (seq, payload) in packets.iter().enumerate() {
+                let seq = if seq == packets.len() - 1 { LAST_PACKET } else { seq as u8 };
+                let mut packet = (index as u16).to_le_bytes().to_vec();
+                packet.push(seq);
+                packet.extend_from_slice(payload);
+                transport.write(Characteristic::Packet, &packet)?;
+            }
+            let ack = transport.notification(Characteristic::Packet)?;
+            if read_u16(&ack, 0)? as usize != index {
+                return Err(DfuError::ProtocolError(format!("ack for sector {} while sending {}", read_u16(&ack, 0)?, index)));
+            }
+            match read_u16(&ack, 2)? {
+                0 => (),
+                1 => return Err(DfuError::Rejected(format!("CRC error in sector {}", index))),
+                2 => return Err(DfuError::Rejected(format!("sector {} out of order", index))),
+                3 => return Err(DfuError::Rejected(format!("bad payload length in sector {}", index))),
+                status => return Err(DfuError::Rejected(format!("sector {} failed (status {})", index, status))),
+            }
+        }
+        send_command(transport, CMD_STOP, &[])?;
+        report(job, firmware.len(), firmware.len())
+    }
+}
+
+/// The state of the BLE firmware update window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DfuPanel {
+    pub protocol: DfuProtocol,
+    /// The BLE address or name of the device to update
+    pub address: String,
+    pub package_path: Option<PathBuf>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    package_error: Option<String>,
+}
+
+impl DfuPanel {
+
+    fn start(&mut self, ctx: &egui::Context, package: DfuPackage) {
+        let protocol = self.protocol;
+        let address = self.address.clone();
+        self.job = Some(jobs::shared(ctx).spawn("BLE firmware update", Some(ctx), move |job| {
+            job.set_status(&format!("connecting to {}", address));
+            let mut transport = connect(&address, protocol).map_err(|e| e.to_string())?;
+            job.set_status("sending firmware");
+            update(protocol, transport.as_mut(), &package, Some(job)).map_err(|e| e.to_string())
+        }));
+    }
+
+    /// Show the update controls and the state of the last update in the Ui.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let running = self.job.map(|id| jobs::shared(ctx).state(id)) == Some(Some(JobState::Running));
+        egui::Grid::new("dfu_grid").num_columns(2).show(ui, |ui| {
+            ui.label("protocol:");
+            egui::ComboBox::from_id_source("dfu_protocol")
+            .selected_text(self.protocol.to_string())
+            .show_ui(ui, |ui| {
+                for p in enum_iterator::all::<DfuProtocol>() {
+                    ui.selectable_value(&mut self.protocol, p, p.to_string());
+                }
+            });
+            ui.end_row();
+            ui.label("device:");
+            ui.add(egui::TextEdit::singleline(&mut self.address).hint_text("AA:BB:CC:DD:EE:FF or name"));
+            ui.end_row();
+            ui.label("package:");
+            ui.horizontal(|ui| {
+                match &self.package_path {
+                    Some(p) => ui.label(p.display().to_string()),
+                    None => ui.label("none"),
+                };
+                if ui.button("file").clicked() {
+                    if let Some(p) = FileDialog::new().add_filter("firmware", &["bin"]).pick_file() {
+                        self.package_path = Some(p);
+                    }
+                }
+                // an extracted nrfutil package
+                if ui.button("folder").clicked() {
+                    if let Some(p) = FileDialog::new().pick_folder() {
+                        self.package_path = Some(p);
+                    }
+                }
+            });
+            ui.end_row();
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            if running {
+                ui.spinner();
+                if ui.button("cancel").clicked() {
+                    if let Some(id) = self.job {
+                        jobs::shared(ctx).cancel(id);
+                    }
+                }
+            } else {
+                let ready = self.package_path.is_some() && !self.address.is_empty();
+                if ui.add_enabled(ready, egui::Button::new("start update")).clicked() {
+                    self.package_error = None;
+                    match self.package_path.as_deref().map(DfuPackage::load) {
+                        Some(Ok(package)) => self.start(ctx, package),
+                        Some(Err(e)) => self.package_error = Some(e.to_string()),
+                        None => (),
+                    }
+                }
+            }
+            if let Some(e) = &self.package_error {
+                ui.colored_label(ui.style().visuals.error_fg_color, e);
+            } else if let Some(Some(state)) = self.job.map(|id| jobs::shared(ctx).state(id)) {
+                match state {
+                    JobState::Failed(e) => ui.colored_label(ui.style().visuals.error_fg_color, e),
+                    JobState::Finished => ui.label("update complete"),
+                    JobState::Cancelled => ui.label("update cancelled"),
+                    JobState::Running => ui.label("updating... (see jobs for progress)"),
+                };
+            }
+        });
+    }
+}
@@ -5,27 +5,44 @@
 //! the system editor.
 
 use egui::{Key, Response};
-use egui_extras::RetainedImage;
 use log::{info, warn};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
-use egui::widget_text::RichText;
 use egui::widgets::Button;
 
 use git2::{Repository, StatusOptions};
 
 use crate::board;
+use crate::board::BoardKind;
+use crate::board::display::BoardDisplayMode;
+use crate::board::fitting;
+use crate::board::favorites::BoardFavorites;
+use crate::board::sorting::BoardSorting;
 use crate::project::Project;
+use crate::project::compat;
+use crate::project::reproducible::RecordAction;
 use crate::app::icons::IconSet;
+use crate::app::texture_cache;
 use crate::app::{Mode, Warnings, Git};
+use crate::app::events::{self, Event};
 
 use enum_iterator;
 
 use serde::{Serialize, Deserialize};
 
 use super::system;
+use super::backend;
+use super::mcuboot;
+use super::partition_table;
+use super::local_sources;
+use super::simulation;
+use super::explain::{self, Fix, KnowledgeBase};
+use super::health::Remedy;
+use super::std_support::StdSupport;
+use super::file_tree::TreeEvent;
+use super::console::{ConsoleAction, FileRef, Stream};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub enum ProjectViewType {
@@ -33,71 +50,155 @@ pub enum ProjectViewType {
     BoardsView,
     FileTree,
     CrateView(String),
+    ConfigView,
+    LintsView,
 }
 
 // this block contains the display related
 // methods for showing the Project in egui.
 impl Project {
 
-    /// Recursively display the project directory.
-    /// <dir> is the starting location, <level> is the recursion depth
-    fn display_directory(&mut self, dir: &Path, level: usize, ctx: &egui::Context, ui: &mut egui::Ui) {
-        let iconref: Arc<IconSet> = ctx.data_mut(|data| {
-            data.get_temp("icons".into()).unwrap()
-        });
-        let icons = iconref.clone();
-        // if the entry is a file, just show it
-        // if the entry is a directory, show it, and if it's "open"
-        //   also recursively display it's contents
-        let children = dir.read_dir().unwrap();
-        for _child in children {
-            let child = _child.unwrap();
-            let file_name = child.file_name().into_string().unwrap();
-            let text = RichText::new(file_name);
-            // FILE case
-            if child.file_type().unwrap().is_file() {
-                let button = egui::widgets::Button::image_and_text(
-                    icons.get("file_icon").unwrap().clone(),
-                    text,
-                ).frame(false);
-                let resp = ui.add(button);
-                if resp.clicked() {
-                    self.code_editor.load_from_file(child.path().as_path()).unwrap_or_else(|_| warn!("error loading file contents"));
-                }
-            } else {
-                // DIRECTORY case
-                egui::CollapsingHeader::new(child.path().file_name().unwrap().to_str().unwrap()).show(ui, |ui| {
-                    self.display_directory(child.path().as_path(), level+1, ctx, ui);
-                });
+    /// show the terminal pane
+    pub fn display_terminal(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let send_string = "";
+
+        // Take in the output of what's running
+        self.console.redact(self.effective_environment().secrets());
+        for line in self.console.poll() {
+            explain::explain_output(KnowledgeBase::builtin(), &line, &mut self.explained);
+        }
+
+        // Report the app events that are relevant to the project
+        let events_rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        while let Ok(event) = events_rx.try_recv() {
+            match event {
+                Event::BuildFinished { success: true } => self.console.log("build finished."),
+                Event::BuildFinished { success: false } => self.console.log("build failed!"),
+                Event::ProbeAttached(probe) => self.console.log(&format!("found probe: {}", probe)),
+                Event::HealthChecked(findings) => self.health = Some(findings),
+                Event::LintsChecked(diagnostics) => self.lint_check = Some(diagnostics),
+                _ => (),
             }
         }
+
+        egui::CollapsingHeader::new("Terminal").show(ui, |ui| {
+            if let Some(ConsoleAction::Open(file_ref)) = self.console.display(ui) {
+                self.open_file_ref(&file_ref);
+            }
+        });
+        self.display_explanations(ui);
+        self.display_health(ctx, ui);
     }
 
-    /// show the terminal pane
-    pub fn display_terminal(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
-        let send_string = "";
+    /// Open a file referred to in the console at its line. Paths are relative to the
+    /// project directory, as Cargo writes them.
+    fn open_file_ref(&mut self, file_ref: &FileRef) {
+        let path = match &self.location {
+            Some(location) if file_ref.path.is_relative() => location.join(&file_ref.path),
+            _ => file_ref.path.clone(),
+        };
+        if let Err(e) = self.code_editor.goto_line(&path, file_ref.line) {
+            self.console.log(&format!("couldn't open {}: {}", path.display(), e));
+        }
+    }
 
-        // If there is an open channel, see if we can get some data from it
-        if let Some(rx) = &self.receiver {
-            while let Ok(s) = rx.try_recv() {
-                self.terminal_buffer += s.as_str();
+    /// Show what the errors in the terminal mean, and offer their fixes.
+    fn display_explanations(&mut self, ui: &mut egui::Ui) {
+        if self.explained.is_empty() {
+            return;
+        }
+        let mut dismissed = None;
+        let mut applied = vec![];
+        egui::CollapsingHeader::new(format!("Explanations ({})", self.explained.len())).default_open(true).show(ui, |ui| {
+            for (i, explained) in self.explained.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.strong(&explained.entry.title);
+                    if ui.small_button("dismiss").clicked() {
+                        dismissed = Some(i);
+                    }
+                });
+                ui.weak(egui::RichText::new(&explained.line).monospace());
+                ui.label(&explained.entry.explanation);
+                ui.horizontal_wrapped(|ui| {
+                    for fix in explained.entry.fixes.iter() {
+                        if let Fix::OpenUrl { url, .. } = fix {
+                            ui.hyperlink_to(fix.to_string(), url);
+                        } else if ui.add_enabled(self.location.is_some(), Button::new(fix.to_string())).clicked() {
+                            applied.push(fix.clone());
+                        }
+                    }
+                });
+                ui.separator();
+            }
+        });
+        if let Some(i) = dismissed {
+            self.explained.remove(i);
+        }
+        for fix in applied {
+            let Some(location) = &self.location else { continue };
+            match fix.apply(location) {
+                Ok(done) => self.console.log(&done),
+                Err(e) => self.console.log(&format!("couldn't {}: {}", fix, e)),
             }
         }
+    }
 
-        egui::CollapsingHeader::new("Terminal").show(ui, |ui| {
-            egui::ScrollArea::both()
-            .auto_shrink([false; 2])
-            .stick_to_bottom(true)
-            .show(ui, |ui| {
-                ui.add(
-                    egui::TextEdit::multiline(&mut self.terminal_buffer)
-                    .code_editor()
-                    .interactive(false)
-                    .desired_width(f32::INFINITY)
-                    .frame(false)
-                )
+    /// Show what the health check found, worst first, with a way to put each right.
+    fn display_health(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let Some(findings) = &self.health else {
+            return;
+        };
+        let mut check_again = false;
+        let mut cleared = false;
+        let mut remedies = vec![];
+        egui::CollapsingHeader::new(format!("Health Check ({})", findings.len())).default_open(true).show(ui, |ui| {
+            ui.horizontal(|ui| {
+                check_again = ui.small_button("check again").clicked();
+                cleared = ui.small_button("clear").clicked();
             });
+            if findings.is_empty() {
+                ui.label("nothing wrong was found.");
+            }
+            for finding in findings.iter() {
+                ui.horizontal(|ui| {
+                    let color = match finding.severity {
+                        backend::Severity::Error => ui.visuals().error_fg_color,
+                        backend::Severity::Warning => ui.visuals().warn_fg_color,
+                        backend::Severity::Note => ui.visuals().weak_text_color(),
+                    };
+                    ui.colored_label(color, finding.severity.to_string());
+                    ui.strong(&finding.title);
+                });
+                ui.label(&finding.detail);
+                if let Some(Remedy::Fix(fix @ Fix::OpenUrl { url, .. })) = &finding.remedy {
+                    ui.hyperlink_to(fix.to_string(), url);
+                } else if let Some(remedy) = &finding.remedy {
+                    if ui.add_enabled(self.location.is_some(), Button::new(remedy.to_string())).clicked() {
+                        remedies.push(remedy.clone());
+                    }
+                }
+                ui.separator();
+            }
         });
+        let Some(location) = self.location.clone() else { return };
+        for remedy in remedies {
+            match remedy {
+                Remedy::Fix(fix) => match fix.apply(&location) {
+                    Ok(done) => self.console.log(&done),
+                    Err(e) => self.console.log(&format!("couldn't {}: {}", fix, e)),
+                },
+                Remedy::Command(args) => {
+                    let cmd = duct::cmd(&args[0], &args[1..]).dir(&location);
+                    self.run_background_commands(&args.join(" "), Stream::Script, &[cmd], ctx);
+                },
+            }
+        }
+        if cleared {
+            self.health = None;
+        }
+        if check_again {
+            self.check_health(ctx);
+        }
     }
 
     /// show the project tree in a Ui
@@ -109,8 +210,23 @@ impl Project {
             },
             Some(l) => l.clone(),   // clone here so we have no refs to self
         };
-        let dir = project_folder.as_path();
-        self.display_directory(dir, 0, ctx, ui);
+        match self.file_tree.display(ctx, ui, &project_folder, &self.trash) {
+            Some(TreeEvent::Open(path)) => {
+                self.code_editor.load_from_file(&path).unwrap_or_else(|_| warn!("error loading file contents"));
+            },
+            Some(TreeEvent::Changed(applied)) => {
+                for (from, to) in applied.moved.iter() {
+                    self.code_editor.file_moved(from, to);
+                }
+                for path in applied.deleted.iter() {
+                    self.code_editor.file_deleted(path);
+                }
+                if !applied.deleted.is_empty() {
+                    self.info_logger(&format!("moved {} files to the trash", applied.deleted.len()));
+                }
+            },
+            None => (),
+        }
     }
 
     /// Show the project toolbar, with buttons to perform various actions
@@ -128,6 +244,39 @@ impl Project {
             if ui.add(button).clicked() {
                 self.build(ctx);
             }
+            ui.menu_button(format!("with {}", self.build_backend.kind), |ui| {
+                let main_board = self.system.main_board.clone();
+                self.build_backend.display_settings(ui, main_board.as_ref());
+                if let Some(location) = &self.location {
+                    if ui.button("detect from project files").clicked() {
+                        self.build_backend.kind = backend::detect(location);
+                    }
+                }
+                ui.separator();
+                self.reproducible.display_settings(ui);
+                if self.reproducible.enabled {
+                    if ui.button("build and record").clicked() {
+                        self.build_and_record(ctx, RecordAction::Record);
+                        ui.close_menu();
+                    }
+                    if ui.button("build and check against the record").clicked() {
+                        self.build_and_record(ctx, RecordAction::Verify);
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.menu_button(format!("from {}", self.dependency_sources.mode), |ui| {
+                self.dependency_sources.display_settings(ui);
+                ui.separator();
+                if ui.button("vendor dependencies").clicked() {
+                    self.vendor_dependencies(ctx);
+                    ui.close_menu();
+                }
+                if ui.button("check offline build").clicked() {
+                    self.check_offline_build(ctx);
+                    ui.close_menu();
+                }
+            });
 
             ui.separator();
             // LOAD CODE ONTO BOARD
@@ -138,6 +287,63 @@ impl Project {
             if ui.add(button).clicked() {
                 self.load_to_board(ctx);
             }
+            if let Some(main_board) = self.system.main_board.clone() {
+                if let Some(layout) = main_board.flash_layout() {
+                    let slot = match self.slot_settings.slot.as_str() {
+                        "" => layout.primary().map(|s| s.name.clone()).unwrap_or_default(),
+                        slot => slot.to_string(),
+                    };
+                    ui.menu_button(format!("to slot {}", slot), |ui| {
+                        let partitions = partition_table::effective(self.partition_table.as_ref(), Some(&main_board));
+                        ui.add(board::display::FlashMapWidget { layout: Some(layout), partitions, flash: main_board.flash(), selected: Some(&slot) });
+                        for s in layout.slots.iter() {
+                            let text = format!("{} (0x{:08x})", s.name, layout.address(s));
+                            ui.radio_value(&mut self.slot_settings.slot, s.name.clone(), text);
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("image version:");
+                            let version = self.slot_settings.version.trim();
+                            let valid = version.is_empty() || version.parse::<mcuboot::ImageVersion>().is_ok();
+                            let mut edit = egui::TextEdit::singleline(&mut self.slot_settings.version)
+                                .hint_text("from Cargo.toml")
+                                .desired_width(100.0);
+                            if !valid {
+                                edit = edit.text_color(ui.style().visuals.error_fg_color);
+                            }
+                            ui.add(edit);
+                        });
+                        ui.checkbox(&mut self.slot_settings.confirm, "confirm image")
+                            .on_hover_text("mark the image as good, instead of letting the bootloader revert it after one boot");
+                        self.display_signing_key(ui);
+                    });
+                }
+            }
+
+            ui.separator();
+            // LOOK FOR DEBUG PROBES
+            if ui.button("Detect Probes").clicked() {
+                self.detect_probes(ctx);
+            }
+
+            ui.separator();
+            // AUDIT THE PROJECT SETUP
+            if ui.button("Health Check").clicked() {
+                self.check_health(ctx);
+            }
+
+            // RUN THE WORKSPACE'S TASKS
+            let tasks: Vec<String> = self.workspace.iter().flat_map(|w| w.settings.tasks.iter().map(|t| t.name.clone())).collect();
+            if !tasks.is_empty() {
+                ui.separator();
+                ui.menu_button("Tasks", |ui| {
+                    for task in tasks.iter() {
+                        if ui.button(task).clicked() {
+                            self.run_task(ctx, task);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
 
             ui.separator();
             // GENERATE PROJECT TEMPLATE
@@ -174,13 +380,32 @@ impl Project {
                 }
             }
 
+            ui.separator();
+            // GENERATE SIMULATION MODULE
+            if ui.button("Gen Sim Mod").on_hover_text("simulate the main board's pins and buses for host tests").clicked() {
+                info!("attempting to generate simulation module...");
+                let (Some(location), Some(board)) = (&self.location, &self.system.main_board) else {
+                    info!("can't generate a simulation without a project location and main board.");
+                    return;
+                };
+                match simulation::write_module(board, location) {
+                    Ok(()) => {
+                        info!("wrote src/{}.", simulation::MODULE_FILE_NAME);
+                    },
+                    Err(e) => {
+                        warn!("couldn't write the simulation module: {:?}", e);
+                    },
+                }
+            }
+
             ui.separator();
             let button = Button::image_and_text(
                 icons.get("trash_icon").unwrap().clone(),
                 " clear terminal",
             ).frame(false);
             if ui.add(button).clicked() {
-                self.terminal_buffer.clear();
+                self.console.clear();
+                self.explained.clear();
             }
             // Open a window to add changes
             // Commit the changes to the git repo with a user message
@@ -227,15 +452,53 @@ impl Project {
                 git_things.display = true;
                 git_things.changes = changes;
                 git_things.repo = Some(repo);
+                git_things.local_sources = local_sources::find(Path::new(&self.get_location()));
             }
 
         });
     }
 
     /// In the provided Ui, create a multi-column layout (tabs) that switches the current view state.
+    /// Show the key slot images are signed with, and let the user pick or generate one.
+    fn display_signing_key(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("signing key:");
+            match &self.slot_settings.signing_key {
+                Some(key) => ui.label(key.display().to_string()),
+                None => ui.label("none (unsigned)"),
+            };
+            let mut picked = None;
+            if ui.small_button("pick").clicked() {
+                picked = rfd::FileDialog::new().add_filter("PEM key", &["pem"]).pick_file();
+            }
+            ui.menu_button("new", |ui| {
+                for key_type in [mcuboot::KeyType::EcdsaP256, mcuboot::KeyType::Ed25519] {
+                    if ui.button(key_type.to_string()).clicked() {
+                        ui.close_menu();
+                        let Some(path) = rfd::FileDialog::new().set_file_name("signing-key.pem").save_file() else {
+                            continue;
+                        };
+                        match mcuboot::SigningKey::generate(key_type).and_then(|pem| Ok(std::fs::write(&path, pem)?)) {
+                            Ok(()) => picked = Some(path),
+                            Err(e) => self.info_logger(&format!("couldn't create a signing key: {}", e)),
+                        }
+                    }
+                }
+            });
+            if self.slot_settings.signing_key.is_some() && ui.small_button("clear").clicked() {
+                self.slot_settings.signing_key = None;
+            }
+            if let Some(path) = picked {
+                // keep keys inside the project relative, so the project can move
+                let relative = self.location.as_ref().and_then(|dir| path.strip_prefix(dir).ok()).map(Path::to_path_buf);
+                self.slot_settings.signing_key = Some(relative.unwrap_or(path));
+            }
+        });
+    }
+
     fn display_sidebar_tabs(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
         // show the tabs to switch between view modes
-        ui.columns(2, |columns| {
+        ui.columns(4, |columns| {
             let mut new_view: ProjectViewType;
             let button = Button::new("File Explorer").frame(false);
             if columns[0].add(button).clicked() {
@@ -248,9 +511,160 @@ impl Project {
                 new_view = ProjectViewType::BoardsView;
                 self.current_view = new_view;
             };
+            let button = Button::new("Config").frame(false);
+            if columns[2].add(button).clicked() {
+                new_view = ProjectViewType::ConfigView;
+                self.current_view = new_view;
+            };
+            let button = Button::new("Lints").frame(false);
+            if columns[3].add(button).clicked() {
+                new_view = ProjectViewType::LintsView;
+                self.current_view = new_view;
+            };
         });
     }
 
+    /// Show the lint settings, and what clippy found with them. They're applied a moment
+    /// after they stop changing, so typing in them doesn't start a check for every key.
+    fn display_lints(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if self.lints.display(ui) {
+            self.lints_changed = Some(std::time::Instant::now());
+        }
+        if let Some(workspace) = &self.workspace {
+            let inherited = &workspace.settings.lints.rules;
+            if !inherited.is_empty() {
+                let names: Vec<String> = inherited.iter().map(|r| r.to_string()).collect();
+                ui.weak(format!("on top of the workspace's lints: {}", names.join(", ")))
+                    .on_hover_text(workspace.to_string());
+            }
+        }
+        if let Some(changed) = self.lints_changed {
+            let wait = std::time::Duration::from_secs(1);
+            if changed.elapsed() >= wait {
+                self.lints_changed = None;
+                self.apply_lints(ctx);
+            } else {
+                ctx.request_repaint_after(wait - changed.elapsed());
+            }
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("apply and check").clicked() {
+                self.lints_changed = None;
+                self.apply_lints(ctx);
+            }
+            match &self.lint_check {
+                Some(diagnostics) => ui.label(backend::summarize(diagnostics)),
+                None => ui.weak("not checked yet"),
+            };
+        });
+        let mut opened = None;
+        for d in self.lint_check.iter().flatten().filter(|d| d.severity != backend::Severity::Note) {
+            let color = match d.severity {
+                backend::Severity::Error => ui.visuals().error_fg_color,
+                _ => ui.visuals().warn_fg_color,
+            };
+            let response = ui.add(egui::Label::new(egui::RichText::new(d.to_string()).color(color)).sense(egui::Sense::click()));
+            if let (true, Some(path), Some(line)) = (response.clicked(), &d.file, d.line) {
+                opened = Some(FileRef { path: path.clone(), line: line as usize, column: d.column.map(|c| c as usize), span: 0..0 });
+            }
+        }
+        if let Some(file_ref) = opened {
+            self.open_file_ref(&file_ref);
+        }
+    }
+
+    /// Add a crate to the project's Cargo.toml with `cargo add`, at a release that works
+    /// with the crates already there.
+    fn add_crate(&mut self, name: &str, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            self.console.log("save project first!");
+            return;
+        };
+        match compat::crate_to_add(&path, name) {
+            Ok(spec) => {
+                let cmd = duct::cmd!("cargo", "-Z", "unstable-options", "-C", &path, "add", spec.as_str());
+                self.run_background_commands(&format!("cargo add {}", spec), Stream::Cargo, &[cmd], ctx);
+            },
+            Err(e) => self.console.log(&format!("not adding {}: {}", name, e)),
+        }
+    }
+
+    /// Show the project's boards as cards, with the selected one opened up to its details
+    /// and crates.
+    fn display_board_cards(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, boards: &[board::Board]) {
+        // a board that's been taken out of the project can't stay selected
+        if !boards.iter().any(|b| Some(b.key()) == self.selected_board) {
+            self.selected_board = None;
+        }
+        // cards of different heights line up along their tops
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Min).with_main_wrap(true), |ui| {
+            for b in boards.iter() {
+                let selected = self.selected_board == Some(b.key());
+                let card = board::display::BoardWidget::new(b).mode(BoardDisplayMode::Compact).selected(selected);
+                if ui.add(card).clicked() {
+                    self.selected_board = if selected { None } else { Some(b.key()) };
+                }
+            }
+        });
+        let Some(b) = boards.iter().find(|b| Some(b.key()) == self.selected_board) else {
+            ui.weak("hover over a board for its details, or click it to open it up");
+            return;
+        };
+        ui.separator();
+        self.display_board_crates(ctx, ui, b);
+    }
+
+    /// Show the quick start and the crates of a board in the project.
+    fn display_board_crates(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, b: &board::Board) {
+        if b.quickstart().is_some() {
+            ui.vertical_centered(|ui| {
+                if ui.small_button("quick start").clicked() {
+                    events::shared(ctx).publish(Event::ShowQuickStart(b.key()));
+                }
+            });
+        }
+        // show the required crates
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            let label = egui::RichText::new("Required Crates").underline();
+            ui.label(label);
+        });
+        if let Some(required_crates) = b.required_crates() {
+            for rc in required_crates.iter() {
+                ui.horizontal(|ui| {
+                    if ui.link(rc).clicked() {
+                        self.add_crate(rc, ctx);
+                    };
+                });
+            }
+        }
+        ui.separator();
+        // show the related crates
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            let label = egui::RichText::new("Related Crates").underline();
+            ui.label(label);
+        });
+        if let Some(related_crates) = b.related_crates() {
+            for rc in related_crates.iter() {
+                ui.horizontal(|ui| {
+                    if ui.link(rc).on_hover_text(compat::docs_url(rc)).clicked() {
+                        ctx.open_url(egui::OpenUrl::new_tab(compat::docs_url(rc)));
+                    };
+                    // crates can only be added to a saved project
+                    let added = self.location.as_deref().map(|path| compat::has_dependency(path, rc));
+                    if added == Some(true) {
+                        ui.weak("in Cargo.toml");
+                    } else if added.is_some() && ui.small_button("add to Cargo.toml").clicked() {
+                        self.add_crate(rc, ctx);
+                    }
+                    if ui.small_button("snippets").clicked() {
+                        self.show_crate_info(rc.clone());
+                    }
+                });
+            }
+        }
+    }
+
     /// Show the crate info
     pub fn show_crate_info(&mut self, crate_name: String) {
         self.current_view = ProjectViewType::CrateView(crate_name);
@@ -267,43 +681,17 @@ impl Project {
             match &self.current_view {
                 ProjectViewType::BoardsView => {
                     let boards = self.system.get_all_boards();
-                    // Now, show the board widgets
-                    for b in boards.iter() {
-                        ui.add(b.clone());
-                        // show the required crates
-                        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                            let label = egui::RichText::new("Required Crates").underline();
-                            ui.label(label);
-                        });
-                        if let Some(required_crates) = b.required_crates() {
-                            for rc in required_crates.iter() {
-                                ui.horizontal(|ui| {
-                                    if ui.link(rc).clicked() {
-                                        if let Some(path) = &self.location {
-                                            let cmd = duct::cmd!("cargo", "-Z", "unstable-options", "-C", path.as_path().to_str().unwrap(), "add", rc.as_str());
-                                            self.run_background_commands(&[cmd], ctx);
-                                        } else {
-                                            self.terminal_buffer += "save project first!\n";
-                                        }
-
-                                    };
-                                });
-                            }
-                        }
-                        ui.separator();
-                        // show the related crates
-                        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
-                            let label = egui::RichText::new("Related Crates").underline();
-                            ui.label(label);
-                        });
-                        if let Some(related_crates) = b.related_crates() {
-                            for rc in related_crates.iter() {
-                                ui.horizontal(|ui| {
-                                    if ui.link(rc).clicked() {
-                                        self.show_crate_info(rc.clone());
-                                    };
-                                });
-                            }
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut self.board_display, BoardDisplayMode::Compact, "cards");
+                        ui.selectable_value(&mut self.board_display, BoardDisplayMode::Detailed, "details");
+                    });
+                    if self.board_display == BoardDisplayMode::Compact {
+                        self.display_board_cards(ctx, ui, &boards);
+                    } else {
+                        // Now, show the board widgets
+                        for b in boards.iter() {
+                            ui.add(b.clone());
+                            self.display_board_crates(ctx, ui, b);
                         }
                     }
                 },
@@ -328,21 +716,100 @@ impl Project {
                         })
                     }
                 },
-                ProjectViewType::FileTree => {
-                    // option to add a new top-level directory
-                    let dir_button = egui::widgets::Button::new("+ dir/file").frame(false);
-                    if ui.add(dir_button).clicked() {
-                        self.new_file().unwrap_or_else(|_| warn!("couldn't create new file"));
+                ProjectViewType::ConfigView => {
+                    egui::CollapsingHeader::new(format!("Environment ({})", self.environment.vars.len())).show(ui, |ui| {
+                        ui.weak("set for builds, loading onto the board and commands run for the project");
+                        self.environment.display(ui);
+                        if let Some(workspace) = &self.workspace {
+                            let inherited: Vec<&str> = workspace.settings.environment.vars.iter().map(|v| v.key.as_str()).collect();
+                            if !inherited.is_empty() {
+                                ui.weak(format!("also set from the workspace: {}", inherited.join(", ")))
+                                    .on_hover_text(workspace.to_string());
+                            }
+                        }
+                    });
+                    egui::CollapsingHeader::new(format!("Standard library ({})", self.std_support.support)).show(ui, |ui| {
+                        self.std_support.display(ui, self.system.main_board.as_ref());
+                        let Some(location) = self.location.clone() else {
+                            ui.label("save the project to change its code");
+                            return;
+                        };
+                        if ui.button("apply to src/main.rs and Cargo.toml").clicked() {
+                            match self.std_support.apply(&location) {
+                                Ok(applied) => {
+                                    let changed: Vec<String> = applied.changed.iter().map(|f| f.display().to_string()).collect();
+                                    self.console.log(&format!("changed for {}: {}", self.std_support.support, changed.join(", ")));
+                                    for note in applied.notes {
+                                        self.console.log(&note);
+                                    }
+                                },
+                                Err(e) => self.console.log(&format!("couldn't change the project for {}: {}", self.std_support.support, e)),
+                            }
+                        }
+                    });
+                    egui::CollapsingHeader::new("Panics and logging").show(ui, |ui| {
+                        self.logging.display(ui, self.system.main_board.as_ref());
+                        let Some(location) = self.location.clone() else {
+                            ui.label("save the project to change its code");
+                            return;
+                        };
+                        if ui.button("apply to the project").clicked() {
+                            match self.logging.apply(&location, &self.std_support) {
+                                Ok(applied) => {
+                                    let changed: Vec<String> = applied.changed.iter().map(|f| f.display().to_string()).collect();
+                                    self.console.log(&format!("changed for {} and {}: {}", self.logging.panic, self.logging.log, changed.join(", ")));
+                                    for note in applied.notes {
+                                        self.console.log(&note);
+                                    }
+                                },
+                                Err(e) => self.console.log(&format!("couldn't change the panic handler and logging: {}", e)),
+                            }
+                        }
+                    });
+                    ui.separator();
+                    let schema = self.config_schema();
+                    if schema.is_empty() {
+                        ui.label("None of the boards in this project have config options.");
+                        return;
+                    }
+                    self.config.display(ui, &schema);
+                    ui.separator();
+                    let features = self.config.enabled_features(&schema);
+                    if !features.is_empty() {
+                        ui.label(format!("features: {}", features.join(", ")));
+                    }
+                    if let Some(location) = self.location.clone() {
+                        if ui.button("generate src/config.rs").clicked() {
+                            match self.config.write(&schema, &location) {
+                                Ok(()) => self.console.log("wrote src/config.rs"),
+                                Err(e) => self.console.log(&format!("couldn't write src/config.rs: {}", e)),
+                            }
+                        }
+                    } else {
+                        ui.label("save the project to generate src/config.rs");
                     }
+                },
+                ProjectViewType::FileTree => {
                     // show the project tree
                     self.display_project_tree(ctx, ui);
                 },
+                ProjectViewType::LintsView => {
+                    self.display_lints(ctx, ui);
+                },
             }
         });
     }
 
+    /// Open the board selector narrowed down to the board called `name`.
+    pub fn show_known_board(&mut self, ctx: &egui::Context, name: &str) {
+        self.board_filter = board::filter::BoardFilter { name: name.to_string(), ..Default::default() };
+        ctx.data_mut(|data| {
+            data.insert_temp(egui::Id::new("show_known_boards"), true);
+        });
+    }
+
     /// Display the list of available boards in a window, and return one if it was clicked
-    pub fn display_known_boards(&mut self, ctx: &egui::Context, should_show: &mut bool) -> Option<board::Board> {
+    pub fn display_known_boards(&mut self, ctx: &egui::Context, should_show: &mut bool, favorites: &mut BoardFavorites, sorting: &mut BoardSorting) -> Option<board::Board> {
 
         let mut board: Option<board::Board> = None;
         // create the window
@@ -353,37 +820,191 @@ impl Project {
         .movable(false)
         .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
         .show(ctx, |ui| {
+            // Narrow the boards down, since there can be a lot of them
+            self.board_filter.display(ui, &self.known_boards);
+            sorting.display(ui);
+            let mut shown = self.board_filter.apply(&self.known_boards);
+            sorting.sort(&mut shown, &self.known_boards);
+            if shown.is_empty() {
+                ui.label("no boards match.");
+            } else if !self.board_filter.is_empty() {
+                ui.weak(format!("{} of {} boards", shown.len(), self.known_boards.len()));
+            }
+            // the boards picked to compare
+            if !self.board_comparison.is_empty() {
+                ui.horizontal(|ui| {
+                    let names: Vec<String> = self.board_comparison.boards(&self.known_boards).iter().map(|b| b.label()).collect();
+                    ui.label(format!("picked to compare: {}", names.join(", ")));
+                    if ui.add_enabled(self.board_comparison.len() > 1, egui::Button::new("compare")).clicked() {
+                        self.board_comparison.open = true;
+                    }
+                    if ui.button("clear").clicked() {
+                        self.board_comparison.clear();
+                    }
+                });
+            }
+            ui.separator();
             // Create a grid-based layout to show all the board widgets
             let available_width = ui.available_width();
             let mut num_cols = (available_width / 260.0) as usize;
             if num_cols == 0 {
                 num_cols = 1;
             }
+            // the boards that fit the ones in the project are suggested first, then the
+            // starred boards go in a section of their own
+            let main_board = self.system.main_board.as_ref();
+            let suggested: Vec<(usize, fitting::Match)> = fitting::suggestions(main_board, &self.system.peripheral_boards, &self.known_boards)
+                .into_iter()
+                .filter(|(i, _)| shown.contains(i))
+                .collect();
+            let suggestions_heading = match main_board {
+                Some(main_board) => format!("Fits the {}", main_board.get_name()),
+                None => "Main boards that fit the project's boards".to_string(),
+            };
+            shown.retain(|i| !suggested.iter().any(|(s, _)| s == i));
+            let (starred, rest) = favorites.split(&shown, &self.known_boards);
             egui::containers::scroll_area::ScrollArea::vertical().show(ui, |ui| {
-                ui.columns(num_cols, |columns| {
-                    for (i, b) in self.known_boards.clone().into_iter().enumerate() {
-                        let col = i % num_cols;
-                        // When a board is clicked, add it to the new project
-                        if columns[col].add(board::display::BoardSelectorWidget(b)).clicked() {
-                            board = Some(self.known_boards[i].clone());
+                if !suggested.is_empty() {
+                    ui.strong(suggestions_heading);
+                    let mut reasons: Vec<&fitting::Match> = vec![];
+                    for (_, m) in suggested.iter() {
+                        if !reasons.contains(&m) {
+                            reasons.push(m);
                         }
                     }
-                });
+                    for m in reasons {
+                        let color = match m.fit {
+                            fitting::Fit::SameFootprint => ui.visuals().warn_fg_color,
+                            _ => ui.visuals().weak_text_color(),
+                        };
+                        ui.colored_label(color, &m.reason);
+                        let fitting: Vec<usize> = suggested.iter().filter(|(_, s)| s == m).map(|(i, _)| *i).collect();
+                        if let Some(b) = self.display_board_columns(ui, &fitting, num_cols, favorites) {
+                            board = Some(b);
+                        }
+                    }
+                    if !shown.is_empty() {
+                        ui.separator();
+                    }
+                }
+                if !starred.is_empty() {
+                    ui.strong("Favorites");
+                    if let Some(b) = self.display_board_columns(ui, &starred, num_cols, favorites) {
+                        board = Some(b);
+                    }
+                }
+                // the rest go in a section for each kind of board
+                let mut first_section = starred.is_empty();
+                for kind in enum_iterator::all::<BoardKind>() {
+                    let of_kind: Vec<usize> = rest.iter().copied().filter(|&i| self.known_boards[i].kind() == kind).collect();
+                    if of_kind.is_empty() {
+                        continue;
+                    }
+                    if !first_section {
+                        ui.separator();
+                    }
+                    first_section = false;
+                    ui.strong(kind.heading());
+                    if let Some(b) = self.display_board_columns(ui, &of_kind, num_cols, favorites) {
+                        board = Some(b);
+                    }
+                }
             });
         });
 
         if response.is_some() {
             // unwrap ok here because we check that response is Some.
             ctx.move_to_top(response.unwrap().response.layer_id);
+            if let Some(b) = self.display_board_comparison(ctx) {
+                board = Some(b);
+            }
+        }
+        if let Some(b) = &board {
+            sorting.used(b);
         }
 
         return board;
 
     }
 
+    /// Show some of the known boards in columns, each with its star and compare toggles.
+    /// Returns the board that was clicked, if one was.
+    fn display_board_columns(&mut self, ui: &mut egui::Ui, indices: &[usize], num_cols: usize, favorites: &mut BoardFavorites) -> Option<board::Board> {
+        let mut board = None;
+        ui.columns(num_cols, |columns| {
+            for (n, &i) in indices.iter().enumerate() {
+                let col = n % num_cols;
+                // When a board is clicked, add it to the new project
+                if columns[col].add(board::display::BoardSelectorWidget(self.known_boards[i].clone())).clicked() {
+                    board = Some(self.known_boards[i].clone());
+                }
+                columns[col].horizontal(|ui| {
+                    let starred = favorites.is_favorite(&self.known_boards[i]);
+                    let star = if starred { "★ favorite" } else { "☆ favorite" };
+                    if ui.selectable_label(starred, star).clicked() {
+                        favorites.toggle(&self.known_boards[i]);
+                    }
+                    // pick boards to compare, as many as fit side by side
+                    let mut picked = self.board_comparison.is_picked(&self.known_boards[i]);
+                    let can_pick = picked || !self.board_comparison.is_full();
+                    if ui.add_enabled(can_pick, egui::Checkbox::new(&mut picked, "compare")).changed() {
+                        self.board_comparison.toggle(&self.known_boards[i]);
+                    }
+                    // share the board and its files as one file
+                    let bundle = ui.add_enabled(self.known_boards[i].file().is_some(), egui::Button::new("share"))
+                        .on_hover_text("save the board and its files as a bundle, which can be imported into another boards directory");
+                    if bundle.clicked() {
+                        let b = &self.known_boards[i];
+                        let file_name = format!("{}.board.json", b.label().replace(char::is_whitespace, "_"));
+                        if let Some(path) = rfd::FileDialog::new().add_filter("board bundle", &["json"]).set_file_name(file_name).save_file() {
+                            if let Err(e) = board::bundle::export(b, &path) {
+                                warn!("couldn't bundle {}: {}", b.label(), e);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        board
+    }
+
+    /// Show the boards picked in the board selector side by side. Returns a board to add
+    /// to the project, if one was picked from the comparison.
+    fn display_board_comparison(&mut self, ctx: &egui::Context) -> Option<board::Board> {
+        let mut open = self.board_comparison.open;
+        if !open {
+            return None;
+        }
+        let boards = self.board_comparison.boards(&self.known_boards);
+        let mut chosen = None;
+        let response = egui::Window::new("Board comparison")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.add(board::display::BoardComparisonWidget(&boards));
+            ui.separator();
+            ui.horizontal(|ui| {
+                for b in boards.iter() {
+                    if ui.button(format!("add {}", b.label())).clicked() {
+                        chosen = Some((*b).clone());
+                    }
+                }
+            });
+        });
+        if let Some(response) = response {
+            ctx.move_to_top(response.response.layer_id);
+        }
+        self.board_comparison.open = open && chosen.is_none();
+        chosen
+    }
+
     /// Show the boards in egui "Area"s so we can move them around!
     pub fn display_system_editor_boards(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
 
+        // boards are hashed by their manufacturer, name and revision, not their image, which
+        // is all that can change
+        #[allow(clippy::mutable_key_type)]
         let mut pin_locations: HashMap<(board::Board, String), egui::Pos2> = HashMap::new();
 
         // iterate through the system boards and draw them on the screen
@@ -394,26 +1015,43 @@ impl Project {
             let scale = ctx.data_mut(|data| {
                 data.get_temp_mut_or(scale_id, 5.0).clone()
             });
+            // draw the board as a schematic symbol instead of its image?
+            let schematic = ctx.data(|data| {
+                data.get_temp::<bool>(egui::Id::new("system_editor_schematic"))
+            }).unwrap_or(false);
 
             // Get the response of the board/pin Ui
-            let board_id = egui::Id::new(board.get_name());
+            let board_id = egui::Id::new(board.key());
             let response = egui::Area::new(board_id).show(ctx, |ui| {
 
                 let mut pin_clicked: Option<String> = None;
 
-                if let Some(svg_board_info) = board.clone().svg_board_info {
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-
-                    let display_size = svg_board_info.physical_size * scale;
+                // where the board was drawn, and its pins in mm from the top left of that
+                let drawn = match board.clone().svg_board_info {
+                    _ if schematic => {
+                        let symbol = board::schematic::SchematicSymbol::new(board);
+                        let (rect, _) = ui.allocate_exact_size(symbol.size * scale, egui::Sense::hover());
+                        let visuals = ui.visuals();
+                        symbol.paint(ui.painter(), rect.left_top(), scale, visuals.strong_text_color(), visuals.extreme_bg_color);
+                        Some((rect, symbol.pin_rects()))
+                    },
+                    Some(svg_board_info) => {
+                        let display_size = svg_board_info.physical_size * scale;
+                        // until the image is decoded, the board's space is kept empty
+                        let image_rect = match texture_cache::board_texture(ctx, board) {
+                            Some(handle) => ui.add(egui::Image::from_texture(&handle).max_size(display_size)).rect,
+                            None => ui.allocate_exact_size(display_size, egui::Sense::hover()).0,
+                        };
+                        Some((image_rect, svg_board_info.pin_rects))
+                    },
+                    None => None,
+                };
 
-                    let image_rect = retained_image.show_max_size(ui, display_size).rect;
+                if let Some((image_rect, pin_rects)) = drawn {
 
                     // iterate through the pin_nodes of the board, and check if their rects (properly scaled and translated)
                     // contain the pointer. If so, actually draw the stuff there.
-                    for (pin_name, mut pin_rect) in board.clone().svg_board_info.unwrap().pin_rects {
+                    for (pin_name, mut pin_rect) in pin_rects {
                         // scale the rects the same amount that the board image was scaled
                         pin_rect.min.x *= scale;
                         pin_rect.min.y *= scale;
@@ -431,7 +1069,11 @@ impl Project {
                         if r.hovered() {
                             ui.painter().circle_filled(r.rect.center(), r.rect.height()/2.0, egui::Color32::GREEN);
                         }
-                        r.clone().on_hover_text(String::from(board.get_name()) + ":" + &pin_name);
+                        let capabilities: Vec<String> = board.pin_capabilities(&pin_name).iter().map(|c| c.to_string()).collect();
+                        match capabilities.is_empty() {
+                            true => r.clone().on_hover_text(String::from(board.get_name()) + ":" + &pin_name),
+                            false => r.clone().on_hover_text(format!("{}:{} ({})", board.get_name(), pin_name, capabilities.join(", "))),
+                        };
                         r.clone().context_menu(|ui| {
                             ui.label("a pin-level menu option");
                         });
@@ -604,7 +1246,7 @@ impl Project {
 
     /// Show the project HUD with information about the current system. Return a "Mode" so that
     /// the calling module (app) can update the GUI accordingly.
-    pub fn display_system_editor_top_bar(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, warning_flags: &mut Warnings) -> Option<Mode> {
+    pub fn display_system_editor_top_bar(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, warning_flags: &mut Warnings, favorites: &mut BoardFavorites, sorting: &mut BoardSorting) -> Option<Mode> {
 
         // prepare the return value
         let mut ret: Option<Mode> = None;
@@ -645,7 +1287,25 @@ impl Project {
         if cui.add(add_board_button).clicked() {
             should_show_boards_window = true;
         }
-        if let Some(b) = self.display_known_boards(ctx, &mut should_show_boards_window) {
+        // switch the boards between their images and schematic symbols
+        let schematic_id = egui::Id::new("system_editor_schematic");
+        let mut schematic = ctx.data(|data| data.get_temp::<bool>(schematic_id)).unwrap_or(false);
+        if cui.toggle_value(&mut schematic, "schematic view").changed() {
+            ctx.data_mut(|data| data.insert_temp(schematic_id, schematic));
+        }
+        cui.checkbox(&mut self.repo_setup.init, "git repository")
+            .on_hover_text("keep the project's code in a git repository, with a .gitignore for build output and keys");
+        cui.add_enabled(self.repo_setup.init, egui::Checkbox::new(&mut self.repo_setup.initial_commit, "first commit"))
+            .on_hover_text("commit the code the project starts with");
+        egui::ComboBox::from_id_source("new_project_std_support")
+            .selected_text(self.std_support.support.to_string())
+            .show_ui(&mut cui, |ui| {
+                for support in enum_iterator::all::<StdSupport>() {
+                    ui.selectable_value(&mut self.std_support.support, support, support.to_string()).on_hover_text(support.describe());
+                }
+            });
+        if let Some(b) = self.display_known_boards(ctx, &mut should_show_boards_window, favorites, sorting) {
+            events::shared(ctx).publish(Event::BoardSelected(b.key()));
             self.add_board(b);
         }
         ctx.data_mut(|data| {
@@ -0,0 +1,572 @@
+//! Title: Iron Coder Project Module - Embed
+//! Description: Turns the images, fonts and sounds a project uses into data its firmware
+//!   can include. Images are scaled and converted to the raw pixel formats embedded-graphics
+//!   draws, WAV files are mixed down and resampled to PCM, and fonts and other files are
+//!   kept as they are. Everything goes into `src/assets.rs` as byte arrays, optionally in
+//!   a linker section of its own, and the module is regenerated before a build whenever
+//!   an asset or its settings change.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use enum_iterator::Sequence;
+use image::imageops::FilterType;
+use serde::{Serialize, Deserialize};
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "assets.rs";
+/// How the module records what it was generated from.
+const FINGERPRINT_PREFIX: &str = "// fingerprint: ";
+/// Bytes per line of an array in the module.
+const BYTES_PER_LINE: usize = 16;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum EmbedError {
+    /// An asset couldn't be read or the module couldn't be written
+    IoError(io::Error),
+    /// An asset's name isn't usable, or is used twice
+    BadName(String),
+    /// An asset isn't in the format its kind needs
+    Unconvertible { asset: String, reason: String },
+}
+
+impl fmt::Display for EmbedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmbedError::IoError(e) => write!(f, "{}", e),
+            EmbedError::BadName(e) => write!(f, "{}", e),
+            EmbedError::Unconvertible { asset, reason } => write!(f, "couldn't convert {}: {}", asset, reason),
+        }
+    }
+}
+
+impl From<io::Error> for EmbedError {
+    fn from(e: io::Error) -> Self {
+        EmbedError::IoError(e)
+    }
+}
+
+/// The pixel formats of embedded-graphics' `ImageRaw`, with multi-byte pixels big-endian
+/// like `ImageRaw` reads them by default.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum PixelFormat {
+    /// One bit a pixel, each row padded to a whole byte, for monochrome OLEDs and e-paper
+    BinaryColor,
+    Gray8,
+    #[default]
+    Rgb565,
+    Rgb888,
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl PixelFormat {
+
+    /// The bytes of one row of pixels.
+    pub fn row_bytes(&self, width: u32) -> usize {
+        let width = width as usize;
+        match self {
+            PixelFormat::BinaryColor => width.div_ceil(8),
+            PixelFormat::Gray8 => width,
+            PixelFormat::Rgb565 => width * 2,
+            PixelFormat::Rgb888 => width * 3,
+        }
+    }
+}
+
+/// The sample formats audio is converted to. Audio is always mono.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum SampleFormat {
+    /// Unsigned 8-bit, centered on 128, as most DACs and PWM audio take it
+    #[default]
+    U8,
+    /// Signed 16-bit little-endian, as I2S codecs take it
+    I16,
+}
+
+impl fmt::Display for SampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SampleFormat::U8 => write!(f, "unsigned 8-bit"),
+            SampleFormat::I16 => write!(f, "signed 16-bit little-endian"),
+        }
+    }
+}
+
+/// What an asset is, and how it's converted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AssetKind {
+    /// A PNG, JPEG or GIF, scaled to the given size. With only one of width and height
+    /// the other keeps the aspect ratio.
+    Image { format: PixelFormat, width: Option<u32>, height: Option<u32> },
+    /// A PCM WAV file, mixed down to mono and resampled
+    Audio { sample_rate: u32, format: SampleFormat },
+    /// A TrueType, OpenType, BDF or PSF font, included as it is
+    Font,
+    /// Any file, included as it is
+    Raw,
+}
+
+impl Default for AssetKind {
+    fn default() -> Self {
+        AssetKind::Image { format: PixelFormat::default(), width: None, height: None }
+    }
+}
+
+impl fmt::Display for AssetKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetKind::Image { .. } => write!(f, "image"),
+            AssetKind::Audio { .. } => write!(f, "audio"),
+            AssetKind::Font => write!(f, "font"),
+            AssetKind::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+impl AssetKind {
+    /// One of each kind, with default settings.
+    pub fn all() -> [AssetKind; 4] {
+        [
+            AssetKind::default(),
+            AssetKind::Audio { sample_rate: 8000, format: SampleFormat::default() },
+            AssetKind::Font,
+            AssetKind::Raw,
+        ]
+    }
+}
+
+/// A file to embed in the firmware.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Asset {
+    /// The name of its static in the module, i.e. "logo" becomes `LOGO`
+    pub name: String,
+    /// The file, relative to the project directory
+    pub source: PathBuf,
+    #[serde(flatten)]
+    pub kind: AssetKind,
+    /// The linker section to put it in, i.e. ".ext_flash", if not the default
+    pub section: Option<String>,
+}
+
+impl Asset {
+    /// The name of the asset's static.
+    pub fn const_name(&self) -> Result<String, EmbedError> {
+        let name: String = self.name.trim().chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        if name.is_empty() || name.chars().all(|c| c == '_') {
+            return Err(EmbedError::BadName(format!("{} needs a name", self.source.display())));
+        }
+        Ok(match name.starts_with(|c: char| c.is_ascii_digit()) {
+            true => format!("_{}", name),
+            false => name,
+        })
+    }
+}
+
+/// The data of a converted asset, and what the firmware needs to know to use it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Converted {
+    pub bytes: Vec<u8>,
+    pub details: Details,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Details {
+    Image { format: PixelFormat, width: u32, height: u32 },
+    Audio { sample_rate: u32, format: SampleFormat, seconds: f64 },
+    File,
+}
+
+/// Scale an image and convert it to a pixel format. Transparent pixels become black.
+pub fn convert_image(data: &[u8], format: PixelFormat, width: Option<u32>, height: Option<u32>) -> Result<(u32, u32, Vec<u8>), String> {
+    let image = image::load_from_memory(data).map_err(|e| e.to_string())?;
+    let (w, h) = (image.width(), image.height());
+    let scale = |to: u32, from: u32, other: u32| ((other as f64 * to as f64 / from as f64).round() as u32).max(1);
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, scale(width, w, h)),
+        (None, Some(height)) => (scale(height, h, w), height),
+        (None, None) => (w, h),
+    };
+    if width == 0 || height == 0 {
+        return Err("the size can't be zero".to_string());
+    }
+    let image = match (width, height) == (w, h) {
+        true => image,
+        false => image.resize_exact(width, height, FilterType::Triangle),
+    }.to_rgba8();
+    let mut bytes = Vec::with_capacity(format.row_bytes(width) * height as usize);
+    for row in image.rows() {
+        let mut bits = 0u8;
+        for (x, pixel) in row.enumerate() {
+            let [r, g, b, a] = pixel.0;
+            let [r, g, b] = [r, g, b].map(|c| (c as u16 * a as u16 / 255) as u8);
+            match format {
+                PixelFormat::BinaryColor => {
+                    let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+                    if luma >= 128 {
+                        bits |= 0x80 >> (x % 8);
+                    }
+                    if x % 8 == 7 || x + 1 == width as usize {
+                        bytes.push(bits);
+                        bits = 0;
+                    }
+                },
+                PixelFormat::Gray8 => bytes.push(((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8),
+                PixelFormat::Rgb565 => {
+                    let pixel = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+                    bytes.extend(pixel.to_be_bytes());
+                },
+                PixelFormat::Rgb888 => bytes.extend([r, g, b]),
+            }
+        }
+    }
+    Ok((width, height, bytes))
+}
+
+/// Read the samples of a PCM WAV file, mixed down to mono, and its sample rate.
+pub fn read_wav(data: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("it isn't a WAV file".to_string());
+    }
+    let (mut format, mut samples) = (None, None);
+    let mut rest = &data[12..];
+    while rest.len() >= 8 {
+        let id = &rest[0..4];
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let body = rest.get(8..8 + size).ok_or("the file is cut short")?;
+        match id {
+            b"fmt " if size >= 16 => {
+                let tag = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real format in its sub-format
+                let tag = match (tag, body.get(24..26)) {
+                    (0xFFFE, Some(sub)) => u16::from_le_bytes([sub[0], sub[1]]),
+                    (tag, _) => tag,
+                };
+                format = Some((tag, channels, rate, bits));
+            },
+            b"data" => samples = Some(body),
+            _ => (),
+        }
+        // chunks are padded to an even length
+        rest = rest.get(8 + size + size % 2..).unwrap_or_default();
+    }
+    let (tag, channels, rate, bits) = format.ok_or("it has no format chunk")?;
+    let samples = samples.ok_or("it has no data chunk")?;
+    if channels == 0 || rate == 0 {
+        return Err("it has no channels".to_string());
+    }
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 8) => |s| (s[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0,
+        (1, 24) => |s| i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2147483648.0,
+        (1, 32) => |s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2147483648.0,
+        (3, 32) => |s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+        _ => return Err(format!("{}-bit samples in format {} aren't supported; save it as 16-bit PCM", bits, tag)),
+    };
+    let width = bits as usize / 8;
+    let mono = samples.chunks_exact(width * channels as usize)
+        .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, rate))
+}
+
+/// Resample mono audio, interpolating between samples.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let count = (samples.len() as u64 * to as u64 / from as u64).max(1) as usize;
+    (0..count).map(|i| {
+        let position = i as f64 * from as f64 / to as f64;
+        let (index, fraction) = (position as usize, position.fract() as f32);
+        let a = samples[index.min(samples.len() - 1)];
+        let b = samples[(index + 1).min(samples.len() - 1)];
+        a + (b - a) * fraction
+    }).collect()
+}
+
+/// Convert a WAV file to mono PCM at a sample rate.
+pub fn convert_audio(data: &[u8], sample_rate: u32, format: SampleFormat) -> Result<(Vec<u8>, f64), String> {
+    if sample_rate == 0 {
+        return Err("the sample rate can't be zero".to_string());
+    }
+    let (samples, rate) = read_wav(data)?;
+    let samples = resample(&samples, rate, sample_rate);
+    let seconds = samples.len() as f64 / sample_rate as f64;
+    let bytes = match format {
+        SampleFormat::U8 => samples.iter().map(|s| (s * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8).collect(),
+        SampleFormat::I16 => samples.iter().flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes()).collect(),
+    };
+    Ok((bytes, seconds))
+}
+
+/// Check that a file is a font a microcontroller font renderer can read.
+pub fn check_font(data: &[u8]) -> Result<(), String> {
+    let known = [&b"\x00\x01\x00\x00"[..], b"OTTO", b"true", b"ttcf", b"STARTFONT", b"\x36\x04", b"\x72\xb5\x4a\x86"];
+    match known.iter().any(|magic| data.starts_with(magic)) {
+        true => Ok(()),
+        false => Err("it isn't a TrueType, OpenType, BDF or PSF font".to_string()),
+    }
+}
+
+/// Convert an asset's file.
+pub fn convert(asset: &Asset, data: &[u8]) -> Result<Converted, EmbedError> {
+    let unconvertible = |reason: String| EmbedError::Unconvertible { asset: asset.source.display().to_string(), reason };
+    let converted = match &asset.kind {
+        AssetKind::Image { format, width, height } => {
+            let (width, height, bytes) = convert_image(data, *format, *width, *height).map_err(unconvertible)?;
+            Converted { bytes, details: Details::Image { format: *format, width, height } }
+        },
+        AssetKind::Audio { sample_rate, format } => {
+            let (bytes, seconds) = convert_audio(data, *sample_rate, *format).map_err(unconvertible)?;
+            Converted { bytes, details: Details::Audio { sample_rate: *sample_rate, format: *format, seconds } }
+        },
+        AssetKind::Font => {
+            check_font(data).map_err(unconvertible)?;
+            Converted { bytes: data.to_vec(), details: Details::File }
+        },
+        AssetKind::Raw => Converted { bytes: data.to_vec(), details: Details::File },
+    };
+    Ok(converted)
+}
+
+/// Read the files of the assets.
+fn read_sources(assets: &[Asset], project_dir: &Path) -> Result<Vec<Vec<u8>>, EmbedError> {
+    assets.iter().map(|asset| {
+        fs::read(project_dir.join(&asset.source))
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't read {}: {}", asset.source.display(), e)).into())
+    }).collect()
+}
+
+/// What the module is generated from: the assets' settings and the contents of their files.
+pub fn fingerprint(assets: &[Asset], sources: &[Vec<u8>]) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    for (asset, data) in assets.iter().zip(sources) {
+        context.update(format!("{:?}", asset).as_bytes());
+        context.update(&(data.len() as u64).to_le_bytes());
+        context.update(data);
+    }
+    context.finish().as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Write out an array's bytes, a line at a time.
+pub(crate) fn byte_lines(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        let line: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+        text += &format!("    {},\n", line.join(", "));
+    }
+    text
+}
+
+/// Generate the module for the assets, given the contents of their files.
+pub fn generate_module(assets: &[Asset], sources: &[Vec<u8>]) -> Result<String, EmbedError> {
+    let mut names: Vec<String> = vec![];
+    let mut code = String::from(
+        "//! The assets embedded in the firmware, generated by Iron Coder from the project's\n\
+         //! asset list. Don't edit this file: it's rewritten when an asset changes.\n",
+    );
+    code += &format!("{}{}\n", FINGERPRINT_PREFIX, fingerprint(assets, sources));
+    code += "#![allow(dead_code)]\n";
+    for (asset, data) in assets.iter().zip(sources) {
+        let name = asset.const_name()?;
+        if names.contains(&name) {
+            return Err(EmbedError::BadName(format!("two assets are named {}", name)));
+        }
+        let converted = convert(asset, data)?;
+        let source = asset.source.display().to_string().replace('\\', "/");
+        code += "\n";
+        match converted.details {
+            Details::Image { format, width, height } => {
+                code += &format!("/// `{}`, {}x{} {}. Draw it with\n", source, width, height, format);
+                code += &format!("/// `ImageRaw::<{}>::new(&{}, {}_WIDTH)`.\n", format, name, name);
+                code += &format!("pub static {}: [u8; {}] = [\n", name, converted.bytes.len());
+                code += &byte_lines(&converted.bytes);
+                code += "];\n";
+                code += &format!("pub const {}_WIDTH: u32 = {};\n", name, width);
+                code += &format!("pub const {}_HEIGHT: u32 = {};\n", name, height);
+            },
+            Details::Audio { sample_rate, format, seconds } => {
+                code += &format!("/// `{}`, {:.2} s of {} mono PCM.\n", source, seconds, format);
+                code += &format!("pub static {}: [u8; {}] = [\n", name, converted.bytes.len());
+                code += &byte_lines(&converted.bytes);
+                code += "];\n";
+                code += &format!("pub const {}_SAMPLE_RATE: u32 = {};\n", name, sample_rate);
+            },
+            Details::File => {
+                code += &format!("/// `{}`, as it is.\n", source);
+                code += &format!("pub static {}: [u8; {}] = [\n", name, converted.bytes.len());
+                code += &byte_lines(&converted.bytes);
+                code += "];\n";
+            },
+        }
+        if let Some(section) = asset.section.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            // the section goes on the static, which starts after the doc comment
+            let start = code.rfind(&format!("pub static {}:", name)).unwrap_or(code.len());
+            code.insert_str(start, &format!("#[link_section = {:?}]\n", section));
+        }
+        names.push(name);
+    }
+    Ok(code)
+}
+
+/// The fingerprint a generated module was made from.
+fn module_fingerprint(module: &str) -> Option<&str> {
+    module.lines().find_map(|l| l.strip_prefix(FINGERPRINT_PREFIX))
+}
+
+/// Regenerate the module if an asset or its settings changed since it was written.
+/// Returns whether it was written. A project without assets is left alone.
+pub fn regenerate(assets: &[Asset], project_dir: &Path) -> Result<bool, EmbedError> {
+    if assets.is_empty() {
+        return Ok(false);
+    }
+    let sources = read_sources(assets, project_dir)?;
+    let path = project_dir.join("src").join(MODULE_FILE_NAME);
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    if module_fingerprint(&existing) == Some(fingerprint(assets, &sources).as_str()) {
+        return Ok(false);
+    }
+    let code = generate_module(assets, &sources)?;
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(&path, code)?;
+    Ok(true)
+}
+
+/// The state of the firmware assets window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct EmbedPanel {
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl EmbedPanel {
+
+    fn kind_editor(ui: &mut egui::Ui, i: usize, kind: &mut AssetKind) {
+        egui::ComboBox::from_id_source(("asset_kind", i)).selected_text(kind.to_string()).show_ui(ui, |ui| {
+            for option in AssetKind::all() {
+                let selected = std::mem::discriminant(kind) == std::mem::discriminant(&option);
+                if ui.selectable_label(selected, option.to_string()).clicked() && !selected {
+                    *kind = option;
+                }
+            }
+        });
+        let size = |ui: &mut egui::Ui, value: &mut Option<u32>, hint: &str| {
+            let mut text = value.map(|v| v.to_string()).unwrap_or_default();
+            if ui.add(egui::TextEdit::singleline(&mut text).desired_width(40.0).hint_text(hint)).changed() {
+                *value = text.trim().parse().ok().filter(|v| *v > 0);
+            }
+        };
+        match kind {
+            AssetKind::Image { format, width, height } => {
+                egui::ComboBox::from_id_source(("asset_format", i)).selected_text(format.to_string()).show_ui(ui, |ui| {
+                    for option in enum_iterator::all::<PixelFormat>() {
+                        ui.selectable_value(format, option, option.to_string());
+                    }
+                });
+                size(ui, width, "width");
+                ui.label("x");
+                size(ui, height, "height");
+            },
+            AssetKind::Audio { sample_rate, format } => {
+                ui.add(egui::DragValue::new(sample_rate).clamp_range(1000..=48000).suffix(" Hz"));
+                egui::ComboBox::from_id_source(("asset_format", i)).selected_text(format.to_string()).show_ui(ui, |ui| {
+                    for option in enum_iterator::all::<SampleFormat>() {
+                        ui.selectable_value(format, option, option.to_string());
+                    }
+                });
+            },
+            AssetKind::Font | AssetKind::Raw => (),
+        }
+    }
+
+    pub fn display(&mut self, ui: &mut egui::Ui, assets: &mut Vec<Asset>, project_dir: Option<&Path>) {
+        let Some(dir) = project_dir else {
+            ui.label("save the project to add assets to it");
+            return;
+        };
+        ui.label(format!("Assets are converted into src/{} before each build; add `mod assets;` to main.rs to use them.", MODULE_FILE_NAME));
+        let mut remove = None;
+        egui::Grid::new("embedded_assets").num_columns(5).striped(true).show(ui, |ui| {
+            ui.label("name");
+            ui.label("file");
+            ui.label("conversion");
+            ui.label("linker section");
+            ui.end_row();
+            for (i, asset) in assets.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut asset.name).desired_width(100.0));
+                ui.horizontal(|ui| {
+                    ui.label(asset.source.display().to_string());
+                    if ui.small_button("…").clicked() {
+                        if let Some(file) = rfd::FileDialog::new().set_directory(dir).pick_file() {
+                            match file.strip_prefix(dir) {
+                                Ok(relative) => asset.source = relative.to_path_buf(),
+                                Err(_) => self.status = Some(Err("assets have to be in the project directory".to_string())),
+                            }
+                        }
+                    }
+                });
+                ui.horizontal(|ui| Self::kind_editor(ui, i, &mut asset.kind));
+                let mut section = asset.section.clone().unwrap_or_default();
+                if ui.add(egui::TextEdit::singleline(&mut section).desired_width(100.0).hint_text("default")).changed() {
+                    asset.section = Some(section).filter(|s| !s.trim().is_empty());
+                }
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            assets.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("add asset").clicked() {
+                if let Some(file) = rfd::FileDialog::new().set_directory(dir).pick_file() {
+                    match file.strip_prefix(dir) {
+                        Ok(relative) => {
+                            let name = relative.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                            let extension = relative.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+                            let kind = match extension.as_str() {
+                                "wav" => AssetKind::all()[1].clone(),
+                                "ttf" | "otf" | "bdf" | "psf" => AssetKind::Font,
+                                "png" | "jpg" | "jpeg" | "gif" => AssetKind::default(),
+                                _ => AssetKind::Raw,
+                            };
+                            assets.push(Asset { name, source: relative.to_path_buf(), kind, section: None });
+                        },
+                        Err(_) => self.status = Some(Err("assets have to be in the project directory".to_string())),
+                    }
+                }
+            }
+            if ui.add_enabled(!assets.is_empty(), egui::Button::new("generate now")).clicked() {
+                self.status = Some(match read_sources(assets, dir).and_then(|sources| generate_module(assets, &sources)) {
+                    Ok(code) => fs::write(dir.join("src").join(MODULE_FILE_NAME), &code)
+                        .map(|_| format!("wrote src/{} ({} bytes of source)", MODULE_FILE_NAME, code.len()))
+                        .map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                });
+            }
+        });
+        match &self.status {
+            Some(Ok(done)) => { ui.label(done); },
+            Some(Err(e)) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            None => (),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Title: Iron Coder Project Module - Environment
+//! Description: The environment variables set for everything run for a project, i.e.
+//!   builds, loading onto the board and the commands run from the project. They're saved
+//!   in the project file. Secret ones, like API tokens, are hidden in the Ui and taken
+//!   out of the console output, so they don't end up in logs that are copied or saved.
+//!   Their values are saved in a file of their own, which is left out of the repository.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+/// What a secret is shown as in the console.
+pub const REDACTED: &str = "********";
+
+/// The file next to the project file that the secret values are saved in.
+pub const SECRETS_FILE_NAME: &str = ".ironcoder-secrets.toml";
+
+/// An environment variable.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct EnvVar {
+    pub key: String,
+    pub value: String,
+    /// Whether the value is hidden
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub secret: bool,
+}
+
+/// Whether a variable can be called `key` on every platform.
+pub fn valid_key(key: &str) -> bool {
+    !key.is_empty() && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Take the secrets out of some output.
+pub fn redact(output: &str, secrets: &[String]) -> String {
+    secrets.iter().filter(|s| !s.is_empty()).fold(output.to_string(), |output, secret| output.replace(secret.as_str(), REDACTED))
+}
+
+/// Read the secret values saved in a project's directory, by key. A project without
+/// any secrets doesn't have the file.
+pub fn load_secrets(project_dir: &Path) -> io::Result<BTreeMap<String, String>> {
+    match fs::read_to_string(project_dir.join(SECRETS_FILE_NAME)) {
+        Ok(contents) => toml::from_str(&contents).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Save the secret values in a project's directory, removing the file once there
+/// aren't any.
+pub fn save_secrets(project_dir: &Path, secrets: &BTreeMap<String, String>) -> io::Result<()> {
+    let path = project_dir.join(SECRETS_FILE_NAME);
+    if secrets.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    fs::write(path, toml::to_string(secrets).map_err(io::Error::other)?)
+}
+
+/// The environment variables of a project, in the order they're set.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ProjectEnvironment {
+    pub vars: Vec<EnvVar>,
+}
+
+impl ProjectEnvironment {
+
+    /// Check the keys are usable and set once.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, var) in self.vars.iter().enumerate() {
+            if !valid_key(&var.key) {
+                return Err(format!("\"{}\" isn't a variable name: use letters, digits and _", var.key));
+            }
+            if self.vars[..i].iter().any(|v| v.key == var.key) {
+                return Err(format!("{} is set twice", var.key));
+            }
+        }
+        Ok(())
+    }
+
+    /// The variables to set, leaving out the ones with unusable keys. A key that's set
+    /// twice gets the last value.
+    pub fn vars(&self) -> Vec<(String, String)> {
+        self.vars.iter().filter(|v| valid_key(&v.key)).map(|v| (v.key.clone(), v.value.clone())).collect()
+    }
+
+    /// The values that are kept out of the console.
+    pub fn secrets(&self) -> Vec<String> {
+        self.vars.iter().filter(|v| v.secret && !v.value.is_empty()).map(|v| v.value.clone()).collect()
+    }
+
+    /// Take the secret values out, leaving them empty, so the rest can be saved in the
+    /// project file.
+    pub fn take_secrets(&mut self) -> BTreeMap<String, String> {
+        self.vars.iter_mut().filter(|v| v.secret).map(|v| (v.key.clone(), std::mem::take(&mut v.value))).collect()
+    }
+
+    /// Put back the secret values, by key.
+    pub fn restore_secrets(&mut self, secrets: &BTreeMap<String, String>) {
+        for var in self.vars.iter_mut().filter(|v| v.secret) {
+            if let Some(value) = secrets.get(&var.key) {
+                var.value = value.clone();
+            }
+        }
+    }
+
+    /// Set the variables for a command.
+    pub fn apply(&self, cmd: duct::Expression) -> duct::Expression {
+        self.vars().into_iter().fold(cmd, |cmd, (key, value)| cmd.env(key, value))
+    }
+
+    /// Show the variables in the Ui, to be edited.
+    pub fn display(&mut self, ui: &mut egui::Ui) {
+        let mut removed = None;
+        egui::Grid::new("project_environment").num_columns(4).show(ui, |ui| {
+            for (i, var) in self.vars.iter_mut().enumerate() {
+                let valid = valid_key(&var.key);
+                let mut key = egui::TextEdit::singleline(&mut var.key).hint_text("NAME").desired_width(120.0);
+                if !valid {
+                    key = key.text_color(ui.visuals().error_fg_color);
+                }
+                ui.add(key);
+                ui.add(egui::TextEdit::singleline(&mut var.value).password(var.secret).hint_text("value").desired_width(160.0));
+                ui.checkbox(&mut var.secret, "secret").on_hover_text("hide the value, and keep it out of the console");
+                if ui.small_button("remove").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = removed {
+            self.vars.remove(i);
+        }
+        if ui.button("add variable").clicked() {
+            self.vars.push(EnvVar::default());
+        }
+        if let Err(e) = self.validate() {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Title: Iron Coder Project Module - Examples
+//! Description: The examples that come with a board, opened in the code editor or used
+//!   to start a project. A project made from an example gets a copy of it, with the
+//!   package named after the project and the board as its main board.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use crate::board::Board;
+use crate::board::examples::MANIFEST_FILE_NAME;
+use crate::project::{Project, PROJECT_FILE_NAME};
+
+/// What isn't copied from the top of an example: its build output, its own project file,
+/// and the manifest that describes it in the board widget. Files by these names further
+/// down are the example's own, and are copied.
+const SKIPPED: [&str; 3] = ["target", PROJECT_FILE_NAME, MANIFEST_FILE_NAME];
+
+/// The file to open to see an example: its `src/main.rs` or `src/lib.rs`, or else the
+/// first Rust file in it. An example that's a single file is itself.
+pub fn example_source(example: &Path) -> Option<PathBuf> {
+    if example.is_file() {
+        return Some(example.to_path_buf());
+    }
+    let src = example.join("src");
+    for candidate in [src.join("main.rs"), src.join("lib.rs")] {
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+    let mut sources: Vec<PathBuf> = fs::read_dir(&src).ok()?.flatten().map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "rs"))
+        .collect();
+    sources.sort();
+    sources.into_iter().next()
+}
+
+/// The name a project gets a package by, i.e. "NeoPixel Demo" becomes "neopixel-demo".
+pub fn package_name(project_name: &str) -> String {
+    let name: String = project_name.trim().to_lowercase().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' })
+        .collect();
+    let name = name.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    match name.chars().next() {
+        None => "project".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("project-{}", name),
+        Some(_) => name,
+    }
+}
+
+/// Name the package in a Cargo manifest, keeping the rest of it as it is.
+pub fn rename_package(manifest: &str, name: &str) -> Result<String, String> {
+    let mut doc: toml_edit::DocumentMut = manifest.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+    let package = doc.get_mut("package").and_then(|p| p.as_table_like_mut()).ok_or("Cargo.toml has no [package]")?;
+    package.insert("name", toml_edit::value(name));
+    Ok(doc.to_string())
+}
+
+/// Copy a directory, leaving out the entries directly in it named in `skipped`.
+fn copy_dir(from: &Path, to: &Path, skipped: &[&str], copied: &mut usize) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if skipped.iter().any(|skipped| entry.file_name() == *skipped) {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, &[], copied)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+            *copied += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Copy an example into a new project directory, which has to be empty if it's there.
+/// Returns how many files were copied.
+pub fn copy_example(example: &Path, destination: &Path) -> io::Result<usize> {
+    if destination.exists() && fs::read_dir(destination)?.next().is_some() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} isn't empty", destination.display())));
+    }
+    let mut copied = 0;
+    if example.is_file() {
+        // a single file example becomes the project's main.rs
+        fs::create_dir_all(destination.join("src"))?;
+        fs::copy(example, destination.join("src").join("main.rs"))?;
+        copied += 1;
+    } else {
+        copy_dir(example, destination, &SKIPPED, &mut copied)?;
+    }
+    Ok(copied)
+}
+
+impl Project {
+
+    /// Make a project in `destination` from one of `board`'s examples, with the board as
+    /// its main board, and open the example's source. The project is named after the
+    /// folder it's in.
+    pub fn new_from_example(&mut self, board: &Board, example: &Path, destination: &Path) -> io::Result<()> {
+        let copied = copy_example(example, destination)?;
+        self.name = crate::paths::display_name(destination).to_string();
+        let manifest = destination.join("Cargo.toml");
+        if manifest.exists() {
+            let renamed = rename_package(&fs::read_to_string(&manifest)?, &package_name(&self.name))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fs::write(&manifest, renamed)?;
+        }
+        self.system.main_board = None;
+        self.system.peripheral_boards.clear();
+        self.add_board(board.clone());
+        self.save_to(destination)?;
+        let example_name = crate::paths::display_name(example);
+        info!("made project <{}> from the {} example of {}", self.name, example_name, board.get_name());
+        self.console.log(&format!("copied {} files from the {} example of {}", copied, example_name, board.get_name()));
+        if let Some(source) = example_source(destination) {
+            self.code_editor.load_from_file(&source)?;
+        }
+        Ok(())
+    }
+}
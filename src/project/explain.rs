@@ -0,0 +1,203 @@
+//! Title: Iron Coder Project Module - Explain
+//! Description: Curated explanations of the errors embedded Rust projects commonly run
+//!   into (a missing critical-section implementation or memory.x, allocators in no_std,
+//!   probes that can't attach), and fixes that can be applied to the project in a click.
+//!   The explanations are kept in `assets/knowledge/errors.toml`, so adding one doesn't
+//!   take any code.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::{Serialize, Deserialize};
+
+/// The knowledge base that ships with Iron Coder.
+const BUILTIN: &str = include_str!("../../assets/knowledge/errors.toml");
+
+/// A change that fixes, or helps fix, an error.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Fix {
+    /// Add a dependency to the project's Cargo.toml, or the features to it if it's there
+    AddDependency {
+        name: String,
+        version: String,
+        #[serde(default)]
+        features: Vec<String>,
+        label: Option<String>,
+    },
+    /// Create a file in the project directory, if it doesn't exist
+    CreateFile { path: String, contents: String, label: Option<String> },
+    /// Open documentation in the browser
+    OpenUrl { url: String, label: Option<String> },
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fix::AddDependency { label: Some(label), .. } | Fix::CreateFile { label: Some(label), .. }
+            | Fix::OpenUrl { label: Some(label), .. } => write!(f, "{}", label),
+            Fix::AddDependency { name, features, .. } if features.is_empty() => write!(f, "add {}", name),
+            Fix::AddDependency { name, features, .. } => write!(f, "add {} with {}", name, features.join(", ")),
+            Fix::CreateFile { path, .. } => write!(f, "create {}", path),
+            Fix::OpenUrl { .. } => write!(f, "read more"),
+        }
+    }
+}
+
+impl Fix {
+
+    /// Apply the fix to a project. Returns what was done. Urls are left to the caller.
+    pub fn apply(&self, project_dir: &Path) -> Result<String, String> {
+        match self {
+            Fix::AddDependency { name, version, features, .. } => {
+                let path = project_dir.join("Cargo.toml");
+                let manifest = fs::read_to_string(&path).map_err(|e| format!("couldn't read Cargo.toml: {}", e))?;
+                let updated = add_dependency(&manifest, name, version, features)?;
+                fs::write(&path, updated).map_err(|e| format!("couldn't write Cargo.toml: {}", e))?;
+                Ok(format!("added {} to Cargo.toml", name))
+            },
+            Fix::CreateFile { path, contents, .. } => {
+                let target = project_dir.join(path);
+                if target.exists() {
+                    return Err(format!("{} already exists", path));
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&target, contents.trim_start()).map_err(|e| format!("couldn't write {}: {}", path, e))?;
+                Ok(format!("created {}", path))
+            },
+            Fix::OpenUrl { url, .. } => Ok(url.clone()),
+        }
+    }
+}
+
+/// Add a dependency to a Cargo manifest, keeping its formatting. A dependency that's
+/// already there keeps its version and gets any features it's missing.
+pub fn add_dependency(manifest: &str, name: &str, version: &str, features: &[String]) -> Result<String, String> {
+    let mut doc: toml_edit::DocumentMut = manifest.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+    let dependencies = doc.entry("dependencies").or_insert(toml_edit::table())
+        .as_table_like_mut().ok_or("[dependencies] in Cargo.toml isn't a table")?;
+    match dependencies.get_mut(name) {
+        None => {
+            let value = match features.is_empty() {
+                true => toml_edit::value(version),
+                false => {
+                    let mut table = toml_edit::InlineTable::new();
+                    table.insert("version", version.into());
+                    table.insert("features", features.iter().collect::<toml_edit::Array>().into());
+                    toml_edit::value(table)
+                },
+            };
+            dependencies.insert(name, value);
+        },
+        Some(existing) => {
+            if features.is_empty() {
+                return Ok(doc.to_string());
+            }
+            // `name = "1.0"` has to become a table to take features
+            if let Some(version) = existing.as_str().map(str::to_string) {
+                let mut table = toml_edit::InlineTable::new();
+                table.insert("version", version.into());
+                *existing = toml_edit::value(table);
+            }
+            let table = existing.as_table_like_mut().ok_or(format!("the {} dependency isn't a version or table", name))?;
+            let list = table.entry("features").or_insert(toml_edit::value(toml_edit::Array::new()))
+                .as_array_mut().ok_or(format!("the features of {} aren't a list", name))?;
+            for feature in features {
+                if !list.iter().any(|f| f.as_str() == Some(feature)) {
+                    list.push(feature.as_str());
+                }
+            }
+        },
+    }
+    Ok(doc.to_string())
+}
+
+/// An error that can be explained.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub id: String,
+    pub title: String,
+    /// Text that shows the error, any one of which identifies it
+    pub patterns: Vec<String>,
+    pub explanation: String,
+    #[serde(default)]
+    pub fixes: Vec<Fix>,
+}
+
+impl Entry {
+    pub fn matches(&self, line: &str) -> bool {
+        let line = line.to_lowercase();
+        self.patterns.iter().any(|p| line.contains(&p.to_lowercase()))
+    }
+}
+
+/// The errors Iron Coder can explain.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KnowledgeBase {
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<Entry>,
+}
+
+impl KnowledgeBase {
+
+    /// Parse and check a knowledge base.
+    pub fn from_toml_str(toml: &str) -> Result<Self, String> {
+        let knowledge: Self = toml::from_str(toml).map_err(|e| e.to_string())?;
+        for (i, entry) in knowledge.entries.iter().enumerate() {
+            if entry.id.is_empty() || knowledge.entries[..i].iter().any(|e| e.id == entry.id) {
+                return Err(format!("entry {} needs an id of its own", i + 1));
+            }
+            if entry.patterns.is_empty() || entry.patterns.iter().any(|p| p.trim().len() < 4) {
+                return Err(format!("{} needs patterns specific enough to match only its error", entry.id));
+            }
+            for fix in entry.fixes.iter() {
+                let valid = match fix {
+                    Fix::AddDependency { name, version, .. } => !name.is_empty() && !version.is_empty(),
+                    Fix::CreateFile { path, .. } => {
+                        let path = Path::new(path);
+                        path.is_relative() && path.components().all(|c| matches!(c, std::path::Component::Normal(_)))
+                    },
+                    Fix::OpenUrl { url, .. } => url.starts_with("https://"),
+                };
+                if !valid {
+                    return Err(format!("{} has a fix that can't be applied: {:?}", entry.id, fix));
+                }
+            }
+        }
+        Ok(knowledge)
+    }
+
+    /// The knowledge base that ships with Iron Coder.
+    pub fn builtin() -> &'static Self {
+        static KNOWLEDGE: OnceLock<KnowledgeBase> = OnceLock::new();
+        KNOWLEDGE.get_or_init(|| Self::from_toml_str(BUILTIN).expect("the built-in knowledge base is checked by its tests"))
+    }
+
+    /// The explanation of the error on a line of output, if there is one.
+    pub fn explain(&self, line: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.matches(line))
+    }
+}
+
+/// An error that was explained, and the line of output that showed it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explained {
+    pub line: String,
+    pub entry: Entry,
+}
+
+/// Explain the lines of some output, once for each kind of error.
+pub fn explain_output(knowledge: &KnowledgeBase, output: &str, explained: &mut Vec<Explained>) {
+    for line in output.lines() {
+        if let Some(entry) = knowledge.explain(line) {
+            if !explained.iter().any(|e| e.entry.id == entry.id) {
+                explained.push(Explained { line: line.trim().to_string(), entry: entry.clone() });
+            }
+        }
+    }
+}
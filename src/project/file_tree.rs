@@ -0,0 +1,731 @@
+//! Title: Iron Coder Project Module - File Tree
+//! Description: The project's files as a tree in the sidebar. Entries can be selected
+//!   several at a time, and created, renamed, deleted and moved from their context menus,
+//!   the keyboard, or by dragging them onto a folder. Rust files under a crate keep the
+//!   `mod` declarations of their parent modules in step with them. Deleted entries go to
+//!   the project's trash, so the delete can be undone.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use crate::app::icons::IconSet;
+use crate::paths;
+use crate::project::trash::{self, Retention, Trash};
+
+/// Folders that aren't shown, since they aren't edited by hand.
+const HIDDEN: [&str; 3] = [".git", "target", trash::TRASH_DIR];
+
+/// Something done to the project's files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileAction {
+    NewFile { dir: PathBuf, name: String },
+    NewFolder { dir: PathBuf, name: String },
+    Rename { path: PathBuf, name: String },
+    Delete(Vec<PathBuf>),
+    Move { paths: Vec<PathBuf>, to: PathBuf },
+}
+
+/// What an action did to the files, so open editor tabs can follow them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Applied {
+    pub created: Vec<PathBuf>,
+    /// Where each moved or renamed file or folder was, and where it is now
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    pub deleted: Vec<PathBuf>,
+}
+
+/// A name for a new or renamed file or folder, which has to stay in its folder.
+fn check_name(name: &str) -> io::Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("\"{}\" isn't a file name", name)));
+    }
+    Ok(name)
+}
+
+/// The name of the module a file or folder is, i.e. "sensors" for `sensors.rs` or a
+/// `sensors` folder with a `mod.rs` in it. Crate roots and build scripts aren't modules.
+pub fn module_name(path: &Path, is_dir: bool) -> Option<String> {
+    let name = match is_dir {
+        true => path.file_name()?.to_str()?,
+        false => match path.extension()?.to_str()? {
+            "rs" => path.file_stem()?.to_str()?,
+            _ => return None,
+        },
+    };
+    let is_ident = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let special = ["main", "lib", "mod", "build"].contains(&name);
+    (is_ident && !special).then(|| name.to_string())
+}
+
+/// Whether a file or folder is a module, as it is on disk.
+pub fn existing_module(path: &Path) -> Option<String> {
+    match path.is_dir() {
+        true => path.join("mod.rs").exists().then(|| module_name(path, true)).flatten(),
+        false => module_name(path, false),
+    }
+}
+
+/// The file that declares the modules in `dir`: the crate root, its `mod.rs`, or the
+/// file of the same name next to it.
+pub fn declaring_file(dir: &Path) -> Option<PathBuf> {
+    let candidates = [dir.join("main.rs"), dir.join("lib.rs"), dir.join("mod.rs"), dir.with_extension("rs")];
+    candidates.into_iter().find(|f| f.is_file())
+}
+
+/// The module a line of Rust declares, and with what visibility, i.e. ("pub(crate) ",
+/// "sensors") for `pub(crate) mod sensors;`. Modules with bodies aren't files, so they
+/// don't count.
+fn declaration(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let rest = match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => rest.split_once(')')?.1.trim_start(),
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+        _ => line,
+    };
+    let name = rest.strip_prefix("mod ")?.trim_start().strip_suffix(';')?.trim_end();
+    Some((&line[..line.len() - rest.len()], name))
+}
+
+fn declares(line: &str, name: &str) -> bool {
+    declaration(line).is_some_and(|(_, declared)| declared == name)
+}
+
+/// Add `mod name;` to a module, after its other module declarations, or its inner docs
+/// and attributes if it has none. Does nothing if it's there already.
+pub fn add_declaration(file: &Path, name: &str, visibility: &str) -> io::Result<()> {
+    let code = fs::read_to_string(file)?;
+    if code.lines().any(|l| declares(l, name)) {
+        return Ok(());
+    }
+    let line = format!("{}mod {};", visibility, name);
+    let mut lines: Vec<String> = code.lines().map(str::to_string).collect();
+    match lines.iter().rposition(|l| declaration(l).is_some()) {
+        Some(last) => lines.insert(last + 1, line),
+        None => {
+            let header = lines.iter().take_while(|l| l.starts_with("//!") || l.starts_with("#![") || l.trim().is_empty()).count();
+            // keep a blank line between the header and the rest
+            let at = lines[..header].iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+            let mut block = vec![line];
+            if at > 0 {
+                block.insert(0, String::new());
+            }
+            if lines.get(at).is_some_and(|l| !l.trim().is_empty()) {
+                block.push(String::new());
+            }
+            lines.splice(at..at, block);
+        },
+    }
+    fs::write(file, lines.join("\n") + "\n")
+}
+
+/// Take `mod name;` out of a module. Returns the visibility it had, if it was there.
+pub fn remove_declaration(file: &Path, name: &str) -> io::Result<Option<String>> {
+    let code = fs::read_to_string(file)?;
+    let Some(visibility) = code.lines().find(|l| declares(l, name)).and_then(declaration).map(|(v, _)| v.to_string()) else {
+        return Ok(None);
+    };
+    let lines: Vec<&str> = code.lines().filter(|l| !declares(l, name)).collect();
+    fs::write(file, lines.join("\n") + "\n")?;
+    Ok(Some(visibility))
+}
+
+/// Change `mod from;` to `mod to;` in a module. Returns whether it was there.
+pub fn rename_declaration(file: &Path, from: &str, to: &str) -> io::Result<bool> {
+    let code = fs::read_to_string(file)?;
+    let mut found = false;
+    let lines: Vec<String> = code.lines().map(|l| match declaration(l).filter(|(_, name)| *name == from) {
+        Some((visibility, _)) => {
+            found = true;
+            let indent = &l[..l.len() - l.trim_start().len()];
+            format!("{}{}mod {};", indent, visibility, to)
+        },
+        None => l.to_string(),
+    }).collect();
+    if found {
+        fs::write(file, lines.join("\n") + "\n")?;
+    }
+    Ok(found)
+}
+
+/// Leave out the paths inside other paths of the list, which go along with them.
+pub fn outermost(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = paths.iter().filter_map(|p| p.canonicalize().ok()).collect();
+    paths.sort();
+    paths.dedup();
+    let all = paths.clone();
+    paths.retain(|p| !all.iter().any(|other| other != p && p.starts_with(other)));
+    paths
+}
+
+/// Do something to the project's files.
+pub fn apply(action: &FileAction) -> io::Result<Applied> {
+    let mut applied = Applied::default();
+    match action {
+        FileAction::NewFile { dir, name } => {
+            let path = dir.canonicalize()?.join(check_name(name)?);
+            fs::File::create_new(&path)?;
+            if let (Some(module), Some(parent)) = (module_name(&path, false), declaring_file(dir)) {
+                add_declaration(&parent, &module, "")?;
+            }
+            applied.created.push(path);
+        },
+        FileAction::NewFolder { dir, name } => {
+            let path = dir.canonicalize()?.join(check_name(name)?);
+            fs::create_dir(&path)?;
+            applied.created.push(path);
+        },
+        FileAction::Rename { path, name } => {
+            let path = path.canonicalize()?;
+            let to = path.with_file_name(check_name(name)?);
+            if to == path {
+                return Ok(applied);
+            }
+            if to.exists() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("there's already a {}", to.display())));
+            }
+            let (from_module, is_dir) = (existing_module(&path), path.is_dir());
+            fs::rename(&path, &to)?;
+            let to_module = match is_dir {
+                true => existing_module(&to),
+                false => module_name(&to, false),
+            };
+            if let Some(parent) = to.parent().and_then(declaring_file) {
+                match (from_module, to_module) {
+                    (Some(from), Some(to)) => {
+                        if !rename_declaration(&parent, &from, &to)? {
+                            add_declaration(&parent, &to, "")?;
+                        }
+                    },
+                    (Some(from), None) => {
+                        remove_declaration(&parent, &from)?;
+                    },
+                    (None, Some(to)) => add_declaration(&parent, &to, "")?,
+                    (None, None) => (),
+                }
+            }
+            applied.moved.push((path, to));
+        },
+        FileAction::Delete(paths) => {
+            for path in outermost(paths) {
+                let module = existing_module(&path);
+                match path.is_dir() {
+                    true => fs::remove_dir_all(&path)?,
+                    false => fs::remove_file(&path)?,
+                }
+                if let (Some(module), Some(parent)) = (module, path.parent().and_then(declaring_file)) {
+                    remove_declaration(&parent, &module)?;
+                }
+                applied.deleted.push(path);
+            }
+        },
+        FileAction::Move { paths, to } => {
+            let to = to.canonicalize()?;
+            if !to.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} isn't a folder", to.display())));
+            }
+            for path in outermost(paths) {
+                let dest = to.join(path.file_name().unwrap_or_default());
+                if dest == path {
+                    continue;
+                }
+                if to.starts_with(&path) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't move {} into itself", path.display())));
+                }
+                if dest.exists() {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("there's already a {}", dest.display())));
+                }
+                let module = existing_module(&path);
+                fs::rename(&path, &dest)?;
+                if let Some(module) = module {
+                    let mut visibility = None;
+                    if let Some(parent) = path.parent().and_then(declaring_file) {
+                        visibility = remove_declaration(&parent, &module)?;
+                    }
+                    if let Some(parent) = declaring_file(&to) {
+                        add_declaration(&parent, &module, visibility.as_deref().unwrap_or_default())?;
+                    }
+                }
+                applied.moved.push((path, dest));
+            }
+        },
+    }
+    info!("file tree: {:?}", action);
+    Ok(applied)
+}
+
+/// A name being typed in for an entry.
+#[derive(Clone, Debug, PartialEq)]
+enum Naming {
+    /// A new file or folder, in the folder
+    New { dir: PathBuf, folder: bool },
+    Rename(PathBuf),
+}
+
+/// What happened in the tree that the rest of the project needs to know about.
+#[derive(Debug)]
+pub enum TreeEvent {
+    Open(PathBuf),
+    Changed(Applied),
+}
+
+/// The state of the project file tree.
+#[derive(Default)]
+pub struct FileTree {
+    selected: BTreeSet<PathBuf>,
+    /// Where shift-clicks select from
+    anchor: Option<PathBuf>,
+    /// The entries in the order they were last shown, for shift-clicks
+    shown: Vec<PathBuf>,
+    naming: Option<(Naming, String)>,
+    /// What's waiting for the delete to be confirmed, and whether git tracks it
+    deleting: Vec<(PathBuf, bool)>,
+    /// The trash of the project shown, where deletes go
+    trash: Option<Trash>,
+    error: Option<String>,
+    /// The event of the last action, given out at the end of the frame
+    event: Option<TreeEvent>,
+}
+
+impl FileTree {
+
+    pub fn selected(&self) -> Vec<PathBuf> {
+        self.selected.iter().cloned().collect()
+    }
+
+    pub fn is_selected(&self, path: &Path) -> bool {
+        self.selected.contains(path)
+    }
+
+    /// Select an entry like a click does: ctrl adds or removes it from the selection, and
+    /// shift selects everything shown between it and the last entry clicked.
+    pub fn click(&mut self, path: &Path, modifiers: egui::Modifiers) {
+        let position = |p: &Path| self.shown.iter().position(|s| s == p);
+        if modifiers.shift {
+            if let Some((a, b)) = self.anchor.as_deref().and_then(position).zip(position(path)) {
+                let range = self.shown[a.min(b)..=a.max(b)].to_vec();
+                self.selected = range.into_iter().collect();
+                return;
+            }
+        }
+        if modifiers.command || modifiers.ctrl {
+            if !self.selected.remove(path) {
+                self.selected.insert(path.to_path_buf());
+            }
+        } else {
+            self.selected = BTreeSet::from([path.to_path_buf()]);
+        }
+        self.anchor = Some(path.to_path_buf());
+    }
+
+    /// Set the entries in the order they're shown, as `display` does each frame.
+    pub fn set_shown(&mut self, shown: Vec<PathBuf>) {
+        self.shown = shown;
+    }
+
+    /// Start typing the name of a new file or folder in `dir`.
+    pub fn start_new(&mut self, dir: &Path, folder: bool) {
+        let dir = dir.canonicalize().unwrap_or(dir.to_path_buf());
+        self.naming = Some((Naming::New { dir, folder }, String::new()));
+    }
+
+    /// The folder new entries go in: the selected folder, the folder of the selected
+    /// file, or else `root`.
+    pub fn target_dir(&self, root: &Path) -> PathBuf {
+        match self.selected.iter().next() {
+            Some(path) if self.selected.len() == 1 && path.is_dir() => path.clone(),
+            Some(path) if self.selected.len() == 1 => path.parent().unwrap_or(root).to_path_buf(),
+            _ => root.to_path_buf(),
+        }
+    }
+
+    /// The project's trash, once the tree has been shown.
+    pub fn trash(&self) -> Option<&Trash> {
+        self.trash.as_ref()
+    }
+
+    /// Use the trash of the project in `root` for deletes, clearing out what it no
+    /// longer keeps.
+    pub fn open_trash(&mut self, root: &Path, retention: &Retention) {
+        if self.trash.as_ref().is_some_and(|t| t.root() == root) {
+            return;
+        }
+        let mut trash = Trash::open(root);
+        if let Err(e) = trash.purge_now(retention) {
+            warn!("couldn't clear out the trash: {:?}", e);
+        }
+        self.trash = Some(trash);
+    }
+
+    /// Ask to delete the selected entries.
+    fn start_delete(&mut self) {
+        self.deleting = self.selected().into_iter().map(|p| {
+            let tracked = trash::is_tracked(&p);
+            (p, tracked)
+        }).collect();
+    }
+
+    /// Do an action, keeping the selection on what's still there. Deletes go to the
+    /// trash if there is one.
+    pub fn run(&mut self, action: FileAction) {
+        let result = match (&action, self.trash.as_mut()) {
+            (FileAction::Delete(paths), Some(trash)) => trash.put(paths).map(|deleted| Applied { deleted, ..Default::default() }),
+            _ => apply(&action),
+        };
+        self.finish(result);
+    }
+
+    /// Put back everything from the last delete.
+    pub fn undo_delete(&mut self) {
+        if let Some(trash) = self.trash.as_mut() {
+            let result = trash.undo().map(|created| Applied { created, ..Default::default() });
+            self.finish(result);
+        }
+    }
+
+    /// Put one thing in the trash back.
+    pub fn restore(&mut self, id: u64) {
+        if let Some(trash) = self.trash.as_mut() {
+            let result = trash.restore(id).map(|path| Applied { created: vec![path], ..Default::default() });
+            self.finish(result);
+        }
+    }
+
+    fn finish(&mut self, result: io::Result<Applied>) {
+        match result {
+            Ok(applied) => {
+                self.error = None;
+                for path in applied.deleted.iter() {
+                    self.selected.retain(|s| !s.starts_with(path));
+                }
+                for (from, to) in applied.moved.iter() {
+                    self.selected = self.selected.iter().map(|s| match s.strip_prefix(from) {
+                        Ok(rest) if rest.as_os_str().is_empty() => to.clone(),
+                        Ok(rest) => to.join(rest),
+                        Err(_) => s.clone(),
+                    }).collect();
+                }
+                if !applied.created.is_empty() {
+                    self.selected = applied.created.iter().cloned().collect();
+                }
+                self.event = Some(TreeEvent::Changed(applied));
+            },
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    /// Show the files under `root`, with deletes kept in its trash for as long as
+    /// `retention` says. Returns a file to open, or what was changed.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, root: &Path, retention: &Retention) -> Option<TreeEvent> {
+        // the same paths whether the project location is canonical or not
+        let root = &root.canonicalize().unwrap_or(root.to_path_buf());
+        self.open_trash(root, retention);
+        let icons: Arc<IconSet> = ctx.data_mut(|data| {
+            data.get_temp("icons".into()).expect("error loading shared icons!")
+        });
+        ui.horizontal(|ui| {
+            if ui.button("+ file").clicked() {
+                self.start_new(&self.target_dir(root), false);
+            }
+            if ui.button("+ folder").clicked() {
+                self.start_new(&self.target_dir(root), true);
+            }
+            self.display_trash_menu(ui);
+            if self.selected.len() > 1 {
+                ui.weak(format!("{} selected", self.selected.len()));
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        let mut shown = vec![];
+        let tree = egui::ScrollArea::vertical().id_source("project_file_tree").show(ui, |ui| {
+            self.display_dir(&icons, ui, root, &mut shown);
+        });
+        self.shown = shown;
+        // a drop anywhere else moves to the top of the project
+        let rest = ui.interact(tree.inner_rect, ui.id().with("file_tree_background"), egui::Sense::hover());
+        if let Some(paths) = rest.dnd_release_payload::<Vec<PathBuf>>() {
+            self.run(FileAction::Move { paths: (*paths).clone(), to: root.to_path_buf() });
+        }
+        self.display_delete_confirmation(ctx);
+        // F2 renames and delete deletes, unless something else is being typed in
+        let typing = ctx.memory(|m| m.focused().is_some());
+        if ui.ui_contains_pointer() && !typing && self.naming.is_none() {
+            let (rename, delete, undo) = ctx.input(|i| (
+                i.key_pressed(egui::Key::F2),
+                i.key_pressed(egui::Key::Delete),
+                i.modifiers.command && i.key_pressed(egui::Key::Z),
+            ));
+            if rename && self.selected.len() == 1 {
+                self.start_rename();
+            }
+            if delete && !self.selected.is_empty() {
+                self.start_delete();
+            }
+            if undo {
+                self.undo_delete();
+            }
+        }
+        self.event.take()
+    }
+
+    fn start_rename(&mut self) {
+        if let Some(path) = self.selected.iter().next().cloned() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            self.naming = Some((Naming::Rename(path), name));
+        }
+    }
+
+    fn is_naming_new_in(&self, dir: &Path) -> bool {
+        matches!(&self.naming, Some((Naming::New { dir: d, .. }, _)) if d == dir)
+    }
+
+    fn is_renaming(&self, path: &Path) -> bool {
+        matches!(&self.naming, Some((Naming::Rename(p), _)) if p == path)
+    }
+
+    /// The text box for the name being typed in. Enter does the action, and clicking
+    /// away or escape leaves it.
+    fn display_naming(&mut self, ui: &mut egui::Ui) {
+        let Some((naming, name)) = &mut self.naming else {
+            return;
+        };
+        let hint = match naming {
+            Naming::New { folder: true, .. } => "folder name",
+            Naming::New { folder: false, .. } => "file name, i.e. sensors.rs",
+            Naming::Rename(_) => "new name",
+        };
+        let response = ui.add(egui::TextEdit::singleline(name).hint_text(hint));
+        if !response.has_focus() && !response.lost_focus() {
+            response.request_focus();
+        }
+        if response.lost_focus() {
+            let (naming, name) = self.naming.take().unwrap();
+            if ui.input(|i| i.key_pressed(egui::Key::Enter)) && !name.trim().is_empty() {
+                self.run(match naming {
+                    Naming::New { dir, folder: true } => FileAction::NewFolder { dir, name },
+                    Naming::New { dir, folder: false } => FileAction::NewFile { dir, name },
+                    Naming::Rename(path) => FileAction::Rename { path, name },
+                });
+            }
+        }
+    }
+
+    /// The entries of a folder, folders first, then by name.
+    fn entries(dir: &Path) -> Vec<PathBuf> {
+        let Ok(entries) = dir.read_dir() else {
+            return vec![];
+        };
+        let mut entries: Vec<PathBuf> = entries.flatten()
+            .map(|e| e.path())
+            .filter(|p| !(p.is_dir() && HIDDEN.iter().any(|h| p.ends_with(h))))
+            .collect();
+        entries.sort_by_key(|p| (!p.is_dir(), p.file_name().map(|n| n.to_ascii_lowercase())));
+        entries
+    }
+
+    fn display_dir(&mut self, icons: &IconSet, ui: &mut egui::Ui, dir: &Path, shown: &mut Vec<PathBuf>) {
+        if self.is_naming_new_in(dir) {
+            self.display_naming(ui);
+        }
+        for path in Self::entries(dir) {
+            shown.push(path.clone());
+            if path.is_dir() {
+                let id = ui.make_persistent_id(("file_tree", &path));
+                let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false);
+                // open a folder when something is added to it
+                if self.is_naming_new_in(&path) {
+                    state.set_open(true);
+                }
+                let icon = match state.is_open() {
+                    true => "folder_open_icon",
+                    false => "folder_closed_icon",
+                };
+                state.show_header(ui, |ui| {
+                    self.display_entry(icons, ui, &path, icon);
+                }).body(|ui| {
+                    self.display_dir(icons, ui, &path, shown);
+                });
+            } else {
+                self.display_entry(icons, ui, &path, "file_icon");
+            }
+        }
+    }
+
+    /// One file or folder: select it, open it, drag it, drop things on it, or use its menu.
+    fn display_entry(&mut self, icons: &IconSet, ui: &mut egui::Ui, path: &Path, icon: &str) {
+        if self.is_renaming(path) {
+            self.display_naming(ui);
+            return;
+        }
+        let is_dir = path.is_dir();
+        let text = egui::RichText::new(paths::display_name(path));
+        let button = egui::Button::image_and_text(icons.get(icon).unwrap().clone(), text)
+            .frame(false)
+            .selected(self.is_selected(path))
+            .sense(egui::Sense::click_and_drag());
+        let response = ui.add(button);
+        if response.clicked() {
+            let modifiers = ui.input(|i| i.modifiers);
+            self.click(path, modifiers);
+            // only a plain click opens a file
+            if !(is_dir || modifiers.shift || modifiers.command || modifiers.ctrl) {
+                self.event = Some(TreeEvent::Open(path.to_path_buf()));
+            }
+        }
+        // dragging a selected entry drags the whole selection
+        if response.drag_started() {
+            if !self.is_selected(path) {
+                self.selected = BTreeSet::from([path.to_path_buf()]);
+            }
+            response.dnd_set_drag_payload(self.selected());
+        }
+        // things dropped on a file go in its folder
+        if is_dir && response.dnd_hover_payload::<Vec<PathBuf>>().is_some() {
+            ui.painter().rect_stroke(response.rect, 2.0, ui.visuals().selection.stroke);
+        }
+        if let Some(paths) = response.dnd_release_payload::<Vec<PathBuf>>() {
+            let to = if is_dir { path } else { path.parent().unwrap_or(path) };
+            self.run(FileAction::Move { paths: (*paths).clone(), to: to.to_path_buf() });
+        }
+        response.context_menu(|ui| {
+            if !self.is_selected(path) {
+                self.click(path, egui::Modifiers::NONE);
+            }
+            let dir = match is_dir {
+                true => path.to_path_buf(),
+                false => path.parent().unwrap_or(path).to_path_buf(),
+            };
+            if ui.button("new file").clicked() {
+                self.start_new(&dir, false);
+                ui.close_menu();
+            }
+            if ui.button("new folder").clicked() {
+                self.start_new(&dir, true);
+                ui.close_menu();
+            }
+            ui.separator();
+            if ui.add_enabled(self.selected.len() == 1, egui::Button::new("rename")).clicked() {
+                self.start_rename();
+                ui.close_menu();
+            }
+            let label = match self.selected.len() {
+                1 => "delete".to_string(),
+                n => format!("delete {} items", n),
+            };
+            if ui.button(label).clicked() {
+                self.start_delete();
+                ui.close_menu();
+            }
+            if ui.button("copy path").clicked() {
+                let text = self.selected.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join("\n");
+                ui.output_mut(|o| o.copied_text = text);
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Undo the last delete, or put back or clear out what's in the trash.
+    fn display_trash_menu(&mut self, ui: &mut egui::Ui) {
+        let Some(trash) = &self.trash else {
+            return;
+        };
+        let (mut undo, mut restore, mut empty) = (false, None, false);
+        if ui.add_enabled(!trash.is_empty(), egui::Button::new("undo delete")).on_hover_text("ctrl+z").clicked() {
+            undo = true;
+        }
+        ui.add_enabled_ui(!trash.is_empty(), |ui| {
+            ui.menu_button(format!("trash ({})", trash.len()), |ui| {
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for item in trash.items().iter().rev() {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("restore").clicked() {
+                                restore = Some(item.id);
+                                ui.close_menu();
+                            }
+                            ui.monospace(item.path.display().to_string());
+                            if item.tracked {
+                                ui.colored_label(ui.visuals().warn_fg_color, "deleted in git")
+                                    .on_hover_text("git tracks this, so it shows as deleted until it's restored");
+                            }
+                        });
+                    }
+                });
+                ui.separator();
+                if ui.button("empty the trash for good").clicked() {
+                    empty = true;
+                    ui.close_menu();
+                }
+            });
+        });
+        if undo {
+            self.undo_delete();
+        }
+        if let Some(id) = restore {
+            self.restore(id);
+        }
+        if empty {
+            if let Some(Err(e)) = self.trash.as_mut().map(Trash::empty) {
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn display_delete_confirmation(&mut self, ctx: &egui::Context) {
+        if self.deleting.is_empty() {
+            return;
+        }
+        let mut answer = None;
+        let into_trash = self.trash.is_some();
+        egui::Window::new("Delete files")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            match into_trash {
+                true => ui.label("These go to the project's trash, along with everything in the folders, until you undo the delete:"),
+                false => ui.label("These are deleted for good, along with everything in the folders:"),
+            };
+            for (path, tracked) in self.deleting.iter().take(10) {
+                ui.horizontal(|ui| {
+                    ui.monospace(paths::display_name(path));
+                    if *tracked {
+                        ui.colored_label(ui.visuals().warn_fg_color, "tracked by git");
+                    }
+                });
+            }
+            if self.deleting.len() > 10 {
+                ui.weak(format!("and {} more", self.deleting.len() - 10));
+            }
+            let tracked = self.deleting.iter().filter(|(_, tracked)| *tracked).count();
+            if tracked > 0 {
+                ui.colored_label(ui.visuals().warn_fg_color, format!(
+                    "{} of these are tracked by git, and will show as deleted in the next commit unless they're restored.", tracked,
+                ));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("delete").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("cancel").clicked() {
+                    answer = Some(false);
+                }
+            });
+        });
+        match answer {
+            Some(true) => {
+                let paths = std::mem::take(&mut self.deleting).into_iter().map(|(p, _)| p).collect();
+                self.run(FileAction::Delete(paths));
+            },
+            Some(false) => self.deleting.clear(),
+            None => (),
+        }
+    }
+}
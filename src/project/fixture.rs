@@ -0,0 +1,462 @@
+//! Title: Iron Coder Project Module - Fixture
+//! Description: The test bench a project is tested on, described in a `fixture.toml`
+//!   file in the project directory: the device under test, the boards that stimulate
+//!   it, and the instruments that measure it. Each device is given a role and found by
+//!   what stays the same when it's unplugged (its USB serial number rather than the
+//!   name of its port), so test sequences can address "dut" or "psu" and get the same
+//!   device after every reconnect.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobId, JobState};
+
+/// The name of the fixture file in a project.
+pub const FIXTURE_FILE_NAME: &str = "fixture.toml";
+/// How often the panel looks at the serial ports again.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum FixtureError {
+    FsError(io::Error),
+    /// The fixture file is malformed
+    InvalidFixture(String),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FixtureError::FsError(e) => write!(f, "couldn't read {}: {}", FIXTURE_FILE_NAME, e),
+            FixtureError::InvalidFixture(e) => write!(f, "invalid {}: {}", FIXTURE_FILE_NAME, e),
+        }
+    }
+}
+
+/// What a device is on the bench for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    /// The device under test
+    #[default]
+    Dut,
+    /// A board driving the device's inputs
+    Stimulus,
+    /// A power supply, meter, logic analyzer...
+    Instrument,
+}
+
+impl fmt::Display for DeviceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceKind::Dut => write!(f, "device under test"),
+            DeviceKind::Stimulus => write!(f, "stimulus"),
+            DeviceKind::Instrument => write!(f, "instrument"),
+        }
+    }
+}
+
+/// How to find a device's serial port. Everything given has to match.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PortMatch {
+    /// The USB serial number, which is what tells two of the same board apart
+    pub usb_serial: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// The USB interface, for devices with more than one port
+    pub interface: Option<u8>,
+    /// A fixed port path, for ports that aren't on USB. It can change on reconnect.
+    pub path: Option<PathBuf>,
+    pub baud: Option<u32>,
+}
+
+impl PortMatch {
+
+    fn is_empty(&self) -> bool {
+        self.usb_serial.is_none() && self.vid.is_none() && self.pid.is_none() && self.path.is_none()
+    }
+
+    pub fn matches(&self, port: &SerialPortInfo) -> bool {
+        self.usb_serial.as_ref().is_none_or(|s| port.usb_serial.as_ref() == Some(s))
+            && self.vid.is_none_or(|v| port.vid == v)
+            && self.pid.is_none_or(|p| port.pid == p)
+            && self.interface.is_none_or(|i| port.interface == Some(i))
+            && self.path.as_ref().is_none_or(|p| &port.path == p)
+    }
+}
+
+/// How to find a device's debug probe, by the probe's serial number as probe-rs lists it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProbeMatch {
+    pub serial: String,
+}
+
+/// Where a networked instrument listens, i.e. a SCPI power supply on port 5025.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkMatch {
+    pub host: String,
+    pub port: u16,
+}
+
+/// A device on the bench, i.e.
+/// ```toml
+/// [[device]]
+/// role = "dut"
+/// kind = "dut"
+/// board = "Adafruit Feather nRF52840"
+/// serial = { usb_serial = "E6614103E7", interface = 0, baud = 115200 }
+/// probe = { serial = "000683456789" }
+///
+/// [[device]]
+/// role = "psu"
+/// kind = "instrument"
+/// network = { host = "192.168.1.50", port = 5025 }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FixtureDevice {
+    /// The name test sequences use for the device
+    pub role: String,
+    pub kind: DeviceKind,
+    #[serde(default)]
+    pub description: String,
+    /// The name of the board, if it's one Iron Coder knows
+    pub board: Option<String>,
+    pub serial: Option<PortMatch>,
+    pub probe: Option<ProbeMatch>,
+    pub network: Option<NetworkMatch>,
+}
+
+/// The contents of a project's fixture.toml.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fixture {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, rename = "device")]
+    pub devices: Vec<FixtureDevice>,
+}
+
+impl Fixture {
+
+    /// Parse and check a fixture.
+    pub fn from_toml_str(toml: &str) -> Result<Self, FixtureError> {
+        let fixture: Self = toml::from_str(toml).map_err(|e| FixtureError::InvalidFixture(e.to_string()))?;
+        let invalid = |e: String| Err(FixtureError::InvalidFixture(e));
+        for (i, device) in fixture.devices.iter().enumerate() {
+            let role_ok = !device.role.is_empty() && device.role.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !role_ok {
+                return invalid(format!("{:?} can't be a role; use letters, numbers, - and _", device.role));
+            }
+            if fixture.devices[..i].iter().any(|d| d.role == device.role) {
+                return invalid(format!("there are two devices with the role {}", device.role));
+            }
+            if device.serial.is_none() && device.probe.is_none() && device.network.is_none() {
+                return invalid(format!("{} needs a serial port, probe or network address to be found by", device.role));
+            }
+            if device.serial.as_ref().is_some_and(PortMatch::is_empty) {
+                return invalid(format!("the serial port of {} needs a usb_serial, vid, pid or path", device.role));
+            }
+            if device.network.as_ref().is_some_and(|n| n.host.is_empty() || n.port == 0) {
+                return invalid(format!("the network address of {} needs a host and port", device.role));
+            }
+        }
+        if fixture.devices.iter().filter(|d| d.kind == DeviceKind::Dut).count() > 1 {
+            return invalid("a fixture can only have one device under test".to_string());
+        }
+        Ok(fixture)
+    }
+
+    /// Load the fixture in a project directory, if it has one.
+    pub fn load(project_dir: &Path) -> Option<Result<Self, FixtureError>> {
+        let path = project_dir.join(FIXTURE_FILE_NAME);
+        if !path.exists() {
+            return None;
+        }
+        Some(fs::read_to_string(path).map_err(FixtureError::FsError).and_then(|s| Self::from_toml_str(&s)))
+    }
+
+    pub fn device(&self, role: &str) -> Option<&FixtureDevice> {
+        self.devices.iter().find(|d| d.role == role)
+    }
+
+    /// Find each device among the attached ports and probes. A device that two ports or
+    /// probes match isn't given either, and neither is a port two devices match, so a
+    /// test never runs against a device picked by chance.
+    pub fn resolve(&self, ports: &[SerialPortInfo], probes: &[ProbeInfo]) -> Resolution {
+        let mut resolution = Resolution::default();
+        for device in self.devices.iter() {
+            let mut found = FoundDevice { network: device.network.clone(), ..Default::default() };
+            if let Some(port_match) = &device.serial {
+                let matching: Vec<&SerialPortInfo> = ports.iter().filter(|p| port_match.matches(p)).collect();
+                let others = self.devices.iter().filter(|d| d.role != device.role && d.serial.as_ref().is_some_and(|m| matching.iter().any(|p| m.matches(p))));
+                let shared: Vec<&str> = others.map(|d| d.role.as_str()).collect();
+                match matching.as_slice() {
+                    [] => found.problems.push("its serial port isn't attached".to_string()),
+                    [port] if shared.is_empty() => found.port = Some((*port).clone()),
+                    [port] => found.problems.push(format!("{} is also matched by {}", port.path.display(), shared.join(", "))),
+                    _ => found.problems.push(format!("{} serial ports match it", matching.len())),
+                }
+            }
+            if let Some(probe_match) = &device.probe {
+                let matching: Vec<&ProbeInfo> = probes.iter().filter(|p| p.serial == probe_match.serial).collect();
+                match matching.as_slice() {
+                    [] => found.problems.push("its probe isn't attached".to_string()),
+                    [probe] => found.probe = Some((*probe).clone()),
+                    _ => found.problems.push(format!("{} probes have the serial number {}", matching.len(), probe_match.serial)),
+                }
+            }
+            resolution.devices.insert(device.role.clone(), found);
+        }
+        resolution
+    }
+}
+
+/// A serial port found on the system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SerialPortInfo {
+    /// The device file, i.e. /dev/ttyACM0
+    pub path: PathBuf,
+    pub vid: u16,
+    pub pid: u16,
+    pub usb_serial: Option<String>,
+    pub interface: Option<u8>,
+    pub product: Option<String>,
+}
+
+impl SerialPortInfo {
+    /// The fixture entry that finds this port.
+    pub fn port_match(&self) -> PortMatch {
+        PortMatch {
+            usb_serial: self.usb_serial.clone(),
+            vid: Some(self.vid),
+            pid: Some(self.pid),
+            interface: self.interface,
+            ..Default::default()
+        }
+    }
+}
+
+/// List the USB serial ports under a sysfs tree, i.e. `/sys`, naming them as in `dev`.
+/// Ports that aren't on USB (the built-in ttyS* ones) are left out.
+pub fn serial_ports_in(sys: &Path, dev: &Path) -> Vec<SerialPortInfo> {
+    let read = |dir: &Path, file: &str| fs::read_to_string(dir.join(file)).ok().map(|s| s.trim().to_string());
+    let Ok(entries) = fs::read_dir(sys.join("class").join("tty")) else {
+        return vec![];
+    };
+    let mut ports = vec![];
+    for entry in entries.flatten() {
+        let Ok(device) = fs::canonicalize(entry.path().join("device")) else {
+            continue;
+        };
+        // the interface, and the USB device above it, are ancestors of the port
+        let interface = device.ancestors().find_map(|d| read(d, "bInterfaceNumber")).and_then(|n| u8::from_str_radix(&n, 16).ok());
+        let Some(usb) = device.ancestors().find(|d| d.join("idVendor").exists()) else {
+            continue;
+        };
+        let id = |file| read(usb, file).and_then(|s| u16::from_str_radix(&s, 16).ok());
+        let (Some(vid), Some(pid)) = (id("idVendor"), id("idProduct")) else {
+            continue;
+        };
+        ports.push(SerialPortInfo {
+            path: dev.join(entry.file_name()),
+            vid, pid, interface,
+            usb_serial: read(usb, "serial"),
+            product: read(usb, "product"),
+        });
+    }
+    ports.sort_by(|a, b| a.path.cmp(&b.path));
+    ports
+}
+
+/// List the system's USB serial ports. Only Linux is supported for now; elsewhere,
+/// fixtures can give port paths.
+pub fn serial_ports() -> Vec<SerialPortInfo> {
+    serial_ports_in(Path::new("/sys"), Path::new("/dev"))
+}
+
+/// A debug probe, as listed by probe-rs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProbeInfo {
+    pub description: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub serial: String,
+}
+
+impl ProbeInfo {
+    /// The argument to probe-rs' `--probe` that picks this probe.
+    pub fn selector(&self) -> String {
+        format!("{:04x}:{:04x}:{}", self.vid, self.pid, self.serial)
+    }
+}
+
+/// Read the output of `probe-rs list`, where probes are listed like
+/// "[0]: J-Link (J-Link) -- 1366:0101:000683456789 (J-Link)".
+pub fn parse_probe_list(output: &str) -> Vec<ProbeInfo> {
+    output.lines().filter_map(|line| {
+        let (_, rest) = line.trim_start().strip_prefix('[')?.split_once("]:")?;
+        let (description, ids) = rest.split_once(" -- ")?;
+        let ids = ids.split_whitespace().next()?;
+        let mut parts = ids.splitn(3, ':');
+        let vid = u16::from_str_radix(parts.next()?, 16).ok()?;
+        let pid = u16::from_str_radix(parts.next()?, 16).ok()?;
+        Some(ProbeInfo { description: description.trim().to_string(), vid, pid, serial: parts.next()?.to_string() })
+    }).collect()
+}
+
+/// Where a fixture's device was found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FoundDevice {
+    pub port: Option<SerialPortInfo>,
+    pub probe: Option<ProbeInfo>,
+    pub network: Option<NetworkMatch>,
+    /// Why parts of the device couldn't be found
+    pub problems: Vec<String>,
+}
+
+/// Where each of a fixture's devices was found, by role.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub devices: BTreeMap<String, FoundDevice>,
+}
+
+impl Resolution {
+
+    /// The serial port of the device with a role.
+    pub fn port(&self, role: &str) -> Option<&Path> {
+        self.devices.get(role)?.port.as_ref().map(|p| p.path.as_path())
+    }
+
+    /// The probe-rs probe selector of the device with a role.
+    pub fn probe(&self, role: &str) -> Option<String> {
+        self.devices.get(role)?.probe.as_ref().map(ProbeInfo::selector)
+    }
+
+    /// The host and port of the device with a role.
+    pub fn address(&self, role: &str) -> Option<(&str, u16)> {
+        self.devices.get(role)?.network.as_ref().map(|n| (n.host.as_str(), n.port))
+    }
+
+    /// Whether every device was found.
+    pub fn is_complete(&self) -> bool {
+        self.devices.values().all(|d| d.problems.is_empty())
+    }
+}
+
+/// The state of the test fixture window. The fixture itself is read from the project.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FixturePanel {
+    #[serde(skip)]
+    ports: Vec<SerialPortInfo>,
+    #[serde(skip)]
+    probes: Arc<Mutex<Vec<ProbeInfo>>>,
+    #[serde(skip)]
+    probe_job: Option<JobId>,
+    #[serde(skip)]
+    scanned: Option<Instant>,
+}
+
+impl FixturePanel {
+
+    /// Look for probes with probe-rs on a background job, since it can take a while.
+    fn scan_probes(&mut self, ctx: &egui::Context) {
+        let probes = self.probes.clone();
+        self.probe_job = Some(jobs::shared(ctx).spawn("list debug probes", Some(ctx), move |_job| {
+            let output = duct::cmd!("probe-rs", "list").stderr_to_stdout().unchecked().read()
+                .map_err(|e| format!("couldn't run probe-rs: {}", e))?;
+            *probes.lock().unwrap() = parse_probe_list(&output);
+            Ok(())
+        }));
+    }
+
+    /// Show where the fixture's devices are. Returns a port to open in the serial
+    /// monitor, if one was picked.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, project_dir: Option<&Path>) -> Option<PathBuf> {
+        let Some(dir) = project_dir else {
+            ui.label(format!("save the project, then describe its test bench in {}", FIXTURE_FILE_NAME));
+            return None;
+        };
+        if self.scanned.is_none_or(|t| t.elapsed() > RESCAN_INTERVAL) {
+            self.ports = serial_ports();
+            self.scanned = Some(Instant::now());
+            ctx.request_repaint_after(RESCAN_INTERVAL);
+        }
+        let fixture = match Fixture::load(dir) {
+            None => {
+                ui.label(format!("This project has no {}. Add one to name the devices on its test bench.", FIXTURE_FILE_NAME));
+                Fixture::default()
+            },
+            Some(Err(e)) => {
+                ui.colored_label(ui.style().visuals.error_fg_color, e.to_string());
+                Fixture::default()
+            },
+            Some(Ok(fixture)) => fixture,
+        };
+        let scanning = self.probe_job.is_some_and(|id| jobs::shared(ctx).state(id) == Some(JobState::Running));
+        ui.horizontal(|ui| {
+            if !fixture.name.is_empty() {
+                ui.strong(&fixture.name);
+            }
+            if ui.add_enabled(!scanning, egui::Button::new("look for probes")).clicked() {
+                self.scan_probes(ctx);
+            }
+        });
+        let probes = self.probes.lock().unwrap().clone();
+        let resolution = fixture.resolve(&self.ports, &probes);
+        let mut monitor = None;
+        egui::Grid::new("fixture_devices").num_columns(4).striped(true).show(ui, |ui| {
+            for device in fixture.devices.iter() {
+                let found = &resolution.devices[&device.role];
+                ui.strong(&device.role).on_hover_text(format!("{}\n{}", device.kind, device.description));
+                let mut places = vec![];
+                if let Some(port) = &found.port { places.push(port.path.display().to_string()); }
+                if let Some(probe) = &found.probe { places.push(format!("probe {}", probe.description)); }
+                if let Some(network) = &found.network { places.push(format!("{}:{}", network.host, network.port)); }
+                ui.label(places.join(", "));
+                if found.problems.is_empty() {
+                    ui.label("ready");
+                } else {
+                    ui.colored_label(ui.style().visuals.warn_fg_color, found.problems.join("; "));
+                }
+                if let Some(port) = &found.port {
+                    if ui.small_button("monitor").clicked() {
+                        monitor = Some(port.path.clone());
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        // ports no device claims, with the entry that would claim them
+        let unclaimed: Vec<&SerialPortInfo> = self.ports.iter()
+            .filter(|p| !fixture.devices.iter().any(|d| d.serial.as_ref().is_some_and(|m| m.matches(p))))
+            .collect();
+        if !unclaimed.is_empty() {
+            ui.separator();
+            ui.label("other serial ports:");
+            for port in unclaimed {
+                ui.horizontal(|ui| {
+                    let serial = port.usb_serial.as_deref().unwrap_or("no serial number");
+                    ui.label(format!("{} {:04x}:{:04x} {} ({})", port.path.display(), port.vid, port.pid, port.product.as_deref().unwrap_or_default(), serial));
+                    if ui.small_button("copy fixture entry").clicked() {
+                        let entry = FixtureDevice {
+                            role: "new-device".to_string(),
+                            serial: Some(port.port_match()),
+                            ..Default::default()
+                        };
+                        let text = toml::to_string(&Fixture { name: String::new(), devices: vec![entry] }).unwrap_or_default();
+                        ui.output_mut(|o| o.copied_text = text);
+                    }
+                });
+            }
+        }
+        monitor
+    }
+}
@@ -0,0 +1,545 @@
+//! Title: Iron Coder Project Module - Fonts
+//! Description: Bitmap fonts for embedded displays, cut down to the characters a project
+//!   shows. A TrueType or OpenType font is rendered at one pixel size, for only the
+//!   characters in the project's string literals and chosen text files, and written to
+//!   `src/fonts.rs` as embedded-graphics `MonoFont`s. Most UIs use a few dozen glyphs, so
+//!   this takes a fraction of the flash a full font does.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use serde::{Serialize, Deserialize};
+use syn::visit::Visit;
+
+use crate::project::embed;
+use crate::project::explain;
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "fonts.rs";
+/// Glyphs in a row of a font's image.
+const GLYPHS_PER_ROW: usize = 16;
+/// The characters shown for a number formatted into text.
+const NUMBER_CHARACTERS: &str = "0123456789.-";
+/// The macros whose strings have `{}` placeholders.
+const FORMAT_MACROS: &[&str] = &[
+    "format", "write", "writeln", "print", "println", "eprint", "eprintln", "panic",
+    "format_args", "uwrite", "uwriteln", "info", "warn", "error", "debug", "trace",
+];
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum FontError {
+    /// A font or text file couldn't be read, or the module couldn't be written
+    IoError(io::Error),
+    /// The font file isn't a font
+    BadFont(String),
+    /// A font's settings can't make a font
+    BadSettings(String),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::IoError(e) => write!(f, "{}", e),
+            FontError::BadFont(e) => write!(f, "{}", e),
+            FontError::BadSettings(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> Self {
+        FontError::IoError(e)
+    }
+}
+
+/// A font to cut down, and where its characters come from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct FontSubset {
+    /// The name of its const in the module, i.e. "title" becomes `TITLE`
+    pub name: String,
+    /// The TrueType or OpenType file, relative to the project directory
+    pub source: PathBuf,
+    /// The height of a line, in pixels
+    pub size: f32,
+    /// Include the characters of the string literals in the project's code
+    pub from_code: bool,
+    /// Files (i.e. translations or menu text) whose characters are included, relative to
+    /// the project directory
+    pub text_files: Vec<PathBuf>,
+    /// Characters that are always included
+    pub extra: String,
+}
+
+impl Default for FontSubset {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            source: PathBuf::new(),
+            size: 16.0,
+            from_code: true,
+            text_files: vec![],
+            extra: " ?".to_string(),
+        }
+    }
+}
+
+/// Finds the characters of string and character literals, including those in macros.
+#[derive(Default)]
+struct Literals {
+    characters: BTreeSet<char>,
+}
+
+impl Literals {
+    fn add(&mut self, text: &str, formatted: bool) {
+        let text = match formatted {
+            true => strip_placeholders(text),
+            false => text.to_string(),
+        };
+        self.characters.extend(text.chars().filter(|c| !c.is_control()));
+    }
+
+    fn tokens(&mut self, tokens: proc_macro2::TokenStream, formatted: bool) {
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Group(group) => self.tokens(group.stream(), formatted),
+                proc_macro2::TokenTree::Literal(literal) => {
+                    match syn::parse_str::<syn::Lit>(&literal.to_string()) {
+                        Ok(syn::Lit::Str(s)) => self.add(&s.value(), formatted),
+                        Ok(syn::Lit::Char(c)) => self.add(&c.value().to_string(), false),
+                        _ => (),
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Literals {
+    fn visit_lit_str(&mut self, literal: &'ast syn::LitStr) {
+        self.add(&literal.value(), false);
+    }
+
+    fn visit_lit_char(&mut self, literal: &'ast syn::LitChar) {
+        self.add(&literal.value().to_string(), false);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let name = mac.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+        self.tokens(mac.tokens.clone(), FORMAT_MACROS.contains(&name.as_str()));
+    }
+
+    // doc comments aren't shown on a display
+    fn visit_attribute(&mut self, _attribute: &'ast syn::Attribute) {}
+}
+
+/// A format string's text without its placeholders. A string with placeholders also
+/// gets the characters of formatted numbers.
+pub fn strip_placeholders(format: &str) -> String {
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    let mut placeholders = false;
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                text.push(c);
+                chars.next();
+            },
+            ('{', _) => {
+                placeholders = true;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            },
+            _ => text.push(c),
+        }
+    }
+    if placeholders {
+        text += NUMBER_CHARACTERS;
+    }
+    text
+}
+
+/// The characters of the literals in some Rust code. Code that doesn't parse has none.
+pub fn characters_in_code(code: &str) -> BTreeSet<char> {
+    let mut literals = Literals::default();
+    if let Ok(file) = syn::parse_file(code) {
+        literals.visit_file(&file);
+    }
+    literals.characters
+}
+
+/// The Rust files of a project's `src` directory.
+pub(crate) fn source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            source_files(&path, files);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+impl FontSubset {
+
+    /// The name of the font's const.
+    pub fn const_name(&self) -> Result<String, FontError> {
+        let asset = embed::Asset { name: self.name.clone(), source: self.source.clone(), ..Default::default() };
+        asset.const_name().map_err(|e| FontError::BadSettings(e.to_string()))
+    }
+
+    /// The characters the font needs, in order.
+    pub fn characters(&self, project_dir: &Path) -> Result<Vec<char>, FontError> {
+        let mut characters: BTreeSet<char> = self.extra.chars().filter(|c| !c.is_control()).collect();
+        if self.from_code {
+            let mut files = vec![];
+            source_files(&project_dir.join("src"), &mut files);
+            for file in files {
+                // the generated modules would otherwise add their own characters
+                if file.file_name().is_some_and(|n| n == MODULE_FILE_NAME || n == embed::MODULE_FILE_NAME) {
+                    continue;
+                }
+                characters.extend(characters_in_code(&fs::read_to_string(file)?));
+            }
+        }
+        for file in self.text_files.iter() {
+            let text = fs::read_to_string(project_dir.join(file))
+                .map_err(|e| io::Error::new(e.kind(), format!("couldn't read {}: {}", file.display(), e)))?;
+            characters.extend(text.chars().filter(|c| !c.is_control()));
+        }
+        Ok(characters.into_iter().collect())
+    }
+}
+
+/// A font rendered to a sheet of monospaced glyphs, the way embedded-graphics'
+/// `MonoFont` keeps them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitmapFont {
+    pub characters: Vec<char>,
+    /// The characters the font has no glyph for, which show as its replacement
+    pub missing: Vec<char>,
+    pub width: u32,
+    pub height: u32,
+    pub baseline: u32,
+    /// Pixels, one bit each, a row of glyphs at a time, rows padded to whole bytes
+    pub image: Vec<u8>,
+    pub image_width: u32,
+}
+
+impl BitmapFont {
+
+    /// Render characters of a font at a line height in pixels.
+    pub fn render(font: &[u8], size: f32, characters: &[char]) -> Result<Self, FontError> {
+        let font = FontRef::try_from_slice(font).map_err(|_| FontError::BadFont("it isn't a TrueType or OpenType font".to_string()))?;
+        if !(4.0..=256.0).contains(&size) {
+            return Err(FontError::BadSettings(format!("a {} px font is too small or large", size)));
+        }
+        if characters.is_empty() {
+            return Err(FontError::BadSettings("there are no characters to include".to_string()));
+        }
+        let scaled = font.as_scaled(PxScale::from(size));
+        let baseline = scaled.ascent().ceil().max(0.0) as u32;
+        let height = (scaled.ascent() - scaled.descent()).ceil().max(1.0) as u32;
+        let missing: Vec<char> = characters.iter().copied().filter(|c| font.glyph_id(*c).0 == 0).collect();
+        let width = characters.iter()
+            .map(|c| scaled.h_advance(font.glyph_id(*c)).ceil() as u32)
+            .max().unwrap_or(1).max(1);
+
+        let columns = GLYPHS_PER_ROW.min(characters.len());
+        let rows = characters.len().div_ceil(columns);
+        let image_width = width * columns as u32;
+        let row_bytes = (image_width as usize).div_ceil(8);
+        let mut image = vec![0u8; row_bytes * rows * height as usize];
+        for (i, c) in characters.iter().enumerate() {
+            let (cell_x, cell_y) = ((i % columns) as u32 * width, (i / columns) as u32 * height);
+            let position = ab_glyph::point(cell_x as f32, (cell_y + baseline) as f32);
+            let glyph = font.glyph_id(*c).with_scale_and_position(size, position);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let (x, y) = (bounds.min.x as i64 + x as i64, bounds.min.y as i64 + y as i64);
+                // the parts of a glyph outside its cell are cut off
+                let inside = x >= cell_x as i64 && x < (cell_x + width) as i64 && y >= cell_y as i64 && y < (cell_y + height) as i64;
+                if inside && coverage >= 0.5 {
+                    image[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            });
+        }
+        Ok(Self { characters: characters.to_vec(), missing, width, height, baseline, image, image_width })
+    }
+
+    /// Whether a pixel of the image is set.
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        let row_bytes = (self.image_width as usize).div_ceil(8);
+        self.image[y as usize * row_bytes + x as usize / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// The characters as a `StrGlyphMapping` takes them: runs of three or more
+    /// consecutive characters become ranges.
+    pub fn glyph_mapping(&self) -> String {
+        let mut mapping = String::new();
+        let mut i = 0;
+        while i < self.characters.len() {
+            let start = self.characters[i];
+            let mut end = i;
+            while end + 1 < self.characters.len() && self.characters[end + 1] as u32 == self.characters[end] as u32 + 1 {
+                end += 1;
+            }
+            if end - i >= 2 {
+                mapping.push('\0');
+                mapping.push(start);
+                mapping.push(self.characters[end]);
+            } else {
+                mapping.extend(&self.characters[i..=end]);
+            }
+            i = end + 1;
+        }
+        mapping
+    }
+
+    /// The glyph shown for characters the font doesn't have: `?` if it's included.
+    pub fn replacement(&self) -> usize {
+        self.characters.iter().position(|c| *c == '?').unwrap_or(0)
+    }
+}
+
+/// The code of one font in the module.
+fn font_code(subset: &FontSubset, font: &BitmapFont) -> Result<String, FontError> {
+    let name = subset.const_name()?;
+    let mut code = format!(
+        "/// `{}` at {} px, cut down to {} characters ({} bytes).\n",
+        subset.source.display().to_string().replace('\\', "/"), subset.size, font.characters.len(), font.image.len(),
+    );
+    code += &format!("pub const {}: MonoFont = MonoFont {{\n", name);
+    code += &format!("    image: ImageRaw::new({}_DATA, {}),\n", name, font.image_width);
+    code += &format!("    glyph_mapping: &{}_GLYPHS,\n", name);
+    code += &format!("    character_size: Size::new({}, {}),\n", font.width, font.height);
+    code += "    character_spacing: 0,\n";
+    code += &format!("    baseline: {},\n", font.baseline);
+    code += &format!("    underline: DecorationDimensions::new({}, 1),\n", (font.baseline + 2).min(font.height - 1));
+    code += &format!("    strikethrough: DecorationDimensions::new({}, 1),\n", font.height / 2);
+    code += "};\n";
+    code += &format!("const {}_GLYPHS: StrGlyphMapping = StrGlyphMapping::new({:?}, {});\n", name, font.glyph_mapping(), font.replacement());
+    code += &format!("const {}_DATA: &[u8] = &[\n", name);
+    code += &embed::byte_lines(&font.image);
+    code += "];\n";
+    Ok(code)
+}
+
+/// Render the fonts and generate their module.
+pub fn generate_module(subsets: &[FontSubset], project_dir: &Path) -> Result<String, FontError> {
+    let mut code = String::from(
+        "//! Bitmap fonts cut down to the characters the project shows, generated by Iron\n\
+         //! Coder. Don't edit this file: it's rewritten when the project's text changes.\n\
+         #![allow(dead_code)]\n\n\
+         use embedded_graphics::geometry::Size;\n\
+         use embedded_graphics::image::ImageRaw;\n\
+         use embedded_graphics::mono_font::mapping::StrGlyphMapping;\n\
+         use embedded_graphics::mono_font::{DecorationDimensions, MonoFont};\n",
+    );
+    let mut names = vec![];
+    for subset in subsets {
+        let name = subset.const_name()?;
+        if names.contains(&name) {
+            return Err(FontError::BadSettings(format!("two fonts are named {}", name)));
+        }
+        let data = fs::read(project_dir.join(&subset.source))
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't read {}: {}", subset.source.display(), e)))?;
+        let font = BitmapFont::render(&data, subset.size, &subset.characters(project_dir)?)?;
+        code += "\n";
+        code += &font_code(subset, &font)?;
+        names.push(name);
+    }
+    Ok(code)
+}
+
+/// Regenerate the module if the fonts or the project's text changed, and make sure the
+/// project depends on embedded-graphics. Returns whether it was written. A project
+/// without fonts is left alone.
+pub fn regenerate(subsets: &[FontSubset], project_dir: &Path) -> Result<bool, FontError> {
+    if subsets.is_empty() {
+        return Ok(false);
+    }
+    let code = generate_module(subsets, project_dir)?;
+    let path = project_dir.join("src").join(MODULE_FILE_NAME);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == code) {
+        return Ok(false);
+    }
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(&path, code)?;
+    let manifest_path = project_dir.join("Cargo.toml");
+    if let Ok(manifest) = fs::read_to_string(&manifest_path) {
+        let updated = explain::add_dependency(&manifest, "embedded-graphics", "0.8", &[]).map_err(FontError::BadSettings)?;
+        if updated != manifest {
+            fs::write(&manifest_path, updated)?;
+        }
+    }
+    Ok(true)
+}
+
+/// The state of the bitmap fonts window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FontPanel {
+    selected: usize,
+    #[serde(skip)]
+    preview: Option<(FontSubset, Result<BitmapFont, String>, Option<egui::TextureHandle>)>,
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl FontPanel {
+
+    /// Pick a file in the project directory, relative to it.
+    fn pick_file(&mut self, dir: &Path) -> Option<PathBuf> {
+        let file = rfd::FileDialog::new().set_directory(dir).pick_file()?;
+        match file.strip_prefix(dir) {
+            Ok(relative) => Some(relative.to_path_buf()),
+            Err(_) => {
+                self.status = Some(Err("the file has to be in the project directory".to_string()));
+                None
+            },
+        }
+    }
+
+    fn show_preview(&mut self, ui: &mut egui::Ui, subset: &FontSubset, dir: &Path) {
+        let stale = self.preview.as_ref().is_none_or(|(rendered, ..)| rendered != subset);
+        if stale && ui.button("preview").clicked() {
+            let rendered = fs::read(dir.join(&subset.source)).map_err(FontError::from)
+                .and_then(|data| BitmapFont::render(&data, subset.size, &subset.characters(dir)?))
+                .map_err(|e| e.to_string());
+            self.preview = Some((subset.clone(), rendered, None));
+        }
+        let Some((_, rendered, texture)) = &mut self.preview else {
+            return;
+        };
+        match rendered {
+            Ok(font) => {
+                ui.label(format!(
+                    "{} characters in {}x{} px cells: {} bytes",
+                    font.characters.len(), font.width, font.height, font.image.len(),
+                ));
+                if !font.missing.is_empty() {
+                    let missing: String = font.missing.iter().collect();
+                    ui.colored_label(ui.style().visuals.warn_fg_color, format!("the font doesn't have {:?}", missing));
+                }
+                let texture = texture.get_or_insert_with(|| {
+                    let rows = font.image.len() as u32 / (font.image_width.div_ceil(8));
+                    let pixels = (0..rows).flat_map(|y| (0..font.image_width).map(move |x| (x, y)))
+                        .map(|(x, y)| if font.pixel(x, y) { egui::Color32::WHITE } else { egui::Color32::BLACK })
+                        .collect();
+                    let image = egui::ColorImage { size: [font.image_width as usize, rows as usize], pixels };
+                    ui.ctx().load_texture("bitmap_font_preview", image, egui::TextureOptions::NEAREST)
+                });
+                let size = texture.size_vec2() * 2.0;
+                ui.image((texture.id(), size));
+            },
+            Err(e) => { ui.colored_label(ui.style().visuals.error_fg_color, e.as_str()); },
+        }
+    }
+
+    pub fn display(&mut self, ui: &mut egui::Ui, subsets: &mut Vec<FontSubset>, project_dir: Option<&Path>) {
+        let Some(dir) = project_dir else {
+            ui.label("save the project to add fonts to it");
+            return;
+        };
+        ui.label(format!("Fonts are rendered into src/{} before each build; add `mod fonts;` to main.rs to use them.", MODULE_FILE_NAME));
+        ui.horizontal(|ui| {
+            let selected_text = subsets.get(self.selected).map(|s| s.name.clone()).unwrap_or_default();
+            egui::ComboBox::from_id_source("font_subset").selected_text(selected_text).show_ui(ui, |ui| {
+                for (i, subset) in subsets.iter().enumerate() {
+                    ui.selectable_value(&mut self.selected, i, &subset.name);
+                }
+            });
+            if ui.button("add font").clicked() {
+                if let Some(source) = self.pick_file(dir) {
+                    let name = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    subsets.push(FontSubset { name, source, ..Default::default() });
+                    self.selected = subsets.len() - 1;
+                }
+            }
+            if self.selected < subsets.len() && ui.button("remove").clicked() {
+                subsets.remove(self.selected);
+                self.selected = self.selected.saturating_sub(1);
+            }
+        });
+        let Some(mut subset) = subsets.get(self.selected).cloned() else {
+            return;
+        };
+        egui::Grid::new("font_subset_settings").num_columns(2).show(ui, |ui| {
+            ui.label("name:");
+            ui.text_edit_singleline(&mut subset.name);
+            ui.end_row();
+            ui.label("font:");
+            ui.horizontal(|ui| {
+                ui.label(subset.source.display().to_string());
+                if ui.small_button("…").clicked() {
+                    if let Some(source) = self.pick_file(dir) {
+                        subset.source = source;
+                    }
+                }
+            });
+            ui.end_row();
+            ui.label("size:");
+            ui.add(egui::DragValue::new(&mut subset.size).clamp_range(4.0..=256.0).speed(0.5).suffix(" px"));
+            ui.end_row();
+            ui.label("characters:");
+            ui.checkbox(&mut subset.from_code, "the strings in the project's code");
+            ui.end_row();
+            ui.label("");
+            ui.horizontal(|ui| {
+                ui.label("and:");
+                ui.text_edit_singleline(&mut subset.extra);
+            });
+            ui.end_row();
+            ui.label("text files:");
+            ui.vertical(|ui| {
+                let mut remove = None;
+                for (i, file) in subset.text_files.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(file.display().to_string());
+                        if ui.small_button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    subset.text_files.remove(i);
+                }
+                if ui.small_button("add").clicked() {
+                    if let Some(file) = self.pick_file(dir) {
+                        subset.text_files.push(file);
+                    }
+                }
+            });
+            ui.end_row();
+        });
+        self.show_preview(ui, &subset, dir);
+        subsets[self.selected] = subset;
+        if ui.button("generate now").clicked() {
+            self.status = Some(match regenerate(subsets, dir) {
+                Ok(true) => Ok(format!("wrote src/{}", MODULE_FILE_NAME)),
+                Ok(false) => Ok(format!("src/{} is up to date", MODULE_FILE_NAME)),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+        match &self.status {
+            Some(Ok(done)) => { ui.label(done); },
+            Some(Err(e)) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            None => (),
+        }
+    }
+}
@@ -0,0 +1,417 @@
+//! Title: Iron Coder Project Module - Forwarding
+//! Description: Live forwarding of a project's telemetry for long-running device tests.
+//!   The samples recorded from the event bus can be written to an InfluxDB endpoint in
+//!   batches, and the latest value of each can be served on a Prometheus scrape
+//!   endpoint, so standard dashboards (i.e. Grafana) can follow the test.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event};
+use crate::app::export::{to_influx, Record, Sources, Table};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::project::messages::MessageSchema;
+
+/// The most samples held back while InfluxDB can't be reached.
+const MAX_PENDING: usize = 100_000;
+
+/// A url and its query parameters.
+pub type Endpoint = (String, Vec<(&'static str, String)>);
+
+/// The InfluxDB write API to use.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum InfluxVersion {
+    /// `/write?db=`, for InfluxDB 1.x
+    V1,
+    /// `/api/v2/write?org=&bucket=`, for InfluxDB 2.x and 3.x
+    #[default]
+    V2,
+}
+
+impl fmt::Display for InfluxVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InfluxVersion::V1 => write!(f, "InfluxDB 1.x"),
+            InfluxVersion::V2 => write!(f, "InfluxDB 2.x / 3.x"),
+        }
+    }
+}
+
+/// Where samples are written, i.e.
+/// ```toml
+/// [forwarding.influx]
+/// enabled = true
+/// url = "http://localhost:8086"
+/// bucket = "bench"
+/// org = "lab"
+/// ```
+/// The API token isn't kept in the project.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct InfluxTarget {
+    pub enabled: bool,
+    pub url: String,
+    pub version: InfluxVersion,
+    /// The bucket for 2.x, or the database for 1.x
+    pub bucket: String,
+    pub org: String,
+    pub measurement: String,
+    /// How often batches are written, in seconds
+    pub interval: u32,
+}
+
+impl Default for InfluxTarget {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "http://localhost:8086".to_string(),
+            version: InfluxVersion::default(),
+            bucket: String::new(),
+            org: String::new(),
+            measurement: "telemetry".to_string(),
+            interval: 5,
+        }
+    }
+}
+
+impl InfluxTarget {
+
+    /// The write endpoint and its query parameters.
+    pub fn endpoint(&self) -> Result<Endpoint, String> {
+        let base = self.url.trim().trim_end_matches('/');
+        if !(base.starts_with("http://") || base.starts_with("https://")) {
+            return Err("the InfluxDB url needs to start with http:// or https://".to_string());
+        }
+        if self.bucket.trim().is_empty() {
+            return Err(format!("pick the InfluxDB {} to write to", if self.version == InfluxVersion::V1 { "database" } else { "bucket" }));
+        }
+        if self.measurement.trim().is_empty() {
+            return Err("the InfluxDB measurement needs a name".to_string());
+        }
+        let precision = ("precision", "ns".to_string());
+        Ok(match self.version {
+            InfluxVersion::V1 => (format!("{}/write", base), vec![("db", self.bucket.trim().to_string()), precision]),
+            InfluxVersion::V2 => {
+                if self.org.trim().is_empty() {
+                    return Err("InfluxDB 2.x needs an organization".to_string());
+                }
+                let query = vec![("org", self.org.trim().to_string()), ("bucket", self.bucket.trim().to_string()), precision];
+                (format!("{}/api/v2/write", base), query)
+            },
+        })
+    }
+
+    /// Write samples as line protocol.
+    fn write(&self, token: &str, records: &[Record]) -> Result<(), String> {
+        let (url, query) = self.endpoint()?;
+        let mut fields: Vec<String> = records.iter().flat_map(|r| r.values.iter().map(|(n, _)| n.clone())).collect();
+        fields.sort();
+        fields.dedup();
+        let body = to_influx(&Table::new(records, &fields, None), self.measurement.trim());
+        let mut request = ureq::post(&url)
+            .set("Content-Type", "text/plain; charset=utf-8")
+            .timeout(Duration::from_secs(10));
+        for (name, value) in query.iter() {
+            request = request.query(name, value);
+        }
+        if !token.is_empty() {
+            request = request.set("Authorization", &format!("Token {}", token));
+        }
+        request.send_string(&body).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// The Prometheus scrape endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PrometheusEndpoint {
+    pub enabled: bool,
+    /// The address to listen on. Use 0.0.0.0 to be reachable from other machines.
+    pub address: String,
+    /// The start of the metric names
+    pub prefix: String,
+}
+
+impl Default for PrometheusEndpoint {
+    fn default() -> Self {
+        Self { enabled: false, address: "127.0.0.1:9464".to_string(), prefix: "iron_coder".to_string() }
+    }
+}
+
+impl PrometheusEndpoint {
+    pub fn validate(&self) -> Result<SocketAddr, String> {
+        let prefix_ok = self.prefix.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && self.prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        if !prefix_ok {
+            return Err(format!("{:?} can't start a Prometheus metric name", self.prefix));
+        }
+        self.address.trim().parse().map_err(|_| format!("{:?} isn't an address and port to listen on", self.address))
+    }
+}
+
+/// A project's telemetry forwarding settings.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ForwardingConfig {
+    pub sources: Sources,
+    pub influx: InfluxTarget,
+    pub prometheus: PrometheusEndpoint,
+}
+
+impl ForwardingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.influx.enabled && !self.prometheus.enabled {
+            return Err("turn on InfluxDB or Prometheus forwarding".to_string());
+        }
+        if self.influx.enabled {
+            self.influx.endpoint()?;
+        }
+        if self.prometheus.enabled {
+            self.prometheus.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// The latest value of each field, for scrapes.
+#[derive(Default)]
+pub struct Gauges {
+    /// The value and time of each (source, field)
+    values: BTreeMap<(String, String), (f64, f64)>,
+    /// The number of records from each source
+    records: BTreeMap<String, u64>,
+}
+
+impl Gauges {
+
+    pub fn update(&mut self, record: &Record) {
+        for (field, value) in record.values.iter() {
+            self.values.insert((record.source.clone(), field.clone()), (*value, record.time));
+        }
+        *self.records.entry(record.source.clone()).or_default() += 1;
+    }
+
+    /// The metrics in Prometheus' text format. Field names can be anything, so they're
+    /// labels of one gauge rather than metric names.
+    pub fn exposition(&self, prefix: &str) -> String {
+        let label = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        let mut text = format!("# HELP {0}_telemetry The latest value of a telemetry field.\n# TYPE {0}_telemetry gauge\n", prefix);
+        for ((source, field), (value, time)) in self.values.iter() {
+            let value = if value.is_finite() { value.to_string() } else if value.is_nan() { "NaN".to_string() } else if *value > 0.0 { "+Inf".to_string() } else { "-Inf".to_string() };
+            text += &format!("{}_telemetry{{source=\"{}\",field=\"{}\"}} {} {}\n", prefix, label(source), label(field), value, (time * 1e3) as i64);
+        }
+        text += &format!("# HELP {0}_telemetry_records_total The records received from a source.\n# TYPE {0}_telemetry_records_total counter\n", prefix);
+        for (source, count) in self.records.iter() {
+            text += &format!("{}_telemetry_records_total{{source=\"{}\"}} {}\n", prefix, label(source), count);
+        }
+        text
+    }
+}
+
+/// Answer a scrape. Anything other than a GET of /metrics is not found.
+fn answer_scrape(mut stream: TcpStream, gauges: &Gauges, prefix: &str) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", gauges.exposition(prefix)),
+        _ => ("404 Not Found", "try /metrics\n".to_string()),
+    };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+}
+
+/// Forward the recorded sources until the job is cancelled.
+fn forward(rx: Receiver<Event>, config: ForwardingConfig, token: String, schema: MessageSchema, job: &JobContext) -> Result<(), String> {
+    let listener = match config.prometheus.enabled {
+        true => {
+            let address = config.prometheus.validate()?;
+            let listener = TcpListener::bind(address).map_err(|e| format!("couldn't listen on {}: {}", address, e))?;
+            listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+            Some(listener)
+        },
+        false => None,
+    };
+    let interval = Duration::from_secs(config.influx.interval.max(1) as u64);
+    let mut gauges = Gauges::default();
+    let mut pending: Vec<Record> = vec![];
+    let (mut written, mut scrapes, mut last_write, mut error) = (0, 0, Instant::now(), None);
+    while !job.is_cancelled() {
+        match rx.recv_timeout(Duration::from_millis(50)) {
+            Ok(event) => {
+                let time = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs_f64();
+                if let Some((source, values)) = config.sources.samples(event, &schema) {
+                    let record = Record { time, source, values };
+                    gauges.update(&record);
+                    if config.influx.enabled {
+                        pending.push(record);
+                        if pending.len() > MAX_PENDING {
+                            pending.drain(..pending.len() - MAX_PENDING);
+                        }
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if let Some(listener) = &listener {
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        scrapes += 1;
+                        // a scraper hanging up early isn't our problem
+                        let _ = answer_scrape(stream, &gauges, &config.prometheus.prefix);
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+        }
+        if !pending.is_empty() && last_write.elapsed() >= interval {
+            last_write = Instant::now();
+            // samples are kept until a write gets through
+            match config.influx.write(&token, &pending) {
+                Ok(()) => {
+                    written += pending.len();
+                    pending.clear();
+                    error = None;
+                },
+                Err(e) => error = Some(e),
+            }
+        }
+        let mut status = vec![];
+        if config.influx.enabled {
+            status.push(format!("{} records written, {} waiting", written, pending.len()));
+        }
+        if listener.is_some() {
+            status.push(format!("{} scrapes", scrapes));
+        }
+        if let Some(e) = &error {
+            status.push(format!("last write failed: {}", e));
+        }
+        job.set_status(&status.join(", "));
+    }
+    Ok(())
+}
+
+/// The state of the forwarding window. The settings belong to the project.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ForwardingPanel {
+    #[serde(skip)]
+    token: String,
+    #[serde(skip)]
+    job: Option<JobId>,
+}
+
+impl ForwardingPanel {
+
+    fn start(&mut self, ctx: &egui::Context, config: &ForwardingConfig, schema: &MessageSchema) {
+        let rx = events::shared(ctx).subscribe();
+        let (config, token, schema) = (config.clone(), self.token.clone(), schema.clone());
+        self.job = Some(jobs::shared(ctx).spawn("telemetry forwarding", Some(ctx), move |job| {
+            forward(rx, config, token, schema, job)
+        }));
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, config: &mut ForwardingConfig, schema: &MessageSchema) {
+        let scheduler = jobs::shared(ctx);
+        let running = self.job.is_some_and(|id| scheduler.state(id) == Some(JobState::Running));
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("forward:");
+                ui.checkbox(&mut config.sources.serial_lines, "serial lines");
+                ui.checkbox(&mut config.sources.serial_messages, "serial messages");
+                ui.checkbox(&mut config.sources.network_telemetry, "network telemetry");
+            });
+            ui.separator();
+            ui.checkbox(&mut config.influx.enabled, "write to InfluxDB");
+            ui.add_enabled_ui(config.influx.enabled, |ui| {
+                let influx = &mut config.influx;
+                egui::Grid::new("forwarding_influx").num_columns(2).show(ui, |ui| {
+                    ui.label("version:");
+                    egui::ComboBox::from_id_source("influx_version").selected_text(influx.version.to_string()).show_ui(ui, |ui| {
+                        for version in enum_iterator::all::<InfluxVersion>() {
+                            ui.selectable_value(&mut influx.version, version, version.to_string());
+                        }
+                    });
+                    ui.end_row();
+                    ui.label("url:");
+                    ui.text_edit_singleline(&mut influx.url);
+                    ui.end_row();
+                    if influx.version == InfluxVersion::V2 {
+                        ui.label("organization:");
+                        ui.text_edit_singleline(&mut influx.org);
+                        ui.end_row();
+                    }
+                    ui.label(if influx.version == InfluxVersion::V1 { "database:" } else { "bucket:" });
+                    ui.text_edit_singleline(&mut influx.bucket);
+                    ui.end_row();
+                    ui.label("measurement:");
+                    ui.text_edit_singleline(&mut influx.measurement);
+                    ui.end_row();
+                    ui.label("token:");
+                    ui.add(egui::TextEdit::singleline(&mut self.token).password(true).hint_text("not saved"));
+                    ui.end_row();
+                    ui.label("write every:");
+                    ui.add(egui::DragValue::new(&mut influx.interval).clamp_range(1..=3600).suffix(" s"));
+                    ui.end_row();
+                });
+            });
+            ui.separator();
+            ui.checkbox(&mut config.prometheus.enabled, "serve Prometheus metrics");
+            ui.add_enabled_ui(config.prometheus.enabled, |ui| {
+                egui::Grid::new("forwarding_prometheus").num_columns(2).show(ui, |ui| {
+                    ui.label("listen on:");
+                    ui.text_edit_singleline(&mut config.prometheus.address);
+                    ui.end_row();
+                    ui.label("metric prefix:");
+                    ui.text_edit_singleline(&mut config.prometheus.prefix);
+                    ui.end_row();
+                });
+                if let Ok(address) = config.prometheus.validate() {
+                    ui.weak(format!("scrape http://{}/metrics", address));
+                }
+            });
+        });
+        ui.separator();
+        let valid = config.validate();
+        if let Err(e) = &valid {
+            ui.colored_label(ui.style().visuals.warn_fg_color, e);
+        }
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("stop").clicked() {
+                    if let Some(id) = self.job.take() {
+                        scheduler.cancel(id);
+                    }
+                }
+            } else if ui.add_enabled(valid.is_ok(), egui::Button::new("start")).clicked() {
+                self.start(ctx, config, schema);
+            }
+            if let Some(info) = self.job.and_then(|id| scheduler.jobs().into_iter().find(|j| j.id == id)) {
+                match info.state {
+                    JobState::Failed(e) => ui.colored_label(ui.style().visuals.error_fg_color, e),
+                    _ => ui.label(info.status),
+                };
+            }
+        });
+    }
+}
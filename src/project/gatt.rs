@@ -0,0 +1,732 @@
+//! Title: Iron Coder Project Module - GATT
+//! Description: The BLE GATT services a project's firmware offers, described in a
+//!   `gatt.toml` file in the project directory, and a preview panel that acts like a
+//!   phone app would: connect, discover the services, and read, write and subscribe to
+//!   characteristics, showing values in the formats the description gives them.
+
+use log::info;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use btleplug::api::CharPropFlags;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::project::ble::{BleDevice, BleError};
+
+/// The name of the service description file in a project.
+pub const GATT_FILE_NAME: &str = "gatt.toml";
+/// The most entries kept in the panel's log.
+const MAX_LOG_LEN: usize = 200;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum GattError {
+    FsError(io::Error),
+    /// The service description is malformed
+    InvalidDescription(String),
+    /// The link to the device failed
+    BleError(BleError),
+    /// The device has no such service or characteristic
+    NotFound(String),
+    /// The characteristic doesn't allow the operation
+    NotPermitted(String),
+    /// A value couldn't be converted to or from its format
+    InvalidValue(String),
+}
+
+impl fmt::Display for GattError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GattError::FsError(e) => write!(f, "couldn't read {}: {}", GATT_FILE_NAME, e),
+            GattError::InvalidDescription(e) => write!(f, "invalid {}: {}", GATT_FILE_NAME, e),
+            GattError::BleError(e) => write!(f, "BLE error: {}", e),
+            GattError::NotFound(e) => write!(f, "not found: {}", e),
+            GattError::NotPermitted(e) => write!(f, "not permitted: {}", e),
+            GattError::InvalidValue(e) => write!(f, "invalid value: {}", e),
+        }
+    }
+}
+
+impl From<BleError> for GattError {
+    fn from(e: BleError) -> Self {
+        GattError::BleError(e)
+    }
+}
+
+/// The Bluetooth base UUID, which 16 and 32 bit UUIDs are shorthand for.
+const BASE_UUID_SUFFIX: &str = "-0000-1000-8000-00805f9b34fb";
+
+/// Expand a UUID to its full lowercase 128-bit form, i.e. "180F" to
+/// "0000180f-0000-1000-8000-00805f9b34fb".
+pub fn normalize_uuid(uuid: &str) -> Result<String, GattError> {
+    let uuid = uuid.trim().to_lowercase();
+    let invalid = || GattError::InvalidDescription(format!("{:?} isn't a UUID", uuid));
+    let hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+    let uuid = uuid.strip_prefix("0x").unwrap_or(&uuid).to_string();
+    match uuid.len() {
+        4 | 8 if hex(&uuid) => Ok(format!("{:0>8}{}", uuid, BASE_UUID_SUFFIX)),
+        36 => {
+            let groups: Vec<&str> = uuid.split('-').collect();
+            let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+            if lengths != [8, 4, 4, 4, 12] || !groups.iter().all(|g| hex(g)) {
+                return Err(invalid());
+            }
+            Ok(uuid)
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// The short form of a UUID, if it's based on the Bluetooth base UUID.
+pub fn short_uuid(uuid: &str) -> String {
+    match uuid.strip_suffix(BASE_UUID_SUFFIX) {
+        Some(short) => format!("0x{}", short.trim_start_matches("0000")),
+        None => uuid.to_string(),
+    }
+}
+
+/// The operations a characteristic supports.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Property {
+    Read,
+    Write,
+    WriteWithoutResponse,
+    Notify,
+    Indicate,
+}
+
+/// How a characteristic's value is encoded. Numbers are little-endian, as in the
+/// Bluetooth specifications.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueFormat {
+    U8,
+    U16,
+    U32,
+    I8,
+    I16,
+    I32,
+    F32,
+    Utf8,
+    /// Raw bytes, shown as hex
+    #[default]
+    Bytes,
+}
+
+impl ValueFormat {
+
+    /// Turn text, i.e. "21.5" or "01 ff", into the bytes of a value. `scale` is the
+    /// size of one unit of an integer value, so with a scale of 0.01, "21.5" is 2150.
+    pub fn encode(&self, text: &str, scale: f64) -> Result<Vec<u8>, GattError> {
+        let text = text.trim();
+        let invalid = |e: &dyn fmt::Display| GattError::InvalidValue(format!("{:?}: {}", text, e));
+        let integer = |min: i64, max: i64| -> Result<i64, GattError> {
+            let value: f64 = text.parse().map_err(|e| invalid(&e))?;
+            let raw = (value / scale).round();
+            if !raw.is_finite() || raw < min as f64 || raw > max as f64 {
+                return Err(invalid(&format!("out of range for {:?}", self)));
+            }
+            Ok(raw as i64)
+        };
+        Ok(match self {
+            ValueFormat::U8 => vec![integer(0, u8::MAX as i64)? as u8],
+            ValueFormat::U16 => (integer(0, u16::MAX as i64)? as u16).to_le_bytes().to_vec(),
+            ValueFormat::U32 => (integer(0, u32::MAX as i64)? as u32).to_le_bytes().to_vec(),
+            ValueFormat::I8 => vec![integer(i8::MIN as i64, i8::MAX as i64)? as i8 as u8],
+            ValueFormat::I16 => (integer(i16::MIN as i64, i16::MAX as i64)? as i16).to_le_bytes().to_vec(),
+            ValueFormat::I32 => (integer(i32::MIN as i64, i32::MAX as i64)? as i32).to_le_bytes().to_vec(),
+            ValueFormat::F32 => text.parse::<f32>().map_err(|e| invalid(&e))?.to_le_bytes().to_vec(),
+            ValueFormat::Utf8 => text.as_bytes().to_vec(),
+            ValueFormat::Bytes => {
+                let digits: String = text.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+                if !digits.len().is_multiple_of(2) {
+                    return Err(invalid(&"odd number of hex digits"));
+                }
+                (0..digits.len()).step_by(2)
+                    .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|e| invalid(&e)))
+                    .collect::<Result<_, _>>()?
+            },
+        })
+    }
+
+    /// Show the bytes of a value as text. Values that don't fit the format are shown as hex.
+    pub fn decode(&self, data: &[u8], scale: f64) -> String {
+        let int = |raw: i64| {
+            if scale == 1.0 { raw.to_string() } else { format!("{}", raw as f64 * scale) }
+        };
+        let hex = || data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(" ");
+        match (self, data.len()) {
+            (ValueFormat::U8, 1) => int(data[0] as i64),
+            (ValueFormat::U16, 2) => int(u16::from_le_bytes([data[0], data[1]]) as i64),
+            (ValueFormat::U32, 4) => int(u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as i64),
+            (ValueFormat::I8, 1) => int(data[0] as i8 as i64),
+            (ValueFormat::I16, 2) => int(i16::from_le_bytes([data[0], data[1]]) as i64),
+            (ValueFormat::I32, 4) => int(i32::from_le_bytes([data[0], data[1], data[2], data[3]]) as i64),
+            (ValueFormat::F32, 4) => f32::from_le_bytes([data[0], data[1], data[2], data[3]]).to_string(),
+            (ValueFormat::Utf8, _) => match std::str::from_utf8(data) {
+                Ok(s) => s.to_string(),
+                Err(_) => hex(),
+            },
+            _ => hex(),
+        }
+    }
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// A characteristic, i.e.
+/// ```toml
+/// [[service.characteristic]]
+/// name = "Temperature"
+/// uuid = "2a6e"
+/// properties = ["read", "notify"]
+/// format = "i16"
+/// scale = 0.01
+/// unit = "°C"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GattCharacteristic {
+    pub name: String,
+    pub uuid: String,
+    pub properties: Vec<Property>,
+    #[serde(default)]
+    pub format: ValueFormat,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub unit: String,
+    /// The value a simulated device starts with, as text in the format
+    pub initial: Option<String>,
+}
+
+impl GattCharacteristic {
+    pub fn has(&self, property: Property) -> bool {
+        self.properties.contains(&property)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GattService {
+    pub name: String,
+    pub uuid: String,
+    #[serde(default, rename = "characteristic")]
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+/// The contents of a project's gatt.toml: a list of `[[service]]` tables.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GattDescription {
+    #[serde(default, rename = "service")]
+    pub services: Vec<GattService>,
+}
+
+impl GattDescription {
+
+    /// Parse and check a description, normalizing its UUIDs.
+    pub fn from_toml_str(toml: &str) -> Result<Self, GattError> {
+        let mut description: Self = toml::from_str(toml).map_err(|e| GattError::InvalidDescription(e.to_string()))?;
+        let mut seen: Vec<String> = vec![];
+        for service in description.services.iter_mut() {
+            service.uuid = normalize_uuid(&service.uuid)?;
+            if seen.contains(&service.uuid) {
+                return Err(GattError::InvalidDescription(format!("service {} is declared twice", service.uuid)));
+            }
+            seen.push(service.uuid.clone());
+            let mut characteristics: Vec<String> = vec![];
+            for c in service.characteristics.iter_mut() {
+                c.uuid = normalize_uuid(&c.uuid)?;
+                if characteristics.contains(&c.uuid) {
+                    return Err(GattError::InvalidDescription(format!("characteristic {} is declared twice in {}", c.uuid, service.name)));
+                }
+                characteristics.push(c.uuid.clone());
+                if c.properties.is_empty() {
+                    return Err(GattError::InvalidDescription(format!("characteristic {} has no properties", c.name)));
+                }
+                if !(c.scale.is_finite() && c.scale > 0.0) {
+                    return Err(GattError::InvalidDescription(format!("characteristic {} needs a positive scale", c.name)));
+                }
+                if let Some(initial) = &c.initial {
+                    c.format.encode(initial, c.scale)?;
+                }
+            }
+        }
+        Ok(description)
+    }
+
+    /// Load the description in a project directory, if it has one.
+    pub fn load(project_dir: &Path) -> Option<Result<Self, GattError>> {
+        let path = project_dir.join(GATT_FILE_NAME);
+        if !path.exists() {
+            return None;
+        }
+        Some(fs::read_to_string(path).map_err(GattError::FsError).and_then(|s| Self::from_toml_str(&s)))
+    }
+
+    pub fn characteristic(&self, service: &str, characteristic: &str) -> Option<&GattCharacteristic> {
+        self.services.iter()
+            .find(|s| s.uuid == service)?
+            .characteristics.iter()
+            .find(|c| c.uuid == characteristic)
+    }
+}
+
+/// A service found on a device, with its characteristics' UUIDs and properties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredService {
+    pub uuid: String,
+    pub characteristics: Vec<(String, Vec<Property>)>,
+}
+
+/// A connection to a BLE peripheral's GATT server. UUIDs are in their full form.
+pub trait GattClient {
+    fn discover(&mut self) -> Result<Vec<DiscoveredService>, GattError>;
+    fn read(&mut self, service: &str, characteristic: &str) -> Result<Vec<u8>, GattError>;
+    fn write(&mut self, service: &str, characteristic: &str, data: &[u8], with_response: bool) -> Result<(), GattError>;
+    fn set_notify(&mut self, service: &str, characteristic: &str, enable: bool) -> Result<(), GattError>;
+    /// Wait up to `timeout` for a notification or indication, returning the service,
+    /// characteristic and value.
+    fn notification(&mut self, timeout: Duration) -> Result<Option<(String, String, Vec<u8>)>, GattError>;
+}
+
+/// The properties a device reports for a characteristic, in the order they're listed.
+pub fn properties(flags: CharPropFlags) -> Vec<Property> {
+    [
+        (CharPropFlags::READ, Property::Read),
+        (CharPropFlags::WRITE, Property::Write),
+        (CharPropFlags::WRITE_WITHOUT_RESPONSE, Property::WriteWithoutResponse),
+        (CharPropFlags::NOTIFY, Property::Notify),
+        (CharPropFlags::INDICATE, Property::Indicate),
+    ].into_iter().filter(|(flag, _)| flags.contains(*flag)).map(|(_, property)| property).collect()
+}
+
+fn parse_uuid(uuid: &str) -> Result<Uuid, GattError> {
+    Uuid::parse_str(uuid).map_err(|_| GattError::NotFound(short_uuid(uuid)))
+}
+
+impl GattClient for BleDevice {
+    fn discover(&mut self) -> Result<Vec<DiscoveredService>, GattError> {
+        Ok(self.services().into_iter().map(|s| DiscoveredService {
+            uuid: s.uuid.to_string(),
+            characteristics: s.characteristics.iter().map(|c| (c.uuid.to_string(), properties(c.properties))).collect(),
+        }).collect())
+    }
+
+    fn read(&mut self, service: &str, characteristic: &str) -> Result<Vec<u8>, GattError> {
+        Ok(BleDevice::read(self, parse_uuid(service)?, parse_uuid(characteristic)?)?)
+    }
+
+    fn write(&mut self, service: &str, characteristic: &str, data: &[u8], with_response: bool) -> Result<(), GattError> {
+        Ok(BleDevice::write(self, parse_uuid(service)?, parse_uuid(characteristic)?, data, with_response)?)
+    }
+
+    fn set_notify(&mut self, service: &str, characteristic: &str, enable: bool) -> Result<(), GattError> {
+        Ok(BleDevice::set_notify(self, parse_uuid(service)?, parse_uuid(characteristic)?, enable)?)
+    }
+
+    fn notification(&mut self, timeout: Duration) -> Result<Option<(String, String, Vec<u8>)>, GattError> {
+        let notification = BleDevice::notification(self, timeout)?;
+        Ok(notification.map(|(service, characteristic, value)| (service.to_string(), characteristic.to_string(), value)))
+    }
+}
+
+/// Connect to the device with the address or name over the shared BLE link.
+pub fn connect(address: &str) -> Result<Box<dyn GattClient + Send>, GattError> {
+    let device = BleDevice::connect(address)?;
+    info!("previewing the GATT services of {}", address);
+    Ok(Box::new(device))
+}
+
+/// A device that behaves as the description says, for trying out the panel (and a
+/// phone app's view of the services) before the firmware exists. Written values are
+/// sent back as notifications to subscribers, as if the firmware had updated them.
+pub struct SimulatedPeripheral {
+    description: GattDescription,
+    values: BTreeMap<(String, String), Vec<u8>>,
+    subscribed: Vec<(String, String)>,
+    pending: Vec<(String, String, Vec<u8>)>,
+}
+
+impl SimulatedPeripheral {
+
+    pub fn new(description: &GattDescription) -> Self {
+        let mut values = BTreeMap::new();
+        for service in description.services.iter() {
+            for c in service.characteristics.iter() {
+                let value = c.initial.as_ref().and_then(|v| c.format.encode(v, c.scale).ok()).unwrap_or_default();
+                values.insert((service.uuid.clone(), c.uuid.clone()), value);
+            }
+        }
+        Self { description: description.clone(), values, subscribed: vec![], pending: vec![] }
+    }
+
+    fn find(&self, service: &str, characteristic: &str, property: Property) -> Result<&GattCharacteristic, GattError> {
+        let c = self.description.characteristic(service, characteristic)
+            .ok_or_else(|| GattError::NotFound(short_uuid(characteristic)))?;
+        if !c.has(property) {
+            return Err(GattError::NotPermitted(format!("{} doesn't allow {:?}", c.name, property)));
+        }
+        Ok(c)
+    }
+}
+
+impl GattClient for SimulatedPeripheral {
+    fn discover(&mut self) -> Result<Vec<DiscoveredService>, GattError> {
+        Ok(self.description.services.iter().map(|s| DiscoveredService {
+            uuid: s.uuid.clone(),
+            characteristics: s.characteristics.iter().map(|c| (c.uuid.clone(), c.properties.clone())).collect(),
+        }).collect())
+    }
+
+    fn read(&mut self, service: &str, characteristic: &str) -> Result<Vec<u8>, GattError> {
+        self.find(service, characteristic, Property::Read)?;
+        Ok(self.values.get(&(service.to_string(), characteristic.to_string())).cloned().unwrap_or_default())
+    }
+
+    fn write(&mut self, service: &str, characteristic: &str, data: &[u8], with_response: bool) -> Result<(), GattError> {
+        let property = if with_response { Property::Write } else { Property::WriteWithoutResponse };
+        self.find(service, characteristic, property)?;
+        let key = (service.to_string(), characteristic.to_string());
+        if self.subscribed.contains(&key) {
+            self.pending.push((key.0.clone(), key.1.clone(), data.to_vec()));
+        }
+        self.values.insert(key, data.to_vec());
+        Ok(())
+    }
+
+    fn set_notify(&mut self, service: &str, characteristic: &str, enable: bool) -> Result<(), GattError> {
+        let c = self.description.characteristic(service, characteristic)
+            .ok_or_else(|| GattError::NotFound(short_uuid(characteristic)))?;
+        if !c.has(Property::Notify) && !c.has(Property::Indicate) {
+            return Err(GattError::NotPermitted(format!("{} doesn't notify", c.name)));
+        }
+        let key = (service.to_string(), characteristic.to_string());
+        self.subscribed.retain(|k| *k != key);
+        if enable {
+            self.subscribed.push(key);
+        }
+        Ok(())
+    }
+
+    fn notification(&mut self, _timeout: Duration) -> Result<Option<(String, String, Vec<u8>)>, GattError> {
+        Ok((!self.pending.is_empty()).then(|| self.pending.remove(0)))
+    }
+}
+
+/// A connected device, shared between the panel and the jobs talking to it.
+pub struct GattSession {
+    client: Mutex<Box<dyn GattClient + Send>>,
+    services: Mutex<Vec<DiscoveredService>>,
+    /// The last value seen of each characteristic, by (service, characteristic)
+    values: Mutex<BTreeMap<(String, String), Vec<u8>>>,
+    subscribed: Mutex<Vec<(String, String)>>,
+    log: Mutex<Vec<String>>,
+}
+
+impl GattSession {
+
+    pub fn new(client: Box<dyn GattClient + Send>) -> Self {
+        Self {
+            client: Mutex::new(client),
+            services: Mutex::new(vec![]),
+            values: Mutex::new(BTreeMap::new()),
+            subscribed: Mutex::new(vec![]),
+            log: Mutex::new(vec![]),
+        }
+    }
+
+    fn log(&self, entry: String) {
+        let mut log = self.log.lock().unwrap();
+        log.push(entry);
+        if log.len() > MAX_LOG_LEN {
+            log.remove(0);
+        }
+    }
+
+    pub fn services(&self) -> Vec<DiscoveredService> {
+        self.services.lock().unwrap().clone()
+    }
+
+    pub fn value(&self, service: &str, characteristic: &str) -> Option<Vec<u8>> {
+        self.values.lock().unwrap().get(&(service.to_string(), characteristic.to_string())).cloned()
+    }
+
+    pub fn is_subscribed(&self, service: &str, characteristic: &str) -> bool {
+        self.subscribed.lock().unwrap().contains(&(service.to_string(), characteristic.to_string()))
+    }
+
+    pub fn log_entries(&self) -> Vec<String> {
+        self.log.lock().unwrap().clone()
+    }
+
+    pub fn discover(&self) -> Result<(), GattError> {
+        let services = self.client.lock().unwrap().discover()?;
+        self.log(format!("discovered {} services", services.len()));
+        *self.services.lock().unwrap() = services;
+        Ok(())
+    }
+
+    pub fn read(&self, service: &str, characteristic: &str) -> Result<Vec<u8>, GattError> {
+        let value = self.client.lock().unwrap().read(service, characteristic)?;
+        self.log(format!("read {}: {:02x?}", short_uuid(characteristic), value));
+        self.values.lock().unwrap().insert((service.to_string(), characteristic.to_string()), value.clone());
+        Ok(value)
+    }
+
+    pub fn write(&self, service: &str, characteristic: &str, data: &[u8], with_response: bool) -> Result<(), GattError> {
+        self.client.lock().unwrap().write(service, characteristic, data, with_response)?;
+        self.log(format!("wrote {}: {:02x?}", short_uuid(characteristic), data));
+        Ok(())
+    }
+
+    pub fn set_notify(&self, service: &str, characteristic: &str, enable: bool) -> Result<(), GattError> {
+        self.client.lock().unwrap().set_notify(service, characteristic, enable)?;
+        let key = (service.to_string(), characteristic.to_string());
+        let mut subscribed = self.subscribed.lock().unwrap();
+        subscribed.retain(|k| *k != key);
+        if enable {
+            subscribed.push(key);
+        }
+        let action = if enable { "subscribed to" } else { "unsubscribed from" };
+        self.log(format!("{} {}", action, short_uuid(characteristic)));
+        Ok(())
+    }
+
+    /// Wait for a notification, recording its value. Returns whether one arrived.
+    pub fn poll_notification(&self, timeout: Duration) -> Result<bool, GattError> {
+        let Some((service, characteristic, value)) = self.client.lock().unwrap().notification(timeout)? else {
+            return Ok(false);
+        };
+        self.log(format!("notification from {}: {:02x?}", short_uuid(&characteristic), value));
+        self.values.lock().unwrap().insert((service, characteristic), value);
+        Ok(true)
+    }
+}
+
+/// Receive notifications until the job is cancelled.
+fn listen(session: &GattSession, job: &JobContext) -> Result<(), String> {
+    while !job.is_cancelled() {
+        if session.poll_notification(Duration::from_millis(100)).map_err(|e| e.to_string())? {
+            job.request_repaint();
+        } else {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+    Ok(())
+}
+
+/// The state of the GATT preview window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct GattPanel {
+    /// The BLE address or name of the device
+    pub address: String,
+    #[serde(skip)]
+    session: Option<Arc<GattSession>>,
+    #[serde(skip)]
+    simulated: bool,
+    /// The device being connected to, since scanning for it takes a while
+    #[serde(skip)]
+    connecting: Option<Receiver<Box<dyn GattClient + Send>>>,
+    #[serde(skip)]
+    listener: Option<JobId>,
+    /// The job of the last operation, to show its outcome
+    #[serde(skip)]
+    last_job: Option<JobId>,
+    /// The text typed into each characteristic's write field
+    #[serde(skip)]
+    inputs: BTreeMap<String, String>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl GattPanel {
+
+    fn open(&mut self, ctx: &egui::Context, client: Box<dyn GattClient + Send>) {
+        let session = Arc::new(GattSession::new(client));
+        let listening = session.clone();
+        self.listener = Some(jobs::shared(ctx).spawn("GATT notifications", Some(ctx), move |job| listen(&listening, job)));
+        self.session = Some(session);
+        self.run(ctx, "GATT discovery", |session| session.discover());
+    }
+
+    /// Connect to the device in a job, and open a session once it's connected.
+    fn start_connecting(&mut self, ctx: &egui::Context) {
+        let (tx, rx) = channel();
+        let address = self.address.clone();
+        self.last_job = Some(jobs::shared(ctx).spawn("GATT connect", Some(ctx), move |job| {
+            job.set_status(&format!("scanning for {}", address));
+            let client = connect(&address).map_err(|e| e.to_string())?;
+            // the panel may have stopped waiting, in which case the device is let go
+            let _ = tx.send(client);
+            Ok(())
+        }));
+        self.connecting = Some(rx);
+    }
+
+    fn close(&mut self, ctx: &egui::Context) {
+        if let Some(id) = self.listener.take() {
+            jobs::shared(ctx).cancel(id);
+        }
+        self.session = None;
+    }
+
+    /// Run an operation on the session in a job, since BLE operations block.
+    fn run<F>(&mut self, ctx: &egui::Context, name: &str, operation: F)
+    where
+        F: FnOnce(&GattSession) -> Result<(), GattError> + Send + 'static,
+    {
+        let Some(session) = self.session.clone() else {
+            return;
+        };
+        self.last_job = Some(jobs::shared(ctx).spawn(name, Some(ctx), move |_| operation(&session).map_err(|e| e.to_string())));
+    }
+
+    /// Show one characteristic of a discovered service.
+    fn display_characteristic(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, session: &GattSession, service: &str, characteristic: &(String, Vec<Property>), described: Option<&GattCharacteristic>) {
+        let (uuid, properties) = characteristic;
+        let (format, scale, unit) = described.map_or((ValueFormat::Bytes, 1.0, ""), |c| (c.format, c.scale, c.unit.as_str()));
+        match described {
+            Some(c) => ui.label(&c.name).on_hover_text(short_uuid(uuid)),
+            None => ui.monospace(short_uuid(uuid)),
+        };
+        let props: Vec<String> = properties.iter().map(|p| format!("{:?}", p).to_lowercase()).collect();
+        ui.label(props.join(" "));
+        match session.value(service, uuid) {
+            Some(value) => ui.monospace(format!("{} {}", format.decode(&value, scale), unit)),
+            None => ui.label("-"),
+        };
+        ui.horizontal(|ui| {
+            let (service, uuid) = (service.to_string(), uuid.to_string());
+            if properties.contains(&Property::Read) && ui.small_button("read").clicked() {
+                self.run(ctx, "GATT read", move |s| s.read(&service, &uuid).map(|_| ()));
+                return;
+            }
+            let writable = properties.contains(&Property::Write) || properties.contains(&Property::WriteWithoutResponse);
+            if writable {
+                let input = self.inputs.entry(uuid.clone()).or_default();
+                ui.add(egui::TextEdit::singleline(input).desired_width(80.0).hint_text(format!("{:?}", format).to_lowercase()));
+                if ui.small_button("write").clicked() {
+                    match format.encode(input, scale) {
+                        Ok(data) => {
+                            let with_response = properties.contains(&Property::Write);
+                            self.run(ctx, "GATT write", move |s| s.write(&service, &uuid, &data, with_response));
+                        },
+                        Err(e) => self.error = Some(e.to_string()),
+                    }
+                    return;
+                }
+            }
+            if properties.contains(&Property::Notify) || properties.contains(&Property::Indicate) {
+                let mut subscribed = session.is_subscribed(&service, &uuid);
+                if ui.checkbox(&mut subscribed, "notify").changed() {
+                    self.run(ctx, "GATT subscribe", move |s| s.set_notify(&service, &uuid, subscribed));
+                }
+            }
+        });
+        ui.end_row();
+    }
+
+    /// Show the connection controls and, once connected, the device's services.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, project_dir: Option<&Path>) {
+        let description = match project_dir.and_then(GattDescription::load) {
+            Some(Ok(description)) => description,
+            Some(Err(e)) => {
+                ui.colored_label(ui.style().visuals.error_fg_color, e.to_string());
+                return;
+            },
+            None => {
+                ui.label(format!("This project doesn't describe any GATT services. Add a {} file to the project to preview them.", GATT_FILE_NAME));
+                return;
+            },
+        };
+        if let Some(client) = self.connecting.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.connecting = None;
+            self.simulated = false;
+            self.open(ctx, client);
+        }
+        let connecting = self.last_job.map(|id| jobs::shared(ctx).state(id)) == Some(Some(JobState::Running)) && self.connecting.is_some();
+        ui.horizontal(|ui| {
+            if connecting {
+                ui.spinner();
+                ui.label(format!("connecting to {}", self.address));
+                if ui.button("cancel").clicked() {
+                    if let Some(id) = self.last_job.take() {
+                        jobs::shared(ctx).cancel(id);
+                    }
+                    self.connecting = None;
+                }
+            } else if self.session.is_some() {
+                let target = if self.simulated { "simulated device".to_string() } else { self.address.clone() };
+                ui.label(format!("connected to {}", target));
+                if ui.button("rediscover").clicked() {
+                    self.run(ctx, "GATT discovery", |s| s.discover());
+                }
+                if ui.button("disconnect").clicked() {
+                    self.close(ctx);
+                }
+            } else {
+                ui.label("device:");
+                ui.add(egui::TextEdit::singleline(&mut self.address).hint_text("AA:BB:CC:DD:EE:FF or name"));
+                if ui.add_enabled(!self.address.is_empty(), egui::Button::new("connect")).clicked() {
+                    self.error = None;
+                    self.start_connecting(ctx);
+                }
+                if ui.button("simulate").on_hover_text("act out the services in the description, without a device").clicked() {
+                    self.error = None;
+                    self.simulated = true;
+                    self.open(ctx, Box::new(SimulatedPeripheral::new(&description)));
+                }
+            }
+        });
+        if let Some(Some(JobState::Failed(e))) = self.last_job.map(|id| jobs::shared(ctx).state(id)) {
+            self.error = Some(e);
+            self.last_job = None;
+            self.connecting = None;
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(ui.style().visuals.error_fg_color, e);
+        }
+        let Some(session) = self.session.clone() else {
+            return;
+        };
+        ui.separator();
+        let services = session.services();
+        for service in services.iter() {
+            let described = description.services.iter().find(|s| s.uuid == service.uuid);
+            let title = match described {
+                Some(s) => format!("{} ({})", s.name, short_uuid(&s.uuid)),
+                None => short_uuid(&service.uuid),
+            };
+            egui::CollapsingHeader::new(title).id_source(&service.uuid).default_open(true).show(ui, |ui| {
+                egui::Grid::new(("gatt_characteristics", &service.uuid)).num_columns(4).striped(true).show(ui, |ui| {
+                    for characteristic in service.characteristics.iter() {
+                        let c = description.characteristic(&service.uuid, &characteristic.0);
+                        self.display_characteristic(ctx, ui, &session, &service.uuid, characteristic, c);
+                    }
+                });
+            });
+        }
+        // services the firmware should have, but the device doesn't
+        for missing in description.services.iter().filter(|s| !services.is_empty() && !services.iter().any(|d| d.uuid == s.uuid)) {
+            ui.colored_label(ui.style().visuals.warn_fg_color, format!("{} ({}) isn't on the device", missing.name, short_uuid(&missing.uuid)));
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(120.0).stick_to_bottom(true).show(ui, |ui| {
+            for entry in session.log_entries() {
+                ui.monospace(entry);
+            }
+        });
+    }
+}
@@ -0,0 +1,296 @@
+//! Title: Iron Coder Project Module - Health Check
+//! Description: A one-click audit for "it doesn't build on my machine": whether the
+//!   toolchain and target are installed, memory.x fits the board, a runner is set,
+//!   a probe can be reached and the dependencies resolve. The machine is looked at on a
+//!   job, and the findings are worked out from what was found, worst first, with a fix
+//!   or a command to run where there is one.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::board::units::{self, Memory};
+use crate::project::backend::Severity;
+use crate::project::compat;
+use crate::project::explain::Fix;
+use crate::project::sources::{DependencySources, SourceMode, VENDOR_DIR};
+
+/// A way to put a finding right.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Remedy {
+    /// A change to the project
+    Fix(Fix),
+    /// A command to run in the project directory, program first
+    Command(Vec<String>),
+}
+
+impl fmt::Display for Remedy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Remedy::Fix(fix) => write!(f, "{}", fix),
+            Remedy::Command(args) => write!(f, "run {}", args.join(" ")),
+        }
+    }
+}
+
+/// Something the health check found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub title: String,
+    pub detail: String,
+    pub remedy: Option<Remedy>,
+}
+
+impl Finding {
+    fn new(severity: Severity, title: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { severity, title: title.into(), detail: detail.into(), remedy: None }
+    }
+
+    fn with(mut self, remedy: Remedy) -> Self {
+        self.remedy = Some(remedy);
+        self
+    }
+}
+
+fn command(args: &[&str]) -> Remedy {
+    Remedy::Command(args.iter().map(|a| a.to_string()).collect())
+}
+
+/// What was found out about the machine. None means the tool couldn't be run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Facts {
+    /// The output of `rustc -vV`
+    pub rustc: Option<String>,
+    /// The toolchains rustup has installed
+    pub toolchains: Option<Vec<String>>,
+    /// The targets installed for the project's toolchain
+    pub targets: Option<Vec<String>>,
+    /// The probes probe-rs lists
+    pub probes: Option<Vec<String>>,
+    /// Why Cargo couldn't resolve the dependencies, if it couldn't
+    pub resolve_error: Option<String>,
+}
+
+/// Run a tool, returning its output if it succeeded.
+fn output(cmd: duct::Expression) -> Result<String, String> {
+    let out = cmd.stdout_capture().stderr_capture().unchecked().run().map_err(|e| e.to_string())?;
+    match out.status.success() {
+        true => Ok(String::from_utf8_lossy(&out.stdout).to_string()),
+        false => Err(String::from_utf8_lossy(&out.stderr).to_string()),
+    }
+}
+
+impl Facts {
+
+    /// Look at the machine, from the project directory so rust-toolchain.toml applies.
+    /// This runs Cargo and probe-rs, so call it from a job.
+    pub fn gather(project_dir: &Path) -> Self {
+        let lines = |s: String| s.lines().map(str::trim).filter(|l| !l.is_empty()).map(str::to_string).collect::<Vec<_>>();
+        let resolve_error = match project_dir.join("Cargo.toml").exists() {
+            true => output(duct::cmd!("cargo", "-Z", "unstable-options", "-C", project_dir, "metadata", "--format-version", "1")).err(),
+            false => None,
+        };
+        Self {
+            rustc: output(duct::cmd!("rustc", "-vV").dir(project_dir)).ok(),
+            toolchains: output(duct::cmd!("rustup", "toolchain", "list").dir(project_dir)).ok().map(lines),
+            targets: output(duct::cmd!("rustup", "target", "list", "--installed").dir(project_dir)).ok().map(lines),
+            // probe-rs lists each probe as "[<index>]: <description>"
+            probes: output(duct::cmd!("probe-rs", "list")).ok()
+                .map(|out| lines(out).into_iter().filter(|l| l.starts_with('[')).collect()),
+            resolve_error: resolve_error.map(|e| {
+                let errors: Vec<&str> = e.lines().filter(|l| l.trim_start().starts_with("error")).collect();
+                match errors.is_empty() {
+                    true => e.trim().to_string(),
+                    false => errors.join("\n"),
+                }
+            }),
+        }
+    }
+}
+
+/// The memory of the board the project is for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoardMemory {
+    pub name: String,
+    pub flash: Option<Memory>,
+    pub ram: Option<Memory>,
+}
+
+/// The value of a linker script size, i.e. "2048K - 0x100". None if it isn't one.
+pub fn parse_size(expr: &str) -> Option<u64> {
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    for token in expr.replace('+', " + ").replace('-', " - ").split_whitespace() {
+        match token {
+            "+" => sign = 1,
+            "-" => sign = -1,
+            term => {
+                let upper = term.to_ascii_uppercase();
+                let (digits, multiplier) = match upper.strip_suffix('K') {
+                    Some(d) => (d, 1024),
+                    None => match upper.strip_suffix('M') {
+                        Some(d) => (d, 1024 * 1024),
+                        None => (upper.as_str(), 1),
+                    },
+                };
+                let value = match digits.strip_prefix("0X") {
+                    Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+                    None => digits.parse::<i64>().ok()?,
+                };
+                total += sign * value * multiplier;
+            },
+        }
+    }
+    u64::try_from(total).ok()
+}
+
+/// The lengths of the memory regions in a memory.x, by name.
+pub fn memory_regions(script: &str) -> Vec<(String, u64)> {
+    // drop the comments, which often hold other chips' sizes
+    let mut text = String::new();
+    let mut rest = script;
+    while let Some(start) = rest.find("/*") {
+        text.push_str(&rest[..start]);
+        rest = rest[start..].find("*/").map_or("", |end| &rest[start + end + 2..]);
+    }
+    text.push_str(rest);
+    text.lines().filter_map(|line| {
+        let (name, attributes) = line.split_once(':')?;
+        let name = name.split_whitespace().next()?.to_string();
+        let length = attributes.split(',').find_map(|a| {
+            let (key, value) = a.split_once('=')?;
+            matches!(key.trim().to_ascii_uppercase().as_str(), "LENGTH" | "LEN" | "L").then(|| value.trim().trim_end_matches('}'))
+        })?;
+        Some((name, parse_size(length)?))
+    }).collect()
+}
+
+/// Compare a memory.x region with the board. Board files often give MiB as MB, so a
+/// region up to a sixteenth larger isn't counted as too big.
+fn check_region(findings: &mut Vec<Finding>, board: &BoardMemory, region: &str, length: u64, available: Option<Memory>) {
+    let Some(available) = available else {
+        return;
+    };
+    let locale = units::current_locale();
+    let (length_text, available_text) = (Memory::from_bytes(length).format(&locale), available.format(&locale));
+    if length > available.bytes() + available.bytes() / 16 {
+        findings.push(Finding::new(
+            Severity::Error,
+            format!("memory.x gives more {} than the {} has", region, board.name),
+            format!("{} is {}, but the board has {}; the firmware would be linked into memory that isn't there", region, length_text, available_text),
+        ));
+    } else if length < available.bytes() / 2 {
+        findings.push(Finding::new(
+            Severity::Note,
+            format!("memory.x only uses part of the {}", region),
+            format!("{} is {} of the board's {}; it may have been copied from another board", region, length_text, available_text),
+        ));
+    }
+}
+
+/// A memory.x to fill in, with the board's sizes.
+fn memory_x_template(board: Option<&BoardMemory>) -> String {
+    let size = |memory: Option<Memory>, default: u64| memory.map_or(default, |m| m.bytes()) / 1024;
+    let (flash, ram) = (size(board.and_then(|b| b.flash), 256 * 1024), size(board.and_then(|b| b.ram), 64 * 1024));
+    format!("MEMORY\n{{\n  /* Set the origins from the chip's datasheet */\n  FLASH : ORIGIN = 0x00000000, LENGTH = {}K\n  RAM   : ORIGIN = 0x20000000, LENGTH = {}K\n}}\n", flash, ram)
+}
+
+/// The tables of a Cargo config for the given target: its own, and the `cfg(..)` ones,
+/// which are taken to match.
+fn target_tables<'a>(config: &'a toml::Table, target: Option<&str>) -> Vec<&'a toml::Table> {
+    config.get("target").and_then(toml::Value::as_table).map(|targets| {
+        targets.iter()
+            .filter(|(name, _)| Some(name.as_str()) == target || name.starts_with("cfg("))
+            .filter_map(|(_, table)| table.as_table())
+            .collect()
+    }).unwrap_or_default()
+}
+
+/// Work out what's wrong with the project on this machine, worst first.
+pub fn check(project_dir: &Path, board: Option<&BoardMemory>, facts: &Facts, sources: &DependencySources) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let read = |file: &str| fs::read_to_string(project_dir.join(file)).ok();
+
+    // toolchain
+    let toolchain = read("rust-toolchain.toml")
+        .and_then(|t| t.parse::<toml::Table>().ok())
+        .and_then(|t| t.get("toolchain")?.get("channel")?.as_str().map(str::to_string));
+    match (&facts.rustc, &toolchain, &facts.toolchains) {
+        (None, _, _) => findings.push(Finding::new(Severity::Error, "Rust isn't installed", "rustc couldn't be run from the project directory")
+            .with(Remedy::Fix(Fix::OpenUrl { url: "https://rustup.rs".to_string(), label: Some("install rustup".to_string()) }))),
+        (Some(_), Some(channel), Some(installed)) if !installed.iter().any(|t| t.starts_with(channel.as_str())) => {
+            findings.push(Finding::new(Severity::Error, format!("toolchain {} isn't installed", channel), "rust-toolchain.toml pins it")
+                .with(command(&["rustup", "toolchain", "install", channel])));
+        },
+        _ => (),
+    }
+
+    // target and runner
+    let config = read(".cargo/config.toml").or_else(|| read(".cargo/config"))
+        .and_then(|c| c.parse::<toml::Table>().ok())
+        .unwrap_or_default();
+    let target = config.get("build").and_then(|b| b.get("target")).and_then(toml::Value::as_str);
+    match (target, &facts.targets) {
+        (None, _) => findings.push(Finding::new(Severity::Warning, "no build target is set", "without build.target in .cargo/config.toml, Cargo builds for this computer instead of the board")),
+        (Some(target), Some(installed)) if !installed.iter().any(|t| t == target) => {
+            findings.push(Finding::new(Severity::Error, format!("target {} isn't installed", target), "the standard library for the board's chip is missing")
+                .with(command(&["rustup", "target", "add", target])));
+        },
+        (Some(_), None) => findings.push(Finding::new(Severity::Note, "couldn't check the installed targets", "rustup couldn't be run")),
+        _ => (),
+    }
+    if !target_tables(&config, target).iter().any(|t| t.contains_key("runner")) {
+        findings.push(Finding::new(Severity::Warning, "no runner is set", "cargo run builds the firmware but doesn't load it onto the board without a runner, i.e. probe-rs run --chip <chip>")
+            .with(Remedy::Fix(Fix::OpenUrl { url: "https://doc.rust-lang.org/cargo/reference/config.html#targettriplerunner".to_string(), label: None })));
+    }
+
+    // memory.x
+    let dependencies = read("Cargo.toml").and_then(|m| compat::dependencies(&m).ok()).unwrap_or_default();
+    match read("memory.x") {
+        Some(script) => {
+            for (name, length) in memory_regions(&script) {
+                match (name.as_str(), board) {
+                    ("FLASH", Some(board)) => check_region(&mut findings, board, "flash", length, board.flash),
+                    ("RAM", Some(board)) => check_region(&mut findings, board, "RAM", length, board.ram),
+                    _ => (),
+                }
+            }
+        },
+        None if dependencies.iter().any(|(name, _)| name == "cortex-m-rt") => {
+            let fix = Fix::CreateFile { path: "memory.x".to_string(), contents: memory_x_template(board), label: Some("create a memory.x to fill in".to_string()) };
+            findings.push(Finding::new(Severity::Warning, "there's no memory.x", "cortex-m-rt needs one to link, unless the HAL or BSP provides it")
+                .with(Remedy::Fix(fix)));
+        },
+        None => (),
+    }
+
+    // probe
+    match &facts.probes {
+        None => findings.push(Finding::new(Severity::Warning, "probe-rs isn't installed", "it's needed to load and debug the firmware")
+            .with(command(&["cargo", "install", "probe-rs-tools", "--locked"]))),
+        Some(probes) if probes.is_empty() => findings.push(Finding::new(Severity::Warning, "no debug probe was found",
+            "check the cable, and on Linux that the probe's udev rules are installed")),
+        _ => (),
+    }
+
+    // dependencies
+    if !project_dir.join("Cargo.toml").exists() {
+        findings.push(Finding::new(Severity::Error, "there's no Cargo.toml", "generate the project template first"));
+    }
+    if let Err(e) = sources.validate() {
+        findings.push(Finding::new(Severity::Error, "the dependency sources aren't set up", e));
+    } else if sources.mode == SourceMode::Vendored && !project_dir.join(VENDOR_DIR).is_dir() {
+        findings.push(Finding::new(Severity::Error, "the dependencies haven't been vendored", "vendor them from the dependency sources menu"));
+    }
+    if let Some(error) = &facts.resolve_error {
+        findings.push(Finding::new(Severity::Error, "the dependencies can't be resolved", error.clone()));
+    }
+    for conflict in compat::Matrix::builtin().conflicts(&dependencies) {
+        findings.push(Finding::new(Severity::Warning, "crates that can't work together", conflict));
+    }
+
+    // the sort is stable, so findings of the same severity keep the order they're checked in
+    findings.sort_by_key(|f| f.severity);
+    findings
+}
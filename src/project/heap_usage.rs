@@ -0,0 +1,387 @@
+//! Title: Iron Coder Project Module - Heap Usage
+//! Description: Watching the heap of firmware that allocates with embedded-alloc. A module
+//!   is written to `src/heap_usage.rs` that gives the allocator its memory and counts
+//!   what's in use, its high-water mark and the allocations that failed, and sends them
+//!   over RTT once a report period. The panel attaches to the board with probe-rs, reads
+//!   the reports and warns when the heap is close to full or too big for the board's RAM.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::units::Memory;
+use crate::project::explain;
+use crate::project::rtt::RttAttachment;
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "heap_usage.rs";
+/// The word a report line starts with.
+const REPORT_PREFIX: &str = "heap ";
+/// The smallest heap the allocator can be given, in bytes.
+const MIN_HEAP_BYTES: u32 = 64;
+/// The share of the heap in use at its high-water mark that gets a warning, in percent.
+const NEARLY_FULL_PERCENT: f64 = 90.0;
+/// The share of the RAM a heap can take before it gets a warning, in percent.
+const RAM_SHARE_PERCENT: f64 = 75.0;
+/// The crates the module uses.
+const DEPENDENCIES: [(&str, &str); 3] = [("embedded-alloc", "0.6"), ("critical-section", "1"), ("rtt-target", "0.6")];
+
+/// How to hook the generated module into the firmware.
+pub const INSTRUCTIONS: &str =
+"// the module is the global allocator, so take out any other #[global_allocator]
+extern crate alloc;
+mod heap_usage;
+
+rtt_target::rtt_init_print!();
+heap_usage::init();
+loop {
+    heap_usage::poll(timer.get_counter_low());
+    // ...
+}";
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum HeapUsageError {
+    /// The module or Cargo.toml couldn't be written
+    IoError(io::Error),
+    /// The heap can't be that size
+    BadSize(String),
+    /// Cargo.toml couldn't be updated
+    BadManifest(String),
+}
+
+impl fmt::Display for HeapUsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeapUsageError::IoError(e) => write!(f, "{}", e),
+            HeapUsageError::BadSize(e) => write!(f, "{}", e),
+            HeapUsageError::BadManifest(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for HeapUsageError {
+    fn from(e: io::Error) -> Self {
+        HeapUsageError::IoError(e)
+    }
+}
+
+/// How big the heap is and how often to report on it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct HeapUsageConfig {
+    pub enabled: bool,
+    /// The size of the heap, in bytes
+    pub heap_bytes: u32,
+    pub report_ms: u32,
+}
+
+impl Default for HeapUsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            heap_bytes: 16 * 1024,
+            report_ms: 1000,
+        }
+    }
+}
+
+/// A warning about the heap's size for a board with `ram` of RAM, if there's one.
+pub fn ram_warning(heap_bytes: u32, ram: Option<Memory>) -> Option<String> {
+    let ram = ram?.bytes();
+    let share = heap_bytes as f64 / ram as f64 * 100.0;
+    if heap_bytes as u64 >= ram {
+        Some(format!("the heap is bigger than the board's {} bytes of RAM", ram))
+    } else if share > RAM_SHARE_PERCENT {
+        Some(format!("the heap takes {:.0}% of the RAM, which leaves little for the stack and statics", share))
+    } else {
+        None
+    }
+}
+
+/// The allocator module for a heap of the configured size.
+pub fn generate_module(config: &HeapUsageConfig) -> Result<String, HeapUsageError> {
+    if config.heap_bytes < MIN_HEAP_BYTES {
+        return Err(HeapUsageError::BadSize(format!("the heap needs at least {} bytes", MIN_HEAP_BYTES)));
+    }
+    Ok(format!(
+"//! Heap usage counters, generated by Iron Coder. Don't edit this file: it's written
+//! again before each build.
+//!
+//! The allocator is embedded-alloc's, wrapped to count the bytes in use, their
+//! high-water mark and the allocations that failed. The counters are updated in a
+//! critical section, which the allocator takes anyway.
+
+#![allow(dead_code)]
+
+use core::alloc::{{GlobalAlloc, Layout}};
+use core::cell::Cell;
+use core::mem::MaybeUninit;
+
+use critical_section::Mutex;
+use embedded_alloc::LlffHeap as Heap;
+
+/// The size of the heap, in bytes.
+pub const HEAP_SIZE: usize = {heap_bytes};
+/// How often a report goes out over RTT, in microseconds.
+const REPORT_US: u32 = {report_us};
+
+struct Counters {{
+    used: Cell<usize>,
+    peak: Cell<usize>,
+    failures: Cell<u32>,
+    largest_failed: Cell<usize>,
+    last_report: Cell<u32>,
+}}
+
+static COUNTERS: Mutex<Counters> = Mutex::new(Counters {{
+    used: Cell::new(0),
+    peak: Cell::new(0),
+    failures: Cell::new(0),
+    largest_failed: Cell::new(0),
+    last_report: Cell::new(0),
+}});
+
+/// The allocator, counting what it hands out.
+pub struct Counted {{
+    heap: Heap,
+}}
+
+unsafe impl GlobalAlloc for Counted {{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {{
+        let ptr = self.heap.alloc(layout);
+        critical_section::with(|cs| {{
+            let counters = COUNTERS.borrow(cs);
+            if ptr.is_null() {{
+                counters.failures.set(counters.failures.get().wrapping_add(1));
+                counters.largest_failed.set(counters.largest_failed.get().max(layout.size()));
+            }} else {{
+                let used = counters.used.get() + layout.size();
+                counters.used.set(used);
+                counters.peak.set(counters.peak.get().max(used));
+            }}
+        }});
+        ptr
+    }}
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {{
+        self.heap.dealloc(ptr, layout);
+        critical_section::with(|cs| {{
+            let counters = COUNTERS.borrow(cs);
+            counters.used.set(counters.used.get().saturating_sub(layout.size()));
+        }});
+    }}
+}}
+
+#[global_allocator]
+static HEAP: Counted = Counted {{ heap: Heap::empty() }};
+
+/// Give the allocator its memory. Call it once, before anything is allocated.
+pub fn init() {{
+    static mut MEMORY: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
+    // SAFETY: init is called once, so nothing else uses MEMORY
+    unsafe {{ HEAP.heap.init(core::ptr::addr_of_mut!(MEMORY) as usize, HEAP_SIZE) }}
+}}
+
+/// Send a report if the report period is up, with a clock in microseconds that wraps
+/// round at `u32::MAX`.
+pub fn poll(now_us: u32) {{
+    let due = critical_section::with(|cs| {{
+        let last = &COUNTERS.borrow(cs).last_report;
+        let due = now_us.wrapping_sub(last.get()) >= REPORT_US;
+        if due {{
+            last.set(now_us);
+        }}
+        due
+    }});
+    if due {{
+        report();
+    }}
+}}
+
+/// Send the counters over RTT. The high-water mark and failures count from reset.
+pub fn report() {{
+    let (used, peak, failures, largest_failed) = critical_section::with(|cs| {{
+        let counters = COUNTERS.borrow(cs);
+        (counters.used.get(), counters.peak.get(), counters.failures.get(), counters.largest_failed.get())
+    }});
+    rtt_target::rprintln!(
+        \"heap size={{}} used={{}} peak={{}} failures={{}} largest_failed={{}}\",
+        HEAP_SIZE, used, peak, failures, largest_failed,
+    );
+}}
+", heap_bytes = config.heap_bytes, report_us = config.report_ms.saturating_mul(1000)))
+}
+
+/// Regenerate the module if the heap settings changed, and make sure the project depends
+/// on the crates it uses. Returns whether it was written. Nothing is written unless it's
+/// enabled.
+pub fn regenerate(config: &HeapUsageConfig, project_dir: &Path) -> Result<bool, HeapUsageError> {
+    if !config.enabled {
+        return Ok(false);
+    }
+    let code = generate_module(config)?;
+    let path = project_dir.join("src").join(MODULE_FILE_NAME);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == code) {
+        return Ok(false);
+    }
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(&path, code)?;
+    let manifest_path = project_dir.join("Cargo.toml");
+    if let Ok(manifest) = fs::read_to_string(&manifest_path) {
+        let mut updated = manifest.clone();
+        for (name, version) in DEPENDENCIES {
+            updated = explain::add_dependency(&updated, name, version, &[]).map_err(HeapUsageError::BadManifest)?;
+        }
+        if updated != manifest {
+            fs::write(&manifest_path, updated)?;
+        }
+    }
+    Ok(true)
+}
+
+/// A report sent by the generated module, i.e.
+/// `heap size=16384 used=2048 peak=6144 failures=0 largest_failed=0`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeapReport {
+    pub size: u32,
+    pub used: u32,
+    /// The most that's been in use since reset
+    pub peak: u32,
+    /// How many allocations failed since reset
+    pub failures: u32,
+    /// The size of the biggest allocation that failed
+    pub largest_failed: u32,
+}
+
+impl HeapReport {
+
+    /// Read a report out of a line of RTT output, which probe-rs may have put a prefix
+    /// in front of.
+    pub fn parse(line: &str) -> Option<Self> {
+        let start = line.find(REPORT_PREFIX)?;
+        let mut report = HeapReport::default();
+        for word in line[start + REPORT_PREFIX.len()..].split_whitespace() {
+            let (key, value) = word.split_once('=')?;
+            let value = value.parse().ok()?;
+            match key {
+                "size" => report.size = value,
+                "used" => report.used = value,
+                "peak" => report.peak = value,
+                "failures" => report.failures = value,
+                "largest_failed" => report.largest_failed = value,
+                _ => return None,
+            }
+        }
+        (report.size > 0).then_some(report)
+    }
+
+    /// An amount of the heap as a share of it, in percent.
+    pub fn percent(&self, bytes: u32) -> f64 {
+        bytes as f64 / self.size as f64 * 100.0
+    }
+
+    /// What's worth warning about in the report.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if self.failures > 0 {
+            warnings.push(format!("{} allocations failed, the largest of {} bytes", self.failures, self.largest_failed));
+        }
+        if self.percent(self.peak) >= NEARLY_FULL_PERCENT {
+            warnings.push(format!("the heap has been {:.0}% full", self.percent(self.peak)));
+        }
+        warnings
+    }
+}
+
+/// The state of the heap usage window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HeapUsagePanel {
+    #[serde(flatten)]
+    rtt: RttAttachment,
+    #[serde(skip)]
+    latest: Option<HeapReport>,
+}
+
+impl HeapUsagePanel {
+
+    /// Take in a line of RTT output.
+    pub fn push_line(&mut self, line: &str) {
+        if let Some(report) = HeapReport::parse(line) {
+            self.latest = Some(report);
+        }
+    }
+
+    pub fn latest(&self) -> Option<&HeapReport> {
+        self.latest.as_ref()
+    }
+
+    fn display_config(&mut self, ui: &mut egui::Ui, config: &mut HeapUsageConfig, ram: Option<Memory>) {
+        ui.checkbox(&mut config.enabled, "write src/heap_usage.rs before each build");
+        egui::Grid::new("heap_usage_config").num_columns(2).show(ui, |ui| {
+            ui.label("heap size:");
+            ui.add(egui::DragValue::new(&mut config.heap_bytes).clamp_range(MIN_HEAP_BYTES..=u32::MAX).speed(64).suffix(" bytes"));
+            ui.end_row();
+            ui.label("report every:");
+            ui.add(egui::DragValue::new(&mut config.report_ms).clamp_range(10..=60_000).suffix(" ms"));
+            ui.end_row();
+        });
+        if let Some(warning) = ram_warning(config.heap_bytes, ram) {
+            ui.colored_label(ui.style().visuals.warn_fg_color, warning);
+        }
+        ui.collapsing("how to hook it up", |ui| {
+            let mut code = INSTRUCTIONS.to_string();
+            ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+        });
+    }
+
+    fn display_report(&self, ui: &mut egui::Ui, ram: Option<Memory>) {
+        let Some(report) = &self.latest else {
+            ui.label("waiting for a report from the firmware");
+            return;
+        };
+        egui::Grid::new("heap_usage_report").num_columns(2).show(ui, |ui| {
+            for (label, bytes) in [("in use", report.used), ("high-water mark", report.peak)] {
+                ui.label(label);
+                let share = report.percent(bytes);
+                ui.add(egui::ProgressBar::new((share / 100.0) as f32)
+                    .text(format!("{} of {} bytes ({:.1}%)", bytes, report.size, share))
+                    .desired_width(220.0));
+                ui.end_row();
+            }
+            if let Some(ram) = ram {
+                ui.label("heap of RAM");
+                ui.label(format!("{:.1}%", report.size as f64 / ram.bytes() as f64 * 100.0));
+                ui.end_row();
+            }
+            ui.label("failed allocations");
+            ui.label(report.failures.to_string());
+            ui.end_row();
+        });
+        for warning in report.warnings().into_iter().chain(ram_warning(report.size, ram)) {
+            ui.colored_label(ui.style().visuals.warn_fg_color, warning);
+        }
+    }
+
+    /// Show the heap settings and what the firmware reports. `board` is the main board,
+    /// which names the chip for probe-rs and has the RAM to compare the heap with.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, config: &mut HeapUsageConfig, board: Option<&Board>, project_dir: Option<&Path>) {
+        for line in self.rtt.poll(ctx) {
+            self.push_line(&line);
+        }
+        let ram = board.and_then(Board::ram);
+        self.display_config(ui, config, ram);
+        ui.separator();
+        if self.rtt.display(ctx, ui, "RTT heap usage", board, project_dir) {
+            self.latest = None;
+        }
+        if self.rtt.is_running(ctx) || self.latest.is_some() {
+            self.display_report(ui, ram);
+        }
+    }
+}
@@ -0,0 +1,740 @@
+//! Title: Iron Coder Project Module - Instruments
+//! Description: Bench instruments (power supplies, multimeters) spoken to in SCPI over
+//!   a raw TCP socket or a serial port. Instruments are the `instrument` devices of the
+//!   project's fixture, and test sequences address them by role: set a supply's voltage,
+//!   switch its output, take a measurement. Measurements are published on the event bus
+//!   as the instrument's telemetry, so they're recorded and forwarded alongside the
+//!   telemetry of the device under test.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+use crate::project::fixture::{self, DeviceKind, Fixture, FixtureDevice, FoundDevice, FIXTURE_FILE_NAME};
+use crate::project::mcumgr::{SerialLink, SerialPort};
+
+/// How long to wait for an instrument to answer a query. Some multimeters take a few
+/// seconds to range.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait for a networked instrument to accept the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// SCPI's "not a number" and overload reading.
+const OVERLOAD: f64 = 9.9e37;
+/// The most errors read from an instrument's error queue at once.
+const MAX_QUEUED_ERRORS: usize = 16;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum InstrumentError {
+    /// The connection couldn't be opened, read or written
+    IoError(io::Error),
+    /// The instrument didn't answer in time
+    Timeout,
+    /// The instrument isn't on the bench
+    NotFound(String),
+    /// The instrument put errors in its error queue
+    Rejected(Vec<String>),
+    /// The instrument answered with something that isn't a reading
+    BadResponse(String),
+}
+
+impl fmt::Display for InstrumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstrumentError::IoError(e) => write!(f, "connection error: {}", e),
+            InstrumentError::Timeout => write!(f, "the instrument didn't respond"),
+            InstrumentError::NotFound(e) => write!(f, "{}", e),
+            InstrumentError::Rejected(errors) => write!(f, "the instrument reported {}", errors.join("; ")),
+            InstrumentError::BadResponse(e) => write!(f, "unexpected response: {:?}", e),
+        }
+    }
+}
+
+impl From<io::Error> for InstrumentError {
+    fn from(e: io::Error) -> Self {
+        InstrumentError::IoError(e)
+    }
+}
+
+/// A raw SCPI socket, i.e. port 5025 of a LAN instrument.
+pub struct TcpLink {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl TcpLink {
+    pub fn connect(host: &str, port: u16) -> io::Result<Self> {
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", host));
+        for address in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    return Ok(Self { stream, buffer: vec![] });
+                },
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl SerialLink for TcpLink {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.stream.write_all(data)?;
+        self.stream.flush()
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 1024];
+        loop {
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                    line.pop();
+                }
+                return Ok(Some(line));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the instrument closed the connection")),
+                Ok(n) => self.buffer.extend_from_slice(&buf[..n]),
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// An instrument that speaks SCPI.
+pub struct Instrument {
+    link: Box<dyn SerialLink>,
+}
+
+impl Instrument {
+
+    pub fn new(link: Box<dyn SerialLink>) -> Self {
+        Self { link }
+    }
+
+    /// Connect to a fixture's instrument where it was found: over the network if it has
+    /// an address, otherwise on its serial port.
+    pub fn connect(device: &FixtureDevice, found: &FoundDevice) -> Result<Self, InstrumentError> {
+        if let Some(network) = &found.network {
+            return Ok(Self::new(Box::new(TcpLink::connect(&network.host, network.port)?)));
+        }
+        if let Some(port) = &found.port {
+            return Ok(Self::new(Box::new(SerialPort::open(&port.path)?)));
+        }
+        let why = match found.problems.is_empty() {
+            true => "it has no serial port or network address".to_string(),
+            false => found.problems.join("; "),
+        };
+        Err(InstrumentError::NotFound(format!("{} can't be reached: {}", device.role, why)))
+    }
+
+    /// Send a command that has no response.
+    pub fn command(&mut self, command: &str) -> Result<(), InstrumentError> {
+        self.link.write(format!("{}\n", command.trim()).as_bytes())?;
+        Ok(())
+    }
+
+    /// Send a query and wait for its response.
+    pub fn query(&mut self, query: &str) -> Result<String, InstrumentError> {
+        self.command(query)?;
+        match self.link.read_line(RESPONSE_TIMEOUT)? {
+            Some(line) => Ok(String::from_utf8_lossy(&line).trim().to_string()),
+            None => Err(InstrumentError::Timeout),
+        }
+    }
+
+    /// The manufacturer, model, serial number and firmware version.
+    pub fn identify(&mut self) -> Result<String, InstrumentError> {
+        self.query("*IDN?")
+    }
+
+    /// Empty the instrument's error queue, failing if there was anything in it.
+    pub fn check_errors(&mut self) -> Result<(), InstrumentError> {
+        let mut errors = vec![];
+        while errors.len() < MAX_QUEUED_ERRORS {
+            let error = self.query("SYST:ERR?")?;
+            let code = error.split(',').next().unwrap_or_default().trim();
+            match code.parse::<i32>() {
+                Ok(0) => break,
+                Ok(_) => errors.push(error),
+                Err(_) => return Err(InstrumentError::BadResponse(error)),
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(InstrumentError::Rejected(errors)),
+        }
+    }
+
+    /// Take a reading.
+    pub fn measure(&mut self, quantity: Quantity) -> Result<f64, InstrumentError> {
+        parse_reading(&self.query(quantity.query())?)
+    }
+}
+
+/// Parse the first value of a SCPI reading, i.e. `+3.30120000E+00`.
+pub fn parse_reading(response: &str) -> Result<f64, InstrumentError> {
+    let first = response.split(',').next().unwrap_or_default().trim();
+    match first.parse::<f64>() {
+        Ok(value) if value.abs() >= OVERLOAD => Err(InstrumentError::BadResponse(format!("{} (overload)", first))),
+        Ok(value) => Ok(value),
+        Err(_) => Err(InstrumentError::BadResponse(response.to_string())),
+    }
+}
+
+/// What a measurement step reads.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantity {
+    #[default]
+    DcVoltage,
+    DcCurrent,
+    AcVoltage,
+    Resistance,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Quantity::DcVoltage => write!(f, "DC voltage"),
+            Quantity::DcCurrent => write!(f, "DC current"),
+            Quantity::AcVoltage => write!(f, "AC voltage"),
+            Quantity::Resistance => write!(f, "resistance"),
+        }
+    }
+}
+
+impl Quantity {
+
+    pub fn query(&self) -> &'static str {
+        match self {
+            Quantity::DcVoltage => "MEAS:VOLT:DC?",
+            Quantity::DcCurrent => "MEAS:CURR:DC?",
+            Quantity::AcVoltage => "MEAS:VOLT:AC?",
+            Quantity::Resistance => "MEAS:RES?",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Quantity::DcVoltage | Quantity::AcVoltage => "V",
+            Quantity::DcCurrent => "A",
+            Quantity::Resistance => "Ω",
+        }
+    }
+}
+
+/// One step of a test sequence, i.e.
+/// ```toml
+/// [[instrument_sequences.steps]]
+/// step = "set_voltage"
+/// instrument = "psu"
+/// channel = 1
+/// volts = 3.3
+/// ```
+/// Instruments are named by their role in the fixture. The channel is only needed for
+/// supplies with more than one output.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    SetVoltage { instrument: String, channel: Option<u8>, volts: f64 },
+    SetCurrentLimit { instrument: String, channel: Option<u8>, amps: f64 },
+    Output { instrument: String, channel: Option<u8>, on: bool },
+    /// Take a reading and publish it as the instrument's telemetry, under `name`
+    Measure { instrument: String, channel: Option<u8>, quantity: Quantity, name: String },
+    /// Any other SCPI command. Queries are answered in the log.
+    Command { instrument: String, command: String },
+    Wait { ms: u64 },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let on_channel = |channel: &Option<u8>| channel.map(|c| format!(" on channel {}", c)).unwrap_or_default();
+        match self {
+            Step::SetVoltage { instrument, channel, volts } => write!(f, "set {}{} to {} V", instrument, on_channel(channel), volts),
+            Step::SetCurrentLimit { instrument, channel, amps } => write!(f, "limit {}{} to {} A", instrument, on_channel(channel), amps),
+            Step::Output { instrument, channel, on } => write!(f, "turn {} output{} {}", instrument, on_channel(channel), if *on { "on" } else { "off" }),
+            Step::Measure { instrument, channel, quantity, name } => write!(f, "measure {} as {} with {}{}", quantity, name, instrument, on_channel(channel)),
+            Step::Command { instrument, command } => write!(f, "send {} to {}", command, instrument),
+            Step::Wait { ms } => write!(f, "wait {} ms", ms),
+        }
+    }
+}
+
+impl Step {
+
+    /// The role of the instrument the step uses.
+    pub fn instrument(&self) -> Option<&str> {
+        match self {
+            Step::SetVoltage { instrument, .. } | Step::SetCurrentLimit { instrument, .. }
+            | Step::Output { instrument, .. } | Step::Measure { instrument, .. }
+            | Step::Command { instrument, .. } => Some(instrument),
+            Step::Wait { .. } => None,
+        }
+    }
+
+    /// A step of each kind, to start from when adding one.
+    pub fn templates(instrument: &str) -> Vec<Step> {
+        let instrument = instrument.to_string();
+        vec![
+            Step::SetVoltage { instrument: instrument.clone(), channel: None, volts: 3.3 },
+            Step::SetCurrentLimit { instrument: instrument.clone(), channel: None, amps: 0.5 },
+            Step::Output { instrument: instrument.clone(), channel: None, on: true },
+            Step::Measure { instrument: instrument.clone(), channel: None, quantity: Quantity::DcVoltage, name: "voltage".to_string() },
+            Step::Command { instrument, command: "*RST".to_string() },
+            Step::Wait { ms: 500 },
+        ]
+    }
+
+    /// The kind of step, as it's written in the project file.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Step::SetVoltage { .. } => "set_voltage",
+            Step::SetCurrentLimit { .. } => "set_current_limit",
+            Step::Output { .. } => "output",
+            Step::Measure { .. } => "measure",
+            Step::Command { .. } => "command",
+            Step::Wait { .. } => "wait",
+        }
+    }
+}
+
+/// A named list of steps.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct InstrumentSequence {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+impl InstrumentSequence {
+
+    /// Check the steps against the instruments in the fixture.
+    pub fn validate(&self, fixture: &Fixture) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err(format!("{} has no steps", self.name));
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            let invalid = |e: String| Err(format!("step {} ({}): {}", i + 1, step, e));
+            if let Some(role) = step.instrument() {
+                match fixture.device(role) {
+                    None => return invalid(format!("the fixture has no device {:?}", role)),
+                    Some(device) if device.kind != DeviceKind::Instrument => return invalid(format!("{} isn't an instrument", role)),
+                    Some(_) => (),
+                }
+            }
+            match step {
+                Step::SetVoltage { volts: value, .. } | Step::SetCurrentLimit { amps: value, .. } if !(value.is_finite() && *value >= 0.0) => {
+                    return invalid("needs a value of 0 or more".to_string());
+                },
+                Step::Measure { name, .. } if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '=' || c == ',') => {
+                    return invalid(format!("{:?} can't name a measurement", name));
+                },
+                Step::Command { command, .. } if command.trim().is_empty() || command.contains('\n') => {
+                    return invalid("needs a single SCPI command".to_string());
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// The roles of the instruments the sequence uses.
+    pub fn instruments(&self) -> BTreeSet<String> {
+        self.steps.iter().filter_map(Step::instrument).map(str::to_string).collect()
+    }
+}
+
+/// What a step produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Done,
+    /// A named reading, with its unit
+    Reading(String, f64, &'static str),
+    /// The answer to a query
+    Answer(String),
+}
+
+/// The connected instruments of a running sequence. Outputs a sequence turns on are
+/// remembered so they can be turned off again if it fails or is stopped.
+pub struct Bench {
+    instruments: BTreeMap<String, Instrument>,
+    powered: BTreeSet<(String, Option<u8>)>,
+}
+
+impl Bench {
+
+    pub fn new(instruments: BTreeMap<String, Instrument>) -> Self {
+        Self { instruments, powered: BTreeSet::new() }
+    }
+
+    /// Run a step. Waits are left to the caller, so they can be cancelled.
+    pub fn run(&mut self, step: &Step) -> Result<Outcome, InstrumentError> {
+        let Some(role) = step.instrument() else {
+            return Ok(Outcome::Done);
+        };
+        let instrument = self.instruments.get_mut(role)
+            .ok_or_else(|| InstrumentError::NotFound(format!("{} isn't connected", role)))?;
+        let select = |instrument: &mut Instrument, channel: &Option<u8>| match channel {
+            Some(channel) => instrument.command(&format!("INST:NSEL {}", channel)),
+            None => Ok(()),
+        };
+        let outcome = match step {
+            Step::SetVoltage { channel, volts, .. } => {
+                select(instrument, channel)?;
+                instrument.command(&format!("VOLT {}", volts))?;
+                Outcome::Done
+            },
+            Step::SetCurrentLimit { channel, amps, .. } => {
+                select(instrument, channel)?;
+                instrument.command(&format!("CURR {}", amps))?;
+                Outcome::Done
+            },
+            Step::Output { channel, on, .. } => {
+                select(instrument, channel)?;
+                instrument.command(if *on { "OUTP ON" } else { "OUTP OFF" })?;
+                match on {
+                    true => self.powered.insert((role.to_string(), *channel)),
+                    false => self.powered.remove(&(role.to_string(), *channel)),
+                };
+                Outcome::Done
+            },
+            Step::Measure { channel, quantity, name, .. } => {
+                select(instrument, channel)?;
+                let value = instrument.measure(*quantity)?;
+                Outcome::Reading(name.clone(), value, quantity.unit())
+            },
+            Step::Command { command, .. } if command.trim().ends_with('?') => Outcome::Answer(instrument.query(command)?),
+            Step::Command { command, .. } => {
+                instrument.command(command)?;
+                Outcome::Done
+            },
+            Step::Wait { .. } => Outcome::Done,
+        };
+        instrument.check_errors()?;
+        Ok(outcome)
+    }
+
+    /// Turn off the outputs the sequence turned on, best effort.
+    pub fn power_down(&mut self) -> Vec<String> {
+        let mut failures = vec![];
+        for (role, channel) in std::mem::take(&mut self.powered) {
+            let step = Step::Output { instrument: role.clone(), channel, on: false };
+            if let Err(e) = self.run(&step) {
+                failures.push(format!("couldn't turn {} off: {}", role, e));
+            }
+        }
+        failures
+    }
+}
+
+/// Find the fixture's instruments and connect to each one a sequence uses, before any
+/// step is run.
+fn connect_all(fixture: &Fixture, roles: &BTreeSet<String>) -> Result<BTreeMap<String, Instrument>, InstrumentError> {
+    let resolution = fixture.resolve(&fixture::serial_ports(), &[]);
+    let mut instruments = BTreeMap::new();
+    for role in roles.iter() {
+        let device = fixture.device(role).ok_or_else(|| InstrumentError::NotFound(format!("the fixture has no device {}", role)))?;
+        let found = resolution.devices.get(role).cloned().unwrap_or_default();
+        instruments.insert(role.clone(), Instrument::connect(device, &found)?);
+    }
+    Ok(instruments)
+}
+
+/// Run a sequence, publishing its readings as the telemetry of their instruments.
+fn run_sequence(sequence: InstrumentSequence, fixture: Fixture, bus: events::EventBus, log: Arc<Mutex<Vec<String>>>, job: &JobContext) -> Result<(), String> {
+    let say = |line: String| log.lock().unwrap().push(line);
+    job.set_status("connecting");
+    let mut bench = Bench::new(connect_all(&fixture, &sequence.instruments()).map_err(|e| e.to_string())?);
+    let mut result = Ok(());
+    for (i, step) in sequence.steps.iter().enumerate() {
+        if job.is_cancelled() {
+            result = Err("stopped".to_string());
+            break;
+        }
+        job.set_status(&step.to_string());
+        job.set_progress(i as f32 / sequence.steps.len() as f32);
+        if let Step::Wait { ms } = step {
+            let until = Instant::now() + Duration::from_millis(*ms);
+            while Instant::now() < until && !job.is_cancelled() {
+                std::thread::sleep(until.saturating_duration_since(Instant::now()).min(Duration::from_millis(50)));
+            }
+            continue;
+        }
+        match bench.run(step) {
+            Ok(Outcome::Done) => say(format!("{}: done", step)),
+            Ok(Outcome::Reading(name, value, unit)) => {
+                let device = step.instrument().unwrap_or_default().to_string();
+                say(format!("{}: {} = {} {}", device, name, value, unit));
+                bus.publish(Event::DeviceTelemetry { device, line: format!("{}={}", name, value) });
+            },
+            Ok(Outcome::Answer(answer)) => say(format!("{}: {}", step, answer)),
+            Err(e) => {
+                result = Err(format!("{}: {}", step, e));
+                break;
+            },
+        }
+    }
+    if result.is_err() {
+        for failure in bench.power_down() {
+            say(failure);
+        }
+    }
+    if let Err(e) = &result {
+        say(e.clone());
+    }
+    result
+}
+
+/// The state of the instruments window. The sequences belong to the project.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct InstrumentPanel {
+    selected: usize,
+    console_instrument: String,
+    #[serde(skip)]
+    console_command: String,
+    #[serde(skip)]
+    log: Arc<Mutex<Vec<String>>>,
+    #[serde(skip)]
+    job: Option<JobId>,
+}
+
+impl InstrumentPanel {
+
+    /// Run something against one instrument on a background job.
+    fn talk(&mut self, ctx: &egui::Context, fixture: &Fixture, role: &str, command: &str) {
+        let (fixture, role, command, log) = (fixture.clone(), role.to_string(), command.trim().to_string(), self.log.clone());
+        self.job = Some(jobs::shared(ctx).spawn(&format!("SCPI {}", role), Some(ctx), move |_job| {
+            let roles = BTreeSet::from([role.clone()]);
+            let answer = connect_all(&fixture, &roles).and_then(|mut instruments| {
+                let instrument = instruments.get_mut(&role).expect("just connected");
+                match command.ends_with('?') {
+                    true => instrument.query(&command),
+                    false => instrument.command(&command).and_then(|_| instrument.check_errors()).map(|_| "ok".to_string()),
+                }
+            });
+            let line = match &answer {
+                Ok(answer) => format!("{} {}: {}", role, command, answer),
+                Err(e) => format!("{} {}: {}", role, command, e),
+            };
+            log.lock().unwrap().push(line);
+            answer.map(|_| ()).map_err(|e| e.to_string())
+        }));
+    }
+
+    fn start(&mut self, ctx: &egui::Context, fixture: &Fixture, sequence: &InstrumentSequence) {
+        let (fixture, sequence, log) = (fixture.clone(), sequence.clone(), self.log.clone());
+        let bus = events::shared(ctx);
+        log.lock().unwrap().push(format!("running {}", sequence.name));
+        self.job = Some(jobs::shared(ctx).spawn(&format!("test sequence {}", sequence.name), Some(ctx), move |job| {
+            run_sequence(sequence, fixture, bus, log, job)
+        }));
+    }
+
+    /// Edit the fields of a step.
+    fn step_editor(ui: &mut egui::Ui, i: usize, step: &mut Step, instruments: &[String]) {
+        let channel_editor = |ui: &mut egui::Ui, channel: &mut Option<u8>| {
+            let mut multi = channel.is_some();
+            if ui.checkbox(&mut multi, "channel").changed() {
+                *channel = multi.then_some(1);
+            }
+            if let Some(channel) = channel {
+                ui.add(egui::DragValue::new(channel).clamp_range(1..=8));
+            }
+        };
+        if let Step::SetVoltage { instrument, .. } | Step::SetCurrentLimit { instrument, .. }
+            | Step::Output { instrument, .. } | Step::Measure { instrument, .. }
+            | Step::Command { instrument, .. } = step
+        {
+            egui::ComboBox::from_id_source(("step_instrument", i)).selected_text(instrument.as_str()).show_ui(ui, |ui| {
+                for role in instruments.iter() {
+                    ui.selectable_value(instrument, role.clone(), role);
+                }
+            });
+        }
+        match step {
+            Step::SetVoltage { channel, volts, .. } => {
+                channel_editor(ui, channel);
+                ui.add(egui::DragValue::new(volts).speed(0.01).clamp_range(0.0..=1000.0).suffix(" V"));
+            },
+            Step::SetCurrentLimit { channel, amps, .. } => {
+                channel_editor(ui, channel);
+                ui.add(egui::DragValue::new(amps).speed(0.01).clamp_range(0.0..=100.0).suffix(" A"));
+            },
+            Step::Output { channel, on, .. } => {
+                channel_editor(ui, channel);
+                ui.checkbox(on, "on");
+            },
+            Step::Measure { channel, quantity, name, .. } => {
+                channel_editor(ui, channel);
+                egui::ComboBox::from_id_source(("step_quantity", i)).selected_text(quantity.to_string()).show_ui(ui, |ui| {
+                    for q in enum_iterator::all::<Quantity>() {
+                        ui.selectable_value(quantity, q, q.to_string());
+                    }
+                });
+                ui.label("as");
+                ui.add(egui::TextEdit::singleline(name).desired_width(100.0));
+            },
+            Step::Command { command, .. } => {
+                ui.add(egui::TextEdit::singleline(command).desired_width(200.0).hint_text("i.e. SYST:REM"));
+            },
+            Step::Wait { ms } => {
+                ui.add(egui::DragValue::new(ms).clamp_range(0..=3_600_000).suffix(" ms"));
+            },
+        }
+    }
+
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, sequences: &mut Vec<InstrumentSequence>, project_dir: Option<&Path>) {
+        let Some(dir) = project_dir else {
+            ui.label(format!("save the project, then add its instruments to {}", FIXTURE_FILE_NAME));
+            return;
+        };
+        let fixture = match Fixture::load(dir) {
+            Some(Ok(fixture)) => fixture,
+            Some(Err(e)) => {
+                ui.colored_label(ui.style().visuals.error_fg_color, e.to_string());
+                return;
+            },
+            None => {
+                ui.label(format!("Instruments are the devices of kind \"instrument\" in the project's {}, which it doesn't have yet.", FIXTURE_FILE_NAME));
+                return;
+            },
+        };
+        let instruments: Vec<String> = fixture.devices.iter()
+            .filter(|d| d.kind == DeviceKind::Instrument)
+            .map(|d| d.role.clone())
+            .collect();
+        if instruments.is_empty() {
+            ui.label(format!("{} has no devices of kind \"instrument\"", FIXTURE_FILE_NAME));
+            return;
+        }
+        let scheduler = jobs::shared(ctx);
+        let running = self.job.is_some_and(|id| scheduler.state(id) == Some(JobState::Running));
+        if !instruments.contains(&self.console_instrument) {
+            self.console_instrument = instruments[0].clone();
+        }
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("scpi_console_instrument").selected_text(self.console_instrument.as_str()).show_ui(ui, |ui| {
+                    for role in instruments.iter() {
+                        ui.selectable_value(&mut self.console_instrument, role.clone(), role);
+                    }
+                });
+                ui.add(egui::TextEdit::singleline(&mut self.console_command).desired_width(200.0).hint_text("SCPI command or query"));
+                let role = self.console_instrument.clone();
+                if ui.add_enabled(!self.console_command.trim().is_empty(), egui::Button::new("send")).clicked() {
+                    let command = self.console_command.clone();
+                    self.talk(ctx, &fixture, &role, &command);
+                }
+                if ui.button("identify").clicked() {
+                    self.talk(ctx, &fixture, &role, "*IDN?");
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("sequence:");
+                let selected_text = sequences.get(self.selected).map(|s| s.name.clone()).unwrap_or_default();
+                egui::ComboBox::from_id_source("instrument_sequence").selected_text(selected_text).show_ui(ui, |ui| {
+                    for (i, sequence) in sequences.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected, i, &sequence.name);
+                    }
+                });
+                if ui.button("new").clicked() {
+                    sequences.push(InstrumentSequence { name: format!("sequence {}", sequences.len() + 1), steps: vec![] });
+                    self.selected = sequences.len() - 1;
+                }
+                if self.selected < sequences.len() && ui.button("delete").clicked() {
+                    sequences.remove(self.selected);
+                    self.selected = self.selected.saturating_sub(1);
+                }
+            });
+            let Some(sequence) = sequences.get_mut(self.selected) else {
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.label("name:");
+                ui.text_edit_singleline(&mut sequence.name);
+            });
+            let (mut remove, mut raise) = (None, None);
+            egui::Grid::new("instrument_sequence_steps").num_columns(3).striped(true).show(ui, |ui| {
+                for (i, step) in sequence.steps.iter_mut().enumerate() {
+                    ui.label(format!("{}. {}", i + 1, step.kind()));
+                    ui.horizontal(|ui| Self::step_editor(ui, i, step, &instruments));
+                    ui.horizontal(|ui| {
+                        if i > 0 && ui.small_button("⏶").clicked() {
+                            raise = Some(i);
+                        }
+                        if ui.small_button("🗑").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = raise {
+                sequence.steps.swap(i - 1, i);
+            }
+            if let Some(i) = remove {
+                sequence.steps.remove(i);
+            }
+            ui.menu_button("add step", |ui| {
+                for step in Step::templates(&instruments[0]) {
+                    if ui.button(step.kind()).clicked() {
+                        sequence.steps.push(step);
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+        ui.separator();
+        let valid = sequences.get(self.selected).map(|s| s.validate(&fixture)).unwrap_or(Err("add a sequence".to_string()));
+        if let Err(e) = &valid {
+            ui.colored_label(ui.style().visuals.warn_fg_color, e);
+        }
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("stop").clicked() {
+                    if let Some(id) = self.job.take() {
+                        scheduler.cancel(id);
+                    }
+                }
+            } else if ui.add_enabled(valid.is_ok(), egui::Button::new("run")).clicked() {
+                self.start(ctx, &fixture, &sequences[self.selected]);
+            }
+            if let Some(info) = self.job.and_then(|id| scheduler.jobs().into_iter().find(|j| j.id == id)) {
+                match info.state {
+                    JobState::Running => ui.label(info.status),
+                    JobState::Failed(e) => ui.colored_label(ui.style().visuals.error_fg_color, e),
+                    _ => ui.label("finished"),
+                };
+            }
+            if ui.button("clear log").clicked() {
+                self.log.lock().unwrap().clear();
+            }
+        });
+        egui::ScrollArea::vertical().max_height(200.0).stick_to_bottom(true).show(ui, |ui| {
+            for line in self.log.lock().unwrap().iter() {
+                ui.monospace(line);
+            }
+        });
+    }
+}
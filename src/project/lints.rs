@@ -0,0 +1,422 @@
+//! Title: Iron Coder Project Module - Lints
+//! Description: The lints a project's code is checked with, i.e. denying `unwrap` in
+//!   firmware that mustn't panic. They're kept in the project file, and written to the
+//!   `[lints]` table of its Cargo.toml and to clippy.toml. Clippy is run in the background
+//!   whenever they change, so what they catch shows straight away.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+pub const CLIPPY_CONFIG_FILE_NAME: &str = "clippy.toml";
+
+/// How much a lint matters, as rustc and clippy have it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+    /// Like deny, but it can't be allowed again in the code
+    Forbid,
+}
+
+impl fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Deny => write!(f, "deny"),
+            Self::Forbid => write!(f, "forbid"),
+        }
+    }
+}
+
+/// Which tool a lint belongs to, which is its table under `[lints]`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "lowercase")]
+pub enum LintTool {
+    Rust,
+    Clippy,
+}
+
+impl fmt::Display for LintTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rust => write!(f, "rust"),
+            Self::Clippy => write!(f, "clippy"),
+        }
+    }
+}
+
+/// The lint groups. They're written with a lower priority than single lints, so a lint
+/// can be set apart from its group.
+pub const GROUPS: [(LintTool, &str); 10] = [
+    (LintTool::Rust, "warnings"),
+    (LintTool::Rust, "unused"),
+    (LintTool::Rust, "rust_2018_idioms"),
+    (LintTool::Clippy, "all"),
+    (LintTool::Clippy, "correctness"),
+    (LintTool::Clippy, "suspicious"),
+    (LintTool::Clippy, "style"),
+    (LintTool::Clippy, "perf"),
+    (LintTool::Clippy, "pedantic"),
+    (LintTool::Clippy, "nursery"),
+];
+
+/// The lints that matter most in firmware, and why.
+pub const EMBEDDED_LINTS: [(LintTool, &str, &str); 11] = [
+    (LintTool::Clippy, "unwrap_used", "a panic stops the firmware"),
+    (LintTool::Clippy, "expect_used", "a panic stops the firmware"),
+    (LintTool::Clippy, "panic", "a panic stops the firmware"),
+    (LintTool::Clippy, "indexing_slicing", "indexing out of bounds panics"),
+    (LintTool::Clippy, "arithmetic_side_effects", "overflow panics in debug builds"),
+    (LintTool::Clippy, "float_arithmetic", "chips without an FPU do floating point in software"),
+    (LintTool::Clippy, "large_stack_arrays", "the stack is a few kilobytes, and overflowing it isn't caught"),
+    (LintTool::Clippy, "large_stack_frames", "the stack is a few kilobytes, and overflowing it isn't caught"),
+    (LintTool::Clippy, "undocumented_unsafe_blocks", "register access is unsafe, so say why it's sound"),
+    (LintTool::Clippy, "std_instead_of_core", "no_std code can't use std"),
+    (LintTool::Rust, "unsafe_op_in_unsafe_fn", "keeps unsafe operations in unsafe blocks of their own"),
+];
+
+/// A lint, or a group of them, set to a level.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LintRule {
+    pub tool: LintTool,
+    pub name: String,
+    pub level: LintLevel,
+}
+
+impl LintRule {
+
+    pub fn new(tool: LintTool, name: &str, level: LintLevel) -> Self {
+        Self { tool, name: name.to_string(), level }
+    }
+
+    pub fn is_group(&self) -> bool {
+        GROUPS.iter().any(|(tool, name)| *tool == self.tool && *name == self.name)
+    }
+}
+
+impl fmt::Display for LintRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tool {
+            LintTool::Rust => write!(f, "{} = {}", self.name, self.level),
+            LintTool::Clippy => write!(f, "clippy::{} = {}", self.name, self.level),
+        }
+    }
+}
+
+/// Sets of lints to start from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub enum LintProfile {
+    /// What rustc and clippy do anyway
+    Default,
+    /// Warn about the things that make firmware panic or overflow its stack
+    Embedded,
+    /// The embedded lints denied, along with clippy's pedantic ones
+    Strict,
+}
+
+impl fmt::Display for LintProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Embedded => write!(f, "embedded"),
+            Self::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+impl LintProfile {
+
+    pub fn rules(self) -> Vec<LintRule> {
+        let embedded = |level: LintLevel| EMBEDDED_LINTS.iter()
+            // these two are noisy in most firmware, so they're left to the user
+            .filter(|(_, name, _)| *name != "float_arithmetic" && *name != "arithmetic_side_effects")
+            .map(move |(tool, name, _)| LintRule::new(*tool, name, level));
+        match self {
+            Self::Default => vec![],
+            Self::Embedded => embedded(LintLevel::Warn).collect(),
+            Self::Strict => [LintRule::new(LintTool::Clippy, "pedantic", LintLevel::Warn)].into_iter()
+                .chain(embedded(LintLevel::Deny))
+                .collect(),
+        }
+    }
+}
+
+/// The settings in clippy.toml that matter for firmware. Unset ones are left to clippy.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ClippyConfig {
+    /// The oldest Rust the code has to build with
+    pub msrv: Option<String>,
+    /// The most bytes a function's stack frame can take, for `large_stack_frames`
+    pub stack_size_threshold: Option<u64>,
+    /// The most bytes an array on the stack can take, for `large_stack_arrays`
+    pub array_size_threshold: Option<u64>,
+    /// The most bytes a value can take before it's said to belong on the heap
+    pub too_large_for_stack: Option<u64>,
+}
+
+impl ClippyConfig {
+
+    /// The settings, by their names in clippy.toml.
+    fn entries(&self) -> [(&'static str, Option<toml_edit::Value>); 4] {
+        let number = |n: Option<u64>| n.map(|n| toml_edit::Value::from(n as i64));
+        [
+            ("msrv", self.msrv.as_deref().map(|m| m.trim().into())),
+            ("stack-size-threshold", number(self.stack_size_threshold)),
+            ("array-size-threshold", number(self.array_size_threshold)),
+            ("too-large-for-stack", number(self.too_large_for_stack)),
+        ]
+    }
+}
+
+/// The project's lints, and its clippy settings.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LintSettings {
+    pub rules: Vec<LintRule>,
+    pub clippy: ClippyConfig,
+}
+
+/// Whether a lint name can be written in a `[lints]` table.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl LintSettings {
+
+    /// Check the lints can be written, and are each set once.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !valid_name(&rule.name) {
+                return Err(format!("\"{}\" isn't a lint name: use lowercase letters, digits and _", rule.name));
+            }
+            if self.rules[..i].iter().any(|r| r.tool == rule.tool && r.name == rule.name) {
+                return Err(format!("{} is set twice", rule));
+            }
+        }
+        if self.clippy.msrv.as_deref().is_some_and(|m| m.trim().split('.').any(|part| part.parse::<u32>().is_err())) {
+            return Err("the MSRV should be a version, i.e. 1.75.0".to_string());
+        }
+        Ok(())
+    }
+
+    /// The level a lint is set to, if it's set.
+    pub fn level(&self, tool: LintTool, name: &str) -> Option<LintLevel> {
+        self.rules.iter().find(|r| r.tool == tool && r.name == name).map(|r| r.level)
+    }
+
+    /// Set a lint to a level, or back to its default with `None`.
+    pub fn set(&mut self, tool: LintTool, name: &str, level: Option<LintLevel>) {
+        match (self.rules.iter_mut().find(|r| r.tool == tool && r.name == name), level) {
+            (Some(rule), Some(level)) => rule.level = level,
+            (None, Some(level)) => self.rules.push(LintRule::new(tool, name, level)),
+            (_, None) => self.rules.retain(|r| r.tool != tool || r.name != name),
+        }
+    }
+
+    /// These settings, with `other`'s on top of them, i.e. a project's over its
+    /// workspace's.
+    pub fn overridden_by(&self, other: &LintSettings) -> LintSettings {
+        let mut merged = self.clone();
+        for rule in other.rules.iter() {
+            merged.set(rule.tool, &rule.name, Some(rule.level));
+        }
+        let clippy = &other.clippy;
+        merged.clippy = ClippyConfig {
+            msrv: clippy.msrv.clone().or(merged.clippy.msrv),
+            stack_size_threshold: clippy.stack_size_threshold.or(merged.clippy.stack_size_threshold),
+            array_size_threshold: clippy.array_size_threshold.or(merged.clippy.array_size_threshold),
+            too_large_for_stack: clippy.too_large_for_stack.or(merged.clippy.too_large_for_stack),
+        };
+        merged
+    }
+
+    /// The `[lints]` table, with a table for each tool that has lints set.
+    pub fn lints_table(&self) -> toml_edit::Table {
+        let mut lints = toml_edit::Table::new();
+        for tool in enum_iterator::all::<LintTool>() {
+            let mut table = toml_edit::Table::new();
+            for rule in self.rules.iter().filter(|r| r.tool == tool) {
+                let level = rule.level.to_string();
+                table[rule.name.as_str()] = if rule.is_group() {
+                    let mut group = toml_edit::InlineTable::new();
+                    group.insert("level", level.into());
+                    group.insert("priority", (-1).into());
+                    toml_edit::value(group)
+                } else {
+                    toml_edit::value(level)
+                };
+            }
+            if !table.is_empty() {
+                lints[tool.to_string().as_str()] = toml_edit::Item::Table(table);
+            }
+        }
+        lints
+    }
+
+    /// Write the lints to the `[lints]` table of the project's Cargo.toml, replacing what
+    /// was there. Returns whether the file changed. Lints that come from a Cargo workspace
+    /// are left alone.
+    pub fn write_manifest(&self, project_dir: &Path) -> Result<bool, String> {
+        let path = project_dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&path).map_err(|e| format!("couldn't read Cargo.toml: {}", e))?;
+        let mut doc: toml_edit::DocumentMut = manifest.parse().map_err(|e| format!("couldn't parse Cargo.toml: {}", e))?;
+        if doc.get("lints").and_then(|l| l.get("workspace")).and_then(toml_edit::Item::as_bool) == Some(true) {
+            return Err("the lints come from the Cargo workspace; set them there".to_string());
+        }
+        let lints = self.lints_table();
+        if lints.is_empty() {
+            doc.remove("lints");
+        } else {
+            doc["lints"] = toml_edit::Item::Table(lints);
+        }
+        let updated = doc.to_string();
+        if updated == manifest {
+            return Ok(false);
+        }
+        fs::write(&path, updated).map_err(|e| format!("couldn't write Cargo.toml: {}", e))?;
+        Ok(true)
+    }
+
+    /// Write the clippy settings to the project's clippy.toml, keeping the other settings
+    /// in it. It's only made if there's something to put in it. Returns whether it
+    /// changed.
+    pub fn write_clippy_config(&self, project_dir: &Path) -> Result<bool, String> {
+        let path = project_dir.join(CLIPPY_CONFIG_FILE_NAME);
+        let existing = match fs::read_to_string(&path) {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("couldn't read {}: {}", CLIPPY_CONFIG_FILE_NAME, e)),
+        };
+        let mut doc: toml_edit::DocumentMut = existing.as_deref().unwrap_or("").parse()
+            .map_err(|e| format!("couldn't parse {}: {}", CLIPPY_CONFIG_FILE_NAME, e))?;
+        for (key, value) in self.clippy.entries() {
+            match value {
+                Some(value) => doc[key] = toml_edit::value(value),
+                None => {
+                    doc.remove(key);
+                },
+            }
+        }
+        let updated = doc.to_string();
+        if existing.as_deref() == Some(updated.as_str()) || (existing.is_none() && doc.is_empty()) {
+            return Ok(false);
+        }
+        fs::write(&path, updated).map_err(|e| format!("couldn't write {}: {}", CLIPPY_CONFIG_FILE_NAME, e))?;
+        Ok(true)
+    }
+
+    /// Show the lints and clippy settings to be edited. Returns whether anything changed.
+    pub fn display(&mut self, ui: &mut egui::Ui) -> bool {
+        let before = self.clone();
+        ui.horizontal(|ui| {
+            ui.label("start from");
+            for profile in enum_iterator::all::<LintProfile>() {
+                if ui.button(profile.to_string()).clicked() {
+                    self.rules = profile.rules();
+                }
+            }
+        });
+        let level_picker = |ui: &mut egui::Ui, id: String, level: &mut Option<LintLevel>| {
+            egui::ComboBox::from_id_source(id).selected_text(level.map_or("default".to_string(), |l| l.to_string())).show_ui(ui, |ui| {
+                ui.selectable_value(level, None, "default");
+                for l in enum_iterator::all::<LintLevel>() {
+                    ui.selectable_value(level, Some(l), l.to_string());
+                }
+            });
+        };
+        egui::CollapsingHeader::new("for firmware").default_open(true).show(ui, |ui| {
+            egui::Grid::new("embedded_lints").num_columns(2).show(ui, |ui| {
+                for (tool, name, why) in EMBEDDED_LINTS {
+                    ui.label(name).on_hover_text(why);
+                    let mut level = self.level(tool, name);
+                    level_picker(ui, format!("lint_{}_{}", tool, name), &mut level);
+                    self.set(tool, name, level);
+                    ui.end_row();
+                }
+            });
+        });
+        egui::CollapsingHeader::new("groups").show(ui, |ui| {
+            egui::Grid::new("lint_groups").num_columns(2).show(ui, |ui| {
+                for (tool, name) in GROUPS {
+                    ui.label(format!("{}::{}", tool, name));
+                    let mut level = self.level(tool, name);
+                    level_picker(ui, format!("lint_{}_{}", tool, name), &mut level);
+                    self.set(tool, name, level);
+                    ui.end_row();
+                }
+            });
+        });
+        let others: Vec<LintRule> = self.rules.iter()
+            .filter(|r| !r.is_group() && !EMBEDDED_LINTS.iter().any(|(tool, name, _)| *tool == r.tool && *name == r.name))
+            .cloned()
+            .collect();
+        egui::CollapsingHeader::new(format!("other lints ({})", others.len())).show(ui, |ui| {
+            for rule in others.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}::{}", rule.tool, rule.name));
+                    let mut level = Some(rule.level);
+                    level_picker(ui, format!("lint_{}_{}", rule.tool, rule.name), &mut level);
+                    self.set(rule.tool, &rule.name, level);
+                });
+            }
+            let id = ui.id().with("new_lint");
+            let mut new_lint: (LintTool, String) = ui.data(|d| d.get_temp(id)).unwrap_or((LintTool::Clippy, String::new()));
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("new_lint_tool").selected_text(new_lint.0.to_string()).show_ui(ui, |ui| {
+                    for tool in enum_iterator::all::<LintTool>() {
+                        ui.selectable_value(&mut new_lint.0, tool, tool.to_string());
+                    }
+                });
+                ui.add(egui::TextEdit::singleline(&mut new_lint.1).hint_text("lint_name").desired_width(160.0));
+                let name = new_lint.1.trim().to_string();
+                if ui.add_enabled(valid_name(&name), egui::Button::new("add")).clicked() {
+                    self.set(new_lint.0, &name, Some(LintLevel::Warn));
+                    new_lint.1.clear();
+                }
+            });
+            ui.data_mut(|d| d.insert_temp(id, new_lint));
+        });
+        egui::CollapsingHeader::new(CLIPPY_CONFIG_FILE_NAME).show(ui, |ui| {
+            egui::Grid::new("clippy_config").num_columns(2).show(ui, |ui| {
+                ui.label("MSRV");
+                let mut msrv = self.clippy.msrv.clone().unwrap_or_default();
+                ui.add(egui::TextEdit::singleline(&mut msrv).hint_text("1.75.0").desired_width(80.0));
+                self.clippy.msrv = (!msrv.trim().is_empty()).then_some(msrv);
+                ui.end_row();
+                let thresholds = [
+                    ("stack frame limit", &mut self.clippy.stack_size_threshold, 4096),
+                    ("stack array limit", &mut self.clippy.array_size_threshold, 512),
+                    ("too large for the stack", &mut self.clippy.too_large_for_stack, 200),
+                ];
+                for (label, threshold, suggested) in thresholds {
+                    ui.label(label);
+                    ui.horizontal(|ui| {
+                        let mut set = threshold.is_some();
+                        ui.checkbox(&mut set, "");
+                        match (set, threshold.as_mut()) {
+                            (true, Some(bytes)) => {
+                                ui.add(egui::DragValue::new(bytes).suffix(" bytes"));
+                            },
+                            (true, None) => *threshold = Some(suggested),
+                            (false, _) => *threshold = None,
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+        if let Err(e) = self.validate() {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        *self != before
+    }
+}
@@ -0,0 +1,164 @@
+//! Title: Iron Coder Project Module - Local Sources
+//! Description: Code a project builds that isn't in its own source tree, i.e. a fork of a
+//!   HAL checked out as a git submodule, or a crate it depends on by path. They're listed
+//!   with their state in the commit window, searched along with the project's own code,
+//!   and their files are part of a recorded build.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Repository, StatusOptions, SubmoduleIgnore, SubmoduleStatus};
+
+/// The tables of a manifest that dependencies are listed in.
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Where some local code comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    /// A git submodule of the project's repository
+    Submodule,
+    /// A crate in the project's Cargo.toml with a `path`
+    PathDependency,
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Submodule => write!(f, "submodule"),
+            Self::PathDependency => write!(f, "path dependency"),
+        }
+    }
+}
+
+/// The state of some local code, as far as git can tell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceState {
+    Clean,
+    /// A submodule that hasn't been cloned, or a path that isn't there
+    Missing,
+    /// A submodule checked out at another commit than the one the project has
+    NewCommits,
+    /// Changed files that haven't been committed
+    Modified,
+    /// A path dependency that isn't in a git repository, so its changes can't be told
+    Untracked,
+}
+
+impl fmt::Display for SourceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Clean => write!(f, "clean"),
+            Self::Missing => write!(f, "missing"),
+            Self::NewCommits => write!(f, "at a new commit"),
+            Self::Modified => write!(f, "modified"),
+            Self::Untracked => write!(f, "not in git"),
+        }
+    }
+}
+
+/// Code the project builds from outside its own `src`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalSource {
+    pub kind: SourceKind,
+    /// The submodule's name, or the name of the crate in the manifest
+    pub name: String,
+    /// Where it is, which is outside the project for some path dependencies
+    pub path: PathBuf,
+    pub state: SourceState,
+}
+
+/// The dependencies in a manifest that come from a path, by name, with the path as it's
+/// written. Target-specific dependencies and patches are included.
+pub fn path_dependencies(manifest: &str) -> Result<Vec<(String, PathBuf)>, String> {
+    let manifest: toml::Table = manifest.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let mut tables: Vec<&toml::Table> = DEPENDENCY_TABLES.iter().filter_map(|t| manifest.get(*t)?.as_table()).collect();
+    for target in manifest.get("target").and_then(toml::Value::as_table).into_iter().flat_map(|t| t.values()) {
+        tables.extend(DEPENDENCY_TABLES.iter().filter_map(|t| target.get(*t)?.as_table()));
+    }
+    tables.extend(manifest.get("patch").and_then(toml::Value::as_table).into_iter().flat_map(|p| p.values()).filter_map(toml::Value::as_table));
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    for (name, value) in tables.into_iter().flatten() {
+        let Some(path) = value.get("path").and_then(toml::Value::as_str) else {
+            continue;
+        };
+        if !found.iter().any(|(n, p)| n == name && p == Path::new(path)) {
+            found.push((name.clone(), PathBuf::from(path)));
+        }
+    }
+    Ok(found)
+}
+
+/// Whether the files under a path in a repository have changes that haven't been
+/// committed.
+fn has_changes(repo: &Repository, path: &Path) -> bool {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).exclude_submodules(true);
+    let workdir = repo.workdir().and_then(|w| w.canonicalize().ok());
+    if let (Some(workdir), Ok(path)) = (workdir, path.canonicalize()) {
+        if let Ok(relative) = path.strip_prefix(&workdir) {
+            if !relative.as_os_str().is_empty() {
+                options.pathspec(relative);
+            }
+        }
+    }
+    repo.statuses(Some(&mut options)).is_ok_and(|statuses| !statuses.is_empty())
+}
+
+fn submodule_state(status: SubmoduleStatus) -> SourceState {
+    if status.contains(SubmoduleStatus::WD_UNINITIALIZED) || !status.contains(SubmoduleStatus::IN_WD) {
+        SourceState::Missing
+    } else if status.intersects(SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED | SubmoduleStatus::WD_UNTRACKED) {
+        SourceState::Modified
+    } else if status.contains(SubmoduleStatus::WD_MODIFIED) {
+        SourceState::NewCommits
+    } else {
+        SourceState::Clean
+    }
+}
+
+/// The project's submodules and path dependencies, submodules first.
+pub fn find(project_dir: &Path) -> Vec<LocalSource> {
+    let mut sources = Vec::new();
+    if let Ok(repo) = Repository::open(project_dir) {
+        for submodule in repo.submodules().unwrap_or_default() {
+            let Some(name) = submodule.name() else { continue };
+            let state = repo.submodule_status(name, SubmoduleIgnore::None).map_or(SourceState::Missing, submodule_state);
+            sources.push(LocalSource { kind: SourceKind::Submodule, name: name.to_string(), path: project_dir.join(submodule.path()), state });
+        }
+    }
+    let manifest = fs::read_to_string(project_dir.join("Cargo.toml")).unwrap_or_default();
+    for (name, path) in path_dependencies(&manifest).unwrap_or_default() {
+        let path = project_dir.join(path);
+        let state = if !path.is_dir() {
+            SourceState::Missing
+        } else {
+            // the one it's in, which may be the project's own or a submodule's
+            match Repository::discover(&path) {
+                Ok(repo) if has_changes(&repo, &path) => SourceState::Modified,
+                Ok(_) => SourceState::Clean,
+                Err(_) => SourceState::Untracked,
+            }
+        };
+        sources.push(LocalSource { kind: SourceKind::PathDependency, name, path, state });
+    }
+    sources
+}
+
+/// Show the project's local sources in the commit window.
+pub fn display(ui: &mut egui::Ui, sources: &[LocalSource]) {
+    egui::CollapsingHeader::new(format!("submodules and path dependencies ({})", sources.len())).id_source("local_sources").show(ui, |ui| {
+        for source in sources {
+            let color = match source.state {
+                SourceState::Clean => ui.visuals().text_color(),
+                SourceState::Missing => ui.visuals().error_fg_color,
+                _ => ui.visuals().warn_fg_color,
+            };
+            ui.horizontal(|ui| {
+                ui.strong(&source.name);
+                ui.weak(source.kind.to_string());
+                ui.colored_label(color, source.state.to_string());
+            }).response.on_hover_text(source.path.display().to_string());
+        }
+    });
+}
@@ -0,0 +1,690 @@
+//! Title: Iron Coder Project Module - Localization
+//! Description: String tables for firmware that's shipped in more than one language.
+//!   The user-facing strings live in `locales/<language>.toml`, one key and text per
+//!   line, and are generated into `src/strings.rs` as a module of consts per language.
+//!   A `lang-<language>` cargo feature picks the language a build uses. String literals
+//!   can be extracted from the code into the tables, and the translations are edited in
+//!   a grid with a column per language.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::project::config;
+
+/// The directory of the string tables, in the project directory.
+pub const LOCALES_DIR: &str = "locales";
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "strings.rs";
+/// The longest key made from a string's text.
+const MAX_KEY_LENGTH: usize = 24;
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum LocalizationError {
+    /// A table or source file couldn't be read or written
+    IoError(io::Error),
+    /// A table isn't a table of strings
+    BadTable { file: String, reason: String },
+    /// A key or language can't be used as a Rust name
+    BadName(String),
+}
+
+impl fmt::Display for LocalizationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocalizationError::IoError(e) => write!(f, "{}", e),
+            LocalizationError::BadTable { file, reason } => write!(f, "{} isn't a string table: {}", file, reason),
+            LocalizationError::BadName(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for LocalizationError {
+    fn from(e: io::Error) -> Self {
+        LocalizationError::IoError(e)
+    }
+}
+
+/// How the project's strings are built.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct LocalizationConfig {
+    /// The language the strings are written in, used for any that aren't translated
+    pub base_language: String,
+    /// The language the IDE builds, if not the base language
+    pub build_language: Option<String>,
+}
+
+impl Default for LocalizationConfig {
+    fn default() -> Self {
+        Self { base_language: "en".to_string(), build_language: None }
+    }
+}
+
+impl LocalizationConfig {
+    /// The cargo feature that selects the build language.
+    pub fn build_feature(&self) -> Option<String> {
+        self.build_language.as_deref().filter(|l| *l != self.base_language).map(feature_name)
+    }
+}
+
+/// The cargo feature that selects a language, i.e. `lang-pt-br`.
+pub fn feature_name(language: &str) -> String {
+    format!("lang-{}", language.to_lowercase().replace('_', "-"))
+}
+
+/// The name of a language's module, i.e. `pt_br`.
+pub fn module_name(language: &str) -> String {
+    language.to_lowercase().replace('-', "_")
+}
+
+/// Check that a language code can name a module and a feature, i.e. "de" or "pt-BR".
+pub fn check_language(language: &str) -> Result<(), LocalizationError> {
+    let valid = language.starts_with(|c: char| c.is_ascii_alphabetic())
+        && language.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(()),
+        false => Err(LocalizationError::BadName(format!("{:?} isn't a language code like \"de\" or \"pt-BR\"", language))),
+    }
+}
+
+/// Check that a key can name a const: lowercase letters, digits and underscores.
+pub fn check_key(key: &str) -> Result<(), LocalizationError> {
+    let valid = key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    match valid {
+        true => Ok(()),
+        false => Err(LocalizationError::BadName(format!("{:?} isn't a key like \"temperature_label\"", key))),
+    }
+}
+
+/// A key for a string, made from its text.
+pub fn key_for(text: &str) -> String {
+    let mut key = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            key.push(c);
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+        if key.len() >= MAX_KEY_LENGTH {
+            break;
+        }
+    }
+    let key = key.trim_end_matches('_');
+    match key.starts_with(|c: char| c.is_ascii_lowercase()) {
+        true => key.to_string(),
+        false => format!("s_{}", key),
+    }
+}
+
+/// The strings of every language, by language and key.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Catalog {
+    pub tables: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Catalog {
+
+    /// Read the tables in a project's locales directory. A project without one has none.
+    pub fn load(project_dir: &Path) -> Result<Self, LocalizationError> {
+        let mut catalog = Self::default();
+        let Ok(entries) = fs::read_dir(project_dir.join(LOCALES_DIR)) else {
+            return Ok(catalog);
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().is_none_or(|e| e != "toml") {
+                continue;
+            }
+            let Some(language) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let file = format!("{}/{}.toml", LOCALES_DIR, language);
+            let bad_table = |reason: String| LocalizationError::BadTable { file: file.clone(), reason };
+            check_language(&language)?;
+            let table: BTreeMap<String, String> = toml::from_str(&fs::read_to_string(&path)?).map_err(|e| bad_table(e.to_string()))?;
+            for key in table.keys() {
+                check_key(key).map_err(|e| bad_table(e.to_string()))?;
+            }
+            catalog.tables.insert(language, table);
+        }
+        Ok(catalog)
+    }
+
+    /// Write the tables, keeping the comments and order of the files that are there.
+    pub fn save(&self, project_dir: &Path) -> Result<(), LocalizationError> {
+        let dir = project_dir.join(LOCALES_DIR);
+        fs::create_dir_all(&dir)?;
+        for (language, table) in self.tables.iter() {
+            let path = dir.join(format!("{}.toml", language));
+            let existing = fs::read_to_string(&path).unwrap_or_default();
+            let mut doc: toml_edit::DocumentMut = existing.parse().map_err(|e: toml_edit::TomlError| {
+                LocalizationError::BadTable { file: format!("{}/{}.toml", LOCALES_DIR, language), reason: e.to_string() }
+            })?;
+            let removed: Vec<String> = doc.iter().map(|(k, _)| k.to_string()).filter(|k| !table.contains_key(k)).collect();
+            for key in removed {
+                doc.remove(&key);
+            }
+            for (key, text) in table.iter() {
+                if doc.get(key).and_then(|v| v.as_str()) != Some(text) {
+                    doc[key.as_str()] = toml_edit::value(text.as_str());
+                }
+            }
+            let updated = doc.to_string();
+            if updated != existing {
+                fs::write(&path, updated)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The keys of the base language, which are the strings the firmware has.
+    pub fn keys(&self, base_language: &str) -> Vec<String> {
+        self.tables.get(base_language).map(|t| t.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// The languages, base language first.
+    pub fn languages(&self, base_language: &str) -> Vec<String> {
+        let mut languages = vec![base_language.to_string()];
+        languages.extend(self.tables.keys().filter(|l| *l != base_language).cloned());
+        languages
+    }
+}
+
+/// Generate the module of string tables. Strings missing from a translation fall back
+/// to the base language, and keys that aren't in the base language are left out.
+pub fn generate_module(catalog: &Catalog, base_language: &str) -> Result<String, LocalizationError> {
+    check_language(base_language)?;
+    let languages = catalog.languages(base_language);
+    let keys = catalog.keys(base_language);
+    let empty = BTreeMap::new();
+    let base = catalog.tables.get(base_language).unwrap_or(&empty);
+    let features: Vec<String> = languages.iter().map(|l| feature_name(l)).collect();
+    let mut code = format!(
+        "//! The firmware's user-facing strings, generated by Iron Coder from {}/*.toml.\n\
+         //! Don't edit this file: edit the tables and rebuild. A `lang-<language>` feature\n\
+         //! picks the language; without one, it's {}.\n\
+         #![allow(dead_code)]\n",
+        LOCALES_DIR, base_language,
+    );
+    for (i, a) in features.iter().enumerate() {
+        for b in features[i + 1..].iter() {
+            code += &format!("\n#[cfg(all(feature = {:?}, feature = {:?}))]\ncompile_error!(\"only one language feature can be enabled\");\n", a, b);
+        }
+    }
+    for language in languages.iter() {
+        let table = catalog.tables.get(language).unwrap_or(&empty);
+        code += &format!("\npub mod {} {{\n", module_name(language));
+        code += &format!("    pub const LANGUAGE: &str = {:?};\n", language);
+        for key in keys.iter() {
+            match table.get(key) {
+                Some(text) => code += &format!("    pub const {}: &str = {:?};\n", key.to_uppercase(), text),
+                None => code += &format!("    /// Not translated yet\n    pub const {}: &str = {:?};\n", key.to_uppercase(), base[key]),
+            }
+        }
+        code += "}\n";
+    }
+    let others: Vec<String> = features[1..].iter().map(|f| format!("feature = {:?}", f)).collect();
+    code += &match others.is_empty() {
+        true => format!("\npub use {}::*;\n", module_name(base_language)),
+        false => format!(
+            "\n#[cfg(any(feature = {:?}, not(any({}))))]\npub use {}::*;\n",
+            features[0], others.join(", "), module_name(base_language),
+        ),
+    };
+    for (language, feature) in languages.iter().zip(features.iter()).skip(1) {
+        code += &format!("#[cfg(feature = {:?})]\npub use {}::*;\n", feature, module_name(language));
+    }
+    Ok(code)
+}
+
+/// Regenerate the module if the tables changed, and declare the language features in
+/// Cargo.toml. Returns whether it was written. A project without tables is left alone.
+pub fn regenerate(config: &LocalizationConfig, project_dir: &Path) -> Result<bool, LocalizationError> {
+    let catalog = Catalog::load(project_dir)?;
+    if catalog.tables.is_empty() {
+        return Ok(false);
+    }
+    let code = generate_module(&catalog, &config.base_language)?;
+    let path = project_dir.join("src").join(MODULE_FILE_NAME);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == code) {
+        return Ok(false);
+    }
+    fs::create_dir_all(project_dir.join("src"))?;
+    fs::write(&path, code)?;
+    let manifest_path = project_dir.join("Cargo.toml");
+    if let Ok(manifest) = fs::read_to_string(&manifest_path) {
+        let features: Vec<String> = catalog.languages(&config.base_language).iter().map(|l| feature_name(l)).collect();
+        let bad_manifest = |reason: String| LocalizationError::BadTable { file: "Cargo.toml".to_string(), reason };
+        if let Some(updated) = config::declare_features(&manifest, &features).map_err(bad_manifest)? {
+            fs::write(&manifest_path, updated)?;
+        }
+    }
+    Ok(true)
+}
+
+/// A string literal in the code that could be moved into the string tables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Literal {
+    /// The byte range of the literal, quotes included
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub text: String,
+}
+
+/// Whether a byte, raw or C string starts at a position, and its prefix, rawness and
+/// number of hashes.
+fn prefixed_string(bytes: &[u8], i: usize) -> Option<(&'static str, bool, usize)> {
+    if i > 0 && (bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_') {
+        return None;
+    }
+    let rest = &bytes[i..];
+    let (prefix, raw) = ["br", "cr", "r", "b", "c"].iter()
+        .find(|p| rest.starts_with(p.as_bytes()))
+        .map(|p| (*p, p.ends_with('r')))?;
+    let hashes = match raw {
+        true => rest[prefix.len()..].iter().take_while(|c| **c == b'#').count(),
+        false => 0,
+    };
+    (rest.get(prefix.len() + hashes) == Some(&b'"')).then_some((prefix, raw, hashes))
+}
+
+/// The string literals of some Rust code that aren't in a macro or attribute, and so
+/// could be replaced by a const. Format strings, log messages and attribute values
+/// have to stay literals.
+pub fn find_literals(code: &str) -> Vec<Literal> {
+    let bytes = code.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80;
+    let mut literals = vec![];
+    // whether each open bracket is the start of a macro's or attribute's tokens
+    let mut groups: Vec<bool> = vec![];
+    let (mut after_bang, mut after_hash) = (false, false);
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let in_macro = groups.iter().any(|m| *m);
+        let start = i;
+        match b {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                continue;
+            },
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(bytes.len());
+                if !in_macro {
+                    if let Ok(literal) = syn::parse_str::<syn::LitStr>(&code[start..i]) {
+                        let line = code[..start].matches('\n').count() + 1;
+                        literals.push(Literal { start, end: i, line, text: literal.value() });
+                    }
+                }
+            },
+            b'\'' => {
+                // a char literal, or a lifetime
+                let next = code[i + 1..].chars().next().map(char::len_utf8).unwrap_or(1);
+                if bytes.get(i + 1) == Some(&b'\\') {
+                    i += 2;
+                    while i < bytes.len() && bytes[i] != b'\'' {
+                        i += 1;
+                    }
+                    i += 1;
+                } else if bytes.get(i + 1 + next) == Some(&b'\'') {
+                    i += 2 + next;
+                } else {
+                    i += 1;
+                }
+            },
+            _ if prefixed_string(bytes, i).is_some() => {
+                // byte, raw and C strings start like identifiers
+                let (prefix, raw, hashes) = prefixed_string(bytes, i).unwrap_or_default();
+                i += prefix.len() + hashes + 1;
+                if raw {
+                    let close = format!("\"{}", "#".repeat(hashes)).into_bytes();
+                    while i < bytes.len() && !bytes[i..].starts_with(&close) {
+                        i += 1;
+                    }
+                    i = (i + close.len()).min(bytes.len());
+                } else {
+                    while i < bytes.len() && bytes[i] != b'"' {
+                        i += if bytes[i] == b'\\' { 2 } else { 1 };
+                    }
+                    i = (i + 1).min(bytes.len());
+                }
+                // only raw strings are text
+                if prefix == "r" && !in_macro {
+                    if let Ok(literal) = syn::parse_str::<syn::LitStr>(&code[start..i]) {
+                        let line = code[..start].matches('\n').count() + 1;
+                        literals.push(Literal { start, end: i, line, text: literal.value() });
+                    }
+                }
+            },
+            b'(' | b'[' | b'{' => {
+                groups.push(after_bang || after_hash);
+                i += 1;
+            },
+            b')' | b']' | b'}' => {
+                groups.pop();
+                i += 1;
+            },
+            _ if is_ident(b) => {
+                while i < bytes.len() && is_ident(bytes[i]) {
+                    i += 1;
+                }
+                // `name!(...)`, but not `a != b`
+                after_bang = bytes.get(i) == Some(&b'!') && bytes.get(i + 1) != Some(&b'=');
+                if after_bang {
+                    i += 1;
+                }
+                after_hash = false;
+                continue;
+            },
+            b'#' => {
+                after_hash = true;
+                i += 1;
+                if bytes.get(i) == Some(&b'!') {
+                    i += 1;
+                }
+                continue;
+            },
+            _ if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            },
+            _ => i += 1,
+        }
+        after_bang = false;
+        after_hash = false;
+    }
+    literals
+}
+
+/// The literals worth translating: ones with words in them.
+pub fn candidates(code: &str) -> Vec<Literal> {
+    find_literals(code).into_iter().filter(|l| l.text.chars().any(char::is_alphabetic)).collect()
+}
+
+/// Replace literals in some code with the consts of their keys.
+pub fn replace_literals(code: &str, replacements: &[(Literal, String)]) -> String {
+    let mut code = code.to_string();
+    let mut replacements: Vec<&(Literal, String)> = replacements.iter().collect();
+    replacements.sort_by_key(|(literal, _)| std::cmp::Reverse(literal.start));
+    for (literal, key) in replacements {
+        code.replace_range(literal.start..literal.end, &format!("crate::strings::{}", key.to_uppercase()));
+    }
+    code
+}
+
+/// A literal found in the project, to be extracted or not.
+struct Candidate {
+    file: PathBuf,
+    literal: Literal,
+    key: String,
+    extract: bool,
+}
+
+/// Move literals into the base language's table, and replace them in the code. Literals
+/// with the same text share a key.
+fn extract(catalog: &mut Catalog, base_language: &str, project_dir: &Path, candidates: &[Candidate]) -> Result<usize, LocalizationError> {
+    let mut files: BTreeMap<&Path, Vec<(Literal, String)>> = BTreeMap::new();
+    for candidate in candidates.iter().filter(|c| c.extract) {
+        check_key(&candidate.key)?;
+        let table = catalog.tables.entry(base_language.to_string()).or_default();
+        let key = match table.iter().find(|(_, text)| **text == candidate.literal.text) {
+            Some((key, _)) => key.clone(),
+            None if table.contains_key(&candidate.key) => {
+                return Err(LocalizationError::BadName(format!("{} is already the key of another string", candidate.key)));
+            },
+            None => {
+                table.insert(candidate.key.clone(), candidate.literal.text.clone());
+                candidate.key.clone()
+            },
+        };
+        files.entry(&candidate.file).or_default().push((candidate.literal.clone(), key));
+    }
+    catalog.save(project_dir)?;
+    for (file, replacements) in files.iter() {
+        let path = project_dir.join(file);
+        let code = fs::read_to_string(&path)?;
+        fs::write(&path, replace_literals(&code, replacements))?;
+    }
+    Ok(files.len())
+}
+
+/// The Rust files of a directory, relative to the project directory.
+fn source_files(project_dir: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.is_dir() {
+            source_files(project_dir, &path, files);
+        } else if path.extension().is_some_and(|e| e == "rs") && path.file_name().is_some_and(|n| n != MODULE_FILE_NAME) {
+            files.extend(path.strip_prefix(project_dir).ok().map(Path::to_path_buf));
+        }
+    }
+}
+
+/// The state of the translations window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LocalizationPanel {
+    #[serde(skip)]
+    catalog: Option<Catalog>,
+    #[serde(skip)]
+    changed: bool,
+    #[serde(skip)]
+    new_language: String,
+    #[serde(skip)]
+    new_key: String,
+    #[serde(skip)]
+    candidates: Vec<Candidate>,
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl LocalizationPanel {
+
+    fn find_candidates(&mut self, project_dir: &Path) {
+        let mut files = vec![];
+        source_files(project_dir, &project_dir.join("src"), &mut files);
+        files.sort();
+        self.candidates.clear();
+        for file in files {
+            let Ok(code) = fs::read_to_string(project_dir.join(&file)) else {
+                continue;
+            };
+            for literal in candidates(&code) {
+                let key = key_for(&literal.text);
+                self.candidates.push(Candidate { file: file.clone(), literal, key, extract: false });
+            }
+        }
+        if self.candidates.is_empty() {
+            self.status = Some(Ok("there are no string literals outside macros in src".to_string()));
+        }
+    }
+
+    fn extraction(&mut self, ui: &mut egui::Ui, config: &LocalizationConfig, project_dir: &Path) {
+        ui.horizontal(|ui| {
+            if ui.button("find strings in code").clicked() {
+                self.find_candidates(project_dir);
+            }
+            let selected = self.candidates.iter().filter(|c| c.extract).count();
+            if ui.add_enabled(selected > 0, egui::Button::new(format!("extract {}", selected))).clicked() {
+                let catalog = self.catalog.get_or_insert_with(Catalog::default);
+                self.status = Some(match extract(catalog, &config.base_language, project_dir, &self.candidates) {
+                    Ok(files) => Ok(format!("moved {} strings into the tables and changed {} files; reopen them in the editor", selected, files)),
+                    Err(e) => Err(e.to_string()),
+                });
+                self.candidates.clear();
+            }
+        });
+        if self.candidates.is_empty() {
+            return;
+        }
+        egui::ScrollArea::vertical().id_source("localization_candidates").max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("localization_candidates").num_columns(3).striped(true).show(ui, |ui| {
+                for candidate in self.candidates.iter_mut() {
+                    ui.checkbox(&mut candidate.extract, format!("{}:{}", candidate.file.display(), candidate.literal.line));
+                    ui.label(format!("{:?}", candidate.literal.text));
+                    ui.add(egui::TextEdit::singleline(&mut candidate.key).desired_width(160.0));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn grid(&mut self, ui: &mut egui::Ui, config: &LocalizationConfig) {
+        let Some(catalog) = &mut self.catalog else {
+            return;
+        };
+        let languages = catalog.languages(&config.base_language);
+        let keys = catalog.keys(&config.base_language);
+        let mut remove = None;
+        egui::ScrollArea::both().id_source("localization_grid").max_height(400.0).show(ui, |ui| {
+            egui::Grid::new("localization_grid").num_columns(languages.len() + 2).striped(true).show(ui, |ui| {
+                ui.strong("key");
+                for language in languages.iter() {
+                    ui.strong(language);
+                }
+                ui.end_row();
+                for key in keys.iter() {
+                    ui.label(key);
+                    for language in languages.iter() {
+                        let table = catalog.tables.entry(language.clone()).or_default();
+                        let mut text = table.get(key).cloned().unwrap_or_default();
+                        let untranslated = text.is_empty();
+                        let edit = egui::TextEdit::singleline(&mut text).desired_width(140.0).hint_text("not translated");
+                        let response = ui.add(edit);
+                        if untranslated {
+                            ui.painter().rect_stroke(response.rect, 2.0, (1.0, ui.style().visuals.warn_fg_color));
+                        }
+                        if response.changed() {
+                            match text.is_empty() && *language != config.base_language {
+                                true => table.remove(key),
+                                false => table.insert(key.clone(), text),
+                            };
+                            self.changed = true;
+                        }
+                    }
+                    if ui.small_button("🗑").clicked() {
+                        remove = Some(key.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(key) = remove {
+            for table in catalog.tables.values_mut() {
+                table.remove(&key);
+            }
+            self.changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_key).desired_width(140.0).hint_text("new_key"));
+            if ui.button("add string").clicked() {
+                match check_key(&self.new_key) {
+                    Ok(()) => {
+                        catalog.tables.entry(config.base_language.clone()).or_default().entry(self.new_key.clone()).or_default();
+                        self.new_key.clear();
+                        self.changed = true;
+                    },
+                    Err(e) => self.status = Some(Err(e.to_string())),
+                }
+            }
+            ui.add(egui::TextEdit::singleline(&mut self.new_language).desired_width(60.0).hint_text("de"));
+            if ui.button("add language").clicked() {
+                match check_language(&self.new_language) {
+                    Ok(()) => {
+                        catalog.tables.entry(self.new_language.clone()).or_default();
+                        self.new_language.clear();
+                        self.changed = true;
+                    },
+                    Err(e) => self.status = Some(Err(e.to_string())),
+                }
+            }
+        });
+    }
+
+    pub fn display(&mut self, ui: &mut egui::Ui, config: &mut LocalizationConfig, project_dir: Option<&Path>) {
+        let Some(dir) = project_dir else {
+            ui.label("save the project to add string tables to it");
+            return;
+        };
+        if self.catalog.is_none() {
+            match Catalog::load(dir) {
+                Ok(catalog) => self.catalog = Some(catalog),
+                Err(e) => {
+                    ui.colored_label(ui.style().visuals.error_fg_color, e.to_string());
+                    if ui.button("reload").clicked() {
+                        self.catalog = None;
+                    }
+                    return;
+                },
+            }
+        }
+        ui.label(format!("The tables in {}/ are generated into src/{} before each build; add `mod strings;` to main.rs to use them.", LOCALES_DIR, MODULE_FILE_NAME));
+        let languages = self.catalog.as_ref().map(|c| c.languages(&config.base_language)).unwrap_or_default();
+        ui.horizontal(|ui| {
+            ui.label("written in:");
+            ui.add(egui::TextEdit::singleline(&mut config.base_language).desired_width(60.0));
+            ui.label("build in:");
+            let selected = config.build_language.clone().unwrap_or_else(|| config.base_language.clone());
+            egui::ComboBox::from_id_source("localization_build_language").selected_text(selected).show_ui(ui, |ui| {
+                for language in languages.iter() {
+                    let value = Some(language.clone()).filter(|l| *l != config.base_language);
+                    ui.selectable_value(&mut config.build_language, value, language);
+                }
+            });
+        });
+        ui.separator();
+        self.extraction(ui, config, dir);
+        ui.separator();
+        self.grid(ui, config);
+        ui.horizontal(|ui| {
+            if ui.add_enabled(self.changed, egui::Button::new("save")).clicked() {
+                if let Some(catalog) = &self.catalog {
+                    self.status = Some(catalog.save(dir).map(|_| format!("saved {}/", LOCALES_DIR)).map_err(|e| e.to_string()));
+                    self.changed = false;
+                }
+            }
+            if ui.button("reload").clicked() {
+                self.catalog = None;
+                self.changed = false;
+            }
+        });
+        match &self.status {
+            Some(Ok(done)) => { ui.label(done); },
+            Some(Err(e)) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            None => (),
+        }
+    }
+}
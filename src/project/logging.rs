@@ -0,0 +1,487 @@
+//! Title: Iron Coder Project Module - Panics and Logging
+//! Description: How a project's firmware handles a panic and where its log goes. A panic
+//!   can halt the chip, be printed to a debug probe, or be kept in RAM over a reset with
+//!   panic-persist and sent over serial when the firmware starts again, where the serial
+//!   monitor picks it out. The log can go out with defmt or the log crate over RTT, or
+//!   over a serial port. Applying the settings changes the dependencies, `src/main.rs`,
+//!   the cargo config and the linker script; the serial logger is written to
+//!   `src/logging.rs` before each build.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::project::explain;
+use crate::project::std_support::{self, update_file, Applied, StdSettings, StdSupport};
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "logging.rs";
+/// What the firmware sends before the message of a panic from before the last reset.
+pub const PANIC_MARKER: &str = "PERSISTED PANIC: ";
+/// The crates a log backend brings in, which are taken out when another one is picked.
+const LOG_CRATES: [&str; 3] = ["defmt", "defmt-rtt", "rtt-target"];
+/// Where the RAM panic-persist keeps the message in starts and ends in `memory.x`.
+const PANIC_DUMP_BEGIN: &str = "/* panic-persist: the last 1K of RAM keeps a panic message over a reset */";
+const PANIC_DUMP_END: &str = "/* panic-persist end */";
+const DEFMT_LINK_ARG: &str = "link-arg=-Tdefmt.x";
+
+/// What the firmware does when it panics.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum PanicStrategy {
+    /// Stop in a loop
+    #[default]
+    Halt,
+    /// Print the message to the debug probe, and stop
+    Probe,
+    /// Keep the message in RAM over a reset, to be sent over serial after it
+    Persist,
+}
+
+impl fmt::Display for PanicStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.crate_name())
+    }
+}
+
+impl PanicStrategy {
+
+    pub fn crate_name(&self) -> &'static str {
+        match self {
+            Self::Halt => "panic-halt",
+            Self::Probe => "panic-probe",
+            Self::Persist => "panic-persist",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Halt => "stop in a loop, and show nothing",
+            Self::Probe => "print the panic to the debug probe, and stop",
+            Self::Persist => "reset, and send the panic over serial when the firmware starts again",
+        }
+    }
+
+    fn version(&self) -> &'static str {
+        match self {
+            Self::Halt => "0.2",
+            Self::Probe | Self::Persist => "0.3",
+        }
+    }
+}
+
+/// Where the firmware's log goes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum LogBackend {
+    #[default]
+    None,
+    /// defmt over RTT, which sends the log compressed and formats it on the host
+    Defmt,
+    /// The log crate over RTT
+    LogRtt,
+    /// The log crate over a serial port
+    LogSerial,
+}
+
+impl fmt::Display for LogBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "no logging"),
+            Self::Defmt => write!(f, "defmt over RTT"),
+            Self::LogRtt => write!(f, "log over RTT"),
+            Self::LogSerial => write!(f, "log over serial"),
+        }
+    }
+}
+
+impl LogBackend {
+
+    /// Whether the log goes to a debug probe.
+    pub fn uses_rtt(&self) -> bool {
+        matches!(self, Self::Defmt | Self::LogRtt)
+    }
+}
+
+/// The least important messages that are logged.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warn => write!(f, "warn"),
+            Self::Info => write!(f, "info"),
+            Self::Debug => write!(f, "debug"),
+            Self::Trace => write!(f, "trace"),
+        }
+    }
+}
+
+impl LogLevel {
+
+    /// The level's `log::LevelFilter` variant.
+    fn filter(&self) -> &'static str {
+        match self {
+            Self::Error => "Error",
+            Self::Warn => "Warn",
+            Self::Info => "Info",
+            Self::Debug => "Debug",
+            Self::Trace => "Trace",
+        }
+    }
+}
+
+/// The panic and logging settings of a project.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LoggingSettings {
+    pub panic: PanicStrategy,
+    pub log: LogBackend,
+    pub level: LogLevel,
+}
+
+/// Whether a line of `main.rs` is one the settings put there.
+fn is_managed_line(line: &str) -> bool {
+    let line = line.trim();
+    std_support::is_panic_handler_use(line) || line == "use defmt_rtt as _;" || line == "mod logging;"
+        || line.starts_with("rtt_target::rtt_init_log!(")
+}
+
+/// Whether a line is part of what's at the top of `main.rs`: docs, inner attributes and
+/// the `extern crate`s and generated modules that go with them.
+fn is_header_line(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with("//!") || line.starts_with("#![") || line.starts_with("extern crate") || line == "mod allocator;"
+}
+
+impl LoggingSettings {
+
+    /// Whether the firmware needs the generated module.
+    pub fn needs_module(&self) -> bool {
+        self.log == LogBackend::LogSerial || self.panic == PanicStrategy::Persist
+    }
+
+    /// What's wrong with the settings for the main board, if anything.
+    pub fn warnings(&self, main_board: Option<&Board>) -> Vec<String> {
+        let mut warnings = vec![];
+        let uses_probe = self.log.uses_rtt() || self.panic == PanicStrategy::Probe;
+        if let (true, Some(board), Some(method)) = (uses_probe, main_board, main_board.and_then(Board::flash_method)) {
+            if !method.can_debug() {
+                warnings.push(format!("the {} is flashed with {}, so RTT needs a debug probe connected as well", board.label(), method));
+            }
+        }
+        if self.panic == PanicStrategy::Probe && self.log != LogBackend::Defmt {
+            warnings.push("panic-probe prints the panic with defmt, so without it the panic isn't shown".to_string());
+        }
+        warnings
+    }
+
+    /// The module with the serial logger and the function that sends a persisted panic.
+    pub fn generate_module(&self) -> String {
+        let mut code = "//! Logging and panic reports over a serial port, generated by Iron Coder. Don't edit
+//! this file: it's written again before each build.
+
+use core::fmt::Write;
+".to_string();
+        if self.log == LogBackend::LogSerial {
+            let report = match self.panic {
+                PanicStrategy::Persist => "    report_persisted_panic(&mut *port);\n",
+                _ => "",
+            };
+            code += &format!("
+use core::cell::RefCell;
+use critical_section::Mutex;
+use log::{{LevelFilter, Log, Metadata, Record}};
+
+/// The serial port the log goes to, once init has been called
+static PORT: Mutex<RefCell<Option<&'static mut (dyn Write + Send)>>> = Mutex::new(RefCell::new(None));
+
+struct SerialLogger;
+
+impl Log for SerialLogger {{
+    fn enabled(&self, metadata: &Metadata) -> bool {{
+        metadata.level() <= log::max_level()
+    }}
+
+    fn log(&self, record: &Record) {{
+        critical_section::with(|cs| {{
+            if let Some(port) = PORT.borrow_ref_mut(cs).as_mut() {{
+                let _ = write!(port, \"{{}} {{}}: {{}}\\r\\n\", record.level(), record.target(), record.args());
+            }}
+        }});
+    }}
+
+    fn flush(&self) {{}}
+}}
+
+static LOGGER: SerialLogger = SerialLogger;
+
+/// Send the log to `port`, i.e. a UART from the HAL. Call it once, early in main, before
+/// anything logs.
+pub fn init(port: &'static mut (dyn Write + Send)) {{
+{report}    critical_section::with(|cs| *PORT.borrow_ref_mut(cs) = Some(port));
+    // SAFETY: init is called once, before anything else logs
+    unsafe {{
+        let _ = log::set_logger_racy(&LOGGER);
+        log::set_max_level_racy(LevelFilter::{level});
+    }}
+}}
+", report = report, level = self.level.filter());
+        }
+        if self.panic == PanicStrategy::Persist {
+            code += &format!("
+/// What comes before the message of a panic from before the last reset, so the serial
+/// monitor can pick it out.
+const PANIC_MARKER: &str = \"{marker}\";
+
+/// Send the message of the panic that caused the last reset, if there was one, on one line.
+pub fn report_persisted_panic(port: &mut dyn Write) {{
+    if let Some(message) = panic_persist::get_panic_message_utf8() {{
+        let _ = port.write_str(PANIC_MARKER);
+        for (i, line) in message.lines().enumerate() {{
+            if i > 0 {{
+                let _ = port.write_str(\" / \");
+            }}
+            let _ = port.write_str(line);
+        }}
+        let _ = port.write_str(\"\\r\\n\");
+    }}
+}}
+", marker = PANIC_MARKER);
+        }
+        code
+    }
+
+    /// Regenerate the module if the settings changed. Returns whether it was written.
+    /// Nothing is written if the firmware doesn't need it.
+    pub fn regenerate(&self, project_dir: &Path) -> Result<bool, String> {
+        if !self.needs_module() {
+            return Ok(false);
+        }
+        std_support::write_module(project_dir, MODULE_FILE_NAME, &self.generate_module())
+    }
+
+    /// The manifest with the panic handler and the logging crates the settings need, and
+    /// without the others.
+    pub fn manifest(&self, manifest: &str) -> Result<String, String> {
+        let mut updated = manifest.to_string();
+        for name in std_support::PANIC_HANDLERS.iter().map(|(name, _)| *name).chain(LOG_CRATES) {
+            updated = std_support::remove_dependency(&updated, name)?;
+        }
+        let mut features = vec![];
+        if self.panic == PanicStrategy::Probe && self.log == LogBackend::Defmt {
+            features.push("print-defmt".to_string());
+        }
+        if self.panic == PanicStrategy::Persist {
+            features.push("utf8".to_string());
+        }
+        updated = explain::add_dependency(&updated, self.panic.crate_name(), self.panic.version(), &features)?;
+        let log_crates: &[(&str, &str, &[&str])] = match self.log {
+            LogBackend::None => &[],
+            LogBackend::Defmt => &[("defmt", "0.3", &[]), ("defmt-rtt", "0.4", &[])],
+            LogBackend::LogRtt => &[("rtt-target", "0.6", &["log"]), ("log", "0.4", &[])],
+            LogBackend::LogSerial => &[("log", "0.4", &[]), ("critical-section", "1.1", &[])],
+        };
+        for (name, version, features) in log_crates {
+            let features: Vec<String> = features.iter().map(|f| f.to_string()).collect();
+            updated = explain::add_dependency(&updated, name, version, &features)?;
+        }
+        Ok(updated)
+    }
+
+    /// The main source file with the panic handler, logger and their setup for the
+    /// settings, and notes on what has to be done by hand.
+    pub fn main_rs(&self, main_rs: &str) -> (String, Vec<String>) {
+        let mut notes = vec![];
+        let mut lines: Vec<String> = vec![];
+        // the settings' lines go where the old ones were, or after the header
+        let mut insert_at = None;
+        for line in main_rs.lines() {
+            if is_managed_line(line) {
+                if insert_at.is_none() && !line.starts_with(char::is_whitespace) {
+                    insert_at = Some(lines.len());
+                }
+                continue;
+            }
+            if std_support::is_extra_blank(&lines, line) {
+                continue;
+            }
+            lines.push(line.to_string());
+        }
+        let mut block = vec![format!("use {} as _;", self.panic.crate_name().replace('-', "_"))];
+        if self.log == LogBackend::Defmt {
+            block.push("use defmt_rtt as _;".to_string());
+        }
+        if self.needs_module() {
+            block.push("mod logging;".to_string());
+        }
+        let at = match insert_at {
+            Some(at) => at,
+            None => {
+                let at = lines.iter().position(|l| !is_header_line(l)).unwrap_or(lines.len());
+                if lines.get(at).is_some() {
+                    block.push(String::new());
+                }
+                at
+            },
+        };
+        lines.splice(at..at, block);
+
+        if self.log == LogBackend::LogRtt {
+            match lines.iter().position(|l| l.contains("fn main(") && l.trim_end().ends_with('{')) {
+                Some(main) => lines.insert(main + 1, format!("    rtt_target::rtt_init_log!(log::LevelFilter::{});", self.level.filter())),
+                None => notes.push("call rtt_target::rtt_init_log!() first thing in main".to_string()),
+            }
+        }
+        if self.log == LogBackend::LogSerial {
+            notes.push("pass the serial port to logging::init early in main".to_string());
+        } else if self.panic == PanicStrategy::Persist {
+            notes.push("send the panic from before the reset with logging::report_persisted_panic once the serial port is set up".to_string());
+        }
+        (lines.join("\n") + "\n", notes)
+    }
+
+    /// The cargo config with defmt's linker script and log level if the log goes out with
+    /// defmt, and without them if it doesn't. Returns whether a target to link for was
+    /// found.
+    pub fn cargo_config(&self, config: &str) -> Result<(String, bool), String> {
+        let mut doc: toml_edit::DocumentMut = config.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+        let defmt = self.log == LogBackend::Defmt;
+        let mut found = false;
+        if let Some(targets) = doc.get_mut("target").and_then(toml_edit::Item::as_table_like_mut) {
+            for (_, target) in targets.iter_mut() {
+                let Some(flags) = target.get_mut("rustflags").and_then(toml_edit::Item::as_array_mut) else {
+                    continue;
+                };
+                found = true;
+                let position = flags.iter().position(|f| f.as_str() == Some(DEFMT_LINK_ARG));
+                match (defmt, position) {
+                    (true, None) => {
+                        flags.push("-C");
+                        flags.push(DEFMT_LINK_ARG);
+                    },
+                    (false, Some(i)) => {
+                        flags.remove(i);
+                        if i > 0 && flags.get(i - 1).and_then(|f| f.as_str()) == Some("-C") {
+                            flags.remove(i - 1);
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        }
+        if defmt {
+            doc.entry("env").or_insert(toml_edit::table())["DEFMT_LOG"] = toml_edit::value(self.level.to_string());
+        } else if let Some(env) = doc.get_mut("env").and_then(toml_edit::Item::as_table_like_mut) {
+            env.remove("DEFMT_LOG");
+            if env.is_empty() {
+                doc.remove("env");
+            }
+        }
+        Ok((doc.to_string(), found))
+    }
+
+    /// The linker script with the RAM panic-persist keeps the message in, which is taken
+    /// off the top of the stack, or without it.
+    pub fn memory_x(&self, memory_x: &str) -> String {
+        let mut kept = memory_x.to_string();
+        if let (Some(begin), Some(end)) = (kept.find(PANIC_DUMP_BEGIN), kept.find(PANIC_DUMP_END)) {
+            if begin < end {
+                kept.replace_range(begin..end + PANIC_DUMP_END.len(), "");
+                kept = kept.trim_end().to_string() + "\n";
+            }
+        }
+        if self.panic == PanicStrategy::Persist {
+            kept += &format!("
+{}
+_panic_dump_end = ORIGIN(RAM) + LENGTH(RAM);
+_panic_dump_start = _panic_dump_end - 1K;
+_stack_start = _panic_dump_start;
+{}
+", PANIC_DUMP_BEGIN, PANIC_DUMP_END);
+        }
+        kept
+    }
+
+    /// Change the project's files for the settings. The cargo config and linker script are
+    /// only changed if the project has them.
+    pub fn apply(&self, project_dir: &Path, std_settings: &StdSettings) -> Result<Applied, String> {
+        if std_settings.support == StdSupport::Std {
+            return Err("std programs print their panics and log themselves".to_string());
+        }
+        let mut applied = Applied::default();
+        update_file(project_dir, PathBuf::from("Cargo.toml"), true, &mut applied, |manifest, _| self.manifest(manifest))?;
+        update_file(project_dir, PathBuf::from("src").join("main.rs"), true, &mut applied, |main, notes| {
+            let (main, more) = self.main_rs(main);
+            notes.extend(more);
+            Ok(main)
+        })?;
+        let config = update_file(project_dir, PathBuf::from(".cargo").join("config.toml"), false, &mut applied, |config, notes| {
+            let (config, found) = self.cargo_config(config)?;
+            if self.log == LogBackend::Defmt && !found {
+                notes.push(format!("link with -C {} for the target", DEFMT_LINK_ARG));
+            }
+            Ok(config)
+        })?;
+        if self.log == LogBackend::Defmt && !config {
+            applied.notes.push(format!("link with -C {} in .cargo/config.toml", DEFMT_LINK_ARG));
+        }
+        let linker_script = update_file(project_dir, PathBuf::from("memory.x"), false, &mut applied, |memory, _| Ok(self.memory_x(memory)))?;
+        if self.panic == PanicStrategy::Persist && !linker_script {
+            applied.notes.push("panic-persist needs _panic_dump_start and _panic_dump_end in the linker script".to_string());
+        }
+        if !self.needs_module() {
+            std_support::remove_module(project_dir, MODULE_FILE_NAME, &mut applied)?;
+        } else if self.regenerate(project_dir)? {
+            applied.changed.push(PathBuf::from("src").join(MODULE_FILE_NAME));
+        }
+        Ok(applied)
+    }
+
+    /// Show the settings to be edited. Returns whether they changed.
+    pub fn display(&mut self, ui: &mut egui::Ui, main_board: Option<&Board>) -> bool {
+        let before = self.clone();
+        egui::Grid::new("logging_settings").num_columns(2).show(ui, |ui| {
+            ui.label("on panic:");
+            egui::ComboBox::from_id_source("panic_strategy").selected_text(self.panic.to_string()).show_ui(ui, |ui| {
+                for panic in enum_iterator::all::<PanicStrategy>() {
+                    ui.selectable_value(&mut self.panic, panic, panic.to_string()).on_hover_text(panic.describe());
+                }
+            });
+            ui.end_row();
+            ui.label("log:");
+            egui::ComboBox::from_id_source("log_backend").selected_text(self.log.to_string()).show_ui(ui, |ui| {
+                for log in enum_iterator::all::<LogBackend>() {
+                    ui.selectable_value(&mut self.log, log, log.to_string());
+                }
+            });
+            ui.end_row();
+            if self.log != LogBackend::None {
+                ui.label("level:");
+                egui::ComboBox::from_id_source("log_level").selected_text(self.level.to_string()).show_ui(ui, |ui| {
+                    for level in enum_iterator::all::<LogLevel>() {
+                        ui.selectable_value(&mut self.level, level, level.to_string());
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        for warning in self.warnings(main_board) {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+        *self != before
+    }
+}
@@ -0,0 +1,844 @@
+//! Title: Iron Coder Project Module - LoRaWAN
+//! Description: Credentials and session keys for LoRaWAN boards, generation of the
+//!   matching config module for the firmware, and decoding of uplinks while testing,
+//!   either straight from a gateway (Semtech UDP packet forwarder) or from The Things
+//!   Stack's storage API.
+
+use log::{info, warn};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use quote::quote;
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event, EventBus};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+
+/// The most uplinks we keep around for display.
+const MAX_UPLINKS: usize = 200;
+
+/// How a device joins the network.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Activation {
+    /// Over-the-air activation, using the DevEUI, JoinEUI and AppKey
+    #[default]
+    Otaa,
+    /// Activation by personalization, using a fixed DevAddr and session keys
+    Abp,
+}
+
+/// The LoRaWAN credentials of a project's device. All values are hex strings, written
+/// MSB first, the way network servers show them.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct LoraWanConfig {
+    pub activation: Activation,
+    pub dev_eui: String,
+    pub join_eui: String,
+    pub app_key: String,
+    pub dev_addr: String,
+    pub nwk_s_key: String,
+    pub app_s_key: String,
+}
+
+/// Parse a hex string of exactly N bytes, ignoring spaces, colons and dashes.
+pub fn parse_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !matches!(b, b' ' | b':' | b'-')).collect();
+    if digits.len() != 2 * N {
+        return None;
+    }
+    let mut out = [0u8; N];
+    for (i, pair) in digits.chunks(2).enumerate() {
+        let pair = std::str::from_utf8(pair).ok()?;
+        out[i] = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(out)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Generate a random hex key of N bytes.
+pub fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    match getrandom::getrandom(&mut bytes) {
+        Ok(()) => to_hex(&bytes),
+        Err(e) => {
+            warn!("couldn't get random bytes: {}", e);
+            String::new()
+        },
+    }
+}
+
+impl LoraWanConfig {
+
+    /// Return a description of the first field that isn't valid, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |name: &str, ok: bool| if ok { Ok(()) } else { Err(format!("{} is not valid hex of the right length", name)) };
+        match self.activation {
+            Activation::Otaa => {
+                check("DevEUI", parse_hex::<8>(&self.dev_eui).is_some())?;
+                check("JoinEUI", parse_hex::<8>(&self.join_eui).is_some())?;
+                check("AppKey", parse_hex::<16>(&self.app_key).is_some())?;
+            },
+            Activation::Abp => {
+                check("DevAddr", parse_hex::<4>(&self.dev_addr).is_some())?;
+                check("NwkSKey", parse_hex::<16>(&self.nwk_s_key).is_some())?;
+                check("AppSKey", parse_hex::<16>(&self.app_s_key).is_some())?;
+            },
+        }
+        Ok(())
+    }
+
+    /// Generate the Rust source of a config module holding the credentials. EUIs are
+    /// emitted LSB first, which is what the lorawan-device crate expects.
+    pub fn generate_module(&self) -> Result<String, String> {
+        self.validate()?;
+        let tokens = match self.activation {
+            Activation::Otaa => {
+                let mut dev_eui = parse_hex::<8>(&self.dev_eui).unwrap_or_default();
+                let mut join_eui = parse_hex::<8>(&self.join_eui).unwrap_or_default();
+                dev_eui.reverse();
+                join_eui.reverse();
+                let app_key = parse_hex::<16>(&self.app_key).unwrap_or_default();
+                quote! {
+                    //! LoRaWAN credentials, generated by Iron Coder. Don't edit by hand.
+
+                    /// The device EUI, LSB first
+                    pub const DEV_EUI: [u8; 8] = [#(#dev_eui),*];
+                    /// The join (application) EUI, LSB first
+                    pub const JOIN_EUI: [u8; 8] = [#(#join_eui),*];
+                    /// The application key, MSB first
+                    pub const APP_KEY: [u8; 16] = [#(#app_key),*];
+                }
+            },
+            Activation::Abp => {
+                let dev_addr = u32::from_be_bytes(parse_hex::<4>(&self.dev_addr).unwrap_or_default());
+                let nwk_s_key = parse_hex::<16>(&self.nwk_s_key).unwrap_or_default();
+                let app_s_key = parse_hex::<16>(&self.app_s_key).unwrap_or_default();
+                quote! {
+                    //! LoRaWAN session, generated by Iron Coder. Don't edit by hand.
+
+                    /// The device address
+                    pub const DEV_ADDR: u32 = #dev_addr;
+                    /// The network session key, MSB first
+                    pub const NWK_S_KEY: [u8; 16] = [#(#nwk_s_key),*];
+                    /// The application session key, MSB first
+                    pub const APP_S_KEY: [u8; 16] = [#(#app_s_key),*];
+                }
+            },
+        };
+        let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+        Ok(prettyplease::unparse(&file))
+    }
+
+    /// Write the config module into the project's source directory.
+    pub fn write_module(&self, project_dir: &Path) -> io::Result<()> {
+        let code = self.generate_module().map_err(io::Error::other)?;
+        let src = project_dir.join("src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("lorawan_config.rs"), code)
+    }
+}
+
+/// AES-128 (encryption only, which is all LoRaWAN needs) and AES-CMAC.
+mod aes {
+    const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+    ];
+
+    fn xtime(x: u8) -> u8 {
+        (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+    }
+
+    pub fn encrypt_block(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+        // expand the key into 11 round keys
+        let mut w = [[0u8; 4]; 44];
+        for (i, word) in key.chunks(4).enumerate() {
+            w[i].copy_from_slice(word);
+        }
+        let mut rcon = 1u8;
+        for i in 4..44 {
+            let mut t = w[i - 1];
+            if i % 4 == 0 {
+                t = [SBOX[t[1] as usize] ^ rcon, SBOX[t[2] as usize], SBOX[t[3] as usize], SBOX[t[0] as usize]];
+                rcon = xtime(rcon);
+            }
+            for j in 0..4 {
+                w[i][j] = w[i - 4][j] ^ t[j];
+            }
+        }
+        // the state is stored column by column, like the input
+        let mut s = *block;
+        let add_round_key = |s: &mut [u8; 16], round: usize| {
+            for (i, b) in s.iter_mut().enumerate() {
+                *b ^= w[4 * round + i / 4][i % 4];
+            }
+        };
+        add_round_key(&mut s, 0);
+        for round in 1..=10 {
+            for b in s.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            let old = s;
+            for c in 0..4 {
+                for r in 0..4 {
+                    s[4 * c + r] = old[4 * ((c + r) % 4) + r];
+                }
+            }
+            if round != 10 {
+                for c in 0..4 {
+                    let a = [s[4 * c], s[4 * c + 1], s[4 * c + 2], s[4 * c + 3]];
+                    let b = a.map(xtime);
+                    s[4 * c] = b[0] ^ a[3] ^ a[2] ^ b[1] ^ a[1];
+                    s[4 * c + 1] = b[1] ^ a[0] ^ a[3] ^ b[2] ^ a[2];
+                    s[4 * c + 2] = b[2] ^ a[1] ^ a[0] ^ b[3] ^ a[3];
+                    s[4 * c + 3] = b[3] ^ a[2] ^ a[1] ^ b[0] ^ a[0];
+                }
+            }
+            add_round_key(&mut s, round);
+        }
+        s
+    }
+
+    /// Double a block in GF(2^128), for the CMAC subkeys.
+    fn double(block: &[u8; 16]) -> [u8; 16] {
+        let mut out = [0u8; 16];
+        for i in 0..16 {
+            out[i] = block[i] << 1 | block.get(i + 1).map_or(0, |b| b >> 7);
+        }
+        if block[0] & 0x80 != 0 {
+            out[15] ^= 0x87;
+        }
+        out
+    }
+
+    /// AES-CMAC (RFC 4493)
+    pub fn cmac(key: &[u8; 16], msg: &[u8]) -> [u8; 16] {
+        let k1 = double(&encrypt_block(key, &[0u8; 16]));
+        let k2 = double(&k1);
+        let n = msg.len().div_ceil(16).max(1);
+        let mut x = [0u8; 16];
+        for i in 0..n {
+            let chunk = &msg[(16 * i).min(msg.len())..(16 * i + 16).min(msg.len())];
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            if i == n - 1 {
+                let subkey = if chunk.len() == 16 { k1 } else { block[chunk.len()] = 0x80; k2 };
+                for j in 0..16 {
+                    block[j] ^= subkey[j];
+                }
+            }
+            for j in 0..16 {
+                block[j] ^= x[j];
+            }
+            x = encrypt_block(key, &block);
+        }
+        x
+    }
+}
+
+pub use aes::{cmac, encrypt_block};
+
+/// The LoRaWAN message types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    JoinRequest,
+    JoinAccept,
+    UnconfirmedUp,
+    UnconfirmedDown,
+    ConfirmedUp,
+    ConfirmedDown,
+    Other,
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MessageType::JoinRequest => write!(f, "join request"),
+            MessageType::JoinAccept => write!(f, "join accept"),
+            MessageType::UnconfirmedUp => write!(f, "unconfirmed up"),
+            MessageType::UnconfirmedDown => write!(f, "unconfirmed down"),
+            MessageType::ConfirmedUp => write!(f, "confirmed up"),
+            MessageType::ConfirmedDown => write!(f, "confirmed down"),
+            MessageType::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// A decoded uplink, from whichever source it came.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Uplink {
+    /// Where the uplink came from, i.e. the gateway EUI
+    pub source: String,
+    /// The DevAddr, DevEUI or device id, whatever the source knows
+    pub device: String,
+    pub kind: Option<MessageType>,
+    pub f_cnt: Option<u32>,
+    pub f_port: Option<u8>,
+    /// The application payload, decrypted if we (or the network server) could
+    pub payload: Vec<u8>,
+    pub decrypted: bool,
+    /// Whether the message integrity code checked out, if we had the key to check it
+    pub mic_ok: Option<bool>,
+    pub rssi: Option<f32>,
+    pub snr: Option<f32>,
+}
+
+/// Encrypt or decrypt (it's the same operation) a FRMPayload.
+fn crypt_payload(key: &[u8; 16], uplink: bool, dev_addr: [u8; 4], f_cnt: u32, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len());
+    for (i, chunk) in payload.chunks(16).enumerate() {
+        let mut a = [0u8; 16];
+        a[0] = 0x01;
+        a[5] = if uplink { 0 } else { 1 };
+        a[6..10].copy_from_slice(&dev_addr);
+        a[10..14].copy_from_slice(&f_cnt.to_le_bytes());
+        a[15] = i as u8 + 1;
+        let s = aes::encrypt_block(key, &a);
+        out.extend(chunk.iter().zip(s.iter()).map(|(p, s)| p ^ s));
+    }
+    out
+}
+
+/// Decode a raw PHYPayload. Payloads are decrypted and MICs checked when the session
+/// keys in `config` are for this device.
+pub fn decode_phy_payload(phy: &[u8], config: &LoraWanConfig) -> Result<Uplink, String> {
+    if phy.len() < 5 {
+        return Err("PHYPayload too short".to_string());
+    }
+    let (msg, mic) = phy.split_at(phy.len() - 4);
+    let kind = match phy[0] >> 5 {
+        0 => MessageType::JoinRequest,
+        1 => MessageType::JoinAccept,
+        2 => MessageType::UnconfirmedUp,
+        3 => MessageType::UnconfirmedDown,
+        4 => MessageType::ConfirmedUp,
+        5 => MessageType::ConfirmedDown,
+        _ => MessageType::Other,
+    };
+    let mut uplink = Uplink { kind: Some(kind), ..Default::default() };
+    match kind {
+        MessageType::JoinRequest => {
+            if msg.len() != 19 {
+                return Err("join request has the wrong length".to_string());
+            }
+            let mut dev_eui = [0u8; 8];
+            dev_eui.copy_from_slice(&msg[9..17]);
+            dev_eui.reverse();
+            uplink.device = to_hex(&dev_eui);
+            if let Some(app_key) = parse_hex::<16>(&config.app_key) {
+                if parse_hex::<8>(&config.dev_eui) == Some(dev_eui) {
+                    uplink.mic_ok = Some(aes::cmac(&app_key, msg)[..4] == *mic);
+                }
+            }
+        },
+        MessageType::UnconfirmedUp | MessageType::ConfirmedUp | MessageType::UnconfirmedDown | MessageType::ConfirmedDown => {
+            if msg.len() < 8 {
+                return Err("data message too short".to_string());
+            }
+            let up = matches!(kind, MessageType::UnconfirmedUp | MessageType::ConfirmedUp);
+            let dev_addr = [msg[1], msg[2], msg[3], msg[4]];
+            let f_opts_len = (msg[5] & 0x0f) as usize;
+            let f_cnt = u16::from_le_bytes([msg[6], msg[7]]) as u32;
+            let mut be_addr = dev_addr;
+            be_addr.reverse();
+            uplink.device = to_hex(&be_addr);
+            uplink.f_cnt = Some(f_cnt);
+            let port_at = 8 + f_opts_len;
+            if msg.len() < port_at {
+                return Err("FOpts run past the end of the message".to_string());
+            }
+            if msg.len() > port_at {
+                let f_port = msg[port_at];
+                uplink.f_port = Some(f_port);
+                uplink.payload = msg[port_at + 1..].to_vec();
+                // only decrypt with our session keys if the message is for our device
+                if parse_hex::<4>(&config.dev_addr) == Some(be_addr) {
+                    let key = if f_port == 0 { &config.nwk_s_key } else { &config.app_s_key };
+                    if let Some(key) = parse_hex::<16>(key) {
+                        uplink.payload = crypt_payload(&key, up, dev_addr, f_cnt, &uplink.payload);
+                        uplink.decrypted = true;
+                    }
+                }
+            }
+            if parse_hex::<4>(&config.dev_addr) == Some(be_addr) {
+                if let Some(nwk_s_key) = parse_hex::<16>(&config.nwk_s_key) {
+                    let mut b0 = [0u8; 16];
+                    b0[0] = 0x49;
+                    b0[5] = if up { 0 } else { 1 };
+                    b0[6..10].copy_from_slice(&dev_addr);
+                    b0[10..14].copy_from_slice(&f_cnt.to_le_bytes());
+                    b0[15] = msg.len() as u8;
+                    let mut data = b0.to_vec();
+                    data.extend_from_slice(msg);
+                    uplink.mic_ok = Some(aes::cmac(&nwk_s_key, &data)[..4] == *mic);
+                }
+            }
+        },
+        _ => uplink.payload = msg[1..].to_vec(),
+    }
+    Ok(uplink)
+}
+
+/// Decode a Cayenne LPP payload into one line per value.
+pub fn decode_cayenne_lpp(payload: &[u8]) -> Result<Vec<String>, String> {
+    let mut lines = vec![];
+    let mut i = 0;
+    let int = |bytes: &[u8], signed: bool| -> f64 {
+        let mut v: i64 = 0;
+        for b in bytes {
+            v = (v << 8) | *b as i64;
+        }
+        let bits = 8 * bytes.len() as u32;
+        if signed && v >> (bits - 1) == 1 {
+            v -= 1 << bits;
+        }
+        v as f64
+    };
+    while i + 2 <= payload.len() {
+        let (channel, kind) = (payload[i], payload[i + 1]);
+        let (name, size) = match kind {
+            0 => ("digital input", 1),
+            1 => ("digital output", 1),
+            2 => ("analog input", 2),
+            3 => ("analog output", 2),
+            101 => ("illuminance", 2),
+            102 => ("presence", 1),
+            103 => ("temperature", 2),
+            104 => ("humidity", 1),
+            113 => ("accelerometer", 6),
+            115 => ("barometer", 2),
+            134 => ("gyrometer", 6),
+            136 => ("gps", 9),
+            _ => return Err(format!("unknown LPP type {} on channel {}", kind, channel)),
+        };
+        let data = payload.get(i + 2..i + 2 + size).ok_or(format!("{} on channel {} is truncated", name, channel))?;
+        let value = match kind {
+            0 | 1 | 102 => format!("{}", data[0]),
+            2 | 3 => format!("{:.2}", int(data, true) / 100.0),
+            101 => format!("{} lux", int(data, false)),
+            103 => format!("{:.1} °C", int(data, true) / 10.0),
+            104 => format!("{:.1} %", int(data, false) / 2.0),
+            113 => format!("x {:.3} y {:.3} z {:.3} g", int(&data[0..2], true) / 1000.0, int(&data[2..4], true) / 1000.0, int(&data[4..6], true) / 1000.0),
+            115 => format!("{:.1} hPa", int(data, false) / 10.0),
+            134 => format!("x {:.2} y {:.2} z {:.2} °/s", int(&data[0..2], true) / 100.0, int(&data[2..4], true) / 100.0, int(&data[4..6], true) / 100.0),
+            _ => format!("lat {:.4} lon {:.4} alt {:.2} m", int(&data[0..3], true) / 10000.0, int(&data[3..6], true) / 10000.0, int(&data[6..9], true) / 100.0),
+        };
+        lines.push(format!("ch {} {}: {}", channel, name, value));
+        i += 2 + size;
+    }
+    if i != payload.len() {
+        return Err("trailing bytes after the last LPP value".to_string());
+    }
+    Ok(lines)
+}
+
+/// A packet received by a gateway, as reported by its packet forwarder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RxPacket {
+    pub phy: Vec<u8>,
+    pub rssi: Option<f32>,
+    pub snr: Option<f32>,
+}
+
+/// A parsed Semtech UDP packet forwarder datagram.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForwarderDatagram {
+    /// The acknowledgement to send back, if the datagram needs one
+    pub ack: Option<Vec<u8>>,
+    /// The EUI of the gateway that sent it
+    pub gateway: String,
+    pub packets: Vec<RxPacket>,
+}
+
+/// Handle one Semtech UDP packet forwarder datagram.
+pub fn parse_forwarder_datagram(datagram: &[u8]) -> Result<ForwarderDatagram, String> {
+    if datagram.len() < 12 || datagram[0] != 2 {
+        return Err("not a packet forwarder datagram".to_string());
+    }
+    let gateway = to_hex(&datagram[4..12]);
+    let ack_id = match datagram[3] {
+        0x00 => 0x01,   // PUSH_DATA -> PUSH_ACK
+        0x02 => 0x04,   // PULL_DATA -> PULL_ACK
+        _ => return Ok(ForwarderDatagram { ack: None, gateway, packets: vec![] }),
+    };
+    let ack = vec![datagram[0], datagram[1], datagram[2], ack_id];
+    let mut packets = vec![];
+    if datagram[3] == 0x00 {
+        let json: serde_json::Value = serde_json::from_slice(&datagram[12..]).map_err(|e| e.to_string())?;
+        for rxpk in json["rxpk"].as_array().into_iter().flatten() {
+            if let Some(data) = rxpk["data"].as_str() {
+                let phy = BASE64.decode(data).map_err(|e| e.to_string())?;
+                packets.push(RxPacket {
+                    phy,
+                    rssi: rxpk["rssi"].as_f64().map(|v| v as f32),
+                    snr: rxpk["lsnr"].as_f64().map(|v| v as f32),
+                });
+            }
+        }
+    }
+    Ok(ForwarderDatagram { ack: Some(ack), gateway, packets })
+}
+
+/// Parse The Things Stack storage API response (newline-delimited JSON results).
+/// Returns the uplinks and the latest `received_at` timestamp seen.
+pub fn parse_tts_uplinks(body: &str) -> (Vec<Uplink>, Option<String>) {
+    let mut uplinks = vec![];
+    let mut latest = None;
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        let json: serde_json::Value = match serde_json::from_str(line) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("couldn't parse uplink from the network server: {}", e);
+                continue;
+            },
+        };
+        let result = &json["result"];
+        let msg = &result["uplink_message"];
+        let metadata = msg["rx_metadata"].get(0);
+        uplinks.push(Uplink {
+            source: "The Things Stack".to_string(),
+            device: result["end_device_ids"]["device_id"].as_str().unwrap_or("unknown").to_string(),
+            kind: None,
+            f_cnt: msg["f_cnt"].as_u64().map(|v| v as u32),
+            f_port: msg["f_port"].as_u64().map(|v| v as u8),
+            payload: msg["frm_payload"].as_str().and_then(|p| BASE64.decode(p).ok()).unwrap_or_default(),
+            decrypted: true,
+            mic_ok: None,
+            rssi: metadata.and_then(|m| m["rssi"].as_f64()).map(|v| v as f32),
+            snr: metadata.and_then(|m| m["snr"].as_f64()).map(|v| v as f32),
+        });
+        if let Some(received_at) = result["received_at"].as_str() {
+            latest = Some(received_at.to_string());
+        }
+    }
+    (uplinks, latest)
+}
+
+/// Listen for a gateway's packet forwarder, decoding everything it receives.
+fn listen_for_gateway(port: u16, config: LoraWanConfig, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("couldn't listen on port {}: {}", port, e))?;
+    socket.set_read_timeout(Some(Duration::from_millis(250))).map_err(|e| e.to_string())?;
+    job.set_status(&format!("listening on UDP port {}", port));
+    let mut buf = [0u8; 65536];
+    while !job.is_cancelled() {
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(r) => r,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        match parse_forwarder_datagram(&buf[..len]) {
+            Ok(ForwarderDatagram { ack, gateway, packets }) => {
+                if let Some(ack) = ack {
+                    let _ = socket.send_to(&ack, from);
+                }
+                for RxPacket { phy, rssi, snr } in packets {
+                    match decode_phy_payload(&phy, &config) {
+                        Ok(uplink) => bus.publish(Event::LoraUplink(Uplink { source: gateway.clone(), rssi, snr, ..uplink })),
+                        Err(e) => info!("couldn't decode packet from gateway {}: {}", gateway, e),
+                    }
+                }
+                job.request_repaint();
+            },
+            Err(e) => info!("ignoring datagram from {}: {}", from, e),
+        }
+    }
+    Ok(())
+}
+
+/// Poll The Things Stack storage integration for new uplinks.
+fn poll_network_server(settings: NetworkServer, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    let url = format!("{}/api/v3/as/applications/{}/packages/storage/uplink_message",
+                      settings.url.trim_end_matches('/'), settings.application_id);
+    let mut after: Option<String> = None;
+    let mut last_poll: Option<Instant> = None;
+    while !job.is_cancelled() {
+        if last_poll.is_some_and(|t| t.elapsed() < Duration::from_secs(5)) {
+            std::thread::sleep(Duration::from_millis(250));
+            continue;
+        }
+        last_poll = Some(Instant::now());
+        let mut request = ureq::get(&url)
+            .set("Authorization", &format!("Bearer {}", settings.api_key))
+            .set("Accept", "text/event-stream");
+        request = match &after {
+            Some(after) => request.query("after", after),
+            None => request.query("last", "10m"),
+        };
+        let body = request.call().map_err(|e| e.to_string())?.into_string().map_err(|e| e.to_string())?;
+        let (uplinks, latest) = parse_tts_uplinks(&body);
+        for uplink in uplinks {
+            bus.publish(Event::LoraUplink(uplink));
+        }
+        after = latest.or(after);
+        job.set_status(&format!("polling {}", settings.url));
+        job.request_repaint();
+    }
+    Ok(())
+}
+
+/// Where uplinks are read from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UplinkSource {
+    #[default]
+    Gateway,
+    NetworkServer,
+}
+
+/// The Things Stack connection settings.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct NetworkServer {
+    pub url: String,
+    pub application_id: String,
+    #[serde(skip)]
+    pub api_key: String,
+}
+
+/// How uplink payloads are shown.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadFormat {
+    #[default]
+    Hex,
+    Text,
+    CayenneLpp,
+}
+
+fn format_payload(payload: &[u8], format: PayloadFormat) -> String {
+    match format {
+        PayloadFormat::Hex => to_hex(payload),
+        PayloadFormat::Text => String::from_utf8_lossy(payload).to_string(),
+        PayloadFormat::CayenneLpp => match decode_cayenne_lpp(payload) {
+            Ok(lines) => lines.join(", "),
+            Err(e) => format!("{} ({})", to_hex(payload), e),
+        },
+    }
+}
+
+/// The state of the LoRaWAN window. The credentials themselves belong to the project.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LoraWanPanel {
+    pub source: UplinkSource,
+    pub gateway_port: u16,
+    pub network_server: NetworkServer,
+    pub payload_format: PayloadFormat,
+    #[serde(skip)]
+    uplinks: Vec<Uplink>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl LoraWanPanel {
+
+    fn start(&mut self, ctx: &egui::Context, config: &LoraWanConfig) {
+        let bus = events::shared(ctx);
+        let scheduler = jobs::shared(ctx);
+        self.job = Some(match self.source {
+            UplinkSource::Gateway => {
+                let port = if self.gateway_port == 0 { 1700 } else { self.gateway_port };
+                let config = config.clone();
+                scheduler.spawn("LoRaWAN gateway listener", Some(ctx), move |job| listen_for_gateway(port, config, &bus, job))
+            },
+            UplinkSource::NetworkServer => {
+                let settings = self.network_server.clone();
+                scheduler.spawn("LoRaWAN uplink polling", Some(ctx), move |job| poll_network_server(settings, &bus, job))
+            },
+        });
+    }
+
+    /// Stop listening for uplinks, for when the window is closed. Nothing else shows
+    /// them, so the listener is stopped too.
+    pub fn close(&mut self, ctx: &egui::Context) {
+        if let Some(id) = self.job.take() {
+            jobs::shared(ctx).cancel(id);
+        }
+        self.events = None;
+    }
+
+    fn credentials_ui(&mut self, ui: &mut egui::Ui, config: &mut LoraWanConfig) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut config.activation, Activation::Otaa, "OTAA");
+            ui.radio_value(&mut config.activation, Activation::Abp, "ABP");
+        });
+        egui::Grid::new("lorawan_keys").num_columns(3).show(ui, |ui| {
+            let row = |ui: &mut egui::Ui, label: &str, value: &mut String, len: usize| {
+                ui.label(label);
+                let valid = value.is_empty() || value.chars().filter(|c| c.is_ascii_hexdigit()).count() == 2 * len;
+                let mut edit = egui::TextEdit::singleline(value).font(egui::TextStyle::Monospace).desired_width(280.0);
+                if !valid {
+                    edit = edit.text_color(ui.style().visuals.error_fg_color);
+                }
+                ui.add(edit);
+                if ui.button("generate").clicked() {
+                    *value = match len {
+                        4 => random_hex::<4>(),
+                        8 => random_hex::<8>(),
+                        _ => random_hex::<16>(),
+                    };
+                }
+                ui.end_row();
+            };
+            match config.activation {
+                Activation::Otaa => {
+                    row(ui, "DevEUI", &mut config.dev_eui, 8);
+                    row(ui, "JoinEUI", &mut config.join_eui, 8);
+                    row(ui, "AppKey", &mut config.app_key, 16);
+                },
+                Activation::Abp => {
+                    row(ui, "DevAddr", &mut config.dev_addr, 4);
+                    row(ui, "NwkSKey", &mut config.nwk_s_key, 16);
+                    row(ui, "AppSKey", &mut config.app_s_key, 16);
+                },
+            }
+        });
+        ui.label(egui::RichText::new("keys are stored in the project file").small());
+    }
+
+    /// Show the credentials, code generation and uplink controls in the Ui.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, config: &mut LoraWanConfig, project_dir: Option<&Path>) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        while let Ok(event) = rx.try_recv() {
+            if let Event::LoraUplink(uplink) = event {
+                self.uplinks.push(uplink);
+            }
+        }
+        if self.uplinks.len() > MAX_UPLINKS {
+            self.uplinks.drain(..self.uplinks.len() - MAX_UPLINKS);
+        }
+
+        self.credentials_ui(ui, config);
+        ui.horizontal(|ui| {
+            let button = ui.add_enabled(project_dir.is_some(), egui::Button::new("generate src/lorawan_config.rs"));
+            if button.clicked() {
+                if let Some(dir) = project_dir {
+                    self.message = Some(match config.write_module(dir) {
+                        Ok(()) => "wrote src/lorawan_config.rs".to_string(),
+                        Err(e) => e.to_string(),
+                    });
+                }
+            }
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+        });
+        ui.separator();
+
+        let running = self.job.map(|id| jobs::shared(ctx).state(id)) == Some(Some(JobState::Running));
+        ui.add_enabled_ui(!running, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.source, UplinkSource::Gateway, "gateway (UDP packet forwarder)");
+                ui.radio_value(&mut self.source, UplinkSource::NetworkServer, "The Things Stack");
+            });
+            match self.source {
+                UplinkSource::Gateway => {
+                    if self.gateway_port == 0 {
+                        self.gateway_port = 1700;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("port:");
+                        ui.add(egui::DragValue::new(&mut self.gateway_port).clamp_range(1..=65535));
+                    });
+                },
+                UplinkSource::NetworkServer => {
+                    egui::Grid::new("lorawan_server").num_columns(2).show(ui, |ui| {
+                        let server = &mut self.network_server;
+                        ui.label("server:");
+                        ui.add(egui::TextEdit::singleline(&mut server.url).hint_text("https://eu1.cloud.thethings.network"));
+                        ui.end_row();
+                        ui.label("application id:");
+                        ui.text_edit_singleline(&mut server.application_id);
+                        ui.end_row();
+                        ui.label("API key:");
+                        ui.add(egui::TextEdit::singleline(&mut server.api_key).password(true));
+                        ui.end_row();
+                    });
+                },
+            }
+        });
+        ui.horizontal(|ui| {
+            if running {
+                if ui.button("stop").clicked() {
+                    if let Some(id) = self.job {
+                        jobs::shared(ctx).cancel(id);
+                    }
+                }
+            } else if ui.button("start").clicked() {
+                self.start(ctx, config);
+            }
+            if ui.button("clear").clicked() {
+                self.uplinks.clear();
+            }
+            egui::ComboBox::from_id_source("lorawan_payload_format")
+            .selected_text(format!("{:?}", self.payload_format))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.payload_format, PayloadFormat::Hex, "Hex");
+                ui.selectable_value(&mut self.payload_format, PayloadFormat::Text, "Text");
+                ui.selectable_value(&mut self.payload_format, PayloadFormat::CayenneLpp, "CayenneLpp");
+            });
+            if let Some(Some(JobState::Failed(e))) = self.job.map(|id| jobs::shared(ctx).state(id)) {
+                ui.colored_label(ui.style().visuals.error_fg_color, e);
+            }
+        });
+
+        egui::ScrollArea::vertical().max_height(300.0).stick_to_bottom(true).show(ui, |ui| {
+            egui::Grid::new("lorawan_uplinks").striped(true).num_columns(7).show(ui, |ui| {
+                for header in ["device", "type", "fcnt", "port", "rssi/snr", "mic", "payload"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for uplink in self.uplinks.iter() {
+                    ui.label(&uplink.device).on_hover_text(&uplink.source);
+                    ui.label(uplink.kind.map(|k| k.to_string()).unwrap_or_default());
+                    ui.label(uplink.f_cnt.map(|v| v.to_string()).unwrap_or_default());
+                    ui.label(uplink.f_port.map(|v| v.to_string()).unwrap_or_default());
+                    ui.label(match (uplink.rssi, uplink.snr) {
+                        (Some(rssi), Some(snr)) => format!("{} dBm / {} dB", rssi, snr),
+                        _ => String::new(),
+                    });
+                    ui.label(match uplink.mic_ok {
+                        Some(true) => "ok",
+                        Some(false) => "BAD",
+                        None => "",
+                    });
+                    let payload = if uplink.decrypted {
+                        format_payload(&uplink.payload, self.payload_format)
+                    } else {
+                        format!("{} (encrypted)", to_hex(&uplink.payload))
+                    };
+                    ui.monospace(payload);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,742 @@
+//! Title: Iron Coder Project Module - MCUmgr
+//! Description: Querying and confirming the images on a device running MCUboot, over a
+//!   serial port. Devices with the MCUmgr SMP server are spoken to in SMP (CBOR requests
+//!   in base64 frames, which can share the port with a shell); devices that only have a
+//!   Zephyr shell get the `mcuboot` shell command instead.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event, EventBus};
+use crate::app::jobs::{self, JobContext, JobId, JobState};
+
+use cbor::Value;
+
+/// How long to wait for a device to answer.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the shell has to be quiet before we take its output as complete.
+const SHELL_QUIET_TIME: Duration = Duration::from_millis(500);
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum McumgrError {
+    /// The serial port couldn't be opened, read or written
+    IoError(io::Error),
+    /// The device didn't answer in time
+    Timeout,
+    /// The device sent something we couldn't make sense of
+    ProtocolError(String),
+    /// The device answered with an MCUmgr error code
+    Rejected(i64),
+}
+
+impl fmt::Display for McumgrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            McumgrError::IoError(e) => write!(f, "serial error: {}", e),
+            McumgrError::Timeout => write!(f, "the device didn't respond"),
+            McumgrError::ProtocolError(e) => write!(f, "unexpected response: {}", e),
+            McumgrError::Rejected(rc) => write!(f, "the device refused the request (error {})", rc),
+        }
+    }
+}
+
+impl From<io::Error> for McumgrError {
+    fn from(e: io::Error) -> Self {
+        McumgrError::IoError(e)
+    }
+}
+
+/// A line-based link to a device, i.e. a serial port.
+pub trait SerialLink {
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+    /// Wait up to `timeout` for the next line (without its newline). None on timeout.
+    fn read_line(&mut self, timeout: Duration) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// A serial device file, i.e. /dev/ttyACM0. Lines are read on a separate thread so reads
+/// can time out; the thread ends when the next line arrives after the port is dropped.
+pub struct SerialPort {
+    file: fs::File,
+    lines: Receiver<io::Result<Vec<u8>>>,
+}
+
+impl SerialPort {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let mut reader = BufReader::new(file.try_clone()?);
+        let (tx, lines) = channel();
+        std::thread::spawn(move || loop {
+            let mut line = Vec::new();
+            let result = reader.read_until(b'\n', &mut line);
+            let done = !matches!(result, Ok(n) if n > 0);
+            if tx.send(result.map(|_| line)).is_err() || done {
+                break;
+            }
+        });
+        Ok(Self { file, lines })
+    }
+}
+
+impl SerialLink for SerialPort {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data)?;
+        self.file.flush()
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(Ok(mut line)) if !line.is_empty() => {
+                while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                    line.pop();
+                }
+                Ok(Some(line))
+            },
+            Ok(Ok(_)) | Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the device went away")),
+            Ok(Err(e)) => Err(e),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+        }
+    }
+}
+
+/// Just enough CBOR (RFC 8949) for SMP: the data model, and both definite and
+/// indefinite length arrays and maps, which devices use interchangeably.
+pub mod cbor {
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Value {
+        Uint(u64),
+        /// A negative integer
+        Nint(i64),
+        Bytes(Vec<u8>),
+        Text(String),
+        Array(Vec<Value>),
+        Map(Vec<(Value, Value)>),
+        Bool(bool),
+        Null,
+    }
+
+    impl Value {
+        /// Look up a text key in a map.
+        pub fn get(&self, key: &str) -> Option<&Value> {
+            match self {
+                Value::Map(entries) => entries.iter().find(|(k, _)| matches!(k, Value::Text(t) if t == key)).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+
+        pub fn as_int(&self) -> Option<i64> {
+            match self {
+                Value::Uint(u) => i64::try_from(*u).ok(),
+                Value::Nint(n) => Some(*n),
+                _ => None,
+            }
+        }
+
+        pub fn as_bool(&self) -> Option<bool> {
+            match self {
+                Value::Bool(b) => Some(*b),
+                _ => None,
+            }
+        }
+    }
+
+    fn push_head(out: &mut Vec<u8>, major: u8, n: u64) {
+        let major = major << 5;
+        match n {
+            0..=23 => out.push(major | n as u8),
+            24..=0xff => out.extend([major | 24, n as u8]),
+            0x100..=0xffff => {
+                out.push(major | 25);
+                out.extend((n as u16).to_be_bytes());
+            },
+            0x1_0000..=0xffff_ffff => {
+                out.push(major | 26);
+                out.extend((n as u32).to_be_bytes());
+            },
+            _ => {
+                out.push(major | 27);
+                out.extend(n.to_be_bytes());
+            },
+        }
+    }
+
+    pub fn encode(value: &Value, out: &mut Vec<u8>) {
+        match value {
+            Value::Uint(u) => push_head(out, 0, *u),
+            Value::Nint(n) => push_head(out, 1, (-1 - *n) as u64),
+            Value::Bytes(b) => {
+                push_head(out, 2, b.len() as u64);
+                out.extend(b);
+            },
+            Value::Text(t) => {
+                push_head(out, 3, t.len() as u64);
+                out.extend(t.as_bytes());
+            },
+            Value::Array(items) => {
+                push_head(out, 4, items.len() as u64);
+                items.iter().for_each(|item| encode(item, out));
+            },
+            Value::Map(entries) => {
+                push_head(out, 5, entries.len() as u64);
+                for (k, v) in entries {
+                    encode(k, out);
+                    encode(v, out);
+                }
+            },
+            Value::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+            Value::Null => out.push(0xf6),
+        }
+    }
+
+    const BREAK: u8 = 0xff;
+
+    /// Decode one value from the start of `data`, returning it and the bytes it used.
+    pub fn decode(data: &[u8]) -> Result<(Value, usize), String> {
+        decode_at(data, 0, 0)
+    }
+
+    fn decode_at(data: &[u8], start: usize, depth: usize) -> Result<(Value, usize), String> {
+        if depth > 16 {
+            return Err("CBOR nested too deeply".to_string());
+        }
+        let truncated = || "truncated CBOR".to_string();
+        let initial = *data.get(start).ok_or_else(truncated)?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let mut next = start + 1;
+        let mut read = |len: usize| -> Result<u64, String> {
+            let bytes = data.get(next..next + len).ok_or_else(truncated)?;
+            next += len;
+            Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+        };
+        let arg = match info {
+            0..=23 => Some(info as u64),
+            24 => Some(read(1)?),
+            25 => Some(read(2)?),
+            26 => Some(read(4)?),
+            27 => Some(read(8)?),
+            31 if matches!(major, 4 | 5) => None,
+            _ => return Err(format!("unsupported CBOR item 0x{:02x}", initial)),
+        };
+        let mut at = next;
+        let value = match (major, arg) {
+            (0, Some(n)) => Value::Uint(n),
+            (1, Some(n)) => Value::Nint(-1 - i64::try_from(n).map_err(|_| "CBOR integer out of range".to_string())?),
+            (2 | 3, Some(n)) => {
+                let end = usize::try_from(n).ok().and_then(|n| at.checked_add(n)).ok_or_else(truncated)?;
+                let bytes = data.get(at..end).ok_or_else(truncated)?.to_vec();
+                at = end;
+                if major == 2 {
+                    Value::Bytes(bytes)
+                } else {
+                    Value::Text(String::from_utf8(bytes).map_err(|e| e.to_string())?)
+                }
+            },
+            (4 | 5, len) => {
+                let mut items = vec![];
+                let items_len = if major == 5 { len.map(|n| n.saturating_mul(2)) } else { len };
+                loop {
+                    match items_len {
+                        Some(n) if items.len() as u64 == n => break,
+                        None if data.get(at) == Some(&BREAK) => {
+                            at += 1;
+                            break;
+                        },
+                        _ => (),
+                    }
+                    let (item, used) = decode_at(data, at, depth + 1)?;
+                    items.push(item);
+                    at += used;
+                }
+                if major == 4 {
+                    Value::Array(items)
+                } else {
+                    if items.len() % 2 != 0 {
+                        return Err("CBOR map is missing a value".to_string());
+                    }
+                    let mut entries = vec![];
+                    let mut items = items.into_iter();
+                    while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                        entries.push((k, v));
+                    }
+                    Value::Map(entries)
+                }
+            },
+            (7, Some(20)) => Value::Bool(false),
+            (7, Some(21)) => Value::Bool(true),
+            (7, Some(22 | 23)) => Value::Null,
+            _ => return Err(format!("unsupported CBOR item 0x{:02x}", initial)),
+        };
+        Ok((value, at - start))
+    }
+}
+
+/// The SMP groups and commands we use.
+const GROUP_OS: u16 = 0;
+const GROUP_IMAGE: u16 = 1;
+const OS_RESET: u8 = 5;
+const IMAGE_STATE: u8 = 0;
+const OP_READ: u8 = 0;
+const OP_WRITE: u8 = 2;
+
+/// The markers that start the first and the following frames of a serial SMP packet.
+const FRAME_START: [u8; 2] = [0x06, 0x09];
+const FRAME_CONTINUE: [u8; 2] = [0x04, 0x14];
+/// The most raw bytes in one frame; 93 bytes are 124 base64 characters, which with the
+/// marker and newline is the 127 byte frame limit.
+const FRAME_DATA_LEN: usize = 93;
+
+/// CRC-16/XMODEM, which protects serial SMP packets.
+pub fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = (crc << 1) ^ (0x1021 & (crc >> 15).wrapping_neg());
+        }
+    }
+    crc
+}
+
+/// Build an SMP packet: the 8 byte header, then the CBOR payload.
+pub fn smp_packet(op: u8, group: u16, id: u8, seq: u8, payload: &Value) -> Vec<u8> {
+    let mut body = vec![];
+    cbor::encode(payload, &mut body);
+    let mut packet = vec![op, 0];
+    packet.extend((body.len() as u16).to_be_bytes());
+    packet.extend(group.to_be_bytes());
+    packet.extend([seq, id]);
+    packet.extend(body);
+    packet
+}
+
+/// Wrap an SMP packet for a serial line: length, packet and CRC, base64 encoded and split
+/// into newline-terminated frames.
+pub fn encode_frames(packet: &[u8]) -> Vec<u8> {
+    use base64::Engine;
+    let mut data = ((packet.len() + 2) as u16).to_be_bytes().to_vec();
+    data.extend(packet);
+    data.extend(crc16_xmodem(packet).to_be_bytes());
+    let mut out = vec![];
+    for (i, chunk) in data.chunks(FRAME_DATA_LEN).enumerate() {
+        out.extend(if i == 0 { FRAME_START } else { FRAME_CONTINUE });
+        out.extend(base64::engine::general_purpose::STANDARD.encode(chunk).as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Reassembles an SMP packet from serial frames, ignoring any other lines (i.e. shell
+/// output) in between.
+#[derive(Default)]
+pub struct FrameDecoder {
+    data: Vec<u8>,
+    started: bool,
+}
+
+impl FrameDecoder {
+
+    /// Feed a line. Returns the packet once it's complete.
+    pub fn push_line(&mut self, line: &[u8]) -> Result<Option<Vec<u8>>, McumgrError> {
+        use base64::Engine;
+        let encoded = if let Some(rest) = line.strip_prefix(&FRAME_START[..]) {
+            self.data.clear();
+            self.started = true;
+            rest
+        } else if let Some(rest) = line.strip_prefix(&FRAME_CONTINUE[..]).filter(|_| self.started) {
+            rest
+        } else {
+            return Ok(None);
+        };
+        let decoded = base64::engine::general_purpose::STANDARD.decode(encoded)
+            .map_err(|e| McumgrError::ProtocolError(format!("bad frame: {}", e)))?;
+        self.data.extend(decoded);
+        let Some(len) = self.data.get(..2).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize) else {
+            return Ok(None);
+        };
+        if self.data.len() < len + 2 {
+            return Ok(None);
+        }
+        self.started = false;
+        let packet = &self.data[2..len + 2];
+        let Some((packet, crc)) = packet.split_last_chunk::<2>() else {
+            return Err(McumgrError::ProtocolError("empty packet".to_string()));
+        };
+        if crc16_xmodem(packet) != u16::from_be_bytes(*crc) {
+            return Err(McumgrError::ProtocolError("bad CRC".to_string()));
+        }
+        Ok(Some(packet.to_vec()))
+    }
+}
+
+/// The state of one image slot on a device, as reported by MCUmgr.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageSlot {
+    pub image: u32,
+    pub slot: u32,
+    pub version: String,
+    /// The SHA-256 of the image; empty if the device didn't say
+    pub hash: Vec<u8>,
+    pub bootable: bool,
+    /// The image will be tried on the next boot
+    pub pending: bool,
+    pub confirmed: bool,
+    /// The image that's running
+    pub active: bool,
+    /// The pending image will be kept without needing to be confirmed
+    pub permanent: bool,
+}
+
+impl ImageSlot {
+
+    fn from_cbor(value: &Value) -> Self {
+        let flag = |key| value.get(key).and_then(Value::as_bool).unwrap_or(false);
+        let int = |key| value.get(key).and_then(Value::as_int).and_then(|i| u32::try_from(i).ok()).unwrap_or(0);
+        Self {
+            image: int("image"),
+            slot: int("slot"),
+            version: match value.get("version") {
+                Some(Value::Text(v)) => v.clone(),
+                _ => String::new(),
+            },
+            hash: match value.get("hash") {
+                Some(Value::Bytes(h)) => h.clone(),
+                _ => vec![],
+            },
+            bootable: flag("bootable"),
+            pending: flag("pending"),
+            confirmed: flag("confirmed"),
+            active: flag("active"),
+            permanent: flag("permanent"),
+        }
+    }
+
+    /// The slot's state, in the words mcumgr uses.
+    pub fn flags(&self) -> String {
+        let flags = [(self.active, "active"), (self.confirmed, "confirmed"), (self.pending, "pending"), (self.permanent, "permanent")];
+        let flags: Vec<&str> = flags.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect();
+        flags.join(" ")
+    }
+}
+
+/// An SMP client on a serial link.
+pub struct SmpClient<'a> {
+    link: &'a mut dyn SerialLink,
+    seq: u8,
+}
+
+impl<'a> SmpClient<'a> {
+
+    pub fn new(link: &'a mut dyn SerialLink) -> Self {
+        Self { link, seq: 0 }
+    }
+
+    /// Send a request and wait for the matching response payload.
+    pub fn request(&mut self, op: u8, group: u16, id: u8, payload: &Value) -> Result<Value, McumgrError> {
+        self.seq = self.seq.wrapping_add(1);
+        self.link.write(&encode_frames(&smp_packet(op, group, id, self.seq, payload)))?;
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let mut decoder = FrameDecoder::default();
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let line = self.link.read_line(timeout)?.ok_or(McumgrError::Timeout)?;
+            let Some(packet) = decoder.push_line(&line)? else {
+                continue;
+            };
+            let Some((header, body)) = packet.split_first_chunk::<8>() else {
+                return Err(McumgrError::ProtocolError("short SMP header".to_string()));
+            };
+            // skip stale responses to earlier requests
+            if header[0] != op + 1 || header[6] != self.seq {
+                continue;
+            }
+            let (response, _) = cbor::decode(body).map_err(McumgrError::ProtocolError)?;
+            // SMP version 1 reports errors as "rc", version 2 as an "err" map
+            let rc = response.get("rc").or_else(|| response.get("err").and_then(|e| e.get("rc")));
+            match rc.and_then(Value::as_int) {
+                Some(0) | None => return Ok(response),
+                Some(rc) => return Err(McumgrError::Rejected(rc)),
+            }
+        }
+    }
+
+    fn image_slots(response: &Value) -> Result<Vec<ImageSlot>, McumgrError> {
+        match response.get("images") {
+            Some(Value::Array(images)) => Ok(images.iter().map(ImageSlot::from_cbor).collect()),
+            _ => Err(McumgrError::ProtocolError("no image list".to_string())),
+        }
+    }
+
+    /// Read the state of the image slots.
+    pub fn image_state(&mut self) -> Result<Vec<ImageSlot>, McumgrError> {
+        let response = self.request(OP_READ, GROUP_IMAGE, IMAGE_STATE, &Value::Map(vec![]))?;
+        Self::image_slots(&response)
+    }
+
+    /// Mark an image to be tried on the next boot (`confirm` false), or permanently; or,
+    /// with no hash, confirm the running image.
+    pub fn set_image_state(&mut self, hash: Option<&[u8]>, confirm: bool) -> Result<Vec<ImageSlot>, McumgrError> {
+        let mut payload = vec![(Value::Text("confirm".to_string()), Value::Bool(confirm))];
+        if let Some(hash) = hash {
+            payload.push((Value::Text("hash".to_string()), Value::Bytes(hash.to_vec())));
+        }
+        let response = self.request(OP_WRITE, GROUP_IMAGE, IMAGE_STATE, &Value::Map(payload))?;
+        Self::image_slots(&response)
+    }
+
+    pub fn reset(&mut self) -> Result<(), McumgrError> {
+        self.request(OP_WRITE, GROUP_OS, OS_RESET, &Value::Map(vec![])).map(|_| ())
+    }
+}
+
+/// Send a shell command and collect its output, until the shell goes quiet.
+pub fn shell_command(link: &mut dyn SerialLink, command: &str) -> Result<Vec<String>, McumgrError> {
+    link.write(format!("{}\r\n", command).as_bytes())?;
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    let mut output = vec![];
+    let mut answered = false;
+    loop {
+        let timeout = if answered { SHELL_QUIET_TIME } else { deadline.saturating_duration_since(Instant::now()) };
+        match link.read_line(timeout)? {
+            Some(line) => {
+                answered = true;
+                let line = String::from_utf8_lossy(&line).to_string();
+                // strip the prompt and the echo of the command
+                let line = line.rsplit_once("$ ").map_or(line.as_str(), |(_, rest)| rest).trim_end();
+                if line.trim() != command {
+                    output.push(line.to_string());
+                }
+            },
+            None if answered => return Ok(output),
+            None => return Err(McumgrError::Timeout),
+        }
+    }
+}
+
+/// Make sense of the output of Zephyr's `mcuboot` shell command, i.e.
+/// ```text
+/// swap type: none
+/// confirmed: 0
+///
+/// primary area (1):
+///   version: 1.2.0+0
+///   image size: 43120
+///   magic: good
+///   swap type: test
+///   copy done: set
+///   image ok: unset
+/// ```
+/// The shell doesn't show hashes, so those are left empty.
+pub fn parse_mcuboot_shell(output: &[String]) -> Result<Vec<ImageSlot>, McumgrError> {
+    let mut slots: Vec<ImageSlot> = vec![];
+    let mut confirmed = None;
+    let mut next_swap = String::new();
+    for line in output {
+        let indented = line.starts_with(' ');
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match (indented, key, slots.last_mut()) {
+            (false, "swap type", _) => next_swap = value.to_string(),
+            (false, "confirmed", _) => confirmed = Some(value == "1"),
+            (false, area, _) if area.ends_with(')') && area.contains(" area (") => {
+                let primary = area.starts_with("primary");
+                slots.push(ImageSlot { slot: if primary { 0 } else { 1 }, active: primary, ..Default::default() });
+            },
+            (true, "version", Some(slot)) => slot.version = value.to_string(),
+            (true, "magic", Some(slot)) => slot.bootable = value == "good",
+            (true, "image ok", Some(slot)) => slot.confirmed = value == "set",
+            _ => (),
+        }
+    }
+    if slots.is_empty() {
+        return Err(McumgrError::ProtocolError(output.join("\n")));
+    }
+    for slot in slots.iter_mut() {
+        if slot.active {
+            slot.confirmed = confirmed.unwrap_or(slot.confirmed);
+        } else {
+            slot.pending = slot.bootable && matches!(next_swap.as_str(), "test" | "perm" | "permanent");
+            slot.permanent = slot.pending && next_swap != "test";
+        }
+    }
+    Ok(slots)
+}
+
+/// How to talk to the device.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum DeviceProtocol {
+    #[default]
+    Smp,
+    Shell,
+}
+
+impl fmt::Display for DeviceProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceProtocol::Smp => write!(f, "SMP (mcumgr)"),
+            DeviceProtocol::Shell => write!(f, "Zephyr shell"),
+        }
+    }
+}
+
+/// Something to do to the device's images.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageAction {
+    Read,
+    /// Boot the image with this hash once, on the next reset
+    Test(Vec<u8>),
+    /// Keep the running image
+    Confirm,
+    Reset,
+}
+
+/// Carry out an action, returning the image state afterwards (if it's known).
+pub fn run_action(protocol: DeviceProtocol, link: &mut dyn SerialLink, action: &ImageAction) -> Result<Option<Vec<ImageSlot>>, McumgrError> {
+    match protocol {
+        DeviceProtocol::Smp => {
+            let mut client = SmpClient::new(link);
+            match action {
+                ImageAction::Read => client.image_state().map(Some),
+                ImageAction::Test(hash) => client.set_image_state(Some(hash), false).map(Some),
+                ImageAction::Confirm => client.set_image_state(None, true).map(Some),
+                ImageAction::Reset => client.reset().map(|_| None),
+            }
+        },
+        DeviceProtocol::Shell => {
+            match action {
+                ImageAction::Read => (),
+                ImageAction::Test(_) => { shell_command(link, "mcuboot request_upgrade")?; },
+                ImageAction::Confirm => { shell_command(link, "mcuboot confirm")?; },
+                ImageAction::Reset => {
+                    // the device resets without answering
+                    link.write(b"kernel reboot cold\r\n")?;
+                    return Ok(None);
+                },
+            }
+            parse_mcuboot_shell(&shell_command(link, "mcuboot")?).map(Some)
+        },
+    }
+}
+
+fn image_job(port: &Path, protocol: DeviceProtocol, action: &ImageAction, bus: &EventBus, job: &JobContext) -> Result<(), String> {
+    job.set_status(&format!("talking to {}", port.display()));
+    let mut link = SerialPort::open(port).map_err(|e| format!("couldn't open {}: {}", port.display(), e))?;
+    if let Some(slots) = run_action(protocol, &mut link, action).map_err(|e| e.to_string())? {
+        bus.publish(Event::ImageState(slots));
+    }
+    job.request_repaint();
+    Ok(())
+}
+
+/// The state of the device images window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ImagePanel {
+    /// The serial device, i.e. /dev/ttyACM0 or COM3
+    pub port: String,
+    pub protocol: DeviceProtocol,
+    #[serde(skip)]
+    slots: Vec<ImageSlot>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    events: Option<Receiver<Event>>,
+}
+
+impl ImagePanel {
+
+    fn start(&mut self, ctx: &egui::Context, action: ImageAction) {
+        let port = Path::new(&self.port).to_path_buf();
+        let protocol = self.protocol;
+        let bus = events::shared(ctx);
+        self.job = Some(jobs::shared(ctx).spawn("device images", Some(ctx), move |job| {
+            image_job(&port, protocol, &action, &bus, job)
+        }));
+    }
+
+    /// Stop listening for image state, for when the window is closed.
+    pub fn close(&mut self) {
+        self.events = None;
+    }
+
+    /// Show the device's image slots and the actions that can be taken on them.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let rx = self.events.get_or_insert_with(|| events::shared(ctx).subscribe());
+        while let Ok(event) = rx.try_recv() {
+            if let Event::ImageState(slots) = event {
+                self.slots = slots;
+            }
+        }
+        let state = self.job.and_then(|id| jobs::shared(ctx).state(id));
+        let running = state == Some(JobState::Running);
+        let mut action = None;
+        ui.horizontal(|ui| {
+            ui.label("port:");
+            ui.add(egui::TextEdit::singleline(&mut self.port).hint_text("/dev/ttyACM0").desired_width(120.0));
+            egui::ComboBox::from_id_source("image_protocol")
+            .selected_text(self.protocol.to_string())
+            .show_ui(ui, |ui| {
+                for p in enum_iterator::all::<DeviceProtocol>() {
+                    ui.selectable_value(&mut self.protocol, p, p.to_string());
+                }
+            });
+        });
+        ui.add_enabled_ui(!running && !self.port.is_empty(), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("read").clicked() {
+                    action = Some(ImageAction::Read);
+                }
+                if ui.button("reset device").clicked() {
+                    action = Some(ImageAction::Reset);
+                }
+            });
+            ui.separator();
+            if self.slots.is_empty() {
+                ui.label("no image state read yet");
+            }
+            egui::Grid::new("image_slots").num_columns(5).striped(true).show(ui, |ui| {
+                if !self.slots.is_empty() {
+                    for heading in ["image", "version", "hash", "state", ""] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                }
+                for slot in self.slots.iter() {
+                    ui.label(format!("{}/{}", slot.image, slot.slot));
+                    ui.label(&slot.version);
+                    let hash: String = slot.hash.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+                    ui.monospace(hash);
+                    ui.label(slot.flags());
+                    if slot.active && !slot.confirmed {
+                        if ui.button("confirm").on_hover_text("keep this image, instead of reverting to the old one on the next reset").clicked() {
+                            action = Some(ImageAction::Confirm);
+                        }
+                    } else if !slot.active && slot.bootable && !slot.pending {
+                        if ui.button("test").on_hover_text("boot this image once on the next reset").clicked() {
+                            action = Some(ImageAction::Test(slot.hash.clone()));
+                        }
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(action) = action {
+            self.start(ctx, action);
+        }
+        match state {
+            Some(JobState::Running) => { ui.spinner(); },
+            Some(JobState::Failed(e)) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+            _ => (),
+        }
+    }
+}
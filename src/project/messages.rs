@@ -0,0 +1,589 @@
+//! Title: Iron Coder Project Module - Messages
+//! Description: Schemas of the messages a project's device and the host send each
+//!   other. They're kept in the project file and generated as Rust types for the
+//!   firmware, which sends them encoded with postcard and framed with COBS. The same
+//!   schema drives a host-side decoder, so the serial monitor can show the messages
+//!   instead of raw bytes.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use enum_iterator::Sequence;
+use quote::{format_ident, quote};
+use serde::{Serialize, Deserialize};
+
+/// Which way a message goes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    ToHost,
+    ToDevice,
+    Both,
+}
+
+impl Direction {
+    fn includes(&self, other: Direction) -> bool {
+        *self == Direction::Both || *self == other
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::ToHost => write!(f, "device to host"),
+            Direction::ToDevice => write!(f, "host to device"),
+            Direction::Both => write!(f, "both ways"),
+        }
+    }
+}
+
+/// The type of a field, parsed from its Rust spelling. Strings and lists are heapless,
+/// so the firmware doesn't need an allocator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    /// An unsigned integer of 8 to 64 bits
+    Unsigned(u32),
+    /// A signed integer of 8 to 64 bits
+    Signed(u32),
+    /// A float of 32 or 64 bits
+    Float(u32),
+    /// `String<N>`, up to N bytes of UTF-8
+    String(usize),
+    /// `Vec<T, N>`, up to N items
+    Vec(Box<FieldType>, usize),
+    /// `[T; N]`, exactly N items
+    Array(Box<FieldType>, usize),
+    Option(Box<FieldType>),
+    /// Another message of the schema
+    Message(String),
+}
+
+/// The value of a `N` in a type, i.e. the 16 of `Vec<u8, 16>`.
+fn length(expr: &syn::Expr) -> Result<usize, String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(n), .. }) => match n.base10_parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("{} isn't a usable length", n)),
+            Ok(n) => Ok(n),
+        },
+        _ => Err("lengths need to be numbers".to_string()),
+    }
+}
+
+impl FieldType {
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let ty: syn::Type = syn::parse_str(s).map_err(|_| format!("{:?} isn't a type", s))?;
+        Self::from_syn(&ty)
+    }
+
+    fn from_syn(ty: &syn::Type) -> Result<Self, String> {
+        let unsupported = || format!("{} isn't a supported type", quote!(#ty));
+        let (name, args): (String, Vec<&syn::GenericArgument>) = match ty {
+            syn::Type::Array(a) => return Ok(FieldType::Array(Box::new(Self::from_syn(&a.elem)?), length(&a.len)?)),
+            syn::Type::Path(p) if p.qself.is_none() && p.path.segments.len() == 1 => {
+                let segment = &p.path.segments[0];
+                let args = match &segment.arguments {
+                    syn::PathArguments::None => vec![],
+                    syn::PathArguments::AngleBracketed(a) => a.args.iter().collect(),
+                    _ => return Err(unsupported()),
+                };
+                (segment.ident.to_string(), args)
+            },
+            _ => return Err(unsupported()),
+        };
+        let inner = |arg: Option<&&syn::GenericArgument>| match arg {
+            Some(syn::GenericArgument::Type(ty)) => Self::from_syn(ty),
+            _ => Err(unsupported()),
+        };
+        let len = |arg: Option<&&syn::GenericArgument>| match arg {
+            Some(syn::GenericArgument::Const(e)) => length(e),
+            // a bare number can also parse as a type path
+            Some(syn::GenericArgument::Type(ty)) => Err(format!("{} isn't a length", quote!(#ty))),
+            _ => Err(unsupported()),
+        };
+        let field_type = match (name.as_str(), args.len()) {
+            ("bool", 0) => FieldType::Bool,
+            ("u8", 0) | ("u16", 0) | ("u32", 0) | ("u64", 0) => FieldType::Unsigned(name[1..].parse().unwrap()),
+            ("i8", 0) | ("i16", 0) | ("i32", 0) | ("i64", 0) => FieldType::Signed(name[1..].parse().unwrap()),
+            ("f32", 0) | ("f64", 0) => FieldType::Float(name[1..].parse().unwrap()),
+            ("String", 1) => FieldType::String(len(args.first())?),
+            ("Vec", 2) => FieldType::Vec(Box::new(inner(args.first())?), len(args.get(1))?),
+            ("Option", 1) => FieldType::Option(Box::new(inner(args.first())?)),
+            (_, 0) if is_type_name(&name) => FieldType::Message(name),
+            _ => return Err(unsupported()),
+        };
+        Ok(field_type)
+    }
+
+    /// The type in the generated module.
+    fn rust_type(&self) -> String {
+        match self {
+            FieldType::Bool => "bool".to_string(),
+            FieldType::Unsigned(bits) => format!("u{}", bits),
+            FieldType::Signed(bits) => format!("i{}", bits),
+            FieldType::Float(bits) => format!("f{}", bits),
+            FieldType::String(n) => format!("heapless::String<{}>", n),
+            FieldType::Vec(t, n) => format!("heapless::Vec<{}, {}>", t.rust_type(), n),
+            FieldType::Array(t, n) => format!("[{}; {}]", t.rust_type(), n),
+            FieldType::Option(t) => format!("Option<{}>", t.rust_type()),
+            FieldType::Message(name) => name.clone(),
+        }
+    }
+
+    /// The messages this type refers to.
+    fn messages(&self) -> Vec<&str> {
+        match self {
+            FieldType::Vec(t, _) | FieldType::Array(t, _) | FieldType::Option(t) => t.messages(),
+            FieldType::Message(name) => vec![name.as_str()],
+            _ => vec![],
+        }
+    }
+}
+
+fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_field_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && syn::parse_str::<syn::Ident>(name).is_ok()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Field {
+    pub name: String,
+    /// The field's type, written as in Rust, i.e. "f32", "String<16>" or "Vec<u16, 8>"
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// A message, i.e.
+/// ```toml
+/// [[messages]]
+/// name = "Telemetry"
+/// direction = "to-host"
+/// fields = [{ name = "temperature", type = "f32" }, { name = "label", type = "String<16>" }]
+/// ```
+/// Messages without a direction are only sent inside other messages.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Message {
+    pub name: String,
+    pub direction: Option<Direction>,
+    pub fields: Vec<Field>,
+}
+
+/// The messages of a project.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct MessageSchema {
+    pub messages: Vec<Message>,
+}
+
+impl MessageSchema {
+
+    pub fn message(&self, name: &str) -> Option<&Message> {
+        self.messages.iter().find(|m| m.name == name)
+    }
+
+    /// The messages sent in a direction, in the order of the variants of the generated
+    /// enum for that direction.
+    pub fn sent(&self, direction: Direction) -> impl Iterator<Item = &Message> {
+        self.messages.iter().filter(move |m| m.direction.is_some_and(|d| d.includes(direction)))
+    }
+
+    /// Check that the schema can be generated, and that no message contains itself.
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, message) in self.messages.iter().enumerate() {
+            if !is_type_name(&message.name) {
+                return Err(format!("message {:?} needs to be in UpperCamelCase", message.name));
+            }
+            if self.messages[..i].iter().any(|m| m.name == message.name) {
+                return Err(format!("there are two messages named {}", message.name));
+            }
+            for (j, field) in message.fields.iter().enumerate() {
+                if !is_field_name(&field.name) {
+                    return Err(format!("field {:?} of {} needs to be in snake_case", field.name, message.name));
+                }
+                if message.fields[..j].iter().any(|f| f.name == field.name) {
+                    return Err(format!("{} has two fields named {}", message.name, field.name));
+                }
+                let ty = FieldType::parse(&field.ty).map_err(|e| format!("{}.{}: {}", message.name, field.name, e))?;
+                if let Some(missing) = ty.messages().into_iter().find(|m| self.message(m).is_none()) {
+                    return Err(format!("{}.{} refers to {}, which isn't a message", message.name, field.name, missing));
+                }
+            }
+        }
+        // every field type parsed, so look for messages that end up containing themselves
+        for message in self.messages.iter() {
+            let mut stack: Vec<&str> = vec![&message.name];
+            let mut seen: Vec<&str> = vec![];
+            while let Some(name) = stack.pop() {
+                let contained = self.message(name).into_iter()
+                    .flat_map(|m| m.fields.iter())
+                    .filter_map(|f| FieldType::parse(&f.ty).ok())
+                    .flat_map(|t| t.messages().into_iter().map(String::from).collect::<Vec<_>>());
+                for inner in contained {
+                    if inner == message.name {
+                        return Err(format!("{} contains itself", message.name));
+                    }
+                    if !seen.contains(&inner.as_str()) {
+                        let inner = &self.message(&inner).unwrap().name;
+                        seen.push(inner);
+                        stack.push(inner);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate the Rust source of the messages module.
+    pub fn generate_module(&self) -> Result<String, String> {
+        self.validate()?;
+        let structs = self.messages.iter().map(|m| {
+            let name = format_ident!("{}", m.name);
+            let fields = m.fields.iter().map(|f| {
+                let field = format_ident!("{}", f.name);
+                // the types are checked, so this parses
+                let ty: syn::Type = syn::parse_str(&FieldType::parse(&f.ty).unwrap().rust_type()).unwrap();
+                quote!(pub #field: #ty)
+            });
+            quote! {
+                #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+                pub struct #name {
+                    #(#fields),*
+                }
+            }
+        });
+        let direction_enum = |name: &str, doc: &str, direction: Direction| {
+            let messages: Vec<_> = self.sent(direction).map(|m| format_ident!("{}", m.name)).collect();
+            if messages.is_empty() {
+                return quote!();
+            }
+            let name = format_ident!("{}", name);
+            quote! {
+                #[doc = #doc]
+                #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+                pub enum #name {
+                    #(#messages(#messages)),*
+                }
+            }
+        };
+        let device_messages = direction_enum("DeviceMessage", " The messages the device sends to the host", Direction::ToHost);
+        let host_messages = direction_enum("HostMessage", " The messages the host sends to the device", Direction::ToDevice);
+        let tokens = quote! {
+            //! Messages between the device and the host, generated by Iron Coder from the
+            //! project's message schema. Don't edit by hand. Send them with
+            //! `postcard::to_slice_cobs` so the serial monitor can decode them. Needs the
+            //! `serde` (with `derive`), `postcard` and `heapless` (with `serde`) crates.
+            #![allow(dead_code)]
+            use serde::{Deserialize, Serialize};
+            #(#structs)*
+            #device_messages
+            #host_messages
+        };
+        let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+        Ok(prettyplease::unparse(&file))
+    }
+
+    /// Write the messages module into the project's source directory.
+    pub fn write_module(&self, project_dir: &Path) -> io::Result<()> {
+        let code = self.generate_module().map_err(io::Error::other)?;
+        let src = project_dir.join("src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("messages.rs"), code)
+    }
+
+    /// Decode a postcard-encoded message sent in `direction`, i.e. a `DeviceMessage`
+    /// for messages to the host. The frame's COBS encoding must already be removed.
+    pub fn decode(&self, direction: Direction, bytes: &[u8]) -> Result<Value, String> {
+        self.validate()?;
+        let mut reader = Reader { bytes, pos: 0 };
+        let variant = reader.varint()?;
+        let message = self.sent(direction).nth(variant as usize)
+            .ok_or_else(|| format!("there's no message number {} {}", variant, direction))?;
+        let value = reader.message(self, message)?;
+        match bytes.len() - reader.pos {
+            0 => Ok(value),
+            extra => Err(format!("{} bytes left over after a {}", extra, message.name)),
+        }
+    }
+}
+
+/// A decoded field or message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    Option(Option<Box<Value>>),
+    Message(String, Vec<(String, Value)>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Unsigned(n) => write!(f, "{}", n),
+            Value::Signed(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Value::Option(None) => write!(f, "None"),
+            Value::Option(Some(v)) => write!(f, "Some({})", v),
+            Value::Message(name, fields) => {
+                write!(f, "{} {{", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    write!(f, "{} {}: {}", if i > 0 { "," } else { "" }, field, value)?;
+                }
+                write!(f, " }}")
+            },
+        }
+    }
+}
+
+/// Reads postcard's wire format: varints for integers wider than a byte (zigzag for
+/// signed ones), little-endian floats, and a varint length before strings and lists.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self.bytes.get(self.pos..self.pos + n).ok_or("the message ended early")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for i in 0..10 {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("a varint ran on too long".to_string())
+    }
+
+    fn value(&mut self, schema: &MessageSchema, ty: &FieldType) -> Result<Value, String> {
+        let value = match ty {
+            FieldType::Bool => match self.take(1)?[0] {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                b => return Err(format!("{} isn't a bool", b)),
+            },
+            FieldType::Unsigned(8) => Value::Unsigned(self.take(1)?[0] as u64),
+            FieldType::Signed(8) => Value::Signed(self.take(1)?[0] as i8 as i64),
+            FieldType::Unsigned(bits) => {
+                let n = self.varint()?;
+                if *bits < 64 && n >> bits != 0 {
+                    return Err(format!("{} doesn't fit in a u{}", n, bits));
+                }
+                Value::Unsigned(n)
+            },
+            FieldType::Signed(_) => {
+                let n = self.varint()?;
+                Value::Signed((n >> 1) as i64 ^ -((n & 1) as i64))
+            },
+            FieldType::Float(32) => Value::Float(f32::from_le_bytes(self.take(4)?.try_into().unwrap()) as f64),
+            FieldType::Float(_) => Value::Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            FieldType::String(max) => {
+                let len = self.varint()? as usize;
+                if len > *max {
+                    return Err(format!("a string of {} bytes is longer than {}", len, max));
+                }
+                let s = std::str::from_utf8(self.take(len)?).map_err(|_| "a string isn't UTF-8")?;
+                Value::String(s.to_string())
+            },
+            FieldType::Vec(t, max) => {
+                let len = self.varint()? as usize;
+                if len > *max {
+                    return Err(format!("a list of {} items is longer than {}", len, max));
+                }
+                Value::List((0..len).map(|_| self.value(schema, t)).collect::<Result<_, _>>()?)
+            },
+            FieldType::Array(t, len) => Value::List((0..*len).map(|_| self.value(schema, t)).collect::<Result<_, _>>()?),
+            FieldType::Option(t) => match self.take(1)?[0] {
+                0 => Value::Option(None),
+                1 => Value::Option(Some(Box::new(self.value(schema, t)?))),
+                b => return Err(format!("{} isn't an option tag", b)),
+            },
+            FieldType::Message(name) => {
+                let message = schema.message(name).ok_or_else(|| format!("{} isn't a message", name))?;
+                self.message(schema, message)?
+            },
+        };
+        Ok(value)
+    }
+
+    fn message(&mut self, schema: &MessageSchema, message: &Message) -> Result<Value, String> {
+        let mut fields = vec![];
+        for field in message.fields.iter() {
+            let ty = FieldType::parse(&field.ty)?;
+            let value = self.value(schema, &ty).map_err(|e| format!("{}.{}: {}", message.name, field.name, e))?;
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Value::Message(message.name.clone(), fields))
+    }
+}
+
+/// Remove the COBS encoding of a frame, without its zero delimiter.
+pub fn cobs_decode(frame: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(frame.len());
+    let mut i = 0;
+    while i < frame.len() {
+        let code = frame[i] as usize;
+        if code == 0 {
+            return Err("a COBS frame can't contain a zero".to_string());
+        }
+        let block = frame.get(i + 1..i + code).ok_or("the COBS frame ended early")?;
+        if block.contains(&0) {
+            return Err("a COBS frame can't contain a zero".to_string());
+        }
+        out.extend_from_slice(block);
+        i += code;
+        // a block shorter than 254 bytes stands for the data up to a zero
+        if code < 0xff && i < frame.len() {
+            out.push(0);
+        }
+    }
+    Ok(out)
+}
+
+/// Parse hex bytes, ignoring whitespace, i.e. "03 01 02 00".
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits.chunks(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()).collect()
+}
+
+/// The state of the message schema editor.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct MessagesPanel {
+    /// A COBS frame to try decoding, in hex
+    sample: String,
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl MessagesPanel {
+
+    fn message_ui(ui: &mut egui::Ui, index: usize, message: &mut Message) -> bool {
+        let mut remove = false;
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut message.name).desired_width(120.0));
+            egui::ComboBox::from_id_source(("message_direction", index))
+            .selected_text(message.direction.map_or("only nested".to_string(), |d| d.to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut message.direction, None, "only nested");
+                for direction in enum_iterator::all::<Direction>() {
+                    ui.selectable_value(&mut message.direction, Some(direction), direction.to_string());
+                }
+            });
+            remove = ui.small_button("delete").clicked();
+        });
+        let mut remove_field = None;
+        egui::Grid::new(("message_fields", index)).num_columns(3).show(ui, |ui| {
+            for (i, field) in message.fields.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut field.name).desired_width(120.0).hint_text("name"));
+                let valid = FieldType::parse(&field.ty).is_ok();
+                let mut edit = egui::TextEdit::singleline(&mut field.ty).desired_width(140.0).hint_text("type");
+                if !valid {
+                    edit = edit.text_color(ui.style().visuals.warn_fg_color);
+                }
+                ui.add(edit);
+                if ui.small_button("x").clicked() {
+                    remove_field = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove_field {
+            message.fields.remove(i);
+        }
+        if ui.button("add field").clicked() {
+            let name = (1..).map(|i| format!("field{}", i)).find(|n| !message.fields.iter().any(|f| &f.name == n)).unwrap();
+            message.fields.push(Field { name, ty: "u32".to_string() });
+        }
+        remove
+    }
+
+    /// Show the schema editor, a decoder for pasted frames, and module generation.
+    pub fn display(&mut self, ui: &mut egui::Ui, schema: &mut MessageSchema, project_dir: Option<&Path>) {
+        ui.label("Field types are written as in Rust: bool, u8 to u64, i8 to i64, f32, f64, String<N>, Vec<T, N>, [T; N], Option<T>, or another message.");
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+            let mut remove = None;
+            for (i, message) in schema.messages.iter_mut().enumerate() {
+                egui::CollapsingHeader::new(message.name.clone()).id_source(("message", i)).default_open(true).show(ui, |ui| {
+                    if Self::message_ui(ui, i, message) {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                schema.messages.remove(i);
+            }
+        });
+        if ui.button("add message").clicked() {
+            let name = (1..).map(|i| format!("Message{}", i)).find(|n| schema.message(n).is_none()).unwrap();
+            schema.messages.push(Message { name, direction: Some(Direction::ToHost), fields: vec![] });
+        }
+        ui.separator();
+        let valid = schema.validate();
+        if let Err(e) = &valid {
+            ui.colored_label(ui.style().visuals.warn_fg_color, e);
+        }
+        ui.horizontal(|ui| {
+            ui.label("decode a frame from the device:");
+            ui.add(egui::TextEdit::singleline(&mut self.sample).hint_text("hex, without the trailing 00").desired_width(200.0));
+        });
+        if valid.is_ok() && !self.sample.trim().is_empty() {
+            let decoded = parse_hex_bytes(&self.sample).ok_or("that isn't hex".to_string())
+                .and_then(|frame| cobs_decode(&frame))
+                .and_then(|bytes| schema.decode(Direction::ToHost, &bytes));
+            match decoded {
+                Ok(value) => ui.monospace(value.to_string()),
+                Err(e) => ui.colored_label(ui.style().visuals.warn_fg_color, e),
+            };
+        }
+        ui.horizontal(|ui| {
+            let Some(dir) = project_dir else {
+                ui.label("save the project to generate src/messages.rs");
+                return;
+            };
+            if ui.add_enabled(valid.is_ok(), egui::Button::new("generate src/messages.rs")).clicked() {
+                self.message = Some(match schema.write_module(dir) {
+                    Ok(()) => "wrote src/messages.rs".to_string(),
+                    Err(e) => e.to_string(),
+                });
+            }
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+        });
+    }
+}
@@ -1,10 +1,9 @@
 //! Title: Iron Coder Project Module - Module
 //! Description: This module contains the Project struct and its associated functionality.
 
-use log::{info, warn, debug};
+use log::{info, warn};
 
 // use std::error::Error;
-use std::io::BufRead;
 use std::io;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -14,13 +13,99 @@ use rfd::FileDialog;
 use serde::{Serialize, Deserialize};
 
 use crate::board::Board;
+use crate::board::filter::BoardFilter;
+use crate::board::compare::BoardComparison;
+use crate::board::display::BoardDisplayMode;
+use crate::board::partitions::PartitionTable;
+use crate::paths;
 use crate::app::code_editor::CodeEditor;
+use crate::app::jobs::{self, JobId};
+use crate::app::events::{self, Event};
 
 pub mod display;
 use display::ProjectViewType;
 
 pub mod egui_helpers;
 
+pub mod backend;
+use backend::BuildBackend;
+
+pub mod config;
+use config::ProjectConfig;
+
+pub mod ble;
+pub mod dfu;
+pub mod gatt;
+
+pub mod mcuboot;
+use mcuboot::{SlotImage, SlotSettings};
+pub mod mcumgr;
+pub mod lorawan;
+use lorawan::LoraWanConfig;
+pub mod calibration;
+use calibration::CalibrationConfig;
+pub mod state_machine;
+use state_machine::StateMachine;
+pub mod messages;
+use messages::MessageSchema;
+pub mod forwarding;
+use forwarding::ForwardingConfig;
+pub mod fixture;
+pub mod instruments;
+use instruments::InstrumentSequence;
+pub mod simulation;
+pub mod explain;
+pub mod embed;
+use embed::Asset;
+pub mod fonts;
+use fonts::FontSubset;
+pub mod localization;
+use localization::LocalizationConfig;
+pub mod power;
+use power::PowerPlan;
+pub mod rtt;
+pub mod cpu_usage;
+pub mod heap_usage;
+use heap_usage::HeapUsageConfig;
+pub mod compat;
+use cpu_usage::CpuUsageConfig;
+pub mod sources;
+use sources::DependencySources;
+pub mod reproducible;
+use reproducible::{RecordAction, ReproducibleSettings};
+pub mod health;
+use health::Finding;
+pub mod pins;
+pub mod file_tree;
+use file_tree::FileTree;
+pub mod trash;
+use trash::Retention;
+pub mod console;
+use console::{Console, Stream};
+pub mod example;
+pub mod starter;
+pub mod environment;
+use environment::ProjectEnvironment;
+pub mod vcs;
+pub mod local_sources;
+pub mod workspace;
+use workspace::Workspace;
+pub mod lints;
+use lints::LintSettings;
+pub mod std_support;
+use std_support::{StdSettings, StdSupport};
+pub mod logging;
+
+pub mod panic_dump;
+pub mod storage;
+pub mod config_partition;
+use config_partition::ConfigPartition;
+pub mod partition_table;
+use logging::LoggingSettings;
+use vcs::RepoSetup;
+use pins::PinExports;
+use explain::Explained;
+
 mod system;
 mod test;
 
@@ -29,6 +114,8 @@ use system::System;
 use git2::Repository;
 
 const PROJECT_FILE_NAME: &'static str = ".ironcoder.toml";
+/// The name of the job that loads the firmware onto the board.
+pub const LOAD_JOB_NAME: &str = "load onto board";
 
 pub type Result = core::result::Result<(), ProjectIOError>;
 
@@ -52,17 +139,140 @@ pub struct Project {
     name: String,
     location: Option<PathBuf>,
     pub system: System,
+    pub build_backend: BuildBackend,
+    pub config: ProjectConfig,
+    pub slot_settings: SlotSettings,
+    pub lorawan: LoraWanConfig,
+    pub calibration: CalibrationConfig,
+    pub state_machines: Vec<StateMachine>,
+    pub messages: MessageSchema,
+    pub forwarding: ForwardingConfig,
+    pub instrument_sequences: Vec<InstrumentSequence>,
+    /// Files converted into data in the firmware
+    pub embedded_assets: Vec<Asset>,
+    /// Fonts cut down to the characters the firmware shows
+    pub font_subsets: Vec<FontSubset>,
+    pub localization: LocalizationConfig,
+    /// How the firmware goes between running and the main board's sleep modes
+    pub power_plan: PowerPlan,
+    /// The firmware tasks to time, and their deadlines
+    pub cpu_usage: CpuUsageConfig,
+    /// The size of the firmware's heap, which is watched over RTT
+    pub heap_usage: HeapUsageConfig,
+    /// Where Cargo gets the dependencies from
+    pub dependency_sources: DependencySources,
+    pub reproducible: ReproducibleSettings,
+    /// The exports of the pin assignments that are kept up to date
+    pub pin_exports: PinExports,
+    /// How long files deleted from the project tree are kept in its trash
+    pub trash: Retention,
+    /// The environment variables set for everything run for the project
+    pub environment: ProjectEnvironment,
+    /// The lints the code is checked with, and the clippy settings
+    pub lints: LintSettings,
+    /// Whether the firmware has no std, no std with a heap, or std
+    pub std_support: StdSettings,
+    /// What the firmware does when it panics, and where its log goes
+    pub logging: LoggingSettings,
+    /// The settings written to a partition of the main board's flash
+    pub config_partition: ConfigPartition,
+    /// The project's own partition table, in place of the main board's
+    pub partition_table: Option<PartitionTable>,
+    /// Whether a new project gets a repository, and a first commit
+    #[serde(skip)]
+    pub repo_setup: RepoSetup,
+    /// The settings shared with the other projects in the repository, if it has them
+    #[serde(skip)]
+    workspace: Option<Workspace>,
+    /// What clippy found the last time the lints were checked
+    #[serde(skip)]
+    lint_check: Option<Vec<backend::Diagnostic>>,
+    /// When the lints were last changed, if they haven't been applied since
+    #[serde(skip)]
+    lints_changed: Option<std::time::Instant>,
     #[serde(skip)]
     pub code_editor: CodeEditor,
+    /// The output of the builds, flashes and commands run for the project
+    #[serde(skip)]
+    console: Console,
+    /// The errors in the terminal output that have explanations
     #[serde(skip)]
-    terminal_buffer: String,
+    explained: Vec<Explained>,
+    /// What the last health check found, if one has been run
     #[serde(skip)]
-    receiver: Option<std::sync::mpsc::Receiver<String>>,
+    health: Option<Vec<Finding>>,
+    #[serde(skip)]
+    events: Option<std::sync::mpsc::Receiver<Event>>,
     current_view: ProjectViewType,
     #[serde(skip)]
     pub known_boards: Vec<Board>,
+    /// What the boards in the board selector are narrowed down to
+    #[serde(skip)]
+    board_filter: BoardFilter,
+    /// The boards picked in the board selector to compare
+    #[serde(skip)]
+    board_comparison: BoardComparison,
+    /// How the project's boards are shown in the sidebar
+    #[serde(skip)]
+    board_display: BoardDisplayMode,
+    /// The board opened up in the sidebar, by its key
+    #[serde(skip)]
+    selected_board: Option<String>,
     #[serde(skip)]
     repo: Option<Repository>,
+    #[serde(skip)]
+    file_tree: FileTree,
+}
+
+/// Warn in the terminal about crates in the project that can't work together.
+fn warn_conflicts(project_dir: &Path, tx: &std::sync::mpsc::Sender<String>) {
+    for conflict in compat::check_project(project_dir) {
+        let _ = tx.send(format!("warning: {}\n", conflict));
+    }
+}
+
+/// Package the latest build as a slot image, reporting the result in the terminal.
+fn package_slot_image(slot_image: &SlotImage, project_dir: &Path, tx: &std::sync::mpsc::Sender<String>) -> core::result::Result<PathBuf, String> {
+    match slot_image.package(project_dir) {
+        Ok(image_path) => {
+            let signed = if slot_image.signing_key.is_some() { "signed " } else { "" };
+            let _ = tx.send(format!("packaged {}version {} for slot {}: {}\n", signed, slot_image.version, slot_image.slot, image_path.display()));
+            Ok(image_path)
+        },
+        Err(e) => {
+            let _ = tx.send(format!("couldn't package a slot image: {}\n", e));
+            Err(e.to_string())
+        },
+    }
+}
+
+/// Regenerate the modules made from the project's assets, fonts, string tables, task
+/// list, heap settings, allocator and serial logger if they changed, reporting it in the
+/// terminal.
+fn regenerate_modules(
+    (assets, fonts, localization, cpu_usage, heap_usage, std_support, logging): &(Vec<Asset>, Vec<FontSubset>, LocalizationConfig, CpuUsageConfig, HeapUsageConfig, StdSettings, LoggingSettings),
+    project_dir: &Path,
+    tx: &std::sync::mpsc::Sender<String>,
+) -> core::result::Result<(), String> {
+    let report = |result: core::result::Result<bool, String>, module: &str, failure: &str| match result {
+        Ok(written) => {
+            if written {
+                let _ = tx.send(format!("regenerated src/{}\n", module));
+            }
+            Ok(())
+        },
+        Err(e) => {
+            let _ = tx.send(format!("couldn't {}: {}\n", failure, e));
+            Err(e)
+        },
+    };
+    report(embed::regenerate(assets, project_dir).map_err(|e| e.to_string()), embed::MODULE_FILE_NAME, "embed the assets")?;
+    report(fonts::regenerate(fonts, project_dir).map_err(|e| e.to_string()), fonts::MODULE_FILE_NAME, "render the fonts")?;
+    report(localization::regenerate(localization, project_dir).map_err(|e| e.to_string()), localization::MODULE_FILE_NAME, "generate the string tables")?;
+    report(cpu_usage::regenerate(cpu_usage, project_dir).map_err(|e| e.to_string()), cpu_usage::MODULE_FILE_NAME, "generate the CPU usage counters")?;
+    report(heap_usage::regenerate(heap_usage, project_dir).map_err(|e| e.to_string()), heap_usage::MODULE_FILE_NAME, "generate the heap usage counters")?;
+    report(std_support.regenerate(heap_usage.enabled, project_dir), std_support::MODULE_FILE_NAME, "generate the allocator")?;
+    report(logging.regenerate(project_dir), logging::MODULE_FILE_NAME, "generate the serial logger")
 }
 
 // backend functionality for Project struct
@@ -70,8 +280,7 @@ impl Project {
     // Helper function for printing both to logs and to built-in terminal
     fn info_logger(&mut self, msg: &str) {
         info!("{}", msg);
-        let msg = msg.to_owned() + "\n";
-        self.terminal_buffer += &msg;
+        self.console.log(msg);
     }
 
     pub fn borrow_name(&mut self) -> &mut String {
@@ -95,6 +304,22 @@ impl Project {
         }
     }
 
+    /// The project directory, if the project has been saved
+    pub fn location(&self) -> Option<&Path> {
+        self.location.as_deref()
+    }
+
+    /// The compiler diagnostics in the terminal output
+    pub fn terminal_diagnostics(&self) -> Vec<backend::Diagnostic> {
+        let lines: Vec<String> = self.console.lines().map(str::to_string).collect();
+        backend::parse_diagnostics(&lines)
+    }
+
+    /// Whether any board in the project can talk LoRa
+    pub fn has_lora_board(&self) -> bool {
+        self.system.get_all_boards().iter().any(|b| b.is_lora_capable())
+    }
+
     pub fn add_board(&mut self, board: Board) {
         match board.is_main_board() {
             true => {
@@ -109,7 +334,7 @@ impl Project {
                 // don't duplicate a board
                 if self.system.peripheral_boards.contains(&board) {
                     info!("project <{}> already contains board <{:?}>", self.name, board);
-                    self.terminal_buffer += "project already contains that board\n";
+                    self.console.log("project already contains that board");
                     return;
                 } else {
                     self.system.peripheral_boards.push(board.clone());
@@ -135,6 +360,109 @@ impl Project {
         }
     }
 
+    /// The lints the code is checked with, which are the workspace's with the project's
+    /// own on top.
+    pub fn effective_lints(&self) -> LintSettings {
+        match &self.workspace {
+            Some(workspace) => workspace.settings.lints.overridden_by(&self.lints),
+            None => self.lints.clone(),
+        }
+    }
+
+    /// Write the lints to Cargo.toml and clippy.toml, and check the code with them.
+    fn apply_lints(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            self.info_logger("save the project before setting its lints");
+            return;
+        };
+        let lints = self.effective_lints();
+        if let Err(e) = lints.validate() {
+            self.info_logger(&format!("not applying the lints: {}", e));
+            return;
+        }
+        if let Err(e) = lints.write_manifest(&path).and_then(|_| lints.write_clippy_config(&path)) {
+            self.info_logger(&format!("couldn't apply the lints: {}", e));
+            return;
+        }
+        self.check_lints(ctx);
+    }
+
+    /// Check the code with clippy on a background job. A LintsChecked event is published
+    /// with what it finds.
+    fn check_lints(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            return;
+        };
+        let mut backend = self.build_backend.with_board_defaults(self.system.main_board.as_ref());
+        backend.features = self.write_config(&path);
+        backend.env = self.effective_environment().vars();
+        let tx = self.console.start("clippy", Stream::Cargo);
+        let bus = events::shared(ctx);
+        jobs::shared(ctx).spawn("clippy", Some(ctx), move |job| {
+            let (result, diagnostics) = backend::run_collecting_diagnostics(backend.check_command(&path), &tx, job);
+            bus.publish(Event::LintsChecked(diagnostics));
+            result
+        });
+    }
+
+    /// Pick up the workspace the project is in, if there is one, adding its boards to
+    /// the known boards.
+    fn find_workspace(&mut self, project_directory: &Path) {
+        self.workspace = match workspace::find(project_directory) {
+            Some(Ok(workspace)) => {
+                info!("project is in the workspace {}", workspace);
+                Some(workspace)
+            },
+            Some(Err(e)) => {
+                self.info_logger(&format!("not using the workspace settings: {}", e));
+                None
+            },
+            None => None,
+        };
+        self.add_workspace_boards();
+    }
+
+    /// Add the workspace's boards to the known boards, unless the same board is there
+    /// already. This is done again whenever the known boards are replaced.
+    pub fn add_workspace_boards(&mut self) {
+        let Some(workspace) = &self.workspace else {
+            return;
+        };
+        for board in workspace.boards() {
+            if !self.known_boards.contains(&board) {
+                self.known_boards.push(board);
+            }
+        }
+    }
+
+    /// The workspace the project is in, if it's in one.
+    pub fn workspace(&self) -> Option<&Workspace> {
+        self.workspace.as_ref()
+    }
+
+    /// The environment variables things are run with, which are the workspace's along
+    /// with the project's own.
+    pub fn effective_environment(&self) -> ProjectEnvironment {
+        match &self.workspace {
+            Some(workspace) => workspace.environment(&self.environment),
+            None => self.environment.clone(),
+        }
+    }
+
+    /// Run one of the workspace's tasks in the project's directory.
+    pub fn run_task(&mut self, ctx: &egui::Context, name: &str) {
+        let (Some(location), Some(task)) = (self.location.clone(), self.workspace.as_ref().and_then(|w| w.task(name)).cloned()) else {
+            self.info_logger(&format!("there's no task called {}", name));
+            return;
+        };
+        match task.command(&location) {
+            Ok(cmd) => {
+                self.run_background_commands(&task.name, Stream::Script, &[cmd], ctx);
+            },
+            Err(e) => self.info_logger(&e),
+        }
+    }
+
     /// This method will reload the project based on the current project location
     pub fn reload(&mut self) -> Result {
         if let Some(location) = self.location.clone() {
@@ -145,7 +473,7 @@ impl Project {
     }
 
     /// Load a project from a specified directory, and sync the board assets.
-    fn load_from(&mut self, project_directory: &Path) -> Result {
+    pub fn load_from(&mut self, project_directory: &Path) -> Result {
         let project_file = project_directory.join(PROJECT_FILE_NAME);
         let toml_str = match fs::read_to_string(project_file) {
             Ok(s) => s,
@@ -167,12 +495,42 @@ impl Project {
         self.name = p.name;
         self.location = Some(project_directory.to_path_buf());
         self.system = p.system;
+        self.build_backend = p.build_backend;
+        self.config = p.config;
+        self.slot_settings = p.slot_settings;
+        self.lorawan = p.lorawan;
+        self.calibration = p.calibration;
+        self.state_machines = p.state_machines;
+        self.messages = p.messages;
+        self.forwarding = p.forwarding;
+        self.instrument_sequences = p.instrument_sequences;
+        self.embedded_assets = p.embedded_assets;
+        self.font_subsets = p.font_subsets;
+        self.localization = p.localization;
+        self.power_plan = p.power_plan;
+        self.cpu_usage = p.cpu_usage;
+        self.heap_usage = p.heap_usage;
+        self.dependency_sources = p.dependency_sources;
+        self.reproducible = p.reproducible;
+        self.pin_exports = p.pin_exports;
+        self.trash = p.trash;
+        self.environment = p.environment;
+        match environment::load_secrets(project_directory) {
+            Ok(secrets) => self.environment.restore_secrets(&secrets),
+            Err(e) => warn!("couldn't read the secret environment variables: {:?}", e),
+        }
+        self.lints = p.lints;
+        self.std_support = p.std_support;
+        self.logging = p.logging;
+        self.config_partition = p.config_partition;
+        self.partition_table = p.partition_table;
         self.current_view = p.current_view;
+        self.console.clear();
+        self.find_workspace(project_directory);
         // sync the assets with the global ones
         self.load_board_resources();
-        self.terminal_buffer.clear();
         // Open the repo in the project directory
-        self.repo = match Repository::open(self.get_location()) {
+        self.repo = match Repository::open(project_directory) {
             Ok(repo) => Some(repo),
             Err(e) => {
                 info!("Could not open repo: {}", e);
@@ -203,15 +561,12 @@ impl Project {
                 fs::create_dir(project_folder.as_path())?;
             }
             // check if there is an existing .ironcoder.toml file that we might overwrite
-            for entry in std::fs::read_dir(&project_folder).unwrap() {
-                if entry.unwrap().file_name().to_str().unwrap() == PROJECT_FILE_NAME {
-                    warn!("you might be overwriting an existing Iron Coder project! \
-                           Are you sure you wish to continue?");
-                    self.terminal_buffer += "beware of overwriting and existing project file!\n";
-                    return Ok(());
-                }
+            if project_folder.join(PROJECT_FILE_NAME).try_exists()? {
+                warn!("you might be overwriting an existing Iron Coder project! \
+                       Are you sure you wish to continue?");
+                self.console.log("beware of overwriting and existing project file!");
+                return Ok(());
             }
-            self.location = Some(project_folder);
             // TODo: find template directory based on "programmable board" (for now just use board 0) -- No longer relevant?
             // if let Some(template_dir) = self.system.boards[0].get_template_dir() {
             //     // copy_recursive(template_dir, project_dir)
@@ -223,9 +578,16 @@ impl Project {
             //         }
             //     }
             // }
+            self.save_to(&project_folder)
         } else {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "project save aborted!"));
         }
+    }
+
+    /// Save the project into the given folder without prompting the user.
+    pub fn save_to(&mut self, project_folder: &Path) -> io::Result<()> {
+        self.location = Some(project_folder.to_path_buf());
+        self.find_workspace(project_folder);
         self.save()
     }
 
@@ -239,84 +601,310 @@ impl Project {
             let project_file = project_folder.join(PROJECT_FILE_NAME);
             info!("saving project file to {}", project_file.display().to_string());
 
-            match toml::to_string(self) {
+            // the location isn't written to the project file, since it's wherever the file is
+            // found when loading (and toml can't represent every valid path anyway). The
+            // secret values are saved apart from it, so they aren't committed.
+            let location = self.location.take();
+            let secrets = self.environment.take_secrets();
+            let contents = toml::to_string(self);
+            self.location = location;
+            self.environment.restore_secrets(&secrets);
+            match contents {
                 Ok(contents) => {
                     fs::write(project_file, contents)?;
+                    environment::save_secrets(&project_folder, &secrets)?;
                 },
                 Err(e) => {
                     warn!("couldn't save project to toml file!! {:?}", e);
+                    return Err(io::Error::other(e));
                 }
             }
 
             self.code_editor.save_all().unwrap_or_else(|_| warn!("error saving tabs!"));
+            self.sync_pin_exports();
             Ok(())
         }
     }
 
-    /// Build the code with Cargo
-    fn build(&mut self, ctx: &egui::Context) {
+    /// Rewrite the kept pin assignment exports, if the connections have changed since
+    /// they were last written.
+    pub fn sync_pin_exports(&mut self) {
+        let Some(project_dir) = self.location.clone() else {
+            return;
+        };
+        match self.pin_exports.sync(&self.system, &project_dir) {
+            Ok(written) => {
+                for path in written {
+                    info!("wrote the pin assignments to {}", path.display());
+                }
+            },
+            Err(e) => warn!("couldn't write the pin assignments: {:?}", e),
+        }
+    }
+
+    /// The config options declared by the project's boards
+    pub fn config_schema(&self) -> Vec<config::SchemaEntry> {
+        config::schema(&self.system.get_all_boards())
+    }
+
+    /// Regenerate the config module (if any board has config options), and return the
+    /// cargo features that the config turns on.
+    fn write_config(&mut self, project_dir: &Path) -> Vec<String> {
+        let schema = self.config_schema();
+        if schema.is_empty() {
+            return vec![];
+        }
+        if let Err(e) = self.config.write(&schema, project_dir) {
+            self.info_logger(&format!("couldn't write the project config: {}", e));
+        }
+        self.config.enabled_features(&schema)
+    }
+
+    /// Point Cargo at the project's dependency sources.
+    fn write_sources(&mut self, project_dir: &Path) {
+        if let Err(e) = self.dependency_sources.write_config(project_dir) {
+            self.info_logger(&format!("couldn't set the dependency sources: {}", e));
+        }
+    }
+
+    /// Set up a reproducible build if the project has them turned on, pinning the toolchain.
+    fn make_reproducible(&mut self, build_backend: &mut BuildBackend, project_dir: &Path) {
+        if !self.reproducible.enabled {
+            return;
+        }
+        if build_backend.kind != backend::BackendKind::Cargo {
+            self.info_logger(&format!("reproducible builds are only for Cargo projects; building with {} as usual", build_backend.kind));
+            return;
+        }
+        let mut reproducible = self.reproducible.clone();
+        if let Some(workspace) = &self.workspace {
+            reproducible.toolchain = workspace.toolchain(&self.reproducible.toolchain).to_string();
+        }
+        if let Err(e) = reproducible.write_toolchain(project_dir) {
+            self.info_logger(&format!("couldn't pin the toolchain: {}", e));
+        }
+        build_backend.reproducible = true;
+    }
+
+    /// The slot image to package after building, if the main board has a slot-based
+    /// bootloader. Zephyr and ESP-IDF builds package their own images.
+    fn slot_image(&mut self) -> Option<SlotImage> {
+        let layout = self.system.main_board.as_ref()?.flash_layout()?;
+        if self.build_backend.kind != backend::BackendKind::Cargo {
+            return None;
+        }
+        let project_dir = self.location.clone()?;
+        match SlotImage::new(layout, &self.slot_settings, &project_dir) {
+            Ok(slot_image) => Some(slot_image),
+            Err(e) => {
+                self.info_logger(&format!("not packaging a slot image: {}", e));
+                None
+            },
+        }
+    }
+
+    /// Build the code with the project's build backend on a background job, packaging it
+    /// for the bootloader if needed. A BuildFinished event is published on the bus when
+    /// it's done.
+    pub fn build(&mut self, ctx: &egui::Context) {
+        self.build_and_then(ctx, None);
+    }
+
+    /// Build reproducibly, and record the build or check it against the recorded one.
+    fn build_and_record(&mut self, ctx: &egui::Context, action: RecordAction) {
+        if !self.reproducible.enabled || self.build_backend.kind != backend::BackendKind::Cargo {
+            self.info_logger("turn on reproducible builds for a Cargo project first");
+            return;
+        }
+        self.build_and_then(ctx, Some(action));
+    }
+
+    fn build_and_then(&mut self, ctx: &egui::Context, action: Option<RecordAction>) {
         // Make sure we have a valid path
-        if let Some(path) = &self.location {
+        if let Some(path) = self.location.clone() {
             info!("building project at {}", path.display().to_string());
             self.code_editor.save_all().unwrap_or_else(|_| warn!("error saving tabs!"));
-            let cmd = duct::cmd!("cargo", "-Z", "unstable-options", "-C", path.as_path().to_str().unwrap(), "build");
-            self.run_background_commands(&[cmd], ctx);
+            let mut backend = self.build_backend.with_board_defaults(self.system.main_board.as_ref());
+            backend.features = self.write_config(&path);
+            backend.features.extend(self.localization.build_feature());
+            backend.env = self.effective_environment().vars();
+            self.write_sources(&path);
+            self.make_reproducible(&mut backend, &path);
+            let slot_image = self.slot_image();
+            let name = format!("{} build", backend.tool());
+            let tx = self.console.start(&name, Stream::Cargo);
+            self.explained.clear();
+            let bus = events::shared(ctx);
+            let generated = (self.embedded_assets.clone(), self.font_subsets.clone(), self.localization.clone(), self.cpu_usage.clone(), self.heap_usage.clone(), self.std_support.clone(), self.logging.clone());
+            jobs::shared(ctx).spawn(&name, Some(ctx), move |job| {
+                regenerate_modules(&generated, &path, &tx)?;
+                warn_conflicts(&path, &tx);
+                let cmd = backend.build_command(&path);
+                let mut result = backend::run_with_diagnostics(cmd, &tx, job);
+                if let (Ok(()), Some(slot_image)) = (&result, slot_image) {
+                    result = package_slot_image(&slot_image, &path, &tx).map(|_| ());
+                }
+                if let (Ok(()), Some(action)) = (&result, action) {
+                    result = action.run(&path, &tx);
+                }
+                bus.publish(Event::BuildFinished { success: result.is_ok() });
+                result
+            });
         } else {
             self.info_logger("project needs a valid working directory before building");
         }
     }
 
-    /// Load the code (for now using 'cargo run')
+    /// Load the code onto the board with the project's build backend. Boards with a
+    /// slot-based bootloader get a freshly built image written to the chosen slot.
     fn load_to_board(&mut self, ctx: &egui::Context) {
-        if let Some(path) = &self.location {
-            let cmd = duct::cmd!("cargo", "-Z", "unstable-options", "-C", path.as_path().to_str().unwrap(), "run");
-            self.run_background_commands(&[cmd], ctx);
-            self.info_logger("Successfully flashed board.");
+        if let Some(path) = self.location.clone() {
+            let mut backend = self.build_backend.with_board_defaults(self.system.main_board.as_ref());
+            backend.features = self.write_config(&path);
+            backend.features.extend(self.localization.build_feature());
+            backend.env = self.effective_environment().vars();
+            self.write_sources(&path);
+            self.make_reproducible(&mut backend, &path);
+            let slot_image = self.slot_image();
+            let tx = self.console.start(LOAD_JOB_NAME, Stream::Flasher);
+            let environment = self.effective_environment();
+            self.explained.clear();
+            let generated = (self.embedded_assets.clone(), self.font_subsets.clone(), self.localization.clone(), self.cpu_usage.clone(), self.heap_usage.clone(), self.std_support.clone(), self.logging.clone());
+            if let Some(board) = &self.system.main_board {
+                if let Err(e) = partition_table::apply(self.partition_table.as_ref(), board, &path) {
+                    self.info_logger(&format!("can't load onto the board with this partition table: {}", e));
+                    return;
+                }
+            }
+            // The firmware doesn't return control from `probe-rs run`, so the settings go first
+            let table = partition_table::effective(self.partition_table.as_ref(), self.system.main_board.as_ref());
+            let config_commands = match (&self.system.main_board, table, self.config_partition.flash_with_firmware) {
+                (Some(board), Some(table), true) => self.config_partition.commands(board, table, &path, true),
+                (Some(board), None, true) => Err(format!("{} has no partition table", board.get_name())),
+                _ => Ok(vec![]),
+            };
+            jobs::shared(ctx).spawn(LOAD_JOB_NAME, Some(ctx), move |job| {
+                regenerate_modules(&generated, &path, &tx)?;
+                warn_conflicts(&path, &tx);
+                let config_commands = config_commands.map_err(|e| format!("couldn't make the configuration partition: {}", e))?;
+                jobs::run_commands(&config_commands.into_iter().map(|cmd| environment.apply(cmd)).collect::<Vec<_>>(), &tx, job)?;
+                let Some(slot_image) = slot_image else {
+                    return backend::run_with_diagnostics(backend.flash_command(&path), &tx, job);
+                };
+                backend::run_with_diagnostics(backend.build_command(&path), &tx, job)?;
+                let image_path = package_slot_image(&slot_image, &path, &tx)?;
+                let cmd = slot_image.flash_command(&image_path).map_err(|e| e.to_string())?;
+                jobs::run_commands(&[environment.apply(cmd)], &tx, job)
+            });
         } else {
             self.info_logger("project needs a valid working directory before building");
         }
 
     }
 
-    pub fn new_file(&mut self) -> io::Result<()> {
-        if self.location == None {
-            self.info_logger("must save project before adding files/directories");
-            return Ok(());
-        }
-        if let Some(pathbuf) = FileDialog::new().set_directory(self.location.clone().unwrap()).save_file() {
-            fs::File::create_new(pathbuf)?;
-        } else {
-            warn!("error getting file path");
+    /// Look for attached debug probes using probe-rs, publishing an event for each one found.
+    fn detect_probes(&mut self, ctx: &egui::Context) {
+        let tx = self.console.start("detect probes", Stream::Flasher);
+        let bus = events::shared(ctx);
+        jobs::shared(ctx).spawn("detect probes", Some(ctx), move |_job| {
+            let output = duct::cmd!("probe-rs", "list").stderr_to_stdout().unchecked().read()
+                .map_err(|e| format!("couldn't run probe-rs: {}", e))?;
+            // probe-rs lists each probe as "[<index>]: <description>"
+            let probes: Vec<&str> = output.lines().filter(|l| l.trim_start().starts_with('[')).collect();
+            if probes.is_empty() {
+                let _ = tx.send("no debug probes found.\n".to_string());
+            }
+            for probe in probes {
+                bus.publish(Event::ProbeAttached(probe.trim().to_string()));
+            }
+            Ok(())
+        });
+    }
+
+    /// Vendor the project's dependencies from crates.io or the mirror, then build from the
+    /// vendored copies.
+    fn vendor_dependencies(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            self.info_logger("project needs a valid working directory before vendoring");
+            return;
+        };
+        let vendored = DependencySources { mode: sources::SourceMode::Vendored, ..self.dependency_sources.clone() };
+        if let Err(e) = vendored.validate() {
+            self.info_logger(&format!("can't vendor the dependencies: {}", e));
+            return;
         }
-        Ok(())
+        self.dependency_sources = vendored.clone();
+        let tx = self.console.start("vendor dependencies", Stream::Cargo);
+        let environment = self.effective_environment();
+        jobs::shared(ctx).spawn("vendor dependencies", Some(ctx), move |job| {
+            // Vendoring from the old vendor directory wouldn't pick up anything new
+            vendored.upstream().write_config(&path)?;
+            jobs::run_commands(&[environment.apply(sources::vendor_command(&path))], &tx, job)?;
+            vendored.write_config(&path)?;
+            let _ = tx.send(format!("dependencies vendored into {}/\n", sources::VENDOR_DIR));
+            Ok(())
+        });
     }
 
-    /// This method will run a series of command sequentially on a separate
-    /// thread, sending their output through the channel to the project's terminal buffer
-    /// TODO - fix bug that calling this command again before a former call's thread is
-    ///   complete will overwrite the rx channel in the Project object. Possible solution
-    ///   might be to add a command to a queue to be evaluated.
-    fn run_background_commands(&mut self, cmds: &[duct::Expression], ctx: &egui::Context) {
-        // create comms channel
-        let context = ctx.clone();
-        let commands = cmds.to_owned();
-        let (tx, rx) = std::sync::mpsc::channel();
-        self.receiver = Some(rx);
-        let _ = std::thread::spawn(move || {
-            for cmd in commands.iter() {
-                let reader = cmd.stderr_to_stdout().unchecked().reader().unwrap();
-                let mut lines = std::io::BufReader::new(reader).lines();
-                while let Some(line) = lines.next() {
-                    let line = line.unwrap() + "\n";
-                    debug!("sending line through channel");
-                    tx.send(line).unwrap();
-                    context.request_repaint();
-                }
-            }
-            info!("leaving thread");
+    /// Check the project builds from its lock file without the network.
+    fn check_offline_build(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            self.info_logger("project needs a valid working directory before building");
+            return;
+        };
+        self.write_sources(&path);
+        self.run_background_commands("offline build check", Stream::Cargo, &[sources::offline_check_command(&path)], ctx);
+    }
+
+    /// Audit the project's setup on this machine on a background job. The findings are
+    /// published on the bus.
+    fn check_health(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.location.clone() else {
+            self.info_logger("project needs a valid working directory before checking it");
+            return;
+        };
+        let board = self.system.main_board.as_ref().map(|b| health::BoardMemory {
+            name: b.get_name().to_string(),
+            flash: b.flash(),
+            ram: b.ram(),
+        });
+        let sources = self.dependency_sources.clone();
+        let tx = self.console.start("health check", Stream::IronCoder);
+        let bus = events::shared(ctx);
+        jobs::shared(ctx).spawn("health check", Some(ctx), move |job| {
+            job.set_status("looking at the toolchain, probes and dependencies");
+            let facts = health::Facts::gather(&path);
+            let findings = health::check(&path, board.as_ref(), &facts, &sources);
+            let _ = tx.send(format!("health check: {} findings\n", findings.len()));
+            bus.publish(Event::HealthChecked(findings));
+            Ok(())
         });
     }
 
+    /// Start adding a file or folder in the file tree, next to what's selected in it.
+    pub fn new_file(&mut self, folder: bool) {
+        let Some(location) = self.location.clone() else {
+            self.info_logger("must save project before adding files/directories");
+            return;
+        };
+        self.current_view = ProjectViewType::FileTree;
+        let dir = self.file_tree.target_dir(&location);
+        self.file_tree.start_new(&dir, folder);
+    }
+
+    /// This method will run a series of commands sequentially as a background job on the
+    /// app-wide scheduler, sending their output to a section of its own in the project's
+    /// console, so jobs running at once don't mix their output. The commands get the
+    /// project's environment variables.
+    fn run_background_commands(&mut self, name: &str, stream: Stream, cmds: &[duct::Expression], ctx: &egui::Context) -> JobId {
+        let tx = self.console.start(name, stream);
+        let environment = self.effective_environment();
+        let commands: Vec<duct::Expression> = cmds.iter().map(|cmd| environment.apply(cmd.clone())).collect();
+        jobs::shared(ctx).spawn(name, Some(ctx), move |job| {
+            jobs::run_commands(&commands, &tx, job)
+        })
+    }
+
     /// Generate the Cargo project template based on the main board template (if it has one).
     /// The template will be written to the project directory.
     /// TODO - generally more useful error returns, i.e. if the cargo generate command returns a
@@ -324,20 +912,36 @@ impl Project {
     pub fn generate_cargo_template(&mut self, ctx: &egui::Context) -> Result {
         info!("generating project template");
         let mut cmds: Vec<duct::Expression> = vec![];
+        let location = match &self.location {
+            Some(location) => location.clone(),
+            None => return Err(ProjectIOError::NoProjectDirectory),
+        };
         if let Some(mb) = &self.system.main_board {
             if let Some(template_dir) = mb.get_template_dir() {
                 let cmd = duct::cmd!(
                     "cargo",
                     "generate",
                     "--path",
-                    template_dir.as_path().to_str().unwrap(),
+                    paths::simplified(&template_dir),
                     "--name",
                     self.name.clone(),
                     "--destination",
-                    self.get_location(),
+                    paths::simplified(&location),
                     "--init",
                 );
                 cmds.push(cmd);
+            } else if mb.hal_crate().is_some() || self.std_support.support == StdSupport::Std {
+                // no template, so write the starter program for the board's HAL, or for Linux
+                let package = example::package_name(&self.name);
+                let written = match starter::write_starter(mb, &package, &location, &self.std_support) {
+                    Ok(written) => written,
+                    Err(e) => {
+                        warn!("couldn't write the starter program: {}", e);
+                        return Err(ProjectIOError::NoProjectTemplate);
+                    },
+                };
+                let written: Vec<String> = written.iter().map(|f| f.display().to_string()).collect();
+                self.console.log(&format!("wrote the starter program for {}: {}", mb.label(), written.join(", ")));
             } else {
                 return Err(ProjectIOError::NoProjectTemplate);
             }
@@ -361,15 +965,47 @@ impl Project {
                 }
             }
             */
-            self.run_background_commands(&cmds, ctx);
         } else {
             return Err(ProjectIOError::NoMainBoard);
         }
         // Create a repo to store code
-        self.repo = match Repository::init(self.get_location()) {
-            Ok(repo) => Some(repo),
-            Err(_e) => return Err(ProjectIOError::NoProjectDirectory),
-        };
+        if self.repo_setup.init {
+            self.repo = match Repository::init(&location) {
+                Ok(repo) => Some(repo),
+                Err(_e) => return Err(ProjectIOError::NoProjectDirectory),
+            };
+        }
+        // The template may bring a .gitignore, so what's missing from it is added after
+        let tx = self.console.start("generate template", Stream::Cargo);
+        let environment = self.effective_environment();
+        let commands: Vec<duct::Expression> = cmds.iter().map(|cmd| environment.apply(cmd.clone())).collect();
+        let commit = self.repo_setup.init && self.repo_setup.initial_commit;
+        let secrets = environment.secrets();
+        jobs::shared(ctx).spawn("generate template", Some(ctx), move |job| {
+            jobs::run_commands(&commands, &tx, job)?;
+            match vcs::ensure_gitignore(&location) {
+                Ok(added) if !added.is_empty() => {
+                    let _ = tx.send(format!("added to {}: {}\n", vcs::GITIGNORE_FILE_NAME, added.join(" ")));
+                },
+                Ok(_) => (),
+                Err(e) => {
+                    let _ = tx.send(format!("couldn't write {}: {}\n", vcs::GITIGNORE_FILE_NAME, e));
+                },
+            }
+            if commit {
+                match vcs::initial_commit(&location, &secrets) {
+                    Ok(Some((oid, left_out))) => {
+                        for warning in left_out.iter() {
+                            let _ = tx.send(format!("left out of the first commit: {}\n", warning));
+                        }
+                        let _ = tx.send(format!("made the first commit, {}\n", oid));
+                    },
+                    Ok(None) => (),
+                    Err(e) => return Err(format!("couldn't make the first commit: {}", e.message())),
+                }
+            }
+            Ok(())
+        });
 
         Ok(())
     }
@@ -0,0 +1,273 @@
+//! Title: Iron Coder Project Module - Panic Retrieval
+//! Description: Reads the panic that panic-persist kept in RAM straight off the board with
+//!   the debug probe, for when a board that failed in the field doesn't get to send it over
+//!   serial. The region is found from the firmware's `_panic_dump_start` and
+//!   `_panic_dump_end` symbols, and the addresses in the message are looked up in the
+//!   firmware's debug info, so the panic can be followed to the source.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use addr2line::object::{self, Object, ObjectSymbol};
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobId, JobState};
+use crate::board::Board;
+use crate::project::console::{self, FileRef};
+use crate::project::mcuboot;
+
+/// What panic-persist writes at the start of the region when it keeps a panic.
+pub const PANIC_MAGIC: u32 = 0x0FACADE0;
+/// The symbols the linker script gives the panic region with.
+pub const REGION_SYMBOLS: (&str, &str) = ("_panic_dump_start", "_panic_dump_end");
+
+/// The address of a symbol in the firmware, i.e. a static the probe can read or write.
+pub fn symbol_address(elf: &[u8], name: &str) -> Result<u64, String> {
+    let file = object::File::parse(elf).map_err(|e| format!("the firmware isn't an ELF file: {}", e))?;
+    file.symbols()
+        .find(|s| s.name() == Ok(name))
+        .map(|s| s.address())
+        .ok_or_else(|| format!("the firmware has no {} symbol", name))
+}
+
+/// The region of RAM a panic is kept in, from its first address up to the end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PanicRegion {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl PanicRegion {
+
+    /// Find the region from the firmware's symbols.
+    pub fn from_elf(elf: &[u8]) -> Result<Self, String> {
+        let symbol = |name: &str| symbol_address(elf, name)
+            .map_err(|e| format!("{}; is it built with panic-persist and its memory.x?", e));
+        let region = Self { start: symbol(REGION_SYMBOLS.0)?, end: symbol(REGION_SYMBOLS.1)? };
+        if region.end <= region.start + 8 {
+            return Err(format!("the panic region at {:#x} is too small", region.start));
+        }
+        Ok(region)
+    }
+
+    /// How many 32-bit words the region is.
+    pub fn words(&self) -> u64 {
+        (self.end - self.start) / 4
+    }
+}
+
+/// The bytes of memory in what `probe-rs read` printed as 32-bit words, i.e.
+/// `0facade0 0000001e 696e6170`. Lines that aren't words, like probe-rs's own messages,
+/// are left out.
+pub fn parse_words(output: &[String]) -> Vec<u8> {
+    output.iter()
+        .filter_map(|line| line.split_whitespace()
+            .map(|word| u32::from_str_radix(word.trim_start_matches("0x"), 16))
+            .collect::<Result<Vec<u32>, _>>()
+            .ok())
+        .flatten()
+        .flat_map(u32::to_le_bytes)
+        .collect()
+}
+
+/// The message kept in a panic region: a magic word, the length of the message, and
+/// the message. `None` if no panic is kept.
+pub fn decode_dump(region: &[u8]) -> Option<String> {
+    let word = |at: usize| region.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    if word(0)? != PANIC_MAGIC {
+        return None;
+    }
+    let len = word(4)? as usize;
+    // a length past the region is cut short, since the message would have been too
+    let message = &region[8..region.len().min(8 + len)];
+    Some(String::from_utf8_lossy(message).to_string())
+}
+
+/// The addresses in a panic message, i.e. `0x10001f2a`, which could be code.
+pub fn addresses(message: &str) -> Vec<u64> {
+    let mut found = vec![];
+    for token in message.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let Some(hex) = token.strip_prefix("0x") else { continue };
+        if let Ok(address) = u64::from_str_radix(hex, 16) {
+            if !found.contains(&address) {
+                found.push(address);
+            }
+        }
+    }
+    found
+}
+
+/// A place in the firmware's code an address is in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub address: u64,
+    pub function: Option<String>,
+    pub file: Option<FileRef>,
+}
+
+/// Look up the addresses in the firmware's debug info, innermost inlined function first.
+/// Addresses that aren't in the code are left out.
+pub fn symbolicate(elf: &[u8], addresses: &[u64]) -> Result<Vec<Frame>, String> {
+    let file = object::File::parse(elf).map_err(|e| format!("the firmware isn't an ELF file: {}", e))?;
+    let context = addr2line::Context::new(&file).map_err(|e| format!("couldn't read the firmware's debug info: {}", e))?;
+    let mut frames = vec![];
+    for &address in addresses {
+        let Ok(mut found) = context.find_frames(address).skip_all_loads() else { continue };
+        while let Ok(Some(frame)) = found.next() {
+            let function = frame.function.as_ref().and_then(|f| f.demangle().ok()).map(|f| f.to_string());
+            let file = frame.location.as_ref().and_then(|l| Some(FileRef {
+                path: PathBuf::from(l.file?),
+                line: l.line? as usize,
+                column: l.column.map(|c| c as usize),
+                span: 0..0,
+            }));
+            if function.is_some() || file.is_some() {
+                frames.push(Frame { address, function, file });
+            }
+        }
+    }
+    Ok(frames)
+}
+
+/// The last panic read off a board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetrievedPanic {
+    /// `None` if the board had no panic kept
+    pub message: Option<String>,
+    /// The places in the source the message names, i.e. `src/main.rs:12:5`
+    pub locations: Vec<FileRef>,
+    pub frames: Vec<Frame>,
+}
+
+impl RetrievedPanic {
+
+    /// Make sense of what was read from the panic region.
+    pub fn new(region: &[u8], elf: &[u8]) -> Self {
+        let Some(message) = decode_dump(region) else {
+            return Self { message: None, locations: vec![], frames: vec![] };
+        };
+        let locations = message.lines().flat_map(console::file_refs).collect();
+        // symbols are a help, so a firmware without debug info still shows the message
+        let frames = symbolicate(elf, &addresses(&message)).unwrap_or_default();
+        Self { message: Some(message), locations, frames }
+    }
+}
+
+/// The state of the panic retrieval window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PanicDumpPanel {
+    /// The probe-rs chip name, if not the board's
+    chip: String,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    lines: Option<Receiver<String>>,
+    #[serde(skip)]
+    output: Vec<String>,
+    #[serde(skip)]
+    firmware: Option<PathBuf>,
+    #[serde(skip)]
+    last: Option<RetrievedPanic>,
+    #[serde(skip)]
+    status: Option<String>,
+}
+
+impl PanicDumpPanel {
+
+    /// Start reading the panic region with probe-rs.
+    fn read(&mut self, ctx: &egui::Context, chip: &str, project_dir: &Path) -> Result<(), String> {
+        let firmware = mcuboot::find_firmware(project_dir).map_err(|e| format!("{}; build the firmware first", e))?;
+        let elf = fs::read(&firmware).map_err(|e| format!("couldn't read {}: {}", firmware.display(), e))?;
+        let region = PanicRegion::from_elf(&elf)?;
+        let cmd = duct::cmd!("probe-rs", "read", "--chip", chip, "b32", format!("{:#x}", region.start), region.words().to_string());
+        let (tx, rx) = channel();
+        self.job = Some(jobs::shared(ctx).spawn("read the last panic", Some(ctx), move |job| {
+            jobs::run_commands(&[cmd], &tx, job)
+        }));
+        self.lines = Some(rx);
+        self.output.clear();
+        self.firmware = Some(firmware);
+        Ok(())
+    }
+
+    /// Take in what probe-rs printed, and make sense of it once it's done.
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.lines {
+            self.output.extend(rx.try_iter());
+        }
+        let Some(job) = self.job else { return };
+        match jobs::shared(ctx).state(job) {
+            Some(JobState::Running) => return,
+            Some(JobState::Finished) => {
+                let region = parse_words(&self.output);
+                let elf = self.firmware.as_ref().and_then(|f| fs::read(f).ok()).unwrap_or_default();
+                if region.is_empty() {
+                    self.status = Some(format!("probe-rs didn't read the panic region: {}", self.output.join(" ")));
+                } else {
+                    self.last = Some(RetrievedPanic::new(&region, &elf));
+                    self.status = None;
+                }
+            },
+            Some(JobState::Failed(e)) => self.status = Some(format!("couldn't read the panic region: {}", e)),
+            Some(JobState::Cancelled) | None => (),
+        }
+        self.job = None;
+        self.lines = None;
+    }
+
+    /// Show the window. Returns a place in the source to open, if one was clicked.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, board: Option<&Board>, project_dir: Option<&Path>) -> Option<FileRef> {
+        self.poll(ctx);
+        let board_chip = board.and_then(Board::chip_name).unwrap_or_default().to_string();
+        let reading = self.job.is_some();
+        ui.horizontal(|ui| {
+            ui.label("chip:");
+            ui.add(egui::TextEdit::singleline(&mut self.chip).hint_text(&board_chip).desired_width(140.0));
+            let chip = if self.chip.trim().is_empty() { board_chip.clone() } else { self.chip.trim().to_string() };
+            let button = ui.add_enabled(!reading && !chip.is_empty() && project_dir.is_some(), egui::Button::new("read last panic"))
+                .on_hover_text("read the panic panic-persist kept from the board's RAM, without running the firmware");
+            if button.clicked() {
+                if let Err(e) = self.read(ctx, &chip, project_dir.unwrap()) {
+                    self.status = Some(e);
+                }
+            }
+            if reading {
+                ui.spinner();
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.colored_label(ui.style().visuals.error_fg_color, status);
+        }
+        let last = self.last.as_ref()?;
+        ui.separator();
+        let Some(message) = &last.message else {
+            ui.label("the board has no panic kept.");
+            return None;
+        };
+        let mut open = None;
+        ui.label(egui::RichText::new(message).monospace());
+        for location in last.locations.iter() {
+            if ui.link(format!("{}:{}", location.path.display(), location.line)).clicked() {
+                open = Some(location.clone());
+            }
+        }
+        if !last.frames.is_empty() {
+            egui::CollapsingHeader::new("addresses").default_open(true).show(ui, |ui| {
+                for frame in last.frames.iter() {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!("{:#010x}", frame.address));
+                        ui.label(frame.function.as_deref().unwrap_or("?"));
+                        if let Some(file) = &frame.file {
+                            if ui.link(format!("{}:{}", file.path.display(), file.line)).clicked() {
+                                open = Some(file.clone());
+                            }
+                        }
+                    });
+                }
+            });
+        }
+        open
+    }
+}
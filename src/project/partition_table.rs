@@ -0,0 +1,322 @@
+//! Title: Iron Coder Project Module - Partition Table
+//! Description: The project's own partition table, started from the main board's or from
+//!   ESP-IDF's default and edited in a grid over the flash map. It's checked against the
+//!   board's flash and bootloader, gives the configuration partition its offset, and on
+//!   ESP chips is written to partitions.csv, which espflash and idf.py flash with.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::board::display::FlashMapWidget;
+use crate::board::flashing::FlashMethod;
+use crate::board::partitions::{Partition, PartitionKind, PartitionTable, SECTOR_SIZE};
+use crate::board::units::Memory;
+use crate::project::std_support::{update_file, Applied};
+
+pub const CSV_FILE_NAME: &str = "partitions.csv";
+/// The espflash option that flashes a partition table along with the firmware.
+const ESPFLASH_TABLE_ARG: &str = "--partition-table";
+/// The ESP-IDF options that build the partition table from the project's csv.
+const SDKCONFIG_OPTIONS: [&str; 2] = ["CONFIG_PARTITION_TABLE_CUSTOM", "CONFIG_PARTITION_TABLE_CUSTOM_FILENAME"];
+
+/// Whether the board is ESP32-class, which loads its firmware from a partition.
+pub fn is_esp(board: &Board) -> bool {
+    board.flash_method() == Some(FlashMethod::Espflash) || board.esp_idf_target().is_some()
+}
+
+/// The table a project uses: its own, or else its main board's.
+pub fn effective<'a>(project_table: Option<&'a PartitionTable>, main_board: Option<&'a Board>) -> Option<&'a PartitionTable> {
+    project_table.or_else(|| main_board?.partition_table())
+}
+
+/// Check the table fits the board's flash and stays out of its bootloader, and on ESP
+/// chips that the chip can boot from it.
+pub fn validate(table: &PartitionTable, board: &Board) -> Result<(), String> {
+    table.validate(board.flash(), board.flash_layout())?;
+    if is_esp(board) {
+        table.validate_esp()?;
+    }
+    Ok(())
+}
+
+/// The starting point of a project's table: the board's, or ESP-IDF's default for an
+/// ESP board without one.
+pub fn initial(board: &Board) -> PartitionTable {
+    match board.partition_table() {
+        Some(table) => table.clone(),
+        None if is_esp(board) => PartitionTable::esp_default(),
+        None => PartitionTable { base: 0, partitions: vec![] },
+    }
+}
+
+/// An espflash runner with the partition table option, or without it if there's no csv.
+pub fn runner(runner: &str, csv: Option<&str>) -> String {
+    let mut args: Vec<&str> = vec![];
+    let mut words = runner.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == ESPFLASH_TABLE_ARG {
+            words.next();
+        } else if !word.starts_with(&format!("{}=", ESPFLASH_TABLE_ARG)) {
+            args.push(word);
+        }
+    }
+    if let Some(csv) = csv {
+        args.push(ESPFLASH_TABLE_ARG);
+        args.push(csv);
+    }
+    args.join(" ")
+}
+
+/// The cargo config with each espflash runner flashing the partition table, or not.
+pub fn cargo_config(config: &str, csv: Option<&str>) -> Result<String, String> {
+    let mut doc: toml_edit::DocumentMut = config.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+    if let Some(targets) = doc.get_mut("target").and_then(toml_edit::Item::as_table_like_mut) {
+        for (_, target) in targets.iter_mut() {
+            let Some(runner_item) = target.get_mut("runner") else { continue };
+            let Some(current) = runner_item.as_str().filter(|r| r.starts_with("espflash flash")) else { continue };
+            let updated = runner(current, csv);
+            if updated != current {
+                *runner_item = toml_edit::value(updated);
+            }
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// ESP-IDF's sdkconfig.defaults with the options that use the csv, or without them.
+pub fn sdkconfig_defaults(defaults: &str, csv: Option<&str>) -> String {
+    let mut lines: Vec<String> = defaults.lines()
+        .filter(|l| !SDKCONFIG_OPTIONS.iter().any(|o| l.trim_start().starts_with(&format!("{}=", o))))
+        .map(str::to_string)
+        .collect();
+    if let Some(csv) = csv {
+        lines.push(format!("{}=y", SDKCONFIG_OPTIONS[0]));
+        lines.push(format!("{}=\"{}\"", SDKCONFIG_OPTIONS[1], csv));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Write the project's table to partitions.csv, and have espflash and ESP-IDF flash it;
+/// without a table of its own, the project goes back to the default table. Only ESP
+/// chips read a partition table, so nothing changes for other boards.
+pub fn apply(table: Option<&PartitionTable>, board: &Board, project_dir: &Path) -> Result<Applied, String> {
+    let mut applied = Applied::default();
+    if !is_esp(board) {
+        return Ok(applied);
+    }
+    if let Some(table) = table {
+        validate(table, board)?;
+        let csv = table.to_esp_csv()?;
+        let path = project_dir.join(CSV_FILE_NAME);
+        if fs::read_to_string(&path).ok().as_deref() != Some(csv.as_str()) {
+            fs::write(&path, csv).map_err(|e| format!("couldn't write {}: {}", CSV_FILE_NAME, e))?;
+            applied.changed.push(PathBuf::from(CSV_FILE_NAME));
+        }
+    }
+    let csv = table.map(|_| CSV_FILE_NAME);
+    update_file(project_dir, PathBuf::from(".cargo").join("config.toml"), false, &mut applied, |config, _| cargo_config(config, csv))?;
+    update_file(project_dir, PathBuf::from("sdkconfig.defaults"), false, &mut applied, |defaults, _| Ok(sdkconfig_defaults(defaults, csv)))?;
+    Ok(applied)
+}
+
+/// Parse an offset or size typed in the editor: hex bytes, or a number with a unit.
+pub fn parse_memory(s: &str) -> Result<Memory, String> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).map(Memory::from_bytes).map_err(|_| format!("invalid hex \"{}\"", s));
+    }
+    if s.parse::<f64>().is_ok() {
+        return Err(format!("\"{}\" needs a unit, i.e. 24 KiB, or to be in hex, i.e. 0x6000", s));
+    }
+    s.parse::<Memory>()
+}
+
+/// A partition as it's typed in the editor.
+#[derive(Clone, Debug, PartialEq)]
+struct Row {
+    name: String,
+    kind: PartitionKind,
+    offset: String,
+    size: String,
+}
+
+impl Row {
+    fn new(partition: &Partition) -> Self {
+        Self {
+            name: partition.name.clone(),
+            kind: partition.kind,
+            offset: format!("{:#x}", partition.offset.bytes()),
+            size: partition.size.to_string(),
+        }
+    }
+
+    fn parse(&self) -> Result<Partition, String> {
+        Ok(Partition {
+            name: self.name.trim().to_string(),
+            kind: self.kind,
+            offset: parse_memory(&self.offset)?,
+            size: parse_memory(&self.size)?,
+        })
+    }
+}
+
+/// The state of the partition table window. The table itself is the project's.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PartitionTablePanel {
+    #[serde(skip)]
+    rows: Vec<Row>,
+    /// The table the rows were last read from or written to
+    #[serde(skip)]
+    synced: Option<PartitionTable>,
+    #[serde(skip)]
+    status: Option<Result<String, String>>,
+}
+
+impl PartitionTablePanel {
+
+    /// Edit the rows, and put the table back in the project when they all parse.
+    fn display_rows(&mut self, ui: &mut egui::Ui, table: &mut PartitionTable) {
+        if self.synced.as_ref() != Some(table) {
+            self.rows = table.partitions.iter().map(Row::new).collect();
+            self.synced = Some(table.clone());
+        }
+        let mut remove = None;
+        egui::Grid::new("partition_table_rows").striped(true).show(ui, |ui| {
+            ui.strong("name");
+            ui.strong("kind");
+            ui.strong("offset");
+            ui.strong("size");
+            ui.end_row();
+            for (i, row) in self.rows.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut row.name).desired_width(90.0));
+                egui::ComboBox::from_id_source(("partition_kind", i)).selected_text(row.kind.to_string()).show_ui(ui, |ui| {
+                    for kind in enum_iterator::all::<PartitionKind>() {
+                        ui.selectable_value(&mut row.kind, kind, kind.to_string());
+                    }
+                });
+                for (field, value) in [("offset", &mut row.offset), ("size", &mut row.size)] {
+                    let error = parse_memory(value).err();
+                    let mut edit = egui::TextEdit::singleline(value).desired_width(80.0);
+                    if error.is_some() {
+                        edit = edit.text_color(ui.visuals().error_fg_color);
+                    }
+                    let response = ui.add(edit);
+                    if let Some(e) = error {
+                        response.on_hover_text(format!("{}: {}", field, e));
+                    }
+                }
+                if ui.small_button("✖").on_hover_text("remove the partition").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.rows.remove(i);
+        }
+        if ui.button("add a partition").clicked() {
+            let offset = table.partitions.iter().map(Partition::end).max().unwrap_or(0).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+            self.rows.push(Row {
+                name: format!("data{}", self.rows.len()),
+                kind: PartitionKind::Data,
+                offset: format!("{:#x}", offset),
+                size: "4 KiB".to_string(),
+            });
+        }
+        if let Ok(partitions) = self.rows.iter().map(Row::parse).collect::<Result<Vec<_>, _>>() {
+            if partitions != table.partitions {
+                table.partitions = partitions;
+                self.synced = Some(table.clone());
+            }
+        }
+    }
+
+    fn display_files(&mut self, ui: &mut egui::Ui, project_table: &mut Option<PartitionTable>, board: &Board, project_dir: Option<&Path>) {
+        ui.horizontal(|ui| {
+            if ui.button("import partitions.csv...").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("csv", &["csv"]).pick_file() {
+                    match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|csv| PartitionTable::from_esp_csv(&csv)) {
+                        Ok(table) => {
+                            *project_table = Some(table);
+                            self.status = Some(Ok(format!("imported {}", path.display())));
+                        },
+                        Err(e) => self.status = Some(Err(format!("couldn't import {}: {}", path.display(), e))),
+                    }
+                }
+            }
+            let Some(table) = project_table.as_ref() else { return };
+            if is_esp(board) && ui.button("export partitions.csv...").clicked() {
+                if let Some(path) = rfd::FileDialog::new().set_file_name(CSV_FILE_NAME).save_file() {
+                    self.status = Some(table.to_esp_csv()
+                        .and_then(|csv| fs::write(&path, csv).map_err(|e| e.to_string()))
+                        .map(|_| format!("exported {}", path.display()))
+                        .map_err(|e| format!("couldn't export the table: {}", e)));
+                }
+            }
+        });
+        let Some(project_dir) = project_dir else { return };
+        if is_esp(board) {
+            let button = ui.button("apply to the project")
+                .on_hover_text("write partitions.csv, and flash it with espflash or ESP-IDF. Loading onto the board does this too");
+            if button.clicked() {
+                self.status = Some(apply(project_table.as_ref(), board, project_dir).map(|applied| {
+                    let changed: Vec<String> = applied.changed.iter().map(|f| f.display().to_string()).collect();
+                    if changed.is_empty() {
+                        "the project is up to date".to_string()
+                    } else {
+                        format!("changed {}", changed.join(", "))
+                    }
+                }));
+            }
+        }
+    }
+
+    pub fn display(&mut self, ui: &mut egui::Ui, project_table: &mut Option<PartitionTable>, board: Option<&Board>, project_dir: Option<&Path>) {
+        let Some(board) = board else {
+            ui.label("add a main board to the project to lay out its flash");
+            return;
+        };
+        if project_table.is_some() {
+            ui.label("the project's own table");
+        } else if board.partition_table().is_some() {
+            ui.label(format!("{}'s table", board.get_name()));
+        } else {
+            ui.label(format!("{} has no partition table", board.get_name()));
+        }
+        ui.horizontal(|ui| {
+            if project_table.is_none() && ui.button("edit for this project").clicked() {
+                *project_table = Some(initial(board));
+            }
+            if project_table.is_some() && ui.button("use the board's table").clicked() {
+                *project_table = None;
+            }
+        });
+        let shown = effective(project_table.as_ref(), Some(board)).cloned();
+        if let Some(table) = shown.as_ref() {
+            ui.add(FlashMapWidget { layout: board.flash_layout(), partitions: Some(table), flash: board.flash(), selected: None });
+            if let Err(e) = validate(table, board) {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        }
+        ui.separator();
+        match project_table.as_mut() {
+            Some(table) => self.display_rows(ui, table),
+            None => {
+                for partition in shown.iter().flat_map(|t| t.partitions.iter()) {
+                    ui.label(format!("{} ({}): {:#x}, {}", partition.name, partition.kind, partition.offset.bytes(), partition.size));
+                }
+            },
+        }
+        ui.separator();
+        self.display_files(ui, project_table, board, project_dir);
+        match &self.status {
+            Some(Ok(done)) => { ui.label(done); },
+            Some(Err(e)) => { ui.colored_label(ui.visuals().error_fg_color, e); },
+            None => (),
+        }
+    }
+}
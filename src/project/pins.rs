@@ -0,0 +1,425 @@
+//! Title: Iron Coder Project Module - Pin Assignments
+//! Description: Which pin of which board each connection of the project uses, worked out
+//!   from the connections between its boards. They can be exported as CSV for schematic
+//!   tools and spreadsheets, as comments for firmware docs, or as a Rust module with a
+//!   constant for each pin, and the exports the project keeps are rewritten whenever the
+//!   connections change.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use enum_iterator::Sequence;
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+use crate::board::pinout::{InterfaceType, PinDefinition};
+use crate::project::starter;
+use crate::project::system::System;
+
+/// One connection of the project, from the main board's side if it's on one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinAssignment {
+    pub connection: String,
+    /// i.e. "I2C"
+    pub interface: String,
+    /// i.e. "Output"
+    pub direction: String,
+    pub board: String,
+    pub pin: String,
+    /// What the board says the pin is, i.e. "GPIO26"
+    pub function: String,
+    /// The connector the pin is on, i.e. "Feather header", if the board's form factor has one
+    pub connector: String,
+    /// The peripheral of the chip the pin is used with, i.e. "I2C1 SDA"
+    pub peripheral: String,
+    pub to_board: String,
+    pub to_pin: String,
+}
+
+/// The function of a pin that goes with an interface, i.e. "I2C1 SDA" for I2C. A GPIO's
+/// is what the board says the pin is.
+fn peripheral(pin: &PinDefinition, interface: InterfaceType) -> String {
+    if interface == InterfaceType::GPIO || interface == InterfaceType::NONE {
+        return pin.function.clone();
+    }
+    let name = interface.to_string();
+    std::iter::once(&pin.function).chain(pin.alternate_functions.iter())
+        .find(|f| f.trim().to_ascii_uppercase().starts_with(&name))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The project's pin assignments, in the order of its connections.
+pub fn assignments(system: &System) -> Vec<PinAssignment> {
+    let is_main = |name: &str| system.main_board.as_ref().is_some_and(|b| b.get_name() == name);
+    system.connections.iter().map(|c| {
+        let forwards = !is_main(c.end_board.get_name()) || is_main(c.start_board.get_name());
+        let ((board, pin), (to_board, to_pin)) = match forwards {
+            true => ((&c.start_board, &c.start_pin), (&c.end_board, &c.end_pin)),
+            false => ((&c.end_board, &c.end_pin), (&c.start_board, &c.start_pin)),
+        };
+        let interface = &c.interface_mapping.interface;
+        PinAssignment {
+            connection: c.name.clone(),
+            interface: interface.iface_type.to_string(),
+            direction: interface.direction.to_string(),
+            board: board.get_name().to_string(),
+            pin: pin.clone(),
+            function: board.pin(pin).map(|p| p.function.clone()).unwrap_or_default(),
+            connector: board.standard().map(|s| s.connector().to_string()).unwrap_or_default(),
+            peripheral: board.pin(pin).map(|p| peripheral(p, interface.iface_type)).unwrap_or_default(),
+            to_board: to_board.get_name().to_string(),
+            to_pin: to_pin.clone(),
+        }
+    }).collect()
+}
+
+const HEADINGS: [&str; 8] = ["connection", "interface", "direction", "board", "pin", "function", "to board", "to pin"];
+
+impl PinAssignment {
+    fn columns(&self) -> [&str; 8] {
+        [&self.connection, &self.interface, &self.direction, &self.board, &self.pin, &self.function, &self.to_board, &self.to_pin]
+    }
+}
+
+/// The ways pin assignments are exported.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Sequence)]
+pub enum PinFormat {
+    Csv,
+    Markdown,
+    /// Doc comments in a Rust module, for `mod pins;`
+    RustDoc,
+    /// A Rust module with a constant for each pin, for `mod pin_map;`
+    Rust,
+}
+
+/// How the pins are written in the Rust module.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum PinCode {
+    /// Each pin is its number
+    #[default]
+    Numeric,
+    /// Each pin is a type of its own, wrapping its number as a const generic, so passing
+    /// one where another is expected doesn't compile
+    Typed,
+}
+
+impl fmt::Display for PinCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinCode::Numeric => write!(f, "pin numbers"),
+            PinCode::Typed => write!(f, "typed pins"),
+        }
+    }
+}
+
+impl fmt::Display for PinFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinFormat::Csv => write!(f, "CSV"),
+            PinFormat::Markdown => write!(f, "Markdown"),
+            PinFormat::RustDoc => write!(f, "Rust doc comments"),
+            PinFormat::Rust => write!(f, "Rust constants"),
+        }
+    }
+}
+
+impl PinFormat {
+
+    /// Where the export is kept, in the project directory.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            PinFormat::Csv => "pins.csv",
+            PinFormat::Markdown => "pins.md",
+            PinFormat::RustDoc => "src/pins.rs",
+            PinFormat::Rust => "src/pin_map.rs",
+        }
+    }
+
+    /// The export of the assignments, with the pins of a Rust module written as `code`.
+    pub fn export(&self, assignments: &[PinAssignment], code: PinCode) -> String {
+        match self {
+            PinFormat::Csv => to_csv(assignments),
+            PinFormat::Markdown => to_markdown(assignments),
+            PinFormat::RustDoc => to_rust_doc(assignments),
+            PinFormat::Rust => to_rust(assignments, code),
+        }
+    }
+}
+
+pub fn to_csv(assignments: &[PinAssignment]) -> String {
+    let quote = |s: &str| if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() };
+    let mut csv = HEADINGS.join(",").replace(' ', "_") + "\n";
+    for a in assignments {
+        csv += &a.columns().iter().map(|c| quote(c)).collect::<Vec<_>>().join(",");
+        csv.push('\n');
+    }
+    csv
+}
+
+/// A table with its columns lined up, each line starting with `prefix`.
+fn aligned_table(assignments: &[PinAssignment], prefix: &str) -> String {
+    let rows: Vec<[&str; 8]> = std::iter::once(HEADINGS).chain(assignments.iter().map(PinAssignment::columns)).collect();
+    let widths: Vec<usize> = (0..HEADINGS.len()).map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0)).collect();
+    let line = |cells: Vec<String>| format!("{}| {} |\n", prefix, cells.join(" | "));
+    let mut table = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        table += &line(row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell.replace('|', "\\|"), width = width)).collect());
+        if i == 0 {
+            table += &line(widths.iter().map(|width| "-".repeat(*width)).collect());
+        }
+    }
+    table
+}
+
+pub fn to_markdown(assignments: &[PinAssignment]) -> String {
+    format!("# Pin assignments\n\n{}", aligned_table(assignments, ""))
+}
+
+pub fn to_rust_doc(assignments: &[PinAssignment]) -> String {
+    format!("//! Pin assignments, written by Iron Coder from the project's connections.\n//!\n{}", aligned_table(assignments, "//! "))
+}
+
+/// The number of a pin from what the board says it is, counting the pins of each port
+/// on from the last as the nRF chips do, i.e. "GPIO13" is 13 and "P1.02" is 34.
+pub fn pin_number(function: &str) -> Option<u8> {
+    let (port, number) = starter::function_number(function)?;
+    port.checked_mul(32)?.checked_add(number)
+}
+
+/// A connection's name as the words of an identifier, i.e. "status LED" is ["status", "led"].
+fn words(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase).collect()
+}
+
+/// The name of the constant for a connection, i.e. `STATUS_LED`.
+fn constant_name(name: &str) -> String {
+    let name = words(name).join("_").to_uppercase();
+    match name.chars().next() {
+        None => "PIN".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("PIN_{}", name),
+        Some(_) => name,
+    }
+}
+
+/// The name of the type for a connection, i.e. `StatusLed`.
+fn type_name(name: &str) -> String {
+    let name: String = words(name).iter().map(|w| w[..1].to_uppercase() + &w[1..]).collect();
+    match name.chars().next() {
+        None => "Pin".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("Pin{}", name),
+        Some(_) if name == "Pin" || name == "Self" => format!("{}Pin", name),
+        Some(_) => name,
+    }
+}
+
+/// The name of the trait the pins of an interface have, i.e. `I2cPin`.
+fn trait_name(interface: &str) -> String {
+    let interface = interface.to_lowercase();
+    interface[..1].to_uppercase() + &interface[1..] + "Pin"
+}
+
+/// The doc comment of a pin's constant, with where the pin is on the hardware, so it's
+/// there when the constant is hovered in the editor.
+fn doc_comment(a: &PinAssignment) -> String {
+    let mut doc = format!("\n/// {}: {} {}\n///\n/// - silkscreen: {} on the {}\n", a.connection, a.interface, a.direction.to_lowercase(), a.pin, a.board);
+    if !a.connector.is_empty() {
+        doc += &format!("/// - connector: {}\n", a.connector);
+    }
+    if !a.peripheral.is_empty() {
+        doc += &format!("/// - peripheral: {}\n", a.peripheral);
+    }
+    doc + &format!("/// - goes to: {} on the {}\n", a.to_pin, a.to_board)
+}
+
+/// A Rust module with a constant for each pin. Connections whose pins have no number
+/// are left as comments, and two connections with the same name get a number after it.
+pub fn to_rust(assignments: &[PinAssignment], code: PinCode) -> String {
+    let mut module = String::from("//! Pin assignments, written by Iron Coder from the project's connections.\n");
+    if code == PinCode::Typed {
+        module += "//! Each pin is a type of its own, so passing one where another is expected doesn't\n//! compile.\n";
+    }
+    module += "\n#![allow(dead_code)]\n";
+    let numbered: Vec<(&PinAssignment, Option<u8>)> = assignments.iter().map(|a| (a, pin_number(&a.function))).collect();
+    if code == PinCode::Typed {
+        module += "
+/// A pin, by its number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pin<const N: u8>;
+
+impl<const N: u8> Pin<N> {
+    pub const NUMBER: u8 = N;
+}
+";
+        let mut interfaces: Vec<&str> = numbered.iter().filter(|(_, n)| n.is_some()).map(|(a, _)| a.interface.as_str())
+            .filter(|i| !i.is_empty() && *i != "NONE").collect();
+        interfaces.sort();
+        interfaces.dedup();
+        for interface in interfaces {
+            module += &format!("\n/// A pin used for {}.\npub trait {} {{\n    const NUMBER: u8;\n}}\n", interface, trait_name(interface));
+        }
+    }
+    let mut used: Vec<String> = vec![];
+    for (a, number) in numbered {
+        let Some(number) = number else {
+            module += &format!("\n// {}: {} of {} has no pin number in the board file\n", a.connection, a.pin, a.board);
+            continue;
+        };
+        // the same name twice gets a number after it
+        let base = constant_name(&a.connection);
+        let mut constant = base.clone();
+        let mut n = 2;
+        while used.contains(&constant) {
+            constant = format!("{}_{}", base, n);
+            n += 1;
+        }
+        used.push(constant.clone());
+        module += &doc_comment(a);
+        match code {
+            PinCode::Numeric => module += &format!("pub const {}: u8 = {};\n", constant, number),
+            PinCode::Typed => {
+                let ty = type_name(&constant);
+                module += &format!("#[derive(Clone, Copy, Debug, PartialEq, Eq)]\npub struct {}(pub Pin<{}>);\n", ty, number);
+                if !a.interface.is_empty() && a.interface != "NONE" {
+                    module += &format!("\nimpl {} for {} {{\n    const NUMBER: u8 = {};\n}}\n", trait_name(&a.interface), ty, number);
+                }
+                module += &format!("\npub const {}: {} = {}(Pin);\n", constant, ty, ty);
+            },
+        }
+    }
+    module
+}
+
+/// The exports a project keeps up to date, i.e.
+/// ```toml
+/// [pin_exports]
+/// keep = ["Csv", "Rust"]
+/// code = "typed"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct PinExports {
+    pub keep: Vec<PinFormat>,
+    /// How the pins are written in the Rust module
+    pub code: PinCode,
+    /// What was last written of each, so files are only written when they change
+    #[serde(skip)]
+    written: BTreeMap<PinFormat, String>,
+}
+
+impl PinExports {
+
+    pub fn keeps(&self, format: PinFormat) -> bool {
+        self.keep.contains(&format)
+    }
+
+    pub fn set_keep(&mut self, format: PinFormat, keep: bool) {
+        self.keep.retain(|f| *f != format);
+        if keep {
+            self.keep.push(format);
+            self.keep.sort();
+        }
+    }
+
+    /// Rewrite the kept exports whose assignments have changed. Returns the files written.
+    pub fn sync(&mut self, system: &System, project_dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let assignments = assignments(system);
+        let mut written = vec![];
+        for format in self.keep.iter() {
+            let contents = format.export(&assignments, self.code);
+            let path = project_dir.join(format.file_name());
+            if self.written.get(format) == Some(&contents) && path.exists() {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, &contents)?;
+            self.written.insert(*format, contents);
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// The state of the pin assignments window.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct PinsPanel {
+    format: PinFormat,
+}
+
+impl Default for PinsPanel {
+    fn default() -> Self {
+        Self { format: PinFormat::Csv }
+    }
+}
+
+impl PinsPanel {
+
+    /// Show the project's pin assignments, and export them.
+    pub fn display(&mut self, ui: &mut egui::Ui, system: &System, exports: &mut PinExports, project_dir: Option<&Path>) {
+        let assignments = assignments(system);
+        if assignments.is_empty() {
+            ui.label("connect the pins of the project's boards to assign them");
+        } else {
+            egui::ScrollArea::vertical().id_source("pin_assignments").max_height(240.0).show(ui, |ui| {
+                egui::Grid::new("pin_assignments_grid").num_columns(HEADINGS.len()).striped(true).show(ui, |ui| {
+                    for heading in HEADINGS {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for assignment in assignments.iter() {
+                        for column in assignment.columns() {
+                            ui.label(column);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("pin_format").selected_text(self.format.to_string()).show_ui(ui, |ui| {
+                for format in enum_iterator::all::<PinFormat>() {
+                    ui.selectable_value(&mut self.format, format, format.to_string());
+                }
+            });
+            if self.format == PinFormat::Rust {
+                egui::ComboBox::from_id_source("pin_code").selected_text(exports.code.to_string()).show_ui(ui, |ui| {
+                    for code in enum_iterator::all::<PinCode>() {
+                        ui.selectable_value(&mut exports.code, code, code.to_string());
+                    }
+                }).response.on_hover_text("typed pins make passing the wrong pin a compile error");
+            }
+        });
+        let mut export = self.format.export(&assignments, exports.code);
+        ui.horizontal(|ui| {
+            if ui.button("copy").clicked() {
+                ui.output_mut(|o| o.copied_text = export.clone());
+            }
+            if ui.button("save as...").clicked() {
+                let file_name = Path::new(self.format.file_name()).file_name().unwrap_or_default().to_string_lossy().to_string();
+                if let Some(path) = rfd::FileDialog::new().set_file_name(file_name).save_file() {
+                    if let Err(e) = fs::write(&path, &export) {
+                        warn!("couldn't write the pin assignments to {}: {:?}", path.display(), e);
+                    }
+                }
+            }
+            // only a saved project has somewhere to keep them
+            if project_dir.is_some() {
+                let mut keep = exports.keeps(self.format);
+                if ui.checkbox(&mut keep, format!("keep {} up to date", self.format.file_name())).changed() {
+                    exports.set_keep(self.format, keep);
+                }
+            }
+        });
+        egui::ScrollArea::vertical().id_source("pin_export_preview").max_height(200.0).show(ui, |ui| {
+            ui.add(egui::TextEdit::multiline(&mut export).code_editor().interactive(false).desired_width(f32::INFINITY));
+        });
+    }
+}
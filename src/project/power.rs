@@ -0,0 +1,375 @@
+//! Title: Iron Coder Project Module - Low-Power Plan
+//! Description: A plan of how the firmware moves between running and the main board's
+//!   sleep modes, repeated round and round. From the chip's sleep modes the planner
+//!   works out the average current and battery life of the plan, and writes the code that
+//!   enters and leaves each sleep, with the wake source that ends it.
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::toolbox::format_si;
+use crate::board::Board;
+use crate::board::sleep::{SleepHal, SleepKind, SleepMode, SleepModes, WakeSource};
+
+/// One step of a plan: running, or asleep until something wakes the chip.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Phase {
+    /// The sleep mode, or none for running
+    pub mode: Option<String>,
+    /// How long the phase lasts. For a sleep that a pin or a peripheral ends, this is
+    /// how long it's expected to last.
+    pub duration_ms: f64,
+    /// What ends a sleep
+    pub wake: Option<WakeSource>,
+}
+
+/// The steps the firmware goes round, i.e. run for 10 ms and then sleep for a second.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PowerPlan {
+    pub phases: Vec<Phase>,
+    /// The battery's capacity in mAh, for the battery life
+    pub battery_capacity: f64,
+}
+
+impl Default for PowerPlan {
+    fn default() -> Self {
+        Self {
+            phases: vec![Phase { mode: None, duration_ms: 10.0, wake: None }],
+            battery_capacity: 500.0,
+        }
+    }
+}
+
+/// What a plan draws on average.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    /// The time round the plan, in seconds
+    pub period: f64,
+    /// The average current, in amps
+    pub average_current: f64,
+    /// The share of the time spent running
+    pub duty_cycle: f64,
+    /// The share of the charge each phase uses
+    pub shares: Vec<f64>,
+    /// How long the battery lasts, in hours
+    pub battery_life: Option<f64>,
+}
+
+/// The code that makes a timed source wake the chip `ms` milliseconds from now.
+fn timed_wake(hal: SleepHal, source: WakeSource, ms: f64) -> Option<String> {
+    let code = match (hal, source) {
+        (SleepHal::Rp2040Hal, WakeSource::Timer) => format!(
+"alarm.schedule(fugit::MicrosDurationU32::millis({})).unwrap();
+alarm.enable_interrupt();
+", ms.round()),
+        (SleepHal::Rp2040Hal, WakeSource::Rtc) => format!(
+"// an RTC alarm {} ms from now; when dormant the RTC needs its clock from a pin
+rtc.schedule_alarm(alarm_time);
+rtc.enable_interrupt();
+", ms.round()),
+        // the RTC counts the 32.768 kHz clock
+        (SleepHal::NrfHal, WakeSource::Rtc) => format!(
+"rtc.set_compare(hal::rtc::RtcCompareReg::Compare0, {}).unwrap();
+rtc.enable_interrupt(hal::rtc::RtcInterrupt::Compare0, Some(&mut core.NVIC));
+rtc.clear_counter();
+rtc.enable_counter();
+", (ms * 32.768).round()),
+        // the timer counts microseconds
+        (SleepHal::NrfHal, WakeSource::Timer) => format!(
+"timer.enable_interrupt();
+timer.start({}u32);
+", (ms * 1e3).round()),
+        (SleepHal::EspHal, WakeSource::Timer) => format!(
+"let wake = TimerWakeupSource::new(core::time::Duration::from_millis({}));
+", ms.round()),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// The code that sets up `source` to wake the chip from `mode`.
+fn wake_code(hal: SleepHal, mode: &SleepMode, source: WakeSource, ms: f64) -> String {
+    if let Some(code) = timed_wake(hal, source, ms) {
+        return code;
+    }
+    match (hal, source) {
+        (SleepHal::Rp2040Hal, WakeSource::Gpio) if mode.kind == SleepKind::Dormant =>
+            "pin.set_dormant_wake_enabled(hal::gpio::Interrupt::EdgeLow, true);\n".to_string(),
+        (SleepHal::NrfHal, WakeSource::Gpio) if mode.kind == SleepKind::Off =>
+            "p.P0.pin_cnf[PIN].modify(|_, w| w.sense().low());\n".to_string(),
+        (SleepHal::EspHal, WakeSource::Gpio) if mode.kind == SleepKind::Off =>
+            "let wake = Ext0WakeupSource::new(&mut pin, WakeupLevel::Low);\n".to_string(),
+        (SleepHal::EspHal, WakeSource::Gpio) =>
+            "pin.wakeup_enable(true, WakeEvent::LowLevel);\nlet wake = GpioWakeupSource::new();\n".to_string(),
+        (SleepHal::EspHal, _) => format!("// set up the {} to wake the chip as `wake`\n", source),
+        _ => format!("// enable the {} interrupt, which wakes the chip\n", source),
+    }
+}
+
+/// The code that puts the chip into `mode` until `wake` wakes it, which for a timed
+/// source is `ms` milliseconds later. None when the HAL has no way into the mode.
+pub fn enter_code(hal: SleepHal, mode: &SleepMode, wake: WakeSource, ms: f64) -> Option<String> {
+    let sleep = match (hal, mode.kind) {
+        (SleepHal::Rp2040Hal | SleepHal::NrfHal, SleepKind::Wait) => "cortex_m::asm::wfi();\n".to_string(),
+        (SleepHal::Rp2040Hal, SleepKind::DeepWait) => {
+            // the clocks left on in sleep are the ones the wake source runs from
+            let (en0, en1) = match wake {
+                WakeSource::Gpio => (".clk_sys_io().set_bit()", ""),
+                WakeSource::Rtc => (".clk_rtc_rtc().set_bit().clk_sys_rtc().set_bit()", ""),
+                WakeSource::Timer => ("", ".clk_sys_timer().set_bit()"),
+                WakeSource::Uart => ("", ".clk_sys_uart0().set_bit().clk_peri_uart0().set_bit()"),
+                WakeSource::Usb => ("", ".clk_sys_usbctrl().set_bit().clk_usb_usbctrl().set_bit()"),
+                _ => return None,
+            };
+            format!(
+"pac.CLOCKS.sleep_en0().write(|w| unsafe {{ w.bits(0) }}{});
+pac.CLOCKS.sleep_en1().write(|w| unsafe {{ w.bits(0) }}{});
+core.SCB.set_sleepdeep();
+cortex_m::asm::wfi();
+", en0, en1)
+        },
+        (SleepHal::Rp2040Hal, SleepKind::Dormant) => "let xosc = unsafe { xosc.dormant() };\n".to_string(),
+        (SleepHal::NrfHal, SleepKind::Off) =>
+            "p.POWER.systemoff.write(|w| w.systemoff().enter());\nloop {\n    cortex_m::asm::wfe();\n}\n".to_string(),
+        (SleepHal::EspHal, SleepKind::Light) => "rtc.sleep_light(&[&wake]);\n".to_string(),
+        (SleepHal::EspHal, SleepKind::Off) => "rtc.sleep_deep(&[&wake]);\n".to_string(),
+        _ => return None,
+    };
+    Some(wake_code(hal, mode, wake, ms) + &sleep)
+}
+
+/// The code that puts the chip back as it was after waking from `mode`.
+pub fn exit_code(hal: SleepHal, mode: &SleepMode) -> String {
+    match (hal, mode.kind) {
+        (_, SleepKind::Off) => "// waking up is a reset, so the firmware starts again from main\n".to_string(),
+        (SleepHal::Rp2040Hal, SleepKind::DeepWait) =>
+"core.SCB.clear_sleepdeep();
+pac.CLOCKS.sleep_en0().write(|w| unsafe { w.bits(0xffff_ffff) });
+pac.CLOCKS.sleep_en1().write(|w| unsafe { w.bits(0xffff_ffff) });
+".to_string(),
+        (SleepHal::Rp2040Hal, SleepKind::Dormant) =>
+"// the crystal restarts on waking; wait for it before using the clocks
+while pac.XOSC.status().read().stable().bit_is_clear() {}
+".to_string(),
+        _ => String::new(),
+    }
+}
+
+impl PowerPlan {
+
+    /// Each phase with its sleep mode, checking that the plan can run on the chip.
+    fn modes<'a>(&self, sleep: &'a SleepModes) -> Result<Vec<Option<&'a SleepMode>>, String> {
+        if self.phases.is_empty() {
+            return Err("the plan has no phases".to_string());
+        }
+        let mut modes = Vec::new();
+        for (i, phase) in self.phases.iter().enumerate() {
+            if !(phase.duration_ms.is_finite() && phase.duration_ms > 0.0) {
+                return Err(format!("phase {} needs a positive duration", i + 1));
+            }
+            let Some(name) = &phase.mode else {
+                modes.push(None);
+                continue;
+            };
+            let mode = sleep.mode(name).ok_or(format!("the chip has no sleep mode called {}", name))?;
+            match phase.wake {
+                None => return Err(format!("nothing wakes the chip from {} in phase {}", name, i + 1)),
+                Some(w) if !mode.wake_sources.contains(&w) => return Err(format!("the {} can't wake the chip from {}", w, name)),
+                _ => (),
+            }
+            // the next phase after waking through reset is the first
+            if mode.kind.wakes_through_reset() && i + 1 < self.phases.len() {
+                return Err(format!("waking from {} is a reset, so it has to be the last phase", name));
+            }
+            modes.push(Some(mode));
+        }
+        Ok(modes)
+    }
+
+    /// The average current of going round the plan on a chip with `sleep`'s modes.
+    pub fn estimate(&self, sleep: &SleepModes) -> Result<Estimate, String> {
+        let modes = self.modes(sleep)?;
+        let period = self.phases.iter().map(|p| p.duration_ms * 1e-3).sum::<f64>();
+        let charges: Vec<f64> = self.phases.iter().zip(&modes)
+            .map(|(p, m)| m.map_or(sleep.active_current, |m| m.current).amps() * p.duration_ms * 1e-3)
+            .collect();
+        let charge = charges.iter().sum::<f64>();
+        let running = self.phases.iter().filter(|p| p.mode.is_none()).map(|p| p.duration_ms * 1e-3).sum::<f64>();
+        let average_current = charge / period;
+        let battery_life = (self.battery_capacity > 0.0 && average_current > 0.0)
+            .then(|| self.battery_capacity * 1e-3 / average_current);
+        Ok(Estimate {
+            period,
+            average_current,
+            duty_cycle: running / period,
+            shares: charges.iter().map(|c| c / charge).collect(),
+            battery_life,
+        })
+    }
+
+    /// The firmware's main loop for the plan, with placeholders for the work.
+    pub fn generate_code(&self, hal: SleepHal, sleep: &SleepModes) -> Result<String, String> {
+        let modes = self.modes(sleep)?;
+        let mut body = String::new();
+        for (phase, mode) in self.phases.iter().zip(modes) {
+            let duration = format_si(phase.duration_ms * 1e-3, "s");
+            let (Some(mode), Some(wake)) = (mode, phase.wake) else {
+                body += &format!("// run for {}\n\n", duration);
+                continue;
+            };
+            let enter = enter_code(hal, mode, wake, phase.duration_ms).ok_or(format!("{} has no way into {}", hal, mode.name))?;
+            body += &format!("// {} for {}, until the {} wakes the chip\n{}{}\n", mode.name, duration, wake, enter, exit_code(hal, mode));
+        }
+        let body = body.trim_end().to_string() + "\n";
+        if self.phases.last().and_then(|p| p.mode.as_ref()).and_then(|m| sleep.mode(m)).is_some_and(|m| m.kind.wakes_through_reset()) {
+            return Ok(body);
+        }
+        let indented: String = body.lines().map(|l| if l.is_empty() { "\n".to_string() } else { format!("    {}\n", l) }).collect();
+        Ok(format!("loop {{\n{}}}\n", indented))
+    }
+}
+
+/// The state of the low-power planner window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PowerPanel {
+    /// The HAL to write code for, if not the board's
+    hal: Option<SleepHal>,
+}
+
+impl PowerPanel {
+
+    fn display_modes(&self, ui: &mut egui::Ui, sleep: &SleepModes) {
+        egui::Grid::new("power_modes").num_columns(4).striped(true).show(ui, |ui| {
+            for heading in ["mode", "current", "wakes on", "keeps"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+            ui.label("running");
+            ui.label(format_si(sleep.active_current.amps(), "A"));
+            ui.end_row();
+            for mode in &sleep.modes {
+                ui.label(&mode.name);
+                ui.label(format_si(mode.current.amps(), "A"));
+                ui.label(mode.wake_sources.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(", "));
+                let keeps = if mode.retains.is_empty() { "nothing".to_string() } else { mode.retains.join(", ") };
+                let through_reset = if mode.kind.wakes_through_reset() { " (wakes through reset)" } else { "" };
+                ui.label(keeps + through_reset);
+                ui.end_row();
+            }
+        });
+    }
+
+    fn display_phases(&self, ui: &mut egui::Ui, plan: &mut PowerPlan, sleep: &SleepModes) {
+        let mut remove = None;
+        egui::Grid::new("power_phases").num_columns(4).show(ui, |ui| {
+            for (i, phase) in plan.phases.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("power_phase_mode", i))
+                .selected_text(phase.mode.clone().unwrap_or("running".to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut phase.mode, None, "running");
+                    for mode in &sleep.modes {
+                        ui.selectable_value(&mut phase.mode, Some(mode.name.clone()), &mode.name);
+                    }
+                });
+                ui.add(egui::DragValue::new(&mut phase.duration_ms).speed(1.0).clamp_range(0.001..=f64::MAX).suffix(" ms"));
+                match phase.mode.as_ref().and_then(|m| sleep.mode(m)) {
+                    Some(mode) => {
+                        if phase.wake.is_none_or(|w| !mode.wake_sources.contains(&w)) {
+                            phase.wake = mode.wake_sources.first().copied();
+                        }
+                        egui::ComboBox::from_id_source(("power_phase_wake", i))
+                        .selected_text(phase.wake.map(|w| format!("until the {}", w)).unwrap_or_default())
+                        .show_ui(ui, |ui| {
+                            for w in &mode.wake_sources {
+                                ui.selectable_value(&mut phase.wake, Some(*w), format!("until the {}", w));
+                            }
+                        });
+                    },
+                    None => {
+                        phase.wake = None;
+                        ui.label("");
+                    },
+                }
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            plan.phases.remove(i);
+        }
+        if ui.button("add phase").clicked() {
+            let mode = sleep.modes.first();
+            plan.phases.push(Phase {
+                mode: mode.map(|m| m.name.clone()),
+                duration_ms: 1000.0,
+                wake: mode.and_then(|m| m.wake_sources.first().copied()),
+            });
+        }
+    }
+
+    /// Show the main board's sleep modes and edit the project's plan for them.
+    pub fn display(&mut self, ui: &mut egui::Ui, plan: &mut PowerPlan, board: Option<&Board>) {
+        let Some(sleep) = board.and_then(Board::sleep_modes) else {
+            ui.label("the main board doesn't describe its sleep modes");
+            return;
+        };
+        self.display_modes(ui, sleep);
+        ui.separator();
+        self.display_phases(ui, plan, sleep);
+        ui.separator();
+        let estimate = match plan.estimate(sleep) {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                ui.colored_label(ui.style().visuals.error_fg_color, e);
+                return;
+            },
+        };
+        egui::Grid::new("power_estimate").num_columns(2).show(ui, |ui| {
+            ui.label("average current:");
+            ui.strong(format_si(estimate.average_current, "A"));
+            ui.end_row();
+            ui.label("running:");
+            ui.strong(format!("{:.2}% of {}", estimate.duty_cycle * 100.0, format_si(estimate.period, "s")));
+            ui.end_row();
+            ui.label("charge by phase:");
+            ui.label(estimate.shares.iter().map(|s| format!("{:.1}%", s * 100.0)).collect::<Vec<_>>().join(", "));
+            ui.end_row();
+            ui.label("battery:");
+            ui.add(egui::DragValue::new(&mut plan.battery_capacity).clamp_range(0.0..=f64::MAX).suffix(" mAh"));
+            ui.end_row();
+            if let Some(hours) = estimate.battery_life {
+                ui.label("battery life:");
+                ui.strong(if hours < 48.0 { format!("{:.1} hours", hours) } else { format!("{:.1} days", hours / 24.0) });
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        // the board's HAL, unless the user picked another
+        let hal = self.hal.or(sleep.hal);
+        ui.horizontal(|ui| {
+            ui.label("code for:");
+            egui::ComboBox::from_id_source("power_hal")
+            .selected_text(hal.map(|h| h.to_string()).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for h in enum_iterator::all::<SleepHal>() {
+                    ui.selectable_value(&mut self.hal, Some(h), h.to_string());
+                }
+            });
+        });
+        let Some(hal) = hal else {
+            return;
+        };
+        match plan.generate_code(hal, sleep) {
+            Ok(mut code) => {
+                if ui.button("copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = code.clone());
+                }
+                ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+            },
+            Err(e) => { ui.colored_label(ui.style().visuals.error_fg_color, e); },
+        }
+    }
+}
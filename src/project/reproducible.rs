@@ -0,0 +1,275 @@
+//! Title: Iron Coder Project Module - Reproducible Builds
+//! Description: A build mode for Cargo projects that gives byte-identical firmware on any
+//!   machine: the toolchain is pinned in `rust-toolchain.toml`, dependencies come from
+//!   the lock file, and the paths of the project and Cargo home are remapped out of the
+//!   output. A build can be recorded, with the hashes of its inputs and outputs, and a
+//!   later build on another machine checked against the record.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use serde::{Serialize, Deserialize};
+
+use crate::project::local_sources;
+use crate::project::mcuboot;
+
+/// The file a recorded build is kept in, in the project directory.
+pub const RECORD_FILE: &str = "reproducible-build.toml";
+/// Where the paths in the output are remapped to.
+const PROJECT_PREFIX: &str = "/project";
+const CARGO_HOME_PREFIX: &str = "/cargo";
+/// Directories that aren't inputs of the build. Vendored crates are covered by the
+/// checksums in the lock file.
+const SKIPPED: [&str; 3] = ["target", "build", "vendor"];
+
+/// The reproducible build settings of a project.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ReproducibleSettings {
+    pub enabled: bool,
+    /// The toolchain to pin, i.e. "nightly-2024-05-01"
+    pub toolchain: String,
+}
+
+impl ReproducibleSettings {
+
+    pub fn validate(&self) -> Result<(), String> {
+        let toolchain = self.toolchain.trim();
+        if toolchain.is_empty() {
+            return Err("pick a toolchain to pin, i.e. nightly-2024-05-01".to_string());
+        }
+        // A bare channel moves on, so it wouldn't pin anything
+        if matches!(toolchain, "stable" | "beta" | "nightly") {
+            return Err(format!("{} changes over time; pin a version or a dated nightly", toolchain));
+        }
+        Ok(())
+    }
+
+    /// Pin the toolchain in the project's `rust-toolchain.toml`, keeping its components and
+    /// targets. Returns whether the file changed.
+    pub fn write_toolchain(&self, project_dir: &Path) -> Result<bool, String> {
+        self.validate()?;
+        let path = project_dir.join("rust-toolchain.toml");
+        let existing = fs::read_to_string(&path).unwrap_or_default();
+        let mut doc: toml_edit::DocumentMut = existing.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+        doc["toolchain"]["channel"] = toml_edit::value(self.toolchain.trim());
+        let updated = doc.to_string();
+        if updated == existing {
+            return Ok(false);
+        }
+        fs::write(&path, updated).map_err(|e| format!("couldn't write rust-toolchain.toml: {}", e))?;
+        Ok(true)
+    }
+
+    /// Show the settings in the Ui.
+    pub fn display_settings(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "reproducible builds");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("toolchain:");
+                ui.add(egui::TextEdit::singleline(&mut self.toolchain).hint_text("nightly-2024-05-01").desired_width(160.0));
+            });
+            if let Err(e) = self.validate() {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            }
+        });
+    }
+}
+
+/// The rustflags the project's `.cargo/config.toml` sets. Cargo doesn't combine these with
+/// RUSTFLAGS, so they're passed along with the remapping. Tables for `cfg(..)` targets
+/// are taken to match, since embedded projects only build for the one target.
+pub fn config_rustflags(config: &str) -> Vec<String> {
+    let Ok(config) = config.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let flags = |value: Option<&toml::Value>| -> Vec<String> {
+        match value {
+            Some(toml::Value::String(flags)) => flags.split_whitespace().map(str::to_string).collect(),
+            Some(toml::Value::Array(flags)) => flags.iter().filter_map(|f| f.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        }
+    };
+    let build = config.get("build").and_then(toml::Value::as_table);
+    let target = build.and_then(|b| b.get("target")).and_then(toml::Value::as_str);
+    let mut found = Vec::new();
+    if let Some(targets) = config.get("target").and_then(toml::Value::as_table) {
+        for (name, table) in targets {
+            if Some(name.as_str()) == target || name.starts_with("cfg(") {
+                found.extend(flags(table.get("rustflags")));
+            }
+        }
+    }
+    if found.is_empty() {
+        found = flags(build.and_then(|b| b.get("rustflags")));
+    }
+    found
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(|home| PathBuf::from(home).join(".cargo")))
+}
+
+/// The rustflags for a reproducible build of the project: its own, and the remapping of
+/// the paths that differ between machines.
+pub fn rustflags(project_dir: &Path) -> Vec<String> {
+    let config = fs::read_to_string(project_dir.join(".cargo").join("config.toml")).unwrap_or_default();
+    let mut flags = config_rustflags(&config);
+    let mut remap = |from: &Path, to: &str| flags.push(format!("--remap-path-prefix={}={}", from.display(), to));
+    remap(project_dir, PROJECT_PREFIX);
+    if let Some(cargo_home) = cargo_home() {
+        remap(&cargo_home, CARGO_HOME_PREFIX);
+    }
+    flags
+}
+
+/// The environment for a reproducible Cargo build of the project.
+pub fn environment(project_dir: &Path) -> Vec<(String, String)> {
+    vec![
+        ("CARGO_ENCODED_RUSTFLAGS".to_string(), rustflags(project_dir).join("\x1f")),
+        ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+    ]
+}
+
+fn sha256(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The project-relative path of a file, with `/` between the parts on every platform.
+fn relative(project_dir: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(project_dir).unwrap_or(path);
+    path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Hash the files the build reads: everything in the project but build output, git and
+/// hidden files (apart from `.cargo`), and the record itself.
+fn hash_inputs(project_dir: &Path, dir: &Path, hashes: &mut BTreeMap<String, String>) -> Result<(), String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir).map_err(|e| e.to_string())?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if (name.starts_with('.') && name != ".cargo") || (dir == project_dir && (SKIPPED.contains(&name.as_str()) || name == RECORD_FILE)) {
+            continue;
+        }
+        if path.is_dir() {
+            hash_inputs(project_dir, &path, hashes)?;
+        } else {
+            let data = fs::read(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+            hashes.insert(relative(project_dir, &path), sha256(&data));
+        }
+    }
+    Ok(())
+}
+
+/// A build of the project: the toolchain it was built with, and the hashes of what went in
+/// and came out.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct BuildRecord {
+    /// The output of `rustc -vV`
+    pub rustc: String,
+    pub inputs: BTreeMap<String, String>,
+    pub outputs: BTreeMap<String, String>,
+}
+
+impl BuildRecord {
+
+    /// Record the build that was just done in the project.
+    pub fn of_project(project_dir: &Path, rustc: &str) -> Result<Self, String> {
+        let mut inputs = BTreeMap::new();
+        hash_inputs(project_dir, project_dir, &mut inputs)?;
+        // path dependencies outside the project are inputs too, under their names
+        let manifest = fs::read_to_string(project_dir.join("Cargo.toml")).unwrap_or_default();
+        let project = project_dir.canonicalize().map_err(|e| e.to_string())?;
+        for (name, path) in local_sources::path_dependencies(&manifest).unwrap_or_default() {
+            let Ok(path) = project_dir.join(path).canonicalize() else { continue };
+            if path.starts_with(&project) {
+                continue;
+            }
+            let mut hashes = BTreeMap::new();
+            hash_inputs(&path, &path, &mut hashes)?;
+            inputs.extend(hashes.into_iter().map(|(file, hash)| (format!("{}:{}", name, file), hash)));
+        }
+        let firmware = mcuboot::find_firmware(project_dir).map_err(|e| e.to_string())?;
+        let data = fs::read(&firmware).map_err(|e| format!("couldn't read {}: {}", firmware.display(), e))?;
+        let outputs = BTreeMap::from([(relative(project_dir, &firmware), sha256(&data))]);
+        Ok(Self { rustc: rustc.trim().to_string(), inputs, outputs })
+    }
+
+    pub fn load(project_dir: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(project_dir.join(RECORD_FILE))
+            .map_err(|e| format!("couldn't read {}: {}", RECORD_FILE, e))?;
+        toml::from_str(&text).map_err(|e| format!("{} isn't a build record: {}", RECORD_FILE, e))
+    }
+
+    pub fn save(&self, project_dir: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(project_dir.join(RECORD_FILE), text).map_err(|e| format!("couldn't write {}: {}", RECORD_FILE, e))
+    }
+
+    /// How this build differs from the recorded one. Empty if it's the same.
+    pub fn differences(&self, recorded: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.rustc != recorded.rustc {
+            let version = |rustc: &str| rustc.lines().next().unwrap_or_default().to_string();
+            differences.push(format!("built with {}, but the record was built with {}", version(&self.rustc), version(&recorded.rustc)));
+        }
+        for (kind, now, then) in [("input", &self.inputs, &recorded.inputs), ("output", &self.outputs, &recorded.outputs)] {
+            for (path, hash) in then {
+                match now.get(path) {
+                    None => differences.push(format!("{} {} is missing", kind, path)),
+                    Some(h) if h != hash => differences.push(format!("{} {} is different", kind, path)),
+                    Some(_) => (),
+                }
+            }
+            for path in now.keys().filter(|p| !then.contains_key(*p)) {
+                differences.push(format!("{} {} isn't in the record", kind, path));
+            }
+        }
+        differences
+    }
+}
+
+/// What to do with a reproducible build once it's done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordAction {
+    /// Save it as the project's recorded build
+    Record,
+    /// Check it against the recorded build
+    Verify,
+}
+
+impl RecordAction {
+
+    /// Record the build, and save or check it, reporting in the terminal.
+    pub fn run(self, project_dir: &Path, tx: &Sender<String>) -> Result<(), String> {
+        let rustc = duct::cmd!("rustc", "-vV").dir(project_dir).read()
+            .map_err(|e| format!("couldn't run rustc: {}", e))?;
+        let record = BuildRecord::of_project(project_dir, &rustc)?;
+        match self {
+            RecordAction::Record => {
+                record.save(project_dir)?;
+                let _ = tx.send(format!("recorded the build in {}: {} inputs, {} outputs\n", RECORD_FILE, record.inputs.len(), record.outputs.len()));
+                Ok(())
+            },
+            RecordAction::Verify => {
+                let differences = record.differences(&BuildRecord::load(project_dir)?);
+                for difference in &differences {
+                    let _ = tx.send(format!("{}\n", difference));
+                }
+                if !differences.is_empty() {
+                    return Err(format!("the build doesn't match {}", RECORD_FILE));
+                }
+                let _ = tx.send("the build matches the recorded one byte for byte\n".to_string());
+                Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Title: Iron Coder Project Module - RTT
+//! Description: Attaching to the board with probe-rs to read what the firmware sends
+//!   over RTT. The CPU and heap usage windows share it, and each reads its own reports
+//!   out of the lines.
+
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+
+use serde::{Serialize, Deserialize};
+
+use crate::app::events::{self, Event};
+use crate::app::jobs::{self, JobId, JobState};
+use crate::board::Board;
+use crate::project::mcuboot;
+
+/// Attach to the board with probe-rs in a job called `name`, giving the lines of what the
+/// firmware sends over RTT.
+pub fn attach(ctx: &egui::Context, name: &str, chip: &str, project_dir: &Path) -> Result<(JobId, Receiver<String>), String> {
+    let firmware = mcuboot::find_firmware(project_dir).map_err(|e| e.to_string())?;
+    let cmd = duct::cmd!("probe-rs", "attach", "--chip", chip, firmware);
+    let (tx, rx) = channel();
+    let job = jobs::shared(ctx).spawn(name, Some(ctx), move |job| {
+        jobs::run_commands(&[cmd], &tx, job)
+    });
+    Ok((job, rx))
+}
+
+/// The chip to attach to and the probe-rs job reading from it, for a window that shows
+/// what the firmware reports.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RttAttachment {
+    /// The probe-rs chip name, if not the board's
+    chip: String,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    lines: Option<Receiver<String>>,
+    #[serde(skip)]
+    error: Option<String>,
+}
+
+impl RttAttachment {
+
+    pub fn is_running(&self, ctx: &egui::Context) -> bool {
+        self.job.is_some_and(|id| jobs::shared(ctx).state(id) == Some(JobState::Running))
+    }
+
+    /// Take the lines that came in since the last frame. Each is also published, so the
+    /// console sees it.
+    pub fn poll(&mut self, ctx: &egui::Context) -> Vec<String> {
+        let Some(rx) = &self.lines else {
+            return vec![];
+        };
+        let lines: Vec<String> = rx.try_iter().collect();
+        let bus = events::shared(ctx);
+        for line in &lines {
+            bus.publish(Event::RttData(line.clone()));
+        }
+        lines
+    }
+
+    /// Show the chip and the attach or detach button, and why attaching failed. `board`
+    /// is the main board, which names the chip if it isn't set. Returns whether a new
+    /// job was attached, so the window can drop the reports it had.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, name: &str, board: Option<&Board>, project_dir: Option<&Path>) -> bool {
+        let board_chip = board.and_then(Board::chip_name).unwrap_or_default().to_string();
+        let running = self.is_running(ctx);
+        let mut attached = false;
+        ui.horizontal(|ui| {
+            ui.label("chip:");
+            ui.add(egui::TextEdit::singleline(&mut self.chip).hint_text(&board_chip).desired_width(140.0));
+            let chip = if self.chip.trim().is_empty() { board_chip.clone() } else { self.chip.trim().to_string() };
+            if running {
+                if ui.button("detach").clicked() {
+                    jobs::shared(ctx).cancel(self.job.unwrap());
+                }
+            } else if ui.add_enabled(!chip.is_empty() && project_dir.is_some(), egui::Button::new("attach")).clicked() {
+                match attach(ctx, name, &chip, project_dir.unwrap()) {
+                    Ok((job, lines)) => {
+                        self.job = Some(job);
+                        self.lines = Some(lines);
+                        self.error = None;
+                        attached = true;
+                    },
+                    Err(e) => self.error = Some(e),
+                }
+            }
+        });
+        if let Some(e) = &self.error {
+            ui.colored_label(ui.style().visuals.error_fg_color, e);
+        }
+        attached
+    }
+}
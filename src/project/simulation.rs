@@ -0,0 +1,516 @@
+//! Title: Iron Coder Project Module - Simulation
+//! Description: Generates a simulation of the main board for host tests. Each pin and
+//!   bus in the board's pinout becomes a field of a simulated `Board` that implements
+//!   the embedded-hal 1.0 traits, so code written against those traits can be run with
+//!   `cargo test` on the host instead of the chip's HAL. Tests drive the simulation
+//!   through a shared handle: they set and script inputs, put register devices on I2C
+//!   buses, queue SPI and UART responses, and check what the code did.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::board::Board;
+use crate::board::pinout::InterfaceType;
+
+/// The name of the generated module, in the project's source directory.
+pub const MODULE_FILE_NAME: &str = "sim.rs";
+/// The ADC resolution assumed for boards that don't give one.
+const DEFAULT_ADC_BITS: u32 = 12;
+
+/// A field of the simulated board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimField {
+    /// The field's name, which tests also use to refer to it
+    pub name: String,
+    /// The simulated type, i.e. `SimPin`
+    pub kind: &'static str,
+    /// The board pins it stands for
+    pub pins: Vec<String>,
+}
+
+/// Turn a pin name into a field name, i.e. "D13" into `d13` and "8" into `pin_8`.
+fn field_name(pin: &str) -> String {
+    let mut name: String = pin.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        name = format!("pin_{}", name);
+    }
+    if syn::parse_str::<syn::Ident>(&name).is_err() {
+        name += "_pin";
+    }
+    name
+}
+
+/// The fields of the simulated board, in pinout order. A pin gets one field even when
+/// it's in several mappings; buses are named by their kind, numbered from the second.
+pub fn fields(board: &Board) -> Vec<SimField> {
+    let mut fields: Vec<SimField> = vec![];
+    let mut buses = [(InterfaceType::I2C, "i2c", "SimI2c", 0), (InterfaceType::SPI, "spi", "SimSpi", 0), (InterfaceType::UART, "uart", "SimUart", 0usize)];
+    for mapping in board.get_pinout().iter() {
+        let iface_type = mapping.interface.iface_type;
+        if let Some((_, prefix, kind, count)) = buses.iter_mut().find(|(t, ..)| *t == iface_type) {
+            let name = match *count { 0 => prefix.to_string(), n => format!("{}{}", prefix, n) };
+            *count += 1;
+            fields.push(SimField { name, kind, pins: mapping.pins.clone() });
+            continue;
+        }
+        let (suffix, kind) = match iface_type {
+            InterfaceType::PWM => ("_pwm", "SimPwm"),
+            _ => ("", "SimPin"),
+        };
+        for pin in mapping.pins.iter() {
+            let name = format!("{}{}", field_name(pin), suffix);
+            if !fields.iter().any(|f| f.name == name) {
+                fields.push(SimField { name, kind, pins: vec![pin.clone()] });
+            }
+        }
+    }
+    fields
+}
+
+/// The shared state, pins, PWM channels and I2C/SPI buses, which don't depend on the
+/// board beyond its ADC.
+fn runtime(adc_max: u32, adc_reference: f64) -> TokenStream {
+    let adc_max = Literal::u32_unsuffixed(adc_max);
+    let adc_reference = Literal::f64_unsuffixed(adc_reference);
+    quote! {
+        /// The largest reading of the board's ADC
+        pub const ADC_MAX: u32 = #adc_max;
+        /// The voltage of a full-scale ADC reading
+        pub const ADC_REFERENCE: f64 = #adc_reference;
+        /// The largest duty cycle of a simulated PWM channel
+        pub const PWM_MAX: u16 = u16::MAX;
+
+        /// An error on a simulated I2C bus
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum SimError {
+            /// No device answered at the address
+            NoAcknowledge(u8),
+        }
+
+        impl i2c::Error for SimError {
+            fn kind(&self) -> i2c::ErrorKind {
+                i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Address)
+            }
+        }
+
+        /// One I2C transaction, as the simulated bus saw it
+        #[derive(Clone, Debug, Default, PartialEq, Eq)]
+        pub struct I2cTransfer {
+            pub address: u8,
+            pub written: Vec<u8>,
+            pub read: Vec<u8>,
+        }
+
+        /// A device with 256 byte-wide registers. The first byte of a write sets the
+        /// register pointer and the rest are stored from there; reads continue from the
+        /// pointer.
+        #[derive(Clone)]
+        struct RegisterDevice {
+            registers: [u8; 256],
+            pointer: u8,
+        }
+
+        #[derive(Default)]
+        struct State {
+            levels: BTreeMap<&'static str, bool>,
+            scripts: BTreeMap<&'static str, VecDeque<bool>>,
+            history: BTreeMap<&'static str, Vec<bool>>,
+            wires: Vec<(&'static str, &'static str)>,
+            analog: BTreeMap<&'static str, u32>,
+            duty: BTreeMap<&'static str, u16>,
+            i2c_devices: BTreeMap<(&'static str, u8), RegisterDevice>,
+            i2c_log: BTreeMap<&'static str, Vec<I2cTransfer>>,
+            responses: BTreeMap<&'static str, VecDeque<u8>>,
+            written: BTreeMap<&'static str, Vec<u8>>,
+        }
+
+        impl State {
+            fn drive(&mut self, pin: &'static str, high: bool) {
+                self.levels.insert(pin, high);
+                self.history.entry(pin).or_default().push(high);
+                let wired: Vec<&'static str> = self.wires.iter().filter(|(from, _)| *from == pin).map(|(_, to)| *to).collect();
+                for to in wired {
+                    self.levels.insert(to, high);
+                }
+            }
+
+            /// Clock a byte out on a bus, and the next queued response in.
+            fn clock(&mut self, bus: &'static str, out: u8) -> u8 {
+                self.written.entry(bus).or_default().push(out);
+                self.responses.get_mut(bus).and_then(VecDeque::pop_front).unwrap_or(0xff)
+            }
+        }
+
+        /// The state of the simulated board, shared by its pins and buses. Pins and buses
+        /// are named as the fields of `Board`.
+        #[derive(Clone, Default)]
+        pub struct Sim(Rc<RefCell<State>>);
+
+        impl Sim {
+            /// Set the level an input reads.
+            pub fn set_input(&self, pin: &'static str, high: bool) {
+                self.0.borrow_mut().levels.insert(pin, high);
+            }
+
+            /// Queue the levels successive reads of an input return. The last one stays.
+            pub fn script_input(&self, pin: &'static str, levels: impl IntoIterator<Item = bool>) {
+                self.0.borrow_mut().scripts.entry(pin).or_default().extend(levels);
+            }
+
+            /// Connect an output to an input, which then follows it.
+            pub fn wire(&self, output: &'static str, input: &'static str) {
+                self.0.borrow_mut().wires.push((output, input));
+            }
+
+            /// Whether a pin is high.
+            pub fn is_high(&self, pin: &str) -> bool {
+                self.0.borrow().levels.get(pin).copied().unwrap_or(false)
+            }
+
+            /// Every level the code has driven an output to, in order.
+            pub fn history(&self, pin: &str) -> Vec<bool> {
+                self.0.borrow().history.get(pin).cloned().unwrap_or_default()
+            }
+
+            /// Set the voltage on an analog pin.
+            pub fn set_voltage(&self, pin: &'static str, volts: f64) {
+                let reading = (volts / ADC_REFERENCE * ADC_MAX as f64).round().clamp(0.0, ADC_MAX as f64);
+                self.0.borrow_mut().analog.insert(pin, reading as u32);
+            }
+
+            /// The duty cycle last set on a PWM channel, out of `PWM_MAX`.
+            pub fn duty(&self, channel: &str) -> u16 {
+                self.0.borrow().duty.get(channel).copied().unwrap_or(0)
+            }
+
+            /// Put a register device on an I2C bus.
+            pub fn add_i2c_device(&self, bus: &'static str, address: u8) {
+                let device = RegisterDevice { registers: [0; 256], pointer: 0 };
+                self.0.borrow_mut().i2c_devices.insert((bus, address), device);
+            }
+
+            /// Set an I2C device's registers, starting at `register`.
+            pub fn set_registers(&self, bus: &'static str, address: u8, register: u8, values: &[u8]) {
+                let mut state = self.0.borrow_mut();
+                let device = state.i2c_devices.get_mut(&(bus, address)).expect("add the device first");
+                for (i, value) in values.iter().enumerate() {
+                    device.registers[register.wrapping_add(i as u8) as usize] = *value;
+                }
+            }
+
+            /// A register of an I2C device.
+            pub fn register(&self, bus: &'static str, address: u8, register: u8) -> Option<u8> {
+                self.0.borrow().i2c_devices.get(&(bus, address)).map(|d| d.registers[register as usize])
+            }
+
+            /// The transactions on an I2C bus, in order.
+            pub fn i2c_log(&self, bus: &str) -> Vec<I2cTransfer> {
+                self.0.borrow().i2c_log.get(bus).cloned().unwrap_or_default()
+            }
+
+            /// Queue bytes for a SPI bus to clock in, or a UART to receive. Each byte
+            /// clocked on a SPI bus takes one; once they run out it reads 0xff.
+            pub fn queue(&self, bus: &'static str, bytes: &[u8]) {
+                self.0.borrow_mut().responses.entry(bus).or_default().extend(bytes);
+            }
+
+            /// Every byte the code sent on a SPI bus or UART.
+            pub fn written(&self, bus: &str) -> Vec<u8> {
+                self.0.borrow().written.get(bus).cloned().unwrap_or_default()
+            }
+        }
+
+        /// A simulated GPIO or analog pin.
+        pub struct SimPin {
+            name: &'static str,
+            sim: Sim,
+        }
+
+        impl SimPin {
+            /// Take an ADC reading of the pin.
+            pub fn read_analog(&mut self) -> u32 {
+                self.sim.0.borrow().analog.get(self.name).copied().unwrap_or(0)
+            }
+        }
+
+        impl digital::ErrorType for SimPin {
+            type Error = Infallible;
+        }
+
+        impl OutputPin for SimPin {
+            fn set_low(&mut self) -> Result<(), Infallible> {
+                self.sim.0.borrow_mut().drive(self.name, false);
+                Ok(())
+            }
+
+            fn set_high(&mut self) -> Result<(), Infallible> {
+                self.sim.0.borrow_mut().drive(self.name, true);
+                Ok(())
+            }
+        }
+
+        impl StatefulOutputPin for SimPin {
+            fn is_set_high(&mut self) -> Result<bool, Infallible> {
+                Ok(self.sim.is_high(self.name))
+            }
+
+            fn is_set_low(&mut self) -> Result<bool, Infallible> {
+                Ok(!self.sim.is_high(self.name))
+            }
+        }
+
+        impl InputPin for SimPin {
+            fn is_high(&mut self) -> Result<bool, Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                if let Some(level) = state.scripts.get_mut(self.name).and_then(VecDeque::pop_front) {
+                    state.levels.insert(self.name, level);
+                }
+                Ok(state.levels.get(self.name).copied().unwrap_or(false))
+            }
+
+            fn is_low(&mut self) -> Result<bool, Infallible> {
+                self.is_high().map(|high| !high)
+            }
+        }
+
+        /// A simulated PWM channel.
+        pub struct SimPwm {
+            name: &'static str,
+            sim: Sim,
+        }
+
+        impl pwm::ErrorType for SimPwm {
+            type Error = Infallible;
+        }
+
+        impl SetDutyCycle for SimPwm {
+            fn max_duty_cycle(&self) -> u16 {
+                PWM_MAX
+            }
+
+            fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+                self.sim.0.borrow_mut().duty.insert(self.name, duty);
+                Ok(())
+            }
+        }
+
+        /// A simulated I2C bus. Addresses without a device aren't acknowledged.
+        pub struct SimI2c {
+            name: &'static str,
+            sim: Sim,
+        }
+
+        impl i2c::ErrorType for SimI2c {
+            type Error = SimError;
+        }
+
+        impl I2c for SimI2c {
+            fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), SimError> {
+                let mut state = self.sim.0.borrow_mut();
+                let state = &mut *state;
+                let device = state.i2c_devices.get_mut(&(self.name, address)).ok_or(SimError::NoAcknowledge(address))?;
+                let mut transfer = I2cTransfer { address, ..Default::default() };
+                for operation in operations.iter_mut() {
+                    match operation {
+                        Operation::Write(bytes) => {
+                            if let Some((pointer, values)) = bytes.split_first() {
+                                device.pointer = *pointer;
+                                for value in values {
+                                    device.registers[device.pointer as usize] = *value;
+                                    device.pointer = device.pointer.wrapping_add(1);
+                                }
+                            }
+                            transfer.written.extend_from_slice(bytes);
+                        },
+                        Operation::Read(buffer) => {
+                            for byte in buffer.iter_mut() {
+                                *byte = device.registers[device.pointer as usize];
+                                device.pointer = device.pointer.wrapping_add(1);
+                            }
+                            transfer.read.extend_from_slice(buffer);
+                        },
+                    }
+                }
+                state.i2c_log.entry(self.name).or_default().push(transfer);
+                Ok(())
+            }
+        }
+
+        /// A simulated SPI bus. Reads clock out zeros.
+        pub struct SimSpi {
+            name: &'static str,
+            sim: Sim,
+        }
+
+        impl spi::ErrorType for SimSpi {
+            type Error = Infallible;
+        }
+
+        impl SpiBus<u8> for SimSpi {
+            fn read(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                for word in words.iter_mut() {
+                    *word = state.clock(self.name, 0);
+                }
+                Ok(())
+            }
+
+            fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                for word in words.iter() {
+                    state.clock(self.name, *word);
+                }
+                Ok(())
+            }
+
+            fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                for i in 0..read.len().max(write.len()) {
+                    let word = state.clock(self.name, write.get(i).copied().unwrap_or(0));
+                    if let Some(r) = read.get_mut(i) {
+                        *r = word;
+                    }
+                }
+                Ok(())
+            }
+
+            fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                for word in words.iter_mut() {
+                    *word = state.clock(self.name, *word);
+                }
+                Ok(())
+            }
+
+            fn flush(&mut self) -> Result<(), Infallible> {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A simulated UART, only generated for boards that have one since it needs the
+/// embedded-io crate.
+fn uart_runtime() -> TokenStream {
+    quote! {
+        /// A simulated UART. Reads return what was queued, then end of file.
+        pub struct SimUart {
+            name: &'static str,
+            sim: Sim,
+        }
+
+        impl embedded_io::ErrorType for SimUart {
+            type Error = Infallible;
+        }
+
+        impl embedded_io::Read for SimUart {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+                let mut state = self.sim.0.borrow_mut();
+                let Some(queued) = state.responses.get_mut(self.name) else {
+                    return Ok(0);
+                };
+                let n = buf.len().min(queued.len());
+                for (byte, queued) in buf.iter_mut().zip(queued.drain(..n)) {
+                    *byte = queued;
+                }
+                Ok(n)
+            }
+        }
+
+        impl embedded_io::Write for SimUart {
+            fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+                self.sim.0.borrow_mut().written.entry(self.name).or_default().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> Result<(), Infallible> {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Generate the simulation module of a board.
+pub fn generate_module(board: &Board) -> Result<String, String> {
+    let fields = fields(board);
+    if fields.is_empty() {
+        return Err(format!("the {} has no pinout to simulate", board.get_name()));
+    }
+    let (adc_bits, adc_reference) = board.adc().map(|adc| (adc.resolution, adc.reference)).unwrap_or((DEFAULT_ADC_BITS, 3.3));
+    let adc_max = ((1u64 << adc_bits) - 1) as u32;
+    let has_uart = fields.iter().any(|f| f.kind == "SimUart");
+    let dependencies = match has_uart {
+        true => "`embedded-hal` (1.0) and `embedded-io` (0.6) crates",
+        false => "`embedded-hal` crate (1.0)",
+    };
+    let module_doc = [
+        format!(" A simulation of the {} for host tests, generated by Iron Coder from the", board.get_name()),
+        " board's pinout. Don't edit by hand. Declare it with `#[cfg(test)] mod sim;` and".to_string(),
+        format!(" add the {} to the project's dev-dependencies.", dependencies),
+    ];
+    let declarations = fields.iter().map(|f| {
+        let name = format_ident!("{}", f.name);
+        let kind = format_ident!("{}", f.kind);
+        let doc = format!(" {}", f.pins.join(", "));
+        quote! {
+            #[doc = #doc]
+            pub #name: #kind
+        }
+    });
+    let constructors = fields.iter().map(|f| {
+        let name = format_ident!("{}", f.name);
+        let kind = format_ident!("{}", f.kind);
+        let key = &f.name;
+        quote!(#name: #kind { name: #key, sim: sim.clone() })
+    });
+    let board_doc = format!(" The pins and buses of the {}. Tests refer to them by field name.", board.get_name());
+    let runtime = runtime(adc_max, adc_reference);
+    let uart = if has_uart { uart_runtime() } else { quote!() };
+    let tokens = quote! {
+        #(#![doc = #module_doc])*
+        #![allow(dead_code)]
+        use std::cell::RefCell;
+        use std::collections::{BTreeMap, VecDeque};
+        use std::convert::Infallible;
+        use std::rc::Rc;
+        use embedded_hal::digital::{self, InputPin, OutputPin, StatefulOutputPin};
+        use embedded_hal::i2c::{self, I2c, Operation};
+        use embedded_hal::pwm::{self, SetDutyCycle};
+        use embedded_hal::spi::{self, SpiBus};
+
+        #runtime
+        #uart
+
+        #[doc = #board_doc]
+        pub struct Board {
+            #(#declarations),*
+        }
+
+        impl Board {
+            /// A board on a fresh simulation, and the handle that drives it.
+            pub fn new() -> (Self, Sim) {
+                let sim = Sim::default();
+                let board = Self {
+                    #(#constructors),*
+                };
+                (board, sim)
+            }
+        }
+    };
+    let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+    Ok(prettyplease::unparse(&file))
+}
+
+/// Write the simulation module into the project's source directory.
+pub fn write_module(board: &Board, project_dir: &Path) -> io::Result<()> {
+    let code = generate_module(board).map_err(io::Error::other)?;
+    let src = project_dir.join("src");
+    fs::create_dir_all(&src)?;
+    fs::write(src.join(MODULE_FILE_NAME), code)
+}
@@ -0,0 +1,164 @@
+//! Title: Iron Coder Project Module - Dependency Sources
+//! Description: Where Cargo gets a project's dependencies from, for labs that can't reach
+//!   crates.io. A project can use a registry mirror in its place, or vendor its
+//!   dependencies into the project (from crates.io or the mirror) and build from there,
+//!   optionally with the network off altogether. The choice is written to the project's
+//!   `.cargo/config.toml` before each build, leaving the rest of that file alone.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+/// The directory in the project the dependencies are vendored into.
+pub const VENDOR_DIR: &str = "vendor";
+/// The names of the sources written to the Cargo config.
+const MIRROR_SOURCE: &str = "iron-coder-mirror";
+const VENDORED_SOURCE: &str = "vendored-sources";
+
+/// Where the dependencies come from.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceMode {
+    #[default]
+    CratesIo,
+    /// A registry that mirrors crates.io
+    Mirror,
+    /// The project's vendor directory
+    Vendored,
+}
+
+impl fmt::Display for SourceMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceMode::CratesIo => write!(f, "crates.io"),
+            SourceMode::Mirror => write!(f, "registry mirror"),
+            SourceMode::Vendored => write!(f, "vendored"),
+        }
+    }
+}
+
+/// Where a project's dependencies come from.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct DependencySources {
+    pub mode: SourceMode,
+    /// The index of the mirror, i.e. "sparse+https://mirror.example.com/index/". Vendoring
+    /// fetches from it too, when it's set.
+    pub mirror: String,
+    /// Keep Cargo off the network
+    pub offline: bool,
+}
+
+impl DependencySources {
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mirror = self.mirror.trim();
+        let schemes = ["sparse+https://", "sparse+http://", "https://", "http://", "ssh://", "git://", "file://"];
+        if !mirror.is_empty() && !schemes.iter().any(|s| mirror.starts_with(s) && mirror.len() > s.len()) {
+            return Err(format!("the mirror should be an index URL, i.e. sparse+https://mirror.example.com/index/ (got {})", mirror));
+        }
+        if self.mode == SourceMode::Mirror && mirror.is_empty() {
+            return Err("the mirror needs an index URL".to_string());
+        }
+        if self.offline && self.mode == SourceMode::Mirror {
+            return Err("a mirror can't be used offline; vendor the dependencies instead".to_string());
+        }
+        Ok(())
+    }
+
+    /// The sources to vendor from: the mirror if there is one, or else crates.io.
+    pub fn upstream(&self) -> Self {
+        let mode = if self.mirror.trim().is_empty() { SourceMode::CratesIo } else { SourceMode::Mirror };
+        Self { mode, mirror: self.mirror.clone(), offline: false }
+    }
+
+    /// Write the sources into the text of a Cargo config, replacing the ones written
+    /// before and keeping everything else.
+    pub fn apply_to(&self, config: &str) -> Result<String, String> {
+        self.validate()?;
+        let mut doc: toml_edit::DocumentMut = config.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+        if let Some(source) = doc.get_mut("source").and_then(toml_edit::Item::as_table_like_mut) {
+            source.remove(MIRROR_SOURCE);
+            source.remove(VENDORED_SOURCE);
+            if let Some(crates_io) = source.get_mut("crates-io").and_then(toml_edit::Item::as_table_like_mut) {
+                crates_io.remove("replace-with");
+                if crates_io.is_empty() {
+                    source.remove("crates-io");
+                }
+            }
+            if source.is_empty() {
+                doc.remove("source");
+            }
+        }
+        if let Some(net) = doc.get_mut("net").and_then(toml_edit::Item::as_table_like_mut) {
+            net.remove("offline");
+            if net.is_empty() {
+                doc.remove("net");
+            }
+        }
+        let (name, key, value) = match self.mode {
+            SourceMode::CratesIo => ("", "", ""),
+            SourceMode::Mirror => (MIRROR_SOURCE, "registry", self.mirror.trim()),
+            SourceMode::Vendored => (VENDORED_SOURCE, "directory", VENDOR_DIR),
+        };
+        if !name.is_empty() {
+            doc["source"]["crates-io"]["replace-with"] = toml_edit::value(name);
+            doc["source"][name][key] = toml_edit::value(value);
+            if let Some(source) = doc["source"].as_table_mut() {
+                source.set_implicit(true);
+            }
+        }
+        if self.offline {
+            doc["net"]["offline"] = toml_edit::value(true);
+        }
+        Ok(doc.to_string())
+    }
+
+    /// Bring the project's `.cargo/config.toml` in line with the sources. Returns whether
+    /// it changed.
+    pub fn write_config(&self, project_dir: &Path) -> Result<bool, String> {
+        if self.mode == SourceMode::Vendored && !project_dir.join(VENDOR_DIR).is_dir() {
+            return Err(format!("there's no {} directory; vendor the dependencies first", VENDOR_DIR));
+        }
+        let path = project_dir.join(".cargo").join("config.toml");
+        let existing = fs::read_to_string(&path).unwrap_or_default();
+        let updated = self.apply_to(&existing)?;
+        if updated == existing {
+            return Ok(false);
+        }
+        fs::create_dir_all(project_dir.join(".cargo")).map_err(|e| e.to_string())?;
+        fs::write(&path, updated).map_err(|e| format!("couldn't write .cargo/config.toml: {}", e))?;
+        Ok(true)
+    }
+
+    /// Show the source settings in the Ui.
+    pub fn display_settings(&mut self, ui: &mut egui::Ui) {
+        for mode in enum_iterator::all::<SourceMode>() {
+            ui.radio_value(&mut self.mode, mode, mode.to_string());
+        }
+        if self.mode != SourceMode::CratesIo {
+            ui.horizontal(|ui| {
+                ui.label(if self.mode == SourceMode::Vendored { "vendor from:" } else { "mirror:" });
+                let hint = if self.mode == SourceMode::Vendored { "crates.io" } else { "sparse+https://..." };
+                ui.add(egui::TextEdit::singleline(&mut self.mirror).hint_text(hint).desired_width(260.0));
+            });
+        }
+        ui.add_enabled(self.mode != SourceMode::Mirror, egui::Checkbox::new(&mut self.offline, "build offline"));
+        if let Err(e) = self.validate() {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+    }
+}
+
+/// The command that vendors the project's dependencies into its vendor directory.
+pub fn vendor_command(project_dir: &Path) -> duct::Expression {
+    duct::cmd!("cargo", "-Z", "unstable-options", "-C", project_dir, "vendor", "--versioned-dirs", VENDOR_DIR)
+}
+
+/// The command that checks the project builds without the network, from its lock file.
+pub fn offline_check_command(project_dir: &Path) -> duct::Expression {
+    duct::cmd!("cargo", "-Z", "unstable-options", "-C", project_dir, "build", "--offline", "--locked")
+}
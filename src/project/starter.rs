@@ -0,0 +1,453 @@
+//! Title: Iron Coder Project Module - Starter Code
+//! Description: A minimal program for a board whose files don't come with a project
+//!   template, written for the HAL crate named in the board file. It sets up the clocks
+//!   and blinks the board's LED, along with the manifest, linker script and cargo config
+//!   to build it, so a new project has something to run straight away.
+//!
+//! ```toml
+//! bsp_crate = "adafruit-feather-rp2040"
+//! hal_crate = "rp2040-hal"
+//! led = "d13"
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::board::Board;
+use crate::board::pinout::PinDefinition;
+use crate::board::flashing::FlashMethod;
+use crate::project::std_support::{self, StdSettings, StdSupport};
+
+/// How long the LED is on and off for, in milliseconds.
+const BLINK_MS: u32 = 500;
+
+/// The HALs there's starter code for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StarterHal {
+    Rp2040Hal,
+    /// Any of the nRF HALs, i.e. nrf52832-hal
+    NrfHal,
+    EspHal,
+}
+
+impl StarterHal {
+
+    /// The starter HAL for a HAL crate, if there's one.
+    pub fn for_crate(hal_crate: &str) -> Option<Self> {
+        match hal_crate {
+            "rp2040-hal" => Some(StarterHal::Rp2040Hal),
+            "esp-hal" => Some(StarterHal::EspHal),
+            c if c.starts_with("nrf") && c.ends_with("-hal") => Some(StarterHal::NrfHal),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StarterHal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StarterHal::Rp2040Hal => write!(f, "rp2040-hal"),
+            StarterHal::NrfHal => write!(f, "nrf-hal"),
+            StarterHal::EspHal => write!(f, "esp-hal"),
+        }
+    }
+}
+
+/// The port and number of a pin from what the board says it is, i.e. "GPIO13" is (0, 13)
+/// and "P1.02" is (1, 2).
+pub fn function_number(function: &str) -> Option<(u8, u8)> {
+    let function = function.trim();
+    if let Some(number) = function.strip_prefix("GPIO") {
+        return Some((0, number.parse().ok()?));
+    }
+    let (port, number) = function.strip_prefix('P')?.split_once('.')?;
+    Some((port.parse().ok()?, number.parse().ok()?))
+}
+
+/// The port and number of a pin.
+pub fn pin_number(pin: &PinDefinition) -> Option<(u8, u8)> {
+    function_number(&pin.function)
+}
+
+/// The HAL of a board, and its crate, checking the starter code can be written for it.
+fn board_hal(board: &Board) -> Result<(StarterHal, &str), String> {
+    let hal_crate = board.hal_crate().ok_or(format!("{} doesn't name its HAL crate", board.get_name()))?;
+    let hal = StarterHal::for_crate(hal_crate).ok_or(format!("there's no starter code for {}", hal_crate))?;
+    Ok((hal, hal_crate))
+}
+
+/// The board's LED pin, if it has one the starter code can use.
+fn led_pin(board: &Board) -> Result<Option<(u8, u8)>, String> {
+    match board.led() {
+        None => Ok(None),
+        Some(pin) => pin_number(pin).map(Some).ok_or(format!("can't tell which GPIO the LED pin {} is", pin.name)),
+    }
+}
+
+/// The ESP chip the board has, which picks the esp-hal feature and the target.
+fn esp_chip(board: &Board) -> &str {
+    board.esp_idf_target().unwrap_or("esp32")
+}
+
+/// The loop at the end of `main`, which blinks `led` if there's one.
+fn blink_loop(led: bool, on: &str, off: &str, delay: &str) -> String {
+    if led {
+        format!(
+"    loop {{
+        {on}
+        {delay}
+        {off}
+        {delay}
+    }}
+", on = on, off = off, delay = delay)
+    } else {
+        format!(
+"    // the board file doesn't say where the LED is; the program goes here
+    loop {{
+        {delay}
+    }}
+", delay = delay)
+    }
+}
+
+/// The `main.rs` of the starter program.
+pub fn main_rs(board: &Board) -> Result<String, String> {
+    let (hal, hal_crate) = board_hal(board)?;
+    let led = led_pin(board)?;
+    let header = format!("//! Starter program for the {}, made by Iron Coder\n\n#![no_std]\n#![no_main]\n\n", board.label());
+    // where the LED is on the board, for whoever reads the code
+    let led_note = board.led().map(|p| format!("    // the LED is {} on the silkscreen, {} of the chip\n", p.name, p.function)).unwrap_or_default();
+    let code = match hal {
+        StarterHal::Rp2040Hal => {
+            let led_setup = led.map(|(_, n)| format!(
+                "    let pins = hal::gpio::Pins::new(pac.IO_BANK0, pac.PADS_BANK0, sio.gpio_bank0, &mut pac.RESETS);\n    let mut led = pins.gpio{}.into_push_pull_output();\n\n", n,
+            )).unwrap_or_default();
+            format!(
+"use panic_halt as _;
+{imports}use rp2040_hal as hal;
+use hal::{{pac, Clock}};
+
+/// The second stage bootloader, which works with any flash chip
+#[link_section = \".boot2\"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+#[hal::entry]
+fn main() -> ! {{
+    let mut pac = pac::Peripherals::take().unwrap();
+    let core = pac::CorePeripherals::take().unwrap();
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+    // run the system clock at 125 MHz from the crystal
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    ).ok().unwrap();
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().to_Hz());
+
+    let {sio} = hal::Sio::new(pac.SIO);
+{led_setup}{blink}}}
+",
+                imports = if led.is_some() { "use embedded_hal::digital::OutputPin;\n" } else { "" },
+                sio = if led.is_some() { "sio" } else { "_sio" },
+                led_setup = led_note + &led_setup,
+                blink = blink_loop(led.is_some(), "led.set_high().unwrap();", "led.set_low().unwrap();", &format!("delay.delay_ms({});", BLINK_MS)),
+            )
+        },
+        StarterHal::NrfHal => {
+            let led_setup = led.map(|(port, n)| format!(
+                "    let port = hal::gpio::p{port}::Parts::new(p.P{port});\n    let mut led = port.p{port}_{n:02}.into_push_pull_output(Level::High);\n\n",
+                port = port, n = n,
+            )).unwrap_or_default();
+            format!(
+"use panic_halt as _;
+use embedded_hal::delay::DelayNs;
+{imports}use {hal} as hal;
+use hal::{{clocks::Clocks, {level}pac, Timer}};
+
+#[cortex_m_rt::entry]
+fn main() -> ! {{
+    let p = pac::Peripherals::take().unwrap();
+    // run the high frequency clock from the crystal
+    let _clocks = Clocks::new(p.CLOCK).enable_ext_hfosc();
+    let mut timer = Timer::new(p.TIMER0);
+
+{led_setup}{blink}}}
+",
+                imports = if led.is_some() { "use embedded_hal::digital::OutputPin;\n" } else { "" },
+                level = if led.is_some() { "gpio::Level, " } else { "" },
+                hal = hal_crate.replace('-', "_"),
+                led_setup = led_note + &led_setup,
+                blink = blink_loop(led.is_some(), "led.set_low().unwrap();", "led.set_high().unwrap();", &format!("timer.delay_ms({});", BLINK_MS)),
+            )
+        },
+        StarterHal::EspHal => {
+            let led_setup = led.map(|(_, n)| format!(
+                "    let io = Io::new(peripherals.GPIO, peripherals.IO_MUX);\n    let mut led = Output::new(io.pins.gpio{}, Level::Low);\n\n", n,
+            )).unwrap_or_default();
+            format!(
+"use esp_backtrace as _;
+use esp_hal::{{
+    clock::ClockControl,
+    delay::Delay,
+{gpio}    peripherals::Peripherals,
+    prelude::*,
+    system::SystemControl,
+}};
+
+#[entry]
+fn main() -> ! {{
+    let peripherals = Peripherals::take();
+    let system = SystemControl::new(peripherals.SYSTEM);
+    // run the CPU at its fastest clock
+    let clocks = ClockControl::max(system.clock_control).freeze();
+    let delay = Delay::new(&clocks);
+
+{led_setup}{blink}}}
+",
+                led_setup = led_note + &led_setup,
+                gpio = if led.is_some() { "    gpio::{Io, Level, Output},\n" } else { "" },
+                blink = blink_loop(led.is_some(), "led.set_high();", "led.set_low();", &format!("delay.delay_millis({});", BLINK_MS)),
+            )
+        },
+    };
+    Ok(header + &code)
+}
+
+/// The dependencies of the starter program, as lines of a `[dependencies]` table.
+fn dependencies(board: &Board, hal: StarterHal, hal_crate: &str) -> Vec<String> {
+    let mut deps = match hal {
+        StarterHal::Rp2040Hal => vec![
+            "cortex-m = \"0.7\"".to_string(),
+            "cortex-m-rt = \"0.7\"".to_string(),
+            "embedded-hal = \"1.0\"".to_string(),
+            "panic-halt = \"0.2\"".to_string(),
+            "rp2040-boot2 = \"0.3\"".to_string(),
+            "rp2040-hal = { version = \"0.10\", features = [\"rt\", \"critical-section-impl\"] }".to_string(),
+        ],
+        StarterHal::NrfHal => vec![
+            "cortex-m = { version = \"0.7\", features = [\"critical-section-single-core\"] }".to_string(),
+            "cortex-m-rt = \"0.7\"".to_string(),
+            "embedded-hal = \"1.0\"".to_string(),
+            "panic-halt = \"0.2\"".to_string(),
+            format!("{} = \"0.18\"", hal_crate),
+        ],
+        StarterHal::EspHal => {
+            let chip = esp_chip(board);
+            vec![
+                format!("esp-backtrace = {{ version = \"0.13\", features = [\"{}\", \"panic-handler\", \"exception-handler\", \"println\"] }}", chip),
+                format!("esp-hal = {{ version = \"0.19\", features = [\"{}\"] }}", chip),
+                format!("esp-println = {{ version = \"0.10\", features = [\"{}\"] }}", chip),
+            ]
+        },
+    };
+    if let Some(bsp) = board.bsp_crate() {
+        if !deps.iter().any(|d| d.starts_with(&format!("{} ", bsp))) {
+            deps.push(format!("{} = \"*\"", bsp));
+        }
+    }
+    deps
+}
+
+/// The `Cargo.toml` of the starter program, for a package called `package`.
+pub fn cargo_toml(board: &Board, package: &str) -> Result<String, String> {
+    let (hal, hal_crate) = board_hal(board)?;
+    Ok(format!(
+"[package]
+name = \"{}\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[dependencies]
+{}
+
+[profile.release]
+debug = 2
+",
+        package,
+        dependencies(board, hal, hal_crate).join("\n"),
+    ))
+}
+
+/// The target triple to build for, which is the board's if it gives one.
+fn target(board: &Board, hal: StarterHal) -> String {
+    if let Some(triple) = board.target_triple() {
+        return triple.to_string();
+    }
+    match hal {
+        StarterHal::Rp2040Hal => "thumbv6m-none-eabi".to_string(),
+        StarterHal::NrfHal => match board.cpu() {
+            Some("Cortex-M0") => "thumbv6m-none-eabi".to_string(),
+            Some("Cortex-M33") => "thumbv8m.main-none-eabihf".to_string(),
+            _ => "thumbv7em-none-eabihf".to_string(),
+        },
+        StarterHal::EspHal => match esp_chip(board) {
+            "esp32c2" | "esp32c3" => "riscv32imc-unknown-none-elf".to_string(),
+            chip if chip.starts_with("esp32c") || chip.starts_with("esp32h") => "riscv32imac-unknown-none-elf".to_string(),
+            chip => format!("xtensa-{}-none-elf", chip),
+        },
+    }
+}
+
+/// The `.cargo/config.toml` of the starter program, which sets the target and how to
+/// run on the board.
+pub fn cargo_config(board: &Board) -> Result<String, String> {
+    let (hal, _) = board_hal(board)?;
+    let target = target(board, hal);
+    let (method, rustflags) = match hal {
+        StarterHal::Rp2040Hal => (FlashMethod::Uf2, "[\"-C\", \"link-arg=-Tlink.x\"]"),
+        StarterHal::NrfHal => (FlashMethod::ProbeRs, "[\"-C\", \"link-arg=-Tlink.x\"]"),
+        StarterHal::EspHal => (FlashMethod::Espflash, "[\"-C\", \"link-arg=-Tlinkall.x\", \"-C\", \"link-arg=-nostartfiles\"]"),
+    };
+    // the board's own way of flashing, if cargo can run it
+    let runner = board.flash_method().and_then(|m| m.runner(board.chip_name()))
+        .or_else(|| method.runner(board.chip_name()))
+        .unwrap_or_default();
+    let mut config = format!(
+"[build]
+target = \"{target}\"
+
+[target.{target}]
+runner = \"{runner}\"
+rustflags = {rustflags}
+", target = target, runner = runner, rustflags = rustflags);
+    if target.starts_with("xtensa") {
+        config.push_str("\n[unstable]\nbuild-std = [\"core\"]\n");
+    }
+    Ok(config)
+}
+
+/// The `memory.x` linker script of a Cortex-M starter program. None for chips whose HAL
+/// brings its own, or when the board file doesn't give its flash and RAM.
+pub fn memory_x(board: &Board) -> Option<String> {
+    let (hal, _) = board_hal(board).ok()?;
+    match hal {
+        StarterHal::Rp2040Hal => {
+            // the flash is external, and the second stage bootloader takes its first 256 bytes
+            let flash_k = board.flash().map_or(2048, |f| f.bytes() / 1024);
+            Some(format!(
+"MEMORY {{
+    BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+    FLASH : ORIGIN = 0x10000100, LENGTH = {}K - 0x100
+    RAM   : ORIGIN = 0x20000000, LENGTH = 256K
+}}
+
+EXTERN(BOOT2_FIRMWARE)
+
+SECTIONS {{
+    .boot2 ORIGIN(BOOT2) :
+    {{
+        KEEP(*(.boot2));
+    }} > BOOT2
+}} INSERT BEFORE .text;
+", flash_k))
+        },
+        StarterHal::NrfHal => Some(format!(
+"MEMORY
+{{
+    FLASH : ORIGIN = 0x00000000, LENGTH = {}K
+    RAM : ORIGIN = 0x20000000, LENGTH = {}K
+}}
+", board.flash()?.bytes() / 1024, board.ram()?.bytes() / 1024)),
+        StarterHal::EspHal => None,
+    }
+}
+
+/// The `main.rs` of the starter program for a board that runs Linux, which blinks the
+/// LED through the GPIO character device.
+pub fn std_main_rs(board: &Board, package: &str) -> String {
+    let header = format!("//! Starter program for the {}, made by Iron Coder\n\n", board.label());
+    let Some((pin, (_, line))) = board.led().and_then(|p| Some((p, pin_number(p)?))) else {
+        return format!(
+"{header}fn main() {{
+    // the board file doesn't say where the LED is; the program goes here
+    println!(\"Hello from the {label}!\");
+}}
+", header = header, label = board.label());
+    };
+    format!(
+"{header}use std::error::Error;
+use std::thread::sleep;
+use std::time::Duration;
+
+use embedded_hal::digital::OutputPin;
+use linux_embedded_hal::gpio_cdev::{{Chip, LineRequestFlags}};
+use linux_embedded_hal::CdevPin;
+
+fn main() -> Result<(), Box<dyn Error>> {{
+    // the LED is {name} on the silkscreen, {function} of the chip
+    let mut chip = Chip::new(\"/dev/gpiochip0\")?;
+    let handle = chip.get_line({line})?.request(LineRequestFlags::OUTPUT, 0, \"{package}\")?;
+    let mut led = CdevPin::new(handle)?;
+    loop {{
+        led.set_high().unwrap();
+        sleep(Duration::from_millis(500));
+        led.set_low().unwrap();
+        sleep(Duration::from_millis(500));
+    }}
+}}
+", header = header, name = pin.name, function = pin.function, line = line, package = package)
+}
+
+/// The `Cargo.toml` of the starter program for a board that runs Linux.
+pub fn std_cargo_toml(package: &str) -> String {
+    format!(
+"[package]
+name = \"{}\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[dependencies]
+embedded-hal = \"1.0\"
+linux-embedded-hal = \"0.4\"
+", package)
+}
+
+/// Write the starter program for `board` into `destination`, with the standard library
+/// settings, leaving any file that's already there alone. Returns the files that were
+/// written.
+pub fn write_starter(board: &Board, package: &str, destination: &Path, std_settings: &StdSettings) -> io::Result<Vec<PathBuf>> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let main_path = PathBuf::from("src").join("main.rs");
+    let mut files = if std_settings.support == StdSupport::Std {
+        vec![
+            (main_path, std_main_rs(board, package)),
+            (PathBuf::from("Cargo.toml"), std_cargo_toml(package)),
+        ]
+    } else {
+        let (main, _) = std_settings.main_rs(&main_rs(board).map_err(invalid)?);
+        let mut files = vec![
+            (main_path, main),
+            (PathBuf::from("Cargo.toml"), std_settings.manifest(&cargo_toml(board, package).map_err(invalid)?).map_err(invalid)?),
+            (PathBuf::from(".cargo").join("config.toml"), cargo_config(board).map_err(invalid)?),
+        ];
+        if let Some(memory) = memory_x(board) {
+            files.push((PathBuf::from("memory.x"), memory));
+        }
+        files
+    };
+    if std_settings.support == StdSupport::NoStdAlloc {
+        files.push((PathBuf::from("src").join(std_support::MODULE_FILE_NAME), std_settings.generate_module(false).map_err(invalid)?));
+    }
+    let mut written = vec![];
+    for (file, contents) in files {
+        let path = destination.join(&file);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        written.push(file);
+    }
+    Ok(written)
+}
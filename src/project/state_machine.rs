@@ -0,0 +1,699 @@
+//! Title: Iron Coder Project Module - State Machine
+//! Description: State machines drawn in the designer (states, events, guards and
+//!   actions), saved in the project and compiled to a Rust module: a `State` and an
+//!   `Event` enum, an `Actions` trait for the firmware to implement, and a `Machine`
+//!   whose `handle` method is a match over the transitions. The designer can read a
+//!   generated module back, so edits to the transition table in the code show up in the
+//!   diagram too.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use quote::{format_ident, quote};
+use serde::{Serialize, Deserialize};
+
+/// A state, with the actions run on entering and leaving it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct State {
+    pub name: String,
+    pub entry: Option<String>,
+    pub exit: Option<String>,
+    /// Where the state is drawn in the designer
+    pub position: (f32, f32),
+}
+
+/// A transition from one state to another on an event, if the guard allows it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    pub event: String,
+    pub guard: Option<String>,
+    pub action: Option<String>,
+}
+
+impl Transition {
+    /// The label of the transition in the diagram, i.e. "Toggle [is_ready] / turn_on".
+    pub fn label(&self) -> String {
+        let mut label = self.event.clone();
+        if let Some(guard) = &self.guard {
+            label += &format!(" [{}]", guard);
+        }
+        if let Some(action) = &self.action {
+            label += &format!(" / {}", action);
+        }
+        label
+    }
+}
+
+/// A state machine, i.e.
+/// ```toml
+/// [[state_machines]]
+/// name = "Blinky"
+/// initial = "Off"
+/// events = ["Toggle"]
+/// states = [{ name = "Off" }, { name = "On", entry = "light_on" }]
+/// transitions = [
+///     { from = "Off", to = "On", event = "Toggle", guard = "is_ready" },
+///     { from = "On", to = "Off", event = "Toggle" },
+/// ]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct StateMachine {
+    pub name: String,
+    pub initial: String,
+    pub states: Vec<State>,
+    pub events: Vec<String>,
+    pub transitions: Vec<Transition>,
+}
+
+fn is_type_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn is_fn_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && syn::parse_str::<syn::Ident>(name).is_ok()
+}
+
+/// The position of the n-th state added to a machine without one.
+fn default_position(n: usize) -> (f32, f32) {
+    (20.0 + 140.0 * (n % 4) as f32, 20.0 + 100.0 * (n / 4) as f32)
+}
+
+impl StateMachine {
+
+    /// The name of the generated module, i.e. "door_lock" for "DoorLock".
+    pub fn module_name(&self) -> String {
+        let mut module = String::new();
+        for (i, c) in self.name.chars().enumerate() {
+            if c.is_ascii_uppercase() && i > 0 {
+                module.push('_');
+            }
+            module.push(c.to_ascii_lowercase());
+        }
+        module
+    }
+
+    /// The generated module's path, relative to the project directory.
+    pub fn module_path(&self) -> PathBuf {
+        Path::new("src").join(format!("{}.rs", self.module_name()))
+    }
+
+    pub fn state(&self, name: &str) -> Option<&State> {
+        self.states.iter().find(|s| s.name == name)
+    }
+
+    /// The guards and actions the machine calls, in the order they first appear.
+    fn functions(&self) -> (Vec<&str>, Vec<&str>) {
+        let mut guards: Vec<&str> = vec![];
+        let mut actions: Vec<&str> = vec![];
+        let state_actions = self.states.iter().flat_map(|s| [&s.entry, &s.exit]);
+        let transition_actions = self.transitions.iter().map(|t| &t.action);
+        for action in state_actions.chain(transition_actions).flatten() {
+            if !actions.contains(&action.as_str()) {
+                actions.push(action);
+            }
+        }
+        for guard in self.transitions.iter().filter_map(|t| t.guard.as_ref()) {
+            if !guards.contains(&guard.as_str()) {
+                guards.push(guard);
+            }
+        }
+        (guards, actions)
+    }
+
+    /// Check that the machine can be compiled.
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_type_name(&self.name) {
+            return Err(format!("the machine's name ({:?}) needs to be in UpperCamelCase", self.name));
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            if !is_type_name(&state.name) {
+                return Err(format!("state {:?} needs to be in UpperCamelCase", state.name));
+            }
+            if self.states[..i].iter().any(|s| s.name == state.name) {
+                return Err(format!("there are two states named {}", state.name));
+            }
+        }
+        for (i, event) in self.events.iter().enumerate() {
+            if !is_type_name(event) {
+                return Err(format!("event {:?} needs to be in UpperCamelCase", event));
+            }
+            if self.events[..i].contains(event) {
+                return Err(format!("there are two events named {}", event));
+            }
+        }
+        if self.state(&self.initial).is_none() {
+            return Err("pick an initial state".to_string());
+        }
+        let (guards, actions) = self.functions();
+        for function in guards.iter().chain(actions.iter()) {
+            if !is_fn_name(function) {
+                return Err(format!("{:?} needs to be a snake_case function name", function));
+            }
+        }
+        if let Some(both) = guards.iter().find(|g| actions.contains(g)) {
+            return Err(format!("{} can't be both a guard and an action", both));
+        }
+        for (i, t) in self.transitions.iter().enumerate() {
+            if self.state(&t.from).is_none() || self.state(&t.to).is_none() {
+                return Err(format!("transition {} goes between states that don't exist", t.label()));
+            }
+            if !self.events.contains(&t.event) {
+                return Err(format!("transition {} is on an event that doesn't exist", t.label()));
+            }
+            // an earlier transition on the same event that always fires hides this one
+            let hidden = self.transitions[..i].iter().any(|e| e.from == t.from && e.event == t.event && (e.guard.is_none() || e.guard == t.guard));
+            if hidden {
+                return Err(format!("transition {} from {} can never fire", t.label(), t.from));
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate the Rust source of the machine's module.
+    pub fn generate_module(&self) -> Result<String, String> {
+        self.validate()?;
+        let state = |name: &str| { let n = format_ident!("{}", name); quote!(State::#n) };
+        let call = |f: &str| { let f = format_ident!("{}", f); quote!(actions.#f()) };
+        let states = self.states.iter().map(|s| format_ident!("{}", s.name));
+        let events = self.events.iter().map(|e| format_ident!("{}", e));
+        let (guards, actions) = self.functions();
+        let guards = guards.iter().map(|g| format_ident!("{}", g));
+        let actions = actions.iter().map(|a| format_ident!("{}", a));
+        let arms = self.transitions.iter().map(|t| {
+            let (from, to) = (state(&t.from), state(&t.to));
+            let event = format_ident!("{}", t.event);
+            let guard = t.guard.as_deref().map(|g| { let g = call(g); quote!(if #g) });
+            let action = t.action.as_deref().map(|a| { let a = call(a); quote!(#a;) });
+            quote! {
+                (#from, Event::#event) #guard => {
+                    self.leave(actions);
+                    #action
+                    #to
+                }
+            }
+        });
+        let state_arms = |f: fn(&State) -> &Option<String>| {
+            let arms: Vec<_> = self.states.iter().filter_map(|s| {
+                let (name, action) = (state(&s.name), call(f(s).as_deref()?));
+                Some(quote!(#name => #action,))
+            }).collect();
+            arms
+        };
+        let entry_arms = state_arms(|s| &s.entry);
+        let exit_arms = state_arms(|s| &s.exit);
+        let initial = state(&self.initial);
+        let doc = format!(
+            " The {} state machine, generated by Iron Coder from the state machine designer.",
+            self.name,
+        );
+        let tokens = quote! {
+            #![doc = #doc]
+            //! The designer reads the states, events and the match arms of `handle` back
+            //! from this file, so they can be edited here as well as in the diagram.
+            #![allow(dead_code)]
+
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            pub enum State {
+                #(#states),*
+            }
+
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            pub enum Event {
+                #(#events),*
+            }
+
+            /// The guards and actions of the machine, for the firmware to implement.
+            pub trait Actions {
+                #(fn #guards(&mut self) -> bool;)*
+                #(fn #actions(&mut self);)*
+            }
+
+            pub struct Machine {
+                state: State,
+            }
+
+            impl Machine {
+                pub const INITIAL: State = #initial;
+
+                pub fn new() -> Self {
+                    Self { state: Self::INITIAL }
+                }
+
+                pub fn state(&self) -> State {
+                    self.state
+                }
+
+                /// Handle an event, returning whether it caused a transition. The source
+                /// state's exit action runs first, then the transition's action, then the
+                /// target state's entry action.
+                pub fn handle<A: Actions>(&mut self, actions: &mut A, event: Event) -> bool {
+                    let next = match (self.state, event) {
+                        #(#arms)*
+                        _ => return false,
+                    };
+                    self.state = next;
+                    Self::enter(actions, next);
+                    true
+                }
+
+                fn leave<A: Actions>(&self, actions: &mut A) {
+                    match self.state {
+                        #(#exit_arms)*
+                        _ => {}
+                    }
+                }
+
+                fn enter<A: Actions>(actions: &mut A, state: State) {
+                    match state {
+                        #(#entry_arms)*
+                        _ => {}
+                    }
+                }
+            }
+        };
+        let file: syn::File = syn::parse2(tokens).map_err(|e| e.to_string())?;
+        Ok(prettyplease::unparse(&file))
+    }
+
+    /// Write the machine's module into the project's source directory.
+    pub fn write_module(&self, project_dir: &Path) -> io::Result<()> {
+        let code = self.generate_module().map_err(io::Error::other)?;
+        let path = project_dir.join(self.module_path());
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, code)
+    }
+
+    /// Read a machine back from a module in the generated form. Positions are taken
+    /// from `layout` for states it has, so the diagram doesn't move around.
+    pub fn from_module(name: &str, code: &str, layout: Option<&StateMachine>) -> Result<Self, String> {
+        let file = syn::parse_file(code).map_err(|e| e.to_string())?;
+        let mut machine = StateMachine { name: name.to_string(), ..Default::default() };
+        let mut handle = None;
+        let mut enter = None;
+        let mut leave = None;
+        for item in file.items.iter() {
+            match item {
+                syn::Item::Enum(e) if e.ident == "State" => {
+                    machine.states = e.variants.iter().map(|v| State { name: v.ident.to_string(), ..Default::default() }).collect();
+                },
+                syn::Item::Enum(e) if e.ident == "Event" => {
+                    machine.events = e.variants.iter().map(|v| v.ident.to_string()).collect();
+                },
+                syn::Item::Impl(i) if matches!(&*i.self_ty, syn::Type::Path(p) if p.path.is_ident("Machine")) => {
+                    for item in i.items.iter() {
+                        match item {
+                            syn::ImplItem::Const(c) if c.ident == "INITIAL" => {
+                                machine.initial = state_name(&c.expr).ok_or("INITIAL isn't a state")?;
+                            },
+                            syn::ImplItem::Fn(f) if f.sig.ident == "handle" => handle = Some(&f.block),
+                            syn::ImplItem::Fn(f) if f.sig.ident == "enter" => enter = Some(&f.block),
+                            syn::ImplItem::Fn(f) if f.sig.ident == "leave" => leave = Some(&f.block),
+                            _ => (),
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        let handle = handle.ok_or("there's no Machine::handle")?;
+        let transitions = handle.stmts.iter().find_map(|s| match s {
+            syn::Stmt::Local(syn::Local { init: Some(init), .. }) => match &*init.expr {
+                syn::Expr::Match(m) => Some(m),
+                _ => None,
+            },
+            _ => None,
+        }).ok_or("Machine::handle doesn't match on the state and event")?;
+        for arm in transitions.arms.iter() {
+            if let Some(transition) = parse_transition(arm)? {
+                machine.transitions.push(transition);
+            }
+        }
+        for (block, entry) in [(enter, true), (leave, false)] {
+            let Some(syn::Stmt::Expr(syn::Expr::Match(m), _)) = block.and_then(|b| b.stmts.first()) else {
+                continue;
+            };
+            for arm in m.arms.iter() {
+                let (syn::Pat::Path(p), Some(action)) = (&arm.pat, called_action(&arm.body)) else {
+                    continue;
+                };
+                let name = p.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+                if let Some(state) = machine.states.iter_mut().find(|s| s.name == name) {
+                    *(if entry { &mut state.entry } else { &mut state.exit }) = Some(action);
+                }
+            }
+        }
+        for (n, state) in machine.states.iter_mut().enumerate() {
+            state.position = layout.and_then(|l| l.state(&state.name)).map_or(default_position(n), |s| s.position);
+        }
+        machine.validate()?;
+        Ok(machine)
+    }
+
+    /// Whether two machines have the same states, events and transitions, ignoring
+    /// where the states are drawn.
+    pub fn same_logic(&self, other: &StateMachine) -> bool {
+        let strip = |m: &StateMachine| {
+            let mut m = m.clone();
+            m.states.iter_mut().for_each(|s| s.position = (0.0, 0.0));
+            m
+        };
+        strip(self) == strip(other)
+    }
+}
+
+/// The name of the state in a `State::Name` expression or pattern path.
+fn state_name(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Path(p) if p.path.segments.len() == 2 && p.path.segments[0].ident == "State" => Some(p.path.segments[1].ident.to_string()),
+        syn::Expr::Block(b) => b.block.stmts.last().and_then(|s| match s {
+            syn::Stmt::Expr(e, None) => state_name(e),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// The name of the function in an `actions.name()` call.
+fn called_action(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::MethodCall(m) if m.args.is_empty() && matches!(&*m.receiver, syn::Expr::Path(p) if p.path.is_ident("actions")) => Some(m.method.to_string()),
+        _ => None,
+    }
+}
+
+/// Read a transition from an arm like
+/// `(State::Off, Event::Toggle) if actions.is_ready() => { self.leave(actions); actions.turn_on(); State::On }`.
+/// The catch-all arm gives None.
+fn parse_transition(arm: &syn::Arm) -> Result<Option<Transition>, String> {
+    let syn::Pat::Tuple(tuple) = &arm.pat else {
+        return Ok(None);
+    };
+    let path = |pat: Option<&syn::Pat>, kind: &str| match pat {
+        Some(syn::Pat::Path(p)) if p.path.segments.len() == 2 && p.path.segments[0].ident == kind => Ok(p.path.segments[1].ident.to_string()),
+        _ => Err(format!("a transition needs to match on ({}::_, ...)", kind)),
+    };
+    let from = path(tuple.elems.first(), "State")?;
+    let event = path(tuple.elems.iter().nth(1), "Event")?;
+    let guard = match &arm.guard {
+        Some((_, guard)) => Some(called_action(guard).ok_or_else(|| format!("the guard of {} on {} isn't an actions.guard() call", from, event))?),
+        None => None,
+    };
+    let to = state_name(&arm.body).ok_or_else(|| format!("the transition from {} on {} doesn't end in a state", from, event))?;
+    let action = match &*arm.body {
+        syn::Expr::Block(b) => b.block.stmts.iter().find_map(|s| match s {
+            syn::Stmt::Expr(e, Some(_)) => called_action(e),
+            _ => None,
+        }),
+        _ => None,
+    };
+    Ok(Some(Transition { from, to, event, guard, action }))
+}
+
+/// The size of a state in the diagram.
+const STATE_SIZE: egui::Vec2 = egui::vec2(110.0, 44.0);
+
+/// The point where a line from the center of `rect` in direction `dir` leaves it.
+fn edge_point(rect: egui::Rect, dir: egui::Vec2) -> egui::Pos2 {
+    let half = rect.size() / 2.0;
+    let t = (half.x / dir.x.abs().max(1e-6)).min(half.y / dir.y.abs().max(1e-6));
+    rect.center() + dir * t
+}
+
+/// Turn an optional function name field into the text edited in the Ui and back.
+fn optional_edit(ui: &mut egui::Ui, value: &mut Option<String>, hint: &str) {
+    let mut text = value.clone().unwrap_or_default();
+    if ui.add(egui::TextEdit::singleline(&mut text).desired_width(90.0).hint_text(hint)).changed() {
+        *value = (!text.trim().is_empty()).then(|| text.trim().to_string());
+    }
+}
+
+/// The state of the state machine designer.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct StateMachinePanel {
+    /// The index of the machine being edited
+    selected: usize,
+    #[serde(skip)]
+    dragging: Option<usize>,
+    /// When the generated module was last compared with the diagram
+    #[serde(skip)]
+    checked: Option<SystemTime>,
+    /// The machine read back from its module, if it differs from the diagram
+    #[serde(skip)]
+    from_code: Option<StateMachine>,
+    #[serde(skip)]
+    message: Option<String>,
+}
+
+impl StateMachinePanel {
+
+    /// Draw the diagram, and let states be dragged around.
+    fn display_diagram(&mut self, ui: &mut egui::Ui, machine: &mut StateMachine) {
+        let size = egui::vec2(ui.available_width().max(400.0), 300.0);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+        let visuals = ui.style().visuals.clone();
+        painter.rect_filled(response.rect, 2.0, visuals.extreme_bg_color);
+        let origin = response.rect.min.to_vec2();
+        let rect_of = |s: &State| egui::Rect::from_min_size(egui::pos2(s.position.0, s.position.1) + origin, STATE_SIZE);
+        if response.drag_started() {
+            let pointer = response.interact_pointer_pos();
+            self.dragging = machine.states.iter().rposition(|s| pointer.is_some_and(|p| rect_of(s).contains(p)));
+        }
+        if let Some(state) = self.dragging.and_then(|i| machine.states.get_mut(i)) {
+            let delta = response.drag_delta();
+            let max = size - STATE_SIZE;
+            state.position = ((state.position.0 + delta.x).clamp(0.0, max.x), (state.position.1 + delta.y).clamp(0.0, max.y));
+        }
+        if response.drag_stopped() {
+            self.dragging = None;
+        }
+        let stroke = egui::Stroke::new(1.5, visuals.text_color());
+        let font = egui::FontId::proportional(11.0);
+        for (i, t) in machine.transitions.iter().enumerate() {
+            let (Some(from), Some(to)) = (machine.state(&t.from), machine.state(&t.to)) else { continue };
+            let (from, to) = (rect_of(from), rect_of(to));
+            // transitions between the same pair of states are spread apart
+            let parallel = machine.transitions[..i].iter().filter(|o| (o.from == t.from && o.to == t.to) || (o.from == t.to && o.to == t.from)).count();
+            if t.from == t.to {
+                let top = from.center_top() + egui::vec2(-20.0 + 14.0 * parallel as f32, 0.0);
+                let loop_top = top + egui::vec2(0.0, -24.0);
+                painter.line_segment([top, loop_top], stroke);
+                painter.line_segment([loop_top, loop_top + egui::vec2(30.0, 0.0)], stroke);
+                painter.arrow(loop_top + egui::vec2(30.0, 0.0), egui::vec2(0.0, 24.0), stroke);
+                painter.text(loop_top + egui::vec2(15.0, -2.0), egui::Align2::CENTER_BOTTOM, t.label(), font.clone(), visuals.text_color());
+                continue;
+            }
+            let dir = (to.center() - from.center()).normalized();
+            let offset = dir.rot90() * (6.0 + 12.0 * parallel as f32);
+            let start = edge_point(from, dir) + offset;
+            let end = edge_point(to, -dir) + offset;
+            painter.arrow(start, end - start, stroke);
+            painter.text(start + (end - start) / 2.0 + offset, egui::Align2::CENTER_CENTER, t.label(), font.clone(), visuals.text_color());
+        }
+        for state in machine.states.iter() {
+            let rect = rect_of(state);
+            let initial = state.name == machine.initial;
+            let fill = if initial { visuals.selection.bg_fill } else { visuals.widgets.inactive.bg_fill };
+            painter.rect(rect, 8.0, fill, visuals.widgets.noninteractive.fg_stroke);
+            painter.text(rect.center(), egui::Align2::CENTER_CENTER, &state.name, egui::FontId::proportional(14.0), visuals.strong_text_color());
+            if initial {
+                painter.circle_filled(rect.left_center() + egui::vec2(-10.0, 0.0), 4.0, visuals.text_color());
+            }
+        }
+    }
+
+    /// Show the tables of states, events and transitions.
+    fn display_tables(&mut self, ui: &mut egui::Ui, machine: &mut StateMachine) {
+        let state_names: Vec<String> = machine.states.iter().map(|s| s.name.clone()).collect();
+        egui::CollapsingHeader::new("states").default_open(true).show(ui, |ui| {
+            let mut remove = None;
+            egui::Grid::new("sm_states").num_columns(5).striped(true).show(ui, |ui| {
+                for (i, state) in machine.states.iter_mut().enumerate() {
+                    ui.radio_value(&mut machine.initial, state.name.clone(), "").on_hover_text("the initial state");
+                    let old_name = state.name.clone();
+                    if ui.add(egui::TextEdit::singleline(&mut state.name).desired_width(90.0)).changed() {
+                        // keep the transitions and the initial state pointing at it
+                        for t in machine.transitions.iter_mut() {
+                            if t.from == old_name { t.from = state.name.clone(); }
+                            if t.to == old_name { t.to = state.name.clone(); }
+                        }
+                        if machine.initial == old_name { machine.initial = state.name.clone(); }
+                    }
+                    optional_edit(ui, &mut state.entry, "entry action");
+                    optional_edit(ui, &mut state.exit, "exit action");
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                let name = machine.states.remove(i).name;
+                machine.transitions.retain(|t| t.from != name && t.to != name);
+            }
+            if ui.button("add state").clicked() {
+                let n = machine.states.len();
+                let name = (1..).map(|i| format!("State{}", i)).find(|n| machine.state(n).is_none()).unwrap();
+                if machine.states.is_empty() {
+                    machine.initial = name.clone();
+                }
+                machine.states.push(State { name, position: default_position(n), ..Default::default() });
+            }
+        });
+        egui::CollapsingHeader::new("events").default_open(true).show(ui, |ui| {
+            let mut remove = None;
+            ui.horizontal_wrapped(|ui| {
+                for (i, event) in machine.events.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(event).desired_width(80.0));
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                }
+                if ui.button("add event").clicked() {
+                    let name = (1..).map(|i| format!("Event{}", i)).find(|n| !machine.events.contains(n)).unwrap();
+                    machine.events.push(name);
+                }
+            });
+            if let Some(i) = remove {
+                let name = machine.events.remove(i);
+                machine.transitions.retain(|t| t.event != name);
+            }
+        });
+        egui::CollapsingHeader::new("transitions").default_open(true).show(ui, |ui| {
+            let mut remove = None;
+            egui::Grid::new("sm_transitions").num_columns(6).striped(true).show(ui, |ui| {
+                for heading in ["from", "event", "guard", "action", "to", ""] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (i, t) in machine.transitions.iter_mut().enumerate() {
+                    let choose = |ui: &mut egui::Ui, id: &str, value: &mut String, options: &[String]| {
+                        egui::ComboBox::from_id_source((id, i)).selected_text(value.as_str()).show_ui(ui, |ui| {
+                            for option in options {
+                                ui.selectable_value(value, option.clone(), option);
+                            }
+                        });
+                    };
+                    choose(ui, "sm_from", &mut t.from, &state_names);
+                    choose(ui, "sm_event", &mut t.event, &machine.events);
+                    optional_edit(ui, &mut t.guard, "guard");
+                    optional_edit(ui, &mut t.action, "action");
+                    choose(ui, "sm_to", &mut t.to, &state_names);
+                    if ui.small_button("x").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                machine.transitions.remove(i);
+            }
+            let can_add = !machine.states.is_empty() && !machine.events.is_empty();
+            if ui.add_enabled(can_add, egui::Button::new("add transition")).clicked() {
+                let from = machine.states[0].name.clone();
+                machine.transitions.push(Transition { to: from.clone(), from, event: machine.events[0].clone(), ..Default::default() });
+            }
+        });
+    }
+
+    /// Compare the machine with its generated module when the module changes on disk.
+    fn check_module(&mut self, machine: &StateMachine, project_dir: &Path) {
+        let path = project_dir.join(machine.module_path());
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified.is_none() || modified == self.checked {
+            return;
+        }
+        self.checked = modified;
+        self.from_code = fs::read_to_string(&path).ok()
+            .and_then(|code| StateMachine::from_module(&machine.name, &code, Some(machine)).ok())
+            .filter(|m| !m.same_logic(machine));
+    }
+
+    /// Show the designer for the project's machines.
+    pub fn display(&mut self, ui: &mut egui::Ui, machines: &mut Vec<StateMachine>, project_dir: Option<&Path>) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("sm_selected")
+            .selected_text(machines.get(self.selected).map(|m| m.name.as_str()).unwrap_or("none"))
+            .show_ui(ui, |ui| {
+                for (i, m) in machines.iter().enumerate() {
+                    if ui.selectable_value(&mut self.selected, i, &m.name).clicked() {
+                        self.checked = None;
+                        self.from_code = None;
+                    }
+                }
+            });
+            if ui.button("new machine").clicked() {
+                let name = (1..).map(|i| format!("Machine{}", i)).find(|n| !machines.iter().any(|m| &m.name == n)).unwrap();
+                machines.push(StateMachine { name, ..Default::default() });
+                self.selected = machines.len() - 1;
+                self.checked = None;
+                self.from_code = None;
+            }
+            if self.selected < machines.len() && ui.button("delete").clicked() {
+                machines.remove(self.selected);
+                self.selected = self.selected.saturating_sub(1);
+                self.checked = None;
+                self.from_code = None;
+            }
+        });
+        let Some(machine) = machines.get_mut(self.selected) else {
+            ui.label("This project has no state machines yet.");
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("name:");
+            if ui.text_edit_singleline(&mut machine.name).changed() {
+                self.checked = None;
+            }
+        });
+        if let Some(dir) = project_dir {
+            self.check_module(machine, dir);
+        }
+        self.display_diagram(ui, machine);
+        egui::ScrollArea::vertical().max_height(260.0).show(ui, |ui| {
+            self.display_tables(ui, machine);
+        });
+        ui.separator();
+        if let Err(e) = machine.validate() {
+            ui.colored_label(ui.style().visuals.warn_fg_color, e);
+        }
+        let module = machine.module_path().display().to_string();
+        if let Some(from_code) = self.from_code.clone() {
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.style().visuals.warn_fg_color, format!("{} doesn't match the diagram", module));
+                if ui.button("update the diagram").clicked() {
+                    *machine = from_code;
+                    self.from_code = None;
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let Some(dir) = project_dir else {
+                ui.label(format!("save the project to generate {}", module));
+                return;
+            };
+            if ui.add_enabled(machine.validate().is_ok(), egui::Button::new(format!("generate {}", module))).clicked() {
+                self.message = Some(match machine.write_module(dir) {
+                    Ok(()) => format!("wrote {}", module),
+                    Err(e) => e.to_string(),
+                });
+                self.from_code = None;
+                self.checked = fs::metadata(dir.join(machine.module_path())).and_then(|m| m.modified()).ok();
+            }
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+        });
+    }
+}
@@ -0,0 +1,380 @@
+//! Title: Iron Coder Project Module - Std Support
+//! Description: How much of the standard library a project's firmware has: none
+//!   (`no_std`), none but with a heap for the `alloc` collections, or all of it, for boards
+//!   that run Linux. Switching changes the crate attributes and panic handler in
+//!   `src/main.rs` and the dependencies in Cargo.toml. With a heap, an allocator module is
+//!   written to `src/allocator.rs` before each build.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::board::Board;
+use crate::project::explain;
+use crate::project::heap_usage;
+
+/// The name of the module written to the project's `src` directory.
+pub const MODULE_FILE_NAME: &str = "allocator.rs";
+/// The crates that handle panics without std, the first of which is added when there's none.
+pub(crate) const PANIC_HANDLERS: [(&str, &str); 5] = [("panic-halt", "0.2"), ("panic-probe", "0.3"), ("panic-persist", "0.3"), ("panic-reset", "0.1"), ("esp-backtrace", "0.13")];
+const ALLOCATOR_CRATE: (&str, &str) = ("embedded-alloc", "0.6");
+/// The smallest heap the allocator can be given, in bytes.
+const MIN_HEAP_BYTES: u32 = 64;
+
+/// How much of the standard library the firmware has.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum StdSupport {
+    /// Only `core`, with no heap
+    #[default]
+    NoStd,
+    /// `core` and `alloc`, with a heap from embedded-alloc
+    NoStdAlloc,
+    /// All of std, for boards that run Linux
+    Std,
+}
+
+impl fmt::Display for StdSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoStd => write!(f, "no_std"),
+            Self::NoStdAlloc => write!(f, "no_std + alloc"),
+            Self::Std => write!(f, "std"),
+        }
+    }
+}
+
+impl StdSupport {
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::NoStd => "bare metal, with no heap",
+            Self::NoStdAlloc => "bare metal, with a heap for Vec, String and Box",
+            Self::Std => "the whole standard library, for boards that run Linux",
+        }
+    }
+}
+
+/// The heaps embedded-alloc has.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum Allocator {
+    /// A linked list of free blocks, which is small
+    #[default]
+    Llff,
+    /// Two-level segregated fit, which takes the same time for every allocation
+    Tlsf,
+}
+
+impl fmt::Display for Allocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Llff => write!(f, "linked list"),
+            Self::Tlsf => write!(f, "TLSF"),
+        }
+    }
+}
+
+impl Allocator {
+
+    /// The type of the heap in embedded-alloc.
+    pub fn heap_type(&self) -> &'static str {
+        match self {
+            Self::Llff => "LlffHeap",
+            Self::Tlsf => "TlsfHeap",
+        }
+    }
+}
+
+/// The standard library settings of a project.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StdSettings {
+    pub support: StdSupport,
+    pub allocator: Allocator,
+    /// The size of the heap, in bytes
+    pub heap_bytes: u32,
+}
+
+impl Default for StdSettings {
+    fn default() -> Self {
+        Self {
+            support: StdSupport::default(),
+            allocator: Allocator::default(),
+            heap_bytes: 16 * 1024,
+        }
+    }
+}
+
+/// What applying the settings to a project changed, and what's left to do by hand.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Applied {
+    /// The files that changed, relative to the project
+    pub changed: Vec<PathBuf>,
+    pub notes: Vec<String>,
+}
+
+/// Change a file of the project, noting it if it changed. Returns whether the file was
+/// there; only a `required` one has to be.
+pub(crate) fn update_file(
+    project_dir: &Path,
+    file: PathBuf,
+    required: bool,
+    applied: &mut Applied,
+    change: impl FnOnce(&str, &mut Vec<String>) -> Result<String, String>,
+) -> Result<bool, String> {
+    let path = project_dir.join(&file);
+    let existing = match fs::read_to_string(&path) {
+        Ok(existing) => existing,
+        Err(_) if !required => return Ok(false),
+        Err(e) => return Err(format!("couldn't read {}: {}", file.display(), e)),
+    };
+    let updated = change(&existing, &mut applied.notes)?;
+    if updated != existing {
+        fs::write(&path, updated).map_err(|e| format!("couldn't write {}: {}", file.display(), e))?;
+        applied.changed.push(file);
+    }
+    Ok(true)
+}
+
+/// Write a generated module to the project's `src` directory, unless it's there already.
+/// Returns whether it was written.
+pub(crate) fn write_module(project_dir: &Path, file_name: &str, code: &str) -> Result<bool, String> {
+    let path = project_dir.join("src").join(file_name);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == code) {
+        return Ok(false);
+    }
+    fs::create_dir_all(project_dir.join("src")).map_err(|e| e.to_string())?;
+    fs::write(&path, code).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Take a generated module out of the project's `src` directory, noting it if it was there.
+pub(crate) fn remove_module(project_dir: &Path, file_name: &str, applied: &mut Applied) -> Result<(), String> {
+    let module = PathBuf::from("src").join(file_name);
+    if project_dir.join(&module).exists() {
+        fs::remove_file(project_dir.join(&module)).map_err(|e| format!("couldn't remove {}: {}", module.display(), e))?;
+        applied.changed.push(module);
+    }
+    Ok(())
+}
+
+/// Whether a line of a source file being rewritten is a blank one after another, as
+/// where a line was taken out, so it can be left out.
+pub(crate) fn is_extra_blank(lines: &[String], line: &str) -> bool {
+    line.trim().is_empty() && lines.last().is_some_and(|l| l.trim().is_empty())
+}
+
+/// Whether a line is the `use` that links in a panic handler, i.e. `use panic_halt as _;`.
+pub(crate) fn is_panic_handler_use(line: &str) -> bool {
+    PANIC_HANDLERS.iter().any(|(name, _)| line.trim() == format!("use {} as _;", name.replace('-', "_")))
+}
+
+/// Take a dependency out of a manifest, if it's there.
+pub(crate) fn remove_dependency(manifest: &str, name: &str) -> Result<String, String> {
+    let mut doc: toml_edit::DocumentMut = manifest.parse().map_err(|e: toml_edit::TomlError| e.to_string())?;
+    if let Some(dependencies) = doc.get_mut("dependencies").and_then(toml_edit::Item::as_table_like_mut) {
+        dependencies.remove(name);
+    }
+    Ok(doc.to_string())
+}
+
+impl StdSettings {
+
+    /// What's wrong with the settings for the main board, if anything.
+    pub fn warnings(&self, main_board: Option<&Board>) -> Vec<String> {
+        let mut warnings = vec![];
+        match self.support {
+            StdSupport::Std => {
+                if let Some(hal) = main_board.and_then(Board::hal_crate) {
+                    warnings.push(format!("{} is for bare metal; std needs a board that runs Linux", hal));
+                }
+            },
+            StdSupport::NoStdAlloc => {
+                if self.heap_bytes < MIN_HEAP_BYTES {
+                    warnings.push(format!("the heap needs at least {} bytes", MIN_HEAP_BYTES));
+                }
+                warnings.extend(heap_usage::ram_warning(self.heap_bytes, main_board.and_then(Board::ram)));
+            },
+            StdSupport::NoStd => (),
+        }
+        warnings
+    }
+
+    /// The allocator module. While heap usage is watched, its module is the global
+    /// allocator, so this one hands over to it.
+    pub fn generate_module(&self, heap_usage_enabled: bool) -> Result<String, String> {
+        if heap_usage_enabled {
+            return Ok(format!(
+"//! The global allocator, generated by Iron Coder. Don't edit this file: it's written
+//! again before each build.
+//!
+//! The heap usage module is the allocator while the heap is watched, so its heap is
+//! the one that's used.
+
+/// Give the allocator its memory. Call it once, first thing in main.
+pub fn init() {{
+    crate::{}::init()
+}}
+", heap_usage::MODULE_FILE_NAME.trim_end_matches(".rs")));
+        }
+        if self.heap_bytes < MIN_HEAP_BYTES {
+            return Err(format!("the heap needs at least {} bytes", MIN_HEAP_BYTES));
+        }
+        Ok(format!(
+"//! The global allocator, generated by Iron Coder. Don't edit this file: it's written
+//! again before each build.
+
+use core::mem::MaybeUninit;
+
+use embedded_alloc::{heap_type} as Heap;
+
+/// The size of the heap, in bytes.
+pub const HEAP_SIZE: usize = {heap_bytes};
+
+#[global_allocator]
+static HEAP: Heap = Heap::empty();
+
+/// Give the allocator its memory. Call it once, first thing in main, before anything is
+/// allocated.
+pub fn init() {{
+    static mut MEMORY: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
+    // SAFETY: init is called once, so nothing else uses MEMORY
+    unsafe {{ HEAP.init(core::ptr::addr_of_mut!(MEMORY) as usize, HEAP_SIZE) }}
+}}
+", heap_type = self.allocator.heap_type(), heap_bytes = self.heap_bytes))
+    }
+
+    /// Regenerate the allocator module if the settings changed, and make sure the project
+    /// depends on embedded-alloc. Returns whether it was written. Nothing is written
+    /// without a heap.
+    pub fn regenerate(&self, heap_usage_enabled: bool, project_dir: &Path) -> Result<bool, String> {
+        if self.support != StdSupport::NoStdAlloc {
+            return Ok(false);
+        }
+        let code = self.generate_module(heap_usage_enabled)?;
+        if !write_module(project_dir, MODULE_FILE_NAME, &code)? {
+            return Ok(false);
+        }
+        let manifest_path = project_dir.join("Cargo.toml");
+        if let Ok(manifest) = fs::read_to_string(&manifest_path) {
+            let updated = explain::add_dependency(&manifest, ALLOCATOR_CRATE.0, ALLOCATOR_CRATE.1, &[])?;
+            if updated != manifest {
+                fs::write(&manifest_path, updated).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// The main source file with the attributes, panic handler and allocator set up for
+    /// the settings, and notes on what couldn't be changed.
+    pub fn main_rs(&self, main_rs: &str) -> (String, Vec<String>) {
+        let mut notes = vec![];
+        let managed = ["#![no_std]", "#![no_main]", "extern crate alloc;", "mod allocator;"];
+        let lines: Vec<&str> = main_rs.lines()
+            .filter(|l| !managed.contains(&l.trim()))
+            .filter(|l| self.support != StdSupport::Std || !is_panic_handler_use(l))
+            .collect();
+        let doc_end = lines.iter().position(|l| !l.starts_with("//!")).unwrap_or(lines.len());
+        let (doc, rest) = lines.split_at(doc_end);
+        let rest: Vec<&str> = rest.iter().copied().skip_while(|l| l.trim().is_empty()).collect();
+
+        let mut header: Vec<&str> = match self.support {
+            StdSupport::NoStd => vec!["#![no_std]", "#![no_main]"],
+            StdSupport::NoStdAlloc => vec!["#![no_std]", "#![no_main]", "", "extern crate alloc;", "", "mod allocator;"],
+            StdSupport::Std => vec![],
+        };
+        if self.support != StdSupport::Std && !rest.iter().any(|l| is_panic_handler_use(l)) {
+            header.extend(["", "use panic_halt as _;"]);
+        }
+        let mut out: Vec<String> = doc.iter().map(|l| l.to_string()).collect();
+        if !doc.is_empty() && !header.is_empty() {
+            out.push(String::new());
+        }
+        out.extend(header.iter().map(|l| l.to_string()));
+        if !out.is_empty() && !rest.is_empty() {
+            out.push(String::new());
+        }
+        for line in rest.iter() {
+            if is_extra_blank(&out, line) {
+                continue;
+            }
+            out.push(line.to_string());
+            if self.support == StdSupport::NoStdAlloc && line.contains("fn main(") && line.trim_end().ends_with('{')
+                && !main_rs.contains("allocator::init()") {
+                out.push("    allocator::init();".to_string());
+            }
+        }
+        if self.support == StdSupport::NoStdAlloc && !out.iter().any(|l| l.contains("allocator::init()")) {
+            notes.push("call allocator::init() first thing in main".to_string());
+        }
+        if self.support == StdSupport::Std && rest.iter().any(|l| l.trim().starts_with("#[") && l.contains("entry]")) {
+            notes.push("main still has the #[entry] of a bare metal HAL, which std programs don't use".to_string());
+        }
+        (out.join("\n") + "\n", notes)
+    }
+
+    /// The manifest with the panic handler and allocator crates the settings need, and
+    /// without the ones they don't.
+    pub fn manifest(&self, manifest: &str) -> Result<String, String> {
+        let mut updated = manifest.to_string();
+        if self.support == StdSupport::Std {
+            for (name, _) in PANIC_HANDLERS {
+                updated = remove_dependency(&updated, name)?;
+            }
+        } else {
+            let doc: toml::Table = manifest.parse().map_err(|e: toml::de::Error| e.to_string())?;
+            let dependencies = doc.get("dependencies").and_then(toml::Value::as_table);
+            if !PANIC_HANDLERS.iter().any(|(name, _)| dependencies.is_some_and(|d| d.contains_key(*name))) {
+                updated = explain::add_dependency(&updated, PANIC_HANDLERS[0].0, PANIC_HANDLERS[0].1, &[])?;
+            }
+        }
+        if self.support == StdSupport::NoStdAlloc {
+            explain::add_dependency(&updated, ALLOCATOR_CRATE.0, ALLOCATOR_CRATE.1, &[])
+        } else {
+            remove_dependency(&updated, ALLOCATOR_CRATE.0)
+        }
+    }
+
+    /// Change the project's `src/main.rs` and Cargo.toml for the settings. Without a heap,
+    /// the allocator module is taken out.
+    pub fn apply(&self, project_dir: &Path) -> Result<Applied, String> {
+        let mut applied = Applied::default();
+        update_file(project_dir, PathBuf::from("src").join("main.rs"), true, &mut applied, |main, notes| {
+            let (main, more) = self.main_rs(main);
+            notes.extend(more);
+            Ok(main)
+        })?;
+        update_file(project_dir, PathBuf::from("Cargo.toml"), true, &mut applied, |manifest, _| self.manifest(manifest))?;
+        if self.support != StdSupport::NoStdAlloc {
+            remove_module(project_dir, MODULE_FILE_NAME, &mut applied)?;
+        }
+        Ok(applied)
+    }
+
+    /// Show the settings to be edited. Returns whether they changed.
+    pub fn display(&mut self, ui: &mut egui::Ui, main_board: Option<&Board>) -> bool {
+        let before = self.clone();
+        for support in enum_iterator::all::<StdSupport>() {
+            ui.radio_value(&mut self.support, support, support.to_string()).on_hover_text(support.describe());
+        }
+        if self.support == StdSupport::NoStdAlloc {
+            ui.horizontal(|ui| {
+                ui.label("heap:");
+                egui::ComboBox::from_id_source("std_support_allocator").selected_text(self.allocator.to_string()).show_ui(ui, |ui| {
+                    for allocator in enum_iterator::all::<Allocator>() {
+                        ui.selectable_value(&mut self.allocator, allocator, allocator.to_string());
+                    }
+                });
+                ui.add(egui::DragValue::new(&mut self.heap_bytes).speed(256).clamp_range(MIN_HEAP_BYTES..=u32::MAX).suffix(" bytes"));
+            });
+        }
+        for warning in self.warnings(main_board) {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+        *self != before
+    }
+}
@@ -0,0 +1,753 @@
+//! Title: Iron Coder Project Module - Embedded Storage
+//! Description: Looking from the host at what the firmware wrote to its storage, and
+//!   starting the filesystem in the firmware. FAT volumes, as embedded-sdmmc writes them,
+//!   are read in place from a card image or from the card in a USB reader, without ever
+//!   writing to it; littlefs images are unpacked with mklittlefs. The SD card init code
+//!   uses the SPI pins the project's connections wire the card to.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+
+use enum_iterator::Sequence;
+use serde::{Serialize, Deserialize};
+
+use crate::app::jobs::{self, JobId, JobState};
+use crate::app::preview::{self, FileKind};
+use crate::app::toolbox::format_si;
+use crate::board::Board;
+use crate::project::compat;
+use crate::project::pins::{self, PinAssignment};
+use crate::project::starter::{self, StarterHal};
+use crate::project::system::System;
+
+const SECTOR_SIZE: usize = 512;
+/// The MBR partition types of FAT volumes.
+const FAT_PARTITION_TYPES: [u8; 6] = [0x01, 0x04, 0x06, 0x0b, 0x0c, 0x0e];
+/// The most of a file that's read for its preview.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+/// The lines of a binary file's hex dump in its preview.
+const PREVIEW_HEX_LINES: usize = 32;
+
+/// The filesystems there's tooling for, by the crate the firmware uses.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Sequence)]
+pub enum Filesystem {
+    /// FAT16 and FAT32 on an SD card, with embedded-sdmmc
+    #[default]
+    Fat,
+    /// littlefs in flash, with littlefs2
+    Littlefs,
+}
+
+impl fmt::Display for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filesystem::Fat => write!(f, "FAT (embedded-sdmmc)"),
+            Filesystem::Littlefs => write!(f, "littlefs (littlefs2)"),
+        }
+    }
+}
+
+impl Filesystem {
+
+    pub fn crate_name(&self) -> &'static str {
+        match self {
+            Filesystem::Fat => "embedded-sdmmc",
+            Filesystem::Littlefs => "littlefs2",
+        }
+    }
+}
+
+/// The filesystems the project's Cargo.toml has the crate of.
+pub fn project_filesystems(project_dir: &Path) -> Vec<Filesystem> {
+    enum_iterator::all::<Filesystem>().filter(|fs| compat::has_dependency(project_dir, fs.crate_name())).collect()
+}
+
+/// Where the FAT volume starts on a card, from its first sector. A card formatted without
+/// a partition table starts with the volume's boot sector; otherwise the first FAT
+/// partition of the MBR is used, like embedded-sdmmc's `VolumeIdx(0)`.
+pub fn fat_volume_start(sector: &[u8]) -> Result<u64, String> {
+    if sector.len() < SECTOR_SIZE || sector[510..512] != [0x55, 0xaa] {
+        return Err("there's no partition table or FAT boot sector at the start".to_string());
+    }
+    // FAT16 and FAT32 boot sectors name their type at different places
+    if &sector[0x36..0x39] == b"FAT" || &sector[0x52..0x57] == b"FAT32" {
+        return Ok(0);
+    }
+    for entry in sector[0x1be..0x1fe].chunks(16) {
+        if FAT_PARTITION_TYPES.contains(&entry[4]) {
+            let lba = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]);
+            return Ok(lba as u64 * SECTOR_SIZE as u64);
+        }
+    }
+    Err("the partition table has no FAT partition".to_string())
+}
+
+/// A FAT volume on a disk, which can only be read. fatfs wants to be able to write, and
+/// clears the dirty flag a card that wasn't unmounted has; those writes fail instead, so
+/// that looking at a card never changes it.
+struct ReadOnlyVolume<T> {
+    disk: T,
+    start: u64,
+}
+
+impl<T: Read> Read for ReadOnlyVolume<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.disk.read(buf)
+    }
+}
+
+impl<T> Write for ReadOnlyVolume<T> {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, "the card is opened read-only"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Seek> Seek for ReadOnlyVolume<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            other => other,
+        };
+        let at = self.disk.seek(pos)?;
+        at.checked_sub(self.start).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start of the volume"))
+    }
+}
+
+type FatVolume = fatfs::FileSystem<ReadOnlyVolume<File>>;
+
+/// Mount the FAT volume of a card image or card.
+fn mount_fat(image: &Path) -> Result<FatVolume, String> {
+    let mut disk = File::open(image).map_err(|e| format!("couldn't open {}: {}", image.display(), e))?;
+    let mut sector = [0u8; SECTOR_SIZE];
+    disk.read_exact(&mut sector).map_err(|e| format!("couldn't read {}: {}", image.display(), e))?;
+    let start = fat_volume_start(&sector)?;
+    disk.seek(SeekFrom::Start(start)).map_err(|e| format!("couldn't read {}: {}", image.display(), e))?;
+    let volume = ReadOnlyVolume { disk, start };
+    fatfs::FileSystem::new(volume, fatfs::FsOptions::new()).map_err(|e| format!("the volume isn't FAT: {}", e))
+}
+
+/// A file or directory the firmware wrote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsEntry {
+    /// From the root, i.e. `LOGS/DAY1.CSV`
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+    /// i.e. `2024-05-01 12:30:00`, if the filesystem keeps it
+    pub modified: Option<String>,
+}
+
+/// The contents of a filesystem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Listing {
+    /// i.e. `FAT32 volume "LOGGER", 3.2 GB free of 3.9 GB`
+    pub description: String,
+    pub entries: Vec<FsEntry>,
+}
+
+/// Add the entries of a FAT directory and the ones below it, directories first.
+fn walk_fat<T: fatfs::ReadWriteSeek>(dir: &fatfs::Dir<T>, prefix: &str, entries: &mut Vec<FsEntry>) -> io::Result<()> {
+    let mut found = vec![];
+    for entry in dir.iter() {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == "." || name == ".." {
+            continue;
+        }
+        found.push(entry);
+    }
+    found.sort_by_key(|e| (!e.is_dir(), e.file_name().to_lowercase()));
+    for entry in found {
+        let path = format!("{}{}", prefix, entry.file_name());
+        let m = entry.modified();
+        entries.push(FsEntry {
+            path: path.clone(),
+            size: if entry.is_dir() { 0 } else { entry.len() },
+            is_dir: entry.is_dir(),
+            modified: Some(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", m.date.year, m.date.month, m.date.day, m.time.hour, m.time.min, m.time.sec)),
+        });
+        if entry.is_dir() {
+            walk_fat(&entry.to_dir(), &format!("{}/", path), entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Everything on the FAT volume of a card image or card.
+pub fn list_fat(image: &Path) -> Result<Listing, String> {
+    let volume = mount_fat(image)?;
+    let mut entries = vec![];
+    walk_fat(&volume.root_dir(), "", &mut entries).map_err(|e| format!("couldn't read the volume: {}", e))?;
+    let fat_type = match volume.fat_type() {
+        fatfs::FatType::Fat12 => "FAT12",
+        fatfs::FatType::Fat16 => "FAT16",
+        fatfs::FatType::Fat32 => "FAT32",
+    };
+    let mut description = format!("{} volume \"{}\"", fat_type, volume.volume_label().trim());
+    if let Ok(stats) = volume.stats() {
+        let cluster = stats.cluster_size() as f64;
+        description += &format!(", {} free of {}",
+            format_si(stats.free_clusters() as f64 * cluster, "B"),
+            format_si(stats.total_clusters() as f64 * cluster, "B"));
+    }
+    Ok(Listing { description, entries })
+}
+
+/// Up to `limit` bytes of the start of a file on the FAT volume of a card image or card.
+pub fn read_fat_file(image: &Path, path: &str, limit: u64) -> Result<Vec<u8>, String> {
+    let volume = mount_fat(image)?;
+    let file = volume.root_dir().open_file(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+    let mut bytes = vec![];
+    file.take(limit).read_to_end(&mut bytes).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    Ok(bytes)
+}
+
+/// How a littlefs image is laid out, which mklittlefs has to be told.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct LittlefsGeometry {
+    /// The flash's erase block, in bytes
+    pub block_size: u32,
+    /// The flash's program page, in bytes
+    pub page_size: u32,
+    /// How many blocks the filesystem has
+    pub blocks: u32,
+}
+
+impl Default for LittlefsGeometry {
+    fn default() -> Self {
+        Self { block_size: 4096, page_size: 256, blocks: 256 }
+    }
+}
+
+impl LittlefsGeometry {
+
+    pub fn size(&self) -> u64 {
+        self.block_size as u64 * self.blocks as u64
+    }
+}
+
+/// The command that unpacks a littlefs image into a directory.
+pub fn unpack_littlefs(image: &Path, geometry: &LittlefsGeometry, out_dir: &Path) -> duct::Expression {
+    duct::cmd!(
+        "mklittlefs", "-u", out_dir,
+        "-b", geometry.block_size.to_string(),
+        "-p", geometry.page_size.to_string(),
+        "-s", geometry.size().to_string(),
+        image,
+    )
+}
+
+/// Add the entries of an unpacked directory and the ones below it, directories first.
+fn walk_dir(dir: &Path, prefix: &str, entries: &mut Vec<FsEntry>) -> io::Result<()> {
+    let mut found: Vec<fs::DirEntry> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+    found.sort_by_key(|e| (!e.path().is_dir(), e.file_name().to_string_lossy().to_lowercase()));
+    for entry in found {
+        let path = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let is_dir = entry.path().is_dir();
+        // littlefs keeps no times, so the unpacked files' are when they were unpacked
+        entries.push(FsEntry { path: path.clone(), size: if is_dir { 0 } else { entry.metadata()?.len() }, is_dir, modified: None });
+        if is_dir {
+            walk_dir(&entry.path(), &format!("{}/", path), entries)?;
+        }
+    }
+    Ok(())
+}
+
+/// Everything in a littlefs image unpacked into `dir`.
+pub fn list_unpacked(dir: &Path, geometry: &LittlefsGeometry) -> Result<Listing, String> {
+    let mut entries = vec![];
+    walk_dir(dir, "", &mut entries).map_err(|e| format!("couldn't read the unpacked image: {}", e))?;
+    let used: u64 = entries.iter().map(|e| e.size).sum();
+    let description = format!("littlefs image, {} in files of {}", format_si(used as f64, "B"), format_si(geometry.size() as f64, "B"));
+    Ok(Listing { description, entries })
+}
+
+/// Removable disks, i.e. an SD card in a USB reader. Only Linux lists them; elsewhere the
+/// device is typed in, i.e. `/dev/rdisk4` or `\\.\PhysicalDrive2`.
+pub fn removable_disks() -> Vec<PathBuf> {
+    let Ok(devices) = fs::read_dir("/sys/block") else { return vec![] };
+    let mut disks: Vec<PathBuf> = devices.flatten()
+        .filter(|d| fs::read_to_string(d.path().join("removable")).is_ok_and(|r| r.trim() == "1"))
+        // a reader with no card in it has no size
+        .filter(|d| fs::read_to_string(d.path().join("size")).is_ok_and(|s| s.trim() != "0"))
+        .map(|d| Path::new("/dev").join(d.file_name()))
+        .collect();
+    disks.sort();
+    disks
+}
+
+/// The SPI lines of an SD card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SdLine {
+    Sck,
+    Mosi,
+    Miso,
+    Cs,
+}
+
+impl fmt::Display for SdLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SdLine::Sck => write!(f, "SCK"),
+            SdLine::Mosi => write!(f, "MOSI"),
+            SdLine::Miso => write!(f, "MISO"),
+            SdLine::Cs => write!(f, "CS"),
+        }
+    }
+}
+
+impl SdLine {
+
+    /// The line a name is for, from the controller's side (`SPI0 TX`) or the card's
+    /// (`DI`), including the SD bus pins a card reuses in SPI mode (`CMD`, `DAT0`, `DAT3`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        name.split(|c: char| !c.is_ascii_alphanumeric()).find_map(|word| match word.to_ascii_uppercase().as_str() {
+            "SCK" | "SCLK" | "CLK" => Some(SdLine::Sck),
+            "MOSI" | "COPI" | "TX" | "DI" | "CMD" => Some(SdLine::Mosi),
+            "MISO" | "CIPO" | "RX" | "DO" | "DAT0" => Some(SdLine::Miso),
+            "CS" | "SS" | "CSN" | "NSS" | "DAT3" => Some(SdLine::Cs),
+            _ => None,
+        })
+    }
+}
+
+/// The main board's pins the SD card is wired to, as (port, number).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SdPins {
+    pub sck: (u8, u8),
+    pub mosi: (u8, u8),
+    pub miso: (u8, u8),
+    pub cs: (u8, u8),
+    /// The SPI peripheral the pins are on, i.e. `SPI0`, if the board says
+    pub spi: Option<String>,
+}
+
+/// Find the SD card's pins in the project's connections. A connection's line is told from
+/// the chip peripheral of its pin, then the card's pin, then the connection's name.
+pub fn sd_pins(assignments: &[PinAssignment]) -> Result<SdPins, String> {
+    let mut found: [Option<(u8, u8)>; 4] = [None; 4];
+    let mut spi = None;
+    for a in assignments {
+        let Some(line) = [&a.peripheral, &a.to_pin, &a.connection].iter().find_map(|n| SdLine::from_name(n)) else { continue };
+        // the chip select is a plain GPIO, the other lines are SPI
+        if a.interface != "SPI" && line != SdLine::Cs {
+            continue;
+        }
+        let number = starter::function_number(&a.function)
+            .ok_or_else(|| format!("can't tell which GPIO {} of the {} is", a.pin, a.board))?;
+        found[line as usize] = Some(number);
+        if spi.is_none() {
+            spi = a.peripheral.split_whitespace().find(|w| w.starts_with("SPI")).map(str::to_string);
+        }
+    }
+    let missing: Vec<String> = [SdLine::Sck, SdLine::Mosi, SdLine::Miso, SdLine::Cs].iter()
+        .filter(|&&line| found[line as usize].is_none())
+        .map(|line| line.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("the card's {} isn't connected to the main board", missing.join(", ")));
+    }
+    let pin = |line: SdLine| found[line as usize].unwrap();
+    Ok(SdPins { sck: pin(SdLine::Sck), mosi: pin(SdLine::Mosi), miso: pin(SdLine::Miso), cs: pin(SdLine::Cs), spi })
+}
+
+/// The stand-in clock embedded-sdmmc stamps files with, and the volume opened on the card.
+const SDMMC_COMMON: &str = "
+// embedded-sdmmc stamps files with the time from this; a real one would read the RTC
+struct Clock;
+impl embedded_sdmmc::TimeSource for Clock {
+    fn get_timestamp(&self) -> embedded_sdmmc::Timestamp {
+        embedded_sdmmc::Timestamp { year_since_1970: 54, zero_indexed_month: 0, zero_indexed_day: 0, hours: 0, minutes: 0, seconds: 0 }
+    }
+}
+
+let sdcard = embedded_sdmmc::SdCard::new(spi, delay);
+let mut volume_mgr = embedded_sdmmc::VolumeManager::new(sdcard, Clock);
+// the first FAT partition, which is what the storage browser reads too
+let mut volume = volume_mgr.open_volume(embedded_sdmmc::VolumeIdx(0)).unwrap();
+let mut root = volume.open_root_dir().unwrap();
+";
+
+/// The code that starts embedded-sdmmc on the card's SPI pins, for the main board's HAL.
+/// `delay` is any embedded-hal delay, i.e. the HAL's timer.
+pub fn sdmmc_init_code(hal: StarterHal, pins: &SdPins) -> String {
+    let name = |(port, n): (u8, u8)| match hal {
+        StarterHal::NrfHal => format!("P{}.{:02}", port, n),
+        _ => format!("GPIO{}", n),
+    };
+    let mut code = format!("// the SD card: SCK {}, MOSI {}, MISO {}, CS {}\n",
+        name(pins.sck), name(pins.mosi), name(pins.miso), name(pins.cs));
+    code += &match hal {
+        StarterHal::Rp2040Hal => {
+            // the RP2040's SPI blocks take turns along the GPIOs, 8 at a time
+            let spi = pins.spi.clone().unwrap_or_else(|| format!("SPI{}", (pins.sck.1 / 8) % 2));
+            format!(
+"let pins = hal::gpio::Pins::new(pac.IO_BANK0, pac.PADS_BANK0, sio.gpio_bank0, &mut pac.RESETS);
+let sck = pins.gpio{sck}.into_function::<hal::gpio::FunctionSpi>();
+let mosi = pins.gpio{mosi}.into_function::<hal::gpio::FunctionSpi>();
+let miso = pins.gpio{miso}.into_function::<hal::gpio::FunctionSpi>();
+let cs = pins.gpio{cs}.into_push_pull_output_in_state(hal::gpio::PinState::High);
+// cards start out at 400 kHz at most
+let spi = hal::spi::Spi::<_, _, _, 8>::new(pac.{spi}, (mosi, miso, sck)).init(
+    &mut pac.RESETS,
+    clocks.peripheral_clock.freq(),
+    400.kHz(),
+    embedded_hal::spi::MODE_0,
+);
+let spi = embedded_hal_bus::spi::ExclusiveDevice::new(spi, cs, delay.clone()).unwrap();
+", sck = pins.sck.1, mosi = pins.mosi.1, miso = pins.miso.1, cs = pins.cs.1, spi = spi)
+        },
+        StarterHal::NrfHal => {
+            let mut ports: Vec<u8> = [pins.sck, pins.mosi, pins.miso, pins.cs].iter().map(|p| p.0).collect();
+            ports.sort();
+            ports.dedup();
+            let parts: String = ports.iter().map(|p| format!("let port{p} = hal::gpio::p{p}::Parts::new(p.P{p});\n", p = p)).collect();
+            let pin = |(port, n): (u8, u8)| format!("port{}.p{}_{:02}", port, port, n);
+            format!(
+"{parts}let sck = {sck}.into_push_pull_output(Level::Low).degrade();
+let mosi = {mosi}.into_push_pull_output(Level::Low).degrade();
+let miso = {miso}.into_floating_input().degrade();
+let cs = {cs}.into_push_pull_output(Level::High);
+// cards start out at 400 kHz at most
+let spi = hal::spim::Spim::new(
+    p.SPIM0,
+    hal::spim::Pins {{ sck: Some(sck), mosi: Some(mosi), miso: Some(miso) }},
+    hal::spim::Frequency::K250,
+    hal::spim::MODE_0,
+    0xff,
+);
+let spi = embedded_hal_bus::spi::ExclusiveDevice::new(spi, cs, delay.clone()).unwrap();
+", parts = parts, sck = pin(pins.sck), mosi = pin(pins.mosi), miso = pin(pins.miso), cs = pin(pins.cs))
+        },
+        StarterHal::EspHal => format!(
+"let io = Io::new(peripherals.GPIO, peripherals.IO_MUX);
+let cs = Output::new(io.pins.gpio{cs}, Level::High);
+// cards start out at 400 kHz at most
+let spi = Spi::new(peripherals.SPI2, 400.kHz(), SpiMode::Mode0)
+    .with_sck(io.pins.gpio{sck})
+    .with_mosi(io.pins.gpio{mosi})
+    .with_miso(io.pins.gpio{miso});
+let spi = embedded_hal_bus::spi::ExclusiveDevice::new(spi, cs, delay.clone()).unwrap();
+", sck = pins.sck.1, mosi = pins.mosi.1, miso = pins.miso.1, cs = pins.cs.1),
+    };
+    code + SDMMC_COMMON
+}
+
+/// The code that mounts littlefs2 on the flash from `start`, formatting it the first
+/// time. The flash is driven through embedded-storage's NorFlash, which esp-storage has
+/// for ESP chips. Other boards' drivers are left to the user, behind a compile_error!.
+pub fn littlefs_init_code(hal: Option<StarterHal>, geometry: &LittlefsGeometry, start: u32) -> String {
+    let driver = match hal {
+        Some(StarterHal::EspHal) => "esp_storage::FlashStorage::new()",
+        _ => "compile_error!(\"put the board's flash driver here, anything that implements embedded_storage::nor_flash::NorFlash\")",
+    };
+    format!(
+"// littlefs in {size} of flash from {start:#x}: {blocks} blocks of {block} bytes
+struct Flash<F> {{
+    flash: F,
+    start: u32,
+}}
+impl<F: embedded_storage::nor_flash::NorFlash> littlefs2::driver::Storage for Flash<F> {{
+    const READ_SIZE: usize = {page};
+    const WRITE_SIZE: usize = {page};
+    const BLOCK_SIZE: usize = {block};
+    const BLOCK_COUNT: usize = {blocks};
+    const BLOCK_CYCLES: isize = 500;
+    type CACHE_SIZE = littlefs2::consts::U{page};
+    type LOOKAHEAD_SIZE = littlefs2::consts::U8;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> littlefs2::io::Result<usize> {{
+        self.flash.read(self.start + off as u32, buf).map_err(|_| littlefs2::io::Error::Io)?;
+        Ok(buf.len())
+    }}
+    fn write(&mut self, off: usize, data: &[u8]) -> littlefs2::io::Result<usize> {{
+        self.flash.write(self.start + off as u32, data).map_err(|_| littlefs2::io::Error::Io)?;
+        Ok(data.len())
+    }}
+    fn erase(&mut self, off: usize, len: usize) -> littlefs2::io::Result<usize> {{
+        let from = self.start + off as u32;
+        self.flash.erase(from, from + len as u32).map_err(|_| littlefs2::io::Error::Io)?;
+        Ok(len)
+    }}
+}}
+
+let mut flash = Flash {{ flash: {driver}, start: {start:#x} }};
+let mut alloc = littlefs2::fs::Filesystem::allocate();
+// blank flash has no filesystem on it yet
+if !littlefs2::fs::Filesystem::is_mountable(&mut flash) {{
+    littlefs2::fs::Filesystem::format(&mut flash).unwrap();
+}}
+let fs = littlefs2::fs::Filesystem::mount(&mut alloc, &mut flash).unwrap();
+", size = format_si(geometry.size() as f64, "B"), start = start, blocks = geometry.blocks, block = geometry.block_size, page = geometry.page_size, driver = driver)
+}
+
+/// What a file's preview shows: its text, or a hex dump of its start.
+pub fn preview_text(path: &str, bytes: &[u8]) -> String {
+    match preview::detect(Path::new(path), bytes) {
+        FileKind::Text => String::from_utf8_lossy(bytes).to_string(),
+        _ => (0..bytes.len().min(PREVIEW_HEX_LINES * preview::HEX_LINE_BYTES))
+            .step_by(preview::HEX_LINE_BYTES)
+            .map(|offset| preview::hex_line(bytes, offset) + "\n")
+            .collect(),
+    }
+}
+
+fn code_block(ui: &mut egui::Ui, mut code: String) {
+    if ui.button("copy").clicked() {
+        ui.output_mut(|o| o.copied_text = code.clone());
+    }
+    ui.add(egui::TextEdit::multiline(&mut code).code_editor().desired_width(f32::INFINITY));
+}
+
+/// The state of the storage window.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct StoragePanel {
+    filesystem: Filesystem,
+    /// The card image, littlefs image or card device that's read
+    image: String,
+    littlefs: LittlefsGeometry,
+    /// Where littlefs starts in the flash, for the init code
+    littlefs_start: u32,
+    #[serde(skip)]
+    disks: Vec<PathBuf>,
+    #[serde(skip)]
+    listing: Option<Result<Listing, String>>,
+    /// Where the littlefs image was unpacked to
+    #[serde(skip)]
+    unpacked: Option<PathBuf>,
+    #[serde(skip)]
+    job: Option<JobId>,
+    #[serde(skip)]
+    lines: Option<Receiver<String>>,
+    #[serde(skip)]
+    output: Vec<String>,
+    #[serde(skip)]
+    selected: Option<String>,
+    #[serde(skip)]
+    preview: Option<Result<String, String>>,
+}
+
+impl StoragePanel {
+
+    /// Read what's on the image, straight away for FAT and with mklittlefs for littlefs.
+    fn read(&mut self, ctx: &egui::Context) {
+        self.selected = None;
+        self.preview = None;
+        let image = PathBuf::from(self.image.trim());
+        match self.filesystem {
+            Filesystem::Fat => self.listing = Some(list_fat(&image)),
+            Filesystem::Littlefs => {
+                let out_dir = std::env::temp_dir().join(format!("iron-coder-littlefs-{}", std::process::id()));
+                let _ = fs::remove_dir_all(&out_dir);
+                let cmd = unpack_littlefs(&image, &self.littlefs, &out_dir);
+                let (tx, rx) = channel();
+                self.job = Some(jobs::shared(ctx).spawn("unpack the littlefs image", Some(ctx), move |job| {
+                    jobs::run_commands(&[cmd], &tx, job)
+                }));
+                self.lines = Some(rx);
+                self.output.clear();
+                self.unpacked = Some(out_dir);
+                self.listing = None;
+            },
+        }
+    }
+
+    /// Take in what mklittlefs printed, and list the unpacked image once it's done.
+    fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(rx) = &self.lines {
+            self.output.extend(rx.try_iter());
+        }
+        let Some(job) = self.job else { return };
+        match jobs::shared(ctx).state(job) {
+            Some(JobState::Running) => return,
+            Some(JobState::Finished) => {
+                let dir = self.unpacked.clone().unwrap_or_default();
+                self.listing = Some(list_unpacked(&dir, &self.littlefs));
+            },
+            Some(JobState::Failed(e)) => {
+                self.listing = Some(Err(format!("couldn't unpack the image: {} {}", e, self.output.join(" "))));
+            },
+            Some(JobState::Cancelled) | None => (),
+        }
+        self.job = None;
+        self.lines = None;
+    }
+
+    /// The start of a file of the last listing.
+    fn read_file(&self, path: &str, limit: u64) -> Result<Vec<u8>, String> {
+        match self.filesystem {
+            Filesystem::Fat => read_fat_file(Path::new(self.image.trim()), path, limit),
+            Filesystem::Littlefs => {
+                let file = self.unpacked.clone().unwrap_or_default().join(path);
+                let file = File::open(&file).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+                let mut bytes = vec![];
+                file.take(limit).read_to_end(&mut bytes).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+                Ok(bytes)
+            },
+        }
+    }
+
+    fn display_source(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("image:");
+            ui.add(egui::TextEdit::singleline(&mut self.image).hint_text("card.img or /dev/sdb").desired_width(220.0));
+            if ui.button("open...").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.image = path.display().to_string();
+                }
+            }
+        });
+        if self.filesystem == Filesystem::Fat {
+            ui.horizontal(|ui| {
+                if ui.button("find cards").on_hover_text("list the removable disks, i.e. a card in a USB reader").clicked() {
+                    self.disks = removable_disks();
+                }
+                for disk in self.disks.clone() {
+                    if ui.selectable_label(self.image == disk.display().to_string(), disk.display().to_string()).clicked() {
+                        self.image = disk.display().to_string();
+                    }
+                }
+            });
+        }
+        let reading = self.job.is_some();
+        ui.horizontal(|ui| {
+            let button = ui.add_enabled(!reading && !self.image.trim().is_empty(), egui::Button::new("read"))
+                .on_hover_text("list what's on it, without writing to it");
+            if button.clicked() {
+                self.read(ctx);
+            }
+            if reading {
+                ui.spinner();
+            }
+        });
+    }
+
+    fn display_listing(&mut self, ui: &mut egui::Ui) {
+        let listing = match &self.listing {
+            None => return,
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            },
+            Some(Ok(listing)) => listing.clone(),
+        };
+        ui.label(&listing.description);
+        if listing.entries.is_empty() {
+            ui.weak("it's empty");
+        }
+        egui::ScrollArea::vertical().max_height(240.0).id_source("storage_listing").show(ui, |ui| {
+            egui::Grid::new("storage_listing_grid").num_columns(3).striped(true).show(ui, |ui| {
+                for entry in listing.entries.iter() {
+                    let depth = entry.path.matches('/').count();
+                    let name = entry.path.rsplit('/').next().unwrap_or_default();
+                    let label = format!("{}{}{}", "    ".repeat(depth), name, if entry.is_dir { "/" } else { "" });
+                    if entry.is_dir {
+                        ui.label(label);
+                        ui.label("");
+                    } else {
+                        if ui.selectable_label(self.selected.as_ref() == Some(&entry.path), label).clicked() {
+                            self.preview = Some(self.read_file(&entry.path, PREVIEW_BYTES).map(|bytes| preview_text(&entry.path, &bytes)));
+                            self.selected = Some(entry.path.clone());
+                        }
+                        ui.label(format_si(entry.size as f64, "B"));
+                    }
+                    ui.weak(entry.modified.as_deref().unwrap_or_default());
+                    ui.end_row();
+                }
+            });
+        });
+        let Some(selected) = self.selected.clone() else { return };
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.strong(&selected);
+            if ui.button("save...").on_hover_text("copy the file to the computer").clicked() {
+                let name = selected.rsplit('/').next().unwrap_or_default().to_string();
+                if let Some(path) = rfd::FileDialog::new().set_file_name(name).save_file() {
+                    let saved = self.read_file(&selected, u64::MAX)
+                        .and_then(|bytes| fs::write(&path, bytes).map_err(|e| format!("couldn't write {}: {}", path.display(), e)));
+                    if let Err(e) = saved {
+                        self.preview = Some(Err(e));
+                    }
+                }
+            }
+        });
+        match &self.preview {
+            Some(Ok(text)) => {
+                egui::ScrollArea::vertical().max_height(200.0).id_source("storage_preview").show(ui, |ui| {
+                    ui.monospace(text);
+                });
+            },
+            Some(Err(e)) => { ui.colored_label(ui.visuals().error_fg_color, e); },
+            None => (),
+        }
+    }
+
+    fn display_init_code(&mut self, ui: &mut egui::Ui, system: &System) {
+        let hal = system.main_board.as_ref().and_then(Board::hal_crate).and_then(StarterHal::for_crate);
+        match self.filesystem {
+            Filesystem::Fat => {
+                let Some(hal) = hal else {
+                    ui.label("there's init code for rp2040-hal, the nRF HALs and esp-hal main boards");
+                    return;
+                };
+                match sd_pins(&pins::assignments(system)) {
+                    Ok(pins) => code_block(ui, sdmmc_init_code(hal, &pins)),
+                    Err(e) => {
+                        ui.label(e);
+                        ui.weak("embedded-sdmmc talks to cards over SPI, so wire SCK, MOSI and MISO as SPI and CS as a GPIO");
+                    },
+                }
+            },
+            Filesystem::Littlefs => {
+                egui::Grid::new("littlefs_geometry").num_columns(2).show(ui, |ui| {
+                    ui.label("starts at:");
+                    ui.add(egui::DragValue::new(&mut self.littlefs_start).hexadecimal(8, false, true).speed(self.littlefs.block_size));
+                    ui.end_row();
+                    ui.label("block size:");
+                    ui.add(egui::DragValue::new(&mut self.littlefs.block_size).clamp_range(128..=65536).suffix(" B"));
+                    ui.end_row();
+                    ui.label("page size:");
+                    ui.add(egui::DragValue::new(&mut self.littlefs.page_size).clamp_range(1..=self.littlefs.block_size).suffix(" B"));
+                    ui.end_row();
+                    ui.label("blocks:");
+                    ui.add(egui::DragValue::new(&mut self.littlefs.blocks).clamp_range(2..=65536));
+                    ui.end_row();
+                });
+                code_block(ui, littlefs_init_code(hal, &self.littlefs, self.littlefs_start));
+            },
+        }
+    }
+
+    /// Show the window.
+    pub fn display(&mut self, ctx: &egui::Context, ui: &mut egui::Ui, system: &System, project_dir: Option<&Path>) {
+        self.poll(ctx);
+        let used = project_dir.map(project_filesystems).unwrap_or_default();
+        ui.horizontal(|ui| {
+            ui.label("filesystem:");
+            egui::ComboBox::from_id_source("storage_filesystem").selected_text(self.filesystem.to_string()).show_ui(ui, |ui| {
+                for filesystem in enum_iterator::all::<Filesystem>() {
+                    ui.selectable_value(&mut self.filesystem, filesystem, filesystem.to_string());
+                }
+            });
+        });
+        if project_dir.is_some() && !used.contains(&self.filesystem) {
+            ui.weak(format!("the project doesn't use {} yet", self.filesystem.crate_name()));
+        }
+        ui.separator();
+        self.display_source(ctx, ui);
+        self.display_listing(ui);
+        ui.separator();
+        egui::CollapsingHeader::new("init code").show(ui, |ui| {
+            self.display_init_code(ui, system);
+        });
+    }
+}
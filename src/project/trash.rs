@@ -0,0 +1,254 @@
+//! Title: Iron Coder Project Module - Trash
+//! Description: Files deleted from the project tree go to a trash folder in the project
+//!   rather than being removed for good, so a delete can be undone. The trash keeps a
+//!   manifest of where each thing was and whether git tracked it, and clears out what's
+//!   older, or more, than the project keeps.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use git2::Repository;
+use log::{info, warn};
+use serde::{Serialize, Deserialize};
+
+use crate::project::file_tree::{add_declaration, declaring_file, existing_module, outermost, remove_declaration};
+
+/// The trash folder, in the project directory. It ignores itself, so it's never committed.
+pub const TRASH_DIR: &str = ".trash";
+const MANIFEST_FILE_NAME: &str = "trash.toml";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How long deleted files are kept in the trash, i.e.
+/// ```toml
+/// [trash]
+/// keep_days = 30
+/// keep_items = 100
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Retention {
+    pub keep_days: u64,
+    /// The most files and folders kept, the oldest going first
+    pub keep_items: usize,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self { keep_days: 30, keep_items: 100 }
+    }
+}
+
+/// A file or folder in the trash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrashItem {
+    /// The folder it's kept in, in the trash
+    pub id: u64,
+    /// Where it was, relative to the project directory
+    pub path: PathBuf,
+    /// When it was deleted, in seconds since the Unix epoch
+    pub deleted_at: u64,
+    /// The delete it was part of, so what was deleted together is put back together
+    pub batch: u64,
+    /// Whether git tracked it, in which case it shows as deleted in git until it's put back
+    pub tracked: bool,
+    /// The visibility of the `mod` declaration it had, if it was a module
+    pub declared: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Manifest {
+    items: Vec<TrashItem>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Whether git tracks a file, or anything in a folder, in the repo the project is in.
+pub fn is_tracked(path: &Path) -> bool {
+    let Ok(repo) = Repository::discover(path.parent().unwrap_or(path)) else {
+        return false;
+    };
+    let (Some(workdir), Ok(index)) = (repo.workdir(), repo.index()) else {
+        return false;
+    };
+    let workdir = workdir.canonicalize().unwrap_or(workdir.to_path_buf());
+    let Ok(relative) = path.strip_prefix(&workdir) else {
+        return false;
+    };
+    index.iter().any(|entry| Path::new(&*String::from_utf8_lossy(&entry.path)).starts_with(relative))
+}
+
+/// The trash of a project.
+pub struct Trash {
+    root: PathBuf,
+    items: Vec<TrashItem>,
+}
+
+impl Trash {
+
+    /// The trash of the project in `root`, which is empty if nothing's been deleted yet.
+    pub fn open(root: &Path) -> Self {
+        let root = root.canonicalize().unwrap_or(root.to_path_buf());
+        let manifest = root.join(TRASH_DIR).join(MANIFEST_FILE_NAME);
+        let items = match fs::read_to_string(&manifest) {
+            Ok(contents) => match toml::from_str::<Manifest>(&contents) {
+                Ok(manifest) => manifest.items,
+                Err(e) => {
+                    warn!("couldn't read the trash manifest {}: {:?}", manifest.display(), e);
+                    vec![]
+                },
+            },
+            Err(_) => vec![],
+        };
+        Self { root, items }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(TRASH_DIR)
+    }
+
+    /// What's in the trash, the most recently deleted last.
+    pub fn items(&self) -> &[TrashItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let manifest = Manifest { items: self.items.clone() };
+        let contents = toml::to_string(&manifest).map_err(io::Error::other)?;
+        fs::write(self.dir().join(MANIFEST_FILE_NAME), contents)
+    }
+
+    fn next_id(&self) -> u64 {
+        let mut id = self.items.iter().map(|i| i.id + 1).max().unwrap_or(1);
+        while self.dir().join(id.to_string()).exists() {
+            id += 1;
+        }
+        id
+    }
+
+    fn item_path(&self, item: &TrashItem) -> PathBuf {
+        self.dir().join(item.id.to_string()).join(item.path.file_name().unwrap_or_default())
+    }
+
+    /// Move files and folders of the project to the trash, as one delete. Their `mod`
+    /// declarations are taken out, and put back with them. Returns where they were.
+    pub fn put(&mut self, paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let ignore = dir.join(".gitignore");
+        if !ignore.exists() {
+            fs::write(&ignore, "*\n")?;
+        }
+        let (deleted_at, batch) = (now(), self.next_id());
+        let mut deleted = vec![];
+        for path in outermost(paths) {
+            let Ok(relative) = path.strip_prefix(&self.root).map(Path::to_path_buf) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} isn't in the project", path.display())));
+            };
+            if path.starts_with(&dir) || relative.as_os_str().is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't put {} in the trash", path.display())));
+            }
+            let (module, tracked) = (existing_module(&path), is_tracked(&path));
+            let id = self.next_id();
+            let mut item = TrashItem { id, path: relative, deleted_at, batch, tracked, declared: None };
+            fs::create_dir(dir.join(id.to_string()))?;
+            fs::rename(&path, self.item_path(&item))?;
+            if let (Some(module), Some(parent)) = (module, path.parent().and_then(declaring_file)) {
+                item.declared = remove_declaration(&parent, &module)?;
+            }
+            self.items.push(item);
+            self.save()?;
+            deleted.push(path);
+        }
+        info!("put {} files in the trash", deleted.len());
+        Ok(deleted)
+    }
+
+    /// Put something in the trash back where it was. Returns where that is.
+    pub fn restore(&mut self, id: u64) -> io::Result<PathBuf> {
+        let Some(position) = self.items.iter().position(|i| i.id == id) else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("nothing in the trash is numbered {}", id)));
+        };
+        let item = self.items[position].clone();
+        let to = self.root.join(&item.path);
+        if to.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("there's already a {}", to.display())));
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.item_path(&item), &to)?;
+        let _ = fs::remove_dir(self.dir().join(id.to_string()));
+        if let (Some(visibility), Some(module), Some(parent)) = (&item.declared, existing_module(&to), to.parent().and_then(declaring_file)) {
+            add_declaration(&parent, &module, visibility)?;
+        }
+        self.items.remove(position);
+        self.save()?;
+        Ok(to)
+    }
+
+    /// Put back everything from the last delete. Returns where it went.
+    pub fn undo(&mut self) -> io::Result<Vec<PathBuf>> {
+        let Some(batch) = self.items.iter().map(|i| i.batch).max() else {
+            return Ok(vec![]);
+        };
+        let ids: Vec<u64> = self.items.iter().filter(|i| i.batch == batch).map(|i| i.id).collect();
+        ids.into_iter().map(|id| self.restore(id)).collect()
+    }
+
+    fn remove(&mut self, keep: impl Fn(usize, &TrashItem) -> bool) -> io::Result<usize> {
+        let total = self.items.len();
+        let mut removed = vec![];
+        let mut i = 0;
+        self.items.retain(|item| {
+            let kept = keep(i, item);
+            if !kept {
+                removed.push(item.id);
+            }
+            i += 1;
+            kept
+        });
+        for id in removed.iter() {
+            fs::remove_dir_all(self.dir().join(id.to_string()))?;
+        }
+        if !removed.is_empty() {
+            self.save()?;
+            info!("cleared {} of {} files out of the trash", removed.len(), total);
+        }
+        Ok(removed.len())
+    }
+
+    /// Delete for good what's been in the trash longer, or is more, than `retention`
+    /// keeps. Returns how many were deleted.
+    pub fn purge(&mut self, retention: &Retention, now: u64) -> io::Result<usize> {
+        let oldest = now.saturating_sub(retention.keep_days * SECONDS_PER_DAY);
+        let first_kept = self.items.len().saturating_sub(retention.keep_items);
+        self.remove(|i, item| i >= first_kept && item.deleted_at >= oldest)
+    }
+
+    /// Delete everything in the trash for good.
+    pub fn empty(&mut self) -> io::Result<usize> {
+        self.remove(|_, _| false)
+    }
+
+    /// Purge the trash with the current time.
+    pub fn purge_now(&mut self, retention: &Retention) -> io::Result<usize> {
+        self.purge(retention, now())
+    }
+}
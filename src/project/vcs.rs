@@ -0,0 +1,178 @@
+//! Title: Iron Coder Project Module - Version Control
+//! Description: Keeping a project's repository tidy, i.e. a `.gitignore` that leaves out
+//!   build output, keys and editor leftovers, a first commit for new projects, and warnings
+//!   about files that shouldn't be committed.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+
+use super::environment;
+use super::trash;
+
+pub const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+/// The message of a new project's first commit.
+pub const INITIAL_COMMIT_MESSAGE: &str = "Start the project";
+
+/// What a new project's `.gitignore` leaves out, under a heading for each kind of file.
+pub const IGNORED: [(&str, &[&str]); 3] = [
+    ("build output", &["/target/"]),
+    ("keys and secrets", &[".env", ".env.*", "*.pem", "*.key", environment::SECRETS_FILE_NAME]),
+    ("editor and recovery files", &["*.rs.bk", "*.orig", "*.rej", "*.swp", "*~", "/.trash/"]),
+];
+
+/// Files bigger than this aren't looked through for secrets.
+const MAX_SCANNED_BYTES: u64 = 1 << 20;
+
+/// Whether to make a repository for a new project, and commit what it starts with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RepoSetup {
+    pub init: bool,
+    pub initial_commit: bool,
+}
+
+impl Default for RepoSetup {
+    fn default() -> Self {
+        Self { init: true, initial_commit: true }
+    }
+}
+
+/// A pattern without the slashes that don't change what it matches at the top of a
+/// project, so `target/` and `/target/` are the same.
+fn normalized(pattern: &str) -> &str {
+    pattern.trim().trim_start_matches('/').trim_end_matches('/')
+}
+
+/// Add what's missing from the project's `.gitignore`, making it if there isn't one. A
+/// template may bring its own, which is kept. Returns the patterns that were added.
+pub fn ensure_gitignore(project_dir: &Path) -> io::Result<Vec<&'static str>> {
+    let path = project_dir.join(GITIGNORE_FILE_NAME);
+    let existing = match fs::read_to_string(&path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let present: Vec<&str> = existing.lines().map(normalized).collect();
+    let mut added = vec![];
+    let mut contents = existing.clone();
+    for (heading, patterns) in IGNORED {
+        let missing: Vec<&str> = patterns.iter().copied().filter(|p| !present.contains(&normalized(p))).collect();
+        if missing.is_empty() {
+            continue;
+        }
+        if !contents.is_empty() {
+            if !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push('\n');
+        }
+        contents += &format!("# {}\n", heading);
+        for pattern in missing.iter() {
+            contents += &format!("{}\n", pattern);
+        }
+        added.extend(missing);
+    }
+    if contents != existing {
+        fs::write(&path, contents)?;
+    }
+    Ok(added)
+}
+
+/// Commit everything that isn't ignored, if the repository doesn't have a commit yet.
+/// Files that are build output or may hold one of the secrets are checked for first and
+/// left out. The commit is signed with the user's git identity, if they have one. Returns
+/// the new commit with what was left out, or `None` if there already was one.
+pub fn initial_commit(project_dir: &Path, secrets: &[String]) -> Result<Option<(Oid, Vec<CommitWarning>)>, git2::Error> {
+    let repo = Repository::open(project_dir)?;
+    if repo.head().is_ok_and(|head| head.target().is_some()) {
+        return Ok(None);
+    }
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    let paths: Vec<String> = index.iter().filter_map(|entry| String::from_utf8(entry.path).ok()).collect();
+    let warnings = check_commit(project_dir, &paths, secrets);
+    for warning in warnings.iter() {
+        index.remove_path(Path::new(&warning.path))?;
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = match repo.signature() {
+        Ok(signature) => signature,
+        Err(_) => Signature::now("Iron Coder", "iron-coder@localhost")?,
+    };
+    let oid = repo.commit(Some("HEAD"), &signature, &signature, INITIAL_COMMIT_MESSAGE, &tree, &[])?;
+    Ok(Some((oid, warnings)))
+}
+
+/// Why a file shouldn't be committed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommitRisk {
+    /// Made by a build, so it's out of date as soon as the code changes
+    BuildArtifact,
+    /// A key, or a file with one of the project's secret values in it
+    Secret,
+}
+
+/// A file about to be committed that probably shouldn't be.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitWarning {
+    pub path: String,
+    pub risk: CommitRisk,
+}
+
+impl fmt::Display for CommitWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.risk {
+            CommitRisk::BuildArtifact => write!(f, "{} is build output", self.path),
+            CommitRisk::Secret => write!(f, "{} may hold a secret", self.path),
+        }
+    }
+}
+
+fn is_build_artifact(path: &Path) -> bool {
+    let first = path.components().next().and_then(|c| c.as_os_str().to_str());
+    if matches!(first, Some("target") | Some(trash::TRASH_DIR)) {
+        return true;
+    }
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    matches!(extension, "elf" | "bin" | "hex" | "uf2" | "o" | "pdb")
+}
+
+fn is_secret_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    name == ".env" || name.starts_with(".env.") || name == environment::SECRETS_FILE_NAME || name.starts_with("id_rsa") || name.starts_with("id_ed25519")
+        || matches!(extension, "pem" | "key" | "p12" | "pfx")
+}
+
+/// Whether a file has any of the secret values in it, i.e. the project file once a
+/// secret environment variable has been set.
+fn has_secret(path: &Path, secrets: &[String]) -> bool {
+    if secrets.is_empty() || fs::metadata(path).map_or(true, |m| !m.is_file() || m.len() > MAX_SCANNED_BYTES) {
+        return false;
+    }
+    let Ok(contents) = fs::read(path) else {
+        return false;
+    };
+    let contents = String::from_utf8_lossy(&contents);
+    secrets.iter().filter(|s| !s.is_empty()).any(|s| contents.contains(s.as_str()))
+}
+
+/// The files, relative to the repository, that are build output or may hold a secret.
+pub fn check_commit(repo_dir: &Path, paths: &[String], secrets: &[String]) -> Vec<CommitWarning> {
+    paths.iter().filter_map(|p| {
+        let path = Path::new(p);
+        let risk = if is_build_artifact(path) {
+            CommitRisk::BuildArtifact
+        } else if is_secret_file(path) || has_secret(&repo_dir.join(path), secrets) {
+            CommitRisk::Secret
+        } else {
+            return None;
+        };
+        Some(CommitWarning { path: p.clone(), risk })
+    }).collect()
+}
@@ -0,0 +1,151 @@
+//! Title: Iron Coder Project Module - Workspace
+//! Description: Settings shared by the projects in one repository, i.e. boards of the
+//!   team's own, the toolchain, environment variables, lints and tasks. They're kept in a
+//!   `.ironcoder-workspace.toml` above the projects, which each project picks up when it's
+//!   opened, so firmware crates in a monorepo don't each repeat them. A project's own
+//!   settings win over the shared ones.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+use crate::board::{self, Board};
+use super::environment::{EnvVar, ProjectEnvironment};
+use super::lints::LintSettings;
+
+pub const WORKSPACE_FILE_NAME: &str = ".ironcoder-workspace.toml";
+
+/// A command the projects in the workspace can run, i.e. a formatter or a script that
+/// checks the firmware's size.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Task {
+    pub name: String,
+    /// The program and its arguments
+    pub command: Vec<String>,
+}
+
+impl Task {
+
+    /// The command, run in the project's directory.
+    pub fn command(&self, project_dir: &Path) -> Result<duct::Expression, String> {
+        let Some((program, args)) = self.command.split_first() else {
+            return Err(format!("task {} has no command", self.name));
+        };
+        Ok(duct::cmd(program, args).dir(project_dir))
+    }
+}
+
+/// What's in the workspace file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct WorkspaceSettings {
+    /// Directories of board files, relative to the workspace file, that are added to the
+    /// known boards
+    pub boards: Vec<PathBuf>,
+    /// The toolchain reproducible builds are pinned to, if a project doesn't pick one
+    pub toolchain: Option<String>,
+    /// Set before the project's own variables, which replace ones with the same key
+    pub environment: ProjectEnvironment,
+    /// The lints the projects start from, which they can set differently
+    pub lints: LintSettings,
+    pub tasks: Vec<Task>,
+}
+
+impl WorkspaceSettings {
+
+    /// Check the settings can be used by the projects.
+    pub fn validate(&self) -> Result<(), String> {
+        self.environment.validate()?;
+        self.lints.validate()?;
+        for (i, task) in self.tasks.iter().enumerate() {
+            if task.name.trim().is_empty() {
+                return Err("every task needs a name".to_string());
+            }
+            if task.command.is_empty() {
+                return Err(format!("task {} has no command", task.name));
+            }
+            if self.tasks[..i].iter().any(|t| t.name == task.name) {
+                return Err(format!("there are two tasks called {}", task.name));
+            }
+        }
+        if self.toolchain.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err("the toolchain is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A workspace file that was found, and the directory it's in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub settings: WorkspaceSettings,
+}
+
+impl fmt::Display for Workspace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root.join(WORKSPACE_FILE_NAME).display())
+    }
+}
+
+/// Look for a workspace file in the project's directory and the ones above it, taking the
+/// nearest. `None` if there isn't one.
+pub fn find(project_dir: &Path) -> Option<Result<Workspace, String>> {
+    let root = project_dir.ancestors().find(|dir| dir.join(WORKSPACE_FILE_NAME).is_file())?;
+    Some(load(root))
+}
+
+/// Load the workspace file in a directory.
+pub fn load(root: &Path) -> Result<Workspace, String> {
+    let path = root.join(WORKSPACE_FILE_NAME);
+    let contents = fs::read_to_string(&path).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    let settings: WorkspaceSettings = toml::from_str(&contents).map_err(|e| format!("{} isn't valid: {}", path.display(), e))?;
+    settings.validate().map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Workspace { root: root.to_path_buf(), settings })
+}
+
+impl Workspace {
+
+    /// The workspace's board directories, laid out like the shipped one, by manufacturer.
+    pub fn board_dirs(&self) -> Vec<PathBuf> {
+        self.settings.boards.iter().map(|dir| self.root.join(dir)).collect()
+    }
+
+    /// The boards in the workspace's board directories. Boards that don't load are left
+    /// out, with a warning.
+    pub fn boards(&self) -> Vec<Board> {
+        self.board_dirs().iter().flat_map(|dir| {
+            board::get_boards(dir).unwrap_or_else(|report| {
+                warn!("some of the workspace boards in {} didn't load: {}", dir.display(), report);
+                report.boards
+            })
+        }).collect()
+    }
+
+    /// The variables a project runs with: the workspace's, then the project's own, which
+    /// replace the ones with the same key.
+    pub fn environment(&self, project: &ProjectEnvironment) -> ProjectEnvironment {
+        let mut vars: Vec<EnvVar> = self.settings.environment.vars.iter()
+            .filter(|v| !project.vars.iter().any(|p| p.key == v.key))
+            .cloned()
+            .collect();
+        vars.extend(project.vars.iter().cloned());
+        ProjectEnvironment { vars }
+    }
+
+    /// The toolchain to pin, which is the project's own if it has one.
+    pub fn toolchain<'a>(&'a self, project: &'a str) -> &'a str {
+        match &self.settings.toolchain {
+            Some(toolchain) if project.trim().is_empty() => toolchain.trim(),
+            _ => project,
+        }
+    }
+
+    pub fn task(&self, name: &str) -> Option<&Task> {
+        self.settings.tasks.iter().find(|t| t.name == name)
+    }
+}
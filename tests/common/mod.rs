@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use iron_coder::board::{self, Board};
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+pub fn fixture_boards() -> Vec<Board> {
+    board::get_boards(&fixtures_dir().join("boards")).unwrap_or_else(|report| panic!("{}", report))
+}
+
+/// A fresh scratch directory for a single test, removed on drop.
+pub struct ScratchDir(pub PathBuf);
+
+impl ScratchDir {
+    #[allow(dead_code)]
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("iron-coder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,139 @@
+//! Headless integration tests for the app core. These drive the board database, the
+//! project model and the build pipeline through their non-GUI APIs, using the fixture
+//! boards and projects in tests/fixtures, so they can run in CI without a display.
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use iron_coder::app::events::{self, Event};
+use iron_coder::app::jobs;
+use iron_coder::board::config::ConfigValue;
+use iron_coder::project::Project;
+
+mod common;
+use common::{fixture_boards, fixtures_dir, ScratchDir};
+
+fn fixture_project() -> Project {
+    let mut project = Project::default();
+    project.known_boards = fixture_boards();
+    project.load_from(&fixtures_dir().join("projects").join("blinky")).expect("couldn't load fixture project");
+    project
+}
+
+#[test]
+fn fixture_boards_load() {
+    let boards = fixture_boards();
+    let mut names: Vec<&str> = boards.iter().map(|b| b.get_name()).collect();
+    names.sort();
+    assert_eq!(names, ["Test Main Board", "Test Peripheral"]);
+    for b in boards.iter() {
+        assert!(b.svg_board_info.is_some(), "{} is missing its svg info", b.get_name());
+    }
+    let main = boards.iter().find(|b| b.is_main_board()).unwrap();
+    assert_eq!(main.required_crates().unwrap(), ["cortex-m", "cortex-m-rt", "panic-halt"]);
+}
+
+#[test]
+fn fixture_project_loads_and_syncs_boards() {
+    let project = fixture_project();
+    assert!(project.has_main_board());
+    let boards = project.system.get_all_boards();
+    assert_eq!(boards.len(), 2);
+    // board resources come from the known boards, not the project file
+    assert!(boards.iter().all(|b| b.svg_board_info.is_some()));
+}
+
+#[test]
+fn project_round_trip() {
+    let scratch = ScratchDir::new("round-trip");
+    let mut project = fixture_project();
+    *project.borrow_name() = "round trip".to_string();
+    project.save_to(&scratch.0).unwrap();
+
+    let mut reloaded = Project::default();
+    reloaded.known_boards = fixture_boards();
+    reloaded.load_from(&scratch.0).unwrap();
+    assert_eq!(reloaded.borrow_name(), "round trip");
+    assert_eq!(reloaded.get_location(), scratch.0.display().to_string());
+    assert_eq!(reloaded.system.get_all_boards(), project.system.get_all_boards());
+}
+
+#[cfg(unix)]
+#[test]
+fn project_round_trip_non_utf8_path() {
+    use std::os::unix::ffi::OsStrExt;
+    let scratch = ScratchDir::new("non-utf8");
+    let project_dir = scratch.0.join(std::ffi::OsStr::from_bytes(b"caf\xe9 project"));
+    fs::create_dir(&project_dir).unwrap();
+    let mut project = fixture_project();
+    project.save_to(&project_dir).unwrap();
+
+    let mut reloaded = Project::default();
+    reloaded.known_boards = fixture_boards();
+    reloaded.load_from(&project_dir).unwrap();
+    assert_eq!(reloaded.system.get_all_boards(), project.system.get_all_boards());
+}
+
+#[test]
+fn generated_system_module_parses() {
+    let scratch = ScratchDir::new("codegen");
+    let mut project = fixture_project();
+    let module = scratch.0.join("system.rs");
+    assert!(project.system.generate_system_module(&module).is_ok());
+    let code = fs::read_to_string(&module).unwrap();
+    let file: syn::File = syn::parse_str(&code).expect("generated module doesn't parse");
+    assert!(!file.items.is_empty());
+}
+
+#[test]
+fn build_reports_through_event_bus() {
+    // the fixture project has no Cargo manifest, so the build has to fail, and say so
+    let scratch = ScratchDir::new("build");
+    let mut project = fixture_project();
+    project.save_to(&scratch.0).unwrap();
+
+    let ctx = egui::Context::default();
+    let rx = events::shared(&ctx).subscribe();
+    project.build(&ctx);
+
+    let scheduler = jobs::shared(&ctx);
+    let start = Instant::now();
+    while scheduler.num_running() > 0 {
+        assert!(start.elapsed() < Duration::from_secs(60), "build job never finished");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let events: Vec<Event> = rx.try_iter().collect();
+    assert_eq!(events, [Event::BuildFinished { success: false }]);
+}
+
+#[test]
+fn project_config_generates_module_and_features() {
+    let scratch = ScratchDir::new("config");
+    let mut project = fixture_project();
+    let schema = project.config_schema();
+    let names: Vec<&str> = schema.iter().map(|e| e.option.name.as_str()).collect();
+    assert_eq!(names, ["BLINK_MS", "DEFMT", "DEFMT_LEVEL"]);
+    // DEFMT_LEVEL only applies once DEFMT is on
+    assert!(project.config.enabled_features(&schema).is_empty());
+    project.config.set(&schema[1].option, ConfigValue::Bool(true)).unwrap();
+    assert_eq!(project.config.enabled_features(&schema), ["defmt", "defmt-info"]);
+    assert!(project.config.set(&schema[0].option, ConfigValue::Int(5)).is_err());
+    project.config.set(&schema[0].option, ConfigValue::Int(250)).unwrap();
+
+    fs::write(scratch.0.join("Cargo.toml"), "[package]\nname = \"blinky\" # keep me\n\n[features]\ndefmt = [\"dep:defmt\"]\n").unwrap();
+    project.config.write(&schema, &scratch.0).unwrap();
+    let code = fs::read_to_string(scratch.0.join("src").join("config.rs")).unwrap();
+    syn::parse_file(&code).expect("generated config doesn't parse");
+    assert!(code.contains("pub const BLINK_MS: u32 = 250;"), "{}", code);
+    assert!(code.contains("pub const DEFMT_LEVEL: DefmtLevel = DefmtLevel::Info;"), "{}", code);
+    let manifest = fs::read_to_string(scratch.0.join("Cargo.toml")).unwrap();
+    assert!(manifest.contains("# keep me") && manifest.contains("defmt = [\"dep:defmt\"]"), "{}", manifest);
+    assert!(manifest.contains("defmt-trace = []"), "{}", manifest);
+
+    // the values are kept in the project file
+    project.save_to(&scratch.0).unwrap();
+    let mut reloaded = Project::default();
+    reloaded.known_boards = fixture_boards();
+    reloaded.load_from(&scratch.0).unwrap();
+    assert_eq!(reloaded.config, project.config);
+}
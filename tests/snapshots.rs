@@ -0,0 +1,178 @@
+//! Snapshot tests for the board widgets. Each widget is laid out in a headless egui
+//! Context, and the shapes it paints are written out as text and compared against the
+//! files in tests/snapshots. This catches layout regressions (truncated labels, broken
+//! image scaling) across every built-in colorscheme and a couple of display scales.
+//!
+//! Run with UPDATE_SNAPSHOTS=1 to accept the current output as the new snapshots.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::epaint::{ClippedShape, Shape};
+use egui::{Color32, Rect, Widget};
+
+use iron_coder::app::colorscheme::{self, ColorScheme};
+use iron_coder::app::setup_fonts_and_style;
+use iron_coder::board::display::{BoardComparisonWidget, BoardDisplayMode, BoardMiniWidget, BoardSelectorWidget, BoardWidget};
+use iron_coder::board::units::{self, NumberLocale};
+use iron_coder::board::Board;
+
+mod common;
+use common::fixture_boards;
+
+/// The display scales (native pixels per point) that every widget is rendered at.
+const SCALES: [f32; 2] = [1.0, 2.0];
+
+fn snapshots_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots")
+}
+
+/// A fixture board with its image decoded, as the app decodes them when it starts, so the
+/// widgets don't show a spinner in its place.
+fn fixture_board(name: &str) -> Board {
+    let board = fixture_boards().into_iter().find(|b| b.get_name() == name).unwrap();
+    if let Some(svg_board_info) = &board.svg_board_info {
+        assert!(svg_board_info.image.wait().is_some(), "{}'s image can't be decoded", name);
+    }
+    board
+}
+
+/// Lay out `add_contents` in a fresh Context and return the painted shapes. Two frames
+/// are run, since some containers only know their size after the first one.
+fn render(cs: &ColorScheme, scale: f32, mut add_contents: impl FnMut(&mut egui::Ui)) -> Vec<ClippedShape> {
+    // numbers are formatted for the user's locale, so pin it
+    assert_eq!(units::pin_locale(NumberLocale::default()), NumberLocale::default());
+    let ctx = egui::Context::default();
+    setup_fonts_and_style(&ctx);
+    colorscheme::set_colorscheme(&ctx, cs.clone());
+    let mut raw_input = egui::RawInput {
+        screen_rect: Some(Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(480.0, 360.0))),
+        ..Default::default()
+    };
+    raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(scale);
+    let mut shapes = vec![];
+    for _ in 0..2 {
+        shapes = ctx.run(raw_input.clone(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| add_contents(ui));
+        }).shapes;
+    }
+    shapes
+}
+
+fn fmt_rect(r: Rect) -> String {
+    format!("[{:.1} {:.1} {:.1} {:.1}]", r.min.x, r.min.y, r.max.x, r.max.y)
+}
+
+fn fmt_color(c: Color32) -> String {
+    let [r, g, b, a] = c.to_array();
+    format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+}
+
+fn describe_shape(out: &mut String, shape: &Shape) {
+    match shape {
+        Shape::Noop => (),
+        Shape::Vec(shapes) => shapes.iter().for_each(|s| describe_shape(out, s)),
+        Shape::Rect(r) if r.fill_texture_id != egui::TextureId::default() => {
+            writeln!(out, "image {}", fmt_rect(r.rect)).unwrap();
+        },
+        Shape::Rect(r) => {
+            writeln!(out, "rect {} fill={} stroke={:.1}{}", fmt_rect(r.rect), fmt_color(r.fill), r.stroke.width, fmt_color(r.stroke.color)).unwrap();
+        },
+        Shape::Text(t) => {
+            let color = t.galley.job.sections.first().map(|s| s.format.color).unwrap_or(t.fallback_color);
+            let elided = if t.galley.elided { " elided" } else { "" };
+            writeln!(out, "text {} {:?} color={}{}", fmt_rect(t.galley.rect.translate(t.pos.to_vec2())), t.galley.job.text, fmt_color(color), elided).unwrap();
+        },
+        Shape::Mesh(m) => {
+            // texture ids depend on upload order, so only note whether there is one
+            let kind = if m.texture_id == egui::TextureId::default() { "mesh" } else { "image" };
+            writeln!(out, "{} {}", kind, fmt_rect(m.calc_bounds())).unwrap();
+        },
+        other => {
+            writeln!(out, "shape {}", fmt_rect(other.visual_bounding_rect())).unwrap();
+        },
+    }
+}
+
+fn describe(shapes: &[ClippedShape]) -> String {
+    let mut out = String::new();
+    for ClippedShape { shape, .. } in shapes {
+        describe_shape(&mut out, shape);
+    }
+    out
+}
+
+/// Compare `actual` against the named snapshot, or (re)write it if UPDATE_SNAPSHOTS is set.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = snapshots_dir().join(format!("{}.snap", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(snapshots_dir()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!("missing snapshot {}; run with UPDATE_SNAPSHOTS=1 to create it", path.display())
+    });
+    if expected != actual {
+        let new_path = path.with_extension("snap.new");
+        fs::write(&new_path, actual).unwrap();
+        panic!("snapshot {} changed; the new output was written to {}", path.display(), new_path.display());
+    }
+}
+
+/// Render a widget in every colorscheme at every scale, and check each against its snapshot.
+fn check_widget(name: &str, add_contents: impl Fn(&mut egui::Ui)) {
+    for cs in colorscheme::SYSTEM_COLORSCHEMES.iter() {
+        for scale in SCALES {
+            let shapes = render(cs, scale, &add_contents);
+            let variant = format!("{}_{}_{}x", name, cs.name.to_lowercase().replace(' ', "_"), scale);
+            assert_snapshot(&variant, &describe(&shapes));
+        }
+    }
+}
+
+#[test]
+fn board_widget() {
+    let board = fixture_board("Test Main Board");
+    check_widget("board", |ui| { board.clone().ui(ui); });
+}
+
+#[test]
+fn board_card_widget() {
+    let main = fixture_board("Test Main Board");
+    let peripheral = fixture_board("Test Peripheral");
+    // the selected card is opened up to the details
+    check_widget("board_card", |ui| {
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Min).with_main_wrap(true), |ui| {
+            ui.add(BoardWidget::new(&main).mode(BoardDisplayMode::Compact));
+            ui.add(BoardWidget::new(&peripheral).mode(BoardDisplayMode::Compact).selected(true));
+        });
+    });
+}
+
+#[test]
+fn board_selector_widget() {
+    let main = fixture_board("Test Main Board");
+    let peripheral = fixture_board("Test Peripheral");
+    // laid out in columns, like the board selector window does
+    check_widget("board_selector", |ui| {
+        ui.columns(2, |columns| {
+            columns[0].add(BoardSelectorWidget(main.clone()));
+            columns[1].add(BoardSelectorWidget(peripheral.clone()));
+        });
+    });
+}
+
+#[test]
+fn board_mini_widget() {
+    let board = fixture_board("Test Peripheral");
+    check_widget("board_mini", |ui| { ui.add(BoardMiniWidget(board.clone())); });
+}
+
+#[test]
+fn board_comparison_widget() {
+    let main = fixture_board("Test Main Board");
+    let peripheral = fixture_board("Test Peripheral");
+    check_widget("board_comparison", |ui| { ui.add(BoardComparisonWidget(&[&main, &peripheral])); });
+}